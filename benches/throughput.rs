@@ -0,0 +1,23 @@
+//! The throughput of the consensus of 4, 21 and 100 validators with the mock crypto: the
+//! commits per second, the p99 round latency and the signature verifications per second in
+//! the wall time. Run by `cargo bench --features testing --bench throughput`.
+
+use mlm::testing::load::{self, LoadConfig};
+
+/// The validators and the heights measured of them.
+const SUITE: [(usize, u64); 3] = [(4, 50), (21, 20), (100, 5)];
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .unwrap();
+
+    for (validators, heights) in SUITE {
+        let report = runtime
+            .block_on(load::run(&LoadConfig::new(validators, heights)))
+            .expect("Commit the heights");
+        println!("throughput of {}", report);
+    }
+}
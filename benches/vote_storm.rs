@@ -0,0 +1,80 @@
+//! The allocations of the message handling in a vote storm of 100 validators, where the
+//! leader of each round verifies the votes of all of the others. Run by
+//! `cargo bench --features testing --bench vote_storm`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use mlm::testing::{Latency, SimConfig, Simulator};
+
+const VALIDATORS: usize = 100;
+const HEIGHTS: u64 = 5;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The system allocator counting the allocations and their bytes.
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let mut sim = Simulator::new(SimConfig {
+            nodes: VALIDATORS,
+            latency: Latency::Fixed(10),
+            ..SimConfig::default()
+        });
+        let nodes = (0..VALIDATORS).collect::<Vec<_>>();
+        sim.start();
+        // The first height warms up the caches and the channels of the nodes.
+        assert!(sim.run_until(&nodes, 1, Duration::from_secs(600)).await);
+
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+        let start = Instant::now();
+        assert!(
+            sim.run_until(&nodes, HEIGHTS + 1, Duration::from_secs(600))
+                .await
+        );
+        let elapsed = start.elapsed();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+        sim.stop();
+
+        println!(
+            "vote storm of {} validators, {} heights in {:?}: {} allocations, {} KiB per height",
+            VALIDATORS,
+            HEIGHTS,
+            elapsed,
+            allocations / HEIGHTS,
+            bytes / HEIGHTS / 1024
+        );
+    });
+}
@@ -7,7 +7,7 @@ use std::thread;
 use std::time::Duration;
 
 use async_trait::async_trait;
-use bytes::{Bytes, BytesMut};
+use bytes::Bytes;
 use creep::Context;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use hasher::{Hasher, HasherKeccak};
@@ -17,7 +17,9 @@ use rand::random;
 use serde::{Deserialize, Serialize};
 
 use mlm::error::ConsensusError;
-use mlm::types::{Commit, Hash, MlmMsg, Node, Status, ViewChangeReason};
+use mlm::types::{
+    Address, AggregatedSignature, Commit, Hash, MlmMsg, Node, Status, ViewChangeReason,
+};
 use mlm::{Codec, Consensus, Crypto, DurationConfig, Mlm, MlmHandler, Wal};
 
 lazy_static! {
@@ -26,7 +28,7 @@ lazy_static! {
 
 const SPEAKER_NUM: u8 = 10;
 
-const SPEECH_INTERVAL: u64 = 1000; // ms
+const SPEECH_INTERVAL: Duration = Duration::from_millis(1000);
 
 type Channel = (Sender<MlmMsg<Speech>>, Receiver<MlmMsg<Speech>>);
 
@@ -50,12 +52,14 @@ macro_rules! impl_codec_for {
     ($($struc: ident),+) => {
         $(
             impl Codec for $struc {
-                fn encode(&self) -> Result<Bytes, Box<dyn Error + Send>> {
-                    Ok(Bytes::from(bincode::serialize(&self.inner).unwrap()))
+                type Error = bincode::Error;
+
+                fn encode(&self) -> Result<Bytes, Self::Error> {
+                    Ok(Bytes::from(bincode::serialize(&self.inner)?))
                 }
 
-                fn decode(data: Bytes) -> Result<Self, Box<dyn Error + Send>> {
-                    let data: Option<Bytes> = bincode::deserialize(&data).unwrap();
+                fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+                    let data: Option<Bytes> = bincode::deserialize(data)?;
                     Ok($struc { inner: data.unwrap() })
                 }
             }
@@ -67,12 +71,14 @@ impl_codec_for!(Speech, Detail);
 
 struct MockWal {
     inner: Mutex<Option<Bytes>>,
+    last_signed: Mutex<Option<Bytes>>,
 }
 
 impl MockWal {
     fn new() -> MockWal {
         MockWal {
             inner: Mutex::new(None),
+            last_signed: Mutex::new(None),
         }
     }
 }
@@ -87,31 +93,42 @@ impl Wal for MockWal {
     async fn load(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
         Ok(self.inner.lock().unwrap().as_ref().cloned())
     }
+
+    async fn save_last_signed(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        *self.last_signed.lock().unwrap() = Some(info);
+        Ok(())
+    }
+
+    async fn load_last_signed(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        Ok(self.last_signed.lock().unwrap().as_ref().cloned())
+    }
 }
 
 struct MockCrypto {
-    name: Bytes,
+    name: Address,
 }
 
 impl MockCrypto {
-    fn new(name: Bytes) -> Self {
+    fn new(name: Address) -> Self {
         MockCrypto { name }
     }
 }
 
 impl Crypto for MockCrypto {
-    fn hash(&self, speech: Bytes) -> Bytes {
+    type QcSignature = AggregatedSignature;
+
+    fn hash(&self, speech: Bytes) -> Hash {
         hash(&speech)
     }
 
-    fn sign(&self, _hash: Bytes) -> Result<Bytes, Box<dyn Error + Send>> {
-        Ok(self.name.clone())
+    fn sign(&self, _hash: Hash) -> Result<Bytes, Box<dyn Error + Send>> {
+        Ok(self.name.clone().into())
     }
 
     fn aggregate_signatures(
         &self,
         _signatures: Vec<Bytes>,
-        _speaker: Vec<Bytes>,
+        _speaker: Vec<Address>,
     ) -> Result<Bytes, Box<dyn Error + Send>> {
         Ok(Bytes::new())
     }
@@ -119,8 +136,8 @@ impl Crypto for MockCrypto {
     fn verify_signature(
         &self,
         _signature: Bytes,
-        _hash: Bytes,
-        _voter: Bytes,
+        _hash: Hash,
+        _voter: Address,
     ) -> Result<(), Box<dyn Error + Send>> {
         Ok(())
     }
@@ -128,8 +145,8 @@ impl Crypto for MockCrypto {
     fn verify_aggregated_signature(
         &self,
         _aggregated_signature: Bytes,
-        _hash: Bytes,
-        _voters: Vec<Bytes>,
+        _hash: Hash,
+        _voters: Vec<Address>,
     ) -> Result<(), Box<dyn Error + Send>> {
         Ok(())
     }
@@ -137,7 +154,7 @@ impl Crypto for MockCrypto {
 
 struct Brain {
     speaker_list: Vec<Node>,
-    talk_to: HashMap<Bytes, Sender<MlmMsg<Speech>>>,
+    talk_to: HashMap<Address, Sender<MlmMsg<Speech>>>,
     hearing: Receiver<MlmMsg<Speech>>,
     consensus_speech: Arc<Mutex<HashMap<u64, Bytes>>>,
 }
@@ -145,7 +162,7 @@ struct Brain {
 impl Brain {
     fn new(
         speaker_list: Vec<Node>,
-        talk_to: HashMap<Bytes, Sender<MlmMsg<Speech>>>,
+        talk_to: HashMap<Address, Sender<MlmMsg<Speech>>>,
         hearing: Receiver<MlmMsg<Speech>>,
         consensus_speech: Arc<Mutex<HashMap<u64, Bytes>>>,
     ) -> Brain {
@@ -202,6 +219,9 @@ impl Consensus<Speech> for Brain {
             interval: Some(SPEECH_INTERVAL),
             timer_config: None,
             authority_list: self.speaker_list.clone(),
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: Vec::new(),
         })
     }
 
@@ -227,7 +247,7 @@ impl Consensus<Speech> for Brain {
     async fn transmit_to_relayer(
         &self,
         _ctx: Context,
-        name: Bytes,
+        name: Address,
         words: MlmMsg<Speech>,
     ) -> Result<(), Box<dyn Error + Send>> {
         self.talk_to.get(&name).unwrap().send(words).unwrap();
@@ -254,9 +274,9 @@ struct Speaker {
 
 impl Speaker {
     fn new(
-        name: Bytes,
+        name: Address,
         speaker_list: Vec<Node>,
-        talk_to: HashMap<Bytes, Sender<MlmMsg<Speech>>>,
+        talk_to: HashMap<Address, Sender<MlmMsg<Speech>>>,
         hearing: Receiver<MlmMsg<Speech>>,
         consensus_speech: Arc<Mutex<HashMap<u64, Bytes>>>,
     ) -> Self {
@@ -283,6 +303,9 @@ impl Speaker {
                     interval: Some(SPEECH_INTERVAL),
                     timer_config: None,
                     authority_list: speaker_list,
+                    next_authority_list: None,
+                    proposer_seed: None,
+                    skipped_proposers: Vec::new(),
                 }),
             )
             .unwrap();
@@ -296,7 +319,7 @@ impl Speaker {
 
     async fn run(
         &self,
-        interval: u64,
+        interval: Duration,
         timer_config: Option<DurationConfig>,
         speaker_list: Vec<Node>,
     ) -> Result<(), Box<dyn Error + Send>> {
@@ -351,10 +374,10 @@ impl Speaker {
 #[tokio::main]
 async fn main() {
     let speaker_list: Vec<Node> = (0..SPEAKER_NUM)
-        .map(|_| Node::new(gen_random_bytes()))
+        .map(|_| Node::new(gen_random_address()))
         .collect();
     let channels: Vec<Channel> = (0..SPEAKER_NUM).map(|_| unbounded()).collect();
-    let hearings: HashMap<Bytes, Receiver<MlmMsg<Speech>>> = speaker_list
+    let hearings: HashMap<Address, Receiver<MlmMsg<Speech>>> = speaker_list
         .iter()
         .map(|node| node.address.clone())
         .zip(channels.iter().map(|(_, receiver)| receiver.clone()))
@@ -366,7 +389,7 @@ async fn main() {
 
     for speaker in speaker_list.iter() {
         let name = speaker.address.clone();
-        let mut talk_to: HashMap<Bytes, Sender<MlmMsg<Speech>>> = speaker_list_clone
+        let mut talk_to: HashMap<Address, Sender<MlmMsg<Speech>>> = speaker_list_clone
             .iter()
             .map(|speaker| speaker.address.clone())
             .zip(channels.iter().map(|(sender, _)| sender.clone()))
@@ -398,10 +421,14 @@ fn gen_random_bytes() -> Bytes {
     Bytes::from(vec)
 }
 
-fn hash(bytes: &Bytes) -> Bytes {
+fn gen_random_address() -> Address {
+    Address::new(gen_random_bytes()).unwrap()
+}
+
+fn hash(bytes: &Bytes) -> Hash {
     let mut out = [0u8; 32];
     out.copy_from_slice(&HASHER_INST.digest(bytes));
-    BytesMut::from(&out[..]).freeze()
+    Hash::from(out)
 }
 
 fn timer_config() -> Option<DurationConfig> {
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mlm::fuzz::FuzzBlock;
+use mlm::types::MlmMsg;
+
+fuzz_target!(|msg: MlmMsg<FuzzBlock>| {
+    mlm::fuzz::verify_msg(msg);
+});
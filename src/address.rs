@@ -0,0 +1,247 @@
+use std::fmt::Debug;
+
+use bytes::{Bytes, BytesMut};
+use hummer::coding::{hex_decode, hex_encode};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::error::ConsensusError;
+use crate::types::{Address, Node};
+use crate::ConsensusResult;
+
+/// The mapping from the public keys of the authorities to the addresses used in the authority
+/// list and the vote bitmaps. Every node of a network must use the same scheme.
+pub trait AddressScheme: Debug + Send + Sync {
+    /// Derive the address of a public key.
+    fn address_from_pubkey(&self, pubkey: &Bytes) -> ConsensusResult<Address>;
+
+    /// Check whether the address is well formed in the scheme.
+    fn is_valid_address(&self, address: &Address) -> bool;
+}
+
+/// The address is the last `len` bytes of the keccak256 hash of the public key. The default
+/// length is 20 bytes, which is the same as the Ethereum address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashAddressScheme {
+    len: usize,
+}
+
+impl Default for HashAddressScheme {
+    fn default() -> Self {
+        HashAddressScheme { len: 20 }
+    }
+}
+
+impl HashAddressScheme {
+    /// Create a hash address scheme with the given address length, which is at most 32.
+    /// Return `InvalidAddress` if the length is 0 or over 32.
+    pub fn new(len: usize) -> ConsensusResult<Self> {
+        if len == 0 || len > 32 {
+            return Err(ConsensusError::InvalidAddress);
+        }
+        Ok(HashAddressScheme { len })
+    }
+}
+
+impl AddressScheme for HashAddressScheme {
+    fn address_from_pubkey(&self, pubkey: &Bytes) -> ConsensusResult<Address> {
+        if pubkey.is_empty() {
+            return Err(ConsensusError::InvalidAddress);
+        }
+
+        let mut hash = [0u8; 32];
+        let mut hasher = Keccak::v256();
+        hasher.update(pubkey);
+        hasher.finalize(&mut hash);
+        Address::from_slice(&hash[32 - self.len..])
+    }
+
+    fn is_valid_address(&self, address: &Address) -> bool {
+        address.len() == self.len
+    }
+}
+
+/// The address is the public key itself. If the key length is set, only the keys of the
+/// length are valid.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RawAddressScheme {
+    key_len: Option<usize>,
+}
+
+impl RawAddressScheme {
+    /// Create a raw address scheme that accepts public keys of the given length only.
+    pub fn with_key_len(key_len: usize) -> Self {
+        RawAddressScheme {
+            key_len: Some(key_len),
+        }
+    }
+}
+
+impl AddressScheme for RawAddressScheme {
+    fn address_from_pubkey(&self, pubkey: &Bytes) -> ConsensusResult<Address> {
+        let address = Address::new(pubkey.clone())?;
+        if !self.is_valid_address(&address) {
+            return Err(ConsensusError::InvalidAddress);
+        }
+        Ok(address)
+    }
+
+    fn is_valid_address(&self, address: &Address) -> bool {
+        match self.key_len {
+            Some(len) => address.len() == len,
+            None => !address.is_empty(),
+        }
+    }
+}
+
+/// A chain specific scheme which puts a fixed prefix, such as a chain id or a version byte,
+/// before the addresses of an inner scheme.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrefixedAddressScheme<S: AddressScheme> {
+    prefix: Bytes,
+    inner: S,
+}
+
+impl<S: AddressScheme> PrefixedAddressScheme<S> {
+    /// Create a prefixed address scheme.
+    pub fn new(prefix: Bytes, inner: S) -> Self {
+        PrefixedAddressScheme { prefix, inner }
+    }
+}
+
+impl<S: AddressScheme> AddressScheme for PrefixedAddressScheme<S> {
+    fn address_from_pubkey(&self, pubkey: &Bytes) -> ConsensusResult<Address> {
+        let address = self.inner.address_from_pubkey(pubkey)?;
+        let mut res = BytesMut::with_capacity(self.prefix.len() + address.len());
+        res.extend_from_slice(&self.prefix);
+        res.extend_from_slice(&address);
+        Address::new(res.freeze())
+    }
+
+    fn is_valid_address(&self, address: &Address) -> bool {
+        address.starts_with(&self.prefix)
+            && Address::new(address.as_bytes().slice(self.prefix.len()..))
+                .is_ok_and(|inner| self.inner.is_valid_address(&inner))
+    }
+}
+
+/// Encode an address as a hex string without the `0x` prefix.
+pub fn address_to_hex(address: &Address) -> String {
+    hex_encode(address)
+}
+
+/// Decode an address from a hex string, with or without the `0x` prefix.
+pub fn address_from_hex(s: &str) -> ConsensusResult<Address> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    let bytes = hex_decode(s).map_err(|e| {
+        ConsensusError::DecodeErr(format!("Decode address error {:?}", e))
+    })?;
+    Address::try_from(bytes)
+}
+
+/// Build an authority list from the public keys and the propose and vote weights of the
+/// authorities.
+pub fn authority_list_from_pubkeys<A: AddressScheme + ?Sized>(
+    scheme: &A,
+    authorities: &[(Bytes, u32, u128)],
+) -> ConsensusResult<Vec<Node>> {
+    authorities
+        .iter()
+        .map(|(pubkey, propose_weight, vote_weight)| {
+            Ok(Node {
+                address: scheme.address_from_pubkey(pubkey)?,
+                propose_weight: *propose_weight,
+                vote_weight: *vote_weight,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use crate::types::Address;
+
+    use super::{
+        address_from_hex, address_to_hex, authority_list_from_pubkeys, AddressScheme,
+        HashAddressScheme, PrefixedAddressScheme, RawAddressScheme,
+    };
+
+    #[test]
+    fn test_hash_scheme() {
+        // The Ethereum address of the uncompressed public key of the private key 1.
+        let pubkey = address_from_hex(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+             483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8",
+        )
+        .unwrap();
+        let scheme = HashAddressScheme::default();
+        let address = scheme.address_from_pubkey(pubkey.as_bytes()).unwrap();
+        assert_eq!(
+            address,
+            address_from_hex("0x7e5f4552091a69125d5dfcb7b8c2659029395bdf").unwrap()
+        );
+        assert!(scheme.is_valid_address(&address));
+        assert!(!scheme.is_valid_address(&pubkey));
+        assert!(scheme.address_from_pubkey(&Bytes::new()).is_err());
+
+        let scheme = HashAddressScheme::new(32).unwrap();
+        let address = scheme.address_from_pubkey(pubkey.as_bytes()).unwrap();
+        assert_eq!(address.len(), 32);
+        assert!(HashAddressScheme::new(0).is_err());
+        assert!(HashAddressScheme::new(33).is_err());
+    }
+
+    #[test]
+    fn test_raw_scheme() {
+        let pubkey = Bytes::from(vec![2u8; 33]);
+        let scheme = RawAddressScheme::default();
+        let address = scheme.address_from_pubkey(&pubkey).unwrap();
+        assert_eq!(address.as_bytes(), &pubkey);
+        assert!(scheme.address_from_pubkey(&Bytes::new()).is_err());
+
+        let scheme = RawAddressScheme::with_key_len(48);
+        assert!(scheme.address_from_pubkey(&pubkey).is_err());
+        assert!(scheme.is_valid_address(&Address::from_slice(&[1u8; 48]).unwrap()));
+    }
+
+    #[test]
+    fn test_prefixed_scheme() {
+        let scheme = PrefixedAddressScheme::new(
+            Bytes::from(vec![0x01]),
+            HashAddressScheme::new(4).unwrap(),
+        );
+        let address = scheme
+            .address_from_pubkey(&Bytes::from(vec![3u8; 33]))
+            .unwrap();
+        assert_eq!(address.len(), 5);
+        assert_eq!(address[0], 0x01);
+        assert!(scheme.is_valid_address(&address));
+        assert!(!scheme.is_valid_address(&Address::from_slice(&address[1..]).unwrap()));
+        assert!(!scheme.is_valid_address(&Address::from_slice(&[0x01]).unwrap()));
+
+        let mut other_chain = address.to_vec();
+        other_chain[0] = 0x02;
+        assert!(!scheme.is_valid_address(&Address::from_slice(&other_chain).unwrap()));
+    }
+
+    #[test]
+    fn test_authority_list_from_pubkeys() {
+        let scheme = RawAddressScheme::default();
+        let authorities = vec![
+            (Bytes::from(vec![1u8; 4]), 1, 2),
+            (Bytes::from(vec![2u8; 4]), 3, 4),
+        ];
+        let list = authority_list_from_pubkeys(&scheme, &authorities).unwrap();
+        assert_eq!(list[1].address.as_bytes(), &Bytes::from(vec![2u8; 4]));
+        assert_eq!(list[1].propose_weight, 3);
+        assert_eq!(list[1].vote_weight, 4);
+
+        let authorities = vec![(Bytes::new(), 1, 1)];
+        assert!(authority_list_from_pubkeys(&scheme, &authorities).is_err());
+        let address = Address::from_slice(&[0xab, 0x01]).unwrap();
+        assert_eq!(address_to_hex(&address), "ab01");
+        assert_eq!(address.to_string(), "ab01");
+        assert!(address_from_hex("0x").is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::address::AddressScheme;
+use crate::error::AuthorityListError;
+use crate::types::{Address, Node};
+use crate::utils::auth_manage::{quorum, validate_authority_list};
+
+/// An authority list built by `AuthorityListBuilder`, with the totals that `AuthorityManage`
+/// derives from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorityList {
+    /// The authorities sorted by the address, which is the order of the vote bitmaps.
+    pub nodes: Vec<Node>,
+    /// The public keys of the authorities in the order of `nodes`.
+    pub pubkeys: Vec<Bytes>,
+    /// The sum of the vote weights.
+    pub vote_weight_sum: u128,
+    /// The sum of the propose weights.
+    pub propose_weight_sum: u64,
+    /// The least vote weight above two thirds of the sum, which is a quorum by the default
+    /// threshold policy.
+    pub quorum_weight: u128,
+}
+
+/// The builder of an authority list from the addresses, the public keys and the stakes of the
+/// validators, which checks the entries and normalizes the weights before the list reaches the
+/// consensus, instead of failing at the thresholds of a running height.
+///
+/// The vote weights are the stakes divided by their greatest common divisor, so the thresholds
+/// are the same as those of the raw stakes. The propose weights follow the vote weights scaled
+/// down to fit `u32`, and each authority keeps at least one, unless they are set equal.
+///
+/// ```ignore
+/// let list = AuthorityListBuilder::new()
+///     .scheme(Arc::new(HashAddressScheme::default()))
+///     .authority(address_a, pubkey_a, 3_000_000)
+///     .authority(address_b, pubkey_b, 1_000_000)
+///     .build()?;
+/// let mlm = MlmBuilder::new(address, consensus, crypto, wal)
+///     .authority_list(list.nodes)
+///     .build()?;
+/// ```
+#[derive(Debug, Default)]
+pub struct AuthorityListBuilder {
+    scheme: Option<Arc<dyn AddressScheme>>,
+    entries: Vec<(Address, Bytes, u128)>,
+    equal_propose_weights: bool,
+}
+
+impl AuthorityListBuilder {
+    /// Create a builder of an empty authority list.
+    pub fn new() -> Self {
+        AuthorityListBuilder::default()
+    }
+
+    /// Check that the address of each authority is derived from its public key by the scheme.
+    pub fn scheme(mut self, scheme: Arc<dyn AddressScheme>) -> Self {
+        self.scheme = Some(scheme);
+        self
+    }
+
+    /// Add an authority of the address, the public key and the stake.
+    pub fn authority(mut self, address: Address, pubkey: Bytes, stake: u128) -> Self {
+        self.entries.push((address, pubkey, stake));
+        self
+    }
+
+    /// Set whether every authority proposes with the same weight instead of by its stake.
+    pub fn equal_propose_weights(mut self, equal: bool) -> Self {
+        self.equal_propose_weights = equal;
+        self
+    }
+
+    /// Check the authorities and build the authority list. An error is returned if there is
+    /// no authority, an address or a public key is given twice, a stake is zero, an address is
+    /// not derived from its public key by the scheme, or the sum of the weights overflows.
+    pub fn build(self) -> Result<AuthorityList, AuthorityListError> {
+        if self.entries.is_empty() {
+            return Err(AuthorityListError::Empty);
+        }
+
+        let mut addresses = HashSet::with_capacity(self.entries.len());
+        let mut pubkeys = HashSet::with_capacity(self.entries.len());
+        let mut divisor = 0u128;
+        for (address, pubkey, stake) in self.entries.iter() {
+            if !addresses.insert(address) {
+                return Err(AuthorityListError::DuplicateAddress(address.clone()));
+            }
+            if !pubkeys.insert(pubkey) {
+                return Err(AuthorityListError::DuplicatePubkey(pubkey.clone()));
+            }
+            if *stake == 0 {
+                return Err(AuthorityListError::ZeroVoteWeight(address.clone()));
+            }
+            if let Some(scheme) = self.scheme.as_ref() {
+                if scheme.address_from_pubkey(pubkey).ok().as_ref() != Some(address) {
+                    return Err(AuthorityListError::AddressMismatch(address.clone()));
+                }
+            }
+            divisor = gcd(divisor, *stake);
+        }
+
+        let mut entries = self.entries;
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let max_weight = entries.iter().map(|(_, _, stake)| stake / divisor).max();
+        let propose_divisor = max_weight.unwrap_or(1).div_ceil(u128::from(u32::MAX));
+
+        let mut list = AuthorityList {
+            nodes: Vec::with_capacity(entries.len()),
+            pubkeys: Vec::with_capacity(entries.len()),
+            vote_weight_sum: 0,
+            propose_weight_sum: 0,
+            quorum_weight: 0,
+        };
+        for (address, pubkey, stake) in entries.into_iter() {
+            let vote_weight = stake / divisor;
+            let propose_weight = if self.equal_propose_weights {
+                1
+            } else {
+                (vote_weight / propose_divisor).max(1) as u32
+            };
+            list.vote_weight_sum = list
+                .vote_weight_sum
+                .checked_add(vote_weight)
+                .ok_or(AuthorityListError::VoteWeightOverflow)?;
+            list.propose_weight_sum += u64::from(propose_weight);
+            list.nodes.push(Node {
+                address,
+                propose_weight,
+                vote_weight,
+            });
+            list.pubkeys.push(pubkey);
+        }
+        validate_authority_list(&list.nodes)?;
+        list.quorum_weight = quorum(list.vote_weight_sum);
+        Ok(list)
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use super::AuthorityListBuilder;
+    use crate::address::{AddressScheme, HashAddressScheme};
+    use crate::error::AuthorityListError;
+    use crate::types::Address;
+    #[cfg(feature = "runtime")]
+    use crate::utils::auth_manage::AuthorityManage;
+
+    fn gen_pubkey(index: u8) -> Bytes {
+        Bytes::from(vec![index; 33])
+    }
+
+    fn gen_address(index: u8) -> Address {
+        HashAddressScheme::default()
+            .address_from_pubkey(&gen_pubkey(index))
+            .unwrap()
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_authority_list_builder() {
+        let stakes = [
+            (1u8, 4_000_000u128),
+            (2, 2_000_000),
+            (3, 2_000_000),
+            (4, 6_000_000),
+        ];
+        let list = stakes
+            .iter()
+            .fold(AuthorityListBuilder::new(), |builder, (index, stake)| {
+                builder.authority(gen_address(*index), gen_pubkey(*index), *stake)
+            })
+            .scheme(Arc::new(HashAddressScheme::default()))
+            .build()
+            .unwrap();
+
+        // The weights keep the ratios of the stakes, and the nodes are sorted by the address
+        // with their public keys.
+        assert_eq!(list.vote_weight_sum, 7);
+        assert_eq!(list.propose_weight_sum, 7);
+        assert_eq!(list.quorum_weight, 5);
+        for (node, pubkey) in list.nodes.iter().zip(list.pubkeys.iter()) {
+            let (index, stake) = stakes
+                .iter()
+                .find(|(i, _)| gen_pubkey(*i) == pubkey)
+                .unwrap();
+            assert_eq!(node.address, gen_address(*index));
+            assert_eq!(node.vote_weight, stake / 2_000_000);
+            assert_eq!(u128::from(node.propose_weight), node.vote_weight);
+        }
+        assert!(
+            list.nodes
+                .windows(2)
+                .all(|pair| pair[0].address < pair[1].address)
+        );
+
+        // The totals are the ones of the authority manage.
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut list.nodes.clone());
+        assert_eq!(authority.get_vote_weight_sum(), list.vote_weight_sum);
+        assert_eq!(authority.get_authority_list(), list.nodes);
+
+        // The propose weights of the huge stakes fit `u32`, and the small ones keep one.
+        let list = AuthorityListBuilder::new()
+            .authority(gen_address(1), gen_pubkey(1), u128::from(u32::MAX) * 6)
+            .authority(gen_address(2), gen_pubkey(2), 7)
+            .build()
+            .unwrap();
+        let weights = list
+            .nodes
+            .iter()
+            .map(|node| (node.vote_weight, node.propose_weight))
+            .collect::<Vec<_>>();
+        assert!(weights.contains(&(7, 1)));
+        assert!(weights.contains(&(u128::from(u32::MAX) * 6, u32::MAX)));
+
+        let list = AuthorityListBuilder::new()
+            .authority(gen_address(1), gen_pubkey(1), 100)
+            .authority(gen_address(2), gen_pubkey(2), 300)
+            .equal_propose_weights(true)
+            .build()
+            .unwrap();
+        assert!(list.nodes.iter().all(|node| node.propose_weight == 1));
+    }
+
+    #[test]
+    fn test_invalid_authorities() {
+        let build = |entries: &[(u8, u8, u128)]| {
+            entries
+                .iter()
+                .fold(
+                    AuthorityListBuilder::new(),
+                    |builder, (address, pubkey, stake)| {
+                        builder.authority(
+                            gen_address(*address),
+                            gen_pubkey(*pubkey),
+                            *stake,
+                        )
+                    },
+                )
+                .scheme(Arc::new(HashAddressScheme::default()))
+                .build()
+        };
+
+        assert_eq!(build(&[]), Err(AuthorityListError::Empty));
+        assert_eq!(
+            build(&[(1, 1, 1), (1, 2, 1)]),
+            Err(AuthorityListError::DuplicateAddress(gen_address(1)))
+        );
+        assert_eq!(
+            build(&[(1, 1, 1), (2, 1, 1)]),
+            Err(AuthorityListError::DuplicatePubkey(gen_pubkey(1)))
+        );
+        assert_eq!(
+            build(&[(1, 1, 1), (2, 2, 0)]),
+            Err(AuthorityListError::ZeroVoteWeight(gen_address(2)))
+        );
+        assert_eq!(
+            build(&[(1, 1, 1), (2, 3, 1)]),
+            Err(AuthorityListError::AddressMismatch(gen_address(2)))
+        );
+        assert_eq!(
+            build(&[(1, 1, u128::MAX), (2, 2, u128::MAX - 1)]),
+            Err(AuthorityListError::VoteWeightOverflow)
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use rlp::{DecoderError, Rlp, RlpStream};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ConsensusError, ErrorKind};
+use crate::types::Hash;
+use crate::{Codec, ConsensusResult, Crypto};
+
+/// An ordered batch of blocks which is proposed, voted and committed as one block, so that the
+/// consensus overhead is amortized across the blocks. A chain opts in by running the engine
+/// on `Batch<T>` instead of `T`: `Consensus::get_block()` returns a batch with its root as the
+/// hash, `Consensus::check_block()` checks the blocks and the root by `verify_root()`, and
+/// `Consensus::commit()` delivers the whole batch. The proof of the height then covers the
+/// batch root, and a block in the batch is proved by a `BatchMemberProof` to the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Batch<T: Codec> {
+    /// The blocks in order.
+    pub blocks: Vec<T>,
+    /// The hashes of the blocks in order.
+    pub hashes: Vec<Hash>,
+}
+
+impl<T: Codec> Batch<T> {
+    /// Create a batch of the blocks with their hashes.
+    pub fn new(blocks: Vec<(T, Hash)>) -> Self {
+        let (blocks, hashes) = blocks.into_iter().unzip();
+        Batch { blocks, hashes }
+    }
+
+    /// The number of the blocks in the batch.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Whether the batch has no block.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The merkle root of the block hashes, which is the hash of the batch in the consensus.
+    pub fn root<C: Crypto + ?Sized>(&self, crypto: &C) -> Hash {
+        batch_root(crypto, &self.hashes)
+    }
+
+    /// Verify that the batch is well formed and its root is the given hash.
+    pub fn verify_root<C: Crypto + ?Sized>(
+        &self,
+        crypto: &C,
+        root: &Hash,
+    ) -> ConsensusResult<()> {
+        if self.blocks.is_empty() || self.blocks.len() != self.hashes.len() {
+            return Err(ConsensusError::DecodeErr(format!(
+                "Invalid batch of {} blocks with {} hashes",
+                self.blocks.len(),
+                self.hashes.len()
+            )));
+        }
+
+        if self.root(crypto) != *root {
+            return Err(ConsensusError::CryptoErr("Batch root mismatch".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Get the proof of the block at the index to the batch root.
+    pub fn member_proof<C: Crypto + ?Sized>(
+        &self,
+        crypto: &C,
+        index: usize,
+    ) -> Option<BatchMemberProof> {
+        batch_member_proof(crypto, &self.hashes, index)
+    }
+
+    /// Take the blocks with their hashes in order.
+    pub fn into_blocks(self) -> Vec<(T, Hash)> {
+        self.blocks.into_iter().zip(self.hashes).collect()
+    }
+}
+
+impl<T: Codec> Codec for Batch<T> {
+    type Error = ConsensusError;
+
+    fn encode(&self) -> Result<Bytes, Self::Error> {
+        let mut s = RlpStream::new_list(2);
+        s.begin_list(self.blocks.len());
+        for block in self.blocks.iter() {
+            let block = block.encode().map_err(|e| {
+                ConsensusError::with_source(ErrorKind::Protocol, "Encode batch block", e)
+            })?;
+            s.append(&block.to_vec());
+        }
+        s.begin_list(self.hashes.len());
+        for hash in self.hashes.iter() {
+            s.append(&hash.to_vec());
+        }
+        Ok(Bytes::from(s.out().to_vec()))
+    }
+
+    /// Decode the blocks from the slices of the data, so a block is copied at most once by
+    /// its own codec.
+    fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+        let decode_err = |e: DecoderError| {
+            ConsensusError::DecodeErr(format!("Decode batch error {:?}", e))
+        };
+
+        let r = Rlp::new(data);
+        let list = r.at(0).map_err(decode_err)?;
+        if !list.is_list() {
+            return Err(decode_err(DecoderError::RlpExpectedToBeList));
+        }
+        let blocks = list
+            .iter()
+            .map(|block| {
+                T::decode_ref(block.data().map_err(decode_err)?).map_err(|e| {
+                    ConsensusError::with_source(
+                        ErrorKind::Protocol,
+                        "Decode batch block",
+                        e,
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let hashes = r
+            .at(1)
+            .and_then(|list| list.as_list::<Vec<u8>>())
+            .map_err(decode_err)?
+            .into_iter()
+            .map(Hash::from)
+            .collect();
+
+        Ok(Batch { blocks, hashes })
+    }
+}
+
+/// The merkle proof of a block in a batch to the batch root.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct BatchMemberProof {
+    /// The index of the block in the batch.
+    pub index: usize,
+    /// The number of the blocks in the batch.
+    pub len: usize,
+    /// The sibling hashes from the leaf to the root. A node without a sibling is promoted to
+    /// the next level as it is, and has no sibling in the proof.
+    #[serde(with = "super::serde_multi_hex")]
+    pub siblings: Vec<Hash>,
+}
+
+impl BatchMemberProof {
+    /// Verify that the block hash is at the index of the batch of the root.
+    pub fn verify<C: Crypto + ?Sized>(
+        &self,
+        crypto: &C,
+        hash: &Hash,
+        root: &Hash,
+    ) -> bool {
+        if self.index >= self.len {
+            return false;
+        }
+
+        let mut siblings = self.siblings.iter();
+        let mut node = hash.clone();
+        let mut pos = self.index;
+        let mut len = self.len;
+        while len > 1 {
+            let sibling = pos ^ 1;
+            if sibling < len {
+                let sibling = match siblings.next() {
+                    Some(sibling) => sibling,
+                    None => return false,
+                };
+                node = if pos & 1 == 0 {
+                    hash_pair(crypto, &node, sibling)
+                } else {
+                    hash_pair(crypto, sibling, &node)
+                };
+            }
+            pos /= 2;
+            len = len.div_ceil(2);
+        }
+
+        siblings.next().is_none() && node == *root
+    }
+}
+
+/// Compute the merkle root of the block hashes. The root of a single hash is the hash itself,
+/// and the root of no hash is the hash of the empty bytes.
+pub fn batch_root<C: Crypto + ?Sized>(crypto: &C, hashes: &[Hash]) -> Hash {
+    if hashes.is_empty() {
+        return crypto.hash(Bytes::new());
+    }
+
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        level = next_level(crypto, &level);
+    }
+    level.remove(0)
+}
+
+/// Get the merkle proof of the hash at the index to the root of the hashes.
+pub fn batch_member_proof<C: Crypto + ?Sized>(
+    crypto: &C,
+    hashes: &[Hash],
+    index: usize,
+) -> Option<BatchMemberProof> {
+    if index >= hashes.len() {
+        return None;
+    }
+
+    let mut siblings = Vec::new();
+    let mut level = hashes.to_vec();
+    let mut pos = index;
+    while level.len() > 1 {
+        let sibling = pos ^ 1;
+        if sibling < level.len() {
+            siblings.push(level[sibling].clone());
+        }
+        level = next_level(crypto, &level);
+        pos /= 2;
+    }
+
+    Some(BatchMemberProof {
+        index,
+        len: hashes.len(),
+        siblings,
+    })
+}
+
+fn next_level<C: Crypto + ?Sized>(crypto: &C, level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_pair(crypto, left, right),
+            [node] => node.clone(),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn hash_pair<C: Crypto + ?Sized>(crypto: &C, left: &Hash, right: &Hash) -> Hash {
+    let mut buf = BytesMut::with_capacity(left.len() + right.len());
+    buf.put_slice(left);
+    buf.put_slice(right);
+    crypto.hash(buf.freeze())
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use bytes::Bytes;
+
+    use super::{batch_root, Batch};
+    use crate::error::ConsensusError;
+    use crate::test_utils::KeccakCrypto;
+    use crate::{Codec, Crypto};
+
+    fn gen_batch(len: u8) -> Batch<Bytes> {
+        Batch::new(
+            (0..len)
+                .map(|i| {
+                    let block = Bytes::from(vec![i; 8]);
+                    let hash = KeccakCrypto.hash(block.clone());
+                    (block, hash)
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_batch_codec() {
+        let batch = gen_batch(3);
+        let res = Batch::<Bytes>::decode(batch.encode().unwrap()).unwrap();
+        assert_eq!(res, batch);
+        assert_eq!(res.into_blocks().len(), 3);
+    }
+
+    #[test]
+    fn test_batch_decode_error() {
+        let data = gen_batch(3).encode().unwrap();
+        assert_eq!(Batch::<Bytes>::decode_ref(&data).unwrap(), gen_batch(3));
+        assert!(matches!(
+            Batch::<Bytes>::decode_ref(&[0x01]),
+            Err(ConsensusError::DecodeErr(_))
+        ));
+
+        // The error of a block is kept as the source.
+        let inner = Batch {
+            blocks: vec![Bytes::from_static(&[0x01])],
+            hashes: vec![],
+        };
+        let err = Batch::<Batch<Bytes>>::decode(inner.encode().unwrap()).unwrap_err();
+        let source = err.source().unwrap().downcast_ref::<ConsensusError>();
+        assert!(matches!(source, Some(ConsensusError::DecodeErr(_))));
+    }
+
+    #[test]
+    fn test_batch_root() {
+        let crypto = KeccakCrypto;
+        let batch = gen_batch(1);
+        assert_eq!(batch.root(&crypto), batch.hashes[0]);
+
+        for len in 2..8 {
+            let batch = gen_batch(len);
+            let root = batch.root(&crypto);
+            assert!(batch.verify_root(&crypto, &root).is_ok());
+            assert!(batch.verify_root(&crypto, &batch.hashes[0]).is_err());
+
+            for index in 0..batch.len() {
+                let proof = batch.member_proof(&crypto, index).unwrap();
+                assert!(proof.verify(&crypto, &batch.hashes[index], &root));
+                let other = &batch.hashes[(index + 1) % batch.len()];
+                assert!(!proof.verify(&crypto, other, &root));
+            }
+            assert!(batch.member_proof(&crypto, batch.len()).is_none());
+        }
+
+        // The order of the blocks is committed by the root.
+        let mut hashes = gen_batch(4).hashes;
+        let root = batch_root(&crypto, &hashes);
+        hashes.swap(0, 1);
+        assert_ne!(batch_root(&crypto, &hashes), root);
+    }
+}
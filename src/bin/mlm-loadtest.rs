@@ -0,0 +1,57 @@
+//! Run the simulated validators with the mock crypto and report their throughput in the wall
+//! time, which measures 4, 21 and 100 validators of 10 heights by default. Run by
+//! `cargo run --release --features loadtest --bin mlm-loadtest -- --heights 20 4 21`.
+
+use std::env;
+use std::process;
+
+use mlm::testing::load::{self, LoadConfig};
+
+const USAGE: &str = "usage: mlm-loadtest [--heights N] [VALIDATORS]..";
+
+fn parse_args() -> Result<(u64, Vec<usize>), String> {
+    let mut heights = 10;
+    let mut validators = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--heights" {
+            let value = args.next().ok_or("missing the number of the heights")?;
+            heights = value
+                .parse()
+                .map_err(|_| format!("invalid number of the heights {}", value))?;
+        } else {
+            validators.push(
+                arg.parse()
+                    .map_err(|_| format!("invalid number of the validators {}", arg))?,
+            );
+        }
+    }
+    if validators.is_empty() {
+        validators = vec![4, 21, 100];
+    }
+    Ok((heights, validators))
+}
+
+fn main() {
+    let (heights, validators) = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .unwrap();
+
+    for validators in validators {
+        let config = LoadConfig::new(validators, heights);
+        match runtime.block_on(load::run(&config)) {
+            Some(report) => println!("{}", report),
+            None => {
+                eprintln!("{} validators stall before {} heights", validators, heights);
+                process::exit(1);
+            }
+        }
+    }
+}
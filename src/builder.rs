@@ -0,0 +1,344 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{ConfigError, ConsensusError};
+use crate::rng::Rng;
+use crate::types::{Address, Node};
+use crate::utils::auth_manage::validate_authority_list;
+use crate::{
+    BackoffConfig, Codec, Consensus, ConsensusResult, Crypto, DurationConfig, Mlm,
+    MlmConfig, Wal,
+};
+
+/// The options of the first height that a built instance starts with by `Mlm::start()`.
+#[derive(Clone, Debug)]
+pub(crate) struct Launch {
+    pub(crate) interval: Duration,
+    pub(crate) authority_list: Vec<Node>,
+    pub(crate) timer_config: Option<DurationConfig>,
+}
+
+/// The builder of an mlm instance, which validates the configuration before the instance is
+/// created, instead of failing at a height of the running consensus.
+///
+/// ```ignore
+/// let mlm = MlmBuilder::new(address, consensus, crypto, wal)
+///     .interval(Duration::from_secs(3))
+///     .authority_list(authority_list)
+///     .require_authority(true)
+///     .verify_concurrency(8)
+///     .build()?;
+/// mlm.start(0).await?;
+/// ```
+pub struct MlmBuilder<T: Codec, F: Consensus<T>, C: Crypto, W: Wal> {
+    address: Address,
+    consensus: Arc<F>,
+    crypto: Arc<C>,
+    wal: Arc<W>,
+    config: MlmConfig,
+    interval: Option<Duration>,
+    authority_list: Option<Vec<Node>>,
+    timer_config: Option<DurationConfig>,
+    rng: Option<Arc<dyn Rng>>,
+    require_authority: bool,
+    pin_block: PhantomData<T>,
+}
+
+impl<T, F, C, W> MlmBuilder<T, F, C, W>
+where
+    T: Codec + Send + Sync + 'static,
+    F: Consensus<T> + 'static,
+    C: Crypto + Send + Sync + 'static,
+    W: Wal + 'static,
+{
+    /// Create a builder of the node of the address with the default engine configuration.
+    pub fn new(
+        address: Address,
+        consensus: Arc<F>,
+        crypto: Arc<C>,
+        wal: Arc<W>,
+    ) -> Self {
+        MlmBuilder {
+            address,
+            consensus,
+            crypto,
+            wal,
+            config: MlmConfig::default(),
+            interval: None,
+            authority_list: None,
+            timer_config: None,
+            rng: None,
+            require_authority: false,
+            pin_block: PhantomData,
+        }
+    }
+
+    /// Set the engine configuration. The knobs of the builder set afterwards change it.
+    pub fn config(mut self, config: MlmConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the height interval of the first height, which is required.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Set the authority list of the first height, which is required.
+    pub fn authority_list(mut self, authority_list: Vec<Node>) -> Self {
+        self.authority_list = Some(authority_list);
+        self
+    }
+
+    /// Set the timeout ratios of the steps. The classic ratios are used without it.
+    pub fn timer_config(mut self, timer_config: DurationConfig) -> Self {
+        self.timer_config = Some(timer_config);
+        self
+    }
+
+    /// Set the backoff policy of the timeouts on repeated rounds, which is kept in the timer
+    /// config of the classic ratios if there is none.
+    pub fn backoff(mut self, backoff: BackoffConfig) -> Self {
+        self.timer_config
+            .get_or_insert_with(DurationConfig::classic)
+            .backoff = Some(backoff);
+        self
+    }
+
+    /// Change the round on the precommit timeout without the chokes, which is kept in the
+    /// timer config of the classic ratios if there is none.
+    pub fn choke_free(mut self, choke_free: bool) -> Self {
+        self.timer_config
+            .get_or_insert_with(DurationConfig::classic)
+            .set_choke_free(choke_free);
+        self
+    }
+
+    /// Set the source of the randomness of the engine, which is seeded by the address of the
+    /// node without it.
+    pub fn rng(mut self, rng: Arc<dyn Rng>) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+
+    /// Require the address of the node to be in the authority list of the first height,
+    /// which a validator should be while an observer need not.
+    pub fn require_authority(mut self, require: bool) -> Self {
+        self.require_authority = require;
+        self
+    }
+
+    /// Set the number of the messages handled between the timer checks and the max number
+    /// of the messages drained from an inbox at a time. Zero means the defaults.
+    pub fn message_buffers(
+        mut self,
+        message_budget: usize,
+        inbox_drain_limit: usize,
+    ) -> Self {
+        self.config
+            .set_message_buffers(message_budget, inbox_drain_limit);
+        self
+    }
+
+    /// Set the max number of the signature verifications running at once. Zero means no
+    /// limit.
+    pub fn verify_concurrency(mut self, verify_concurrency: usize) -> Self {
+        self.config.set_verify_concurrency(verify_concurrency);
+        self
+    }
+
+    /// Check the configuration without building the instance.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        validate(
+            &self.address,
+            &self.config,
+            &self.launch()?,
+            self.require_authority,
+        )
+    }
+
+    /// Validate the configuration and create the instance, which is started by
+    /// `Mlm::start()` with the options of the first height.
+    pub fn build(self) -> ConsensusResult<Mlm<T, F, C, W>> {
+        let launch = self.launch().map_err(ConsensusError::InvalidConfig)?;
+        validate(&self.address, &self.config, &launch, self.require_authority)
+            .map_err(ConsensusError::InvalidConfig)?;
+
+        let mlm = Mlm::new(self.address, self.consensus, self.crypto, self.wal);
+        mlm.set_config(self.config);
+        mlm.set_launch(launch);
+        if let Some(rng) = self.rng {
+            mlm.set_rng(rng);
+        }
+        Ok(mlm)
+    }
+
+    fn launch(&self) -> Result<Launch, ConfigError> {
+        Ok(Launch {
+            interval: self.interval.ok_or(ConfigError::Missing("interval"))?,
+            authority_list: self
+                .authority_list
+                .clone()
+                .ok_or(ConfigError::Missing("authority list"))?,
+            timer_config: self.timer_config.clone(),
+        })
+    }
+}
+
+fn validate(
+    address: &Address,
+    config: &MlmConfig,
+    launch: &Launch,
+    require_authority: bool,
+) -> Result<(), ConfigError> {
+    let interval = launch.interval.as_millis() as u64;
+    if interval == 0 {
+        return Err(ConfigError::ZeroInterval);
+    }
+    let tuning = config.interval_tuning;
+    if tuning.max > 0 && (interval < tuning.min || interval > tuning.max) {
+        return Err(ConfigError::IntervalOutOfBounds {
+            interval,
+            min: tuning.min,
+            max: tuning.max,
+        });
+    }
+
+    let authority_list = &launch.authority_list;
+    validate_authority_list(authority_list).map_err(ConfigError::AuthorityList)?;
+    if require_authority && !authority_list.iter().any(|node| &node.address == address) {
+        return Err(ConfigError::NotAuthority(address.clone()));
+    }
+
+    if let Some(timer_config) = launch.timer_config.as_ref() {
+        timer_config.validate()?;
+    }
+
+    // Each authority signs two votes in a round.
+    let votes = authority_list.len() * 2;
+    if config.round_message_cap > 0 && config.round_message_cap < votes {
+        return Err(ConfigError::CapacityTooSmall {
+            name: "round message cap",
+            capacity: config.round_message_cap,
+            min: votes,
+        });
+    }
+    if config.future_buffer_total > 0
+        && config.future_buffer_total < config.future_buffer_per_peer
+    {
+        return Err(ConfigError::CapacityTooSmall {
+            name: "future buffer total",
+            capacity: config.future_buffer_total,
+            min: config.future_buffer_per_peer,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{validate, Launch};
+    use crate::config::IntervalTuning;
+    use crate::error::{AuthorityListError, ConfigError};
+    use crate::test_utils::index_address;
+    use crate::types::Node;
+    use crate::{BackoffConfig, DurationConfig, MlmConfig};
+
+    fn gen_launch(authorities: u8) -> Launch {
+        Launch {
+            interval: Duration::from_secs(1),
+            authority_list: (0..authorities)
+                .map(|index| Node::new(index_address(index)))
+                .collect(),
+            timer_config: None,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = MlmConfig::default();
+        let mut launch = gen_launch(4);
+        assert_eq!(validate(&index_address(9), &config, &launch, false), Ok(()));
+        assert_eq!(
+            validate(&index_address(9), &config, &launch, true),
+            Err(ConfigError::NotAuthority(index_address(9)))
+        );
+        assert_eq!(validate(&index_address(1), &config, &launch, true), Ok(()));
+
+        launch.interval = Duration::from_micros(500);
+        assert_eq!(
+            validate(&index_address(1), &config, &launch, true),
+            Err(ConfigError::ZeroInterval)
+        );
+        launch.interval = Duration::from_secs(2);
+        let mut tuned = config.clone();
+        tuned.set_interval_tuning(IntervalTuning::new(500, 1500, 0));
+        assert_eq!(
+            validate(&index_address(1), &tuned, &launch, true),
+            Err(ConfigError::IntervalOutOfBounds {
+                interval: 2000,
+                min: 500,
+                max: 1500
+            })
+        );
+
+        assert_eq!(
+            validate(&index_address(1), &config, &gen_launch(0), false),
+            Err(ConfigError::AuthorityList(AuthorityListError::Empty))
+        );
+
+        let mut timer_config = DurationConfig::new(24, 10, 0, 3);
+        launch.timer_config = Some(timer_config.clone());
+        assert_eq!(
+            validate(&index_address(1), &config, &launch, true),
+            Err(ConfigError::ZeroTimeoutRatio("precommit"))
+        );
+        // The brake ratio is unused without the chokes.
+        timer_config.precommit_ratio = 5;
+        timer_config.brake_ratio = 0;
+        launch.timer_config = Some(timer_config.clone());
+        assert_eq!(
+            validate(&index_address(1), &config, &launch, true),
+            Err(ConfigError::ZeroTimeoutRatio("brake"))
+        );
+        timer_config.set_choke_free(true);
+        launch.timer_config = Some(timer_config.clone());
+        assert_eq!(validate(&index_address(1), &config, &launch, true), Ok(()));
+        timer_config.backoff = Some(BackoffConfig {
+            base: 20,
+            multiplier: 20,
+            cap: 10,
+        });
+        launch.timer_config = Some(timer_config);
+        assert_eq!(
+            validate(&index_address(1), &config, &launch, true),
+            Err(ConfigError::InvalidBackoff { base: 20, cap: 10 })
+        );
+        launch.timer_config = None;
+
+        let mut capped = config.clone();
+        capped.set_round_caps(1, 6);
+        assert_eq!(
+            validate(&index_address(1), &capped, &launch, true),
+            Err(ConfigError::CapacityTooSmall {
+                name: "round message cap",
+                capacity: 6,
+                min: 8
+            })
+        );
+        let mut buffered = config;
+        buffered.set_future_buffer(16, 8);
+        assert_eq!(
+            validate(&index_address(1), &buffered, &launch, true),
+            Err(ConfigError::CapacityTooSmall {
+                name: "future buffer total",
+                capacity: 8,
+                min: 16
+            })
+        );
+    }
+}
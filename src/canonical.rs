@@ -0,0 +1,226 @@
+//! The canonical byte encodings that the signatures of the consensus messages are made over,
+//! which are the rlp encodings of the `RlpCodec`. An external signer or an implementation in
+//! another language must produce the same bytes, so the encodings are pinned by the golden
+//! vectors of the tests, and a change of them breaks the signatures of every chain.
+//!
+//! An integer is the rlp of its big endian bytes without the leading zeros, and a vote type
+//! is the integer `1` of a prevote or `2` of a precommit.
+//!
+//! - vote: `[height, round, vote_type, block_hash]`
+//! - vote extension: `[vote, extension]`, where the `vote` is the list of the precommit
+//!   above and the `extension` is the payload of the application.
+//! - proposal: `[height, round, block_hash, lock, proposer, content]`, followed by the
+//!   `timestamp` if it is stamped. The `content` is the block encoded by its `Codec`, and
+//!   the `lock` is the empty list `[]` without a lock or `[polc]` with one.
+//! - polc: `[lock_round, [[signature, address_bitmap], vote_type, height, round,
+//!   block_hash, leader]]`
+//! - choke: `[height, round]`
+//! - heartbeat: `[height, round, timestamp, address]`
+//! - attestation: `[height, round, lock_hash, timestamp, address]`, which is signed for
+//!   the external monitors and not sent to the other nodes.
+//! - key rotation: `[epoch, address, payload]`
+//! - VRF input: `[height, round, seed]`, which is the input of the VRF of the leader
+//!   election rather than a signature preimage.
+
+use bytes::Bytes;
+use rlp::RlpStream;
+
+use crate::types::{Attestation, HashChoke, Heartbeat, KeyRotation, Proposal, Vote};
+use crate::wire::{self, RlpCodec};
+use crate::{Codec, ConsensusResult};
+
+/// The preimage of the signature of a proposal, which fails if the block fails to encode.
+pub fn proposal_preimage<T: Codec>(proposal: &Proposal<T>) -> ConsensusResult<Bytes> {
+    wire::proposal_preimage(&RlpCodec, proposal)
+}
+
+/// The preimage of the signature of a vote, which is also the preimage of the aggregated
+/// signature of the votes.
+pub fn vote_preimage(vote: &Vote) -> Bytes {
+    Bytes::from(rlp::encode(vote))
+}
+
+/// The preimage of the signature of the extension of a precommit, which binds the payload to
+/// the vote.
+pub fn vote_extension_preimage(vote: &Vote, extension: &[u8]) -> Bytes {
+    let mut stream = RlpStream::new_list(2);
+    stream.append(vote).append(&extension.to_vec());
+    stream.out().freeze()
+}
+
+/// The preimage of the signature of a choke of the height and the round, which is also the
+/// preimage of the aggregated signature of a timeout certificate.
+pub fn choke_preimage(height: u64, round: u64) -> Bytes {
+    Bytes::from(rlp::encode(&HashChoke { height, round }))
+}
+
+/// The preimage of the signature of a heartbeat.
+pub fn heartbeat_preimage(heartbeat: &Heartbeat) -> Bytes {
+    Bytes::from(rlp::encode(heartbeat))
+}
+
+/// The preimage of the signature of an attestation, which is rlp whatever the wire codec
+/// is since the attestations are verified by the monitors outside of the nodes.
+pub fn attestation_preimage(attestation: &Attestation) -> Bytes {
+    Bytes::from(rlp::encode(attestation))
+}
+
+/// The preimage of the signature of a key rotation.
+pub fn key_rotation_preimage(rotation: &KeyRotation) -> Bytes {
+    Bytes::from(rlp::encode(rotation))
+}
+
+/// The input of the VRF of the leader election of the height and the round.
+pub fn vrf_input(height: u64, round: u64, seed: &[u8]) -> Bytes {
+    let mut stream = RlpStream::new_list(3);
+    stream.append(&height).append(&round).append(&seed.to_vec());
+    stream.out().freeze()
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use hummer::coding::hex_decode;
+
+    use super::*;
+    use crate::types::{
+        Address, AggregatedSignature, AggregatedVote, Hash, PoLC, VoteType,
+    };
+
+    const HASH: &str =
+        "a01111111111111111111111111111111111111111111111111111111111111111";
+    const ADDRESS: &str = "942222222222222222222222222222222222222222";
+
+    /// The vectors are split by the items of the lists, where `8203e8` is the height 1000 and
+    /// `86018bcfe56800` is the timestamp 1700000000000.
+    fn vote_vector() -> String {
+        ["e6", "8203e8", "02", "02", HASH].concat()
+    }
+
+    fn vote_extension_vector() -> String {
+        ["ea", &vote_vector(), "82aabb"].concat()
+    }
+
+    fn proposal_vector() -> String {
+        let timestamp = "86018bcfe56800";
+        ["f845", "8203e8", "02", HASH, "c0", ADDRESS, "82aabb", timestamp].concat()
+    }
+
+    fn locked_proposal_vector() -> String {
+        let signature = "c7843333333381e0";
+        let lock_votes = ["f843", signature, "01", "8203e8", "01", HASH, ADDRESS];
+        let lock = ["f848", "f846", "01", &lock_votes.concat()].concat();
+        ["f887", "8203e8", "02", HASH, &lock, ADDRESS, "82aabb"].concat()
+    }
+
+    const CHOKE: &str = "c48203e802";
+
+    fn heartbeat_vector() -> String {
+        ["e0", "8203e8", "02", "86018bcfe56800", ADDRESS].concat()
+    }
+
+    fn attestation_vector() -> String {
+        ["f841", "8203e8", "02", HASH, "86018bcfe56800", ADDRESS].concat()
+    }
+
+    fn key_rotation_vector() -> String {
+        ["db", "8203e8", ADDRESS, "82aabb"].concat()
+    }
+
+    fn vrf_input_vector() -> String {
+        ["c7", "8203e8", "02", "82aabb"].concat()
+    }
+
+    fn golden(vector: &str) -> Bytes {
+        Bytes::from(hex_decode(vector).unwrap())
+    }
+
+    fn gen_proposal(lock: Option<PoLC>, timestamp: Option<u64>) -> Proposal<Bytes> {
+        Proposal {
+            height: 1000,
+            round: 2,
+            content: Bytes::from(vec![0xaa, 0xbb]),
+            block_hash: Hash::from(vec![0x11; 32]),
+            lock,
+            proposer: Address::from_slice(&[0x22; 20]).unwrap(),
+            timestamp,
+        }
+    }
+
+    /// The preimages must not change, or the signatures of the existing chains fail.
+    #[test]
+    fn test_golden_vectors() {
+        let vote = Vote {
+            height: 1000,
+            round: 2,
+            vote_type: VoteType::Precommit,
+            block_hash: Hash::from(vec![0x11; 32]),
+        };
+        assert_eq!(vote_preimage(&vote), golden(&vote_vector()));
+        assert_eq!(
+            vote_extension_preimage(&vote, &[0xaa, 0xbb]),
+            golden(&vote_extension_vector())
+        );
+
+        let proposal = gen_proposal(None, Some(1_700_000_000_000));
+        assert_eq!(
+            proposal_preimage(&proposal).unwrap(),
+            golden(&proposal_vector())
+        );
+
+        let lock = PoLC {
+            lock_round: 1,
+            lock_votes: AggregatedVote {
+                signature: AggregatedSignature {
+                    signature: Bytes::from(vec![0x33; 4]),
+                    address_bitmap: Bytes::from(vec![0b1110_0000]),
+                },
+                vote_type: VoteType::Prevote,
+                height: 1000,
+                round: 1,
+                block_hash: Hash::from(vec![0x11; 32]),
+                leader: Address::from_slice(&[0x22; 20]).unwrap(),
+                extensions: Vec::new(),
+            },
+        };
+        let proposal = gen_proposal(Some(lock), None);
+        assert_eq!(
+            proposal_preimage(&proposal).unwrap(),
+            golden(&locked_proposal_vector())
+        );
+
+        assert_eq!(choke_preimage(1000, 2), golden(CHOKE));
+
+        let heartbeat = Heartbeat {
+            height: 1000,
+            round: 2,
+            timestamp: 1_700_000_000_000,
+            address: Address::from_slice(&[0x22; 20]).unwrap(),
+        };
+        assert_eq!(heartbeat_preimage(&heartbeat), golden(&heartbeat_vector()));
+
+        let attestation = Attestation {
+            height: 1000,
+            round: 2,
+            lock_hash: Hash::from(vec![0x11; 32]),
+            timestamp: 1_700_000_000_000,
+            address: Address::from_slice(&[0x22; 20]).unwrap(),
+        };
+        assert_eq!(
+            attestation_preimage(&attestation),
+            golden(&attestation_vector())
+        );
+
+        let rotation = KeyRotation {
+            epoch: 1000,
+            address: Address::from_slice(&[0x22; 20]).unwrap(),
+            payload: Bytes::from(vec![0xaa, 0xbb]),
+        };
+        assert_eq!(
+            key_rotation_preimage(&rotation),
+            golden(&key_rotation_vector())
+        );
+
+        assert_eq!(vrf_input(1000, 2, &[0xaa, 0xbb]), golden(&vrf_input_vector()));
+    }
+}
@@ -1,36 +1,77 @@
 use std::convert::TryFrom;
+use std::time::Duration;
 
 use bytes::Bytes;
 use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
 
+use crate::batch::BatchMemberProof;
+use crate::replay::{TraceEntry, TraceRecord};
+use crate::smr::pure::{SmrInput, SmrOutput};
 use crate::smr::smr_types::Step;
 use crate::types::{
-    Address, AggregatedChoke, AggregatedSignature, AggregatedVote, Choke, Commit, Hash,
-    HashChoke, Node, PoLC, Proof, Proposal, Signature, SignedChoke, SignedProposal,
-    SignedVote, Status, UpdateFrom, Vote, VoteType,
+    Address, AggregatedChoke, AggregatedSignature, AggregatedVote, Attestation,
+    BlockPart, BlockResponse, Choke, Commit, CompactProposal, GetBlock, HandoverProof,
+    Hash, HashChoke, Heartbeat, KeyRotation, Node, PartedProposal, PoLC, Proof,
+    Proposal, Signature, SignedChoke, SignedHeartbeat, SignedKeyRotation,
+    SignedProposal, SignedVote, Status, UpdateFrom, Vote, VoteExtension, VoteType,
 };
-use crate::wal::{WalInfo, WalLock};
-use crate::{Codec, DurationConfig};
+use crate::wal::{LastSigned, StepRecord, WalInfo, WalLock};
+use crate::{BackoffConfig, Codec, DurationConfig};
+
+const NANOS_PER_MILLI: u32 = 1_000_000;
+
+/// Decode an address, which rejects the empty and the oversized ones.
+fn decode_address(bytes: Vec<u8>) -> Result<Address, DecoderError> {
+    Address::try_from(bytes).map_err(|_| DecoderError::Custom("Invalid address"))
+}
 
 // impl Encodable and Decodable trait for SignedProposal
 impl<T: Codec> Encodable for SignedProposal<T> {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(2)
-            .append(&self.signature.to_vec())
-            .append(&self.proposal);
+        // The timeout certificate and the VRF proof are appended only if they exist, so the
+        // proposals without them keep the format of the nodes that do not know them.
+        if !self.vrf_proof.is_empty() {
+            s.begin_list(4)
+                .append(&self.signature.to_vec())
+                .append(&self.proposal)
+                .append(&self.timeout_cert)
+                .append(&self.vrf_proof.to_vec());
+            return;
+        }
+        match &self.timeout_cert {
+            Some(tc) => s
+                .begin_list(3)
+                .append(&self.signature.to_vec())
+                .append(&self.proposal)
+                .append(tc),
+            None => s
+                .begin_list(2)
+                .append(&self.signature.to_vec())
+                .append(&self.proposal),
+        };
     }
 }
 
 impl<T: Codec> Decodable for SignedProposal<T> {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(2) => {
+            Prototype::List(len @ 2..=4) => {
                 let tmp: Vec<u8> = r.val_at(0)?;
                 let signature = Signature::from(tmp);
                 let proposal: Proposal<T> = r.val_at(1)?;
+                let (timeout_cert, vrf_proof) = match len {
+                    4 => {
+                        let tmp: Vec<u8> = r.val_at(3)?;
+                        (r.val_at(2)?, Bytes::from(tmp))
+                    }
+                    3 => (Some(r.val_at(2)?), Bytes::new()),
+                    _ => (None, Bytes::new()),
+                };
                 Ok(SignedProposal {
                     signature,
                     proposal,
+                    timeout_cert,
+                    vrf_proof,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -39,33 +80,38 @@ impl<T: Codec> Decodable for SignedProposal<T> {
 }
 
 // impl Encodable and Decodable trait for Proposal
+// The timestamp is appended only if it exists, so the proposals without it keep the format
+// of the nodes that do not know it.
 impl<T: Codec> Encodable for Proposal<T> {
     fn rlp_append(&self, s: &mut RlpStream) {
         let content = self.content.encode().unwrap().to_vec();
-        s.begin_list(6)
+        s.begin_list(if self.timestamp.is_some() { 7 } else { 6 })
             .append(&self.height)
             .append(&self.round)
             .append(&self.block_hash.to_vec())
             .append(&self.lock)
             .append(&self.proposer.to_vec())
             .append(&content);
+        if let Some(timestamp) = self.timestamp {
+            s.append(&timestamp);
+        }
     }
 }
 
 impl<T: Codec> Decodable for Proposal<T> {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(6) => {
+            Prototype::List(len @ 6..=7) => {
                 let height: u64 = r.val_at(0)?;
                 let round: u64 = r.val_at(1)?;
                 let tmp: Vec<u8> = r.val_at(2)?;
                 let block_hash = Hash::from(tmp);
                 let lock = r.val_at(3)?;
                 let tmp: Vec<u8> = r.val_at(4)?;
-                let proposer = Address::from(tmp);
-                let tmp: Vec<u8> = r.val_at(5)?;
-                let content = Codec::decode(Bytes::from(tmp))
+                let proposer = decode_address(tmp)?;
+                let content = T::decode_ref(r.at(5)?.data()?)
                     .map_err(|_| DecoderError::Custom("Codec decode error."))?;
+                let timestamp = if len == 7 { Some(r.val_at(6)?) } else { None };
                 Ok(Proposal {
                     height,
                     round,
@@ -73,6 +119,246 @@ impl<T: Codec> Decodable for Proposal<T> {
                     block_hash,
                     lock,
                     proposer,
+                    timestamp,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for CompactProposal
+// A compact proposal with a timestamp carries the optional timeout certificate before it, and
+// one with a VRF proof carries both of them as optional items before the proof.
+impl Encodable for CompactProposal {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let len = match (&self.timeout_cert, self.timestamp) {
+            _ if !self.vrf_proof.is_empty() => 9,
+            (_, Some(_)) => 8,
+            (Some(_), None) => 7,
+            (None, None) => 6,
+        };
+        s.begin_list(len)
+            .append(&self.signature.to_vec())
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.block_hash.to_vec())
+            .append(&self.lock)
+            .append(&self.proposer.to_vec());
+        if len == 9 {
+            s.append(&self.timeout_cert)
+                .append(&self.timestamp)
+                .append(&self.vrf_proof.to_vec());
+            return;
+        }
+        match (&self.timeout_cert, self.timestamp) {
+            (tc, Some(timestamp)) => s.append(tc).append(&timestamp),
+            (Some(tc), None) => s.append(tc),
+            (None, None) => s,
+        };
+    }
+}
+
+impl Decodable for CompactProposal {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(len @ 6..=9) => {
+                let tmp: Vec<u8> = r.val_at(0)?;
+                let signature = Signature::from(tmp);
+                let height: u64 = r.val_at(1)?;
+                let round: u64 = r.val_at(2)?;
+                let tmp: Vec<u8> = r.val_at(3)?;
+                let block_hash = Hash::from(tmp);
+                let lock = r.val_at(4)?;
+                let tmp: Vec<u8> = r.val_at(5)?;
+                let proposer = decode_address(tmp)?;
+                let (timeout_cert, timestamp) = match len {
+                    9 => (r.val_at(6)?, r.val_at(7)?),
+                    8 => (r.val_at(6)?, Some(r.val_at(7)?)),
+                    7 => (Some(r.val_at(6)?), None),
+                    _ => (None, None),
+                };
+                let vrf_proof = if len == 9 {
+                    let tmp: Vec<u8> = r.val_at(8)?;
+                    Bytes::from(tmp)
+                } else {
+                    Bytes::new()
+                };
+                Ok(CompactProposal {
+                    signature,
+                    height,
+                    round,
+                    block_hash,
+                    lock,
+                    proposer,
+                    timeout_cert,
+                    timestamp,
+                    vrf_proof,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for GetBlock
+impl Encodable for GetBlock {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4)
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.block_hash.to_vec())
+            .append(&self.requester.to_vec());
+    }
+}
+
+impl Decodable for GetBlock {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(4) => {
+                let height: u64 = r.val_at(0)?;
+                let round: u64 = r.val_at(1)?;
+                let tmp: Vec<u8> = r.val_at(2)?;
+                let block_hash = Hash::from(tmp);
+                let tmp: Vec<u8> = r.val_at(3)?;
+                let requester = decode_address(tmp)?;
+                Ok(GetBlock {
+                    height,
+                    round,
+                    block_hash,
+                    requester,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for BlockResponse
+impl Encodable for BlockResponse {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4)
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.block_hash.to_vec())
+            .append(&self.content.to_vec());
+    }
+}
+
+impl Decodable for BlockResponse {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(4) => {
+                let height: u64 = r.val_at(0)?;
+                let round: u64 = r.val_at(1)?;
+                let tmp: Vec<u8> = r.val_at(2)?;
+                let block_hash = Hash::from(tmp);
+                let tmp: Vec<u8> = r.val_at(3)?;
+                let content = Bytes::from(tmp);
+                Ok(BlockResponse {
+                    height,
+                    round,
+                    block_hash,
+                    content,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for PartedProposal
+impl Encodable for PartedProposal {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3)
+            .append(&self.proposal)
+            .append(&self.total)
+            .append(&self.root.to_vec());
+    }
+}
+
+impl Decodable for PartedProposal {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(3) => {
+                let proposal: CompactProposal = r.val_at(0)?;
+                let total: u64 = r.val_at(1)?;
+                let tmp: Vec<u8> = r.val_at(2)?;
+                let root = Hash::from(tmp);
+                Ok(PartedProposal {
+                    proposal,
+                    total,
+                    root,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for BlockPart
+impl Encodable for BlockPart {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(5)
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.root.to_vec())
+            .append(&self.content.to_vec())
+            .append(&self.proof);
+    }
+}
+
+impl Decodable for BlockPart {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(5) => {
+                let height: u64 = r.val_at(0)?;
+                let round: u64 = r.val_at(1)?;
+                let tmp: Vec<u8> = r.val_at(2)?;
+                let root = Hash::from(tmp);
+                let tmp: Vec<u8> = r.val_at(3)?;
+                let content = Bytes::from(tmp);
+                let proof: BatchMemberProof = r.val_at(4)?;
+                Ok(BlockPart {
+                    height,
+                    round,
+                    root,
+                    content,
+                    proof,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for BatchMemberProof
+impl Encodable for BatchMemberProof {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let siblings = self
+            .siblings
+            .iter()
+            .map(|sibling| sibling.to_vec())
+            .collect::<Vec<_>>();
+        s.begin_list(3)
+            .append(&(self.index as u64))
+            .append(&(self.len as u64))
+            .append_list::<Vec<u8>, _>(&siblings);
+    }
+}
+
+impl Decodable for BatchMemberProof {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(3) => {
+                let index: u64 = r.val_at(0)?;
+                let len: u64 = r.val_at(1)?;
+                let tmp: Vec<Vec<u8>> = r.list_at(2)?;
+                let siblings = tmp.into_iter().map(Hash::from).collect();
+                Ok(BatchMemberProof {
+                    index: index as usize,
+                    len: len as usize,
+                    siblings,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -133,23 +419,28 @@ impl Decodable for AggregatedSignature {
 }
 
 // impl Encodable and Decodable trait for AggregatedVote
+// The vote extensions are appended only if there are any, so a QC without them keeps the
+// original encoding.
 impl Encodable for AggregatedVote {
     fn rlp_append(&self, s: &mut RlpStream) {
         let vote_type: u8 = self.vote_type.clone().into();
-        s.begin_list(6)
+        s.begin_list(if self.extensions.is_empty() { 6 } else { 7 })
             .append(&self.signature)
             .append(&vote_type)
             .append(&self.height)
             .append(&self.round)
             .append(&self.block_hash.to_vec())
             .append(&self.leader.to_vec());
+        if !self.extensions.is_empty() {
+            s.append_list(&self.extensions);
+        }
     }
 }
 
 impl Decodable for AggregatedVote {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(6) => {
+            Prototype::List(len @ 6..=7) => {
                 let signature: AggregatedSignature = r.val_at(0)?;
                 let tmp: u8 = r.val_at(1)?;
                 let vote_type = VoteType::try_from(tmp)
@@ -159,7 +450,14 @@ impl Decodable for AggregatedVote {
                 let tmp: Vec<u8> = r.val_at(4)?;
                 let block_hash = Hash::from(tmp);
                 let tmp: Vec<u8> = r.val_at(5)?;
-                let leader = Address::from(tmp);
+                // The initial QC has no leader, and it is kept in the WAL before the first
+                // commit.
+                let leader = if tmp.is_empty() {
+                    Address::default()
+                } else {
+                    decode_address(tmp)?
+                };
+                let extensions = if len == 7 { r.list_at(6)? } else { Vec::new() };
                 Ok(AggregatedVote {
                     signature,
                     vote_type,
@@ -167,6 +465,7 @@ impl Decodable for AggregatedVote {
                     round,
                     block_hash,
                     leader,
+                    extensions,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -175,28 +474,73 @@ impl Decodable for AggregatedVote {
 }
 
 // impl Encodable and Decodable trait for SignedVote
+// The extension and its signature are appended only if the vote has an extension.
 impl Encodable for SignedVote {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(3)
+        s.begin_list(if self.extension.is_empty() { 3 } else { 5 })
             .append(&self.signature.to_vec())
             .append(&self.vote)
             .append(&self.voter.to_vec());
+        if !self.extension.is_empty() {
+            s.append(&self.extension.to_vec())
+                .append(&self.extension_signature.to_vec());
+        }
     }
 }
 
 impl Decodable for SignedVote {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(3) => {
+            Prototype::List(len @ (3 | 5)) => {
                 let tmp: Vec<u8> = r.val_at(0)?;
                 let signature = Signature::from(tmp);
                 let vote = r.val_at(1)?;
                 let tmp: Vec<u8> = r.val_at(2)?;
-                let voter = Address::from(tmp);
+                let voter = decode_address(tmp)?;
+                let (extension, extension_signature) = if len == 5 {
+                    let extension: Vec<u8> = r.val_at(3)?;
+                    let signature: Vec<u8> = r.val_at(4)?;
+                    (Bytes::from(extension), Signature::from(signature))
+                } else {
+                    (Bytes::new(), Signature::new())
+                };
                 Ok(SignedVote {
                     signature,
                     vote,
                     voter,
+                    extension,
+                    extension_signature,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for VoteExtension
+impl Encodable for VoteExtension {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3)
+            .append(&self.voter.to_vec())
+            .append(&self.payload.to_vec())
+            .append(&self.signature.to_vec());
+    }
+}
+
+impl Decodable for VoteExtension {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(3) => {
+                let tmp: Vec<u8> = r.val_at(0)?;
+                let voter = decode_address(tmp)?;
+                let tmp: Vec<u8> = r.val_at(1)?;
+                let payload = Bytes::from(tmp);
+                let tmp: Vec<u8> = r.val_at(2)?;
+                let signature = Signature::from(tmp);
+                Ok(VoteExtension {
+                    voter,
+                    payload,
+                    signature,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -240,29 +584,48 @@ impl Decodable for Vote {
 }
 
 // impl Encodable and Decodable trait for Commit
+// The round and the timestamps are appended after the original items, a commit of the three
+// original items is decided in the round of its proof.
 impl<T: Codec> Encodable for Commit<T> {
     fn rlp_append(&self, s: &mut RlpStream) {
         let content = self.content.encode().unwrap().to_vec();
-        s.begin_list(3)
+        s.begin_list(if self.timestamp.is_some() { 8 } else { 7 })
             .append(&self.height)
             .append(&self.proof)
-            .append(&content);
+            .append(&content)
+            .append(&self.round)
+            .append(&self.height_start)
+            .append(&self.round_start)
+            .append(&self.committed_at);
+        if let Some(timestamp) = self.timestamp {
+            s.append(&timestamp);
+        }
     }
 }
 
 impl<T: Codec> Decodable for Commit<T> {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(3) => {
+            Prototype::List(len @ (3 | 7 | 8)) => {
                 let height: u64 = r.val_at(0)?;
                 let proof: Proof = r.val_at(1)?;
-                let tmp: Vec<u8> = r.val_at(2)?;
-                let content = Codec::decode(Bytes::from(tmp))
+                let content = T::decode_ref(r.at(2)?.data()?)
                     .map_err(|_| DecoderError::Custom("Codec decode error."))?;
+                let (round, height_start, round_start, committed_at) = if len >= 7 {
+                    (r.val_at(3)?, r.val_at(4)?, r.val_at(5)?, r.val_at(6)?)
+                } else {
+                    (proof.round, 0, 0, 0)
+                };
+                let timestamp = if len == 8 { Some(r.val_at(7)?) } else { None };
                 Ok(Commit {
                     height,
                     proof,
                     content,
+                    round,
+                    height_start,
+                    round_start,
+                    committed_at,
+                    timestamp,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -271,30 +634,37 @@ impl<T: Codec> Decodable for Commit<T> {
 }
 
 // impl Encodable and Decodable trait for Proof
+// A proof with the vote extensions appends the extensions, others keep the original encoding.
 impl Encodable for Proof {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(4)
+        let len = if self.extensions.is_empty() { 4 } else { 5 };
+        s.begin_list(len)
             .append(&self.height)
             .append(&self.round)
             .append(&self.block_hash.to_vec())
             .append(&self.signature);
+        if len == 5 {
+            s.append_list(&self.extensions);
+        }
     }
 }
 
 impl Decodable for Proof {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(4) => {
+            Prototype::List(len @ 4..=5) => {
                 let height: u64 = r.val_at(0)?;
                 let round: u64 = r.val_at(1)?;
                 let tmp: Vec<u8> = r.val_at(2)?;
                 let block_hash = Hash::from(tmp);
                 let signature: AggregatedSignature = r.val_at(3)?;
+                let extensions = if len == 5 { r.list_at(4)? } else { Vec::new() };
                 Ok(Proof {
                     height,
                     round,
                     block_hash,
                     signature,
+                    extensions,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -304,27 +674,82 @@ impl Decodable for Proof {
 
 impl Encodable for DurationConfig {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(4)
+        // The backoff policy is appended only when it is set, and the choke free flag only
+        // when it is true, so that the encoding of a config without them is compatible with
+        // the former version.
+        let len = if self.backoff.is_some() { 7 } else { 4 };
+        let len = if self.choke_free { len + 1 } else { len };
+        s.begin_list(len)
             .append(&self.propose_ratio)
             .append(&self.prevote_ratio)
             .append(&self.precommit_ratio)
             .append(&self.brake_ratio);
+
+        if let Some(backoff) = self.backoff.as_ref() {
+            s.append(&backoff.base)
+                .append(&backoff.multiplier)
+                .append(&backoff.cap);
+        }
+        if self.choke_free {
+            s.append(&true);
+        }
     }
 }
 
 impl Decodable for DurationConfig {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(4) => {
+            Prototype::List(len @ 4..=5) | Prototype::List(len @ 7..=8) => {
                 let propose_ratio: u64 = r.val_at(0)?;
                 let prevote_ratio: u64 = r.val_at(1)?;
                 let precommit_ratio: u64 = r.val_at(2)?;
                 let brake_ratio: u64 = r.val_at(3)?;
+                let backoff = if len >= 7 {
+                    Some(BackoffConfig {
+                        base: r.val_at(4)?,
+                        multiplier: r.val_at(5)?,
+                        cap: r.val_at(6)?,
+                    })
+                } else {
+                    None
+                };
+                let choke_free: bool = if len == 5 || len == 8 {
+                    r.val_at(len - 1)?
+                } else {
+                    false
+                };
                 Ok(DurationConfig {
                     propose_ratio,
                     prevote_ratio,
                     precommit_ratio,
                     brake_ratio,
+                    backoff,
+                    choke_free,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for HandoverProof
+impl Encodable for HandoverProof {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2)
+            .append(&self.proof)
+            .append_list(&self.next_authority_list);
+    }
+}
+
+impl Decodable for HandoverProof {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let proof: Proof = r.val_at(0)?;
+                let next_authority_list: Vec<Node> = r.list_at(1)?;
+                Ok(HandoverProof {
+                    proof,
+                    next_authority_list,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -332,35 +757,99 @@ impl Decodable for DurationConfig {
     }
 }
 
-// impl Encodable and Decodable trait for Status
+// impl Encodable and Decodable trait for Status. The interval is encoded in milliseconds as
+// before, and the sub-millisecond nanoseconds of it, if any, are appended as the fifth item.
+// The authority list of the next epoch, if any, is appended as the sixth item after the
+// sub-millisecond nanoseconds. The proposer seed, if any, is appended as the seventh item,
+// and then the authority list of the next epoch is encoded as an optional one. The skipped
+// proposers, if any, are appended as the eighth item, and then the proposer seed is encoded
+// as an optional one too.
 impl Encodable for Status {
     fn rlp_append(&self, s: &mut RlpStream) {
-        let interval = if self.interval.is_none() {
-            0u64
-        } else {
-            self.interval.unwrap()
-        };
+        let interval = self.interval.unwrap_or_default();
+        let millis = interval.as_millis() as u64;
+        let sub_millis = interval.subsec_nanos() % NANOS_PER_MILLI;
 
         let config = if self.timer_config.is_none() {
             DurationConfig::default()
         } else {
             self.timer_config.clone().unwrap()
         };
-        s.begin_list(4)
+        let len = if !self.skipped_proposers.is_empty() {
+            8
+        } else if self.proposer_seed.is_some() {
+            7
+        } else if self.next_authority_list.is_some() {
+            6
+        } else if sub_millis != 0 {
+            5
+        } else {
+            4
+        };
+        s.begin_list(len)
             .append(&self.height)
-            .append(&interval)
+            .append(&millis)
             .append(&config)
             .append_list(&self.authority_list);
+        if len > 4 {
+            s.append(&sub_millis);
+        }
+        if len == 8 {
+            match self.next_authority_list.as_ref() {
+                Some(next_authority_list) => {
+                    s.begin_list(1).append_list(next_authority_list);
+                }
+                None => {
+                    s.begin_list(0);
+                }
+            }
+            match self.proposer_seed {
+                Some(proposer_seed) => {
+                    s.begin_list(1).append(&proposer_seed);
+                }
+                None => {
+                    s.begin_list(0);
+                }
+            }
+            let skipped = self
+                .skipped_proposers
+                .iter()
+                .map(|address| address.to_vec())
+                .collect::<Vec<_>>();
+            s.append_list::<Vec<u8>, _>(&skipped);
+        } else if let Some(proposer_seed) = self.proposer_seed {
+            match self.next_authority_list.as_ref() {
+                Some(next_authority_list) => {
+                    s.begin_list(1).append_list(next_authority_list);
+                }
+                None => {
+                    s.begin_list(0);
+                }
+            }
+            s.append(&proposer_seed);
+        } else if let Some(next_authority_list) = self.next_authority_list.as_ref() {
+            s.append_list(next_authority_list);
+        }
     }
 }
 
 impl Decodable for Status {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(4) => {
+            Prototype::List(len @ 4..=8) => {
                 let height: u64 = r.val_at(0)?;
-                let tmp: u64 = r.val_at(1)?;
-                let interval = if tmp == 0 { None } else { Some(tmp) };
+                let millis: u64 = r.val_at(1)?;
+                let sub_millis: u32 = if len > 4 { r.val_at(4)? } else { 0 };
+                if sub_millis >= NANOS_PER_MILLI {
+                    return Err(DecoderError::Custom("Invalid sub-millisecond nanos."));
+                }
+                let interval = Duration::from_millis(millis)
+                    + Duration::from_nanos(u64::from(sub_millis));
+                let interval = if interval.is_zero() {
+                    None
+                } else {
+                    Some(interval)
+                };
                 let tmp: DurationConfig = r.val_at(2)?;
                 let timer_config = if tmp == DurationConfig::default() {
                     None
@@ -368,12 +857,41 @@ impl Decodable for Status {
                     Some(tmp)
                 };
                 let authority_list: Vec<Node> = r.list_at(3)?;
+                let next_authority_list = match len {
+                    6 => Some(r.list_at(5)?),
+                    7 | 8 => match r.at(5)?.item_count()? {
+                        0 => None,
+                        1 => Some(r.at(5)?.list_at(0)?),
+                        _ => return Err(DecoderError::RlpIncorrectListLen),
+                    },
+                    _ => None,
+                };
+                let proposer_seed = match len {
+                    7 => Some(r.val_at(6)?),
+                    8 => match r.at(6)?.item_count()? {
+                        0 => None,
+                        1 => Some(r.at(6)?.val_at(0)?),
+                        _ => return Err(DecoderError::RlpIncorrectListLen),
+                    },
+                    _ => None,
+                };
+                let skipped_proposers = if len == 8 {
+                    r.list_at::<Vec<u8>>(7)?
+                        .into_iter()
+                        .map(decode_address)
+                        .collect::<Result<Vec<_>, _>>()?
+                } else {
+                    Vec::new()
+                };
 
                 Ok(Status {
                     height,
                     interval,
                     timer_config,
                     authority_list,
+                    next_authority_list,
+                    proposer_seed,
+                    skipped_proposers,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -396,9 +914,9 @@ impl Decodable for Node {
         match r.prototype()? {
             Prototype::List(3) => {
                 let tmp: Vec<u8> = r.val_at(0)?;
-                let address = Address::from(tmp);
+                let address = decode_address(tmp)?;
                 let propose_weight: u32 = r.val_at(1)?;
-                let vote_weight: u32 = r.val_at(2)?;
+                let vote_weight: u128 = r.val_at(2)?;
                 Ok(Node {
                     address,
                     propose_weight,
@@ -445,7 +963,7 @@ impl Decodable for UpdateFrom {
                         let qc: AggregatedChoke = r.val_at(1)?;
                         UpdateFrom::ChokeQC(qc)
                     }
-                    _ => unreachable!(),
+                    _ => return Err(DecoderError::Custom("Invalid update from")),
                 };
                 Ok(res)
             }
@@ -470,8 +988,7 @@ impl<T: Codec> Decodable for WalLock<T> {
             Prototype::List(3) => {
                 let lock_round: u64 = r.val_at(0)?;
                 let lock_votes: AggregatedVote = r.val_at(1)?;
-                let tmp: Vec<u8> = r.val_at(2)?;
-                let content = Codec::decode(Bytes::from(tmp))
+                let content = T::decode_ref(r.at(2)?.data()?)
                     .map_err(|_| DecoderError::Custom("Codec decode error."))?;
                 Ok(WalLock {
                     lock_round,
@@ -486,31 +1003,44 @@ impl<T: Codec> Decodable for WalLock<T> {
 
 impl<T: Codec> Encodable for WalInfo<T> {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(5)
+        // The pending commit is only appended when there is one, so that the other records keep
+        // the encoding of the previous versions.
+        let len = if self.pending_commit.is_some() { 7 } else { 6 };
+        s.begin_list(len)
             .append(&self.height)
             .append(&self.round)
             .append::<u8>(&self.step.clone().into())
             .append(&self.lock)
-            .append(&self.from);
+            .append(&self.from)
+            .append(&self.status);
+        if self.pending_commit.is_some() {
+            s.append(&self.pending_commit);
+        }
     }
 }
 
 impl<T: Codec> Decodable for WalInfo<T> {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(5) => {
+            // The wal info without a status is saved before the status is persisted.
+            Prototype::List(len @ 5..=7) => {
                 let height: u64 = r.val_at(0)?;
                 let round: u64 = r.val_at(1)?;
                 let tmp: u8 = r.val_at(2)?;
-                let step = Step::from(tmp);
+                let step = Step::try_from(tmp)
+                    .map_err(|_| DecoderError::Custom("Invalid step"))?;
                 let lock = r.val_at(3)?;
                 let from: UpdateFrom = r.val_at(4)?;
+                let status = if len >= 6 { r.val_at(5)? } else { None };
+                let pending_commit = if len == 7 { r.val_at(6)? } else { None };
                 Ok(WalInfo {
                     height,
                     round,
                     step,
                     lock,
                     from,
+                    status,
+                    pending_commit,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -518,12 +1048,138 @@ impl<T: Codec> Decodable for WalInfo<T> {
     }
 }
 
-impl Encodable for Choke {
+impl Encodable for LastSigned {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(3)
+        s.begin_list(4)
             .append(&self.height)
             .append(&self.round)
-            .append(&self.from);
+            .append::<u8>(&self.step.clone().into())
+            .append(&self.hash.to_vec());
+    }
+}
+
+impl Decodable for LastSigned {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(4) => {
+                let height: u64 = r.val_at(0)?;
+                let round: u64 = r.val_at(1)?;
+                let tmp: u8 = r.val_at(2)?;
+                let step = Step::try_from(tmp)
+                    .map_err(|_| DecoderError::Custom("Invalid step"))?;
+                let tmp: Vec<u8> = r.val_at(3)?;
+                let hash = Hash::from(tmp);
+                Ok(LastSigned {
+                    height,
+                    round,
+                    step,
+                    hash,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+impl Encodable for StepRecord {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            StepRecord::NewRound { height, round } => {
+                s.begin_list(3).append(&0u8).append(height).append(round);
+            }
+            StepRecord::Locked {
+                height,
+                round,
+                block_hash,
+            } => {
+                s.begin_list(4)
+                    .append(&1u8)
+                    .append(height)
+                    .append(round)
+                    .append(&block_hash.to_vec());
+            }
+            StepRecord::Voted {
+                height,
+                round,
+                vote_type,
+                block_hash,
+            } => {
+                s.begin_list(5)
+                    .append(&2u8)
+                    .append(height)
+                    .append(round)
+                    .append::<u8>(&vote_type.clone().into())
+                    .append(&block_hash.to_vec());
+            }
+            StepRecord::Committed {
+                height,
+                round,
+                block_hash,
+            } => {
+                s.begin_list(4)
+                    .append(&3u8)
+                    .append(height)
+                    .append(round)
+                    .append(&block_hash.to_vec());
+            }
+            StepRecord::Checkpoint { height } => {
+                s.begin_list(2).append(&4u8).append(height);
+            }
+        }
+    }
+}
+
+impl Decodable for StepRecord {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        let tag: u8 = r.val_at(0)?;
+        let record = match (tag, r.item_count()?) {
+            (0, 3) => {
+                let (height, round) = height_round(r)?;
+                StepRecord::NewRound { height, round }
+            }
+            (1, 4) => {
+                let (height, round) = height_round(r)?;
+                StepRecord::Locked {
+                    height,
+                    round,
+                    block_hash: hash_at(r, 3)?,
+                }
+            }
+            (2, 5) => {
+                let (height, round) = height_round(r)?;
+                let tmp: u8 = r.val_at(3)?;
+                let vote_type = VoteType::try_from(tmp)
+                    .map_err(|_| DecoderError::Custom("Invalid vote type"))?;
+                StepRecord::Voted {
+                    height,
+                    round,
+                    vote_type,
+                    block_hash: hash_at(r, 4)?,
+                }
+            }
+            (3, 4) => {
+                let (height, round) = height_round(r)?;
+                StepRecord::Committed {
+                    height,
+                    round,
+                    block_hash: hash_at(r, 3)?,
+                }
+            }
+            (4, 2) => StepRecord::Checkpoint {
+                height: r.val_at(1)?,
+            },
+            _ => return Err(DecoderError::Custom("Invalid step record")),
+        };
+        Ok(record)
+    }
+}
+
+impl Encodable for Choke {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3)
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.from);
     }
 }
 
@@ -562,7 +1218,7 @@ impl Decodable for SignedChoke {
                 let signature = Signature::from(tmp);
                 let choke: Choke = r.val_at(1)?;
                 let tmp: Vec<u8> = r.val_at(2)?;
-                let address = Address::from(tmp);
+                let address = decode_address(tmp)?;
                 Ok(SignedChoke {
                     signature,
                     choke,
@@ -598,7 +1254,10 @@ impl Decodable for AggregatedChoke {
                 let tmp: Vec<u8> = r.val_at(2)?;
                 let signature = Signature::from(tmp);
                 let tmp: Vec<Vec<u8>> = r.list_at(3)?;
-                let voters = tmp.into_iter().map(Address::from).collect::<Vec<_>>();
+                let voters = tmp
+                    .into_iter()
+                    .map(decode_address)
+                    .collect::<Result<Vec<_>, _>>()?;
                 Ok(AggregatedChoke {
                     height,
                     round,
@@ -617,15 +1276,472 @@ impl Encodable for HashChoke {
     }
 }
 
+impl Encodable for Heartbeat {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4)
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.timestamp)
+            .append(&self.address.to_vec());
+    }
+}
+
+impl Decodable for Heartbeat {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(4) => {
+                let (height, round) = (r.val_at(0)?, r.val_at(1)?);
+                let timestamp: u64 = r.val_at(2)?;
+                let tmp: Vec<u8> = r.val_at(3)?;
+                Ok(Heartbeat {
+                    height,
+                    round,
+                    timestamp,
+                    address: decode_address(tmp)?,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+impl Encodable for SignedHeartbeat {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2)
+            .append(&self.signature.to_vec())
+            .append(&self.heartbeat);
+    }
+}
+
+impl Decodable for SignedHeartbeat {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let tmp: Vec<u8> = r.val_at(0)?;
+                Ok(SignedHeartbeat {
+                    signature: Signature::from(tmp),
+                    heartbeat: r.val_at(1)?,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+impl Encodable for Attestation {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(5)
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.lock_hash.to_vec())
+            .append(&self.timestamp)
+            .append(&self.address.to_vec());
+    }
+}
+
+impl Decodable for Attestation {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(5) => {
+                let (height, round) = (r.val_at(0)?, r.val_at(1)?);
+                let timestamp: u64 = r.val_at(3)?;
+                let tmp: Vec<u8> = r.val_at(4)?;
+                Ok(Attestation {
+                    height,
+                    round,
+                    lock_hash: hash_at(r, 2)?,
+                    timestamp,
+                    address: decode_address(tmp)?,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+impl Encodable for KeyRotation {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3)
+            .append(&self.epoch)
+            .append(&self.address.to_vec())
+            .append(&self.payload.to_vec());
+    }
+}
+
+impl Decodable for KeyRotation {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(3) => {
+                let epoch: u64 = r.val_at(0)?;
+                let address: Vec<u8> = r.val_at(1)?;
+                let payload: Vec<u8> = r.val_at(2)?;
+                Ok(KeyRotation {
+                    epoch,
+                    address: decode_address(address)?,
+                    payload: Bytes::from(payload),
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+impl Encodable for SignedKeyRotation {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2)
+            .append(&self.signature.to_vec())
+            .append(&self.rotation);
+    }
+}
+
+impl Decodable for SignedKeyRotation {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let tmp: Vec<u8> = r.val_at(0)?;
+                Ok(SignedKeyRotation {
+                    signature: Signature::from(tmp),
+                    rotation: r.val_at(1)?,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// The optional durations of the trace records are encoded in nanoseconds.
+fn duration_nanos(duration: &Option<Duration>) -> Option<u64> {
+    duration.map(|d| d.as_nanos() as u64)
+}
+
+fn hash_at(r: &Rlp, index: usize) -> Result<Hash, DecoderError> {
+    let tmp: Vec<u8> = r.val_at(index)?;
+    Ok(Hash::from(tmp))
+}
+
+fn height_round(r: &Rlp) -> Result<(u64, u64), DecoderError> {
+    Ok((r.val_at(1)?, r.val_at(2)?))
+}
+
+impl Encodable for SmrInput {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            SmrInput::NewHeight {
+                height,
+                interval,
+                config,
+            } => {
+                s.begin_list(4)
+                    .append(&0u8)
+                    .append(height)
+                    .append(&duration_nanos(interval))
+                    .append(config);
+            }
+            SmrInput::Proposal {
+                height,
+                round,
+                hash,
+                lock_round,
+            } => {
+                s.begin_list(5)
+                    .append(&1u8)
+                    .append(height)
+                    .append(round)
+                    .append(&hash.to_vec())
+                    .append(lock_round);
+            }
+            SmrInput::PrevoteQC {
+                height,
+                round,
+                hash,
+            } => {
+                s.begin_list(4)
+                    .append(&2u8)
+                    .append(height)
+                    .append(round)
+                    .append(&hash.to_vec());
+            }
+            SmrInput::PrecommitQC {
+                height,
+                round,
+                hash,
+            } => {
+                s.begin_list(4)
+                    .append(&3u8)
+                    .append(height)
+                    .append(round)
+                    .append(&hash.to_vec());
+            }
+            SmrInput::ProposeTimeout { height, round } => {
+                s.begin_list(3).append(&4u8).append(height).append(round);
+            }
+            SmrInput::PrevoteTimeout { height, round } => {
+                s.begin_list(3).append(&5u8).append(height).append(round);
+            }
+            SmrInput::PrecommitTimeout { height, round } => {
+                s.begin_list(3).append(&6u8).append(height).append(round);
+            }
+            SmrInput::BrakeTimeout { height, round } => {
+                s.begin_list(3).append(&7u8).append(height).append(round);
+            }
+            SmrInput::ContinueRound { height, round } => {
+                s.begin_list(3).append(&8u8).append(height).append(round);
+            }
+            SmrInput::UpdateConfig(config) => {
+                s.begin_list(2).append(&9u8).append(config);
+            }
+            SmrInput::Stop => {
+                s.begin_list(1).append(&10u8);
+            }
+            SmrInput::Wal {
+                height,
+                round,
+                step,
+                lock,
+            } => {
+                s.begin_list(6)
+                    .append(&11u8)
+                    .append(height)
+                    .append(round)
+                    .append::<u8>(&step.clone().into())
+                    .append(&lock.as_ref().map(|(round, _)| *round))
+                    .append(&lock.as_ref().map(|(_, hash)| hash.to_vec()));
+            }
+        }
+    }
+}
+
+impl Decodable for SmrInput {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        let tag: u8 = r.val_at(0)?;
+        let input = match (tag, r.item_count()?) {
+            (0, 4) => {
+                let interval: Option<u64> = r.val_at(2)?;
+                SmrInput::NewHeight {
+                    height: r.val_at(1)?,
+                    interval: interval.map(Duration::from_nanos),
+                    config: r.val_at(3)?,
+                }
+            }
+            (1, 5) => {
+                let (height, round) = height_round(r)?;
+                SmrInput::Proposal {
+                    height,
+                    round,
+                    hash: hash_at(r, 3)?,
+                    lock_round: r.val_at(4)?,
+                }
+            }
+            (2, 4) => {
+                let (height, round) = height_round(r)?;
+                SmrInput::PrevoteQC {
+                    height,
+                    round,
+                    hash: hash_at(r, 3)?,
+                }
+            }
+            (3, 4) => {
+                let (height, round) = height_round(r)?;
+                SmrInput::PrecommitQC {
+                    height,
+                    round,
+                    hash: hash_at(r, 3)?,
+                }
+            }
+            (4..=8, 3) => {
+                let (height, round) = height_round(r)?;
+                match tag {
+                    4 => SmrInput::ProposeTimeout { height, round },
+                    5 => SmrInput::PrevoteTimeout { height, round },
+                    6 => SmrInput::PrecommitTimeout { height, round },
+                    7 => SmrInput::BrakeTimeout { height, round },
+                    _ => SmrInput::ContinueRound { height, round },
+                }
+            }
+            (9, 2) => SmrInput::UpdateConfig(r.val_at(1)?),
+            (10, 1) => SmrInput::Stop,
+            (11, 6) => {
+                let (height, round) = height_round(r)?;
+                let tmp: u8 = r.val_at(3)?;
+                let step = Step::try_from(tmp)
+                    .map_err(|_| DecoderError::Custom("Invalid step"))?;
+                let lock_round: Option<u64> = r.val_at(4)?;
+                let lock_hash: Option<Vec<u8>> = r.val_at(5)?;
+                SmrInput::Wal {
+                    height,
+                    round,
+                    step,
+                    lock: lock_round.zip(lock_hash.map(Hash::from)),
+                }
+            }
+            _ => return Err(DecoderError::Custom("Invalid smr input")),
+        };
+        Ok(input)
+    }
+}
+
+impl Encodable for SmrOutput {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            SmrOutput::NewRound {
+                height,
+                round,
+                lock_round,
+                lock_proposal,
+                interval,
+                config,
+            } => {
+                s.begin_list(7)
+                    .append(&0u8)
+                    .append(height)
+                    .append(round)
+                    .append(lock_round)
+                    .append(&lock_proposal.as_ref().map(|hash| hash.to_vec()))
+                    .append(&duration_nanos(interval))
+                    .append(config);
+            }
+            SmrOutput::Prevote {
+                height,
+                round,
+                hash,
+                lock_round,
+            } => {
+                s.begin_list(5)
+                    .append(&1u8)
+                    .append(height)
+                    .append(round)
+                    .append(&hash.to_vec())
+                    .append(lock_round);
+            }
+            SmrOutput::Precommit {
+                height,
+                round,
+                hash,
+                lock_round,
+            } => {
+                s.begin_list(5)
+                    .append(&2u8)
+                    .append(height)
+                    .append(round)
+                    .append(&hash.to_vec())
+                    .append(lock_round);
+            }
+            SmrOutput::Commit { hash } => {
+                s.begin_list(2).append(&3u8).append(&hash.to_vec());
+            }
+            SmrOutput::Brake {
+                height,
+                round,
+                lock_round,
+            } => {
+                s.begin_list(4)
+                    .append(&4u8)
+                    .append(height)
+                    .append(round)
+                    .append(lock_round);
+            }
+            SmrOutput::UpdateConfig(config) => {
+                s.begin_list(2).append(&5u8).append(config);
+            }
+            SmrOutput::Stop => {
+                s.begin_list(1).append(&6u8);
+            }
+        }
+    }
+}
+
+impl Decodable for SmrOutput {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        let tag: u8 = r.val_at(0)?;
+        let output = match (tag, r.item_count()?) {
+            (0, 7) => {
+                let (height, round) = height_round(r)?;
+                let lock_proposal: Option<Vec<u8>> = r.val_at(4)?;
+                let interval: Option<u64> = r.val_at(5)?;
+                SmrOutput::NewRound {
+                    height,
+                    round,
+                    lock_round: r.val_at(3)?,
+                    lock_proposal: lock_proposal.map(Hash::from),
+                    interval: interval.map(Duration::from_nanos),
+                    config: r.val_at(6)?,
+                }
+            }
+            (1, 5) => {
+                let (height, round) = height_round(r)?;
+                SmrOutput::Prevote {
+                    height,
+                    round,
+                    hash: hash_at(r, 3)?,
+                    lock_round: r.val_at(4)?,
+                }
+            }
+            (2, 5) => {
+                let (height, round) = height_round(r)?;
+                SmrOutput::Precommit {
+                    height,
+                    round,
+                    hash: hash_at(r, 3)?,
+                    lock_round: r.val_at(4)?,
+                }
+            }
+            (3, 2) => SmrOutput::Commit {
+                hash: hash_at(r, 1)?,
+            },
+            (4, 4) => {
+                let (height, round) = height_round(r)?;
+                SmrOutput::Brake {
+                    height,
+                    round,
+                    lock_round: r.val_at(3)?,
+                }
+            }
+            (5, 2) => SmrOutput::UpdateConfig(r.val_at(1)?),
+            (6, 1) => SmrOutput::Stop,
+            _ => return Err(DecoderError::Custom("Invalid smr output")),
+        };
+        Ok(output)
+    }
+}
+
+// A trace record is the list of the timestamp, zero and the input, or the timestamp, one and
+// the output.
+impl Encodable for TraceRecord {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3).append(&self.timestamp);
+        match &self.entry {
+            TraceEntry::Input(input) => s.append(&0u8).append(input),
+            TraceEntry::Output(output) => s.append(&1u8).append(output),
+        };
+    }
+}
+
+impl Decodable for TraceRecord {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(3) => {
+                let timestamp: u64 = r.val_at(0)?;
+                let tmp: u8 = r.val_at(1)?;
+                let entry = match tmp {
+                    0 => TraceEntry::Input(r.val_at(2)?),
+                    1 => TraceEntry::Output(r.val_at(2)?),
+                    _ => return Err(DecoderError::Custom("Invalid trace entry")),
+                };
+                Ok(TraceRecord { timestamp, entry })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use std::error::Error;
-
     use bincode::{deserialize, serialize};
     use rand::random;
     use serde::{Deserialize, Serialize};
 
     use super::*;
+    use crate::test_utils::gen_address;
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     struct Pill {
@@ -634,15 +1750,14 @@ mod test {
     }
 
     impl Codec for Pill {
-        fn encode(&self) -> Result<Bytes, Box<dyn Error + Send>> {
-            let encode: Vec<u8> = serialize(&self).expect("Serialize Pill error");
-            Ok(Bytes::from(encode))
+        type Error = bincode::Error;
+
+        fn encode(&self) -> Result<Bytes, Self::Error> {
+            Ok(Bytes::from(serialize(&self)?))
         }
 
-        fn decode(data: Bytes) -> Result<Self, Box<dyn Error + Send>> {
-            let decode: Pill =
-                deserialize(data.as_ref()).expect("Deserialize Pill error.");
-            Ok(decode)
+        fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+            deserialize(data)
         }
     }
 
@@ -659,6 +1774,8 @@ mod test {
             SignedProposal {
                 signature: gen_signature(),
                 proposal: Proposal::new(content, lock),
+                timeout_cert: None,
+                vrf_proof: Bytes::new(),
             }
         }
     }
@@ -676,6 +1793,7 @@ mod test {
                 block_hash,
                 lock,
                 proposer,
+                timestamp: random::<bool>().then(random::<u64>),
             }
         }
     }
@@ -695,6 +1813,8 @@ mod test {
                 signature: gen_signature(),
                 vote: Vote::new(vote_type),
                 voter: gen_address(),
+                extension: Bytes::new(),
+                extension_signature: Signature::new(),
             }
         }
     }
@@ -708,6 +1828,7 @@ mod test {
                 round: random::<u64>(),
                 block_hash: gen_hash(),
                 leader: gen_address(),
+                extensions: Vec::new(),
             }
         }
     }
@@ -727,10 +1848,16 @@ mod test {
         fn new(content: T) -> Self {
             let height = random::<u64>();
             let proof = Proof::new();
+            let height_start = random::<u32>() as u64;
             Commit {
                 height,
                 content,
+                round: proof.round,
                 proof,
+                height_start,
+                round_start: height_start + 10,
+                committed_at: height_start + 20,
+                timestamp: random::<bool>().then(random::<u64>),
             }
         }
     }
@@ -742,6 +1869,7 @@ mod test {
                 round: random::<u64>(),
                 block_hash: gen_hash(),
                 signature: gen_aggr_signature(),
+                extensions: Vec::new(),
             }
         }
     }
@@ -778,13 +1906,23 @@ mod test {
     }
 
     impl Status {
-        fn new(time: Option<u64>, is_update_config: bool) -> Self {
+        fn new(time: Option<Duration>, is_update_config: bool) -> Self {
             let config = if is_update_config {
                 Some(DurationConfig {
                     propose_ratio: random::<u64>(),
                     prevote_ratio: random::<u64>(),
                     precommit_ratio: random::<u64>(),
                     brake_ratio: random::<u64>(),
+                    backoff: if random::<bool>() {
+                        Some(BackoffConfig {
+                            base: random::<u64>(),
+                            multiplier: random::<u64>(),
+                            cap: random::<u64>(),
+                        })
+                    } else {
+                        None
+                    },
+                    choke_free: random::<bool>(),
                 })
             } else {
                 None
@@ -795,6 +1933,9 @@ mod test {
                 interval: time,
                 timer_config: config,
                 authority_list: vec![Node::new(gen_address())],
+                next_authority_list: None,
+                proposer_seed: None,
+                skipped_proposers: Vec::new(),
             }
         }
     }
@@ -816,12 +1957,19 @@ mod test {
             let round = random::<u64>();
             let step = Step::Precommit;
             let from = UpdateFrom::ChokeQC(AggregatedChoke::new());
+            let status = if random::<bool>() {
+                Some(Status::new(Some(Duration::from_nanos(random())), true))
+            } else {
+                None
+            };
             WalInfo {
                 height,
                 round,
                 step,
                 lock,
                 from,
+                status,
+                pending_commit: None,
             }
         }
     }
@@ -830,14 +1978,18 @@ mod test {
         Hash::from((0..16).map(|_| random::<u8>()).collect::<Vec<_>>())
     }
 
-    fn gen_address() -> Address {
-        Address::from((0..32).map(|_| random::<u8>()).collect::<Vec<_>>())
-    }
-
     fn gen_signature() -> Signature {
         Signature::from((0..64).map(|_| random::<u8>()).collect::<Vec<_>>())
     }
 
+    fn gen_extension() -> VoteExtension {
+        VoteExtension {
+            voter: gen_address(),
+            payload: Bytes::from((0..16).map(|_| random::<u8>()).collect::<Vec<_>>()),
+            signature: gen_signature(),
+        }
+    }
+
     fn gen_aggr_signature() -> AggregatedSignature {
         AggregatedSignature {
             signature: gen_signature(),
@@ -869,6 +2021,29 @@ mod test {
             rlp::decode(&signed_proposal.rlp_bytes()).unwrap();
         assert_eq!(signed_proposal, res);
 
+        // Test SignedProposal with a timeout certificate
+        let mut signed_proposal = SignedProposal::new(Pill::new(), None);
+        signed_proposal.timeout_cert = Some(AggregatedChoke::new());
+        let res: SignedProposal<Pill> =
+            rlp::decode(&signed_proposal.rlp_bytes()).unwrap();
+        assert_eq!(signed_proposal, res);
+        let compact = CompactProposal::from_signed(&signed_proposal);
+        let res: CompactProposal = rlp::decode(&compact.rlp_bytes()).unwrap();
+        assert_eq!(compact, res);
+
+        // Test SignedProposal with a VRF proof, with and without the timeout certificate
+        for timeout_cert in [None, Some(AggregatedChoke::new())] {
+            let mut signed_proposal = SignedProposal::new(Pill::new(), None);
+            signed_proposal.timeout_cert = timeout_cert;
+            signed_proposal.vrf_proof = Bytes::from(vec![7u8; 80]);
+            let res: SignedProposal<Pill> =
+                rlp::decode(&signed_proposal.rlp_bytes()).unwrap();
+            assert_eq!(signed_proposal, res);
+            let compact = CompactProposal::from_signed(&signed_proposal);
+            let res: CompactProposal = rlp::decode(&compact.rlp_bytes()).unwrap();
+            assert_eq!(compact, res);
+        }
+
         // Test SignedVote
         let signed_vote = SignedVote::new(2u8);
         let res: SignedVote = rlp::decode(&signed_vote.rlp_bytes()).unwrap();
@@ -878,6 +2053,19 @@ mod test {
         let res: SignedVote = rlp::decode(&signed_vote.rlp_bytes()).unwrap();
         assert_eq!(signed_vote, res);
 
+        // A vote of no voter is rejected when it is decoded.
+        let mut signed_vote = SignedVote::new(1u8);
+        signed_vote.voter = Address::default();
+        assert!(rlp::decode::<SignedVote>(&signed_vote.rlp_bytes()).is_err());
+
+        // The extension of a precommit is appended, and a vote without it keeps its items.
+        let mut signed_vote = SignedVote::new(2u8);
+        assert_eq!(Rlp::new(&signed_vote.rlp_bytes()).item_count().unwrap(), 3);
+        signed_vote.extension = Bytes::from(vec![3u8; 16]);
+        signed_vote.extension_signature = gen_signature();
+        let res: SignedVote = rlp::decode(&signed_vote.rlp_bytes()).unwrap();
+        assert_eq!(signed_vote, res);
+
         // Test AggregatedVote
         let aggregated_vote = AggregatedVote::new(2u8);
         let res: AggregatedVote = rlp::decode(&aggregated_vote.rlp_bytes()).unwrap();
@@ -887,10 +2075,48 @@ mod test {
         let res: AggregatedVote = rlp::decode(&aggregated_vote.rlp_bytes()).unwrap();
         assert_eq!(aggregated_vote, res);
 
+        // But the initial QC of no leader is kept.
+        let mut aggregated_vote = AggregatedVote::new(1u8);
+        aggregated_vote.leader = Address::default();
+        let res: AggregatedVote = rlp::decode(&aggregated_vote.rlp_bytes()).unwrap();
+        assert_eq!(aggregated_vote, res);
+
+        let mut aggregated_vote = AggregatedVote::new(2u8);
+        aggregated_vote.extensions = vec![gen_extension(), gen_extension()];
+        let res: AggregatedVote = rlp::decode(&aggregated_vote.rlp_bytes()).unwrap();
+        assert_eq!(aggregated_vote, res);
+
+        // Test Proof with the vote extensions
+        let mut proof = Proof::new();
+        proof.extensions = vec![gen_extension()];
+        let res: Proof = rlp::decode(&proof.rlp_bytes()).unwrap();
+        assert_eq!(proof, res);
+
+        // Test HandoverProof
+        let handover = HandoverProof {
+            proof: Proof::new(),
+            next_authority_list: vec![
+                Node::new(gen_address()),
+                Node::new(gen_address()),
+            ],
+        };
+        let res: HandoverProof = rlp::decode(&handover.rlp_bytes()).unwrap();
+        assert_eq!(handover, res);
+
         // Test Commit
         let commit = Commit::new(Pill::new());
         let res: Commit<Pill> = rlp::decode(&commit.rlp_bytes()).unwrap();
         assert_eq!(commit, res);
+        assert_eq!(commit.finality_latency(), Duration::from_millis(20));
+
+        // A commit of the three original items is decided in the round of its proof.
+        let mut s = RlpStream::new_list(3);
+        s.append(&commit.height)
+            .append(&commit.proof)
+            .append(&commit.content.encode().unwrap().to_vec());
+        let res: Commit<Pill> = rlp::decode(&s.out()).unwrap();
+        assert_eq!(res.round, commit.proof.round);
+        assert_eq!(res.committed_at, 0);
 
         // Test Status
         let status = Status::new(None, true);
@@ -898,9 +2124,41 @@ mod test {
         assert_eq!(status, res);
 
         // Test Status
-        let status = Status::new(Some(3000), false);
+        let status = Status::new(Some(Duration::from_millis(3000)), false);
+        let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
+        assert_eq!(status, res);
+        assert_eq!(Rlp::new(&status.rlp_bytes()).item_count().unwrap(), 4);
+
+        // Test Status of a sub-millisecond interval
+        let status = Status::new(Some(Duration::from_micros(2500)), false);
         let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
         assert_eq!(status, res);
+        assert_eq!(Rlp::new(&status.rlp_bytes()).item_count().unwrap(), 5);
+
+        // Test Status of the last height of an epoch
+        let mut status = Status::new(Some(Duration::from_millis(3000)), false);
+        status.next_authority_list = Some(vec![Node::new(gen_address())]);
+        let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
+        assert_eq!(status, res);
+        assert_eq!(Rlp::new(&status.rlp_bytes()).item_count().unwrap(), 6);
+
+        // Test Status with a proposer seed, with and without the next authority list
+        status.proposer_seed = Some(7);
+        let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
+        assert_eq!(status, res);
+        status.next_authority_list = None;
+        let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
+        assert_eq!(status, res);
+        assert_eq!(Rlp::new(&status.rlp_bytes()).item_count().unwrap(), 7);
+
+        // Test Status with the skipped proposers, with and without the proposer seed
+        status.skipped_proposers = vec![gen_address(), gen_address()];
+        let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
+        assert_eq!(status, res);
+        status.proposer_seed = None;
+        let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
+        assert_eq!(status, res);
+        assert_eq!(Rlp::new(&status.rlp_bytes()).item_count().unwrap(), 8);
 
         // Test Aggregated Choke
         let aggregated_choke = AggregatedChoke::new();
@@ -922,6 +2180,31 @@ mod test {
         let res: SignedChoke = rlp::decode(&signed_choke.rlp_bytes()).unwrap();
         assert_eq!(signed_choke, res);
 
+        // Test Signed Heartbeat
+        let signed_heartbeat = SignedHeartbeat {
+            signature: gen_signature(),
+            heartbeat: Heartbeat {
+                height: random::<u64>(),
+                round: random::<u64>(),
+                timestamp: random::<u64>(),
+                address: gen_address(),
+            },
+        };
+        let res: SignedHeartbeat = rlp::decode(&signed_heartbeat.rlp_bytes()).unwrap();
+        assert_eq!(signed_heartbeat, res);
+
+        // Test Signed Key Rotation
+        let signed_rotation = SignedKeyRotation {
+            signature: gen_signature(),
+            rotation: KeyRotation {
+                epoch: random::<u64>(),
+                address: gen_address(),
+                payload: gen_signature(),
+            },
+        };
+        let res: SignedKeyRotation = rlp::decode(&signed_rotation.rlp_bytes()).unwrap();
+        assert_eq!(signed_rotation, res);
+
         // Test Wal Info
         let pill = Pill::new();
         let wal_info = WalInfo::new(Some(pill));
@@ -931,5 +2214,85 @@ mod test {
         let wal_info = WalInfo::new(None);
         let res: WalInfo<Pill> = rlp::decode(&wal_info.rlp_bytes()).unwrap();
         assert_eq!(wal_info, res);
+
+        // The wal info saved without a status is still decoded.
+        let mut stream = RlpStream::new_list(5);
+        stream
+            .append(&wal_info.height)
+            .append(&wal_info.round)
+            .append::<u8>(&wal_info.step.clone().into())
+            .append(&wal_info.lock)
+            .append(&wal_info.from);
+        let res: WalInfo<Pill> = rlp::decode(&stream.out()).unwrap();
+        assert_eq!(res.status, None);
+        assert_eq!(res.height, wal_info.height);
+
+        // Test Last Signed
+        let last_signed = LastSigned {
+            height: random::<u64>(),
+            round: random::<u64>(),
+            step: Step::Brake,
+            hash: gen_hash(),
+        };
+        let res: LastSigned = rlp::decode(&last_signed.rlp_bytes()).unwrap();
+        assert_eq!(last_signed, res);
+
+        // An invalid step or update from is a decode error instead of a panic.
+        let mut stream = RlpStream::new_list(5);
+        stream
+            .append(&wal_info.height)
+            .append(&wal_info.round)
+            .append(&9u8)
+            .append(&wal_info.lock)
+            .append(&wal_info.from);
+        assert!(rlp::decode::<WalInfo<Pill>>(&stream.out()).is_err());
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&3u8).append(&AggregatedChoke::new());
+        assert!(rlp::decode::<UpdateFrom>(&stream.out()).is_err());
+
+        // Test the trace records
+        let entries = vec![
+            TraceEntry::Input(SmrInput::NewHeight {
+                height: random::<u64>(),
+                interval: Some(Duration::from_micros(2500)),
+                config: Some(DurationConfig::new(15, 10, 10, 7)),
+            }),
+            TraceEntry::Input(SmrInput::BrakeTimeout {
+                height: random::<u64>(),
+                round: random::<u64>(),
+            }),
+            TraceEntry::Input(SmrInput::Wal {
+                height: random::<u64>(),
+                round: random::<u64>(),
+                step: Step::Precommit,
+                lock: Some((random::<u64>(), gen_hash())),
+            }),
+            TraceEntry::Output(SmrOutput::NewRound {
+                height: random::<u64>(),
+                round: random::<u64>(),
+                lock_round: None,
+                lock_proposal: None,
+                interval: None,
+                config: None,
+            }),
+            TraceEntry::Output(SmrOutput::Precommit {
+                height: random::<u64>(),
+                round: random::<u64>(),
+                hash: gen_hash(),
+                lock_round: Some(random::<u64>()),
+            }),
+            TraceEntry::Output(SmrOutput::Stop),
+        ];
+        for entry in entries {
+            let record = TraceRecord {
+                timestamp: random::<u64>(),
+                entry,
+            };
+            let res: TraceRecord = rlp::decode(&record.rlp_bytes()).unwrap();
+            assert_eq!(record, res);
+        }
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&4u8).append(&1u64);
+        assert!(rlp::decode::<SmrInput>(&stream.out()).is_err());
     }
 }
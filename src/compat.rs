@@ -0,0 +1,365 @@
+//! The fixtures of the wire messages and the wal records encoded by the released versions,
+//! which are stored as `tests/compat/<version>.json` and must be decoded and acted on by the
+//! current code. The fixture of the current version is written by
+//! `cargo test --all-features compat -- --ignored` when the crate is released, and is never
+//! changed afterwards.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bytes::Bytes;
+use hummer::coding::{hex_decode, hex_encode};
+
+use crate::smr::smr_types::Step;
+use crate::state::snapshot::ConsensusSnapshot;
+use crate::test_utils::index_address;
+use crate::types::{
+    AggregatedSignature, AggregatedVote, Hash, Node, Proof, Status, UpdateFrom, VoteType,
+};
+use crate::wal::{
+    decode_record, encode_record, LastSigned, WalInfo, WalLock, WalRecordKind,
+};
+use crate::wire::RlpCodec;
+
+/// The encoded entries of a fixture by their names.
+type Fixture = BTreeMap<String, String>;
+
+const HEIGHT: u64 = 5;
+const ROUND: u64 = 2;
+const RUN_ID: &[u8] = b"compat-run";
+
+fn fixture_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/compat")
+}
+
+/// Read the fixtures of all of the versions, ordered by the file names.
+fn read_fixtures() -> Vec<(String, Fixture)> {
+    let mut paths = fs::read_dir(fixture_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            let fixture = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+            (name, fixture)
+        })
+        .collect()
+}
+
+fn entry(version: &str, fixture: &Fixture, name: &str) -> Vec<u8> {
+    let hex = fixture
+        .get(name)
+        .unwrap_or_else(|| panic!("no {} in the fixture of {}", name, version));
+    hex_decode(hex).unwrap()
+}
+
+fn gen_hash(index: u8) -> Hash {
+    Hash::from(vec![index; 32])
+}
+
+fn gen_signature() -> AggregatedSignature {
+    AggregatedSignature {
+        signature: Bytes::from(vec![7u8; 96]),
+        address_bitmap: Bytes::from(vec![0b1110_0000]),
+    }
+}
+
+fn gen_qc(vote_type: VoteType, round: u64) -> AggregatedVote {
+    AggregatedVote {
+        signature: gen_signature(),
+        vote_type,
+        height: HEIGHT,
+        round,
+        block_hash: gen_hash(1),
+        leader: index_address(1),
+        extensions: Vec::new(),
+    }
+}
+
+fn gen_wal_info() -> WalInfo<Bytes> {
+    WalInfo {
+        height: HEIGHT,
+        round: ROUND,
+        step: Step::Prevote,
+        lock: Some(WalLock {
+            lock_round: 1,
+            lock_votes: gen_qc(VoteType::Prevote, 1),
+            content: Bytes::from_static(b"compat block"),
+        }),
+        from: UpdateFrom::PrevoteQC(gen_qc(VoteType::Prevote, 1)),
+        status: Some(Status {
+            height: HEIGHT,
+            interval: Some(Duration::from_millis(3000)),
+            timer_config: None,
+            authority_list: (1..=4)
+                .map(|index| Node::new(index_address(index)))
+                .collect(),
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: Vec::new(),
+        }),
+        pending_commit: None,
+    }
+}
+
+fn gen_last_signed() -> LastSigned {
+    LastSigned {
+        height: HEIGHT,
+        round: ROUND,
+        step: Step::Prevote,
+        hash: gen_hash(1),
+    }
+}
+
+fn gen_checkpoint() -> ConsensusSnapshot<Bytes> {
+    ConsensusSnapshot {
+        wal_info: gen_wal_info(),
+        last_signed: Some(gen_last_signed()),
+        qcs: vec![gen_qc(VoteType::Prevote, 1)],
+    }
+}
+
+fn gen_proofs() -> Vec<Proof> {
+    (1..HEIGHT)
+        .map(|height| Proof {
+            height,
+            round: 0,
+            block_hash: gen_hash(height as u8),
+            signature: gen_signature(),
+            extensions: Vec::new(),
+        })
+        .collect()
+}
+
+fn wal_fixture() -> Fixture {
+    let wal_info = gen_wal_info();
+    let mut legacy = encode_record(&wal_info, &RlpCodec, &[]).unwrap().to_vec();
+    legacy.remove(0);
+    [
+        (
+            "wal_info",
+            encode_record(&wal_info, &RlpCodec, &[]).unwrap().to_vec(),
+        ),
+        (
+            "wal_info.tagged",
+            encode_record(&wal_info, &RlpCodec, RUN_ID)
+                .unwrap()
+                .to_vec(),
+        ),
+        ("wal_info.legacy", legacy),
+        (
+            "last_signed",
+            encode_record(&gen_last_signed(), &RlpCodec, &[])
+                .unwrap()
+                .to_vec(),
+        ),
+        (
+            "checkpoint",
+            encode_record(&gen_checkpoint(), &RlpCodec, RUN_ID)
+                .unwrap()
+                .to_vec(),
+        ),
+        (
+            "proof_archive",
+            encode_record(&gen_proofs(), &RlpCodec, &[])
+                .unwrap()
+                .to_vec(),
+        ),
+    ]
+    .into_iter()
+    .map(|(name, record)| (name.to_string(), hex_encode(record)))
+    .collect()
+}
+
+fn check_wal(version: &str, fixture: &Fixture) {
+    let expected = gen_wal_info();
+    for (name, run_id) in [
+        ("wal_info", None),
+        ("wal_info.tagged", Some(RUN_ID)),
+        ("wal_info.legacy", None),
+    ] {
+        let record = entry(version, fixture, name);
+        let wal_info: WalInfo<Bytes> =
+            decode_record(WalRecordKind::WalInfo, &record, &RlpCodec, None, run_id)
+                .unwrap_or_else(|e| panic!("{} of {}: {}", name, version, e));
+        assert_eq!(wal_info, expected, "{} of {}", name, version);
+
+        // The lock is still valid in the recovered round, and restores the polc.
+        let lock = wal_info.lock.as_ref().unwrap();
+        lock.check(wal_info.height, wal_info.round, &wal_info.step)
+            .unwrap();
+        let polc = wal_info.into_smr_base().polc.unwrap();
+        assert_eq!((polc.round, polc.hash), (1, gen_hash(1)));
+    }
+
+    let record = entry(version, fixture, "last_signed");
+    let last_signed: LastSigned =
+        decode_record(WalRecordKind::LastSigned, &record, &RlpCodec, None, None)
+            .unwrap();
+    assert_eq!(last_signed, gen_last_signed(), "last signed of {}", version);
+    // The recovered record keeps the node from signing a conflicting vote.
+    let mut conflict = last_signed.clone();
+    conflict.hash = gen_hash(2);
+    assert!(last_signed.check(&conflict).is_err());
+    conflict.round += 1;
+    assert_eq!(last_signed.check(&conflict), Ok(true));
+
+    let record = entry(version, fixture, "checkpoint");
+    let checkpoint: ConsensusSnapshot<Bytes> = decode_record(
+        WalRecordKind::Checkpoint,
+        &record,
+        &RlpCodec,
+        None,
+        Some(RUN_ID),
+    )
+    .unwrap();
+    assert_eq!(checkpoint, gen_checkpoint(), "checkpoint of {}", version);
+
+    let record = entry(version, fixture, "proof_archive");
+    let proofs: Vec<Proof> =
+        decode_record(WalRecordKind::ProofArchive, &record, &RlpCodec, None, None)
+            .unwrap();
+    assert_eq!(proofs, gen_proofs(), "proof archive of {}", version);
+}
+
+#[cfg(any(feature = "tcp", feature = "mlm-libp2p", feature = "ffi"))]
+mod wire {
+    use bytes::Bytes;
+    use hummer::coding::hex_encode;
+
+    use super::{entry, gen_hash, gen_qc, Fixture, HEIGHT, ROUND};
+    use crate::test_utils::index_address;
+    use crate::transport::{decode_payload, encode_payload, unversioned_payload};
+    use crate::types::{
+        Choke, Heartbeat, MlmMsg, PoLC, Proposal, SignedChoke, SignedHeartbeat,
+        SignedProposal, SignedVote, UpdateFrom, Vote, VoteType,
+    };
+
+    fn gen_vote(voter: u8) -> SignedVote {
+        SignedVote {
+            signature: Bytes::from(vec![voter; 64]),
+            vote: Vote {
+                height: HEIGHT,
+                round: ROUND,
+                vote_type: VoteType::Prevote,
+                block_hash: gen_hash(1),
+            },
+            voter: index_address(voter),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        }
+    }
+
+    fn gen_messages() -> Vec<(&'static str, MlmMsg<Bytes>)> {
+        let proposal = SignedProposal {
+            signature: Bytes::from(vec![1u8; 64]),
+            proposal: Proposal {
+                height: HEIGHT,
+                round: ROUND,
+                content: Bytes::from_static(b"compat block"),
+                block_hash: gen_hash(1),
+                lock: Some(PoLC {
+                    lock_round: 1,
+                    lock_votes: gen_qc(VoteType::Prevote, 1),
+                }),
+                proposer: index_address(1),
+                timestamp: Some(1_700_000_000_000),
+            },
+            timeout_cert: None,
+            vrf_proof: Bytes::new(),
+        };
+        let choke = SignedChoke {
+            signature: Bytes::from(vec![2u8; 64]),
+            choke: Choke {
+                height: HEIGHT,
+                round: ROUND,
+                from: UpdateFrom::PrevoteQC(gen_qc(VoteType::Prevote, 1)),
+            },
+            address: index_address(2),
+        };
+        let heartbeat = SignedHeartbeat {
+            signature: Bytes::from(vec![3u8; 64]),
+            heartbeat: Heartbeat {
+                height: HEIGHT,
+                round: ROUND,
+                timestamp: 1_700_000_000_000,
+                address: index_address(3),
+            },
+        };
+        vec![
+            ("signed_proposal", MlmMsg::SignedProposal(proposal)),
+            ("signed_vote", MlmMsg::SignedVote(gen_vote(1))),
+            (
+                "aggregated_vote",
+                MlmMsg::AggregatedVote(gen_qc(VoteType::Precommit, ROUND)),
+            ),
+            ("signed_choke", MlmMsg::SignedChoke(choke)),
+            ("signed_heartbeat", MlmMsg::SignedHeartbeat(heartbeat)),
+            (
+                "signed_vote_batch",
+                MlmMsg::SignedVoteBatch((1..=3).map(gen_vote).collect()),
+            ),
+        ]
+    }
+
+    pub(super) fn fixture() -> Fixture {
+        let mut fixture = Fixture::new();
+        for (name, msg) in gen_messages() {
+            let payload = encode_payload(&msg).unwrap();
+            if name == "signed_vote" {
+                fixture.insert(
+                    "signed_vote.unversioned".to_string(),
+                    hex_encode(unversioned_payload(payload.clone())),
+                );
+            }
+            fixture.insert(name.to_string(), hex_encode(payload));
+        }
+        fixture
+    }
+
+    pub(super) fn check(version: &str, fixture: &Fixture) {
+        let mut messages = gen_messages();
+        messages.push(("signed_vote.unversioned", MlmMsg::SignedVote(gen_vote(1))));
+        for (name, expected) in messages {
+            let payload = entry(version, fixture, name);
+            let msg = decode_payload::<Bytes>(&payload)
+                .unwrap_or_else(|e| panic!("{} of {}: {}", name, version, e));
+            assert_eq!(msg, expected, "{} of {}", name, version);
+
+            // The decoded message is relayed to the current peers as it is.
+            let relayed = encode_payload(&msg).unwrap();
+            assert_eq!(decode_payload::<Bytes>(&relayed).unwrap(), expected);
+        }
+    }
+}
+
+#[test]
+fn test_compat_fixtures() {
+    let fixtures = read_fixtures();
+    assert!(!fixtures.is_empty());
+    for (version, fixture) in fixtures.iter() {
+        check_wal(version, fixture);
+        #[cfg(any(feature = "tcp", feature = "mlm-libp2p", feature = "ffi"))]
+        wire::check(version, fixture);
+    }
+}
+
+/// Write the fixture of the current version, which is run once with all of the features when
+/// the crate is released.
+#[test]
+#[ignore]
+fn write_compat_fixture() {
+    #[allow(unused_mut)]
+    let mut fixture = wal_fixture();
+    #[cfg(any(feature = "tcp", feature = "mlm-libp2p", feature = "ffi"))]
+    fixture.extend(wire::fixture());
+    let path = fixture_dir().join(format!("{}.json", env!("CARGO_PKG_VERSION")));
+    let json = serde_json::to_string_pretty(&fixture).unwrap();
+    fs::write(path, json + "\n").unwrap();
+}
@@ -0,0 +1,685 @@
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::memory::CacheWeights;
+use crate::threads::ThreadHints;
+use crate::{BackoffConfig, DurationConfig};
+
+/// The cargo features of the crate, with whether each of them is enabled in the build.
+#[cfg(feature = "runtime")]
+const FEATURES: &[(&str, bool)] = &[
+    ("affinity", cfg!(feature = "affinity")),
+    ("compression", cfg!(feature = "compression")),
+    ("default-crypto", cfg!(feature = "default-crypto")),
+    ("ffi", cfg!(feature = "ffi")),
+    ("fs", cfg!(feature = "fs")),
+    ("fuzzing", cfg!(feature = "fuzzing")),
+    ("loadtest", cfg!(feature = "loadtest")),
+    ("mlm-libp2p", cfg!(feature = "mlm-libp2p")),
+    ("multi_proposal", cfg!(feature = "multi_proposal")),
+    ("random_leader", cfg!(feature = "random_leader")),
+    ("rocksdb", cfg!(feature = "rocksdb")),
+    ("runtime", cfg!(feature = "runtime")),
+    ("strict-panic", cfg!(feature = "strict-panic")),
+    ("tcp", cfg!(feature = "tcp")),
+    ("testing", cfg!(feature = "testing")),
+    ("trace_check", cfg!(feature = "trace_check")),
+    ("wal-aes-gcm", cfg!(feature = "wal-aes-gcm")),
+    ("wire-bincode", cfg!(feature = "wire-bincode")),
+    ("wire-protobuf", cfg!(feature = "wire-protobuf")),
+];
+
+/// Engine level options of an mlm instance. The default value keeps the classic behaviour of
+/// the protocol, every option is opt-in.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MlmConfig {
+    /// Run consensus in pipelined mode. The state starts the proposal and prevote phase of
+    /// height `H + 1` while the `commit()` interface is still executing the block of height
+    /// `H`. The returned status is applied before precommit of `H + 1`. If its authority list,
+    /// its next epoch or its proposer seed differs from the ones that `H + 1` started with,
+    /// height `H + 1` is restarted by the status, and a new interval or timer configuration
+    /// takes effect from height `H + 2` otherwise. The commit of `H` is kept in the wal until
+    /// the status is applied, and is delivered again after a restart of the node.
+    pub pipelined: bool,
+    /// Move to the round of the current height that a quorum of the vote weight is proven to
+    /// be at by its signed votes, chokes and heartbeats, skipping the remaining timeouts of the
+    /// rounds in between, such as a node rejoining after a restart. The signed votes of the higher
+    /// rounds are handled at once instead of waiting for their rounds in the future buffer. A
+    /// quorum at a higher height is left to the block sync of the application.
+    pub fast_resync: bool,
+    /// Extend the precommits of the blocks with the payloads of `Consensus::extend_vote()`,
+    /// which are carried to the precommit QCs and the proofs. The extensions of the other
+    /// authorities are verified whether it is enabled or not.
+    pub vote_extensions: bool,
+    /// The number of designated proposers of each round. The extra proposers follow the
+    /// primary one in the sorted authority list, and a node prevotes for the lowest ranked
+    /// proposal it has received after waiting `proposal_wait` for each lower rank. A value
+    /// less than two disables multiple proposals.
+    #[cfg(feature = "multi_proposal")]
+    pub proposer_count: usize,
+    /// Milliseconds to wait for a proposal of each lower rank, or for the proposals of the
+    /// round in the VRF leader mode. Zero means a quarter of the block interval.
+    pub proposal_wait: u64,
+    /// Elect the proposer of each round secretly by a VRF instead of the rotation. Every
+    /// authority evaluates its VRF over the height, the round and `vrf_seed`, and proposes
+    /// with the proof, then a node prevotes for the proposal of the lowest VRF output that it
+    /// has received in `proposal_wait`. Since the proposer is unknown until its proposal is
+    /// out, it can not be targeted ahead of the round. The rotation leader still relays the
+    /// votes and re-proposes a lock, and `proposer_count` is ignored. Every node of the
+    /// network should enable it together. It does not need the `multi_proposal` feature.
+    pub vrf_leader: bool,
+    /// The seed of the VRF leader election, such as the genesis hash, which must be the same
+    /// on all of the nodes.
+    #[serde(with = "crate::serde_hex")]
+    pub vrf_seed: Bytes,
+    /// The proportion of the step timeout in tenths, after which a replica resends its vote of
+    /// the step once if the QC has not formed. The vote is lost if the leader misses it, and
+    /// the resend saves waiting for the whole timeout on a lossy network. Zero disables the
+    /// resend, and so does a value not less than ten since the step has timed out by then.
+    pub vote_resend_ratio: u64,
+    /// The proportion of the step timeout in tenths, after which a node routes its vote of the
+    /// step to the next relayer in the fallback chain if the QC has not formed. The chain
+    /// follows the leader round robin in the sorted authority list, and the vote moves one hop
+    /// further every such interval until the step times out, so a QC can still form when the
+    /// leader is partitioned from most of the authorities. Zero disables the fallback, and so
+    /// does a value not less than ten.
+    pub relayer_fallback_ratio: u64,
+    /// The number of the backup relayers that a node sends its votes to at once besides the
+    /// leader, so a QC still forms in time if the leader drops the votes. The backup relayers
+    /// aggregate the votes as the leader does, and a node handles only the first QC of a block
+    /// hash from the relayers. Zero disables the backup relayers.
+    pub backup_relayers: usize,
+    /// The policy of picking the backup relayers.
+    pub relayer_policy: RelayerPolicy,
+    /// Attach the timeout certificate of the previous round, which aggregates the chokes above
+    /// the threshold, to the proposal of the next round. The nodes behind jump to the round of
+    /// the proposal at once instead of timing out on their own. The nodes always accept the
+    /// certificates, which older versions can not decode.
+    pub timeout_cert: bool,
+    /// Re-broadcast each QC of the current height received from the network once, so the
+    /// nodes that miss the votes or the QC from the leader can still advance. A node handles
+    /// only the first QC of a block hash then.
+    pub gossip_qc: bool,
+    /// Milliseconds to wait for the async crypto to sign. Zero means the prevote timeout.
+    pub sign_timeout: u64,
+    /// Milliseconds between the probes of the signer while it is unavailable, such as a key
+    /// not unsealed yet or a locked HSM. The node keeps verifying and tracking the consensus
+    /// as an observer meanwhile. Zero means the height interval.
+    pub signer_retry_interval: u64,
+    /// Aggregate the votes of a QC by `Consensus::aggregate_votes()` of the adapter instead of
+    /// `Crypto::aggregate_signatures()`, which enables hardware accelerated or MPC based
+    /// aggregation services. The engine still collects the votes and verifies the result.
+    pub external_aggregation: bool,
+    /// The memory budget in bytes of the caches of the proposals and the votes, which is
+    /// shared by the caches in proportion to `cache_weights`. A cache over its limit evicts
+    /// the messages of the future heights from the highest one, the messages of the current
+    /// height are always kept. Zero means no limit.
+    pub memory_budget: usize,
+    /// The weights of the caches in the memory budget.
+    pub cache_weights: CacheWeights,
+    /// The number of the rounds below the current round whose signed proposals, votes and
+    /// chokes are kept. The signed messages of the older rounds of the height are evicted as
+    /// the round goes on, while the QCs of the rounds are kept. Zero keeps all of the rounds.
+    pub round_retention: u64,
+    /// The max number of the signed votes of a round of a height, and of the chokes of a
+    /// round. The messages over it are dropped. It should be at least twice the number of the
+    /// authorities, since each of them signs two votes in a round. Zero means no cap.
+    pub round_message_cap: usize,
+    /// The number of the past heights below the last committed one whose proposals, votes and
+    /// QCs are kept in the caches, for serving the late peers and the vote export. The
+    /// messages of the older heights are evicted as the node goes to a new height, and the
+    /// memory budget does not evict the kept ones. Zero keeps only the last committed height.
+    pub height_retention: u64,
+    /// Hand each past height evicted by `height_retention` to `Consensus::archive_height()`
+    /// of the adapter before it is dropped, including the heights abandoned by a jump.
+    pub archive_expired_heights: bool,
+    /// The number of the message channels that the handlers of the instance are spread
+    /// over, which reduces the contention of the handlers sending from many threads. The
+    /// messages sent by one handler clone keep their order, while those of the different
+    /// clones are received in any order. A value less than two means a single channel.
+    pub sender_shards: usize,
+    /// Milliseconds to wait after the instance starts before the first step, which gives the
+    /// network layer time to connect to the peers. Zero means no delay.
+    pub startup_delay: u64,
+    /// Wait until `Consensus::network_ready()` of the adapter returns true before the first
+    /// step, so that the first rounds are not burnt by the timeouts while the peers are
+    /// connecting. The messages received meanwhile are kept in the channel.
+    pub ready_gate: bool,
+    /// Milliseconds after which the first step starts even if the network is not ready. Zero
+    /// means waiting until it is ready.
+    pub ready_timeout: u64,
+    /// Milliseconds without a height progress after which the node reports a dump of its
+    /// state by `Consensus::report_stall()`. Zero disables the watchdog.
+    pub stall_timeout: u64,
+    /// The number of the last processed messages kept for the stall dump.
+    pub stall_history: usize,
+    /// Milliseconds in which the node reports its connectivity by
+    /// `Consensus::report_connectivity()` if the authorities that it has heard from can not
+    /// reach a quorum. Zero disables the report.
+    pub connectivity_window: u64,
+    /// Report the timing of each committed height by `Consensus::report_height()`. The node
+    /// records the arrival of the votes and estimates the bytes of the messages it transfers
+    /// only if it is enabled.
+    pub height_report: bool,
+    /// Milliseconds between the signed heartbeats that the node broadcasts regardless of the
+    /// round progress, whose liveness is read by `Mlm::validator_liveness()`. Zero disables
+    /// the heartbeats.
+    pub heartbeat_interval: u64,
+    /// Milliseconds between the signed attestations of the state of the node, which are
+    /// delivered to `Consensus::report_attestation()` for the external monitors. Zero
+    /// disables the attestations.
+    pub attestation_interval: u64,
+    /// The number of the brake rounds in a row after which the node escalates by
+    /// `Consensus::on_consensus_halted()`, so that the application can recover it, such as by
+    /// a resync or an alert. A brake round is a choke broadcast by the brake step of a round
+    /// or by each brake timeout of it while the chokes do not reach a quorum. The node
+    /// escalates again every such number of the brake rounds until a round ends without a
+    /// brake or the node reaches the next height. Zero disables the escalation.
+    pub max_brake_rounds: u64,
+    /// The number of the rounds of a height after which the node escalates by
+    /// `Consensus::on_max_rounds()`, such as to call for a manual intervention or to fall
+    /// back to another authority list, instead of running the rounds forever. The node
+    /// escalates when it enters the round of this number, and again every such number of
+    /// rounds until it reaches the next height. Zero disables the escalation.
+    pub max_rounds: u64,
+    /// The number of the heights between the checkpoints, the full snapshots of the consensus
+    /// state saved by `Wal::save_checkpoint()` when the node reaches a multiple of it. The
+    /// node recovers from the latest checkpoint if the wal information is lost or older than
+    /// it. Zero disables the checkpoints.
+    pub checkpoint_interval: u64,
+    /// The max number of the buffered messages of the future heights and rounds from each
+    /// signer. Zero means no cap of the signers.
+    pub future_buffer_per_peer: usize,
+    /// The max number of the buffered messages of the future heights and rounds. The messages
+    /// are replayed when the node reaches their height and round, and the least recent ones
+    /// are evicted over the caps. Zero disables the buffer, and such messages are cached by
+    /// the handlers as before.
+    pub future_buffer_total: usize,
+    /// The messages per second of each peer that the node verifies, the messages over it are
+    /// dropped before the verification so that a misbehaving peer cannot saturate the
+    /// verification pool. The messages without a transport peer are limited by their signers.
+    /// Zero disables the rate limit.
+    pub rate_limit: u64,
+    /// The max number of the messages of each peer verified in a burst over the rate.
+    pub rate_limit_burst: u64,
+    /// The max number of the votes of the current height kept in the seen-set, by which the
+    /// exact duplicates of a vote delivered by a gossip network are dropped before the
+    /// verification. Zero disables the seen-set.
+    pub vote_dedup_capacity: usize,
+    /// The max distance of the message heights from the current height. The messages out of
+    /// the window are dropped and counted before the verification. Zero disables the window.
+    pub height_window: u64,
+    /// Report the messages out of the height window by `Consensus::report_error()`.
+    pub report_out_of_window: bool,
+    /// Broadcast the proposals without the blocks, and the other nodes fetch the block of a
+    /// proposal from the proposer once. This saves the bandwidth of the large blocks, and costs
+    /// a round trip before the block is checked. The adapter must deliver the `CompactProposal`,
+    /// `GetBlock` and `BlockResponse` messages to the handler.
+    pub compact_proposal: bool,
+    /// Milliseconds to wait for `Consensus::check_availability()` before prevoting in the
+    /// compact proposal mode, after which the node prevotes nil. Zero means the prevote
+    /// timeout.
+    pub availability_timeout: u64,
+    /// The max size in bytes of the encoded block broadcast in one proposal. The block of a
+    /// larger proposal is split into the `BlockPart`s of this size, whose merkle root is
+    /// carried by a `PartedProposal`, and the other nodes reassemble and verify the parts. This
+    /// keeps the proposals under the message size ceiling of the transport. The adapter must
+    /// deliver the `PartedProposal` and `BlockPart` messages to the handler. Zero disables the
+    /// parts.
+    pub block_part_size: usize,
+    /// The id of the run of the instance, which tags the wal records. A record tagged with
+    /// another run id is refused when the wal is loaded unless `WalMigration::adopt_run()`
+    /// adopts it, so a node never starts from the wal of another run sharing the storage. The
+    /// id should be kept across the restarts of the same run. Empty means untagged records.
+    #[serde(with = "crate::serde_hex")]
+    pub run_id: Bytes,
+    /// The hints of the threads that run the state loop, the verification pool, the SMR and
+    /// the timer. The default runs them on the runtime of the caller.
+    pub thread_hints: ThreadHints,
+    /// The retry policy of `Consensus::get_block()` and `Consensus::check_block()`. A failed
+    /// call is retried until it succeeds, the attempts run out or the deadline passes, and the
+    /// terminal failure is reported by `Consensus::report_error()`. The default makes a single
+    /// attempt.
+    pub adapter_retry: AdapterRetry,
+    /// Milliseconds that the proposer waits for something to propose, if
+    /// `Consensus::get_block()` returns `ConsensusError::NothingToPropose`. The call is
+    /// repeated meanwhile, and an empty block is asked for after the wait. The wait is bounded
+    /// by the propose timeout. Zero disables the empty block suppression, and every call
+    /// allows an empty block.
+    pub empty_block_wait: u64,
+    /// Never propose an empty block in the empty block suppression. The rounds time out
+    /// without a proposal until there is something to propose, so no height is produced.
+    pub skip_empty_blocks: bool,
+    /// The bounds of the effective interval that the proposer of the next height waits for
+    /// after a commit. The effective interval is tuned by the recent block times so that they
+    /// stay near the interval of the status, which becomes the target. The timeouts of the
+    /// steps still follow the interval of the status.
+    pub interval_tuning: IntervalTuning,
+    /// The max percentage that each step timeout is randomly lengthened by, so that the nodes
+    /// do not time out at the same instant and send the chokes all at once. The jitter is
+    /// seeded by the address of the node. Zero disables the jitter, and the values above a
+    /// hundred are taken as a hundred.
+    pub timer_jitter: u64,
+    /// The id of the instance among the instances of a process, such as the id of the shard
+    /// or the chain. It labels the events, the errors, the send metrics, the spans and the
+    /// timer logs of the instance, and should also name the column family of the instance in
+    /// a shared `RocksWal`. Empty means a single instance.
+    pub instance_id: String,
+    /// The byte length of the block hashes, which is the output length of `Crypto::hash()`
+    /// of the chain, such as 20 or 64. A message carrying a non empty hash of another length
+    /// is dropped and reported before the verification, and so is a block of the adapter
+    /// whose hash is of another length. Zero means any length.
+    pub hash_len: usize,
+    /// Milliseconds that the timestamp of a proposal may be ahead of the local clock. The
+    /// proposers stamp their proposals, and a node drops a proposal of its height without a
+    /// timestamp, ahead of its clock by more than it, or not later than the median time of
+    /// the recent commits, and reports it as an invalid message. The validated timestamp is
+    /// carried by the `RoundContext` of `check_block()` and by the `Commit`. Every node of
+    /// the network should enable it together. Zero disables the timestamps.
+    pub timestamp_drift: u64,
+    /// The number of the recent commits whose median time the timestamp of a proposal must
+    /// be later than. The commits are counted since the node starts. Zero means eleven.
+    pub median_time_window: usize,
+    /// The number of the recent heights whose commit proofs are kept in memory, from which
+    /// `MlmHandler::get_proof()` serves the proofs to the syncing peers without the storage
+    /// of the application. The proofs of the lower heights are dropped over it. Zero
+    /// disables the archive.
+    pub proof_archive_size: usize,
+    /// Save the archive of the commit proofs by `Wal::save_proof_archive()` on each commit,
+    /// so that a restarted node keeps serving the proofs of the heights before the restart.
+    pub persist_proof_archive: bool,
+    /// Save the signed votes collected in the current round by `Wal::save_round_votes()`, so
+    /// that a relayer restarted before the QC of the round restores them and still forms the
+    /// QC instead of timing out the round. Only the votes of one round are saved, which are
+    /// at most two of each authority.
+    pub persist_round_votes: bool,
+    /// The max number of the messages that the state handles before it yields to the other
+    /// tasks of the runtime. Zero means sixty four.
+    pub message_budget: usize,
+    /// The max number of the messages that the inboxes of the state drain from their
+    /// channels in one poll, which are ordered by their priority. Zero means two hundred and
+    /// fifty six.
+    pub inbox_drain_limit: usize,
+    /// The max number of the messages whose signatures are verified at the same time. The
+    /// other messages wait for their turns before the verification. Zero means no limit.
+    pub verify_concurrency: usize,
+}
+
+/// The configuration that an instance runs with, returned by `MlmHandler::effective_config()`
+/// and published by the `ConfigResolved` event when the state starts. The timeouts are
+/// resolved from the height interval and the timeout ratios in effect, and the options whose
+/// zero means a default carry the value in use, so an operator reads the values of the live
+/// timers rather than the configured ones. It is serializable to JSON for an admin endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EffectiveConfig {
+    /// The height interval in milliseconds.
+    pub interval_ms: u64,
+    /// The interval tuned by the recent block times in milliseconds, which is the height
+    /// interval unless `MlmConfig::interval_tuning` is enabled.
+    pub tuned_interval_ms: u64,
+    /// The timeout ratios in effect, which are the classic ones unless a status or
+    /// `MlmHandler::update_timer_config()` sets them.
+    pub timer_config: DurationConfig,
+    /// The timeout of the propose step of the first round in milliseconds.
+    pub propose_timeout_ms: u64,
+    /// The timeout of the prevote step of the first round in milliseconds.
+    pub prevote_timeout_ms: u64,
+    /// The timeout of the precommit step of the first round in milliseconds.
+    pub precommit_timeout_ms: u64,
+    /// The timeout of the brake step of the first round in milliseconds.
+    pub brake_timeout_ms: u64,
+    /// The backoff policy of the timeouts on the repeated rounds.
+    pub backoff: BackoffConfig,
+    /// The max percentage that each step timeout is randomly lengthened by.
+    pub timer_jitter: u64,
+    /// Milliseconds to wait for a proposal of each lower rank.
+    pub proposal_wait_ms: u64,
+    /// Milliseconds to wait for the async crypto to sign.
+    pub sign_timeout_ms: u64,
+    /// Milliseconds between the probes of an unavailable signer.
+    pub signer_retry_interval_ms: u64,
+    /// Milliseconds to wait for the data of a block in the compact proposal mode.
+    pub availability_timeout_ms: u64,
+    /// The max number of the messages that the state handles before it yields.
+    pub message_budget: usize,
+    /// The max number of the messages drained from the inbox channels in one poll.
+    pub inbox_drain_limit: usize,
+    /// The number of the recent commits whose median time bounds the proposal timestamps.
+    pub median_time_window: usize,
+    /// The cargo features enabled in the build.
+    pub features: Vec<String>,
+    /// The engine options as they are configured.
+    pub mlm_config: MlmConfig,
+}
+
+/// The names of the cargo features enabled in the build.
+#[cfg(feature = "runtime")]
+pub(crate) fn enabled_features() -> Vec<String> {
+    FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// The retry policy of the adapter calls of a round. Since a failed check of an invalid block
+/// is retried as well, the attempts should be kept small.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AdapterRetry {
+    /// The max number of the attempts of a call. A value less than two makes a single attempt.
+    pub attempts: u32,
+    /// Milliseconds to wait before the first retry, which doubles on each following retry.
+    pub backoff: u64,
+    /// Milliseconds after the first attempt after which no more retry is made. It is bounded
+    /// by the propose timeout of the round, and zero means the propose timeout.
+    pub deadline: u64,
+}
+
+/// The bounds of the tuned interval, in milliseconds.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IntervalTuning {
+    /// The lower bound of the effective interval.
+    pub min: u64,
+    /// The upper bound of the effective interval. Zero disables the tuning.
+    pub max: u64,
+    /// The number of the recent block times that the interval is tuned by. Zero means ten.
+    pub window: usize,
+}
+
+impl IntervalTuning {
+    /// Create the bounds of the tuned interval.
+    pub fn new(min: u64, max: u64, window: usize) -> Self {
+        IntervalTuning { min, max, window }
+    }
+}
+
+impl AdapterRetry {
+    /// Create a retry policy.
+    pub fn new(attempts: u32, backoff: u64, deadline: u64) -> Self {
+        AdapterRetry {
+            attempts,
+            backoff,
+            deadline,
+        }
+    }
+}
+
+/// The policy of picking the backup relayers of the votes besides the leader.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RelayerPolicy {
+    /// The authorities following the leader in the sorted authority list, which are the hops
+    /// of the relayer fallback chain.
+    #[default]
+    FallbackChain,
+    /// The proposers of the next rounds of the height, which are likely to be online since
+    /// they are going to propose.
+    NextProposers,
+}
+
+impl MlmConfig {
+    /// Set whether to run consensus in pipelined mode.
+    pub fn set_pipelined(&mut self, pipelined: bool) {
+        self.pipelined = pipelined;
+    }
+
+    /// Set whether to move to the round that a quorum is proven to be at.
+    pub fn set_fast_resync(&mut self, fast_resync: bool) {
+        self.fast_resync = fast_resync;
+    }
+
+    /// Set whether to extend the precommits with the payloads of the application.
+    pub fn set_vote_extensions(&mut self, vote_extensions: bool) {
+        self.vote_extensions = vote_extensions;
+    }
+
+    /// Set the proportion of the step timeout in tenths after which a vote is resent.
+    pub fn set_vote_resend_ratio(&mut self, ratio: u64) {
+        self.vote_resend_ratio = ratio;
+    }
+
+    /// Set the proportion of the step timeout in tenths after which a vote falls back to the
+    /// next relayer.
+    pub fn set_relayer_fallback_ratio(&mut self, ratio: u64) {
+        self.relayer_fallback_ratio = ratio;
+    }
+
+    /// Set the number of the backup relayers of the votes and the policy of picking them.
+    pub fn set_backup_relayers(&mut self, count: usize, policy: RelayerPolicy) {
+        self.backup_relayers = count;
+        self.relayer_policy = policy;
+    }
+
+    /// Set whether to attach the timeout certificates to the proposals.
+    pub fn set_timeout_cert(&mut self, timeout_cert: bool) {
+        self.timeout_cert = timeout_cert;
+    }
+
+    /// Set whether to re-broadcast the QCs received from the network.
+    pub fn set_gossip_qc(&mut self, gossip_qc: bool) {
+        self.gossip_qc = gossip_qc;
+    }
+
+    /// Set the milliseconds to wait for the async crypto to sign.
+    pub fn set_sign_timeout(&mut self, sign_timeout: u64) {
+        self.sign_timeout = sign_timeout;
+    }
+
+    /// Set the milliseconds between the probes of the signer while it is unavailable.
+    pub fn set_signer_retry_interval(&mut self, signer_retry_interval: u64) {
+        self.signer_retry_interval = signer_retry_interval;
+    }
+
+    /// Set whether to aggregate the votes by the aggregation service of the adapter.
+    pub fn set_external_aggregation(&mut self, external_aggregation: bool) {
+        self.external_aggregation = external_aggregation;
+    }
+
+    /// Set the memory budget in bytes of the caches and the weights of the caches in it.
+    pub fn set_memory_budget(&mut self, budget: usize, weights: CacheWeights) {
+        self.memory_budget = budget;
+        self.cache_weights = weights;
+    }
+
+    /// Set the number of the rounds kept below the current round and the max number of the
+    /// signed messages of a round.
+    pub fn set_round_caps(&mut self, retention: u64, message_cap: usize) {
+        self.round_retention = retention;
+        self.round_message_cap = message_cap;
+    }
+
+    /// Set the number of the past heights kept below the last committed one and whether to
+    /// archive the evicted ones by the adapter.
+    pub fn set_height_retention(&mut self, retention: u64, archive_expired: bool) {
+        self.height_retention = retention;
+        self.archive_expired_heights = archive_expired;
+    }
+
+    /// Set the number of the message channels that the handlers are spread over.
+    pub fn set_sender_shards(&mut self, shards: usize) {
+        self.sender_shards = shards;
+    }
+
+    /// Set the milliseconds to wait before the first step.
+    pub fn set_startup_delay(&mut self, startup_delay: u64) {
+        self.startup_delay = startup_delay;
+    }
+
+    /// Set whether to wait for the network to be ready before the first step and the
+    /// milliseconds to wait at most.
+    pub fn set_ready_gate(&mut self, ready_gate: bool, ready_timeout: u64) {
+        self.ready_gate = ready_gate;
+        self.ready_timeout = ready_timeout;
+    }
+
+    /// Set the milliseconds without a height progress after which the node reports a stall
+    /// and the number of the last messages kept for the dump.
+    pub fn set_stall_watchdog(&mut self, stall_timeout: u64, stall_history: usize) {
+        self.stall_timeout = stall_timeout;
+        self.stall_history = stall_history;
+    }
+
+    /// Set the milliseconds in which the node reports its connectivity if it has not heard
+    /// from a quorum.
+    pub fn set_connectivity_window(&mut self, connectivity_window: u64) {
+        self.connectivity_window = connectivity_window;
+    }
+
+    /// Set whether to report the timing of each committed height.
+    pub fn set_height_report(&mut self, height_report: bool) {
+        self.height_report = height_report;
+    }
+
+    /// Set the milliseconds between the heartbeats of the node.
+    pub fn set_heartbeat_interval(&mut self, heartbeat_interval: u64) {
+        self.heartbeat_interval = heartbeat_interval;
+    }
+
+    /// Set the milliseconds between the attestations of the node.
+    pub fn set_attestation_interval(&mut self, attestation_interval: u64) {
+        self.attestation_interval = attestation_interval;
+    }
+
+    /// Set the number of the brake rounds in a row after which the node escalates.
+    pub fn set_max_brake_rounds(&mut self, max_brake_rounds: u64) {
+        self.max_brake_rounds = max_brake_rounds;
+    }
+
+    /// Set the number of the rounds of a height after which the node escalates.
+    pub fn set_max_rounds(&mut self, max_rounds: u64) {
+        self.max_rounds = max_rounds;
+    }
+
+    /// Set the number of the heights between the checkpoints.
+    pub fn set_checkpoint_interval(&mut self, checkpoint_interval: u64) {
+        self.checkpoint_interval = checkpoint_interval;
+    }
+
+    /// Set the caps of the buffer of the messages ahead of the node, of each signer and of all.
+    pub fn set_future_buffer(&mut self, per_peer: usize, total: usize) {
+        self.future_buffer_per_peer = per_peer;
+        self.future_buffer_total = total;
+    }
+
+    /// Set the messages per second and the burst of each peer that the node verifies.
+    pub fn set_rate_limit(&mut self, rate: u64, burst: u64) {
+        self.rate_limit = rate;
+        self.rate_limit_burst = burst;
+    }
+
+    /// Set the max number of the votes kept in the seen-set of the duplicate votes.
+    pub fn set_vote_dedup_capacity(&mut self, capacity: usize) {
+        self.vote_dedup_capacity = capacity;
+    }
+
+    /// Set the max distance of the message heights from the current height and whether to
+    /// report the messages out of it.
+    pub fn set_height_window(&mut self, window: u64, report: bool) {
+        self.height_window = window;
+        self.report_out_of_window = report;
+    }
+
+    /// Set whether to broadcast the proposals without the blocks.
+    pub fn set_compact_proposal(&mut self, compact_proposal: bool) {
+        self.compact_proposal = compact_proposal;
+    }
+
+    /// Set the milliseconds to wait for the data availability check before prevoting.
+    pub fn set_availability_timeout(&mut self, availability_timeout: u64) {
+        self.availability_timeout = availability_timeout;
+    }
+
+    /// Set the max size in bytes of the encoded block broadcast in one proposal.
+    pub fn set_block_part_size(&mut self, block_part_size: usize) {
+        self.block_part_size = block_part_size;
+    }
+
+    /// Set the id of the run which tags the wal records.
+    pub fn set_run_id(&mut self, run_id: Bytes) {
+        self.run_id = run_id;
+    }
+
+    /// Set the hints of the threads that run the consensus critical tasks.
+    pub fn set_thread_hints(&mut self, hints: ThreadHints) {
+        self.thread_hints = hints;
+    }
+
+    /// Set the retry policy of the adapter calls to get and to check the blocks.
+    pub fn set_adapter_retry(&mut self, retry: AdapterRetry) {
+        self.adapter_retry = retry;
+    }
+
+    /// Set the milliseconds to wait for something to propose before an empty block and whether
+    /// to never propose an empty block.
+    pub fn set_empty_block_suppression(&mut self, wait: u64, skip: bool) {
+        self.empty_block_wait = wait;
+        self.skip_empty_blocks = skip;
+    }
+
+    /// Set the bounds of the effective interval tuned by the recent block times.
+    pub fn set_interval_tuning(&mut self, tuning: IntervalTuning) {
+        self.interval_tuning = tuning;
+    }
+
+    /// Set the max percentage that each step timeout is randomly lengthened by.
+    pub fn set_timer_jitter(&mut self, timer_jitter: u64) {
+        self.timer_jitter = timer_jitter;
+    }
+
+    /// Set the id of the instance among the instances of a process.
+    pub fn set_instance_id(&mut self, instance_id: String) {
+        self.instance_id = instance_id;
+    }
+
+    /// Set the byte length of the block hashes.
+    pub fn set_hash_len(&mut self, hash_len: usize) {
+        self.hash_len = hash_len;
+    }
+
+    /// Set the milliseconds that the timestamp of a proposal may be ahead of the local clock
+    /// and the number of the recent commits of the median time.
+    pub fn set_proposal_timestamp(&mut self, drift: u64, median_time_window: usize) {
+        self.timestamp_drift = drift;
+        self.median_time_window = median_time_window;
+    }
+
+    /// Set the number of the recent heights whose commit proofs are kept and whether to save
+    /// them to the wal.
+    pub fn set_proof_archive(&mut self, size: usize, persist: bool) {
+        self.proof_archive_size = size;
+        self.persist_proof_archive = persist;
+    }
+
+    /// Set whether to save the signed votes collected in the current round to the wal.
+    pub fn set_persist_round_votes(&mut self, persist_round_votes: bool) {
+        self.persist_round_votes = persist_round_votes;
+    }
+
+    /// Set the max number of the messages handled before the state yields and the max number
+    /// of the messages drained by its inboxes in one poll.
+    pub fn set_message_buffers(
+        &mut self,
+        message_budget: usize,
+        inbox_drain_limit: usize,
+    ) {
+        self.message_budget = message_budget;
+        self.inbox_drain_limit = inbox_drain_limit;
+    }
+
+    /// Set the max number of the messages whose signatures are verified at the same time.
+    pub fn set_verify_concurrency(&mut self, verify_concurrency: usize) {
+        self.verify_concurrency = verify_concurrency;
+    }
+
+    /// Set the number of designated proposers of each round and the wait for each rank.
+    #[cfg(feature = "multi_proposal")]
+    pub fn set_multi_proposal(&mut self, proposer_count: usize, proposal_wait: u64) {
+        self.proposer_count = proposer_count;
+        self.proposal_wait = proposal_wait;
+    }
+
+    /// Set whether to elect the proposers by the VRF and the seed of the election.
+    pub fn set_vrf_leader(&mut self, vrf_leader: bool, vrf_seed: Bytes) {
+        self.vrf_leader = vrf_leader;
+        self.vrf_seed = vrf_seed;
+    }
+}
@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use blst::min_pk::{
+    AggregateSignature, PublicKey as BlsPublicKey, SecretKey as BlsSecretKey,
+    Signature as BlsSignature,
+};
+use blst::BLST_ERROR;
+use bytes::{Bytes, BytesMut};
+use parking_lot::RwLock;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{All, Message, Secp256k1, SecretKey as SecpSecretKey};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::address::{AddressScheme, HashAddressScheme};
+use crate::error::ConsensusError;
+use crate::types::{Address, AggregatedSignature, Hash, Signature};
+use crate::{ConsensusResult, Crypto};
+
+/// The length of a recoverable secp256k1 signature.
+pub const SECP_SIGNATURE_LEN: usize = 65;
+/// The length of a BLS signature.
+pub const BLS_SIGNATURE_LEN: usize = 96;
+/// The length of a BLS public key.
+pub const BLS_PUBKEY_LEN: usize = 48;
+
+const BLS_SIG_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+const BLS_POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// A crypto of secp256k1 and BLS12-381. The addresses are the Ethereum addresses of the
+/// secp256k1 public keys.
+///
+/// A signature is a 65 bytes recoverable secp256k1 signature followed by a 96 bytes BLS
+/// signature of the same hash. The secp256k1 part binds the signature to the address of the
+/// signer, and the BLS part is aggregated by the relayer. An aggregated signature is a 96 bytes
+/// BLS signature, which is verified by the registered BLS public keys of the voters. The BLS
+/// public keys are registered with proofs of possession to prevent rogue key attacks, see
+/// `bls_proof_of_possession()`.
+#[derive(Debug)]
+pub struct DefaultCrypto {
+    secp: Secp256k1<All>,
+    secp_key: SecpSecretKey,
+    bls_key: BlsSecretKey,
+    address: Address,
+    bls_pubkeys: RwLock<HashMap<Address, BlsPublicKey>>,
+}
+
+impl DefaultCrypto {
+    /// Create a crypto with the 32 bytes secp256k1 and BLS private keys, and the BLS public
+    /// keys of the authorities with their proofs of possession.
+    pub fn new(
+        secp_private_key: &[u8],
+        bls_private_key: &[u8],
+        bls_pubkeys: HashMap<Address, (Bytes, Bytes)>,
+    ) -> ConsensusResult<Self> {
+        let secp = Secp256k1::new();
+        let secp_key = SecpSecretKey::from_slice(secp_private_key)
+            .map_err(|e| ConsensusError::CryptoErr(format!("{:?}", e)))?;
+        let bls_key = BlsSecretKey::from_bytes(bls_private_key)
+            .map_err(|e| ConsensusError::CryptoErr(format!("{:?}", e)))?;
+
+        let pubkey = secp_key.public_key(&secp).serialize_uncompressed();
+        let address = HashAddressScheme::default()
+            .address_from_pubkey(&Bytes::copy_from_slice(&pubkey[1..]))?;
+
+        let crypto = DefaultCrypto {
+            secp,
+            secp_key,
+            bls_key,
+            address,
+            bls_pubkeys: RwLock::new(HashMap::new()),
+        };
+        crypto.update_bls_pubkeys(bls_pubkeys)?;
+        Ok(crypto)
+    }
+
+    /// Replace the BLS public keys of the authorities, which should be called when the
+    /// authority list changes. Each key is given with its proof of possession, which is
+    /// verified unless the key is already registered for the address. The keys are kept
+    /// unchanged if any of the proofs is invalid.
+    pub fn update_bls_pubkeys(
+        &self,
+        bls_pubkeys: HashMap<Address, (Bytes, Bytes)>,
+    ) -> ConsensusResult<()> {
+        let mut pubkeys = HashMap::with_capacity(bls_pubkeys.len());
+        {
+            let registered = self.bls_pubkeys.read();
+            for (address, (pubkey, pop)) in bls_pubkeys.into_iter() {
+                let key = bls_pubkey_from_bytes(&pubkey)?;
+                if registered.get(&address) != Some(&key)
+                    && !verify_proof_of_possession(&pubkey, &pop)
+                {
+                    return Err(ConsensusError::CryptoErr(format!(
+                        "Invalid proof of possession of the BLS public key of {:?}",
+                        address
+                    )));
+                }
+                pubkeys.insert(address, key);
+            }
+        }
+        *self.bls_pubkeys.write() = pubkeys;
+        Ok(())
+    }
+
+    /// The address of self.
+    pub fn address(&self) -> Address {
+        self.address.clone()
+    }
+
+    /// The BLS public key of self.
+    pub fn bls_pubkey(&self) -> Bytes {
+        Bytes::copy_from_slice(&self.bls_key.sk_to_pk().to_bytes())
+    }
+
+    /// The proof of possession of the BLS private key, which is registered with the BLS public
+    /// key.
+    pub fn bls_proof_of_possession(&self) -> Bytes {
+        let pubkey = self.bls_key.sk_to_pk().to_bytes();
+        let pop = self.bls_key.sign(&pubkey, BLS_POP_DST, &[]);
+        Bytes::copy_from_slice(&pop.to_bytes())
+    }
+
+    fn secp_message(hash: &Hash) -> Result<Message, Box<dyn Error + Send>> {
+        Message::from_digest_slice(hash).map_err(crypto_err)
+    }
+}
+
+impl Crypto for DefaultCrypto {
+    type QcSignature = AggregatedSignature;
+
+    fn hash(&self, msg: Bytes) -> Hash {
+        Hash::from(keccak256(&msg))
+    }
+
+    fn sign(&self, hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+        let msg = DefaultCrypto::secp_message(&hash)?;
+        let (recovery_id, secp_sig) = self
+            .secp
+            .sign_ecdsa_recoverable(&msg, &self.secp_key)
+            .serialize_compact();
+        let bls_sig = self.bls_key.sign(&hash, BLS_SIG_DST, &[]);
+
+        let mut res = BytesMut::with_capacity(SECP_SIGNATURE_LEN + BLS_SIGNATURE_LEN);
+        res.extend_from_slice(&secp_sig);
+        res.extend_from_slice(&[recovery_id.to_i32() as u8]);
+        res.extend_from_slice(&bls_sig.to_bytes());
+        Ok(res.freeze())
+    }
+
+    fn aggregate_signatures(
+        &self,
+        signatures: Vec<Signature>,
+        _voters: Vec<Address>,
+    ) -> Result<Signature, Box<dyn Error + Send>> {
+        aggregate_signatures(&signatures)
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+    }
+
+    fn fold_signature(
+        &self,
+        aggregate: Option<&Signature>,
+        signature: &Signature,
+        _voter: &Address,
+    ) -> Result<Option<Signature>, Box<dyn Error + Send>> {
+        let sig =
+            bls_part(signature).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        let folded = match aggregate {
+            Some(aggregate) => {
+                let aggregate =
+                    BlsSignature::from_bytes(aggregate).map_err(crypto_err)?;
+                let mut folded = AggregateSignature::from_signature(&aggregate);
+                folded.add_signature(&sig, true).map_err(crypto_err)?;
+                folded
+            }
+            None => AggregateSignature::aggregate(&[&sig], true).map_err(crypto_err)?,
+        };
+        Ok(Some(Bytes::copy_from_slice(
+            &folded.to_signature().to_bytes(),
+        )))
+    }
+
+    fn verify_signature(
+        &self,
+        signature: Signature,
+        hash: Hash,
+        voter: Address,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        if signature.len() != SECP_SIGNATURE_LEN + BLS_SIGNATURE_LEN {
+            return Err(crypto_err("Invalid signature length"));
+        }
+
+        let msg = DefaultCrypto::secp_message(&hash)?;
+        let recovery_id = RecoveryId::from_i32(signature[SECP_SIGNATURE_LEN - 1] as i32)
+            .map_err(crypto_err)?;
+        let secp_sig = RecoverableSignature::from_compact(
+            &signature[..SECP_SIGNATURE_LEN - 1],
+            recovery_id,
+        )
+        .map_err(crypto_err)?;
+        let pubkey = self
+            .secp
+            .recover_ecdsa(&msg, &secp_sig)
+            .map_err(crypto_err)?
+            .serialize_uncompressed();
+        let address = HashAddressScheme::default()
+            .address_from_pubkey(&Bytes::copy_from_slice(&pubkey[1..]))
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        if address != voter {
+            return Err(crypto_err("Signature of another address"));
+        }
+
+        // The BLS part is checked as well, otherwise an invalid one poisons the aggregated
+        // signature.
+        let pubkeys = self.bls_pubkeys.read();
+        let bls_pubkey = pubkeys
+            .get(&voter)
+            .ok_or_else(|| crypto_err("No BLS public key of the voter"))?;
+        let bls_sig = BlsSignature::from_bytes(&signature[SECP_SIGNATURE_LEN..])
+            .map_err(crypto_err)?;
+        match bls_sig.verify(true, &hash, BLS_SIG_DST, &[], bls_pubkey, true) {
+            BLST_ERROR::BLST_SUCCESS => Ok(()),
+            e => Err(crypto_err(e)),
+        }
+    }
+
+    fn verify_aggregated_signature(
+        &self,
+        aggregate_signature: Signature,
+        msg_hash: Hash,
+        voters: Vec<Address>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let all_pubkeys = self.bls_pubkeys.read();
+        let pubkeys = voters
+            .iter()
+            .map(|voter| {
+                all_pubkeys
+                    .get(voter)
+                    .ok_or_else(|| crypto_err("No BLS public key of the voter"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if pubkeys.is_empty() {
+            return Err(crypto_err("No voters"));
+        }
+
+        let sig = BlsSignature::from_bytes(&aggregate_signature).map_err(crypto_err)?;
+        match sig.fast_aggregate_verify(true, &msg_hash, BLS_SIG_DST, &pubkeys) {
+            BLST_ERROR::BLST_SUCCESS => Ok(()),
+            e => Err(Box::new(ConsensusError::AggregatedSignatureErr(format!(
+                "{:?}",
+                e
+            )))),
+        }
+    }
+}
+
+/// Aggregate the BLS parts of the signatures of `DefaultCrypto`. The relayer can call this to
+/// build an aggregated vote without a private key.
+pub fn aggregate_signatures(signatures: &[Signature]) -> ConsensusResult<Signature> {
+    let bls_sigs = signatures
+        .iter()
+        .map(bls_part)
+        .collect::<ConsensusResult<Vec<_>>>()?;
+    if bls_sigs.is_empty() {
+        return Err(ConsensusError::AggregatedSignatureErr(
+            "No signatures".to_string(),
+        ));
+    }
+
+    let sig_refs = bls_sigs.iter().collect::<Vec<_>>();
+    let aggregated = AggregateSignature::aggregate(&sig_refs, true)
+        .map_err(|e| ConsensusError::AggregatedSignatureErr(format!("{:?}", e)))?;
+    Ok(Bytes::copy_from_slice(
+        &aggregated.to_signature().to_bytes(),
+    ))
+}
+
+fn bls_part(signature: &Signature) -> ConsensusResult<BlsSignature> {
+    if signature.len() != SECP_SIGNATURE_LEN + BLS_SIGNATURE_LEN {
+        return Err(ConsensusError::CryptoErr(
+            "Invalid signature length".to_string(),
+        ));
+    }
+    BlsSignature::from_bytes(&signature[SECP_SIGNATURE_LEN..])
+        .map_err(|e| ConsensusError::CryptoErr(format!("{:?}", e)))
+}
+
+/// Verify the proof of possession of a BLS public key before registering it.
+pub fn verify_proof_of_possession(bls_pubkey: &Bytes, pop: &Bytes) -> bool {
+    let (pubkey, pop) = match (
+        bls_pubkey_from_bytes(bls_pubkey),
+        BlsSignature::from_bytes(pop),
+    ) {
+        (Ok(pubkey), Ok(pop)) => (pubkey, pop),
+        _ => return false,
+    };
+    pop.verify(true, bls_pubkey, BLS_POP_DST, &[], &pubkey, true)
+        == BLST_ERROR::BLST_SUCCESS
+}
+
+fn bls_pubkey_from_bytes(pubkey: &[u8]) -> ConsensusResult<BlsPublicKey> {
+    if pubkey.len() != BLS_PUBKEY_LEN {
+        return Err(ConsensusError::CryptoErr(
+            "Invalid BLS public key length".to_string(),
+        ));
+    }
+    BlsPublicKey::key_validate(pubkey)
+        .map_err(|e| ConsensusError::CryptoErr(format!("{:?}", e)))
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut res = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(data);
+    hasher.finalize(&mut res);
+    res
+}
+
+fn crypto_err<E: std::fmt::Debug>(e: E) -> Box<dyn Error + Send> {
+    Box::new(ConsensusError::CryptoErr(format!("{:?}", e)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use bytes::Bytes;
+
+    use super::{aggregate_signatures, verify_proof_of_possession, DefaultCrypto};
+    use crate::Crypto;
+
+    fn gen_cryptos(n: u8) -> Vec<DefaultCrypto> {
+        let keys = (1..=n)
+            .map(|i| (vec![i; 32], vec![i + 100; 32]))
+            .collect::<Vec<_>>();
+        let mut pubkeys = HashMap::new();
+        for (secp_key, bls_key) in keys.iter() {
+            let crypto = DefaultCrypto::new(secp_key, bls_key, HashMap::new()).unwrap();
+            let pop = crypto.bls_proof_of_possession();
+            pubkeys.insert(crypto.address(), (crypto.bls_pubkey(), pop));
+        }
+
+        keys.iter()
+            .map(|(secp_key, bls_key)| {
+                DefaultCrypto::new(secp_key, bls_key, pubkeys.clone()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let cryptos = gen_cryptos(2);
+        let hash = cryptos[0].hash(Bytes::from("mlm"));
+        let sig = cryptos[0].sign(hash.clone()).unwrap();
+
+        assert!(cryptos[1]
+            .verify_signature(sig.clone(), hash.clone(), cryptos[0].address())
+            .is_ok());
+        assert!(cryptos[1]
+            .verify_signature(sig.clone(), hash.clone(), cryptos[1].address())
+            .is_err());
+
+        let other_hash = cryptos[0].hash(Bytes::from("other"));
+        assert!(cryptos[1]
+            .verify_signature(sig, other_hash, cryptos[0].address())
+            .is_err());
+    }
+
+    #[test]
+    fn test_aggregated_signature() {
+        let cryptos = gen_cryptos(4);
+        let hash = cryptos[0].hash(Bytes::from("mlm"));
+        let sigs = cryptos
+            .iter()
+            .map(|c| c.sign(hash.clone()).unwrap())
+            .collect::<Vec<_>>();
+        let voters = cryptos.iter().map(|c| c.address()).collect::<Vec<_>>();
+
+        let aggregated = cryptos[0]
+            .aggregate_signatures(sigs[..3].to_vec(), voters[..3].to_vec())
+            .unwrap();
+        assert_eq!(aggregated, aggregate_signatures(&sigs[..3]).unwrap());
+        assert!(cryptos[3]
+            .verify_aggregated_signature(
+                aggregated.clone(),
+                hash.clone(),
+                voters[..3].to_vec()
+            )
+            .is_ok());
+        assert!(cryptos[3]
+            .verify_aggregated_signature(aggregated, hash, voters[1..].to_vec())
+            .is_err());
+    }
+
+    #[test]
+    fn test_fold_signature() {
+        let cryptos = gen_cryptos(4);
+        let hash = cryptos[0].hash(Bytes::from("mlm"));
+        let sigs = cryptos
+            .iter()
+            .map(|c| c.sign(hash.clone()).unwrap())
+            .collect::<Vec<_>>();
+
+        // The signatures folded one by one aggregate to the one aggregated at once.
+        let mut folded = None;
+        for (sig, crypto) in sigs.iter().zip(cryptos.iter()).rev() {
+            folded = cryptos[0]
+                .fold_signature(folded.as_ref(), sig, &crypto.address())
+                .unwrap();
+        }
+        assert_eq!(folded.unwrap(), aggregate_signatures(&sigs).unwrap());
+        assert!(cryptos[0]
+            .fold_signature(None, &Bytes::from(vec![1u8; 8]), &cryptos[0].address())
+            .is_err());
+    }
+
+    #[test]
+    fn test_proof_of_possession() {
+        let cryptos = gen_cryptos(2);
+        let pop = cryptos[0].bls_proof_of_possession();
+        assert!(verify_proof_of_possession(&cryptos[0].bls_pubkey(), &pop));
+        assert!(!verify_proof_of_possession(&cryptos[1].bls_pubkey(), &pop));
+    }
+
+    #[test]
+    fn test_update_bls_pubkeys() {
+        let cryptos = gen_cryptos(2);
+        let rogue =
+            DefaultCrypto::new(&[9u8; 32], &[109u8; 32], HashMap::new()).unwrap();
+        let hash = cryptos[0].hash(Bytes::from("mlm"));
+        let sig = cryptos[0].sign(hash.clone()).unwrap();
+        let aggregated = aggregate_signatures(&[sig]).unwrap();
+        let voters = vec![cryptos[0].address()];
+
+        // A new key whose proof of possession is of another key is rejected, and the
+        // registered keys are kept.
+        let mut pubkeys = HashMap::new();
+        pubkeys.insert(
+            cryptos[0].address(),
+            (cryptos[0].bls_pubkey(), Bytes::new()),
+        );
+        pubkeys.insert(
+            rogue.address(),
+            (rogue.bls_pubkey(), cryptos[1].bls_proof_of_possession()),
+        );
+        assert!(cryptos[1].update_bls_pubkeys(pubkeys).is_err());
+        assert!(cryptos[1]
+            .verify_aggregated_signature(
+                aggregated.clone(),
+                hash.clone(),
+                voters.clone()
+            )
+            .is_ok());
+
+        // A registered key moved to another address is new for the address.
+        let mut pubkeys = HashMap::new();
+        pubkeys.insert(rogue.address(), (cryptos[0].bls_pubkey(), Bytes::new()));
+        assert!(cryptos[1].update_bls_pubkeys(pubkeys).is_err());
+
+        // A registered key is kept without its proof, and a new key with a valid proof is
+        // added.
+        let mut pubkeys = HashMap::new();
+        pubkeys.insert(
+            cryptos[0].address(),
+            (cryptos[0].bls_pubkey(), Bytes::new()),
+        );
+        pubkeys.insert(
+            rogue.address(),
+            (rogue.bls_pubkey(), rogue.bls_proof_of_possession()),
+        );
+        assert!(cryptos[1].update_bls_pubkeys(pubkeys).is_ok());
+        assert!(cryptos[1]
+            .verify_aggregated_signature(aggregated, hash, voters)
+            .is_ok());
+    }
+}
@@ -1,11 +1,20 @@
+#[cfg(feature = "runtime")]
+use std::any::Any;
 #[cfg(test)]
 use std::cmp::{Eq, PartialEq};
 use std::error::Error;
+use std::sync::Arc;
 
+use bytes::Bytes;
 use derive_more::Display;
+use hummer::coding::hex_encode;
 
-/// Mlm consensus error.
+use crate::types::{Address, Hash};
+
+/// Mlm consensus error. The variants are grouped by `kind()`, and more variants may be added,
+/// so a match on it needs a wildcard arm.
 #[derive(Clone, Debug, Display)]
+#[non_exhaustive]
 pub enum ConsensusError {
     ///
     #[display(fmt = "Invalid address")]
@@ -76,23 +85,497 @@ pub enum ConsensusError {
     ///
     #[display(fmt = "Crypto error {}", _0)]
     CryptoErr(String),
+    /// The height of a message is out of the acceptance window.
+    #[display(fmt = "Message height {} out of the window of height {}", msg, local)]
+    OutOfWindow {
+        /// The current height.
+        local: u64,
+        /// The height of the message.
+        msg: u64,
+    },
+    /// The length of a hash differs from the configured hash length.
+    #[display(fmt = "Hash of {} bytes, expect {} bytes", actual, expect)]
+    InvalidHashLength {
+        /// The configured hash length.
+        expect: usize,
+        /// The length of the hash.
+        actual: usize,
+    },
+    /// An internal invariant of the engine is violated.
+    #[display(fmt = "Invariant violation {}", _0)]
+    InvariantErr(String),
+    /// The signer fails to sign, and the node observes the consensus until it is ready.
+    #[display(fmt = "Signer unavailable {}", _0)]
+    SignerUnavailable(String),
+    /// Signing the message conflicts with the last signed message.
+    #[display(fmt = "Double sign error {}", _0)]
+    DoubleSignErr(String),
+    /// A call of the adapter fails after all of the attempts of the retry policy.
+    #[display(fmt = "Adapter {} failed after {} attempts {}", call, attempts, reason)]
+    AdapterErr {
+        /// The name of the call.
+        call: String,
+        /// The number of the attempts made.
+        attempts: u32,
+        /// The error of the last attempt.
+        reason: String,
+    },
+    /// A call of the adapter is cancelled at the deadline of its round, and its late result
+    /// is discarded.
+    #[display(fmt = "Adapter {} cancelled at the deadline", _0)]
+    AdapterTimeout(String),
+    /// Returned by `Consensus::get_block()` if the application has nothing to propose and the
+    /// call does not allow an empty block.
+    #[display(fmt = "Nothing to propose")]
+    NothingToPropose,
     ///
     #[display(fmt = "Aggregated signature error {}", _0)]
     AggregatedSignatureErr(String),
+    /// A message of a newer protocol version or of an unknown type, which is skipped.
+    #[display(fmt = "Unsupported message of version {}, tag {}", version, tag)]
+    UnsupportedMessage {
+        /// The protocol version of the message.
+        version: u8,
+        /// The message tag.
+        tag: u8,
+    },
+    /// The authority list or the weights of the authorities are invalid.
+    #[display(fmt = "Authority error {}", _0)]
+    AuthErr(String),
+    /// The authority list of a status is invalid, and the status is rejected.
+    #[display(fmt = "Invalid authority list of height {}, {}", height, error)]
+    InvalidAuthorityUpdate {
+        /// The height of the status.
+        height: u64,
+        /// What is invalid.
+        error: AuthorityListError,
+    },
+    /// A message or a value fails to decode.
+    #[display(fmt = "Decode error {}", _0)]
+    DecodeErr(String),
+    /// A message or a value fails to encode by the wire codec.
+    #[display(fmt = "Encode error {}", _0)]
+    EncodeErr(String),
+    /// The configuration of an instance is invalid, which is returned by `MlmBuilder`.
+    #[display(fmt = "Invalid config {}", _0)]
+    InvalidConfig(ConfigError),
+    /// The hash of a proposal differs from the hash of its block computed by the adapter.
+    #[display(fmt = "Block hash mismatch {}", _0)]
+    BlockHashMismatch(Box<HashMismatch>),
+    /// An error of the kind caused by the source error.
+    #[display(fmt = "{}: {}", context, source)]
+    Wrapped {
+        /// The kind of the error.
+        kind: ErrorKind,
+        /// What fails.
+        context: String,
+        /// The error that causes it, which is returned by `source()`.
+        source: Arc<dyn Error + Send + Sync>,
+    },
     /// Other error.
     #[display(fmt = "Other error {}", _0)]
     Other(String),
 }
 
-impl Error for ConsensusError {}
+impl Error for ConsensusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConsensusError::Wrapped { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// The reason that an authority list is invalid.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum AuthorityListError {
+    /// The authority list is empty.
+    #[display(fmt = "empty authority list")]
+    Empty,
+    /// An address is in the authority list more than once.
+    #[display(fmt = "duplicate address {}", "hex_encode(_0)")]
+    DuplicateAddress(Address),
+    /// A public key is in the authority list more than once.
+    #[display(fmt = "duplicate public key {}", "hex_encode(_0)")]
+    DuplicatePubkey(Bytes),
+    /// The address of an authority is not derived from its public key by the address scheme.
+    #[display(fmt = "address {} mismatches the public key", "hex_encode(_0)")]
+    AddressMismatch(Address),
+    /// An authority has no vote weight.
+    #[display(fmt = "zero vote weight of {}", "hex_encode(_0)")]
+    ZeroVoteWeight(Address),
+    /// The sum of the vote weights overflows.
+    #[display(fmt = "vote weight sum overflow")]
+    VoteWeightOverflow,
+    /// The sum of the propose weights is zero, so no proposer can be picked.
+    #[display(fmt = "zero propose weight sum")]
+    ZeroProposeWeight,
+    /// The quorums of the threshold policy do not intersect in an honest voter for the
+    /// authority list.
+    #[display(fmt = "threshold policy unsafe for {} authorities", _0)]
+    UnsafeThreshold(usize),
+}
+
+/// The forensic record of a block whose hash differs from the hash of its proposal, which
+/// is reported by `ConsensusError::BlockHashMismatch`.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[display(
+    fmt = "at {} height {}, round {}, proposer {}, proposal hash {}, computed hash {}, \
+           block digest {}",
+    stage,
+    height,
+    round,
+    "hex_encode(proposer)",
+    proposal_hash,
+    computed_hash,
+    block_digest
+)]
+pub struct HashMismatch {
+    /// Where the mismatch is found.
+    pub stage: MismatchStage,
+    /// The height of the block.
+    pub height: u64,
+    /// The round of the proposal.
+    pub round: u64,
+    /// The proposer of the block.
+    pub proposer: Address,
+    /// The hash carried by the proposal.
+    pub proposal_hash: Hash,
+    /// The hash computed by `Consensus::block_hash()`.
+    pub computed_hash: Hash,
+    /// The digest of the encoded block by `Crypto::hash()`, which tells the block apart
+    /// whatever the hashes say. It is empty if the block fails to encode.
+    pub block_digest: Hash,
+}
+
+/// Where a block hash mismatch is found.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum MismatchStage {
+    /// After `Consensus::check_block()` passes, so the proposer lies about the hash.
+    #[display(fmt = "check")]
+    Check,
+    /// Before the block is committed, so the decided hash is not of the committed block.
+    #[display(fmt = "commit")]
+    Commit,
+}
+
+/// The reason that the configuration of an instance is invalid.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// A required option is not given.
+    #[display(fmt = "missing {}", _0)]
+    Missing(&'static str),
+    /// The address of the node is not in the authority list, while it is required to be.
+    #[display(fmt = "address {} not in the authority list", "hex_encode(_0)")]
+    NotAuthority(Address),
+    /// The authority list is invalid.
+    #[display(fmt = "{}", _0)]
+    AuthorityList(AuthorityListError),
+    /// The height interval is zero.
+    #[display(fmt = "zero interval")]
+    ZeroInterval,
+    /// The height interval is out of the bounds of `MlmConfig::interval_tuning`, in
+    /// milliseconds.
+    #[display(fmt = "interval {} out of the bounds [{}, {}]", interval, min, max)]
+    IntervalOutOfBounds {
+        /// The height interval.
+        interval: u64,
+        /// The lower bound.
+        min: u64,
+        /// The upper bound.
+        max: u64,
+    },
+    /// The timeout ratio of the step is zero, so the step times out at once.
+    #[display(fmt = "zero {} timeout ratio", _0)]
+    ZeroTimeoutRatio(&'static str),
+    /// The backoff policy has a zero base or a cap below the base.
+    #[display(fmt = "backoff of base {}, cap {}", base, cap)]
+    InvalidBackoff {
+        /// The coefficient of round zero in tenths.
+        base: u64,
+        /// The max coefficient in tenths.
+        cap: u64,
+    },
+    /// The ratio of a `StakeRatioThreshold` is not above two thirds, or its denominator is
+    /// zero.
+    #[display(
+        fmt = "threshold ratio {}/{} not above two thirds",
+        numerator,
+        denominator
+    )]
+    UnsafeThresholdRatio {
+        /// The numerator of the ratio.
+        numerator: u64,
+        /// The denominator of the ratio.
+        denominator: u64,
+    },
+    /// A capacity of the config is below the least that it should hold.
+    #[display(fmt = "{} of {} below {}", name, capacity, min)]
+    CapacityTooSmall {
+        /// The name of the option.
+        name: &'static str,
+        /// The capacity.
+        capacity: usize,
+        /// The least capacity.
+        min: usize,
+    },
+}
+
+/// The category of a consensus error, for the callers to tell the failures apart without
+/// matching the messages.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Signing, verifying or aggregating the signatures fails.
+    #[display(fmt = "Crypto")]
+    Crypto,
+    /// The wal or the storage fails.
+    #[display(fmt = "Wal")]
+    Wal,
+    /// A channel between the components or a connection to the other nodes is broken.
+    #[display(fmt = "Channel")]
+    Channel,
+    /// An address or the authority list is invalid.
+    #[display(fmt = "Auth")]
+    Auth,
+    /// The evidence of a node breaking the safety, such as the conflicting proposals or
+    /// signatures.
+    #[display(fmt = "Byzantine")]
+    Byzantine,
+    /// A timer or a call with a deadline fails.
+    #[display(fmt = "Timeout")]
+    Timeout,
+    /// An invalid, outdated or undecodable message from the other nodes.
+    #[display(fmt = "Protocol")]
+    Protocol,
+    /// A call of the adapter fails.
+    #[display(fmt = "Adapter")]
+    Adapter,
+    /// The state of the engine is inconsistent.
+    #[display(fmt = "Internal")]
+    Internal,
+    /// The configuration of an instance is invalid.
+    #[display(fmt = "Config")]
+    Config,
+    /// An uncategorized error.
+    #[display(fmt = "Other")]
+    Other,
+}
+
+/// The message of a source error that is not `Sync`, with the messages of its own sources.
+#[derive(Debug, Display)]
+#[display(fmt = "{}", message)]
+struct SourceMessage {
+    message: String,
+    source: Option<Box<SourceMessage>>,
+}
+
+impl SourceMessage {
+    fn new(err: &(dyn Error + 'static)) -> Self {
+        SourceMessage {
+            message: err.to_string(),
+            source: err.source().map(|e| Box::new(SourceMessage::new(e))),
+        }
+    }
+}
+
+impl Error for SourceMessage {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+/// Severity of a consensus error.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorSeverity {
+    /// An invalid or outdated message from the other nodes, the instance goes on as usual.
+    #[display(fmt = "Warning")]
+    Warning,
+    /// A failed step of the local node, which is recovered by the timeouts of the round.
+    #[display(fmt = "Error")]
+    Error,
+    /// The wal or the signing of the node is broken, which needs the attention of the operator.
+    #[display(fmt = "Fatal")]
+    Fatal,
+}
+
+impl ConsensusError {
+    /// An error of the violated invariant, which is delivered as a fatal error instead of
+    /// panicking. It panics with the `strict-panic` feature to debug the violation.
+    pub(crate) fn invariant<S: Into<String>>(msg: S) -> Self {
+        let msg = msg.into();
+        if cfg!(feature = "strict-panic") {
+            panic!("Mlm: invariant violation {}", msg);
+        }
+        ConsensusError::InvariantErr(msg)
+    }
+
+    /// An error of the kind caused by the source error.
+    pub fn with_source<C, E>(kind: ErrorKind, context: C, source: E) -> Self
+    where
+        C: Into<String>,
+        E: Error + Send + Sync + 'static,
+    {
+        ConsensusError::Wrapped {
+            kind,
+            context: context.into(),
+            source: Arc::new(source),
+        }
+    }
+
+    /// An error of the kind caused by an error of the adapter. A source of `ConsensusError`
+    /// is kept as it is, and the other sources are kept as their messages since they are not
+    /// `Sync`.
+    pub fn with_boxed_source<C: Into<String>>(
+        kind: ErrorKind,
+        context: C,
+        source: Box<dyn Error + Send>,
+    ) -> Self {
+        let source: Arc<dyn Error + Send + Sync> =
+            match source.downcast::<ConsensusError>() {
+                Ok(err) => Arc::new(*err),
+                Err(err) => Arc::new(SourceMessage::new(err.as_ref())),
+            };
+        ConsensusError::Wrapped {
+            kind,
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// Get the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ConsensusError::CryptoErr(_)
+            | ConsensusError::AggregatedSignatureErr(_)
+            | ConsensusError::SignerUnavailable(_) => ErrorKind::Crypto,
+            ConsensusError::StorageErr(_)
+            | ConsensusError::SaveWalErr { .. }
+            | ConsensusError::LoadWalErr(_) => ErrorKind::Wal,
+            ConsensusError::ChannelErr(_)
+            | ConsensusError::TriggerSMRErr(_)
+            | ConsensusError::MonitorEventErr(_)
+            | ConsensusError::ThrowEventErr(_) => ErrorKind::Channel,
+            ConsensusError::InvalidAddress
+            | ConsensusError::AuthErr(_)
+            | ConsensusError::InvalidAuthorityUpdate { .. } => ErrorKind::Auth,
+            ConsensusError::MultiProposal(_, _)
+            | ConsensusError::DoubleSignErr(_)
+            | ConsensusError::CorrectnessErr(_)
+            | ConsensusError::BlockHashMismatch(_) => ErrorKind::Byzantine,
+            ConsensusError::TimerErr(_) | ConsensusError::AdapterTimeout(_) => {
+                ErrorKind::Timeout
+            }
+            ConsensusError::ProposalErr(_)
+            | ConsensusError::PrevoteErr(_)
+            | ConsensusError::PrecommitErr(_)
+            | ConsensusError::BrakeErr(_)
+            | ConsensusError::RoundDiff { .. }
+            | ConsensusError::OutOfWindow { .. }
+            | ConsensusError::InvalidHashLength { .. }
+            | ConsensusError::UnsupportedMessage { .. }
+            | ConsensusError::DecodeErr(_) => ErrorKind::Protocol,
+            ConsensusError::AdapterErr { .. } | ConsensusError::NothingToPropose => {
+                ErrorKind::Adapter
+            }
+            ConsensusError::SelfCheckErr(_)
+            | ConsensusError::StateErr(_)
+            | ConsensusError::EncodeErr(_)
+            | ConsensusError::InvariantErr(_) => ErrorKind::Internal,
+            ConsensusError::InvalidConfig(_) => ErrorKind::Config,
+            ConsensusError::Wrapped { kind, .. } => *kind,
+            ConsensusError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    /// Whether the error is fatal, after which the node should not go on without the
+    /// attention of the operator.
+    pub fn is_fatal(&self) -> bool {
+        self.severity() == ErrorSeverity::Fatal
+    }
+
+    /// Get the severity of the error.
+    pub fn severity(&self) -> ErrorSeverity {
+        match self {
+            // A mismatch of the decided block breaks the consistency of the chain.
+            ConsensusError::BlockHashMismatch(mismatch)
+                if mismatch.stage == MismatchStage::Commit =>
+            {
+                ErrorSeverity::Fatal
+            }
+            ConsensusError::SaveWalErr { .. }
+            | ConsensusError::LoadWalErr(_)
+            | ConsensusError::DoubleSignErr(_)
+            | ConsensusError::SignerUnavailable(_)
+            | ConsensusError::InvalidAuthorityUpdate { .. }
+            | ConsensusError::InvalidConfig(_)
+            | ConsensusError::InvariantErr(_) => ErrorSeverity::Fatal,
+            ConsensusError::InvalidAddress
+            | ConsensusError::ProposalErr(_)
+            | ConsensusError::PrevoteErr(_)
+            | ConsensusError::PrecommitErr(_)
+            | ConsensusError::BrakeErr(_)
+            | ConsensusError::RoundDiff { .. }
+            | ConsensusError::OutOfWindow { .. }
+            | ConsensusError::InvalidHashLength { .. }
+            | ConsensusError::MultiProposal(_, _)
+            | ConsensusError::AggregatedSignatureErr(_)
+            | ConsensusError::UnsupportedMessage { .. }
+            | ConsensusError::DecodeErr(_) => ErrorSeverity::Warning,
+            // A wrapped consensus error is as severe as itself.
+            ConsensusError::Wrapped { source, .. } => source
+                .downcast_ref::<ConsensusError>()
+                .map_or(ErrorSeverity::Error, ConsensusError::severity),
+            _ => ErrorSeverity::Error,
+        }
+    }
+}
+
+/// The reason that the engine of an instance stopped, which `Mlm::run()` resolves to once the
+/// engine has started, for a supervisor to tell a requested stop from a failure.
+#[derive(Clone, Debug, Display)]
+#[non_exhaustive]
+pub enum ConsensusExit {
+    /// The engine is stopped by `MlmMsg::Stop`.
+    #[display(fmt = "Stopped")]
+    Stopped,
+    /// The engine fails with a fatal error.
+    #[display(fmt = "Fatal error {}", _0)]
+    Fatal(ConsensusError),
+    /// A task of the engine panicked, with the message of the panic.
+    #[display(fmt = "Task panicked: {}", _0)]
+    Panicked(String),
+}
+
+impl ConsensusExit {
+    /// Whether the engine stopped on request rather than by a failure.
+    pub fn is_requested(&self) -> bool {
+        matches!(self, ConsensusExit::Stopped)
+    }
+
+    #[cfg(feature = "runtime")]
+    pub(crate) fn panicked(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "unknown panic".to_string(),
+            },
+        };
+        ConsensusExit::Panicked(message)
+    }
+}
 
 #[cfg(test)]
 impl PartialEq for ConsensusError {
     fn eq(&self, other: &Self) -> bool {
         use self::ConsensusError::{
-            CorrectnessErr, InvalidAddress, MonitorEventErr, Other, PrecommitErr,
-            PrevoteErr, ProposalErr, RoundDiff, SelfCheckErr, ThrowEventErr,
-            TriggerSMRErr,
+            AdapterTimeout, CorrectnessErr, InvalidAddress, MonitorEventErr, Other,
+            PrecommitErr, PrevoteErr, ProposalErr, RoundDiff, SelfCheckErr,
+            ThrowEventErr, TriggerSMRErr, Wrapped,
         };
         match (self, other) {
             // If compare objects are the following types of error, as long as the error type need
@@ -110,7 +593,21 @@ impl PartialEq for ConsensusError {
             (RoundDiff { local: m, vote: n }, RoundDiff { local: p, vote: q }) => {
                 m == p && n == q
             }
-            (Other(x), Other(y)) | (CorrectnessErr(x), CorrectnessErr(y)) => x == y,
+            (
+                Wrapped {
+                    kind: a,
+                    context: x,
+                    ..
+                },
+                Wrapped {
+                    kind: b,
+                    context: y,
+                    ..
+                },
+            ) => a == b && x == y,
+            (Other(x), Other(y))
+            | (CorrectnessErr(x), CorrectnessErr(y))
+            | (AdapterTimeout(x), AdapterTimeout(y)) => x == y,
             _ => false,
         }
     }
@@ -118,3 +615,86 @@ impl PartialEq for ConsensusError {
 
 #[cfg(test)]
 impl Eq for ConsensusError {}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(
+            ConsensusError::MultiProposal(1, 0).kind(),
+            ErrorKind::Byzantine
+        );
+        assert_eq!(
+            ConsensusError::ChannelErr("closed".to_string()).kind(),
+            ErrorKind::Channel
+        );
+        assert!(ConsensusError::LoadWalErr("corrupted".to_string()).is_fatal());
+        assert!(!ConsensusError::TimerErr("stopped".to_string()).is_fatal());
+        let err = ConsensusError::InvalidAuthorityUpdate {
+            height: 1,
+            error: AuthorityListError::Empty,
+        };
+        assert_eq!(err.kind(), ErrorKind::Auth);
+        assert!(err.is_fatal());
+        let err =
+            ConsensusError::InvalidConfig(ConfigError::ZeroTimeoutRatio("propose"));
+        assert_eq!(err.kind(), ErrorKind::Config);
+        assert_eq!(err.to_string(), "Invalid config zero propose timeout ratio");
+
+        // A mismatch of the block to commit is fatal, while one of a proposal is not.
+        let mut mismatch = HashMismatch {
+            stage: MismatchStage::Check,
+            height: 1,
+            round: 2,
+            proposer: Address::from_slice(&[1u8; 20]).unwrap(),
+            proposal_hash: Hash::from_slice(&[2u8; 4]),
+            computed_hash: Hash::from_slice(&[3u8; 4]),
+            block_digest: Hash::new(),
+        };
+        let err = ConsensusError::BlockHashMismatch(Box::new(mismatch.clone()));
+        assert_eq!(err.kind(), ErrorKind::Byzantine);
+        assert_eq!(err.severity(), ErrorSeverity::Error);
+        assert!(err
+            .to_string()
+            .starts_with("Block hash mismatch at check height 1"));
+        mismatch.stage = MismatchStage::Commit;
+        assert!(ConsensusError::BlockHashMismatch(Box::new(mismatch)).is_fatal());
+
+        // The source of a consensus error is kept as it is.
+        let err = ConsensusError::with_boxed_source(
+            ErrorKind::Adapter,
+            "commit",
+            Box::new(ConsensusError::DoubleSignErr("height 1".to_string())),
+        );
+        assert_eq!(err.kind(), ErrorKind::Adapter);
+        assert!(err.is_fatal());
+        let source = err.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<ConsensusError>().unwrap().kind(),
+            ErrorKind::Byzantine
+        );
+        assert!(source.source().is_none());
+
+        // The other sources are kept as their messages, with the chain of their sources.
+        let inner = ConsensusError::with_source(
+            ErrorKind::Wal,
+            "flush",
+            io::Error::other("disk full"),
+        );
+        let err = ConsensusError::with_boxed_source(
+            ErrorKind::Adapter,
+            "commit",
+            Box::new(io::Error::other(inner)),
+        );
+        assert_eq!(err.to_string(), "commit: flush: disk full");
+        assert!(!err.is_fatal());
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<ConsensusError>().is_none());
+        assert_eq!(source.source().unwrap().to_string(), "disk full");
+    }
+}
@@ -0,0 +1,561 @@
+#[cfg(feature = "runtime")]
+use std::collections::BTreeMap;
+#[cfg(feature = "runtime")]
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use derive_more::Display;
+#[cfg(feature = "runtime")]
+use futures::channel::mpsc::{channel, Receiver, Sender};
+#[cfg(feature = "runtime")]
+use parking_lot::{Mutex, RwLock};
+
+use crate::error::{ConsensusError, ErrorKind, ErrorSeverity};
+use crate::types::{Address, CorrelationId, Hash, Node, ViewChangeReason, VoteType};
+#[cfg(feature = "runtime")]
+use crate::utils::auth_manage::quorum;
+use crate::{DurationConfig, EffectiveConfig};
+
+/// A consensus event of an mlm instance. The timestamp is the milliseconds since the Unix
+/// epoch when the event happens.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[display(
+    fmt = "{:?} height {}, round {}, id {}",
+    kind,
+    height,
+    round,
+    "self.correlation_id()"
+)]
+pub struct MlmEvent {
+    /// Height of the event.
+    pub height: u64,
+    /// Round of the event.
+    pub round: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Kind of the event.
+    pub kind: MlmEventKind,
+    /// The number of the events dropped before this one since the subscriber was full.
+    pub dropped: u64,
+    /// The id of the instance, which is empty for a single instance.
+    pub instance: String,
+}
+
+/// Kinds of the consensus events.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MlmEventKind {
+    /// Goto a new round.
+    NewRound,
+    /// Receive a valid proposal of the current round, which is handed to SMR.
+    ProposalReceived {
+        /// Proposer of the proposal.
+        proposer: Address,
+        /// Block hash of the proposal.
+        block_hash: Hash,
+    },
+    /// A prevote or precommit QC of the current height is formed or received.
+    QcFormed {
+        /// Type of the QC.
+        vote_type: VoteType,
+        /// Block hash of the QC.
+        block_hash: Hash,
+    },
+    /// The block of the height is decided and handed to the `commit()` interface.
+    Committed {
+        /// Block hash of the committed block.
+        block_hash: Hash,
+    },
+    /// The round is changed without a commit. The round of the event is the changed round.
+    ViewChange {
+        /// The reason of the view change.
+        reason: ViewChangeReason,
+    },
+    /// The signer fails to sign, and the node observes the consensus without proposing and
+    /// voting until the signer is ready.
+    SignerUnavailable,
+    /// The signer is ready again, and the node participates from the next step.
+    SignerReady,
+    /// The node jumps to the height of the event by a status far ahead of it, abandoning the
+    /// in-flight rounds and the messages of the heights in between.
+    HeightJump {
+        /// The height jumped from.
+        from: u64,
+    },
+    /// The runtime configuration of the instance is changed. The height of the event is the
+    /// height from which the change applies.
+    ConfigChanged {
+        /// What triggers the change.
+        source: ConfigSource,
+        /// The changed items with their values before and after.
+        diff: Vec<ConfigDiff>,
+    },
+    /// The configuration that the state starts with, published once when it runs.
+    ConfigResolved {
+        /// The resolved configuration.
+        config: Box<EffectiveConfig>,
+    },
+    /// The state stops, which is the last event of the instance. The message channels of the
+    /// instance are closed and the wal is flushed before it is published.
+    Exit {
+        /// The last height decided and handed to `commit()`, which is the one below the
+        /// height of the event.
+        committed_height: u64,
+        /// Whether the state is stopped on request by `MlmHandler::stop()`, rather than by a
+        /// failure.
+        requested: bool,
+    },
+}
+
+/// The trigger of a runtime configuration change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// The status returned by `commit()` or synchronized by the application.
+    Status,
+    /// The timer configuration updated by `MlmHandler::update_timer_config()`.
+    TimerUpdate,
+}
+
+/// A changed item of the runtime configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigDiff {
+    /// The height interval.
+    Interval {
+        /// The interval before the change.
+        before: Duration,
+        /// The interval after the change.
+        after: Duration,
+    },
+    /// The timeout configuration, `None` means the default one.
+    TimerConfig {
+        /// The configuration before the change.
+        before: Option<DurationConfig>,
+        /// The configuration after the change.
+        after: Option<DurationConfig>,
+    },
+    /// The authority list.
+    AuthorityList {
+        /// The authorities joined.
+        added: Vec<Node>,
+        /// The authorities left.
+        removed: Vec<Node>,
+        /// The authorities whose weights are changed, with their new weights.
+        reweighted: Vec<Node>,
+    },
+    /// The least vote weight of a quorum.
+    Quorum {
+        /// The weight before the change.
+        before: u128,
+        /// The weight after the change.
+        after: u128,
+    },
+}
+
+/// The runtime configuration of an instance, whose changes are published as the
+/// `ConfigChanged` events.
+#[cfg(feature = "runtime")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RuntimeConfig {
+    pub(crate) interval: Duration,
+    pub(crate) timer_config: Option<DurationConfig>,
+    pub(crate) authority_list: Vec<Node>,
+    pub(crate) vote_weight_sum: u128,
+}
+
+#[cfg(feature = "runtime")]
+impl RuntimeConfig {
+    /// The items changed from this configuration to the other one.
+    pub(crate) fn diff(&self, other: &RuntimeConfig) -> Vec<ConfigDiff> {
+        let mut diff = Vec::new();
+        if self.interval != other.interval {
+            diff.push(ConfigDiff::Interval {
+                before: self.interval,
+                after: other.interval,
+            });
+        }
+        if self.timer_config != other.timer_config {
+            diff.push(ConfigDiff::TimerConfig {
+                before: self.timer_config.clone(),
+                after: other.timer_config.clone(),
+            });
+        }
+
+        let before = self
+            .authority_list
+            .iter()
+            .map(|node| (&node.address, node))
+            .collect::<BTreeMap<_, _>>();
+        let after = other
+            .authority_list
+            .iter()
+            .map(|node| (&node.address, node))
+            .collect::<BTreeMap<_, _>>();
+        let added = after
+            .iter()
+            .filter(|(address, _)| !before.contains_key(*address))
+            .map(|(_, node)| (*node).clone())
+            .collect::<Vec<_>>();
+        let removed = before
+            .iter()
+            .filter(|(address, _)| !after.contains_key(*address))
+            .map(|(_, node)| (*node).clone())
+            .collect::<Vec<_>>();
+        let reweighted = after
+            .iter()
+            .filter(|(address, node)| {
+                before.get(*address).is_some_and(|old| {
+                    old.vote_weight != node.vote_weight
+                        || old.propose_weight != node.propose_weight
+                })
+            })
+            .map(|(_, node)| (*node).clone())
+            .collect::<Vec<_>>();
+        if !added.is_empty() || !removed.is_empty() || !reweighted.is_empty() {
+            diff.push(ConfigDiff::AuthorityList {
+                added,
+                removed,
+                reweighted,
+            });
+        }
+
+        let (before, after) = (self.quorum(), other.quorum());
+        if before != after {
+            diff.push(ConfigDiff::Quorum { before, after });
+        }
+        diff
+    }
+
+    /// The least vote weight above two thirds of the sum.
+    fn quorum(&self) -> u128 {
+        quorum(self.vote_weight_sum)
+    }
+}
+
+/// An error of an mlm instance. The timestamp is the milliseconds since the Unix epoch when
+/// the error happens.
+#[derive(Clone, Debug, Display)]
+#[display(
+    fmt = "{} {} height {}, round {}, id {}",
+    severity,
+    error,
+    height,
+    round,
+    "self.correlation_id()"
+)]
+pub struct MlmError {
+    /// Height of the state when the error happens.
+    pub height: u64,
+    /// Round of the state when the error happens.
+    pub round: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Severity of the error.
+    pub severity: ErrorSeverity,
+    /// Kind of the error.
+    pub kind: ErrorKind,
+    /// The error.
+    pub error: ConsensusError,
+    /// The number of the errors dropped before this one since the subscriber was full.
+    pub dropped: u64,
+    /// The id of the instance, which is empty for a single instance.
+    pub instance: String,
+    /// The transport address of the peer whose message causes the error, which is carried by
+    /// the context of the message, such as the sender of a conflicting proposal.
+    pub peer: Option<String>,
+}
+
+impl MlmEvent {
+    /// The correlation ID of the round of the event.
+    pub fn correlation_id(&self) -> CorrelationId {
+        CorrelationId::new(self.height, self.round)
+    }
+}
+
+impl MlmError {
+    /// The correlation ID of the round when the error happens.
+    pub fn correlation_id(&self) -> CorrelationId {
+        CorrelationId::new(self.height, self.round)
+    }
+}
+
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+struct Subscriber<T> {
+    tx: Sender<T>,
+    dropped: u64,
+}
+
+#[cfg(feature = "runtime")]
+impl<T> Subscriber<T> {
+    /// Send the item, or drop and count it if the subscriber is full. Return false if the
+    /// receiver is dropped.
+    fn send(&mut self, item: T) -> bool {
+        match self.tx.try_send(item) {
+            Ok(()) => {
+                self.dropped = 0;
+                true
+            }
+            Err(e) if e.is_full() => {
+                self.dropped += 1;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// The subscribers of the consensus events and errors. Each subscriber gets a copy of every
+/// event, and a subscriber is removed when its receiver is dropped. The subscribers are
+/// bounded, an event or an error is dropped and counted instead of blocking the state when a
+/// subscriber is full.
+#[cfg(feature = "runtime")]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EventBus {
+    events: Arc<Mutex<Vec<Subscriber<MlmEvent>>>>,
+    errors: Arc<Mutex<Vec<Subscriber<MlmError>>>>,
+    instance: Arc<RwLock<String>>,
+}
+
+#[cfg(feature = "runtime")]
+impl EventBus {
+    pub(crate) fn new() -> Self {
+        EventBus::default()
+    }
+
+    /// Set the id of the instance that labels the events and the errors.
+    pub(crate) fn set_instance(&self, instance: &str) {
+        *self.instance.write() = instance.to_string();
+    }
+
+    pub(crate) fn instance(&self) -> String {
+        self.instance.read().clone()
+    }
+
+    pub(crate) fn subscribe(&self, capacity: usize) -> Receiver<MlmEvent> {
+        let (tx, rx) = channel(capacity);
+        self.events.lock().push(Subscriber { tx, dropped: 0 });
+        rx
+    }
+
+    pub(crate) fn subscribe_errors(&self, capacity: usize) -> Receiver<MlmError> {
+        let (tx, rx) = channel(capacity);
+        self.errors.lock().push(Subscriber { tx, dropped: 0 });
+        rx
+    }
+
+    pub(crate) fn publish_error(&self, height: u64, round: u64, error: ConsensusError) {
+        self.publish_peer_error(height, round, None, error)
+    }
+
+    /// Publish an error caused by a message of the peer.
+    pub(crate) fn publish_peer_error(
+        &self,
+        height: u64,
+        round: u64,
+        peer: Option<String>,
+        error: ConsensusError,
+    ) {
+        let mut subscribers = self.errors.lock();
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let severity = error.severity();
+        let kind = error.kind();
+        let timestamp = now_millis();
+        let instance = self.instance();
+        subscribers.retain_mut(|sub| {
+            let err = MlmError {
+                height,
+                round,
+                timestamp,
+                severity,
+                kind,
+                error: error.clone(),
+                dropped: sub.dropped,
+                instance: instance.clone(),
+                peer: peer.clone(),
+            };
+            sub.send(err)
+        });
+    }
+
+    pub(crate) fn publish(&self, height: u64, round: u64, kind: MlmEventKind) {
+        let mut subscribers = self.events.lock();
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let timestamp = now_millis();
+        let instance = self.instance();
+        subscribers.retain_mut(|sub| {
+            let event = MlmEvent {
+                height,
+                round,
+                timestamp,
+                kind: kind.clone(),
+                dropped: sub.dropped,
+                instance: instance.clone(),
+            };
+            sub.send(event)
+        });
+    }
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod test {
+    use futures::StreamExt;
+
+    use std::time::Duration;
+
+    use super::{ConfigDiff, EventBus, MlmEventKind, RuntimeConfig};
+    use crate::error::{ConsensusError, ErrorKind, ErrorSeverity};
+    use crate::types::{Address, Hash, Node};
+    use crate::DurationConfig;
+
+    #[tokio::test]
+    async fn test_event_bus() {
+        let bus = EventBus::new();
+        // Publish without subscribers is a no-op.
+        bus.publish(1, 0, MlmEventKind::NewRound);
+
+        let mut rx_1 = bus.subscribe(16);
+        let rx_2 = bus.subscribe(16);
+        drop(rx_2);
+
+        bus.publish(
+            2,
+            1,
+            MlmEventKind::Committed {
+                block_hash: Hash::from(vec![1u8]),
+            },
+        );
+        let event = rx_1.next().await.unwrap();
+        assert_eq!(event.height, 2);
+        assert_eq!(event.round, 1);
+        assert_eq!(event.correlation_id().to_string(), "mlm-2-1");
+        assert!(event.timestamp > 0);
+        assert_eq!(event.dropped, 0);
+        assert_eq!(
+            event.kind,
+            MlmEventKind::Committed {
+                block_hash: Hash::from(vec![1u8]),
+            }
+        );
+
+        assert!(event.instance.is_empty());
+
+        // The events are labeled with the instance id.
+        bus.set_instance("shard-1");
+        bus.publish(3, 0, MlmEventKind::NewRound);
+        assert_eq!(rx_1.next().await.unwrap().instance, "shard-1");
+
+        // The dropped subscriber is removed.
+        assert_eq!(bus.events.lock().len(), 1);
+
+        // The events are dropped and counted while the subscriber is full, rather than
+        // queued without a limit.
+        let mut rx_3 = bus.subscribe(0);
+        for round in 0..4 {
+            bus.publish(4, round, MlmEventKind::NewRound);
+        }
+        assert_eq!(rx_3.next().await.unwrap().round, 0);
+        bus.publish(5, 0, MlmEventKind::NewRound);
+        let event = rx_3.next().await.unwrap();
+        assert_eq!(event.height, 5);
+        assert_eq!(event.dropped, 3);
+
+        // The other subscriber keeps up with all of the events.
+        let rounds = (0..5)
+            .map(|_| rx_1.try_recv().unwrap().round)
+            .collect::<Vec<_>>();
+        assert_eq!(rounds, vec![0, 1, 2, 3, 0]);
+        assert!(rx_1.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_error_bus() {
+        let bus = EventBus::new();
+        bus.publish_error(1, 0, ConsensusError::Other("a".to_string()));
+
+        // The capacity of a bounded channel is the buffer plus one for the sender.
+        let mut rx = bus.subscribe_errors(0);
+        bus.publish_error(1, 0, ConsensusError::Other("b".to_string()));
+        bus.publish_error(1, 1, ConsensusError::Other("c".to_string()));
+        bus.publish_error(1, 2, ConsensusError::Other("d".to_string()));
+
+        let err = rx.next().await.unwrap();
+        assert_eq!(err.round, 0);
+        assert_eq!(err.severity, ErrorSeverity::Error);
+        assert_eq!(err.dropped, 0);
+        assert_eq!(err.peer, None);
+
+        bus.publish_peer_error(
+            2,
+            0,
+            Some("peer-1".to_string()),
+            ConsensusError::DoubleSignErr("e".to_string()),
+        );
+        let err = rx.next().await.unwrap();
+        assert_eq!(err.height, 2);
+        assert_eq!(err.peer.as_deref(), Some("peer-1"));
+        assert_eq!(err.severity, ErrorSeverity::Fatal);
+        assert_eq!(err.kind, ErrorKind::Byzantine);
+        assert_eq!(err.dropped, 2);
+
+        drop(rx);
+        bus.publish_error(2, 1, ConsensusError::Other("f".to_string()));
+        assert!(bus.errors.lock().is_empty());
+    }
+
+    #[test]
+    fn test_config_diff() {
+        let node = |byte: u8, vote_weight: u128| Node {
+            address: Address::from_slice(&[byte]).unwrap(),
+            propose_weight: 1,
+            vote_weight,
+        };
+        let before = RuntimeConfig {
+            interval: Duration::from_secs(1),
+            timer_config: None,
+            authority_list: vec![node(1, 1), node(2, 1), node(3, 1)],
+            vote_weight_sum: 3,
+        };
+        assert!(before.diff(&before).is_empty());
+
+        let after = RuntimeConfig {
+            interval: Duration::from_secs(2),
+            timer_config: Some(DurationConfig::new(20, 10, 10, 10)),
+            authority_list: vec![node(2, 3), node(3, 1), node(4, 1)],
+            vote_weight_sum: 5,
+        };
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                ConfigDiff::Interval {
+                    before: Duration::from_secs(1),
+                    after: Duration::from_secs(2),
+                },
+                ConfigDiff::TimerConfig {
+                    before: None,
+                    after: Some(DurationConfig::new(20, 10, 10, 10)),
+                },
+                ConfigDiff::AuthorityList {
+                    added: vec![node(4, 1)],
+                    removed: vec![node(1, 1)],
+                    reweighted: vec![node(2, 3)],
+                },
+                ConfigDiff::Quorum {
+                    before: 3,
+                    after: 4,
+                },
+            ]
+        );
+    }
+}
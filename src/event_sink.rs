@@ -0,0 +1,160 @@
+use std::fmt::Debug;
+
+use creep::Context;
+use derive_more::Display;
+use hummer::coding::hex_encode;
+
+use crate::types::{Address, VoteType};
+
+/// A typed event of an invalid message received by the node, which the integrators can route
+/// to the structured logs, the metrics or the banning of the peers.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum ConsensusEvent {
+    /// The signature of a message fails the verification against its signer.
+    #[display(
+        fmt = "invalid {} signature height {}, round {}, from {}, {}",
+        kind,
+        height,
+        round,
+        "hex_encode(signer)",
+        error
+    )]
+    SignatureInvalid {
+        /// The kind of the message.
+        kind: String,
+        /// The height of the message.
+        height: u64,
+        /// The round of the message.
+        round: u64,
+        /// The signer of the message.
+        signer: Address,
+        /// The error of the verification.
+        error: String,
+    },
+    /// The extension of a precommit fails the verification of its signature or is rejected
+    /// by `Consensus::verify_vote_extension()`.
+    #[display(
+        fmt = "invalid vote extension height {}, round {}, from {}, {}",
+        height,
+        round,
+        "hex_encode(voter)",
+        error
+    )]
+    VoteExtensionInvalid {
+        /// The height of the precommit.
+        height: u64,
+        /// The round of the precommit.
+        round: u64,
+        /// The voter of the precommit.
+        voter: Address,
+        /// The error of the verification.
+        error: String,
+    },
+    /// The voters of a QC are not above the threshold of the vote weights.
+    #[display(
+        fmt = "{:?} QC below threshold height {}, round {}, leader {}",
+        vote_type,
+        height,
+        round,
+        "hex_encode(leader)"
+    )]
+    QcBelowThreshold {
+        /// The type of the QC.
+        vote_type: VoteType,
+        /// The height of the QC.
+        height: u64,
+        /// The round of the QC.
+        round: u64,
+        /// The leader that aggregates the QC.
+        leader: Address,
+    },
+    /// The aggregated signature of a QC fails the verification against its voters.
+    #[display(
+        fmt = "invalid {:?} QC signature height {}, round {}, leader {}, {}",
+        vote_type,
+        height,
+        round,
+        "hex_encode(leader)",
+        error
+    )]
+    QcSignatureInvalid {
+        /// The type of the QC.
+        vote_type: VoteType,
+        /// The height of the QC.
+        height: u64,
+        /// The round of the QC.
+        round: u64,
+        /// The leader that aggregates the QC.
+        leader: Address,
+        /// The error of the verification.
+        error: String,
+    },
+    /// The timeout certificate carried by a proposal fails the verification.
+    #[display(
+        fmt = "invalid timeout certificate height {}, round {}, from {}, {}",
+        height,
+        round,
+        "hex_encode(proposer)",
+        error
+    )]
+    TimeoutCertInvalid {
+        /// The height of the proposal.
+        height: u64,
+        /// The round of the proposal.
+        round: u64,
+        /// The proposer of the proposal.
+        proposer: Address,
+        /// The error of the verification.
+        error: String,
+    },
+    /// A proposal is signed by an authority that is not a proposer of its round.
+    #[display(
+        fmt = "proposal from wrong proposer height {}, round {}, from {}",
+        height,
+        round,
+        "hex_encode(proposer)"
+    )]
+    ProposalFromWrongProposer {
+        /// The height of the proposal.
+        height: u64,
+        /// The round of the proposal.
+        round: u64,
+        /// The proposer of the proposal.
+        proposer: Address,
+    },
+    /// A message is signed by a node out of the authority list.
+    #[display(
+        fmt = "{} from unknown signer height {}, round {}, from {}",
+        kind,
+        height,
+        round,
+        "hex_encode(signer)"
+    )]
+    UnknownSigner {
+        /// The kind of the message.
+        kind: String,
+        /// The height of the message.
+        height: u64,
+        /// The round of the message.
+        round: u64,
+        /// The signer of the message.
+        signer: Address,
+    },
+}
+
+/// Trait for the consumers of the typed events of the invalid messages. The events are sent
+/// from the state and the signature verification tasks, so the sink must not block.
+pub trait ConsensusEventSink: Debug + Send + Sync {
+    /// Handle an event with the context of the message.
+    fn on_event(&self, ctx: Context, event: ConsensusEvent);
+}
+
+/// The event sink that logs the events as errors, which is the default of an instance.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogEventSink;
+
+impl ConsensusEventSink for LogEventSink {
+    fn on_event(&self, _ctx: Context, event: ConsensusEvent) {
+        log::error!("Mlm: {}", event);
+    }
+}
@@ -0,0 +1,943 @@
+use std::convert::Infallible;
+use std::error::Error;
+use std::ffi::{CString, c_char, c_void};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use std::{ptr, slice};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+
+use crate::error::ConsensusError;
+use crate::mlm::{Mlm, MlmHandler};
+use crate::transport::{decode_payload, encode_payload};
+use crate::types::{
+    Address, AggregatedSignature, Commit, Hash, MlmMsg, Node, Signature, Status,
+    ViewChangeReason,
+};
+use crate::{Codec, Consensus, Context, Crypto, Wal};
+
+/// The return code of a successful call, of both the exported functions and the callbacks.
+pub const MLM_OK: i32 = 0;
+/// The return code of a failed exported function. A callback fails by any code but `MLM_OK`.
+pub const MLM_ERR: i32 = -1;
+
+/// A view of the bytes owned by the caller, which is only valid during the call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MlmBytes {
+    /// The pointer to the bytes, which may be null if the length is zero.
+    pub data: *const u8,
+    /// The length of the bytes.
+    pub len: usize,
+}
+
+impl MlmBytes {
+    /// A view of the slice.
+    pub fn from_slice(data: &[u8]) -> Self {
+        MlmBytes {
+            data: data.as_ptr(),
+            len: data.len(),
+        }
+    }
+
+    /// Copy the bytes of the view.
+    ///
+    /// # Safety
+    ///
+    /// The data must be valid for reads of the length, unless the length is zero.
+    pub unsafe fn to_bytes(self) -> Bytes {
+        if self.len == 0 {
+            return Bytes::new();
+        }
+        Bytes::copy_from_slice(slice::from_raw_parts(self.data, self.len))
+    }
+}
+
+/// The output of a callback, which is owned by the engine and written by the host with
+/// `mlm_buffer_write()`. A buffer that is not written is empty, or none for `wal_load` and
+/// `wal_load_last_signed`.
+#[derive(Debug, Default)]
+pub struct MlmBuffer(Option<Vec<u8>>);
+
+impl MlmBuffer {
+    fn into_bytes(self) -> Bytes {
+        self.0.map(Bytes::from).unwrap_or_default()
+    }
+
+    fn into_json<D: DeserializeOwned>(
+        self,
+        call: &str,
+    ) -> Result<D, Box<dyn Error + Send>> {
+        serde_json::from_slice(&self.0.unwrap_or_default()).map_err(|e| {
+            Box::new(ConsensusError::DecodeErr(format!(
+                "ffi {} json error {}",
+                call, e
+            ))) as Box<dyn Error + Send>
+        })
+    }
+}
+
+/// The callbacks of the `Consensus`, `Crypto` and `Wal` hooks of an instance, which are
+/// registered by `mlm_new()`. The `user_data` is passed back as the first argument of each
+/// of them. They are called from the threads of the engine, possibly concurrently, and block
+/// the engine until they return, so they must be thread safe and quick. The messages are the
+/// payloads of `encode_payload()`, the commit is rlp encoded, and the authority list and the
+/// status are JSON of `Vec<Node>` and `Status`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MlmCallbacks {
+    /// The opaque pointer of the host.
+    pub user_data: *mut c_void,
+    /// Write the block of the height and its hash.
+    pub get_block:
+        extern "C" fn(*mut c_void, u64, *mut MlmBuffer, *mut MlmBuffer) -> i32,
+    /// Check the block of the height by its hash and the block.
+    pub check_block: extern "C" fn(*mut c_void, u64, MlmBytes, MlmBytes) -> i32,
+    /// Commit the height by the rlp encoded commit, and write the JSON of the next status.
+    pub commit: extern "C" fn(*mut c_void, u64, MlmBytes, *mut MlmBuffer) -> i32,
+    /// Write the JSON of the authority list of the height.
+    pub get_authority_list: extern "C" fn(*mut c_void, u64, *mut MlmBuffer) -> i32,
+    /// Broadcast the payload to the other replicas.
+    pub broadcast_to_other: extern "C" fn(*mut c_void, MlmBytes) -> i32,
+    /// Transmit the payload to the relayer of the address.
+    pub transmit_to_relayer: extern "C" fn(*mut c_void, MlmBytes, MlmBytes) -> i32,
+    /// Report the error message.
+    pub report_error: extern "C" fn(*mut c_void, *const c_char),
+    /// Report the view change of the height and the round with the reason message.
+    pub report_view_change: extern "C" fn(*mut c_void, u64, u64, *const c_char),
+    /// Write the hash of the message. A failure is reported as fatal, and the node never
+    /// signs again.
+    pub hash: extern "C" fn(*mut c_void, MlmBytes, *mut MlmBuffer) -> i32,
+    /// Write the signature of the hash.
+    pub sign: extern "C" fn(*mut c_void, MlmBytes, *mut MlmBuffer) -> i32,
+    /// Write the aggregated signature of the signatures and the voters of the same count.
+    pub aggregate_signatures: extern "C" fn(
+        *mut c_void,
+        *const MlmBytes,
+        *const MlmBytes,
+        usize,
+        *mut MlmBuffer,
+    ) -> i32,
+    /// Verify the signature of the hash by the voter.
+    pub verify_signature:
+        extern "C" fn(*mut c_void, MlmBytes, MlmBytes, MlmBytes) -> i32,
+    /// Verify the aggregated signature of the hash by the voters of the count.
+    pub verify_aggregated_signature:
+        extern "C" fn(*mut c_void, MlmBytes, MlmBytes, *const MlmBytes, usize) -> i32,
+    /// Save the wal information.
+    pub wal_save: extern "C" fn(*mut c_void, MlmBytes) -> i32,
+    /// Write the saved wal information, or nothing if none is saved.
+    pub wal_load: extern "C" fn(*mut c_void, *mut MlmBuffer) -> i32,
+    /// Save the record of the last signed message durably, which replaces the saved one.
+    pub wal_save_last_signed: extern "C" fn(*mut c_void, MlmBytes) -> i32,
+    /// Write the saved record of the last signed message, or nothing if none is saved.
+    pub wal_load_last_signed: extern "C" fn(*mut c_void, *mut MlmBuffer) -> i32,
+}
+
+// SAFETY: the host guarantees that the callbacks and the user data are thread safe, which is
+// the contract of `mlm_new()`.
+unsafe impl Send for MlmCallbacks {}
+unsafe impl Sync for MlmCallbacks {}
+
+/// The block of the host, which is opaque to the engine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FfiBlock(pub Bytes);
+
+impl Codec for FfiBlock {
+    type Error = Infallible;
+
+    fn encode(&self) -> Result<Bytes, Self::Error> {
+        Ok(self.0.clone())
+    }
+
+    fn decode(data: Bytes) -> Result<Self, Self::Error> {
+        Ok(FfiBlock(data))
+    }
+
+    fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+        Ok(FfiBlock(Bytes::copy_from_slice(data)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+/// The adapter calling the callbacks of the host. A failed hash callback is fatal, after
+/// which the adapter refuses to sign, since the hash of a message is unknown.
+#[derive(Debug)]
+pub struct FfiAdapter(MlmCallbacks, AtomicBool);
+
+impl FfiAdapter {
+    fn new(callbacks: MlmCallbacks) -> Self {
+        FfiAdapter(callbacks, AtomicBool::new(false))
+    }
+}
+
+fn check(call: &str, code: i32) -> Result<(), Box<dyn Error + Send>> {
+    if code == MLM_OK {
+        return Ok(());
+    }
+    Err(Box::new(ConsensusError::Other(format!(
+        "ffi {} error code {}",
+        call, code
+    ))))
+}
+
+fn to_c_string(msg: String) -> CString {
+    CString::new(msg.replace('\0', " ")).unwrap_or_default()
+}
+
+#[async_trait]
+impl Consensus<FfiBlock> for FfiAdapter {
+    async fn get_block(
+        &self,
+        _ctx: Context,
+        height: u64,
+    ) -> Result<(FfiBlock, Hash), Box<dyn Error + Send>> {
+        let (mut block, mut hash) = (MlmBuffer::default(), MlmBuffer::default());
+        let code = (self.0.get_block)(self.0.user_data, height, &mut block, &mut hash);
+        check("get_block", code)?;
+        Ok((FfiBlock(block.into_bytes()), Hash::from(hash.into_bytes())))
+    }
+
+    async fn check_block(
+        &self,
+        _ctx: Context,
+        height: u64,
+        hash: Hash,
+        block: FfiBlock,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let code = (self.0.check_block)(
+            self.0.user_data,
+            height,
+            MlmBytes::from_slice(&hash),
+            MlmBytes::from_slice(&block.0),
+        );
+        check("check_block", code)
+    }
+
+    async fn commit(
+        &self,
+        _ctx: Context,
+        height: u64,
+        commit: Commit<FfiBlock>,
+    ) -> Result<Status, Box<dyn Error + Send>> {
+        let commit = rlp::encode(&commit);
+        let mut status = MlmBuffer::default();
+        let code = (self.0.commit)(
+            self.0.user_data,
+            height,
+            MlmBytes::from_slice(&commit),
+            &mut status,
+        );
+        check("commit", code)?;
+        status.into_json("commit")
+    }
+
+    async fn get_authority_list(
+        &self,
+        _ctx: Context,
+        height: u64,
+    ) -> Result<Vec<Node>, Box<dyn Error + Send>> {
+        let mut list = MlmBuffer::default();
+        let code = (self.0.get_authority_list)(self.0.user_data, height, &mut list);
+        check("get_authority_list", code)?;
+        list.into_json("get_authority_list")
+    }
+
+    async fn broadcast_to_other(
+        &self,
+        _ctx: Context,
+        msg: MlmMsg<FfiBlock>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let payload = encode_payload(&msg)?;
+        let code = (self.0.broadcast_to_other)(
+            self.0.user_data,
+            MlmBytes::from_slice(&payload),
+        );
+        check("broadcast_to_other", code)
+    }
+
+    async fn transmit_to_relayer(
+        &self,
+        _ctx: Context,
+        addr: Address,
+        msg: MlmMsg<FfiBlock>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let payload = encode_payload(&msg)?;
+        let code = (self.0.transmit_to_relayer)(
+            self.0.user_data,
+            MlmBytes::from_slice(&addr),
+            MlmBytes::from_slice(&payload),
+        );
+        check("transmit_to_relayer", code)
+    }
+
+    fn report_error(&self, _ctx: Context, error: ConsensusError) {
+        let msg = to_c_string(error.to_string());
+        (self.0.report_error)(self.0.user_data, msg.as_ptr());
+    }
+
+    fn report_view_change(
+        &self,
+        _ctx: Context,
+        height: u64,
+        round: u64,
+        reason: ViewChangeReason,
+    ) {
+        let reason = to_c_string(reason.to_string());
+        (self.0.report_view_change)(self.0.user_data, height, round, reason.as_ptr());
+    }
+}
+
+impl Crypto for FfiAdapter {
+    type QcSignature = AggregatedSignature;
+
+    fn hash(&self, msg: Bytes) -> Hash {
+        let mut hash = MlmBuffer::default();
+        let code =
+            (self.0.hash)(self.0.user_data, MlmBytes::from_slice(&msg), &mut hash);
+        if code != MLM_OK {
+            log::error!("Mlm: ffi hash error code {}", code);
+            // The error is reported once, and the node observes without signing from then on.
+            if !self.1.swap(true, Ordering::SeqCst) {
+                let e = ConsensusError::SignerUnavailable(format!(
+                    "ffi hash error code {}",
+                    code
+                ));
+                self.report_error(Context::new(), e);
+            }
+        }
+        Hash::from(hash.into_bytes())
+    }
+
+    fn sign(&self, hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+        if self.1.load(Ordering::SeqCst) {
+            return Err(Box::new(ConsensusError::SignerUnavailable(
+                "ffi hash failed".to_string(),
+            )));
+        }
+        let mut signature = MlmBuffer::default();
+        let code = (self.0.sign)(
+            self.0.user_data,
+            MlmBytes::from_slice(&hash),
+            &mut signature,
+        );
+        check("sign", code)?;
+        Ok(signature.into_bytes())
+    }
+
+    fn aggregate_signatures(
+        &self,
+        signatures: Vec<Signature>,
+        voters: Vec<Address>,
+    ) -> Result<Signature, Box<dyn Error + Send>> {
+        if signatures.len() != voters.len() {
+            return Err(Box::new(ConsensusError::CryptoErr(
+                "ffi aggregate_signatures length mismatch".to_string(),
+            )));
+        }
+        let signatures = signatures
+            .iter()
+            .map(|s| MlmBytes::from_slice(s))
+            .collect::<Vec<_>>();
+        let voters = voters
+            .iter()
+            .map(|v| MlmBytes::from_slice(v))
+            .collect::<Vec<_>>();
+        let mut signature = MlmBuffer::default();
+        let code = (self.0.aggregate_signatures)(
+            self.0.user_data,
+            signatures.as_ptr(),
+            voters.as_ptr(),
+            voters.len(),
+            &mut signature,
+        );
+        check("aggregate_signatures", code)?;
+        Ok(signature.into_bytes())
+    }
+
+    fn verify_signature(
+        &self,
+        signature: Signature,
+        hash: Hash,
+        voter: Address,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let code = (self.0.verify_signature)(
+            self.0.user_data,
+            MlmBytes::from_slice(&signature),
+            MlmBytes::from_slice(&hash),
+            MlmBytes::from_slice(&voter),
+        );
+        check("verify_signature", code)
+    }
+
+    fn verify_aggregated_signature(
+        &self,
+        aggregate_signature: Signature,
+        msg_hash: Hash,
+        voters: Vec<Address>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let voters = voters
+            .iter()
+            .map(|v| MlmBytes::from_slice(v))
+            .collect::<Vec<_>>();
+        let code = (self.0.verify_aggregated_signature)(
+            self.0.user_data,
+            MlmBytes::from_slice(&aggregate_signature),
+            MlmBytes::from_slice(&msg_hash),
+            voters.as_ptr(),
+            voters.len(),
+        );
+        check("verify_aggregated_signature", code)
+    }
+}
+
+#[async_trait]
+impl Wal for FfiAdapter {
+    async fn save(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        let code = (self.0.wal_save)(self.0.user_data, MlmBytes::from_slice(&info));
+        check("wal_save", code)
+    }
+
+    async fn load(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        let mut info = MlmBuffer::default();
+        let code = (self.0.wal_load)(self.0.user_data, &mut info);
+        check("wal_load", code)?;
+        Ok(info.0.map(Bytes::from))
+    }
+
+    async fn save_last_signed(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        let code =
+            (self.0.wal_save_last_signed)(self.0.user_data, MlmBytes::from_slice(&info));
+        check("wal_save_last_signed", code)
+    }
+
+    async fn load_last_signed(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        let mut info = MlmBuffer::default();
+        let code = (self.0.wal_load_last_signed)(self.0.user_data, &mut info);
+        check("wal_load_last_signed", code)?;
+        Ok(info.0.map(Bytes::from))
+    }
+}
+
+/// An instance created by `mlm_new()` and released by `mlm_free()`.
+pub struct MlmInstance {
+    mlm: Mlm<FfiBlock, FfiAdapter, FfiAdapter, FfiAdapter>,
+    handler: MlmHandler<FfiBlock>,
+}
+
+/// Create an instance of the address with the callbacks of the host. It returns null if the
+/// address is empty or too long.
+///
+/// # Safety
+///
+/// The address must be valid for reads. The callbacks and the user data must be thread safe
+/// and stay valid until the instance is freed.
+#[no_mangle]
+pub unsafe extern "C" fn mlm_new(
+    address: MlmBytes,
+    callbacks: MlmCallbacks,
+) -> *mut MlmInstance {
+    let address = match Address::new(address.to_bytes()) {
+        Ok(address) => address,
+        Err(_) => return ptr::null_mut(),
+    };
+    let adapter = Arc::new(FfiAdapter::new(callbacks));
+    let mlm = Mlm::new(address, Arc::clone(&adapter), Arc::clone(&adapter), adapter);
+    let handler = mlm.get_handler();
+    Box::into_raw(Box::new(MlmInstance { mlm, handler }))
+}
+
+/// Run the instance from the height with the interval in milliseconds and the JSON of the
+/// authority list. The consensus starts by the status sent by `mlm_send_status()`. It blocks
+/// the calling thread on a runtime of its own until the instance is stopped by `mlm_stop()`,
+/// and returns `MLM_ERR` if the instance fails, panics or runs twice.
+///
+/// # Safety
+///
+/// The instance must be created by `mlm_new()` and not freed, and the authority list must be
+/// valid for reads.
+#[no_mangle]
+pub unsafe extern "C" fn mlm_run(
+    instance: *const MlmInstance,
+    init_height: u64,
+    interval: u64,
+    authority_list: MlmBytes,
+) -> i32 {
+    let instance = match instance.as_ref() {
+        Some(instance) => instance,
+        None => return MLM_ERR,
+    };
+    let authority_list: Vec<Node> =
+        match serde_json::from_slice(&authority_list.to_bytes()) {
+            Ok(list) => list,
+            Err(e) => {
+                log::error!("Mlm: ffi run with an invalid authority list {}", e);
+                return MLM_ERR;
+            }
+        };
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::error!("Mlm: ffi build the runtime error {:?}", e);
+            return MLM_ERR;
+        }
+    };
+
+    let res = runtime.block_on(instance.mlm.run(
+        init_height,
+        Duration::from_millis(interval),
+        authority_list,
+        None,
+    ));
+    match res {
+        Ok(exit) if exit.is_requested() => MLM_OK,
+        Ok(exit) => {
+            log::error!("Mlm: ffi run exit, {}", exit);
+            MLM_ERR
+        }
+        Err(e) => {
+            log::error!("Mlm: ffi run error {:?}", e);
+            MLM_ERR
+        }
+    }
+}
+
+/// Send a message received from the other replicas to the instance, which is a payload of
+/// `encode_payload()`.
+///
+/// # Safety
+///
+/// The instance must be created by `mlm_new()` and not freed, and the payload must be valid
+/// for reads.
+#[no_mangle]
+pub unsafe extern "C" fn mlm_send_msg(
+    instance: *const MlmInstance,
+    payload: MlmBytes,
+) -> i32 {
+    let instance = match instance.as_ref() {
+        Some(instance) => instance,
+        None => return MLM_ERR,
+    };
+    let msg = match decode_payload(&payload.to_bytes()) {
+        Ok(msg) => msg,
+        Err(e) => {
+            log::warn!("Mlm: ffi send an invalid message {:?}", e);
+            return MLM_ERR;
+        }
+    };
+    send(instance, msg)
+}
+
+/// Send the JSON of a rich status to the instance, e.g. after the node syncs to a height.
+///
+/// # Safety
+///
+/// The instance must be created by `mlm_new()` and not freed, and the status must be valid
+/// for reads.
+#[no_mangle]
+pub unsafe extern "C" fn mlm_send_status(
+    instance: *const MlmInstance,
+    status: MlmBytes,
+) -> i32 {
+    let instance = match instance.as_ref() {
+        Some(instance) => instance,
+        None => return MLM_ERR,
+    };
+    match serde_json::from_slice(&status.to_bytes()) {
+        Ok(status) => send(instance, MlmMsg::RichStatus(status)),
+        Err(e) => {
+            log::warn!("Mlm: ffi send an invalid status {}", e);
+            MLM_ERR
+        }
+    }
+}
+
+/// Stop the instance, so the running `mlm_run()` returns.
+///
+/// # Safety
+///
+/// The instance must be created by `mlm_new()` and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn mlm_stop(instance: *const MlmInstance) -> i32 {
+    match instance.as_ref() {
+        Some(instance) => send(instance, MlmMsg::Stop),
+        None => MLM_ERR,
+    }
+}
+
+/// Free the instance. It must not be running.
+///
+/// # Safety
+///
+/// The instance must be created by `mlm_new()` and not freed, or null.
+#[no_mangle]
+pub unsafe extern "C" fn mlm_free(instance: *mut MlmInstance) {
+    if !instance.is_null() {
+        drop(Box::from_raw(instance));
+    }
+}
+
+/// Write the bytes to the output of a callback, replacing the written ones.
+///
+/// # Safety
+///
+/// The buffer must be the one passed to the running callback, and the data must be valid for
+/// reads.
+#[no_mangle]
+pub unsafe extern "C" fn mlm_buffer_write(buffer: *mut MlmBuffer, data: MlmBytes) {
+    if let Some(buffer) = buffer.as_mut() {
+        buffer.0 = Some(data.to_bytes().to_vec());
+    }
+}
+
+fn send(instance: &MlmInstance, msg: MlmMsg<FfiBlock>) -> i32 {
+    match instance.handler.send_msg(Context::new(), msg) {
+        Ok(()) => MLM_OK,
+        Err(e) => {
+            log::warn!("Mlm: ffi send message error {:?}", e);
+            MLM_ERR
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::{c_char, c_void};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use bytes::Bytes;
+    use tiny_keccak::{Hasher, Keccak};
+
+    use super::*;
+    use crate::transport::encode_payload;
+    use crate::types::{Address, Hash, SignedVote, Vote, VoteType};
+
+    /// The host of a single node, which commits the heights by its own votes.
+    struct Host {
+        address: Vec<u8>,
+        commits: AtomicU64,
+        errors: AtomicU64,
+        wal: Mutex<Option<Vec<u8>>>,
+        last_signed: Mutex<Option<Vec<u8>>>,
+    }
+
+    unsafe fn host<'a>(user_data: *mut c_void) -> &'a Host {
+        &*(user_data as *const Host)
+    }
+
+    fn node(host: &Host) -> Node {
+        Node::new(Address::from_slice(&host.address).unwrap())
+    }
+
+    fn authority_list(host: &Host) -> Vec<u8> {
+        serde_json::to_vec(&vec![node(host)]).unwrap()
+    }
+
+    unsafe fn write(buffer: *mut MlmBuffer, data: &[u8]) {
+        mlm_buffer_write(buffer, MlmBytes::from_slice(data));
+    }
+
+    fn keccak(data: &[u8]) -> [u8; 32] {
+        let mut hasher = Keccak::v256();
+        hasher.update(data);
+        let mut output = [0u8; 32];
+        hasher.finalize(&mut output);
+        output
+    }
+
+    extern "C" fn get_block(
+        _: *mut c_void,
+        height: u64,
+        block: *mut MlmBuffer,
+        hash: *mut MlmBuffer,
+    ) -> i32 {
+        let content = height.to_be_bytes();
+        unsafe {
+            write(block, &content);
+            write(hash, &keccak(&content));
+        }
+        MLM_OK
+    }
+
+    extern "C" fn check_block(_: *mut c_void, _: u64, _: MlmBytes, _: MlmBytes) -> i32 {
+        MLM_OK
+    }
+
+    extern "C" fn commit(
+        user_data: *mut c_void,
+        height: u64,
+        commit: MlmBytes,
+        status: *mut MlmBuffer,
+    ) -> i32 {
+        unsafe {
+            let host = host(user_data);
+            let commit: Commit<FfiBlock> = rlp::decode(&commit.to_bytes()).unwrap();
+            assert_eq!(commit.height, height);
+            host.commits.fetch_add(1, Ordering::SeqCst);
+
+            let next = Status {
+                height: height + 1,
+                interval: Some(Duration::from_millis(10)),
+                timer_config: None,
+                authority_list: vec![node(host)],
+                next_authority_list: None,
+                proposer_seed: None,
+                skipped_proposers: Vec::new(),
+            };
+            write(status, &serde_json::to_vec(&next).unwrap());
+        }
+        MLM_OK
+    }
+
+    extern "C" fn get_authority_list(
+        user_data: *mut c_void,
+        _: u64,
+        list: *mut MlmBuffer,
+    ) -> i32 {
+        unsafe { write(list, &authority_list(host(user_data))) };
+        MLM_OK
+    }
+
+    extern "C" fn broadcast_to_other(_: *mut c_void, _: MlmBytes) -> i32 {
+        MLM_OK
+    }
+
+    extern "C" fn transmit_to_relayer(_: *mut c_void, _: MlmBytes, _: MlmBytes) -> i32 {
+        MLM_OK
+    }
+
+    extern "C" fn report_error(user_data: *mut c_void, _: *const c_char) {
+        unsafe { host(user_data).errors.fetch_add(1, Ordering::SeqCst) };
+    }
+
+    extern "C" fn report_view_change(_: *mut c_void, _: u64, _: u64, _: *const c_char) {}
+
+    extern "C" fn hash(_: *mut c_void, msg: MlmBytes, hash: *mut MlmBuffer) -> i32 {
+        unsafe { write(hash, &keccak(&msg.to_bytes())) };
+        MLM_OK
+    }
+
+    extern "C" fn failing_hash(_: *mut c_void, _: MlmBytes, _: *mut MlmBuffer) -> i32 {
+        1
+    }
+
+    extern "C" fn sign(
+        user_data: *mut c_void,
+        _: MlmBytes,
+        signature: *mut MlmBuffer,
+    ) -> i32 {
+        unsafe { write(signature, &host(user_data).address) };
+        MLM_OK
+    }
+
+    extern "C" fn aggregate_signatures(
+        _: *mut c_void,
+        _: *const MlmBytes,
+        _: *const MlmBytes,
+        _: usize,
+        signature: *mut MlmBuffer,
+    ) -> i32 {
+        unsafe { write(signature, b"aggregated") };
+        MLM_OK
+    }
+
+    extern "C" fn verify_signature(
+        _: *mut c_void,
+        signature: MlmBytes,
+        _: MlmBytes,
+        voter: MlmBytes,
+    ) -> i32 {
+        if unsafe { signature.to_bytes() == voter.to_bytes() } {
+            MLM_OK
+        } else {
+            1
+        }
+    }
+
+    extern "C" fn verify_aggregated_signature(
+        _: *mut c_void,
+        _: MlmBytes,
+        _: MlmBytes,
+        _: *const MlmBytes,
+        _: usize,
+    ) -> i32 {
+        MLM_OK
+    }
+
+    extern "C" fn wal_save(user_data: *mut c_void, info: MlmBytes) -> i32 {
+        unsafe {
+            *host(user_data).wal.lock().unwrap() = Some(info.to_bytes().to_vec());
+        }
+        MLM_OK
+    }
+
+    extern "C" fn wal_load(user_data: *mut c_void, info: *mut MlmBuffer) -> i32 {
+        unsafe {
+            if let Some(saved) = host(user_data).wal.lock().unwrap().as_ref() {
+                write(info, saved);
+            }
+        }
+        MLM_OK
+    }
+
+    extern "C" fn wal_save_last_signed(user_data: *mut c_void, info: MlmBytes) -> i32 {
+        unsafe {
+            *host(user_data).last_signed.lock().unwrap() =
+                Some(info.to_bytes().to_vec());
+        }
+        MLM_OK
+    }
+
+    extern "C" fn wal_load_last_signed(
+        user_data: *mut c_void,
+        info: *mut MlmBuffer,
+    ) -> i32 {
+        unsafe {
+            if let Some(saved) = host(user_data).last_signed.lock().unwrap().as_ref() {
+                write(info, saved);
+            }
+        }
+        MLM_OK
+    }
+
+    fn callbacks(host: &Host) -> MlmCallbacks {
+        MlmCallbacks {
+            user_data: host as *const Host as *mut c_void,
+            get_block,
+            check_block,
+            commit,
+            get_authority_list,
+            broadcast_to_other,
+            transmit_to_relayer,
+            report_error,
+            report_view_change,
+            hash,
+            sign,
+            aggregate_signatures,
+            verify_signature,
+            verify_aggregated_signature,
+            wal_save,
+            wal_load,
+            wal_save_last_signed,
+            wal_load_last_signed,
+        }
+    }
+
+    #[test]
+    fn test_ffi_run() {
+        let host = Host {
+            address: vec![7u8; 20],
+            commits: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            wal: Mutex::new(None),
+            last_signed: Mutex::new(None),
+        };
+        let list = authority_list(&host);
+        let instance = unsafe {
+            mlm_new(MlmBytes::from_slice(&host.address), callbacks(&host)) as usize
+        };
+
+        let run = thread::spawn(move || unsafe {
+            mlm_run(
+                instance as *const MlmInstance,
+                0,
+                10,
+                MlmBytes::from_slice(&list),
+            )
+        });
+
+        // The messages are decoded from the payloads, and the garbage is rejected.
+        let vote = MlmMsg::<FfiBlock>::SignedVote(SignedVote {
+            signature: Bytes::from(vec![1u8; 20]),
+            vote: Vote {
+                height: 100,
+                round: 0,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from(vec![2u8; 32]),
+            },
+            voter: Address::from_slice(&[1u8; 20]).unwrap(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        });
+        let payload = encode_payload(&vote).unwrap();
+        let instance = instance as *mut MlmInstance;
+        unsafe {
+            assert_eq!(
+                mlm_send_msg(instance, MlmBytes::from_slice(&payload)),
+                MLM_OK
+            );
+            assert_eq!(
+                mlm_send_msg(instance, MlmBytes::from_slice(b"garbage")),
+                MLM_ERR
+            );
+        }
+
+        // The node starts by the status of the first height, and commits the heights by the
+        // callbacks.
+        let status = serde_json::to_vec(&Status {
+            height: 1,
+            interval: Some(Duration::from_millis(10)),
+            timer_config: None,
+            authority_list: vec![node(&host)],
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: Vec::new(),
+        })
+        .unwrap();
+        unsafe {
+            assert_eq!(
+                mlm_send_status(instance, MlmBytes::from_slice(&status)),
+                MLM_OK
+            );
+        }
+        let start = Instant::now();
+        while host.commits.load(Ordering::SeqCst) < 3 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(host.wal.lock().unwrap().is_some());
+        assert!(host.last_signed.lock().unwrap().is_some());
+
+        unsafe {
+            assert_eq!(mlm_stop(instance), MLM_OK);
+            assert_eq!(run.join().unwrap(), MLM_OK);
+            mlm_free(instance);
+        }
+    }
+
+    #[test]
+    fn test_ffi_hash_error() {
+        let host = Host {
+            address: vec![7u8; 20],
+            commits: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            wal: Mutex::new(None),
+            last_signed: Mutex::new(None),
+        };
+        let mut callbacks = callbacks(&host);
+        callbacks.hash = failing_hash;
+        let adapter = FfiAdapter::new(callbacks);
+        assert!(adapter.sign(Hash::from(vec![1u8; 32])).is_ok());
+
+        // A failed hash is reported once, and no hash is signed after it.
+        assert!(adapter.hash(Bytes::from("mlm")).is_empty());
+        assert!(adapter.hash(Bytes::from("mlm")).is_empty());
+        assert_eq!(host.errors.load(Ordering::SeqCst), 1);
+        let err = adapter.sign(Hash::new()).unwrap_err();
+        let err = err.downcast_ref::<ConsensusError>().unwrap();
+        assert!(matches!(err, ConsensusError::SignerUnavailable(_)));
+        assert!(err.is_fatal());
+    }
+}
@@ -0,0 +1,368 @@
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use creep::Context;
+use futures::channel::mpsc::unbounded;
+use futures::executor::block_on;
+use rlp::{Decodable, Encodable};
+
+use crate::error::ConsensusError;
+use crate::event_sink::LogEventSink;
+use crate::state::digest::DigestCache;
+use crate::state::parallel::parallel_verify;
+use crate::telemetry::NoopTrace;
+use crate::types::{
+    arbitrary_bytes, Address, AggregatedSignature, AggregatedVote, BlockPart,
+    BlockResponse, Commit, CompactProposal, GetBlock, Hash, MlmMsg, Node, PartedProposal,
+    Signature, SignedChoke, SignedHeartbeat, SignedKeyRotation, SignedProposal,
+    SignedVote, Status, ViewChangeReason,
+};
+#[cfg(feature = "wire-protobuf")]
+use crate::types::{Proposal, Vote};
+use crate::utils::auth_manage::AuthorityManage;
+use crate::wire::RlpCodec;
+#[cfg(feature = "wire-protobuf")]
+use crate::wire::{ProtobufCodec, WireCodec};
+use crate::{Codec, Consensus, Crypto};
+
+/// The number of the authorities of the fuzzed verification, the address of the `i`th of
+/// which is 20 bytes of `i`.
+const AUTHORITY_NUM: u8 = 4;
+
+/// The block of the fuzzed messages, which is the raw bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzBlock(pub Bytes);
+
+impl Codec for FuzzBlock {
+    type Error = Infallible;
+
+    fn encode(&self) -> Result<Bytes, Self::Error> {
+        Ok(self.0.clone())
+    }
+
+    fn decode(data: Bytes) -> Result<Self, Self::Error> {
+        Ok(FuzzBlock(data))
+    }
+
+    fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+        Ok(FuzzBlock(Bytes::copy_from_slice(data)))
+    }
+}
+
+impl<'a> arbitrary::Arbitrary<'a> for FuzzBlock {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_bytes(u).map(FuzzBlock)
+    }
+}
+
+/// Decode the data as each of the wire messages by rlp, as a payload from a peer is decoded
+/// after its tag. A decoded message must be encoded and decoded back to itself, and the
+/// malformed data must be rejected without a panic.
+pub fn decode_rlp(data: &[u8]) {
+    roundtrip::<SignedProposal<FuzzBlock>>(data);
+    roundtrip::<SignedVote>(data);
+    roundtrip::<AggregatedVote>(data);
+    roundtrip::<SignedChoke>(data);
+    roundtrip::<CompactProposal>(data);
+    roundtrip::<GetBlock>(data);
+    roundtrip::<BlockResponse>(data);
+    roundtrip::<PartedProposal>(data);
+    roundtrip::<BlockPart>(data);
+    roundtrip::<SignedHeartbeat>(data);
+    roundtrip::<SignedKeyRotation>(data);
+
+    if let Ok(votes) = rlp::Rlp::new(data).as_list::<SignedVote>() {
+        let encoded = rlp::encode_list(&votes);
+        assert_eq!(rlp::Rlp::new(&encoded).as_list::<SignedVote>(), Ok(votes));
+    }
+}
+
+/// Decode the data as a vote and as a proposal by rlp, and check the signature preimages of
+/// them by the rlp and the protobuf codecs against each other. The preimage of each codec
+/// must decode back to the message, and the message decoded from either preimage must be
+/// signed over the same hash as the original by both codecs and by the digests of the
+/// verification, so the encodings never split the nodes over the bytes that they sign.
+#[cfg(feature = "wire-protobuf")]
+pub fn diff_codecs(data: &[u8]) {
+    let codecs: [&dyn WireCodec; 2] = [&RlpCodec, &ProtobufCodec];
+
+    if let Ok(vote) = rlp::decode::<Vote>(data) {
+        let decoded = [
+            rlp::decode::<Vote>(&RlpCodec.vote_preimage(&vote).unwrap()).ok(),
+            ProtobufCodec::decode_vote(&ProtobufCodec.vote_preimage(&vote).unwrap()),
+        ];
+        for decoded in decoded {
+            assert_eq!(decoded.as_ref(), Some(&vote));
+        }
+        for codec in codecs {
+            let hash = FuzzCrypto.hash(codec.vote_preimage(&vote).unwrap());
+            assert_eq!(
+                DigestCache::new().vote(&FuzzCrypto, codec, &vote).unwrap(),
+                hash
+            );
+        }
+    }
+
+    if let Ok(mut proposal) = rlp::decode::<Proposal<Bytes>>(data) {
+        // The lock is a prevote QC, which carries no vote extensions.
+        if let Some(polc) = proposal.lock.as_mut() {
+            polc.lock_votes.extensions.clear();
+        }
+        let decoded = [
+            rlp::decode::<Proposal<Bytes>>(
+                &RlpCodec.proposal_preimage(&proposal).unwrap(),
+            )
+            .ok(),
+            ProtobufCodec::decode_proposal(
+                &ProtobufCodec.proposal_preimage(&proposal).unwrap(),
+            ),
+        ];
+        for decoded in decoded {
+            let decoded = decoded.unwrap();
+            assert_eq!(decoded, proposal);
+            for codec in codecs {
+                assert_eq!(
+                    FuzzCrypto.hash(codec.proposal_preimage(&decoded).unwrap()),
+                    FuzzCrypto.hash(codec.proposal_preimage(&proposal).unwrap())
+                );
+            }
+        }
+    }
+}
+
+fn roundtrip<M: Decodable + Encodable + Debug + PartialEq>(data: &[u8]) {
+    if let Ok(msg) = rlp::decode::<M>(data) {
+        assert_eq!(rlp::decode::<M>(&rlp::encode(&msg)), Ok(msg));
+    }
+}
+
+/// Verify the message by the verification task of the received messages with the authorities
+/// of `AUTHORITY_NUM` nodes, and return whether it passes. A signature is valid if it is the
+/// address of the signer, and an aggregated signature is valid if it is not empty, so the
+/// fuzzer reaches the checks behind the signatures. The invalid message must be reported
+/// without a panic.
+pub fn verify_msg(msg: MlmMsg<FuzzBlock>) -> bool {
+    let mut authority_list = (0..AUTHORITY_NUM)
+        .map(|i| Node::new(Address::from_slice(&[i; 20]).unwrap()))
+        .collect();
+    let mut authority = AuthorityManage::new();
+    authority.update(&mut authority_list);
+
+    let (tx, mut rx) = unbounded();
+    block_on(parallel_verify(
+        Context::new(),
+        msg,
+        &NoopTrace,
+        Arc::new(LogEventSink),
+        Arc::new(FuzzAdapter),
+        Arc::new(FuzzCrypto),
+        Arc::new(RlpCodec),
+        Arc::new(authority),
+        Arc::new(DigestCache::new()),
+        tx,
+    ));
+    rx.try_recv().is_ok()
+}
+
+struct FuzzCrypto;
+
+impl Crypto for FuzzCrypto {
+    type QcSignature = AggregatedSignature;
+
+    fn hash(&self, msg: Bytes) -> Hash {
+        Hash::from(msg)
+    }
+
+    fn sign(&self, _hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+        Ok(Signature::new())
+    }
+
+    fn aggregate_signatures(
+        &self,
+        _signatures: Vec<Signature>,
+        _voters: Vec<Address>,
+    ) -> Result<Signature, Box<dyn Error + Send>> {
+        Ok(Signature::new())
+    }
+
+    fn verify_signature(
+        &self,
+        signature: Signature,
+        _hash: Hash,
+        voter: Address,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        if signature != voter.as_bytes() {
+            return Err(invalid_signature());
+        }
+        Ok(())
+    }
+
+    fn verify_aggregated_signature(
+        &self,
+        aggregate_signature: Signature,
+        _msg_hash: Hash,
+        _voters: Vec<Address>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        if aggregate_signature.is_empty() {
+            return Err(invalid_signature());
+        }
+        Ok(())
+    }
+}
+
+fn invalid_signature() -> Box<dyn Error + Send> {
+    Box::new(ConsensusError::CryptoErr(
+        "Invalid fuzzed signature".to_string(),
+    ))
+}
+
+/// The adapter of the fuzzed verification, which only takes the reports.
+struct FuzzAdapter;
+
+#[async_trait]
+impl Consensus<FuzzBlock> for FuzzAdapter {
+    async fn get_block(
+        &self,
+        _ctx: Context,
+        _height: u64,
+    ) -> Result<(FuzzBlock, Hash), Box<dyn Error + Send>> {
+        Err(unsupported())
+    }
+
+    async fn check_block(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _hash: Hash,
+        _block: FuzzBlock,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Err(unsupported())
+    }
+
+    async fn commit(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _commit: Commit<FuzzBlock>,
+    ) -> Result<Status, Box<dyn Error + Send>> {
+        Err(unsupported())
+    }
+
+    async fn get_authority_list(
+        &self,
+        _ctx: Context,
+        _height: u64,
+    ) -> Result<Vec<Node>, Box<dyn Error + Send>> {
+        Err(unsupported())
+    }
+
+    async fn broadcast_to_other(
+        &self,
+        _ctx: Context,
+        _msg: MlmMsg<FuzzBlock>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
+    async fn transmit_to_relayer(
+        &self,
+        _ctx: Context,
+        _addr: Address,
+        _msg: MlmMsg<FuzzBlock>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
+    fn report_error(&self, _ctx: Context, _error: ConsensusError) {}
+
+    fn report_view_change(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _round: u64,
+        _reason: ViewChangeReason,
+    ) {
+    }
+}
+
+fn unsupported() -> Box<dyn Error + Send> {
+    Box::new(ConsensusError::Other(
+        "Unsupported by the fuzzed verification".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{decode_rlp, verify_msg, FuzzBlock};
+    use crate::types::{Address, Hash, MlmMsg, SignedVote, Vote, VoteType};
+
+    fn gen_vote(signature: Bytes) -> SignedVote {
+        SignedVote {
+            signature,
+            vote: Vote {
+                height: 1,
+                round: 0,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from([1u8; 32]),
+            },
+            voter: Address::from_slice(&[1u8; 20]).unwrap(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        }
+    }
+
+    #[test]
+    fn test_fuzz_entries() {
+        let vote = gen_vote(Bytes::from(vec![1u8; 20]));
+        decode_rlp(&rlp::encode(&vote));
+        decode_rlp(&[0xc3, 0x01, 0x02]);
+        decode_rlp(&[0xff; 8]);
+
+        assert!(verify_msg(MlmMsg::SignedVote(vote)));
+        let forged = gen_vote(Bytes::from(vec![2u8; 20]));
+        assert!(!verify_msg(MlmMsg::<FuzzBlock>::SignedVote(forged)));
+    }
+
+    #[cfg(feature = "wire-protobuf")]
+    #[test]
+    fn test_diff_codecs() {
+        use super::diff_codecs;
+        use crate::types::{AggregatedSignature, AggregatedVote, PoLC, Proposal};
+
+        let vote = gen_vote(Bytes::new()).vote;
+        diff_codecs(&rlp::encode(&vote));
+
+        let proposal = Proposal {
+            height: 1,
+            round: 1,
+            content: Bytes::from(vec![1, 2, 3]),
+            block_hash: vote.block_hash.clone(),
+            lock: Some(PoLC {
+                lock_round: 0,
+                lock_votes: AggregatedVote {
+                    signature: AggregatedSignature {
+                        signature: Bytes::from(vec![9u8; 96]),
+                        address_bitmap: Bytes::from(vec![0b1110_0000]),
+                    },
+                    vote_type: VoteType::Prevote,
+                    height: 1,
+                    round: 0,
+                    block_hash: vote.block_hash.clone(),
+                    leader: Address::from_slice(&[1u8; 20]).unwrap(),
+                    extensions: Vec::new(),
+                },
+            }),
+            proposer: Address::from_slice(&[1u8; 20]).unwrap(),
+            timestamp: Some(1_700_000_000_000),
+        };
+        diff_codecs(&rlp::encode(&proposal));
+        diff_codecs(&[0xc3, 0x01, 0x02]);
+        diff_codecs(&[0xff; 8]);
+    }
+}
@@ -7,45 +7,202 @@
 #![recursion_limit = "512"]
 #![allow(clippy::mutable_key_type)]
 
+/// The mapping from public keys to addresses.
+pub mod address;
+/// The builder of the authority lists from the stakes of the validators.
+pub mod authority;
+/// Consensus on ordered batches of blocks.
+pub mod batch;
+/// The builder of an mlm instance validating its configuration.
+#[cfg(feature = "runtime")]
+pub mod builder;
+/// The canonical encodings of the signature preimages for the external signers.
+pub mod canonical;
+/// The compatibility of the wire messages and the wal records with the released versions.
+#[cfg(all(test, feature = "runtime"))]
+mod compat;
 /// A module that impl rlp encodable and decodable trait for types that need to save wal.
 mod codec;
+/// Configuration of an mlm instance.
+pub mod config;
+/// The default crypto of secp256k1 and BLS signatures.
+#[cfg(feature = "default-crypto")]
+pub mod crypto;
 /// Mlm error module.
 pub mod error;
+/// Consensus events and errors published to the subscribers.
+pub mod event;
+/// Typed events of the invalid messages for the integrators.
+pub mod event_sink;
+/// The C interface for embedding the engine in the nodes written in other languages. The
+/// shared library is built by `cargo rustc --release --features ffi --crate-type cdylib`.
+#[cfg(feature = "ffi")]
+pub mod ffi;
+/// The entry points of the fuzz targets of the wire messages.
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+/// Memory accounting of the caches of an mlm instance.
+pub mod memory;
 /// Create and run the mlm consensus process.
+#[cfg(feature = "runtime")]
 pub mod mlm;
+/// The signature schemes of the QCs.
+pub mod multisig;
+/// The traits and types that most integrations need, imported by `use mlm::prelude::*`.
+pub mod prelude;
+/// Stores and verification of the proofs of the committed heights.
+pub mod proof;
+/// The recorder of the inputs and the outputs of the state machine, and the replay of them.
+pub mod replay;
+/// The injectable source of the randomness of the engine.
+pub mod rng;
+/// The executor that the background tasks and the timers run on.
+pub mod runtime;
 /// serialize Bytes in hex format
 pub mod serde_hex;
+/// serialize Option<Duration> in milliseconds
+mod serde_millis;
 /// serialize Vec<Bytes> in hex format
 mod serde_multi_hex;
 /// State machine replicas module to do state changes.
 mod smr;
 /// The state module to storage proposals and votes.
+#[cfg(feature = "runtime")]
 mod state;
+/// Pluggable telemetry of the message handling.
+pub mod telemetry;
+/// A deterministic simulator of mlm nodes and the byzantine behaviors for testing.
+#[cfg(feature = "testing")]
+pub mod testing;
+/// The thread hints of the consensus critical tasks.
+pub mod threads;
+/// Pluggable quorum threshold policies of the votes.
+pub mod threshold;
 /// The timer module to ensure the protocol liveness.
+#[cfg(feature = "runtime")]
 mod timer;
+/// The stubs shared by the unit tests.
+#[cfg(test)]
+mod test_utils;
+/// Transports between the authorities.
+#[cfg(any(feature = "tcp", feature = "mlm-libp2p", feature = "ffi"))]
+pub mod transport;
 /// Message types using in the mlm consensus protocol.
 pub mod types;
 /// Some utility functions.
 mod utils;
+/// Export of the raw votes of the committed heights.
+pub mod vote_export;
 /// Write ahead log module.
 mod wal;
-
+/// The encodings of the signature preimages and the wal payloads.
+pub mod wire;
+
+pub use self::address::AddressScheme;
+pub use self::authority::{AuthorityList, AuthorityListBuilder};
+pub use self::batch::{Batch, BatchMemberProof};
+#[cfg(feature = "runtime")]
+pub use self::builder::MlmBuilder;
+pub use self::config::{EffectiveConfig, MlmConfig};
+#[cfg(feature = "default-crypto")]
+pub use self::crypto::DefaultCrypto;
+pub use self::event::{ConfigDiff, ConfigSource, MlmError, MlmEvent, MlmEventKind};
+pub use self::event_sink::{ConsensusEvent, ConsensusEventSink, LogEventSink};
+#[cfg(feature = "runtime")]
 pub use self::mlm::Mlm;
-pub use self::mlm::MlmHandler;
-pub use self::utils::auth_manage::{extract_voters, get_leader};
+#[cfg(feature = "runtime")]
+pub use self::mlm::{MlmHandler, SendMetrics};
+pub use self::multisig::{QcSignature, ThresholdSignature};
+pub use self::proof::{
+    verify_handover, verify_proof, verify_proof_with_codec, ProofStore,
+};
+pub use self::rng::{Rng, SeededRng};
+pub use self::runtime::Runtime;
+#[cfg(feature = "runtime")]
+pub use self::runtime::TokioRuntime;
+pub use self::smr::pure::{Smr, SmrInput, SmrOutput, SmrState};
+pub use self::smr::smr_types::Step;
+#[cfg(feature = "trace_check")]
+pub use self::smr::trace;
+#[cfg(feature = "runtime")]
+pub use self::state::aggregation::AggregationStats;
+#[cfg(feature = "runtime")]
+pub use self::state::connectivity::{ConnectivitySummary, PeerConnectivity};
+#[cfg(feature = "runtime")]
+pub use self::state::dedup::VoteDedupStats;
+#[cfg(feature = "runtime")]
+pub use self::state::dump::{ChokeDump, LockDump, QcDump, StateDump, VoteDump};
+#[cfg(feature = "runtime")]
+pub use self::state::future::FutureBufferStats;
+#[cfg(feature = "runtime")]
+pub use self::state::heartbeat::ValidatorLiveness;
+#[cfg(feature = "runtime")]
+pub use self::state::median_time::median_time;
+#[cfg(feature = "runtime")]
+pub use self::state::protocol::{
+    BrakeReport, HaltReport, ProtocolStats, RoundLimitReport,
+};
+#[cfg(feature = "runtime")]
+pub use self::state::qc_gossip::QcGossipStats;
+#[cfg(feature = "runtime")]
+pub use self::state::rate_limit::RateLimitStats;
+#[cfg(feature = "runtime")]
+pub use self::state::reliability::ProposerReliability;
+#[cfg(feature = "runtime")]
+pub use self::state::retention::ExpiredHeight;
+#[cfg(feature = "runtime")]
+pub use self::state::snapshot::ConsensusSnapshot;
+#[cfg(feature = "runtime")]
+pub use self::state::stall::{MessageRecord, StallDump, ValidatorVotes};
+#[cfg(feature = "runtime")]
+pub use self::state::timing::{HeightReport, VoteArrival};
+#[cfg(feature = "runtime")]
+pub use self::state::window::HeightWindowStats;
+#[cfg(feature = "opentelemetry")]
+pub use self::telemetry::OtelTrace;
+#[cfg(feature = "tracing")]
+pub use self::telemetry::TracingTrace;
+pub use self::telemetry::{NoopTrace, SpanAttrs, Trace};
+pub use self::threads::ThreadHints;
+pub use self::threshold::{
+    BftThreshold, FixedCountThreshold, StakeRatioThreshold, ThresholdPolicy,
+};
+pub use self::utils::auth_manage::{
+    build_bitmap, extract_voters, get_leader, proposer_at, seeded_proposer_at,
+    validate_authority_list,
+};
+pub use self::vote_export::{VoteSet, VoteSink};
 pub use creep::Context;
-pub use wal::WalInfo;
-
+#[cfg(feature = "wal-aes-gcm")]
+pub use wal::AesGcmCipher;
+pub use wal::{
+    LastSigned, StepRecord, StepRecords, WalCipher, WalInfo, WalMigration,
+    WalRecordKind, WalSyncPolicy, WalSyncer, WAL_VERSION,
+};
+#[cfg(feature = "rocksdb")]
+pub use wal::{RocksWal, RocksWalDB, DEFAULT_RETAIN_HEIGHTS, DEFAULT_RETAIN_STEPS};
+#[cfg(feature = "wire-bincode")]
+pub use wire::BincodeCodec;
+#[cfg(feature = "wire-protobuf")]
+pub use wire::ProtobufCodec;
+pub use wire::{RlpCodec, WireCodec};
+
+use std::convert::Infallible;
 use std::error::Error;
 use std::fmt::Debug;
+#[cfg(feature = "runtime")]
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
-use crate::error::ConsensusError;
+use crate::error::{ConfigError, ConsensusError};
+use crate::types::{Address, Hash, KeyRotation, Node, Signature};
+#[cfg(feature = "runtime")]
 use crate::types::{
-    Address, Commit, Hash, MlmMsg, Node, Signature, Status, ViewChangeReason,
+    AggregatedSignature, Commit, MlmMsg, SignedAttestation, SignedVote, Status,
+    ViewChangeInfo, ViewChangeReason,
 };
 
 /// Mlm consensus result.
@@ -54,10 +211,14 @@ pub type ConsensusResult<T> = std::result::Result<T, ConsensusError>;
 const INIT_HEIGHT: u64 = 0;
 const INIT_ROUND: u64 = 0;
 
-/// Trait for some functions that consensus needs.
+/// Trait for some functions that consensus needs. The contexts of the calls made by the state
+/// carry the `CorrelationId` of the current round.
+#[cfg(feature = "runtime")]
 #[async_trait]
 pub trait Consensus<T: Codec>: Send + Sync {
-    /// Get a block of the given height and return the block with its hash.
+    /// Get a block of the given height and return the block with its hash. The context carries
+    /// the `RoundContext` of the round to propose in, and the call is cancelled at the deadline
+    /// of it.
     async fn get_block(
         &self,
         ctx: Context,
@@ -65,7 +226,8 @@ pub trait Consensus<T: Codec>: Send + Sync {
     ) -> Result<(T, Hash), Box<dyn Error + Send>>;
 
     /// Check the correctness of a block. If is passed, return the integrated transcations to do
-    /// data persistence.
+    /// data persistence. The context carries the `RoundContext` of the round that the block
+    /// is proposed in, and the call is cancelled at the deadline of it.
     async fn check_block(
         &self,
         ctx: Context,
@@ -74,6 +236,22 @@ pub trait Consensus<T: Codec>: Send + Sync {
         block: T,
     ) -> Result<(), Box<dyn Error + Send>>;
 
+    /// Pre-check a block as soon as its proposal arrives, so that the application starts to
+    /// verify and execute the block in parallel with the vote collection, and the later
+    /// `check_block()` of it is a hit of its cache. The proposal has passed the signature
+    /// verification, but the block may never be checked, e.g. if its round ends before the
+    /// node steps to it. This is called once for a block in a height by the consensus loop,
+    /// so the application spawns the work and returns at once.
+    fn pre_check_block(&self, _ctx: Context, _height: u64, _hash: Hash, _block: &T) {}
+
+    /// Compute the hash of a block as the application does. The state compares it with the
+    /// hash of the proposal once `check_block()` passes and again before committing the block,
+    /// and reports a mismatch by `ConsensusError::BlockHashMismatch` with a forensic record.
+    /// The default computes none and skips the comparisons.
+    fn block_hash(&self, _height: u64, _block: &T) -> Option<Hash> {
+        None
+    }
+
     /// Commit a given height to execute and return the rich status.
     async fn commit(
         &self,
@@ -104,9 +282,134 @@ pub trait Consensus<T: Codec>: Send + Sync {
         msg: MlmMsg<T>,
     ) -> Result<(), Box<dyn Error + Send>>;
 
-    /// Report the mlm error with the corresponding context.
+    /// Aggregate the signed votes of a QC by an external service and return the signature of
+    /// the QC on the wire, which is decoded by `Crypto::QcSignature`. This is called only if
+    /// `MlmConfig::external_aggregation` is enabled, and the result is verified by
+    /// `Crypto::verify_aggregated_signature()`.
+    async fn aggregate_votes(
+        &self,
+        _ctx: Context,
+        _votes: Vec<SignedVote>,
+    ) -> Result<AggregatedSignature, Box<dyn Error + Send>> {
+        Err(Box::new(ConsensusError::CryptoErr(
+            "External aggregation is not supported".to_string(),
+        )))
+    }
+
+    /// Return whether the network layer is ready to start consensus, e.g. it has connected to
+    /// a quorum of the given authorities. This is polled before the first step only if
+    /// `MlmConfig::ready_gate` is enabled.
+    async fn network_ready(&self, _ctx: Context, _authority_list: Vec<Node>) -> bool {
+        true
+    }
+
+    /// Return whether the data of the block of the hash has been published, e.g. to the data
+    /// availability layer of the chain. This is called before prevoting for a block only if
+    /// `MlmConfig::compact_proposal` is enabled, and the node prevotes nil if it returns false
+    /// or does not return in `MlmConfig::availability_timeout`.
+    async fn check_availability(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _hash: Hash,
+    ) -> bool {
+        true
+    }
+
+    /// Extend the precommit of the block of the hash with a payload of the application, such
+    /// as an oracle price or a share of a threshold decryption, which the node signs and
+    /// carries to the precommit QC and the proof of the commit. This is called only if
+    /// `MlmConfig::vote_extensions` is enabled, and the node precommits without an extension
+    /// if it returns an empty payload, an error, or does not return in the precommit timeout.
+    async fn extend_vote(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _round: u64,
+        _hash: Hash,
+    ) -> Result<Bytes, Box<dyn Error + Send>> {
+        Ok(Bytes::new())
+    }
+
+    /// Verify the extension of a precommit of the voter, whose signature has been verified.
+    /// A precommit whose extension is rejected is dropped, so the application rejects only
+    /// the extensions that no honest voter produces. The extensions carried by a QC are
+    /// verified by their signatures only.
+    async fn verify_vote_extension(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _round: u64,
+        _hash: Hash,
+        _voter: Address,
+        _extension: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
+    /// Report the mlm error with the corresponding context. The errors are also delivered to
+    /// the subscribers of `MlmHandler::subscribe_errors()` with their severity. The violated
+    /// invariants and the other fatal errors are reported here instead of panicking, and the
+    /// application decides whether to halt, restart or alert.
     fn report_error(&self, ctx: Context, error: ConsensusError);
 
+    /// Report the dump of the state when the node makes no height progress for
+    /// `MlmConfig::stall_timeout`. It is reported again every such timeout until the node
+    /// reaches the next height.
+    fn report_stall(&self, _ctx: Context, _dump: StallDump) {}
+
+    /// Report the connectivity to the authorities when the node has not heard from a third
+    /// of the vote weight in `MlmConfig::connectivity_window`, which tells a partitioned node
+    /// from a node waiting for a proposer that is down. It is reported again every such
+    /// window until the node hears from a quorum.
+    fn report_connectivity(&self, _ctx: Context, _summary: ConnectivitySummary) {}
+
+    /// Report a brake of the node when it leaves the round, with the time that it stays in
+    /// the brake step and the authorities whose chokes are collected.
+    fn report_brake(&self, _ctx: Context, _report: BrakeReport) {}
+
+    /// Report the timing of a height after it is committed, with the elapsed time of the
+    /// steps, the vote arrivals and the bytes transferred. This is called only if
+    /// `MlmConfig::height_report` is enabled.
+    fn report_height(&self, _ctx: Context, _report: HeightReport) {}
+
+    /// Archive a past height before its proposals, votes and QCs are evicted from the caches
+    /// after `MlmConfig::height_retention` heights, such as to a cold store serving the
+    /// explorers. This is called in the order of the heights only if
+    /// `MlmConfig::archive_expired_heights` is enabled, and should return quickly since the
+    /// state waits for it.
+    fn archive_height(&self, _ctx: Context, _expired: ExpiredHeight<T>) {}
+
+    /// Report a signed attestation of the state of the node every
+    /// `MlmConfig::attestation_interval`, which the application forwards to the external
+    /// monitors. A monitor verifies the signature over
+    /// `canonical::attestation_preimage()` by the address of the attestation, and compares
+    /// the attestations of the validators without trusting their metrics.
+    fn report_attestation(&self, _ctx: Context, _attestation: SignedAttestation) {}
+
+    /// Escalate a halt of the consensus when the node brakes `MlmConfig::max_brake_rounds`
+    /// rounds in a row, so that the application can trigger an external recovery, such as a
+    /// resync or an alert, instead of the node braking forever silently.
+    fn on_consensus_halted(&self, _ctx: Context, _report: HaltReport) {}
+
+    /// Escalate a height that runs `MlmConfig::max_rounds` rounds without a commit, so that
+    /// the application can trigger a manual intervention or fall back to another authority
+    /// list by a new status.
+    fn on_max_rounds(&self, _ctx: Context, _report: RoundLimitReport) {}
+
+    /// Report a message dropped by the verification, such as one with a bad signature, so
+    /// that the application can score and ban the peers sending the invalid messages. The peer
+    /// hint is the peer carried by the context of the message, which is set by the transport
+    /// that receives it, and the kind is the kind of the message.
+    fn report_invalid_msg(
+        &self,
+        _ctx: Context,
+        _peer_hint: Option<String>,
+        _kind: String,
+        _reason: String,
+    ) {
+    }
+
     /// Report the mlm view change reason.
     fn report_view_change(
         &self,
@@ -115,29 +418,166 @@ pub trait Consensus<T: Codec>: Send + Sync {
         round: u64,
         reason: ViewChangeReason,
     );
+
+    /// Report the mlm view change with the expected proposer, the elapsed time of the steps
+    /// and the votes collected in the round. The default reports the reason only by
+    /// `report_view_change()`.
+    fn report_view_change_info(&self, ctx: Context, info: ViewChangeInfo) {
+        self.report_view_change(ctx, info.height, info.round, info.reason)
+    }
 }
 
 /// Trait for doing serialize and deserialize.
 pub trait Codec: Clone + Debug + Send + PartialEq + Eq {
+    /// The error of serializing and deserializing. The engine keeps it as the source of the
+    /// `ConsensusError` it reports, so the adapter can downcast and match on it.
+    type Error: Error + Send + Sync + 'static;
+
     /// Serialize self into bytes.
-    fn encode(&self) -> Result<Bytes, Box<dyn Error + Send>>;
+    fn encode(&self) -> Result<Bytes, Self::Error>;
+
+    /// Deserialize the owned bytes into self. The default deserializes the borrowed bytes,
+    /// and a codec keeping the bytes, such as of a raw block, overrides it to save the copy.
+    fn decode(data: Bytes) -> Result<Self, Self::Error> {
+        Self::decode_ref(&data)
+    }
 
-    /// Deserialize date into self.
-    fn decode(data: Bytes) -> Result<Self, Box<dyn Error + Send>>;
+    /// Deserialize the borrowed bytes into self. The engine decodes the blocks in the
+    /// messages by it, so a codec that does not keep the bytes decodes a block without
+    /// copying it out of the message first.
+    fn decode_ref(data: &[u8]) -> Result<Self, Self::Error>;
+
+    /// The length of the serialized bytes if it is known without serializing, such as of a
+    /// block keeping its bytes. The engine estimates the memory of the cached proposals and
+    /// decides whether to split a block into parts by it, instead of serializing the block.
+    /// The default is `None`.
+    fn size_hint(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// The raw bytes of an encoded block, which is encoded as it is.
+impl Codec for Bytes {
+    type Error = Infallible;
+
+    fn encode(&self) -> Result<Bytes, Self::Error> {
+        Ok(self.clone())
+    }
+
+    fn decode(data: Bytes) -> Result<Self, Self::Error> {
+        Ok(data)
+    }
+
+    fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Bytes::copy_from_slice(data))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len())
+    }
 }
 
-/// Trait for save and load wal information.
+/// Trait for save and load wal information. The saved information is a versioned wal record,
+/// see `WalMigration` for the records of the older versions. A wal may sync the records to the
+/// disk by a `WalSyncPolicy`, except the safety critical ones which must always be durable.
 #[async_trait]
-pub trait Wal {
+pub trait Wal: Send + Sync {
     /// Save wal information.
     async fn save(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>>;
 
+    /// Save wal information that carries a lock. It must be durable, e.g. fsynced, when this
+    /// returns regardless of the sync policy, so that the node keeps the lock across a crash.
+    /// The default saves it by `save()`.
+    async fn save_durable(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        self.save(info).await
+    }
+
+    /// Sync the records saved but not synced yet by the `WalSyncPolicy` to the disk. It is
+    /// called once when the state stops, so that a restart recovers from the last wal
+    /// information. The default does nothing, which suits a wal syncing every record.
+    async fn flush(&self) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
     /// Load wal information.
     async fn load(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>>;
+
+    /// Save the record of the last signed message, which replaces the saved one. The engine
+    /// waits for it before a signature leaves the node, so the record must be durable, e.g.
+    /// fsynced, when this returns. It must be kept across restarts, or the node may sign a
+    /// conflicting message after a restart.
+    async fn save_last_signed(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>>;
+
+    /// Load the record of the last signed message.
+    async fn load_last_signed(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>>;
+
+    /// Save a checkpoint, the record of a full snapshot of the consensus state, which is taken
+    /// every `checkpoint_interval` heights. Only the latest one needs to be kept. The default
+    /// keeps nothing, which leaves the recovery to the wal information alone.
+    async fn save_checkpoint(
+        &self,
+        _checkpoint: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
+    /// Load the latest checkpoint, which is preferred on recovery if it is newer than the wal
+    /// information or the wal information is lost.
+    async fn load_checkpoint(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+
+    /// Save the archive of the recent commit proofs if `MlmConfig::persist_proof_archive` is
+    /// set, which replaces the saved one. The default keeps nothing, and a restarted node
+    /// serves the proofs from its next commit.
+    async fn save_proof_archive(
+        &self,
+        _archive: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
+    /// Load the archive of the recent commit proofs.
+    async fn load_proof_archive(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+
+    /// Save the signed votes collected in the current round if
+    /// `MlmConfig::persist_round_votes` is set, which replace the saved ones. It is called
+    /// on each collected vote, and needs not be durable since the votes only speed up the QC
+    /// of a restarted relayer. The default keeps nothing.
+    async fn save_round_votes(
+        &self,
+        _votes: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
+    /// Load the signed votes collected in the round of the last save.
+    async fn load_round_votes(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+
+    /// Append a step record, which the state writes at each transition relevant to the
+    /// safety, e.g. a round entered, a lock or a vote signed. The recovery never reads it, so
+    /// it needs not be durable, and only the recent records need to be kept. The default
+    /// keeps nothing.
+    async fn append_step(&self, _record: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
+    /// Load the kept step records, the oldest first, which are decoded by `StepRecords`.
+    async fn load_steps(&self) -> Result<Vec<Bytes>, Box<dyn Error + Send>> {
+        Ok(Vec::new())
+    }
 }
 
 /// Trait for some crypto methods.
 pub trait Crypto: Send {
+    /// The signature of a QC, which is `AggregatedSignature` for an aggregate with the bitmap
+    /// of the voters, or `ThresholdSignature` for a threshold signature naming no voters.
+    type QcSignature: QcSignature;
+
     /// Hash a message bytes.
     fn hash(&self, msg: Bytes) -> Hash;
 
@@ -151,6 +591,34 @@ pub trait Crypto: Send {
         voters: Vec<Address>,
     ) -> Result<Signature, Box<dyn Error + Send>>;
 
+    /// Aggregate the signatures of the voters into the signature of a QC with the address
+    /// bitmap of the voters in the authority list, which is the one of the QC built by the
+    /// engine. The authority list needs not be sorted.
+    fn aggregate(
+        &self,
+        signatures: Vec<(Address, Signature)>,
+        authority_list: &[Node],
+    ) -> Result<Self::QcSignature, Box<dyn Error + Send>> {
+        let (voters, signatures): (Vec<_>, Vec<_>) = signatures.into_iter().unzip();
+        let address_bitmap = build_bitmap(authority_list, &voters);
+        let signature = self.aggregate_signatures(signatures, voters)?;
+        Ok(Self::QcSignature::new(signature, address_bitmap))
+    }
+
+    /// Fold the signature of a voter into the running aggregated signature of the previous
+    /// voters, or start one with `None`, so the QC is ready as soon as the vote above the
+    /// threshold arrives. The folded signature of the voters must verify as the one
+    /// aggregated at once. Return `Ok(None)` if the aggregation is not incremental, which is
+    /// the default, then the signatures are aggregated at once when the QC is built.
+    fn fold_signature(
+        &self,
+        _aggregate: Option<&Signature>,
+        _signature: &Signature,
+        _voter: &Address,
+    ) -> Result<Option<Signature>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+
     /// Verify a signature and return the recovered address.
     fn verify_signature(
         &self,
@@ -166,6 +634,78 @@ pub trait Crypto: Send {
         msg_hash: Hash,
         voters: Vec<Address>,
     ) -> Result<(), Box<dyn Error + Send>>;
+
+    /// Verify the threshold signature of a QC against the group public key of the authority
+    /// list, such as the one of the distributed key generation of the authorities of the
+    /// epoch. It is called instead of `verify_aggregated_signature()` if the `QcSignature`
+    /// names no voters, and the threshold of the group must not be below the quorum of the
+    /// threshold policy. The partial signatures of a QC are combined by
+    /// `aggregate_signatures()` with the voters of them.
+    fn verify_threshold_signature(
+        &self,
+        _signature: Signature,
+        _msg_hash: Hash,
+        _authority_list: &[Node],
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Err(Box::new(ConsensusError::CryptoErr(
+            "Threshold signature is not supported".to_string(),
+        )))
+    }
+
+    /// Evaluate the VRF of the node over the input, such as an ECVRF of its key, and return
+    /// the output and the proof of it. It is called in the VRF leader mode of
+    /// `MlmConfig::vrf_leader`, where the proposal of the lowest output of a round wins.
+    fn vrf_prove(&self, _input: Bytes) -> Result<(Bytes, Bytes), Box<dyn Error + Send>> {
+        Err(Box::new(ConsensusError::CryptoErr(
+            "VRF is not supported".to_string(),
+        )))
+    }
+
+    /// Verify the VRF proof of the node over the input and return the output of it. The
+    /// outputs of a round are compared as bytes, so they should be of the same length.
+    fn vrf_verify(
+        &self,
+        _input: Bytes,
+        _proof: Bytes,
+        _address: Address,
+    ) -> Result<Bytes, Box<dyn Error + Send>> {
+        Err(Box::new(ConsensusError::CryptoErr(
+            "VRF is not supported".to_string(),
+        )))
+    }
+
+    /// Called when the authority list of an epoch is known, where the epoch is the first
+    /// height of the list: when a status changes the authority list, or when the list of the
+    /// next epoch is scheduled by `Status::next_authority_list` before the epoch begins.
+    /// Return the key rotation metadata of the node for the group keys of the epoch, such as
+    /// the dealing of a distributed key generation, which is signed and broadcast in a
+    /// `KeyRotation` to the authorities of the current and the next epoch.
+    fn on_authority_change(
+        &self,
+        _epoch: u64,
+        _authority_list: &[Node],
+    ) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+
+    /// Handle the verified key rotation metadata of an authority of the current or the next
+    /// epoch. Return the metadata of the node in reply, such as a complaint of a dealing,
+    /// which is broadcast as well.
+    fn on_key_rotation(
+        &self,
+        _rotation: &KeyRotation,
+    ) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+}
+
+/// A signer backed by a remote service, such as a KMS or an HSM. If it is set by
+/// `Mlm::set_async_crypto()`, it signs instead of `Crypto::sign()`, and the other functions of
+/// `Crypto` are still used.
+#[async_trait]
+pub trait AsyncCrypto: Debug + Send + Sync {
+    /// Sign to the given hash and return the signature if success.
+    async fn sign(&self, hash: Hash) -> Result<Signature, Box<dyn Error + Send>>;
 }
 
 /// The setting of the timeout interval of each step.
@@ -179,6 +719,13 @@ pub struct DurationConfig {
     pub precommit_ratio: u64,
     /// The proportion of retry choke message timeout to the height interval.
     pub brake_ratio: u64,
+    /// The backoff policy of the timeouts on repeated rounds. `None` means the default
+    /// policy.
+    pub backoff: Option<BackoffConfig>,
+    /// Change the round on the precommit timeout alone, without the chokes and the timeout
+    /// certificates of the brake step, which is for a small cluster of trusted nodes.
+    #[serde(default)]
+    pub choke_free: bool,
 }
 
 impl DurationConfig {
@@ -194,29 +741,139 @@ impl DurationConfig {
             prevote_ratio,
             precommit_ratio,
             brake_ratio,
+            backoff: None,
+            choke_free: false,
+        }
+    }
+
+    /// Set the backoff policy of the timeouts on repeated rounds, which fails if the policy is
+    /// invalid.
+    pub fn set_backoff(&mut self, backoff: BackoffConfig) -> Result<(), ConfigError> {
+        backoff.validate()?;
+        self.backoff = Some(backoff);
+        Ok(())
+    }
+
+    /// Set whether the round changes on the precommit timeout without the chokes.
+    pub fn set_choke_free(&mut self, choke_free: bool) {
+        self.choke_free = choke_free;
+    }
+
+    /// Check that none of the steps times out at once, including by the backoff policy.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut ratios = vec![
+            ("propose", self.propose_ratio),
+            ("prevote", self.prevote_ratio),
+            ("precommit", self.precommit_ratio),
+        ];
+        // The brake step never times out without the chokes.
+        if !self.choke_free {
+            ratios.push(("brake", self.brake_ratio));
+        }
+        if let Some((step, _)) = ratios.iter().find(|(_, ratio)| *ratio == 0) {
+            return Err(ConfigError::ZeroTimeoutRatio(step));
         }
+        match self.backoff.as_ref() {
+            Some(backoff) => backoff.validate(),
+            None => Ok(()),
+        }
+    }
+
+    /// The timeout ratios that the timer uses without a timer config.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn classic() -> Self {
+        DurationConfig::new(24, 10, 5, 3)
     }
 
+    #[cfg(feature = "runtime")]
     pub(crate) fn get_propose_config(&self) -> (u64, u64) {
         (self.propose_ratio, 10u64)
     }
 
+    #[cfg(feature = "runtime")]
     pub(crate) fn get_prevote_config(&self) -> (u64, u64) {
         (self.prevote_ratio, 10u64)
     }
 
+    #[cfg(feature = "runtime")]
     pub(crate) fn get_precommit_config(&self) -> (u64, u64) {
         (self.precommit_ratio, 10u64)
     }
 
+    #[cfg(feature = "runtime")]
     pub(crate) fn get_brake_config(&self) -> (u64, u64) {
         (self.brake_ratio, 10u64)
     }
 }
 
-#[cfg(test)]
+/// The exponential backoff policy of the step timeouts, keyed by round. The timeout of round
+/// `r` is multiplied by `min(base * multiplier ^ r, cap)`, where all of the coefficients are in
+/// units of a tenth. The default policy doubles the timeouts per round up to 32 times.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BackoffConfig {
+    /// The coefficient of round zero in tenths.
+    pub base: u64,
+    /// The growth of the coefficient per round in tenths.
+    pub multiplier: u64,
+    /// The max coefficient in tenths.
+    pub cap: u64,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        BackoffConfig {
+            base: 10,
+            multiplier: 20,
+            cap: 320,
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Create a timeout backoff policy, which fails if the base is zero or the cap is below
+    /// the base.
+    pub fn new(base: u64, multiplier: u64, cap: u64) -> Result<Self, ConfigError> {
+        let backoff = BackoffConfig {
+            base,
+            multiplier,
+            cap,
+        };
+        backoff.validate()?;
+        Ok(backoff)
+    }
+
+    /// Check that the base is not zero and the cap is not below it, for a policy whose fields
+    /// are set directly or decoded.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.base == 0 || self.cap < self.base {
+            return Err(ConfigError::InvalidBackoff {
+                base: self.base,
+                cap: self.cap,
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "runtime")]
+    pub(crate) fn get_timeout(&self, timeout: Duration, round: u64) -> Duration {
+        let mut coef = self.base;
+        let mut exp = 0;
+        while exp < round && coef < self.cap && self.multiplier > 10 {
+            coef = coef.saturating_mul(self.multiplier) / 10;
+            exp += 1;
+        }
+
+        let nanos = timeout.as_nanos() * u128::from(coef.min(self.cap)) / 10;
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
 mod test {
-    use super::DurationConfig;
+    use std::time::Duration;
+
+    use super::{BackoffConfig, DurationConfig};
+    use crate::error::ConfigError;
 
     #[test]
     fn test_duration_config() {
@@ -226,4 +883,59 @@ mod test {
         assert_eq!(config.get_precommit_config(), (3, 10));
         assert_eq!(config.get_brake_config(), (4, 10));
     }
+
+    #[test]
+    fn test_backoff() {
+        let timeout = Duration::from_millis(100);
+        let backoff = BackoffConfig::default();
+        for round in 0..10u64 {
+            let coef = 2u32.pow(round.min(5) as u32);
+            assert_eq!(backoff.get_timeout(timeout, round), timeout * coef);
+        }
+
+        let backoff = BackoffConfig::new(10, 15, 40).unwrap();
+        let expect = vec![100, 150, 220, 330, 400, 400];
+        for (round, millis) in expect.into_iter().enumerate() {
+            assert_eq!(
+                backoff.get_timeout(timeout, round as u64),
+                Duration::from_millis(millis)
+            );
+        }
+
+        // A multiplier no more than one never grows the timeout.
+        let backoff = BackoffConfig::new(20, 10, 80).unwrap();
+        assert_eq!(backoff.get_timeout(timeout, u64::MAX), timeout * 2);
+
+        // The sub-millisecond timeouts keep their precision, and a huge one saturates.
+        let backoff = BackoffConfig::default();
+        assert_eq!(
+            backoff.get_timeout(Duration::from_micros(300), 0),
+            Duration::from_micros(300)
+        );
+        assert_eq!(
+            backoff.get_timeout(Duration::from_micros(300), 2),
+            Duration::from_micros(1200)
+        );
+        assert_eq!(
+            backoff.get_timeout(Duration::MAX, 5),
+            Duration::from_nanos(u64::MAX)
+        );
+
+        // A zero base or cap would time out the steps at once.
+        let invalid = |base, cap| ConfigError::InvalidBackoff { base, cap };
+        assert_eq!(BackoffConfig::new(0, 20, 320), Err(invalid(0, 320)));
+        assert_eq!(BackoffConfig::new(10, 20, 0), Err(invalid(10, 0)));
+        assert_eq!(BackoffConfig::new(20, 20, 10), Err(invalid(20, 10)));
+        let mut config = DurationConfig::new(24, 10, 5, 3);
+        assert_eq!(
+            config.set_backoff(BackoffConfig {
+                base: 0,
+                multiplier: 20,
+                cap: 0,
+            }),
+            Err(invalid(0, 0))
+        );
+        assert_eq!(config.backoff, None);
+        assert_eq!(config.validate(), Ok(()));
+    }
 }
@@ -0,0 +1,220 @@
+#[cfg(feature = "runtime")]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "runtime")]
+use std::sync::Arc;
+
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+/// The caches of the engine whose memory is accounted in the memory budget.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+pub enum CacheKind {
+    /// The signed proposals of the retained past, the current and the future heights.
+    #[display(fmt = "proposals")]
+    Proposals,
+    /// The signed votes and the QCs of the retained past, the current and the future heights.
+    #[display(fmt = "votes")]
+    Votes,
+}
+
+#[cfg(feature = "runtime")]
+const CACHE_KINDS: [CacheKind; 2] = [CacheKind::Proposals, CacheKind::Votes];
+
+#[cfg(feature = "runtime")]
+impl CacheKind {
+    fn index(self) -> usize {
+        match self {
+            CacheKind::Proposals => 0,
+            CacheKind::Votes => 1,
+        }
+    }
+}
+
+/// The weights of the caches in the memory budget. Each cache is limited to its share of the
+/// budget in proportion to its weight.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CacheWeights {
+    /// The weight of the proposal cache.
+    pub proposals: u32,
+    /// The weight of the vote cache.
+    pub votes: u32,
+}
+
+impl Default for CacheWeights {
+    fn default() -> Self {
+        CacheWeights {
+            proposals: 3,
+            votes: 1,
+        }
+    }
+}
+
+impl CacheWeights {
+    /// The limit of the cache in the budget. Zero budget means no limit, which is zero too.
+    pub fn limit(&self, budget: usize, kind: CacheKind) -> usize {
+        let sum = self.proposals as u128 + self.votes as u128;
+        if budget == 0 || sum == 0 {
+            return 0;
+        }
+
+        let weight = match kind {
+            CacheKind::Proposals => self.proposals,
+            CacheKind::Votes => self.votes,
+        };
+        (budget as u128 * weight as u128 / sum) as usize
+    }
+}
+
+/// The memory usage of a cache. The usage is estimated by the encoded length of the cached
+/// messages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheUsage {
+    /// The cache.
+    pub kind: CacheKind,
+    /// The estimated bytes used by the cache.
+    pub used: usize,
+    /// The limit of the cache, zero means no limit.
+    pub limit: usize,
+    /// The number of the heights evicted from the cache since the start.
+    pub evicted: u64,
+}
+
+/// The memory report of the caches of an mlm instance.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryReport {
+    /// The memory budget of the caches, zero means no limit.
+    pub budget: usize,
+    /// The usage of each cache.
+    pub caches: Vec<CacheUsage>,
+    /// The number of the signed messages of the superseded rounds evicted or dropped since
+    /// the start, by `MlmConfig::round_retention`.
+    pub pruned: u64,
+    /// The number of the signed messages dropped over the cap of their round since the
+    /// start, by `MlmConfig::round_message_cap`.
+    pub capped: u64,
+    /// The number of the past heights evicted from the caches since the start, by
+    /// `MlmConfig::height_retention`.
+    pub expired_heights: u64,
+}
+
+impl MemoryReport {
+    /// The estimated bytes used by all of the caches.
+    pub fn used(&self) -> usize {
+        self.caches.iter().map(|cache| cache.used).sum()
+    }
+}
+
+/// The memory usage of the caches, which is updated by the state and read by the report.
+#[cfg(feature = "runtime")]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MemoryAccount(Arc<AccountInner>);
+
+#[cfg(feature = "runtime")]
+#[derive(Debug, Default)]
+struct AccountInner {
+    budget: AtomicUsize,
+    used: [AtomicUsize; 2],
+    limit: [AtomicUsize; 2],
+    evicted: [AtomicU64; 2],
+    pruned: AtomicU64,
+    capped: AtomicU64,
+    expired_heights: AtomicU64,
+}
+
+#[cfg(feature = "runtime")]
+impl MemoryAccount {
+    pub(crate) fn new() -> Self {
+        MemoryAccount::default()
+    }
+
+    pub(crate) fn set_budget(&self, budget: usize, weights: &CacheWeights) {
+        self.0.budget.store(budget, Ordering::Relaxed);
+        for kind in CACHE_KINDS.iter() {
+            self.0.limit[kind.index()]
+                .store(weights.limit(budget, *kind), Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn limit(&self, kind: CacheKind) -> usize {
+        self.0.limit[kind.index()].load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn update(&self, kind: CacheKind, used: usize, evicted: usize) {
+        self.0.used[kind.index()].store(used, Ordering::Relaxed);
+        if evicted > 0 {
+            self.0.evicted[kind.index()].fetch_add(evicted as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn add_pruned(&self, pruned: usize) {
+        if pruned > 0 {
+            self.0.pruned.fetch_add(pruned as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn add_capped(&self) {
+        self.0.capped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_expired_heights(&self, expired: usize) {
+        if expired > 0 {
+            self.0
+                .expired_heights
+                .fetch_add(expired as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn report(&self) -> MemoryReport {
+        let caches = CACHE_KINDS
+            .iter()
+            .map(|kind| CacheUsage {
+                kind: *kind,
+                used: self.0.used[kind.index()].load(Ordering::Relaxed),
+                limit: self.0.limit[kind.index()].load(Ordering::Relaxed),
+                evicted: self.0.evicted[kind.index()].load(Ordering::Relaxed),
+            })
+            .collect();
+
+        MemoryReport {
+            budget: self.0.budget.load(Ordering::Relaxed),
+            caches,
+            pruned: self.0.pruned.load(Ordering::Relaxed),
+            capped: self.0.capped.load(Ordering::Relaxed),
+            expired_heights: self.0.expired_heights.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod test {
+    use super::{CacheKind, CacheWeights, MemoryAccount};
+
+    #[test]
+    fn test_memory_account() {
+        let weights = CacheWeights::default();
+        assert_eq!(weights.limit(0, CacheKind::Votes), 0);
+        assert_eq!(weights.limit(400, CacheKind::Proposals), 300);
+        assert_eq!(weights.limit(400, CacheKind::Votes), 100);
+
+        let account = MemoryAccount::new();
+        account.set_budget(400, &weights);
+        account.update(CacheKind::Proposals, 350, 2);
+        account.update(CacheKind::Proposals, 250, 0);
+        account.update(CacheKind::Votes, 50, 0);
+        account.add_pruned(3);
+        account.add_pruned(0);
+        account.add_capped();
+        account.add_expired_heights(2);
+
+        let report = account.report();
+        assert_eq!(report.budget, 400);
+        assert_eq!(report.used(), 300);
+        assert_eq!(report.caches[0].used, 250);
+        assert_eq!(report.caches[0].limit, 300);
+        assert_eq!(report.caches[0].evicted, 2);
+        assert_eq!(report.caches[1].limit, 100);
+        assert_eq!(report.pruned, 3);
+        assert_eq!(report.capped, 1);
+        assert_eq!(report.expired_heights, 2);
+    }
+}
@@ -1,26 +1,95 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
+use bytes::Bytes;
 use creep::Context;
-use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::channel::mpsc::{unbounded, Receiver, UnboundedReceiver, UnboundedSender};
+use futures::channel::oneshot;
+use futures::stream::select_all;
+use futures::FutureExt;
 use parking_lot::RwLock;
 
-use crate::error::ConsensusError;
-use crate::state::process::State;
-use crate::types::{Address, MlmMsg, Node};
-use crate::DurationConfig;
+use crate::builder::Launch;
+use crate::error::{ConfigError, ConsensusError, ConsensusExit, ErrorKind};
+use crate::event::{EventBus, MlmError, MlmEvent};
+use crate::event_sink::ConsensusEventSink;
+use crate::memory::{MemoryAccount, MemoryReport};
+use crate::replay::TraceRecorder;
+use crate::rng::{Rng, SeededRng};
+use crate::runtime::{Runtime, SupervisedRuntime, TokioRuntime};
+use crate::state::aggregation::{AggregationAccount, AggregationStats};
+use crate::state::dedup::{VoteDedupAccount, VoteDedupStats};
+use crate::state::dump::{DumpRequest, StateDump};
+use crate::state::future::{FutureAccount, FutureBufferStats};
+use crate::state::archive::ProofArchive;
+use crate::state::heartbeat::{LivenessAccount, ValidatorLiveness};
+use crate::state::inbox::Inbound;
+use crate::state::process::{State, StateConfig};
+use crate::state::protocol::{ProtocolAccount, ProtocolStats};
+use crate::state::qc_gossip::{QcGossipAccount, QcGossipStats};
+use crate::state::rate_limit::{RateLimitAccount, RateLimitStats};
+use crate::state::snapshot::ConsensusSnapshot;
+use crate::state::window::{HeightWindowAccount, HeightWindowStats};
+use crate::telemetry::{InstanceTrace, NoopTrace, SpanAttrs, Trace};
+use crate::threshold::ThresholdPolicy;
+use crate::types::{Address, ContextExt, MlmMsg, Node, Proof, Status};
+use crate::vote_export::VoteSink;
+use crate::wal::{
+    decode_record, encode_record, open_record, seal_record, WalInfo, WalRecordKind,
+};
+use crate::wire::{RlpCodec, WireCodec};
+use crate::INIT_ROUND;
 use crate::{smr::SMR, timer::Timer};
-use crate::{Codec, Consensus, ConsensusResult, Crypto, Wal};
+use crate::{AddressScheme, AsyncCrypto, DurationConfig, EffectiveConfig, MlmConfig};
+use crate::{
+    Codec, Consensus, ConsensusResult, Crypto, ProofStore, Wal, WalCipher, WalMigration,
+};
 
 type Pile<T> = RwLock<Option<T>>;
+type MsgSender<T> = UnboundedSender<(Context, Inbound<T>)>;
+type MsgReceiver<T> = UnboundedReceiver<(Context, Inbound<T>)>;
+type TrustedSender<T> = UnboundedSender<(Context, MlmMsg<T>)>;
 
 /// An mlm consensus instance.
 pub struct Mlm<T: Codec, F: Consensus<T>, C: Crypto, W: Wal> {
-    sender: Pile<UnboundedSender<(Context, MlmMsg<T>)>>,
-    state_rx: Pile<UnboundedReceiver<(Context, MlmMsg<T>)>>,
+    sender: RwLock<Arc<MsgShards<T>>>,
+    state_rx: Pile<Vec<MsgReceiver<T>>>,
     address: Pile<Address>,
     consensus: Pile<Arc<F>>,
     crypto: Pile<Arc<C>>,
     wal: Pile<Arc<W>>,
+    config: Pile<MlmConfig>,
+    launch: Pile<Launch>,
+    proof_store: Pile<Arc<dyn ProofStore>>,
+    address_scheme: Pile<Arc<dyn AddressScheme>>,
+    async_crypto: Pile<Arc<dyn AsyncCrypto>>,
+    wal_migration: Pile<Arc<dyn WalMigration>>,
+    wal_cipher: Pile<Arc<dyn WalCipher>>,
+    vote_sink: Pile<Arc<dyn VoteSink>>,
+    event_sink: Pile<Arc<dyn ConsensusEventSink>>,
+    threshold_policy: Pile<Arc<dyn ThresholdPolicy>>,
+    trace_recorder: Pile<Arc<TraceRecorder>>,
+    trace: RwLock<Arc<dyn Trace>>,
+    runtime: RwLock<Arc<dyn Runtime>>,
+    wire_codec: RwLock<Arc<dyn WireCodec>>,
+    rng: Pile<Arc<dyn Rng>>,
+    dump_tx: UnboundedSender<DumpRequest<T>>,
+    dump_rx: Pile<UnboundedReceiver<DumpRequest<T>>>,
+    trusted_tx: TrustedSender<T>,
+    trusted_rx: Pile<UnboundedReceiver<(Context, MlmMsg<T>)>>,
+    events: EventBus,
+    memory: MemoryAccount,
+    future: FutureAccount,
+    rate_limit: RateLimitAccount,
+    window: HeightWindowAccount,
+    dedup: VoteDedupAccount,
+    qc_gossip: QcGossipAccount,
+    aggregation: AggregationAccount,
+    protocol: ProtocolAccount,
+    liveness: LivenessAccount,
+    proof_archive: ProofArchive,
 }
 
 impl<T, F, C, W> Mlm<T, F, C, W>
@@ -30,7 +99,8 @@ where
     C: Crypto + Send + Sync + 'static,
     W: Wal + 'static,
 {
-    /// Create a new mlm and return an mlm instance with an unbounded receiver.
+    /// Create a new mlm and return an mlm instance with an unbounded receiver. The
+    /// `MlmBuilder` creates one with its configuration validated.
     pub fn new(
         address: Address,
         consensus: Arc<F>,
@@ -38,115 +108,1181 @@ where
         wal: Arc<W>,
     ) -> Self {
         let (tx, rx) = unbounded();
+        let (dump_tx, dump_rx) = unbounded();
+        let (trusted_tx, trusted_rx) = unbounded();
         Mlm {
-            sender: RwLock::new(Some(tx)),
-            state_rx: RwLock::new(Some(rx)),
+            sender: RwLock::new(Arc::new(MsgShards::new(vec![tx]))),
+            state_rx: RwLock::new(Some(vec![rx])),
             address: RwLock::new(Some(address)),
             consensus: RwLock::new(Some(consensus)),
             crypto: RwLock::new(Some(crypto)),
             wal: RwLock::new(Some(wal)),
+            config: RwLock::new(Some(MlmConfig::default())),
+            launch: RwLock::new(None),
+            proof_store: RwLock::new(None),
+            address_scheme: RwLock::new(None),
+            async_crypto: RwLock::new(None),
+            wal_migration: RwLock::new(None),
+            wal_cipher: RwLock::new(None),
+            vote_sink: RwLock::new(None),
+            event_sink: RwLock::new(None),
+            threshold_policy: RwLock::new(None),
+            trace_recorder: RwLock::new(None),
+            trace: RwLock::new(Arc::new(NoopTrace)),
+            runtime: RwLock::new(Arc::new(TokioRuntime)),
+            wire_codec: RwLock::new(Arc::new(RlpCodec)),
+            rng: RwLock::new(None),
+            dump_tx,
+            dump_rx: RwLock::new(Some(dump_rx)),
+            trusted_tx,
+            trusted_rx: RwLock::new(Some(trusted_rx)),
+            events: EventBus::new(),
+            memory: MemoryAccount::new(),
+            future: FutureAccount::new(),
+            rate_limit: RateLimitAccount::new(),
+            window: HeightWindowAccount::new(),
+            dedup: VoteDedupAccount::new(),
+            qc_gossip: QcGossipAccount::new(),
+            aggregation: AggregationAccount::new(),
+            liveness: LivenessAccount::new(),
+            protocol: ProtocolAccount::new(),
+            proof_archive: ProofArchive::new(),
         }
     }
 
+    /// Set the engine configuration of the mlm instance. This should be called before
+    /// `run()`. The handlers got before it keep sending by the shards at that time.
+    pub fn set_config(&self, config: MlmConfig) {
+        self.add_sender_shards(config.sender_shards);
+        self.events.set_instance(&config.instance_id);
+        *self.config.write() = Some(config);
+    }
+
+    pub(crate) fn set_launch(&self, launch: Launch) {
+        *self.launch.write() = Some(launch);
+    }
+
+    /// Set the store that the proof of each committed height is saved to. This should be
+    /// called before `run()`.
+    pub fn set_proof_store(&self, store: Arc<dyn ProofStore>) {
+        *self.proof_store.write() = Some(store);
+    }
+
+    /// Set the address scheme of the authorities. The nodes whose address is invalid in the
+    /// scheme are excluded from the authority list. This should be called before `run()`.
+    pub fn set_address_scheme(&self, scheme: Arc<dyn AddressScheme>) {
+        *self.address_scheme.write() = Some(scheme);
+    }
+
+    /// Set the async crypto which signs by a remote service instead of `Crypto::sign()`. A
+    /// signing that exceeds the sign timeout of the config fails, and the round goes on by the
+    /// timeouts. This should be called before `run()`.
+    pub fn set_async_crypto(&self, signer: Arc<dyn AsyncCrypto>) {
+        *self.async_crypto.write() = Some(signer);
+    }
+
+    /// Set the migration of the wal records of the older format versions, which are loaded
+    /// when the instance starts. This should be called before `run()`.
+    pub fn set_wal_migration(&self, migration: Arc<dyn WalMigration>) {
+        *self.wal_migration.write() = Some(migration);
+    }
+
+    /// Set the cipher that encrypts all of the records saved to the wal. The encrypted records
+    /// can not be loaded without it, and the plain records saved before are loaded with it
+    /// only if `WalMigration::adopt_plain()` adopts them. This should be called before
+    /// `run()`.
+    pub fn set_wal_cipher(&self, cipher: Arc<dyn WalCipher>) {
+        *self.wal_cipher.write() = Some(cipher);
+    }
+
+    /// Set the sink that the raw votes of each committed height are exported to. This should be
+    /// called before `run()`.
+    pub fn set_vote_sink(&self, sink: Arc<dyn VoteSink>) {
+        *self.vote_sink.write() = Some(sink);
+    }
+
+    /// Set the sink that the typed events of the invalid messages are sent to, instead of
+    /// being logged as errors. This should be called before `run()`.
+    pub fn set_event_sink(&self, sink: Arc<dyn ConsensusEventSink>) {
+        *self.event_sink.write() = Some(sink);
+    }
+
+    /// Set the threshold policy that decides the quorum of the votes and the QCs, which is
+    /// `BftThreshold` by default. All of the nodes of a network must use the same policy. This
+    /// should be called before `run()`.
+    pub fn set_threshold_policy(&self, policy: Arc<dyn ThresholdPolicy>) {
+        *self.threshold_policy.write() = Some(policy);
+    }
+
+    /// Set the recorder that every input and output of the state machine is appended to, for
+    /// `replay::replay()` or `replay::replay_from()` to re-drive the state machine after an
+    /// incident. No trace is recorded by default. This should be called before `run()`.
+    pub fn set_trace_recorder(&self, recorder: Arc<TraceRecorder>) {
+        *self.trace_recorder.write() = Some(recorder);
+    }
+
+    /// Set the telemetry that the spans of the message handling are reported to, which is
+    /// `NoopTrace` by default. This should be called before `get_handler()` and `run()`, the
+    /// handlers got before it keep reporting to the former one.
+    pub fn set_trace(&self, trace: Arc<dyn Trace>) {
+        *self.trace.write() = trace;
+    }
+
+    /// Set the runtime that the SMR, the timer and the background tasks of the state are
+    /// spawned on, which is `TokioRuntime` by default. This should be called before `run()`.
+    pub fn set_runtime(&self, runtime: Arc<dyn Runtime>) {
+        *self.runtime.write() = runtime;
+    }
+
+    /// Set the encoding of the signature preimages and the wal payloads, which is `RlpCodec`
+    /// by default. All of the nodes of the chain must use the same one, and the wal saved by
+    /// another one is not loaded. This should be called before `run()`.
+    pub fn set_wire_codec(&self, codec: Arc<dyn WireCodec>) {
+        *self.wire_codec.write() = codec;
+    }
+
+    /// Set the source of the randomness of the engine, such as the jitter of the timeouts,
+    /// which is seeded by the address of the node by default. A simulation injects a seeded
+    /// one to reproduce an execution. This should be called before `run()`.
+    pub fn set_rng(&self, rng: Arc<dyn Rng>) {
+        *self.rng.write() = Some(rng);
+    }
+
+    /// Get the memory report of the caches of the instance. The usage is updated by the state
+    /// as the messages are handled.
+    pub fn memory_report(&self) -> MemoryReport {
+        self.memory.report()
+    }
+
+    /// Get the stats of the buffer of the messages ahead of the instance, which is enabled by
+    /// `MlmConfig::future_buffer_total`.
+    pub fn future_buffer_stats(&self) -> FutureBufferStats {
+        self.future.stats()
+    }
+
+    /// Get the counters of the messages dropped by the rate limit of the peers, which is
+    /// enabled by `MlmConfig::rate_limit`.
+    pub fn rate_limit_stats(&self) -> RateLimitStats {
+        self.rate_limit.stats()
+    }
+
+    /// Get the counters of the messages dropped by the height window, which is enabled by
+    /// `MlmConfig::height_window`.
+    pub fn height_window_stats(&self) -> HeightWindowStats {
+        self.window.stats()
+    }
+
+    /// Get the counters of the duplicate votes dropped by the seen-set, which is enabled by
+    /// `MlmConfig::vote_dedup_capacity`.
+    pub fn vote_dedup_stats(&self) -> VoteDedupStats {
+        self.dedup.stats()
+    }
+
+    /// Get the counters of the QCs learned from the network and aggregated by the instance.
+    /// The QCs are re-broadcast if `MlmConfig::gossip_qc` is enabled.
+    pub fn qc_gossip_stats(&self) -> QcGossipStats {
+        self.qc_gossip.stats()
+    }
+
+    /// Get the timing of the QCs aggregated by the instance, and how many of them had their
+    /// signatures folded as the votes arrived by `Crypto::fold_signature()`.
+    pub fn aggregation_stats(&self) -> AggregationStats {
+        self.aggregation.stats()
+    }
+
+    /// Get the liveness of the validators by the heartbeats received from them, in the order
+    /// of their addresses. The heartbeats are sent if `MlmConfig::heartbeat_interval` is set.
+    pub fn validator_liveness(&self) -> Vec<ValidatorLiveness> {
+        self.liveness.stats()
+    }
+
+    /// Get the cumulative counters of the protocol since the instance starts.
+    pub fn protocol_stats(&self) -> ProtocolStats {
+        self.protocol.stats()
+    }
+
     /// Get the mlm handler from the mlm instance.
     pub fn get_handler(&self) -> MlmHandler<T> {
-        let sender = self.sender.write();
-        assert!(sender.is_some());
-        let tx = sender.clone().unwrap();
-        MlmHandler::new(tx)
+        let shards = Arc::clone(&self.sender.read());
+        MlmHandler::with_shards(
+            shards,
+            self.instance_trace(),
+            self.dump_tx.clone(),
+            self.trusted_tx.clone(),
+            self.events.clone(),
+            self.protocol.clone(),
+            self.liveness.clone(),
+            self.qc_gossip.clone(),
+            self.proof_archive.clone(),
+        )
     }
 
-    /// Run mlm consensus process. The `interval` is the height interval as millisecond.
-    pub async fn run(
+    /// Run mlm consensus process with the interval, the authority list and the timer config
+    /// given to the `MlmBuilder` that built the instance.
+    pub async fn start(&self, init_height: u64) -> ConsensusResult<ConsensusExit> {
+        let launch = self
+            .launch
+            .write()
+            .take()
+            .ok_or(ConsensusError::InvalidConfig(ConfigError::Missing(
+                "launch",
+            )))?;
+        self.run(
+            init_height,
+            launch.interval,
+            launch.authority_list,
+            launch.timer_config,
+        )
+        .await
+    }
+
+    /// Run mlm consensus process with the height interval in milliseconds. It is the same as
+    /// `run()` with the interval as a duration.
+    pub async fn run_ms(
         &self,
         init_height: u64,
         interval: u64,
         authority_list: Vec<Node>,
         timer_config: Option<DurationConfig>,
-    ) -> ConsensusResult<()> {
+    ) -> ConsensusResult<ConsensusExit> {
+        self.run(
+            init_height,
+            Duration::from_millis(interval),
+            authority_list,
+            timer_config,
+        )
+        .await
+    }
+
+    /// Run mlm consensus process. The `interval` is the height interval. If the wal carries
+    /// the status of a height not lower than `init_height`, the interval, the authority list
+    /// and the timer config of it are used instead of the given ones, so a restarted node
+    /// resumes without a new `RichStatus`. It fails if the engine can not start, and resolves
+    /// to the reason that the engine stopped once it has started, including a panic of the
+    /// SMR, the timer or the state, or of a task that they spawned.
+    pub async fn run(
+        &self,
+        init_height: u64,
+        interval: Duration,
+        authority_list: Vec<Node>,
+        timer_config: Option<DurationConfig>,
+    ) -> ConsensusResult<ConsensusExit> {
+        let (interval, authority_list, timer_config) = match self.load_wal_status().await
+        {
+            Some(status) if status.height >= init_height => {
+                log::info!("Mlm resume with the status of height {}", status.height);
+                (
+                    status.interval.unwrap_or(interval),
+                    status.authority_list,
+                    status.timer_config.or(timer_config),
+                )
+            }
+            _ => (interval, authority_list, timer_config),
+        };
+        if let Some(config) = timer_config.as_ref() {
+            config.validate().map_err(ConsensusError::InvalidConfig)?;
+        }
+
+        let hints = self
+            .config
+            .read()
+            .as_ref()
+            .map(|config| config.thread_hints.clone())
+            .unwrap_or_default();
+        let runtime = hints.build_runtime().map_err(|e| {
+            ConsensusError::with_source(
+                ErrorKind::Internal,
+                "build the consensus runtime",
+                e,
+            )
+        })?;
+
         let (mut smr_provider, evt_state, evt_timer) = SMR::new();
-        let smr_handler = smr_provider.take_smr();
-        let timer = Timer::new(evt_timer, smr_handler.clone(), interval, timer_config);
+        if let Some(recorder) = self.trace_recorder.read().clone() {
+            smr_provider.set_recorder(recorder);
+        }
+        let smr_handler = smr_provider
+            .take_smr()
+            .map_err(|e| self.report_invariant(init_height, e))?;
+        let mut timer = Timer::new(
+            evt_timer,
+            smr_handler.clone(),
+            interval,
+            timer_config.clone(),
+        );
+        let jitter = self
+            .config
+            .read()
+            .as_ref()
+            .map(|config| config.timer_jitter)
+            .unwrap_or_default();
+        let rng = self.rng.read().clone().unwrap_or_else(|| {
+            let address = self.address.read().clone().unwrap_or_default();
+            Arc::new(SeededRng::from_bytes(address.as_bytes()))
+        });
+        timer.set_jitter(jitter, rng);
+        timer.set_instance_id(self.events.instance());
+        if let Some(address) = self.address.read().clone() {
+            timer.set_address(address);
+        }
+        // The panics of the spawned tasks stop the state with the exit.
+        let (exit_tx, exit_rx) = unbounded();
+        let rt: Arc<dyn Runtime> = Arc::new(SupervisedRuntime::new(
+            Arc::clone(&self.runtime.read()),
+            exit_tx,
+        ));
+        timer.set_runtime(Arc::clone(&rt));
         let (verify_sig_tx, verify_sig_rx) = unbounded();
 
+        let trace = self.instance_trace();
         let (rx, mut state, resp) = {
             let mut state_rx = self.state_rx.write();
-            let mut address = self.address.write();
+            let address = self.address.read();
             let mut consensus = self.consensus.write();
             let mut crypto = self.crypto.write();
             let mut wal = self.wal.write();
-            // let sender = self.sender.read();
+            let mut config = self.config.write();
+            let mut proof_store = self.proof_store.write();
 
-            let tmp_rx = state_rx.take().unwrap();
-            let (tmp_state, tmp_resp) = State::new(
+            let (tmp_rx, address, consensus, crypto, wal) = match (
+                state_rx.take(),
+                address.clone(),
+                consensus.take(),
+                crypto.take(),
+                wal.take(),
+            ) {
+                (Some(rx), Some(address), Some(consensus), Some(crypto), Some(wal)) => {
+                    (select_all(rx), address, consensus, crypto, wal)
+                }
+                _ => {
+                    let e = ConsensusError::invariant("mlm instance runs twice");
+                    return Err(self.report_invariant(init_height, e));
+                }
+            };
+            let (mut tmp_state, tmp_resp) = State::new(
                 smr_handler,
-                address.take().unwrap(),
+                address,
                 init_height,
                 interval,
                 authority_list,
                 verify_sig_tx,
-                consensus.take().unwrap(),
-                crypto.take().unwrap(),
-                wal.take().unwrap(),
+                consensus,
+                crypto,
+                wal,
+                StateConfig {
+                    config: config.take().unwrap_or_default(),
+                    duration_config: timer_config,
+                    proof_store: proof_store.take(),
+                    events: self.events.clone(),
+                },
             );
 
-            // assert!(sender.is_none());
-            assert!(address.is_none());
-            assert!(consensus.is_none());
-            assert!(crypto.is_none());
-            assert!(state_rx.is_none());
-            assert!(wal.is_none());
+            if let Some(scheme) = self.address_scheme.write().take() {
+                tmp_state.set_address_scheme(scheme);
+            }
+            if let Some(signer) = self.async_crypto.write().take() {
+                tmp_state.set_async_crypto(signer);
+            }
+            if let Some(migration) = self.wal_migration.write().take() {
+                tmp_state.set_wal_migration(migration);
+            }
+            if let Some(cipher) = self.wal_cipher.write().take() {
+                tmp_state.set_wal_cipher(cipher);
+            }
+            if let Some(sink) = self.vote_sink.write().take() {
+                tmp_state.set_vote_sink(sink);
+            }
+            if let Some(sink) = self.event_sink.write().take() {
+                tmp_state.set_event_sink(sink);
+            }
+            if let Some(policy) = self.threshold_policy.write().take() {
+                tmp_state.set_threshold_policy(policy);
+            }
+            if let Some(dump_rx) = self.dump_rx.write().take() {
+                tmp_state.set_dump_receiver(dump_rx);
+            }
+            if let Some(trusted_rx) = self.trusted_rx.write().take() {
+                tmp_state.set_trusted_receiver(trusted_rx);
+            }
+            tmp_state.set_exit_receiver(exit_rx);
+            tmp_state.set_trace(trace);
+            tmp_state.set_runtime(Arc::clone(&rt));
+            tmp_state.set_wire_codec(Arc::clone(&self.wire_codec.read()));
+            tmp_state.set_memory_account(self.memory.clone());
+            tmp_state.set_future_account(self.future.clone());
+            tmp_state.set_rate_limit_account(self.rate_limit.clone());
+            tmp_state.set_window_account(self.window.clone());
+            tmp_state.set_dedup_account(self.dedup.clone());
+            tmp_state.set_qc_gossip_account(self.qc_gossip.clone());
+            tmp_state.set_aggregation_account(self.aggregation.clone());
+            tmp_state.set_protocol_account(self.protocol.clone());
+            tmp_state.set_liveness_account(self.liveness.clone());
+            tmp_state.set_proof_archive(self.proof_archive.clone());
+            tmp_state.set_step_deadline(timer.step_deadline());
 
             (tmp_rx, tmp_state, tmp_resp)
         };
 
-        log::info!("Mlm start running");
+        log::info!("Mlm start running, instance {:?}", self.events.instance());
+
+        let runtime = match runtime {
+            Some(runtime) => runtime,
+            None => {
+                // Run SMR.
+                smr_provider.run(rt.as_ref());
+
+                // Run timer.
+                timer.run();
+
+                // Run state.
+                let state = state.run(rx, evt_state, resp, verify_sig_rx);
+                let exit = AssertUnwindSafe(state).catch_unwind().await;
+                return Ok(exit.unwrap_or_else(ConsensusExit::panicked));
+            }
+        };
+
+        // The SMR, the timer and the state are spawned on the dedicated runtime, and so are
+        // the verification tasks spawned by the state.
+        {
+            let _guard = runtime.enter();
+            smr_provider.run(rt.as_ref());
+            timer.run();
+        }
+        let res = runtime
+            .spawn(async move { state.run(rx, evt_state, resp, verify_sig_rx).await })
+            .await;
+        runtime.shutdown_background();
+        match res {
+            Ok(exit) => Ok(exit),
+            Err(e) if e.is_panic() => Ok(ConsensusExit::panicked(e.into_panic())),
+            Err(e) => Err(ConsensusError::with_source(
+                ErrorKind::Internal,
+                "state task",
+                e,
+            )),
+        }
+    }
 
-        // Run SMR.
-        smr_provider.run();
+    /// Run mlm consensus process from a snapshot exported by `MlmHandler::export_snapshot()`
+    /// of another instance. The wal info and the last signed record of the snapshot are saved
+    /// to the wal, and the QCs of it are queued before the instance runs, so the instance
+    /// resumes the height of the snapshot with its lock, as a restarted node does. The
+    /// interval, the authority list and the timer config are those of the status of the
+    /// snapshot.
+    pub async fn run_from_snapshot(
+        &self,
+        snapshot: ConsensusSnapshot<T>,
+    ) -> ConsensusResult<ConsensusExit> {
+        let height = snapshot.height();
+        let status = snapshot.status().cloned().ok_or_else(|| {
+            ConsensusError::LoadWalErr(format!(
+                "no status in the snapshot of height {}",
+                height
+            ))
+        })?;
+        let interval = status.interval.ok_or_else(|| {
+            ConsensusError::LoadWalErr(format!(
+                "no interval in the snapshot of height {}",
+                height
+            ))
+        })?;
+        if let Some(lock) = snapshot.wal_info.lock.as_ref() {
+            lock.check(height, snapshot.round(), &snapshot.wal_info.step)
+                .map_err(ConsensusError::LoadWalErr)?;
+        }
+
+        let wal = match self.wal.read().clone() {
+            Some(wal) => wal,
+            None => {
+                let e = ConsensusError::invariant("mlm instance runs twice");
+                return Err(self.report_invariant(height, e));
+            }
+        };
+        let codec = Arc::clone(&self.wire_codec.read());
+        let cipher = self.wal_cipher.read().clone();
+        let run_id = self
+            .config
+            .read()
+            .as_ref()
+            .map(|config| config.run_id.clone())
+            .unwrap_or_default();
+        let save_err = |step: &str| ConsensusError::SaveWalErr {
+            height,
+            round: snapshot.round(),
+            step: step.to_string(),
+        };
+        let info_step = snapshot.wal_info.step.to_string();
+        let record = encode_record(&snapshot.wal_info, codec.as_ref(), &run_id)
+            .map_err(|_| save_err(&info_step))?;
+        let record = seal_record(WalRecordKind::WalInfo, record, cipher.as_deref())
+            .map_err(|_| save_err(&info_step))?;
+        wal.save(record).await.map_err(|_| save_err(&info_step))?;
+        if let Some(last_signed) = snapshot.last_signed.as_ref() {
+            let step = last_signed.step.to_string();
+            let record = encode_record(last_signed, codec.as_ref(), &run_id)
+                .map_err(|_| save_err(&step))?;
+            let record =
+                seal_record(WalRecordKind::LastSigned, record, cipher.as_deref())
+                    .map_err(|_| save_err(&step))?;
+            wal.save_last_signed(record)
+                .await
+                .map_err(|_| save_err(&step))?;
+        }
+
+        log::info!(
+            "Mlm run from the snapshot of height {}, round {}",
+            height,
+            snapshot.round()
+        );
+        let handler = self.get_handler();
+        for qc in snapshot.qcs {
+            handler.send_msg(Context::new(), MlmMsg::AggregatedVote(qc))?;
+        }
+        self.run(height, interval, status.authority_list, status.timer_config)
+            .await
+    }
+
+    /// The telemetry labeling the spans with the instance id and the address of the node.
+    fn instance_trace(&self) -> Arc<dyn Trace> {
+        InstanceTrace::wrap(
+            Arc::clone(&self.trace.read()),
+            &self.events.instance(),
+            self.address.read().clone(),
+        )
+    }
+
+    /// Report an error of the violated invariant to the adapter and the error channel. The
+    /// adapter is not reported if it is taken by the running instance, or its lock is held by
+    /// the caller.
+    fn report_invariant(&self, height: u64, err: ConsensusError) -> ConsensusError {
+        log::error!("Mlm: {}", err);
+        self.events.publish_error(height, INIT_ROUND, err.clone());
+        if let Some(consensus) = self.consensus.try_read().and_then(|c| c.clone()) {
+            consensus.report_error(Context::new(), err.clone());
+        }
+        err
+    }
+
+    /// Add the message channel shards up to the number. The shards are never removed, and
+    /// none is added once the instance runs.
+    fn add_sender_shards(&self, shards: usize) {
+        let mut state_rx = self.state_rx.write();
+        let mut sender = self.sender.write();
+        let receivers = match state_rx.as_mut() {
+            Some(receivers) => receivers,
+            None => return,
+        };
+
+        let mut senders = sender.senders.clone();
+        while senders.len() < shards {
+            let (tx, rx) = unbounded();
+            senders.push(tx);
+            receivers.push(rx);
+        }
+        *sender = Arc::new(MsgShards {
+            senders,
+            next: AtomicUsize::new(0),
+        });
+    }
+
+    /// Load the status saved in the wal, or the one of the checkpoint if it is newer or the
+    /// wal info is lost. An error is left to the state, which reports it when it starts with
+    /// the wal.
+    async fn load_wal_status(&self) -> Option<Status> {
+        let wal = self.wal.read().clone()?;
+        let migration = self.wal_migration.read().clone();
+        let cipher = self.wal_cipher.read().clone();
+        let codec = Arc::clone(&self.wire_codec.read());
+        let run_id = self
+            .config
+            .read()
+            .as_ref()
+            .map(|config| config.run_id.clone())
+            .unwrap_or_default();
+        let open = |kind: WalRecordKind, record: Option<Bytes>| {
+            open_record(kind, record?, cipher.as_deref(), migration.as_deref()).ok()
+        };
+        let info = open(WalRecordKind::WalInfo, wal.load().await.ok().flatten())
+            .and_then(|record| {
+                decode_record::<WalInfo<T>>(
+                    WalRecordKind::WalInfo,
+                    &record,
+                    codec.as_ref(),
+                    migration.as_deref(),
+                    Some(&run_id),
+                )
+                .ok()
+            });
+        let checkpoint = open(
+            WalRecordKind::Checkpoint,
+            wal.load_checkpoint().await.ok().flatten(),
+        )
+        .and_then(|record| {
+            decode_record::<ConsensusSnapshot<T>>(
+                WalRecordKind::Checkpoint,
+                &record,
+                codec.as_ref(),
+                migration.as_deref(),
+                Some(&run_id),
+            )
+            .ok()
+        });
+        match (info, checkpoint) {
+            (Some(info), Some(checkpoint)) if checkpoint.height() > info.height => {
+                checkpoint.wal_info.status
+            }
+            (Some(info), _) => info.status,
+            (None, checkpoint) => checkpoint?.wal_info.status,
+        }
+    }
+}
 
-        // Run timer.
-        timer.run();
+/// The message channel shards of an instance, which the state receives from together. A
+/// handler sends by one of the shards, which are picked in turn.
+#[derive(Debug)]
+struct MsgShards<T: Codec> {
+    senders: Vec<MsgSender<T>>,
+    next: AtomicUsize,
+}
 
-        // Run state.
-        state.run(rx, evt_state, resp, verify_sig_rx).await;
+impl<T: Codec> MsgShards<T> {
+    fn new(senders: Vec<MsgSender<T>>) -> Self {
+        MsgShards {
+            senders,
+            next: AtomicUsize::new(0),
+        }
+    }
 
-        Ok(())
+    fn pick(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % self.senders.len()
     }
 }
 
-/// An mlm handler to send messages to an mlm instance.
-#[derive(Clone, Debug)]
-pub struct MlmHandler<T: Codec>(UnboundedSender<(Context, MlmMsg<T>)>);
+/// The send metrics of a handler clone.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SendMetrics {
+    /// The id of the instance, which is empty for a single instance.
+    pub instance: String,
+    /// The message channel shard that the clone sends by.
+    pub shard: usize,
+    /// The messages sent.
+    pub sent: u64,
+    /// The messages failed to send since the instance is stopped.
+    pub failed: u64,
+}
+
+/// An mlm handler to send messages to an mlm instance. A handler is `Send` and `Sync`, it can
+/// be shared or cloned across threads, and the messages sent by one clone are received in
+/// order. Each clone sends by the next message channel shard of the instance, which spreads
+/// the contention of the clones over `MlmConfig::sender_shards` channels, and has its own
+/// send metrics.
+#[derive(Debug)]
+pub struct MlmHandler<T: Codec> {
+    shards: Arc<MsgShards<T>>,
+    shard: usize,
+    sent: AtomicU64,
+    failed: AtomicU64,
+    trace: Arc<dyn Trace>,
+    dump_tx: UnboundedSender<DumpRequest<T>>,
+    trusted_tx: TrustedSender<T>,
+    events: EventBus,
+    protocol: ProtocolAccount,
+    liveness: LivenessAccount,
+    qc_gossip: QcGossipAccount,
+    proof_archive: ProofArchive,
+}
+
+impl<T: Codec> Clone for MlmHandler<T> {
+    fn clone(&self) -> Self {
+        MlmHandler::with_shards(
+            Arc::clone(&self.shards),
+            Arc::clone(&self.trace),
+            self.dump_tx.clone(),
+            self.trusted_tx.clone(),
+            self.events.clone(),
+            self.protocol.clone(),
+            self.liveness.clone(),
+            self.qc_gossip.clone(),
+            self.proof_archive.clone(),
+        )
+    }
+}
 
 impl<T: Codec> MlmHandler<T> {
-    fn new(tx: UnboundedSender<(Context, MlmMsg<T>)>) -> Self {
-        MlmHandler(tx)
+    #[cfg(test)]
+    pub(crate) fn new(tx: MsgSender<T>, events: EventBus) -> Self {
+        MlmHandler::with_shards(
+            Arc::new(MsgShards::new(vec![tx])),
+            Arc::new(NoopTrace),
+            unbounded().0,
+            unbounded().0,
+            events,
+            ProtocolAccount::new(),
+            LivenessAccount::new(),
+            QcGossipAccount::new(),
+            ProofArchive::new(),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn with_shards(
+        shards: Arc<MsgShards<T>>,
+        trace: Arc<dyn Trace>,
+        dump_tx: UnboundedSender<DumpRequest<T>>,
+        trusted_tx: TrustedSender<T>,
+        events: EventBus,
+        protocol: ProtocolAccount,
+        liveness: LivenessAccount,
+        qc_gossip: QcGossipAccount,
+        proof_archive: ProofArchive,
+    ) -> Self {
+        MlmHandler {
+            shard: shards.pick(),
+            shards,
+            sent: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            trace,
+            dump_tx,
+            trusted_tx,
+            events,
+            protocol,
+            liveness,
+            qc_gossip,
+            proof_archive,
+        }
+    }
+
+    /// Get the send metrics of this clone.
+    pub fn send_metrics(&self) -> SendMetrics {
+        SendMetrics {
+            instance: self.events.instance(),
+            shard: self.shard,
+            sent: self.sent.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get the cumulative counters of the protocol since the instance starts, which are
+    /// serializable to JSON for the explorers.
+    pub fn protocol_stats(&self) -> ProtocolStats {
+        self.protocol.stats()
+    }
+
+    /// Get the liveness of the validators by the heartbeats received from them, in the order
+    /// of their addresses.
+    pub fn validator_liveness(&self) -> Vec<ValidatorLiveness> {
+        self.liveness.stats()
+    }
+
+    /// Get the counters of the QCs learned from the network and aggregated by the instance.
+    pub fn qc_gossip_stats(&self) -> QcGossipStats {
+        self.qc_gossip.stats()
+    }
+
+    /// Get the commit proof of the height from the archive of the recent commit proofs, so
+    /// that the application can serve it to a syncing peer without its own storage. Return
+    /// `None` if the height is not committed yet, is out of the archive or the archive is
+    /// disabled by `MlmConfig::proof_archive_size`.
+    pub fn get_proof(&self, height: u64) -> Option<Proof> {
+        self.proof_archive.get(height)
+    }
+
+    /// Subscribe the consensus events of the instance by a bounded channel of the capacity.
+    /// Every subscriber receives the events published after it subscribes, and is removed
+    /// once the receiver is dropped. The state never waits for a subscriber, an event is
+    /// dropped when the subscriber is full and the count of the dropped events is carried by
+    /// the next delivered one.
+    pub fn subscribe(&self, capacity: usize) -> Receiver<MlmEvent> {
+        self.events.subscribe(capacity)
+    }
+
+    /// Subscribe the errors of the instance by a bounded channel of the capacity, which the
+    /// adapter can consume at its own pace. The state never waits for a subscriber, an error
+    /// is dropped when the subscriber is full and the count of the dropped errors is carried by
+    /// the next delivered one.
+    pub fn subscribe_errors(&self, capacity: usize) -> Receiver<MlmError> {
+        self.events.subscribe_errors(capacity)
     }
 
     /// Send mlm message to the instance. Return `Err()` when the message channel is closed.
     pub fn send_msg(&self, ctx: Context, msg: MlmMsg<T>) -> ConsensusResult<()> {
-        let ctx = match muta_apm::MUTA_TRACER.span(
-            "mlm.send_msg_to_inner",
-            vec![muta_apm::rustracing::tag::Tag::new("kind", "mlm")],
-        ) {
-            Some(mut span) => {
-                span.log(|log| {
-                    log.time(std::time::SystemTime::now());
-                });
-                ctx.with_value("parent_span_ctx", span.context().cloned())
-            }
-            None => ctx,
+        let ctx = self
+            .trace
+            .span(ctx, "mlm.send_msg_to_inner", SpanAttrs::default());
+
+        let tx = &self.shards.senders[self.shard];
+        let res = if tx.is_closed() {
+            Err(ConsensusError::ChannelErr(
+                "[MlmHandler]: channel closed".to_string(),
+            ))
+        } else {
+            tx.unbounded_send((ctx, Inbound::Msg(msg))).map_err(|e| {
+                ConsensusError::ChannelErr(format!("Send message error {:?}", e))
+            })
         };
 
-        if self.0.is_closed() {
+        let counter = if res.is_ok() {
+            &self.sent
+        } else {
+            &self.failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        res
+    }
+
+    /// Send mlm message received from the peer to the instance. The peer, such as the
+    /// transport address or the connection id of the sender, is carried by the context of the
+    /// message, and is reported with the invalid message and the errors it causes, so that the
+    /// network layer can ban the misbehaving peers. Return `Err()` when the message channel is
+    /// closed.
+    pub fn send_msg_from(
+        &self,
+        ctx: Context,
+        peer: String,
+        msg: MlmMsg<T>,
+    ) -> ConsensusResult<()> {
+        self.send_msg(ctx.with_peer(peer), msg)
+    }
+
+    /// Send a batch of mlm messages to the instance, such as the votes received in one frame
+    /// of the transport, under a single span. The batch is sent as one item of the message
+    /// channel, so it is received in order without the other messages of the shard in between,
+    /// and either all or none of it is sent. Return `Err()` when the message channel is closed.
+    pub fn send_msg_many(
+        &self,
+        ctx: Context,
+        msgs: Vec<MlmMsg<T>>,
+    ) -> ConsensusResult<()> {
+        if msgs.is_empty() {
+            return Ok(());
+        }
+
+        let attrs = SpanAttrs::default().with_kind(format!("batch of {}", msgs.len()));
+        let ctx = self.trace.span(ctx, "mlm.send_msg_many_to_inner", attrs);
+
+        let tx = &self.shards.senders[self.shard];
+        let count = msgs.len() as u64;
+        let res = if tx.is_closed() {
             Err(ConsensusError::ChannelErr(
                 "[MlmHandler]: channel closed".to_string(),
             ))
         } else {
-            self.0.unbounded_send((ctx, msg)).map_err(|e| {
-                ConsensusError::Other(format!("Send message error {:?}", e))
+            tx.unbounded_send((ctx, Inbound::Batch(msgs))).map_err(|e| {
+                ConsensusError::ChannelErr(format!("Send message error {:?}", e))
             })
+        };
+
+        let counter = if res.is_ok() {
+            &self.sent
+        } else {
+            &self.failed
+        };
+        counter.fetch_add(count, Ordering::Relaxed);
+        res
+    }
+
+    /// Send a message generated by the local node, such as its own vote or its own proposal, to
+    /// the instance. The proposal and the votes that the instance has signed in the current
+    /// round skip the signature verification, and the others are verified as the ones sent by
+    /// `send_msg()`. Return `Err()` when the instance stops.
+    pub fn send_trusted_msg(&self, ctx: Context, msg: MlmMsg<T>) -> ConsensusResult<()> {
+        let ctx =
+            self.trace
+                .span(ctx, "mlm.send_trusted_msg_to_inner", SpanAttrs::default());
+
+        let res = self.trusted_tx.unbounded_send((ctx, msg)).map_err(|_| {
+            ConsensusError::ChannelErr("[MlmHandler]: channel closed".to_string())
+        });
+        let counter = if res.is_ok() {
+            &self.sent
+        } else {
+            &self.failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        res
+    }
+
+    /// Get a snapshot of the state of the instance for the incident debugging, which is
+    /// serializable to JSON for an admin endpoint. The state answers between the messages it
+    /// handles, so a dump requested before the instance runs waits until it starts. An error
+    /// is returned once the instance stops.
+    pub async fn dump_state(&self) -> ConsensusResult<StateDump> {
+        let (tx, rx) = oneshot::channel();
+        self.dump_tx
+            .unbounded_send(DumpRequest::State(tx))
+            .map_err(|_| {
+                ConsensusError::ChannelErr("[MlmHandler]: channel closed".to_string())
+            })?;
+        rx.await.map_err(|_| {
+            ConsensusError::ChannelErr("[MlmHandler]: state not running".to_string())
+        })
+    }
+
+    /// Get the configuration that the instance runs with, with the timeouts resolved from
+    /// the current interval and timeout ratios and the defaults of the zero options. The state
+    /// answers between the messages it handles, as `dump_state()`.
+    pub async fn effective_config(&self) -> ConsensusResult<EffectiveConfig> {
+        let (tx, rx) = oneshot::channel();
+        self.dump_tx
+            .unbounded_send(DumpRequest::Config(tx))
+            .map_err(|_| {
+                ConsensusError::ChannelErr("[MlmHandler]: channel closed".to_string())
+            })?;
+        rx.await.map_err(|_| {
+            ConsensusError::ChannelErr("[MlmHandler]: state not running".to_string())
+        })
+    }
+
+    /// Export a snapshot of the consensus state of the instance, which another instance runs
+    /// from by `Mlm::run_from_snapshot()`. It is taken from the wal of the current height, so
+    /// an error is returned if the node has no consensus power. The state answers between the
+    /// messages it handles, as `dump_state()`.
+    pub async fn export_snapshot(&self) -> ConsensusResult<ConsensusSnapshot<T>> {
+        let (tx, rx) = oneshot::channel();
+        self.dump_tx
+            .unbounded_send(DumpRequest::Snapshot(tx))
+            .map_err(|_| {
+                ConsensusError::ChannelErr("[MlmHandler]: channel closed".to_string())
+            })?;
+        rx.await.map_err(|_| {
+            ConsensusError::ChannelErr("[MlmHandler]: state not running".to_string())
+        })?
+    }
+
+    /// Update the timeout configuration of the instance in the middle of a height. The timers
+    /// set after the update use the new configuration, until it is replaced by the next update
+    /// or a status with a timer config.
+    pub fn update_timer_config(&self, config: DurationConfig) -> ConsensusResult<()> {
+        config.validate().map_err(ConsensusError::InvalidConfig)?;
+        self.send_msg(Context::new(), MlmMsg::UpdateConfig(config))
+    }
+
+    /// Force the current round of the instance to time out, so the operators can unstick a
+    /// round whose proposer is known to be dead without waiting for the timeouts. The node goes
+    /// to the brake step at once and broadcasts its choke, and the round changes once the
+    /// chokes of the round are above the threshold. The view change is reported with the
+    /// reason `ViewChangeReason::OperatorTriggered`.
+    pub fn force_view_change(&self, reason: String) -> ConsensusResult<()> {
+        self.send_msg(Context::new(), MlmMsg::ForceViewChange(reason))
+    }
+
+    /// Resend the latest signed proposal and signed votes of the node in the current round,
+    /// so the application can nudge the instance after a network blip that may have dropped
+    /// the only copy of them, without waiting for the timeouts. The leader rebroadcasts the
+    /// QCs of the round it has aggregated instead of its own votes.
+    pub fn rebroadcast_current(&self) -> ConsensusResult<()> {
+        self.send_msg(Context::new(), MlmMsg::RebroadcastCurrent)
+    }
+
+    /// Stop the instance, which is the same as sending `MlmMsg::Stop`. The state finishes the
+    /// message it is handling, and stops without handling the pending ones, even in the middle
+    /// of a round. It closes the message channels, flushes the wal by `Wal::flush()` and
+    /// publishes `MlmEventKind::Exit` in order, then `Mlm::run()` returns
+    /// `ConsensusExit::Stopped`. The messages sent after the exit fail as the channels are
+    /// closed.
+    pub fn stop(&self) -> ConsensusResult<()> {
+        self.send_msg(Context::new(), MlmMsg::Stop)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use futures::channel::mpsc::unbounded;
+    use futures::stream::{select_all, StreamExt};
+
+    use super::{MlmHandler, MsgShards};
+    use crate::event::EventBus;
+    use crate::state::archive::ProofArchive;
+    use crate::state::heartbeat::LivenessAccount;
+    use crate::state::inbox::Inbound;
+    use crate::state::protocol::ProtocolAccount;
+    use crate::state::qc_gossip::QcGossipAccount;
+    use crate::telemetry::NoopTrace;
+    use crate::types::{Address, Hash, MlmMsg, SignedVote, Vote, VoteType};
+    use crate::Context;
+
+    const CLONE_KEY: &str = "clone";
+
+    fn gen_vote_msg(height: u64) -> MlmMsg<Bytes> {
+        MlmMsg::SignedVote(SignedVote {
+            signature: Bytes::from(vec![1u8; 8]),
+            vote: Vote {
+                height,
+                round: 0,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from(vec![2u8; 32]),
+            },
+            voter: Address::from_slice(&[3u8; 20]).unwrap(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        })
+    }
+
+    fn assert_send_sync<S: Send + Sync>() {}
+
+    #[test]
+    fn test_handler_send_sync() {
+        assert_send_sync::<MlmHandler<Bytes>>();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_handlers() {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..4).map(|_| unbounded()).unzip();
+        let handler = MlmHandler::with_shards(
+            Arc::new(MsgShards::new(senders)),
+            Arc::new(NoopTrace),
+            unbounded().0,
+            unbounded().0,
+            EventBus::new(),
+            ProtocolAccount::new(),
+            LivenessAccount::new(),
+            QcGossipAccount::new(),
+            ProofArchive::new(),
+        );
+
+        let threads = (0..8usize)
+            .map(|index| {
+                let handler = handler.clone();
+                std::thread::spawn(move || {
+                    for height in 0..500 {
+                        let ctx = Context::new().with_value(CLONE_KEY, index);
+                        handler.send_msg(ctx, gen_vote_msg(height)).unwrap();
+                    }
+                    handler.send_metrics()
+                })
+            })
+            .collect::<Vec<_>>();
+        let metrics = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+
+        // The clones are spread over the shards, and count their own messages.
+        for (index, metric) in metrics.iter().enumerate() {
+            assert_eq!(metric.shard, (index + 1) % 4);
+            assert_eq!(metric.sent, 500);
+            assert_eq!(metric.failed, 0);
+        }
+        assert_eq!(handler.send_metrics().sent, 0);
+
+        // The messages of each clone are received in order.
+        drop(handler);
+        let mut last = HashMap::new();
+        let mut rx = select_all(receivers);
+        while let Some((ctx, Inbound::Msg(msg))) = rx.next().await {
+            let index = *ctx.get::<usize>(CLONE_KEY).unwrap();
+            let height = msg.get_height();
+            if let Some(prev) = last.insert(index, height) {
+                assert_eq!(prev + 1, height);
+            }
+        }
+        assert_eq!(last.len(), 8);
+        assert!(last.values().all(|height| *height == 499));
+    }
+
+    #[test]
+    fn test_send_to_stopped() {
+        let (tx, rx) = unbounded();
+        let handler = MlmHandler::<Bytes>::new(tx, EventBus::new());
+        handler.send_msg(Context::new(), gen_vote_msg(1)).unwrap();
+        drop(rx);
+
+        assert!(handler.send_msg(Context::new(), gen_vote_msg(2)).is_err());
+        let metrics = handler.send_metrics();
+        assert_eq!((metrics.sent, metrics.failed), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_send_msg_many() {
+        let (tx, rx) = unbounded();
+        let handler = MlmHandler::<Bytes>::new(tx, EventBus::new());
+        let batches = (0..4usize)
+            .map(|index| {
+                let handler = handler.clone();
+                std::thread::spawn(move || {
+                    for batch in 0..50u64 {
+                        let ctx = Context::new().with_value(CLONE_KEY, index);
+                        let msgs =
+                            (0..10).map(|i| gen_vote_msg(batch * 10 + i)).collect();
+                        handler.send_msg_many(ctx, msgs).unwrap();
+                    }
+                    handler.send_metrics()
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in batches {
+            assert_eq!(thread.join().unwrap().sent, 500);
         }
+
+        // Each batch is received as one item, and the batches of each clone are in order.
+        let received = rx.take(200).collect::<Vec<_>>().await;
+        let mut last = HashMap::new();
+        for (ctx, item) in received {
+            let msgs = match item {
+                Inbound::Batch(msgs) => msgs,
+                Inbound::Msg(msg) => panic!("a message {} out of the batches", msg),
+            };
+            let index = *ctx.get::<usize>(CLONE_KEY).unwrap();
+            let first = msgs[0].get_height();
+            if let Some(prev) = last.insert(index, first) {
+                assert_eq!(prev + 10, first);
+            }
+            for (i, msg) in msgs.iter().enumerate() {
+                assert_eq!(msg.get_height(), first + i as u64);
+            }
+            assert_eq!(msgs.len(), 10);
+        }
+        assert_eq!(last.len(), 4);
+
+        assert!(handler.send_msg_many(Context::new(), vec![]).is_ok());
+        assert_eq!(handler.send_metrics().sent, 0);
+    }
+
+    #[test]
+    fn test_send_many_to_stopped() {
+        let (tx, rx) = unbounded();
+        let handler = MlmHandler::<Bytes>::new(tx, EventBus::new());
+        drop(rx);
+
+        let msgs = vec![gen_vote_msg(1), gen_vote_msg(2)];
+        assert!(handler.send_msg_many(Context::new(), msgs).is_err());
+        let metrics = handler.send_metrics();
+        assert_eq!((metrics.sent, metrics.failed), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_send_trusted_msg() {
+        let (tx, mut rx) = unbounded();
+        let (trusted_tx, mut trusted_rx) = unbounded();
+        let handler = MlmHandler::<Bytes>::with_shards(
+            Arc::new(MsgShards::new(vec![tx])),
+            Arc::new(NoopTrace),
+            unbounded().0,
+            trusted_tx,
+            EventBus::new(),
+            ProtocolAccount::new(),
+            LivenessAccount::new(),
+            QcGossipAccount::new(),
+            ProofArchive::new(),
+        );
+
+        // The trusted messages skip the message channels of the network.
+        handler
+            .send_trusted_msg(Context::new(), gen_vote_msg(1))
+            .unwrap();
+        assert_eq!(trusted_rx.next().await.unwrap().1, gen_vote_msg(1));
+
+        drop(trusted_rx);
+        assert!(handler
+            .send_trusted_msg(Context::new(), gen_vote_msg(2))
+            .is_err());
+        let metrics = handler.send_metrics();
+        assert_eq!((metrics.sent, metrics.failed), (1, 1));
+        drop(handler);
+        assert!(rx.next().await.is_none());
     }
 }
@@ -0,0 +1,297 @@
+use std::error::Error;
+use std::fmt::Debug;
+
+use bytes::Bytes;
+use hummer::coding::hex_encode;
+
+use crate::error::ConsensusError;
+use crate::types::{
+    AggregatedSignature, Hash, Signature, Vote, VoteExtension, VoteType,
+};
+use crate::utils::auth_manage::AuthorityManage;
+use crate::wire::WireCodec;
+use crate::{ConsensusResult, Crypto};
+
+/// The aggregated signature of a QC of a signature scheme, which is `Crypto::QcSignature`.
+/// The engine carries it as the `AggregatedSignature` of the QC on the wire, so the state
+/// machine is the same for the schemes, and it decides the voters of the QC only by the
+/// address bitmap of the signature if it has one.
+pub trait QcSignature: Clone + Debug + Send + Sync {
+    /// Build the signature of a QC from the aggregated signature of the voters by
+    /// `Crypto::aggregate_signatures()` and the bitmap of the voters in the authority list.
+    fn new(signature: Signature, address_bitmap: Bytes) -> Self;
+
+    /// Decode the signature of a QC from the wire, which fails if it is not of the scheme.
+    fn from_wire(signature: &AggregatedSignature) -> ConsensusResult<Self>;
+
+    /// Encode the signature of a QC to the wire.
+    fn to_wire(&self) -> AggregatedSignature;
+
+    /// The aggregated signature or the threshold signature.
+    fn signature(&self) -> &Signature;
+
+    /// The bitmap of the voters in the authority list, which is checked against the threshold
+    /// before the signature is verified with the voters by
+    /// `Crypto::verify_aggregated_signature()`. It is `None` if the signature names no voters,
+    /// whose quorum is proven by the signature itself, then the signature is verified by
+    /// `Crypto::verify_threshold_signature()`.
+    fn address_bitmap(&self) -> Option<&Bytes>;
+}
+
+/// The aggregated signature with the bitmap of the voters, such as a BLS aggregate or a list
+/// of the signatures of the voters concatenated by `Crypto::aggregate_signatures()`.
+impl QcSignature for AggregatedSignature {
+    fn new(signature: Signature, address_bitmap: Bytes) -> Self {
+        AggregatedSignature {
+            signature,
+            address_bitmap,
+        }
+    }
+
+    fn from_wire(signature: &AggregatedSignature) -> ConsensusResult<Self> {
+        Ok(signature.clone())
+    }
+
+    fn to_wire(&self) -> AggregatedSignature {
+        self.clone()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn address_bitmap(&self) -> Option<&Bytes> {
+        Some(&self.address_bitmap)
+    }
+}
+
+/// A threshold signature, which is one signature of the group key combined from the partial
+/// signatures of a quorum of the shares and names no voters. It is carried with an empty
+/// address bitmap, so a QC and a proof are of a constant size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThresholdSignature(pub Signature);
+
+impl QcSignature for ThresholdSignature {
+    fn new(signature: Signature, _address_bitmap: Bytes) -> Self {
+        ThresholdSignature(signature)
+    }
+
+    fn from_wire(signature: &AggregatedSignature) -> ConsensusResult<Self> {
+        if !signature.address_bitmap.is_empty() {
+            return Err(ConsensusError::AggregatedSignatureErr(
+                "threshold signature with an address bitmap".to_string(),
+            ));
+        }
+        Ok(ThresholdSignature(signature.signature.clone()))
+    }
+
+    fn to_wire(&self) -> AggregatedSignature {
+        AggregatedSignature {
+            signature: self.0.clone(),
+            address_bitmap: Bytes::new(),
+        }
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.0
+    }
+
+    fn address_bitmap(&self) -> Option<&Bytes> {
+        None
+    }
+}
+
+/// Verify the signature of a QC of the scheme of the crypto, which must be above the
+/// threshold of the authorities. An aggregated signature is verified with the voters of the
+/// bitmap, and a threshold signature against the group public key of the authority list.
+pub(crate) fn verify_qc_signature<C: Crypto + ?Sized>(
+    crypto: &C,
+    signature: &AggregatedSignature,
+    hash: Hash,
+    authority: &AuthorityManage,
+) -> Result<(), Box<dyn Error + Send>> {
+    let signature = C::QcSignature::from_wire(signature).map_err(boxed)?;
+    match signature.address_bitmap() {
+        Some(bitmap) => crypto.verify_aggregated_signature(
+            signature.signature().clone(),
+            hash,
+            authority.get_voters(bitmap).map_err(boxed)?,
+        ),
+        None => crypto.verify_threshold_signature(
+            signature.signature().clone(),
+            hash,
+            &authority.get_authority_list(),
+        ),
+    }
+}
+
+/// Verify the vote extensions carried by a QC or a proof of the vote. Only a precommit of a
+/// block has extensions, which are sorted by their unique voters. Each voter is a voter of
+/// the aggregated signature, or an authority if the signature names no voters, and each
+/// extension is signed by its voter over the vote and the payload.
+pub(crate) fn verify_vote_extensions<C: Crypto + ?Sized>(
+    crypto: &C,
+    codec: &dyn WireCodec,
+    vote: &Vote,
+    signature: &AggregatedSignature,
+    extensions: &[VoteExtension],
+    authority: &AuthorityManage,
+) -> Result<(), Box<dyn Error + Send>> {
+    if extensions.is_empty() {
+        return Ok(());
+    }
+    if vote.vote_type != VoteType::Precommit || vote.block_hash.is_empty() {
+        return Err(boxed(ConsensusError::AggregatedSignatureErr(format!(
+            "vote extensions of a {:?} vote",
+            vote.vote_type
+        ))));
+    }
+    if extensions
+        .windows(2)
+        .any(|pair| pair[0].voter >= pair[1].voter)
+    {
+        return Err(boxed(ConsensusError::AggregatedSignatureErr(
+            "vote extensions are not sorted by the unique voters".to_string(),
+        )));
+    }
+
+    let signature = C::QcSignature::from_wire(signature).map_err(boxed)?;
+    let voters = signature
+        .address_bitmap()
+        .map(|bitmap| authority.get_voters(bitmap))
+        .transpose()
+        .map_err(boxed)?;
+    for extension in extensions.iter() {
+        let is_voter = match voters.as_ref() {
+            Some(voters) => voters.contains(&extension.voter),
+            None => authority.contains(&extension.voter),
+        };
+        if !is_voter {
+            return Err(boxed(ConsensusError::AggregatedSignatureErr(format!(
+                "vote extension of {} out of the voters",
+                hex_encode(&extension.voter)
+            ))));
+        }
+        let preimage = codec
+            .vote_extension_preimage(vote, &extension.payload)
+            .map_err(boxed)?;
+        let hash = crypto.hash(preimage);
+        crypto.verify_signature(
+            extension.signature.clone(),
+            hash,
+            extension.voter.clone(),
+        )?;
+    }
+    Ok(())
+}
+
+fn boxed(err: ConsensusError) -> Box<dyn Error + Send> {
+    Box::new(err)
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::marker::PhantomData;
+
+    use bytes::Bytes;
+
+    use super::{verify_qc_signature, QcSignature, ThresholdSignature};
+    use crate::error::ConsensusError;
+    use crate::types::{Address, AggregatedSignature, Hash, Node, Signature};
+    use crate::utils::auth_manage::AuthorityManage;
+    use crate::Crypto;
+
+    /// The aggregated signature is the first bytes of the voters, and the group key of the
+    /// threshold signature is the number of the authorities.
+    struct MockCrypto<S>(PhantomData<S>);
+
+    impl<S: QcSignature> Crypto for MockCrypto<S> {
+        type QcSignature = S;
+
+        fn hash(&self, msg: Bytes) -> Hash {
+            Hash::from(msg)
+        }
+
+        fn sign(&self, _hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+            Ok(Signature::new())
+        }
+
+        fn aggregate_signatures(
+            &self,
+            _signatures: Vec<Signature>,
+            voters: Vec<Address>,
+        ) -> Result<Signature, Box<dyn Error + Send>> {
+            Ok(voters.iter().map(|voter| voter.as_bytes()[0]).collect())
+        }
+
+        fn verify_signature(
+            &self,
+            _signature: Signature,
+            _hash: Hash,
+            _voter: Address,
+        ) -> Result<(), Box<dyn Error + Send>> {
+            Ok(())
+        }
+
+        fn verify_aggregated_signature(
+            &self,
+            aggregate_signature: Signature,
+            _msg_hash: Hash,
+            voters: Vec<Address>,
+        ) -> Result<(), Box<dyn Error + Send>> {
+            let expected = self.aggregate_signatures(Vec::new(), voters)?;
+            check(aggregate_signature == expected)
+        }
+
+        fn verify_threshold_signature(
+            &self,
+            signature: Signature,
+            _msg_hash: Hash,
+            authority_list: &[Node],
+        ) -> Result<(), Box<dyn Error + Send>> {
+            check(signature == vec![authority_list.len() as u8])
+        }
+    }
+
+    fn check(valid: bool) -> Result<(), Box<dyn Error + Send>> {
+        if valid {
+            Ok(())
+        } else {
+            Err(Box::new(ConsensusError::CryptoErr("invalid".to_string())))
+        }
+    }
+
+    #[test]
+    fn test_qc_signatures() {
+        let mut authority_list = (0..4u8)
+            .map(|i| Node::new(Address::from_slice(&[i; 20]).unwrap()))
+            .collect::<Vec<_>>();
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list);
+        let votes = authority_list[..3]
+            .iter()
+            .map(|node| (node.address.clone(), Signature::new()))
+            .collect::<Vec<_>>();
+        let hash = Hash::new();
+
+        let crypto = MockCrypto::<AggregatedSignature>(PhantomData);
+        let qc = crypto.aggregate(votes.clone(), &authority_list).unwrap();
+        assert_eq!(qc.address_bitmap, Bytes::from(vec![0b1110_0000]));
+        assert_eq!(AggregatedSignature::from_wire(&qc.to_wire()).unwrap(), qc);
+        assert!(authority.is_qc_above_threshold::<AggregatedSignature>(&qc).unwrap());
+        assert!(verify_qc_signature(&crypto, &qc, hash.clone(), &authority).is_ok());
+
+        let crypto = MockCrypto::<ThresholdSignature>(PhantomData);
+        let threshold = crypto.aggregate(votes, &authority_list).unwrap();
+        let wire = threshold.to_wire();
+        assert!(wire.address_bitmap.is_empty());
+        assert_eq!(ThresholdSignature::from_wire(&wire).unwrap(), threshold);
+        assert!(ThresholdSignature::from_wire(&qc).is_err());
+        assert!(authority.is_qc_above_threshold::<ThresholdSignature>(&wire).unwrap());
+        // The combined signature is not of the group key of the 4 authorities.
+        assert!(verify_qc_signature(&crypto, &wire, hash.clone(), &authority).is_err());
+        let group = ThresholdSignature(Bytes::from(vec![4u8])).to_wire();
+        assert!(verify_qc_signature(&crypto, &group, hash, &authority).is_ok());
+    }
+}
@@ -0,0 +1,11 @@
+pub use crate::error::ConsensusError;
+pub use crate::types::{
+    Address, AggregatedSignature, AllowEmptyBlock, Commit, ContextExt, CorrelationId,
+    Hash, MlmMsg, Node, Proof, RoundContext, Signature, Status,
+};
+pub use crate::{
+    AsyncCrypto, Codec, ConsensusResult, Context, Crypto, DurationConfig, MlmConfig,
+    QcSignature, Wal,
+};
+#[cfg(feature = "runtime")]
+pub use crate::{Consensus, Mlm, MlmHandler};
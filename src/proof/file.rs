@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use rlp::{Decodable, Encodable};
+
+use crate::proof::ProofStore;
+use crate::types::{HandoverProof, Proof};
+
+const PROOF_EXTENSION: &str = "proof";
+const HANDOVER_EXTENSION: &str = "handover";
+
+/// A proof store in a directory. Each proof is encoded by rlp and saved as a file named by its
+/// height, and so is each handover proof with another extension.
+#[derive(Debug)]
+pub struct FileProofStore {
+    path: PathBuf,
+}
+
+impl FileProofStore {
+    /// Create a file proof store in the given directory. The directory is created if it does
+    /// not exist.
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::create_dir_all(path.as_ref())?;
+        Ok(FileProofStore {
+            path: path.as_ref().to_path_buf(),
+        })
+    }
+
+    fn proof_path(&self, height: u64) -> PathBuf {
+        self.path.join(format!("{}.{}", height, PROOF_EXTENSION))
+    }
+
+    fn handover_path(&self, height: u64) -> PathBuf {
+        self.path.join(format!("{}.{}", height, HANDOVER_EXTENSION))
+    }
+
+    fn read_item<D: Decodable>(
+        &self,
+        path: &Path,
+    ) -> Result<Option<D>, Box<dyn Error + Send>> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        let item =
+            rlp::decode(&data).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        Ok(Some(item))
+    }
+
+    fn write_item<E: Encodable>(
+        &self,
+        path: PathBuf,
+        item: &E,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        // Write to a temporary file first, so that a crash never leaves a broken item.
+        let tmp = path.with_extension("tmp");
+        let data = rlp::encode(item);
+
+        fs::write(&tmp, &data).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        fs::rename(&tmp, &path).map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProofStore for FileProofStore {
+    async fn save(&self, proof: Proof) -> Result<(), Box<dyn Error + Send>> {
+        self.write_item(self.proof_path(proof.height), &proof)
+    }
+
+    async fn get(&self, height: u64) -> Result<Option<Proof>, Box<dyn Error + Send>> {
+        self.read_item(&self.proof_path(height))
+    }
+
+    async fn latest(&self) -> Result<Option<Proof>, Box<dyn Error + Send>> {
+        let dir = fs::read_dir(&self.path)
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        let latest = dir
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != PROOF_EXTENSION {
+                    return None;
+                }
+                path.file_stem()?.to_str()?.parse::<u64>().ok()
+            })
+            .max();
+
+        match latest {
+            Some(height) => self.get(height).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn save_handover(
+        &self,
+        handover: HandoverProof,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        self.write_item(self.handover_path(handover.proof.height), &handover)
+    }
+
+    async fn get_handover(
+        &self,
+        height: u64,
+    ) -> Result<Option<HandoverProof>, Box<dyn Error + Send>> {
+        self.read_item(&self.handover_path(height))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use rand::random;
+
+    use super::FileProofStore;
+    use crate::proof::{test::gen_proof, ProofStore};
+    use crate::types::{Address, HandoverProof, Node};
+
+    #[tokio::test]
+    async fn test_file_store() {
+        let path = std::env::temp_dir().join(format!("mlm-proof-{}", random::<u64>()));
+        let store = FileProofStore::new(&path).unwrap();
+        assert_eq!(store.latest().await.unwrap(), None);
+
+        for height in [9, 10, 2] {
+            store.save(gen_proof(height)).await.unwrap();
+        }
+        assert_eq!(store.get(2).await.unwrap(), Some(gen_proof(2)));
+        assert_eq!(store.get(3).await.unwrap(), None);
+        assert_eq!(store.latest().await.unwrap(), Some(gen_proof(10)));
+
+        // The handover proofs are not taken as the proofs.
+        let handover = HandoverProof {
+            proof: gen_proof(11),
+            next_authority_list: vec![Node::new(
+                Address::from_slice(&[1u8; 32]).unwrap(),
+            )],
+        };
+        store.save_handover(handover.clone()).await.unwrap();
+        assert_eq!(store.get_handover(11).await.unwrap(), Some(handover));
+        assert_eq!(store.get_handover(10).await.unwrap(), None);
+        assert_eq!(store.latest().await.unwrap(), Some(gen_proof(10)));
+
+        // A reopened store reads the proofs saved before.
+        let store = FileProofStore::new(&path).unwrap();
+        assert_eq!(store.get(9).await.unwrap(), Some(gen_proof(9)));
+        fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+
+use crate::proof::ProofStore;
+use crate::types::{HandoverProof, Proof};
+
+/// A proof store in memory. If the capacity is set, only the proofs of the latest heights are
+/// kept, while the handover proofs of the epochs are all kept.
+#[derive(Debug, Default)]
+pub struct MemoryProofStore {
+    proofs: RwLock<BTreeMap<u64, Proof>>,
+    handovers: RwLock<BTreeMap<u64, HandoverProof>>,
+    capacity: Option<usize>,
+}
+
+impl MemoryProofStore {
+    /// Create a memory proof store without capacity limit.
+    pub fn new() -> Self {
+        MemoryProofStore::default()
+    }
+
+    /// Create a memory proof store that keeps the proofs of the latest `capacity` heights.
+    pub fn with_capacity(capacity: usize) -> Self {
+        MemoryProofStore {
+            proofs: RwLock::new(BTreeMap::new()),
+            handovers: RwLock::new(BTreeMap::new()),
+            capacity: Some(capacity),
+        }
+    }
+}
+
+#[async_trait]
+impl ProofStore for MemoryProofStore {
+    async fn save(&self, proof: Proof) -> Result<(), Box<dyn Error + Send>> {
+        let mut proofs = self.proofs.write();
+        proofs.insert(proof.height, proof);
+
+        if let Some(capacity) = self.capacity {
+            while proofs.len() > capacity {
+                proofs.pop_first();
+            }
+        }
+        Ok(())
+    }
+
+    async fn get(&self, height: u64) -> Result<Option<Proof>, Box<dyn Error + Send>> {
+        Ok(self.proofs.read().get(&height).cloned())
+    }
+
+    async fn latest(&self) -> Result<Option<Proof>, Box<dyn Error + Send>> {
+        Ok(self.proofs.read().values().next_back().cloned())
+    }
+
+    async fn save_handover(
+        &self,
+        handover: HandoverProof,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        self.handovers
+            .write()
+            .insert(handover.proof.height, handover);
+        Ok(())
+    }
+
+    async fn get_handover(
+        &self,
+        height: u64,
+    ) -> Result<Option<HandoverProof>, Box<dyn Error + Send>> {
+        Ok(self.handovers.read().get(&height).cloned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoryProofStore;
+    use crate::proof::{test::gen_proof, ProofStore};
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryProofStore::new();
+        assert_eq!(store.latest().await.unwrap(), None);
+
+        for height in [3, 1, 2] {
+            store.save(gen_proof(height)).await.unwrap();
+        }
+        assert_eq!(store.get(1).await.unwrap(), Some(gen_proof(1)));
+        assert_eq!(store.get(4).await.unwrap(), None);
+        assert_eq!(store.latest().await.unwrap(), Some(gen_proof(3)));
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_capacity() {
+        let store = MemoryProofStore::with_capacity(2);
+        for height in 1..=5 {
+            store.save(gen_proof(height)).await.unwrap();
+        }
+
+        assert_eq!(store.get(3).await.unwrap(), None);
+        assert_eq!(store.get(4).await.unwrap(), Some(gen_proof(4)));
+        assert_eq!(store.latest().await.unwrap(), Some(gen_proof(5)));
+    }
+}
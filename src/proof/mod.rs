@@ -0,0 +1,76 @@
+#[cfg(feature = "fs")]
+mod file;
+mod memory;
+#[cfg(feature = "rocksdb")]
+mod rocks;
+mod verify;
+
+#[cfg(feature = "fs")]
+pub use self::file::FileProofStore;
+pub use self::memory::MemoryProofStore;
+#[cfg(feature = "rocksdb")]
+pub use self::rocks::RocksProofStore;
+#[cfg(feature = "runtime")]
+pub use self::verify::ProofVerifier;
+pub use self::verify::{
+    verify_handover, verify_proof, verify_proof_with_codec, verify_proof_with_policy,
+};
+
+use std::error::Error;
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+
+use crate::types::{HandoverProof, Proof};
+
+/// Trait for save and load the proofs of the committed heights. The engine saves the proof of
+/// each height before the `commit()` interface is called, so that a syncing node or a light
+/// client can get the proofs from the crate instead of the adapter.
+#[async_trait]
+pub trait ProofStore: Debug + Send + Sync {
+    /// Save the proof of a committed height.
+    async fn save(&self, proof: Proof) -> Result<(), Box<dyn Error + Send>>;
+
+    /// Get the proof of the given height.
+    async fn get(&self, height: u64) -> Result<Option<Proof>, Box<dyn Error + Send>>;
+
+    /// Get the proof of the highest committed height.
+    async fn latest(&self) -> Result<Option<Proof>, Box<dyn Error + Send>>;
+
+    /// Save the handover proof of the last height of an epoch, which is saved after the proof
+    /// of the height. The default discards it.
+    async fn save_handover(
+        &self,
+        _handover: HandoverProof,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Ok(())
+    }
+
+    /// Get the handover proof of the epoch whose last height is the given height.
+    async fn get_handover(
+        &self,
+        _height: u64,
+    ) -> Result<Option<HandoverProof>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use crate::types::{AggregatedSignature, Hash, Proof};
+
+    pub fn gen_proof(height: u64) -> Proof {
+        Proof {
+            height,
+            round: height % 3,
+            block_hash: Hash::from(height.to_be_bytes().to_vec()),
+            signature: AggregatedSignature {
+                signature: Bytes::from(vec![1, 2, 3]),
+                address_bitmap: Bytes::from(vec![0xff]),
+            },
+            extensions: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::path::Path;
+
+use async_trait::async_trait;
+use rocksdb::{IteratorMode, DB};
+
+use crate::proof::ProofStore;
+use crate::types::Proof;
+
+/// A proof store in rocksdb. The key of a proof is the big endian bytes of its height, so the
+/// last key is always the latest height.
+pub struct RocksProofStore {
+    db: DB,
+}
+
+impl RocksProofStore {
+    /// Open or create a rocksdb proof store in the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, rocksdb::Error> {
+        let db = DB::open_default(path)?;
+        Ok(RocksProofStore { db })
+    }
+}
+
+impl std::fmt::Debug for RocksProofStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RocksProofStore")
+            .field("path", &self.db.path())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl ProofStore for RocksProofStore {
+    async fn save(&self, proof: Proof) -> Result<(), Box<dyn Error + Send>> {
+        self.db
+            .put(proof.height.to_be_bytes(), rlp::encode(&proof))
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+    }
+
+    async fn get(&self, height: u64) -> Result<Option<Proof>, Box<dyn Error + Send>> {
+        let data = self
+            .db
+            .get(height.to_be_bytes())
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+        match data {
+            Some(data) => rlp::decode(&data)
+                .map(Some)
+                .map_err(|e| Box::new(e) as Box<dyn Error + Send>),
+            None => Ok(None),
+        }
+    }
+
+    async fn latest(&self) -> Result<Option<Proof>, Box<dyn Error + Send>> {
+        let data = match self.db.iterator(IteratorMode::End).next() {
+            Some(item) => item.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?.1,
+            None => return Ok(None),
+        };
+
+        rlp::decode(&data)
+            .map(Some)
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::random;
+
+    use super::RocksProofStore;
+    use crate::proof::{test::gen_proof, ProofStore};
+
+    #[tokio::test]
+    async fn test_rocks_store() {
+        let path = std::env::temp_dir().join(format!("mlm-rocks-{}", random::<u64>()));
+        {
+            let store = RocksProofStore::open(&path).unwrap();
+            for height in [255, 256, 1] {
+                store.save(gen_proof(height)).await.unwrap();
+            }
+            assert_eq!(store.get(255).await.unwrap(), Some(gen_proof(255)));
+            assert_eq!(store.get(2).await.unwrap(), None);
+            assert_eq!(store.latest().await.unwrap(), Some(gen_proof(256)));
+        }
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,408 @@
+#[cfg(feature = "runtime")]
+use std::future::Future;
+use std::sync::Arc;
+
+#[cfg(feature = "runtime")]
+use futures::stream::{self, StreamExt};
+
+use crate::error::ConsensusError;
+#[cfg(feature = "runtime")]
+use crate::error::ErrorKind;
+use crate::multisig::{verify_qc_signature, verify_vote_extensions};
+use crate::threshold::{BftThreshold, ThresholdPolicy};
+use crate::types::{HandoverProof, Node, Proof, Vote, VoteType};
+use crate::utils::auth_manage::AuthorityManage;
+use crate::wire::{RlpCodec, WireCodec};
+use crate::{ConsensusResult, Crypto};
+
+/// Verify the proof of a height by the authority list of the height. The aggregated signature
+/// must be above the threshold. The vote extensions of the proof are verified by the
+/// signatures of their voters. It needs no running instance, so a bridge or a light client
+/// verifies the commits with it.
+pub fn verify_proof<C: Crypto + ?Sized>(
+    crypto: &C,
+    proof: &Proof,
+    authority_list: Vec<Node>,
+) -> ConsensusResult<()> {
+    verify_proof_with_policy(crypto, proof, authority_list, Arc::new(BftThreshold))
+}
+
+/// Verify the proof of a height by the authority list of the height, the threshold of which is
+/// decided by the policy of the network.
+pub fn verify_proof_with_policy<C: Crypto + ?Sized>(
+    crypto: &C,
+    proof: &Proof,
+    authority_list: Vec<Node>,
+    policy: Arc<dyn ThresholdPolicy>,
+) -> ConsensusResult<()> {
+    verify_proof_with_codec(crypto, proof, authority_list, policy, &RlpCodec)
+}
+
+/// Verify the proof of a height by the authority list of the height and the threshold policy
+/// of the network, whose signature preimage is encoded by the codec of the network.
+pub fn verify_proof_with_codec<C: Crypto + ?Sized>(
+    crypto: &C,
+    proof: &Proof,
+    mut authority_list: Vec<Node>,
+    policy: Arc<dyn ThresholdPolicy>,
+    codec: &dyn WireCodec,
+) -> ConsensusResult<()> {
+    let mut authority = AuthorityManage::new();
+    authority.set_policy(policy);
+    authority.update(&mut authority_list);
+
+    let signature = &proof.signature;
+    if !authority.is_qc_above_threshold::<C::QcSignature>(signature)? {
+        return Err(ConsensusError::AggregatedSignatureErr(format!(
+            "proof of height {} is not above threshold",
+            proof.height
+        )));
+    }
+
+    let vote = Vote {
+        height: proof.height,
+        round: proof.round,
+        vote_type: VoteType::Precommit,
+        block_hash: proof.block_hash.clone(),
+    };
+    let hash = crypto.hash(codec.vote_preimage(&vote)?);
+    verify_qc_signature(crypto, signature, hash, &authority)
+        .and_then(|()| {
+            verify_vote_extensions(
+                crypto,
+                codec,
+                &vote,
+                signature,
+                &proof.extensions,
+                &authority,
+            )
+        })
+        .map_err(|err| {
+            ConsensusError::AggregatedSignatureErr(format!(
+                "verify proof of height {} error {:?}",
+                proof.height, err
+            ))
+        })
+}
+
+/// Verify the handover proof of an epoch by the authority list of the epoch, and return the
+/// sorted authority list of the next epoch. The handover binds the next list only if the
+/// application commits it in the block of the proof, such as by its hash in the header.
+pub fn verify_handover<C: Crypto + ?Sized>(
+    crypto: &C,
+    handover: &HandoverProof,
+    authority_list: Vec<Node>,
+) -> ConsensusResult<Vec<Node>> {
+    verify_proof(crypto, &handover.proof, authority_list)?;
+    if handover.next_authority_list.is_empty() {
+        return Err(ConsensusError::AuthErr(format!(
+            "handover of height {} has an empty authority list",
+            handover.proof.height
+        )));
+    }
+
+    let mut next_authority_list = handover.next_authority_list.clone();
+    next_authority_list.sort();
+    Ok(next_authority_list)
+}
+
+/// A verifier of the proofs on the sync path. The proofs of consecutive heights are verified
+/// concurrently by a bounded number of blocking tasks, while the verified proofs are applied
+/// strictly in order.
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+pub struct ProofVerifier<C: Crypto> {
+    crypto: Arc<C>,
+    concurrency: usize,
+    policy: Arc<dyn ThresholdPolicy>,
+    codec: Arc<dyn WireCodec>,
+}
+
+#[cfg(feature = "runtime")]
+impl<C: Crypto + Sync + 'static> ProofVerifier<C> {
+    /// Create a verifier which verifies at most `concurrency` proofs at the same time.
+    pub fn new(crypto: Arc<C>, concurrency: usize) -> Self {
+        ProofVerifier {
+            crypto,
+            concurrency: concurrency.max(1),
+            policy: Arc::new(BftThreshold),
+            codec: Arc::new(RlpCodec),
+        }
+    }
+
+    /// Set the threshold policy of the proofs, which is `BftThreshold` by default.
+    pub fn with_policy(mut self, policy: Arc<dyn ThresholdPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set the encoding of the signature preimages of the proofs, which is `RlpCodec` by
+    /// default.
+    pub fn with_codec(mut self, codec: Arc<dyn WireCodec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Verify the proofs with the authority lists of their heights, and call `apply` for each
+    /// verified proof in the order of the input. The verification runs ahead of the applying
+    /// by at most the concurrency. It stops at the first invalid proof or failed apply, and
+    /// returns the number of the applied proofs if all of them succeed.
+    pub async fn verify_and_apply<F, Fut>(
+        &self,
+        proofs: Vec<(Proof, Vec<Node>)>,
+        mut apply: F,
+    ) -> ConsensusResult<usize>
+    where
+        F: FnMut(Proof) -> Fut,
+        Fut: Future<Output = ConsensusResult<()>>,
+    {
+        let mut verified =
+            stream::iter(proofs.into_iter().map(|(proof, authority_list)| {
+                let crypto = Arc::clone(&self.crypto);
+                let policy = Arc::clone(&self.policy);
+                let codec = Arc::clone(&self.codec);
+                tokio::task::spawn_blocking(move || {
+                    verify_proof_with_codec(
+                        crypto.as_ref(),
+                        &proof,
+                        authority_list,
+                        policy,
+                        codec.as_ref(),
+                    )
+                    .map(|_| proof)
+                })
+            }))
+            .buffered(self.concurrency);
+
+        let mut applied = 0;
+        while let Some(res) = verified.next().await {
+            let proof = res.map_err(|e| {
+                ConsensusError::with_source(ErrorKind::Internal, "verify proof task", e)
+            })??;
+            apply(proof).await?;
+            applied += 1;
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::sync::Arc;
+
+    use bit_vec::BitVec;
+    use bytes::Bytes;
+    #[cfg(feature = "runtime")]
+    use parking_lot::Mutex;
+
+    use super::{verify_handover, verify_proof, verify_proof_with_policy};
+    #[cfg(feature = "runtime")]
+    use super::ProofVerifier;
+    use crate::threshold::FixedCountThreshold;
+    use crate::types::{
+        Address, AggregatedSignature, HandoverProof, Hash, Node, Proof, Signature,
+    };
+    use crate::Crypto;
+
+    /// The aggregated signature is valid if it is the hash itself.
+    #[derive(Debug)]
+    struct HashCrypto;
+
+    impl Crypto for HashCrypto {
+        type QcSignature = AggregatedSignature;
+
+        fn hash(&self, msg: Bytes) -> Hash {
+            Hash::from(msg)
+        }
+
+        fn sign(&self, hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+            Ok(hash.into_bytes())
+        }
+
+        fn aggregate_signatures(
+            &self,
+            _signatures: Vec<Signature>,
+            _voters: Vec<Address>,
+        ) -> Result<Signature, Box<dyn Error + Send>> {
+            Ok(Signature::new())
+        }
+
+        fn verify_signature(
+            &self,
+            _signature: Signature,
+            _hash: Hash,
+            _voter: Address,
+        ) -> Result<(), Box<dyn Error + Send>> {
+            Ok(())
+        }
+
+        fn verify_aggregated_signature(
+            &self,
+            aggregate_signature: Signature,
+            msg_hash: Hash,
+            _voters: Vec<Address>,
+        ) -> Result<(), Box<dyn Error + Send>> {
+            if aggregate_signature == msg_hash.into_bytes() {
+                Ok(())
+            } else {
+                Err(Box::new(crate::error::ConsensusError::CryptoErr(
+                    "Invalid signature".to_string(),
+                )))
+            }
+        }
+    }
+
+    fn gen_authority_list() -> Vec<Node> {
+        (0u8..4)
+            .map(|i| Node::new(Address::from_slice(&[i; 20]).unwrap()))
+            .collect()
+    }
+
+    fn gen_proof(height: u64, voters: usize) -> Proof {
+        let mut bitmap = BitVec::from_elem(4, false);
+        (0..voters).for_each(|i| bitmap.set(i, true));
+        let block_hash = Hash::from(height.to_be_bytes().to_vec());
+        let vote = crate::types::Vote {
+            height,
+            round: 0,
+            vote_type: crate::types::VoteType::Precommit,
+            block_hash: block_hash.clone(),
+        };
+
+        Proof {
+            height,
+            round: 0,
+            block_hash,
+            signature: AggregatedSignature {
+                signature: Bytes::from(rlp::encode(&vote)),
+                address_bitmap: Bytes::from(bitmap.to_bytes()),
+            },
+            extensions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        assert!(
+            verify_proof(&HashCrypto, &gen_proof(1, 3), gen_authority_list()).is_ok()
+        );
+        assert!(
+            verify_proof(&HashCrypto, &gen_proof(1, 2), gen_authority_list()).is_err()
+        );
+
+        let mut proof = gen_proof(1, 3);
+        proof.height = 2;
+        assert!(verify_proof(&HashCrypto, &proof, gen_authority_list()).is_err());
+
+        // Two voters are a quorum of the fixed count policy.
+        let policy = Arc::new(FixedCountThreshold(2));
+        let proof = gen_proof(1, 2);
+        assert!(verify_proof_with_policy(
+            &HashCrypto,
+            &proof,
+            gen_authority_list(),
+            policy.clone()
+        )
+        .is_ok());
+        let proof = gen_proof(1, 1);
+        assert!(verify_proof_with_policy(
+            &HashCrypto,
+            &proof,
+            gen_authority_list(),
+            policy
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_proof_bundle() {
+        // A proof shipped as JSON is verified without an instance.
+        let proof = gen_proof(7, 3);
+        let json = serde_json::to_string(&proof).unwrap();
+        let res: Proof = serde_json::from_str(&json).unwrap();
+        assert_eq!(res, proof);
+        assert!(verify_proof(&HashCrypto, &res, gen_authority_list()).is_ok());
+
+        let handover = HandoverProof {
+            proof,
+            next_authority_list: gen_authority_list(),
+        };
+        let json = serde_json::to_string(&handover).unwrap();
+        assert_eq!(
+            serde_json::from_str::<HandoverProof>(&json).unwrap(),
+            handover
+        );
+    }
+
+    #[test]
+    fn test_verify_handover() {
+        let next_authority_list = (6u8..10)
+            .rev()
+            .map(|i| Node::new(Address::from_slice(&[i; 20]).unwrap()))
+            .collect::<Vec<_>>();
+        let mut handover = HandoverProof {
+            proof: gen_proof(3, 3),
+            next_authority_list: next_authority_list.clone(),
+        };
+
+        // The next list is sorted, and the proofs of the next epoch are verified by it.
+        let next =
+            verify_handover(&HashCrypto, &handover, gen_authority_list()).unwrap();
+        let mut sorted = next_authority_list;
+        sorted.sort();
+        assert_eq!(next, sorted);
+        assert!(verify_proof(&HashCrypto, &gen_proof(4, 3), next).is_ok());
+
+        handover.proof = gen_proof(3, 2);
+        assert!(verify_handover(&HashCrypto, &handover, gen_authority_list()).is_err());
+
+        handover.proof = gen_proof(3, 3);
+        handover.next_authority_list.clear();
+        assert!(verify_handover(&HashCrypto, &handover, gen_authority_list()).is_err());
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn test_verify_and_apply() {
+        let verifier = ProofVerifier::new(Arc::new(HashCrypto), 4);
+        let applied = Arc::new(Mutex::new(Vec::new()));
+
+        let proofs = (1..=20)
+            .map(|h| (gen_proof(h, 3), gen_authority_list()))
+            .collect::<Vec<_>>();
+        let res = verifier
+            .verify_and_apply(proofs, |proof| {
+                let applied = Arc::clone(&applied);
+                async move {
+                    applied.lock().push(proof.height);
+                    Ok(())
+                }
+            })
+            .await;
+        assert_eq!(res.unwrap(), 20);
+        assert_eq!(*applied.lock(), (1..=20).collect::<Vec<_>>());
+
+        // Stop at the first invalid proof.
+        applied.lock().clear();
+        let proofs = (1..=5)
+            .map(|h| {
+                (
+                    gen_proof(h, if h == 3 { 2 } else { 3 }),
+                    gen_authority_list(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let res = verifier
+            .verify_and_apply(proofs, |proof| {
+                let applied = Arc::clone(&applied);
+                async move {
+                    applied.lock().push(proof.height);
+                    Ok(())
+                }
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(*applied.lock(), vec![1, 2]);
+    }
+}
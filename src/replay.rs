@@ -0,0 +1,377 @@
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use log::warn;
+use parking_lot::Mutex;
+use rlp::{DecoderError, Rlp};
+
+use crate::error::ConsensusError;
+#[cfg(feature = "fs")]
+use crate::error::ErrorKind;
+use crate::event::now_millis;
+use crate::smr::pure::{Smr, SmrInput, SmrOutput, SmrState};
+use crate::smr::smr_types::Step;
+use crate::wal::WalInfo;
+use crate::{Codec, ConsensusResult};
+
+/// An input or an output of the state machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEntry {
+    /// An input from the state or the timer.
+    Input(SmrInput),
+    /// An output to the state and the timer.
+    Output(SmrOutput),
+}
+
+/// A record of a consensus trace, with the milliseconds since the Unix epoch when the input
+/// is handled or the output is thrown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The input or the output.
+    pub entry: TraceEntry,
+}
+
+/// The recorder that appends every input and output of the state machine of an instance to a
+/// log, which is set by `Mlm::set_trace_recorder()`. Each record is an rlp item, and the log
+/// is read by `read_trace()`. A failed write is logged and the instance goes on.
+pub struct TraceRecorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl fmt::Debug for TraceRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TraceRecorder").finish_non_exhaustive()
+    }
+}
+
+impl TraceRecorder {
+    /// Create a recorder that writes the records to the writer.
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        TraceRecorder {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Create a recorder that appends the records to the file of the path, which is created if
+    /// it does not exist.
+    #[cfg(feature = "fs")]
+    pub fn open<P: AsRef<Path>>(path: P) -> ConsensusResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                ConsensusError::with_source(ErrorKind::Other, "open the trace", e)
+            })?;
+        Ok(TraceRecorder::new(file))
+    }
+
+    pub(crate) fn record(&self, entry: TraceEntry) {
+        let record = TraceRecord {
+            timestamp: now_millis(),
+            entry,
+        };
+        if let Err(e) = self.writer.lock().write_all(&rlp::encode(&record)) {
+            warn!("Mlm: trace recorder write error {:?}", e);
+        }
+    }
+}
+
+/// Read the records of a trace.
+pub fn read_trace(data: &[u8]) -> ConsensusResult<Vec<TraceRecord>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let decode_err = |e| ConsensusError::DecodeErr(format!("trace record {:?}", e));
+        let len = Rlp::new(&data[offset..])
+            .payload_info()
+            .map_err(decode_err)?
+            .total();
+        let item = data
+            .get(offset..offset + len)
+            .ok_or_else(|| decode_err(DecoderError::RlpIsTooShort))?;
+        records.push(rlp::decode(item).map_err(decode_err)?);
+        offset += len;
+    }
+    Ok(records)
+}
+
+/// Read the records of the trace file of the path.
+#[cfg(feature = "fs")]
+pub fn read_trace_file<P: AsRef<Path>>(path: P) -> ConsensusResult<Vec<TraceRecord>> {
+    let data = fs::read(path).map_err(|e| {
+        ConsensusError::with_source(ErrorKind::Other, "read the trace", e)
+    })?;
+    read_trace(&data)
+}
+
+/// The first input whose replayed outputs differ from the recorded ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The index of the input in the records.
+    pub index: usize,
+    /// The input.
+    pub input: SmrInput,
+    /// The outputs recorded after the input, which are the commits alone by
+    /// `replay_from()`.
+    pub recorded: Vec<SmrOutput>,
+    /// The outputs of the replayed state machine, which are the commits alone by
+    /// `replay_from()`.
+    pub replayed: Vec<SmrOutput>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Replay diverges at record {} of {:?}, recorded {:?}, replayed {:?}",
+            self.index, self.input, self.recorded, self.replayed
+        )
+    }
+}
+
+impl Error for Divergence {}
+
+/// Re-drive a state machine by the inputs of the records, and check that each input causes
+/// the outputs recorded after it. The outputs before the first input are skipped. Return the
+/// state after the last input, or the first input that diverges.
+pub fn replay(records: &[TraceRecord]) -> Result<SmrState, Box<Divergence>> {
+    replay_on(Smr::new(), records, |_| true)
+}
+
+/// Reconstruct a state machine from the wal that an instance loads at the restart, re-drive
+/// it by the inputs of the message log recorded after the restart, and check that each input
+/// commits the blocks recorded after it. Only the commits are checked, so the divergence
+/// carries the commits alone. A wal of the commit step is committed by the engine without
+/// the state machine, which then starts by the new height of the log. Return the state after
+/// the last input, or the first input that decides otherwise.
+pub fn replay_from<T: Codec>(
+    wal: Option<&WalInfo<T>>,
+    message_log: &[TraceRecord],
+) -> Result<SmrState, Box<Divergence>> {
+    let mut smr = Smr::new();
+    if let Some(wal) = wal.filter(|wal| wal.step != Step::Commit) {
+        let _ = smr.drive(SmrInput::Wal {
+            height: wal.height,
+            round: wal.round,
+            step: wal.step.clone(),
+            lock: wal.lock.as_ref().map(|lock| {
+                let lock = lock.to_lock();
+                (lock.round, lock.hash)
+            }),
+        });
+    }
+    replay_on(smr, message_log, |output| {
+        matches!(output, SmrOutput::Commit { .. })
+    })
+}
+
+fn replay_on<F: Fn(&SmrOutput) -> bool>(
+    mut smr: Smr,
+    records: &[TraceRecord],
+    checked: F,
+) -> Result<SmrState, Box<Divergence>> {
+    let mut pending: Option<(usize, SmrInput, Vec<SmrOutput>)> = None;
+    for (index, record) in records.iter().enumerate() {
+        match &record.entry {
+            TraceEntry::Input(input) => {
+                if let Some((index, input, recorded)) = pending.take() {
+                    check_step(&mut smr, index, input, recorded, &checked)?;
+                }
+                pending = Some((index, input.clone(), Vec::new()));
+            }
+            TraceEntry::Output(output) if checked(output) => {
+                if let Some((_, _, recorded)) = pending.as_mut() {
+                    recorded.push(output.clone());
+                }
+            }
+            TraceEntry::Output(_) => (),
+        }
+    }
+    if let Some((index, input, recorded)) = pending {
+        check_step(&mut smr, index, input, recorded, &checked)?;
+    }
+    Ok(smr.state())
+}
+
+fn check_step<F: Fn(&SmrOutput) -> bool>(
+    smr: &mut Smr,
+    index: usize,
+    input: SmrInput,
+    recorded: Vec<SmrOutput>,
+    checked: &F,
+) -> Result<(), Box<Divergence>> {
+    // A rejected input is logged and ignored by the engine, so only its outputs are checked.
+    let (mut replayed, _) = smr.drive(input.clone());
+    replayed.retain(|output| checked(output));
+    if replayed != recorded {
+        return Err(Box::new(Divergence {
+            index,
+            input,
+            recorded,
+            replayed,
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod test {
+    use std::io::{self, Write};
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::runtime::TokioRuntime;
+    use crate::smr::smr_types::SMREvent;
+    use crate::smr::SMR;
+    use crate::types::{AggregatedChoke, Hash, UpdateFrom};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Run a state machine by the inputs until it commits the hash, and return its trace.
+    async fn record_trace(inputs: Vec<SmrInput>, hash: &Hash) -> Vec<u8> {
+        let buf = SharedBuf::default();
+        let (mut provider, _evt_state, mut evt_timer) = SMR::new();
+        let mut handler = provider.take_smr().unwrap();
+        provider.set_recorder(Arc::new(TraceRecorder::new(buf.clone())));
+        provider.run(&TokioRuntime);
+
+        for input in inputs {
+            handler.trigger(input.into_trigger()).unwrap();
+        }
+        while let Some(event) = evt_timer.next().await {
+            if event == SMREvent::Commit(hash.clone()) {
+                break;
+            }
+        }
+        let data = buf.0.lock().clone();
+        data
+    }
+
+    /// The inputs of a round that commits the hash at the height one.
+    fn round_inputs(hash: &Hash) -> Vec<SmrInput> {
+        vec![
+            SmrInput::Proposal {
+                height: 1,
+                round: 0,
+                hash: hash.clone(),
+                lock_round: None,
+            },
+            SmrInput::PrevoteQC {
+                height: 1,
+                round: 0,
+                hash: hash.clone(),
+            },
+            SmrInput::PrecommitQC {
+                height: 1,
+                round: 0,
+                hash: hash.clone(),
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let hash = Hash::from(vec![1u8; 32]);
+        let mut inputs = vec![SmrInput::NewHeight {
+            height: 1,
+            interval: None,
+            config: None,
+        }];
+        inputs.extend(round_inputs(&hash));
+        let data = record_trace(inputs, &hash).await;
+        let records = read_trace(&data).unwrap();
+        let inputs = records
+            .iter()
+            .filter(|record| matches!(record.entry, TraceEntry::Input(_)))
+            .count();
+        assert_eq!(inputs, 4);
+        let state = replay(&records).unwrap();
+        assert_eq!(state.height, 1);
+        assert_eq!(state.step, Step::Commit);
+        assert!(read_trace(&data[..data.len() - 1]).is_err());
+
+        // The replay stops at the input whose outputs differ from the recorded ones.
+        let mut tampered = records.clone();
+        let commit = SmrOutput::Commit { hash };
+        let index = tampered
+            .iter()
+            .position(|record| record.entry == TraceEntry::Output(commit.clone()))
+            .unwrap();
+        tampered[index].entry = TraceEntry::Output(SmrOutput::Commit {
+            hash: Hash::from(vec![2u8; 32]),
+        });
+        let divergence = replay(&tampered).unwrap_err();
+        assert!(matches!(divergence.input, SmrInput::PrecommitQC { .. }));
+        assert_eq!(divergence.replayed, vec![commit]);
+    }
+
+    #[tokio::test]
+    async fn test_replay_from_wal() {
+        let hash = Hash::from(vec![1u8; 32]);
+        let mut inputs = vec![SmrInput::Wal {
+            height: 1,
+            round: 0,
+            step: Step::Propose,
+            lock: None,
+        }];
+        inputs.extend(round_inputs(&hash));
+        let records = read_trace(&record_trace(inputs, &hash).await).unwrap();
+
+        // The message log after the wal input decides the same only from the wal.
+        let start = records
+            .iter()
+            .position(|record| {
+                matches!(record.entry, TraceEntry::Input(SmrInput::Proposal { .. }))
+            })
+            .unwrap();
+        let message_log = &records[start..];
+        let wal = WalInfo::<Bytes> {
+            height: 1,
+            round: 0,
+            step: Step::Propose,
+            lock: None,
+            from: UpdateFrom::ChokeQC(AggregatedChoke {
+                height: 1,
+                round: 0,
+                signature: Bytes::new(),
+                voters: Vec::new(),
+            }),
+            status: None,
+            pending_commit: None,
+        };
+        let state = replay_from(Some(&wal), message_log).unwrap();
+        assert_eq!(state.height, 1);
+        assert_eq!(state.step, Step::Commit);
+        assert!(replay_from(Some(&wal), &records).is_ok());
+        assert!(replay(message_log).is_err());
+
+        let divergence = replay_from::<Bytes>(None, message_log).unwrap_err();
+        assert!(matches!(divergence.input, SmrInput::PrecommitQC { .. }));
+        assert_eq!(divergence.recorded, vec![SmrOutput::Commit { hash }]);
+        assert!(divergence.replayed.is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt::Debug;
+
+use parking_lot::Mutex;
+use rand_core::{RngCore, SeedableRng};
+use rand_pcg::Pcg64Mcg as Pcg;
+
+/// The source of the randomness of the engine, such as the jitter of the step timeouts. It is
+/// `SeededRng` seeded by the address of the node by default, and a simulation injects its own
+/// by `Mlm::set_rng()` to reproduce an execution exactly.
+pub trait Rng: Send + Sync + Debug {
+    /// The next random number.
+    fn next_u64(&self) -> u64;
+}
+
+/// The random numbers of a PCG generator seeded at construction, which are the same of the
+/// same seed.
+#[derive(Debug)]
+pub struct SeededRng(Mutex<Pcg>);
+
+impl SeededRng {
+    /// Create the generator of the seed.
+    pub fn new(seed: u64) -> Self {
+        SeededRng(Mutex::new(Pcg::seed_from_u64(seed)))
+    }
+
+    /// Create the generator seeded by the bytes, such as the address of a node.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let seed = bytes
+            .iter()
+            .fold(0u64, |acc, byte| acc.rotate_left(8) ^ u64::from(*byte));
+        SeededRng::new(seed)
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_u64(&self) -> u64 {
+        self.0.lock().next_u64()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Rng, SeededRng};
+
+    #[test]
+    fn test_seeded_rng() {
+        let draw = |rng: &SeededRng| (0..8).map(|_| rng.next_u64()).collect::<Vec<_>>();
+        assert_eq!(draw(&SeededRng::new(7)), draw(&SeededRng::new(7)));
+        assert_ne!(draw(&SeededRng::new(7)), draw(&SeededRng::new(8)));
+        assert_eq!(
+            draw(&SeededRng::from_bytes(&[1, 2, 3])),
+            draw(&SeededRng::new(0x0001_0203))
+        );
+    }
+}
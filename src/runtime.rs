@@ -0,0 +1,124 @@
+use std::fmt::Debug;
+#[cfg(feature = "runtime")]
+use std::panic::AssertUnwindSafe;
+#[cfg(feature = "runtime")]
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "runtime")]
+use futures::channel::mpsc::UnboundedSender;
+use futures::future::BoxFuture;
+#[cfg(feature = "runtime")]
+use futures::FutureExt;
+
+#[cfg(feature = "runtime")]
+use crate::error::ConsensusExit;
+
+/// The executor that the background tasks and the timers of an instance run on. The SMR, the
+/// timer and the signature verification, the commit and the delayed events of the state are
+/// spawned by it, and the step timeouts wait for its sleeps. It is `TokioRuntime` by default,
+/// and an application on another executor, or a test on a deterministic one, implements it
+/// instead.
+pub trait Runtime: Send + Sync + Debug {
+    /// Spawn the task to run in the background.
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+
+    /// A future that completes after the duration.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The runtime of the tokio executor that the instance runs in.
+#[cfg(feature = "runtime")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "runtime")]
+impl Runtime for TokioRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// The runtime that reports the panics of the tasks spawned by the inner one, so that the
+/// engine exits with the panic instead of running on without the task.
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+pub(crate) struct SupervisedRuntime {
+    inner: Arc<dyn Runtime>,
+    exits: UnboundedSender<ConsensusExit>,
+}
+
+#[cfg(feature = "runtime")]
+impl SupervisedRuntime {
+    pub(crate) fn new(
+        inner: Arc<dyn Runtime>,
+        exits: UnboundedSender<ConsensusExit>,
+    ) -> Self {
+        SupervisedRuntime { inner, exits }
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl Runtime for SupervisedRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        let exits = self.exits.clone();
+        self.inner.spawn(Box::pin(async move {
+            if let Err(payload) = AssertUnwindSafe(task).catch_unwind().await {
+                let _ = exits.unbounded_send(ConsensusExit::panicked(payload));
+            }
+        }));
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.inner.sleep(duration)
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::channel::mpsc::unbounded;
+    use futures::future::BoxFuture;
+    use futures::StreamExt;
+
+    use super::{Runtime, SupervisedRuntime};
+    use crate::error::ConsensusExit;
+
+    #[derive(Debug)]
+    struct Spawner;
+
+    impl Runtime for Spawner {
+        fn spawn(&self, task: BoxFuture<'static, ()>) {
+            tokio::spawn(task);
+        }
+
+        fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+            Box::pin(tokio::time::sleep(duration))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervised_runtime() {
+        let (tx, mut rx) = unbounded();
+        let runtime = SupervisedRuntime::new(Arc::new(Spawner), tx);
+        runtime.spawn(Box::pin(async {}));
+        runtime.spawn(Box::pin(async { panic!("verify task {}", 1) }));
+        match rx.next().await {
+            Some(ConsensusExit::Panicked(message)) => {
+                assert_eq!(message, "verify task 1")
+            }
+            exit => panic!("unexpected exit {:?}", exit),
+        }
+
+        runtime.spawn(Box::pin(async { panic!("timer task") }));
+        let exit = rx.next().await.unwrap();
+        assert_eq!(exit.to_string(), "Task panicked: timer task");
+        assert!(!exit.is_requested());
+    }
+}
@@ -1,29 +1,51 @@
 use std::fmt;
+use std::marker::PhantomData;
 
 use bytes::Bytes;
 use hummer::coding::{hex_decode, hex_encode};
 use serde::{de, Deserializer, Serializer};
 
-/// serialize Bytes with hex
-pub fn serialize<S>(val: &Bytes, s: S) -> Result<S::Ok, S::Error>
+/// serialize Bytes or Address with hex
+pub fn serialize<T, S>(val: &T, s: S) -> Result<S::Ok, S::Error>
 where
+    T: AsRef<[u8]>,
     S: Serializer,
 {
-    s.serialize_str(&hex_encode(val))
+    s.serialize_str(&hex_encode(val.as_ref()))
 }
 
-struct StringVisit;
+struct StringVisit<T>(PhantomData<T>);
 
-/// deserialize Bytes with hex
-pub fn deserialize<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+/// deserialize Bytes or Address with hex
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
 where
+    T: TryFrom<Bytes>,
+    T::Error: fmt::Display,
     D: Deserializer<'de>,
 {
-    deserializer.deserialize_str(StringVisit)
+    deserializer.deserialize_str(StringVisit(PhantomData))
 }
 
-impl<'de> de::Visitor<'de> for StringVisit {
-    type Value = Bytes;
+/// deserialize Bytes or Address with hex, where the empty string is the default value
+pub fn deserialize_or_default<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: TryFrom<Bytes> + Default,
+    T::Error: fmt::Display,
+    D: Deserializer<'de>,
+{
+    let value: Bytes = deserialize(deserializer)?;
+    if value.is_empty() {
+        return Ok(T::default());
+    }
+    T::try_from(value).map_err(de::Error::custom)
+}
+
+impl<'de, T> de::Visitor<'de> for StringVisit<T>
+where
+    T: TryFrom<Bytes>,
+    T::Error: fmt::Display,
+{
+    type Value = T;
 
     fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         formatter.write_str("byte array")
@@ -35,6 +57,6 @@ impl<'de> de::Visitor<'de> for StringVisit {
         E: de::Error,
     {
         let value = hex_decode(v).map_err(de::Error::custom)?;
-        Ok(Bytes::from(value))
+        T::try_from(Bytes::from(value)).map_err(de::Error::custom)
     }
 }
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::{de, ser, Deserializer, Serializer};
+
+const NANOS_PER_MILLI: u32 = 1_000_000;
+
+/// Serialize an optional duration in milliseconds. A whole number of milliseconds is an
+/// integer as before the durations, and a sub-millisecond duration is a float in the human
+/// readable formats, which the binary formats do not support.
+pub fn serialize<S>(val: &Option<Duration>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match val {
+        None => s.serialize_none(),
+        Some(d) if d.subsec_nanos() % NANOS_PER_MILLI == 0 => {
+            s.serialize_some(&(d.as_millis() as u64))
+        }
+        Some(d) if s.is_human_readable() => {
+            s.serialize_some(&(d.as_nanos() as f64 / NANOS_PER_MILLI as f64))
+        }
+        Some(d) => Err(ser::Error::custom(format!(
+            "sub-millisecond duration {:?} in a binary format",
+            d
+        ))),
+    }
+}
+
+/// Deserialize an optional duration from the integer or the float milliseconds.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_option(OptionVisit)
+}
+
+struct OptionVisit;
+
+impl<'de> de::Visitor<'de> for OptionVisit {
+    type Value = Option<Duration>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("optional milliseconds")
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(MillisVisit).map(Some)
+        } else {
+            deserializer.deserialize_u64(MillisVisit).map(Some)
+        }
+    }
+}
+
+struct MillisVisit;
+
+impl<'de> de::Visitor<'de> for MillisVisit {
+    type Value = Duration;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("milliseconds")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Duration::from_millis(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        u64::try_from(v)
+            .map(Duration::from_millis)
+            .map_err(|_| de::Error::custom("negative milliseconds"))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        let nanos = (v * NANOS_PER_MILLI as f64).round();
+        if !(0.0..=u64::MAX as f64).contains(&nanos) {
+            return Err(de::Error::custom(format!("invalid milliseconds {}", v)));
+        }
+        Ok(Duration::from_nanos(nanos as u64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Interval {
+        #[serde(with = "super")]
+        interval: Option<Duration>,
+    }
+
+    #[test]
+    fn test_serde_millis() {
+        for (interval, json) in [
+            (None, r#"{"interval":null}"#),
+            (Some(Duration::from_secs(3)), r#"{"interval":3000}"#),
+            (Some(Duration::from_micros(250)), r#"{"interval":0.25}"#),
+        ] {
+            let val = Interval { interval };
+            assert_eq!(serde_json::to_string(&val).unwrap(), json);
+            assert_eq!(serde_json::from_str::<Interval>(json).unwrap(), val);
+        }
+
+        let val = Interval {
+            interval: Some(Duration::from_millis(20)),
+        };
+        let bytes = bincode::serialize(&val).unwrap();
+        assert_eq!(bincode::deserialize::<Interval>(&bytes).unwrap(), val);
+        let val = Interval {
+            interval: Some(Duration::from_micros(20)),
+        };
+        assert!(bincode::serialize(&val).is_err());
+    }
+}
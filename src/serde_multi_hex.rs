@@ -1,4 +1,4 @@
-use std::{fmt, iter::FromIterator};
+use std::fmt;
 
 use bytes::Bytes;
 use derive_more::Constructor;
@@ -17,14 +17,15 @@ struct VecT {
     inner: Vec<TWrapper>,
 }
 
-pub fn serialize<S>(val: &[Bytes], s: S) -> Result<S::Ok, S::Error>
+pub fn serialize<T, S>(val: &[T], s: S) -> Result<S::Ok, S::Error>
 where
+    T: AsRef<[u8]>,
     S: Serializer,
 {
     let inner = val
         .iter()
         .map(|t| TWrapper {
-            inner: t.to_owned(),
+            inner: Bytes::copy_from_slice(t.as_ref()),
         })
         .collect::<Vec<TWrapper>>();
 
@@ -35,8 +36,10 @@ where
     state.end()
 }
 
-pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Bytes>, D::Error>
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
 where
+    T: TryFrom<Bytes>,
+    T::Error: fmt::Display,
     D: Deserializer<'de>,
 {
     #[derive(Deserialize)]
@@ -90,7 +93,9 @@ where
     const FIELDS: &[&str] = &["inner"];
     let vec_t = deserializer.deserialize_struct("VecT", FIELDS, VecTVisitor)?;
 
-    Ok(Vec::from_iter(
-        vec_t.inner.into_iter().map(|wrap_t| wrap_t.inner),
-    ))
+    vec_t
+        .inner
+        .into_iter()
+        .map(|wrap_t| T::try_from(wrap_t.inner).map_err(de::Error::custom))
+        .collect()
 }
@@ -1,23 +1,41 @@
+/// The state machine driven synchronously by the inputs.
+pub mod pure;
 ///
 pub mod smr_types;
 ///
 mod state_machine;
+/// Conformance checks of the state machine against abstract traces.
+#[cfg(feature = "trace_check")]
+pub mod trace;
 
 use std::pin::Pin;
+#[cfg(feature = "runtime")]
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+#[cfg(feature = "runtime")]
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::channel::mpsc::UnboundedReceiver;
 use futures::stream::{FusedStream, Stream, StreamExt};
+#[cfg(feature = "runtime")]
 use log::error;
 
-use crate::smr::smr_types::{
-    SMREvent, SMRStatus, SMRTrigger, TriggerSource, TriggerType,
-};
+#[cfg(feature = "runtime")]
+use crate::replay::TraceRecorder;
+#[cfg(feature = "runtime")]
+use crate::runtime::Runtime;
+use crate::smr::smr_types::SMREvent;
+#[cfg(feature = "runtime")]
+use crate::smr::smr_types::{SMRStatus, SMRTrigger, TriggerSource, TriggerType};
+#[cfg(feature = "runtime")]
 use crate::smr::state_machine::StateMachine;
+#[cfg(feature = "runtime")]
 use crate::types::Hash;
+#[cfg(feature = "runtime")]
 use crate::{error::ConsensusError, ConsensusResult, INIT_ROUND};
 
 ///
+#[cfg(feature = "runtime")]
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub struct SMR {
@@ -25,6 +43,7 @@ pub struct SMR {
     state_machine: StateMachine,
 }
 
+#[cfg(feature = "runtime")]
 impl SMR {
     pub fn new() -> (Self, Event, Event) {
         let (tx, rx) = unbounded();
@@ -40,14 +59,20 @@ impl SMR {
     }
 
     /// Take the SMR handler and this function will be called only once.
-    pub fn take_smr(&mut self) -> SMRHandler {
-        assert!(self.smr_handler.is_some());
-        self.smr_handler.take().unwrap()
+    pub fn take_smr(&mut self) -> ConsensusResult<SMRHandler> {
+        self.smr_handler
+            .take()
+            .ok_or_else(|| ConsensusError::invariant("SMR handler taken twice"))
     }
 
-    /// Run SMR module in tokio environment.
-    pub fn run(mut self) {
-        tokio::spawn(async move {
+    /// Record the inputs and the outputs of the state machine by the recorder.
+    pub fn set_recorder(&mut self, recorder: Arc<TraceRecorder>) {
+        self.state_machine.set_recorder(recorder);
+    }
+
+    /// Run SMR module on the runtime.
+    pub fn run(mut self, runtime: &dyn Runtime) {
+        runtime.spawn(Box::pin(async move {
             loop {
                 let res = self.state_machine.next().await;
                 if let Some(Err(err)) = res {
@@ -56,16 +81,18 @@ impl SMR {
                     break;
                 }
             }
-        });
+        }));
     }
 }
 
 ///
+#[cfg(feature = "runtime")]
 #[derive(Clone, Debug)]
 pub struct SMRHandler {
     tx: UnboundedSender<SMRTrigger>,
 }
 
+#[cfg(feature = "runtime")]
 impl SMRHandler {
     /// Create a new SMR.
     pub fn new(sender: UnboundedSender<SMRTrigger>) -> Self {
@@ -0,0 +1,663 @@
+use std::time::Duration;
+
+use futures::channel::mpsc::unbounded;
+use futures::{FutureExt, StreamExt};
+
+use crate::error::ConsensusError;
+use crate::smr::smr_types::{
+    Lock, SMREvent, SMRStatus, SMRTrigger, Step, TriggerSource, TriggerType,
+};
+use crate::smr::state_machine::StateMachine;
+use crate::smr::Event;
+use crate::types::Hash;
+use crate::wal::SMRBase;
+use crate::{ConsensusResult, DurationConfig, INIT_ROUND};
+
+/// The state machine of the protocol without the channels and the tasks of the engine. Each
+/// input is handled synchronously and returns the outputs that it causes, so the same inputs
+/// always get the same outputs. It can be driven by property based tests, model checkers and
+/// the embedders that run their own event loop, where the embedder plays the roles of the
+/// state and the timer: it turns the messages and the timeouts into inputs, and acts on the
+/// outputs, such as to vote or to set the timer of the next step.
+#[derive(Debug)]
+pub struct Smr {
+    state_machine: StateMachine,
+    // The state and the timer receive the same events, except the timer events after a wal.
+    // The outputs are taken from the timer, and the events of the state are dropped.
+    state_events: Event,
+    timer_events: Event,
+}
+
+impl Default for Smr {
+    fn default() -> Self {
+        Smr::new()
+    }
+}
+
+impl Smr {
+    /// Create a state machine of height zero, which starts by a `SmrInput::NewHeight`.
+    pub fn new() -> Self {
+        let (_, trigger) = unbounded();
+        let (state_machine, state_events, timer_events) = StateMachine::new(trigger);
+        Smr {
+            state_machine,
+            state_events,
+            timer_events,
+        }
+    }
+
+    /// Handle an input and return the outputs in order. An error means the input is rejected,
+    /// which the engine logs and ignores.
+    pub fn step(&mut self, input: SmrInput) -> ConsensusResult<Vec<SmrOutput>> {
+        let (outputs, res) = self.drive(input);
+        res.map(|_| outputs)
+    }
+
+    /// Handle an input and return the outputs in order, including the ones thrown before the
+    /// input is rejected.
+    pub(crate) fn drive(
+        &mut self,
+        input: SmrInput,
+    ) -> (Vec<SmrOutput>, ConsensusResult<()>) {
+        let res = self
+            .state_machine
+            .handle_trigger(input.into_trigger())
+            .unwrap_or(Ok(()));
+
+        while let Some(Some(_)) = self.state_events.next().now_or_never() {}
+        let mut outputs = Vec::new();
+        while let Some(Some(event)) = self.timer_events.next().now_or_never() {
+            outputs.push(SmrOutput::from(event));
+        }
+        (outputs, res)
+    }
+
+    /// The current state.
+    pub fn state(&self) -> SmrState {
+        self.state_machine.smr_state()
+    }
+}
+
+/// The inputs of the state machine, which the engine makes from the messages of the state
+/// and the timeouts of the timer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SmrInput {
+    /// Goto a new height by its rich status.
+    NewHeight {
+        /// The new height.
+        height: u64,
+        /// The new height interval.
+        interval: Option<Duration>,
+        /// The new timeout configuration.
+        config: Option<DurationConfig>,
+    },
+    /// A checked proposal of the round.
+    Proposal {
+        /// Height of the proposal.
+        height: u64,
+        /// Round of the proposal.
+        round: u64,
+        /// Block hash of the proposal.
+        hash: Hash,
+        /// Lock round of the proposal.
+        lock_round: Option<u64>,
+    },
+    /// A prevote QC.
+    PrevoteQC {
+        /// Height of the QC.
+        height: u64,
+        /// Round of the QC.
+        round: u64,
+        /// Block hash of the QC, which is empty for a QC of nil.
+        hash: Hash,
+    },
+    /// A precommit QC.
+    PrecommitQC {
+        /// Height of the QC.
+        height: u64,
+        /// Round of the QC.
+        round: u64,
+        /// Block hash of the QC, which is empty for a QC of nil.
+        hash: Hash,
+    },
+    /// The timeout of the propose step.
+    ProposeTimeout {
+        /// Height of the timer.
+        height: u64,
+        /// Round of the timer.
+        round: u64,
+    },
+    /// The timeout of the prevote step.
+    PrevoteTimeout {
+        /// Height of the timer.
+        height: u64,
+        /// Round of the timer.
+        round: u64,
+    },
+    /// The timeout of the precommit step.
+    PrecommitTimeout {
+        /// Height of the timer.
+        height: u64,
+        /// Round of the timer.
+        round: u64,
+    },
+    /// The timeout of the brake step.
+    BrakeTimeout {
+        /// Height of the timer.
+        height: u64,
+        /// Round of the timer.
+        round: u64,
+    },
+    /// A choke QC of the previous round.
+    ContinueRound {
+        /// Height of the choke QC.
+        height: u64,
+        /// The round to continue, which is the round of the choke QC plus one.
+        round: u64,
+    },
+    /// Update the timeout configuration.
+    UpdateConfig(DurationConfig),
+    /// Stop the state machine.
+    Stop,
+    /// Recover the state machine from the wal, and set the timer of the recovered step.
+    Wal {
+        /// Height of the wal.
+        height: u64,
+        /// Round of the wal.
+        round: u64,
+        /// Step of the wal, which is not the commit step.
+        step: Step,
+        /// Lock round and hash of the wal.
+        lock: Option<(u64, Hash)>,
+    },
+}
+
+impl TryFrom<SMRTrigger> for SmrInput {
+    type Error = ConsensusError;
+
+    fn try_from(trigger: SMRTrigger) -> Result<Self, Self::Error> {
+        let SMRTrigger {
+            trigger_type,
+            source,
+            hash,
+            lock_round,
+            round,
+            height,
+            wal_info,
+        } = trigger;
+        let by_timer = source == TriggerSource::Timer;
+        let input = match trigger_type {
+            TriggerType::NewHeight(status) => SmrInput::NewHeight {
+                height: status.height,
+                interval: status.new_interval,
+                config: status.new_config,
+            },
+            TriggerType::Proposal if by_timer => {
+                SmrInput::ProposeTimeout { height, round }
+            }
+            TriggerType::Proposal => SmrInput::Proposal {
+                height,
+                round,
+                hash,
+                lock_round,
+            },
+            TriggerType::PrevoteQC if by_timer => {
+                SmrInput::PrevoteTimeout { height, round }
+            }
+            TriggerType::PrevoteQC => SmrInput::PrevoteQC {
+                height,
+                round,
+                hash,
+            },
+            TriggerType::PrecommitQC if by_timer => {
+                SmrInput::PrecommitTimeout { height, round }
+            }
+            TriggerType::PrecommitQC => SmrInput::PrecommitQC {
+                height,
+                round,
+                hash,
+            },
+            TriggerType::BrakeTimeout => SmrInput::BrakeTimeout { height, round },
+            TriggerType::ContinueRound => SmrInput::ContinueRound { height, round },
+            TriggerType::WalInfo => {
+                let info = wal_info.ok_or_else(|| {
+                    ConsensusError::StateErr("wal trigger without wal info".to_string())
+                })?;
+                SmrInput::Wal {
+                    height: info.height,
+                    round: info.round,
+                    step: info.step,
+                    lock: info.polc.map(|lock| (lock.round, lock.hash)),
+                }
+            }
+            TriggerType::UpdateConfig(config) => SmrInput::UpdateConfig(config),
+            TriggerType::Stop => SmrInput::Stop,
+        };
+        Ok(input)
+    }
+}
+
+impl SmrInput {
+    pub(crate) fn into_trigger(self) -> SMRTrigger {
+        let (trigger_type, source, hash, lock_round, round, height) = match self {
+            SmrInput::NewHeight {
+                height,
+                interval,
+                config,
+            } => (
+                TriggerType::NewHeight(SMRStatus {
+                    height,
+                    new_interval: interval,
+                    new_config: config,
+                }),
+                TriggerSource::State,
+                Hash::new(),
+                None,
+                INIT_ROUND,
+                height,
+            ),
+            SmrInput::Proposal {
+                height,
+                round,
+                hash,
+                lock_round,
+            } => (
+                TriggerType::Proposal,
+                TriggerSource::State,
+                hash,
+                lock_round,
+                round,
+                height,
+            ),
+            SmrInput::PrevoteQC {
+                height,
+                round,
+                hash,
+            } => (
+                TriggerType::PrevoteQC,
+                TriggerSource::State,
+                hash,
+                Some(round),
+                round,
+                height,
+            ),
+            SmrInput::PrecommitQC {
+                height,
+                round,
+                hash,
+            } => (
+                TriggerType::PrecommitQC,
+                TriggerSource::State,
+                hash,
+                Some(round),
+                round,
+                height,
+            ),
+            SmrInput::ProposeTimeout { height, round } => {
+                timeout(TriggerType::Proposal, height, round)
+            }
+            SmrInput::PrevoteTimeout { height, round } => {
+                timeout(TriggerType::PrevoteQC, height, round)
+            }
+            SmrInput::PrecommitTimeout { height, round } => {
+                timeout(TriggerType::PrecommitQC, height, round)
+            }
+            SmrInput::BrakeTimeout { height, round } => {
+                timeout(TriggerType::BrakeTimeout, height, round)
+            }
+            SmrInput::ContinueRound { height, round } => (
+                TriggerType::ContinueRound,
+                TriggerSource::State,
+                Hash::new(),
+                None,
+                round,
+                height,
+            ),
+            SmrInput::UpdateConfig(config) => (
+                TriggerType::UpdateConfig(config),
+                TriggerSource::State,
+                Hash::new(),
+                None,
+                INIT_ROUND,
+                0,
+            ),
+            SmrInput::Stop => (
+                TriggerType::Stop,
+                TriggerSource::State,
+                Hash::new(),
+                None,
+                INIT_ROUND,
+                0,
+            ),
+            SmrInput::Wal {
+                height,
+                round,
+                step,
+                lock,
+            } => {
+                return SMRTrigger {
+                    trigger_type: TriggerType::WalInfo,
+                    source: TriggerSource::State,
+                    hash: Hash::new(),
+                    lock_round: None,
+                    round,
+                    height,
+                    wal_info: Some(SMRBase {
+                        height,
+                        round,
+                        step,
+                        polc: lock.map(|(round, hash)| Lock { round, hash }),
+                    }),
+                }
+            }
+        };
+
+        SMRTrigger {
+            trigger_type,
+            source,
+            hash,
+            lock_round,
+            round,
+            height,
+            wal_info: None,
+        }
+    }
+}
+
+type TriggerParts = (TriggerType, TriggerSource, Hash, Option<u64>, u64, u64);
+
+fn timeout(trigger_type: TriggerType, height: u64, round: u64) -> TriggerParts {
+    (
+        trigger_type,
+        TriggerSource::Timer,
+        Hash::new(),
+        None,
+        round,
+        height,
+    )
+}
+
+/// The outputs of the state machine, which the engine hands to the state and the timer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SmrOutput {
+    /// Goto a new round, and set the timer of the propose step.
+    NewRound {
+        /// Height of the round.
+        height: u64,
+        /// The new round.
+        round: u64,
+        /// Lock round of the state machine.
+        lock_round: Option<u64>,
+        /// Lock hash of the state machine.
+        lock_proposal: Option<Hash>,
+        /// The new height interval of a new height.
+        interval: Option<Duration>,
+        /// The new timeout configuration of a new height.
+        config: Option<DurationConfig>,
+    },
+    /// Prevote a block hash, and set the timer of the prevote step.
+    Prevote {
+        /// Height of the vote.
+        height: u64,
+        /// Round of the vote.
+        round: u64,
+        /// Block hash of the vote, which is empty for nil.
+        hash: Hash,
+        /// Lock round of the state machine.
+        lock_round: Option<u64>,
+    },
+    /// Precommit a block hash, and set the timer of the precommit step.
+    Precommit {
+        /// Height of the vote.
+        height: u64,
+        /// Round of the vote.
+        round: u64,
+        /// Block hash of the vote, which is empty for nil.
+        hash: Hash,
+        /// Lock round of the state machine.
+        lock_round: Option<u64>,
+    },
+    /// Commit a block hash.
+    Commit {
+        /// The committed block hash.
+        hash: Hash,
+    },
+    /// Broadcast a choke, and set the timer of the brake step.
+    Brake {
+        /// Height of the choke.
+        height: u64,
+        /// Round of the choke.
+        round: u64,
+        /// Lock round of the state machine.
+        lock_round: Option<u64>,
+    },
+    /// Update the timeout configuration of the timer.
+    UpdateConfig(DurationConfig),
+    /// Stop the state and the timer.
+    Stop,
+}
+
+impl From<SMREvent> for SmrOutput {
+    fn from(event: SMREvent) -> Self {
+        match event {
+            SMREvent::NewRoundInfo {
+                height,
+                round,
+                lock_round,
+                lock_proposal,
+                new_interval,
+                new_config,
+                ..
+            } => SmrOutput::NewRound {
+                height,
+                round,
+                lock_round,
+                lock_proposal,
+                interval: new_interval,
+                config: new_config,
+            },
+            SMREvent::PrevoteVote {
+                height,
+                round,
+                block_hash,
+                lock_round,
+            } => SmrOutput::Prevote {
+                height,
+                round,
+                hash: block_hash,
+                lock_round,
+            },
+            SMREvent::PrecommitVote {
+                height,
+                round,
+                block_hash,
+                lock_round,
+            } => SmrOutput::Precommit {
+                height,
+                round,
+                hash: block_hash,
+                lock_round,
+            },
+            SMREvent::Commit(hash) => SmrOutput::Commit { hash },
+            SMREvent::Brake {
+                height,
+                round,
+                lock_round,
+            } => SmrOutput::Brake {
+                height,
+                round,
+                lock_round,
+            },
+            SMREvent::UpdateConfig(config) => SmrOutput::UpdateConfig(config),
+            SMREvent::Stop => SmrOutput::Stop,
+        }
+    }
+}
+
+/// A snapshot of the state machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SmrState {
+    /// Height of the state machine.
+    pub height: u64,
+    /// Round of the state machine.
+    pub round: u64,
+    /// Step of the state machine.
+    pub step: Step,
+    /// Lock round and hash of the state machine.
+    pub lock: Option<(u64, Hash)>,
+    /// The proposal hash of the round.
+    pub block_hash: Hash,
+}
+
+#[cfg(test)]
+mod test {
+    use rand_core::{RngCore, SeedableRng};
+    use rand_pcg::Pcg64;
+
+    use super::{Smr, SmrInput, SmrOutput};
+    use crate::smr::smr_types::Step;
+    use crate::types::Hash;
+
+    fn gen_hash(byte: u8) -> Hash {
+        Hash::from(vec![byte; 32])
+    }
+
+    fn new_height(height: u64) -> SmrInput {
+        SmrInput::NewHeight {
+            height,
+            interval: None,
+            config: None,
+        }
+    }
+
+    #[test]
+    fn test_commit_a_height() {
+        let mut smr = Smr::new();
+        let hash = gen_hash(1);
+        assert_eq!(
+            smr.step(new_height(1)).unwrap(),
+            vec![SmrOutput::NewRound {
+                height: 1,
+                round: 0,
+                lock_round: None,
+                lock_proposal: None,
+                interval: None,
+                config: None,
+            }]
+        );
+
+        let outputs = smr
+            .step(SmrInput::Proposal {
+                height: 1,
+                round: 0,
+                hash: hash.clone(),
+                lock_round: None,
+            })
+            .unwrap();
+        assert_eq!(
+            outputs,
+            vec![SmrOutput::Prevote {
+                height: 1,
+                round: 0,
+                hash: hash.clone(),
+                lock_round: None,
+            }]
+        );
+
+        let outputs = smr
+            .step(SmrInput::PrevoteQC {
+                height: 1,
+                round: 0,
+                hash: hash.clone(),
+            })
+            .unwrap();
+        assert_eq!(
+            outputs,
+            vec![SmrOutput::Precommit {
+                height: 1,
+                round: 0,
+                hash: hash.clone(),
+                lock_round: Some(0),
+            }]
+        );
+        assert_eq!(smr.state().lock, Some((0, hash.clone())));
+
+        let outputs = smr
+            .step(SmrInput::PrecommitQC {
+                height: 1,
+                round: 0,
+                hash: hash.clone(),
+            })
+            .unwrap();
+        assert_eq!(outputs, vec![SmrOutput::Commit { hash }]);
+        assert_eq!(smr.state().step, Step::Commit);
+
+        // A delayed status is rejected without outputs.
+        assert!(smr.step(new_height(1)).is_err());
+        assert_eq!(smr.step(SmrInput::Stop).unwrap(), vec![SmrOutput::Stop]);
+    }
+
+    fn random_input(rng: &mut Pcg64) -> SmrInput {
+        let height = 1 + rng.next_u64() % 2;
+        let round = rng.next_u64() % 3;
+        let hash = match rng.next_u64() % 3 {
+            0 => Hash::new(),
+            n => gen_hash(n as u8),
+        };
+        match rng.next_u64() % 8 {
+            0 => new_height(height),
+            1 => SmrInput::Proposal {
+                height,
+                round,
+                hash,
+                lock_round: None,
+            },
+            2 => SmrInput::PrevoteQC {
+                height,
+                round,
+                hash,
+            },
+            3 => SmrInput::PrecommitQC {
+                height,
+                round,
+                hash,
+            },
+            4 => SmrInput::ProposeTimeout { height, round },
+            5 => SmrInput::PrevoteTimeout { height, round },
+            6 => SmrInput::PrecommitTimeout { height, round },
+            _ => SmrInput::ContinueRound { height, round },
+        }
+    }
+
+    #[test]
+    fn test_random_steps() {
+        for seed in 0..20 {
+            let mut rng = Pcg64::seed_from_u64(seed);
+            let (mut smr_1, mut smr_2) = (Smr::new(), Smr::new());
+            smr_1.step(new_height(1)).unwrap();
+            smr_2.step(new_height(1)).unwrap();
+
+            for _ in 0..50 {
+                let input = random_input(&mut rng);
+                let height = smr_1.state().height;
+                let res_1 = smr_1.step(input.clone()).map_err(|e| e.to_string());
+                let res_2 = smr_2.step(input.clone()).map_err(|e| e.to_string());
+                assert_eq!(res_1, res_2);
+                assert_eq!(smr_1.state(), smr_2.state());
+
+                // The height never goes back, and only a precommit QC commits its hash.
+                assert!(smr_1.state().height >= height);
+                for output in res_1.unwrap_or_default() {
+                    if let SmrOutput::Commit { hash } = output {
+                        match &input {
+                            SmrInput::PrecommitQC { hash: qc_hash, .. } => {
+                                assert_eq!(*qc_hash, hash)
+                            }
+                            _ => panic!("Commit by {:?}", input),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
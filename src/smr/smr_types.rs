@@ -1,7 +1,10 @@
+use std::time::Duration;
+
 use derive_more::Display;
 use hummer::coding::hex_encode;
 use serde::{Deserialize, Serialize};
 
+use crate::error::ConsensusError;
 use crate::types::{Hash, ViewChangeReason};
 use crate::wal::SMRBase;
 use crate::DurationConfig;
@@ -77,15 +80,17 @@ impl From<Step> for u8 {
     }
 }
 
-impl From<u8> for Step {
-    fn from(s: u8) -> Self {
+impl TryFrom<u8> for Step {
+    type Error = ConsensusError;
+
+    fn try_from(s: u8) -> Result<Self, Self::Error> {
         match s {
-            0 => Step::Propose,
-            1 => Step::Prevote,
-            2 => Step::Precommit,
-            3 => Step::Brake,
-            4 => Step::Commit,
-            _ => panic!("Invalid step!"),
+            0 => Ok(Step::Propose),
+            1 => Ok(Step::Prevote),
+            2 => Ok(Step::Precommit),
+            3 => Ok(Step::Brake),
+            4 => Ok(Step::Commit),
+            _ => Err(ConsensusError::DecodeErr(format!("Invalid step {}", s))),
         }
     }
 }
@@ -145,7 +150,7 @@ pub enum SMREvent {
         lock_round: Option<u64>,
         lock_proposal: Option<Hash>,
         from_where: FromWhere,
-        new_interval: Option<u64>,
+        new_interval: Option<Duration>,
         new_config: Option<DurationConfig>,
     },
 
@@ -203,6 +208,12 @@ pub enum SMREvent {
         lock_round: Option<u64>,
     },
 
+    /// Update config event,
+    /// for state: do nothing,
+    /// for timer: update the timeout configuration.
+    #[display(fmt = "Update config event {:?}", _0)]
+    UpdateConfig(DurationConfig),
+
     /// Stop event,
     /// for state: stop process,
     /// for timer: stop process.
@@ -234,6 +245,9 @@ pub enum TriggerType {
     /// Continue new round trigger.
     #[display(fmt = "Continue Round")]
     ContinueRound,
+    /// Update the timeout configuration.
+    #[display(fmt = "Update Config")]
+    UpdateConfig(DurationConfig),
     /// Stop process.
     #[display(fmt = "Stop Process")]
     Stop,
@@ -250,26 +264,34 @@ pub enum TriggerSource {
     Timer = 1,
 }
 
-impl From<TriggerType> for u8 {
-    fn from(t: TriggerType) -> u8 {
+impl TryFrom<TriggerType> for u8 {
+    type Error = ConsensusError;
+
+    fn try_from(t: TriggerType) -> Result<u8, Self::Error> {
         match t {
-            TriggerType::Proposal => 0u8,
-            TriggerType::PrevoteQC => 1u8,
-            TriggerType::PrecommitQC => 2u8,
-            _ => unreachable!(),
+            TriggerType::Proposal => Ok(0u8),
+            TriggerType::PrevoteQC => Ok(1u8),
+            TriggerType::PrecommitQC => Ok(2u8),
+            t => Err(ConsensusError::StateErr(format!(
+                "No code of trigger type {}",
+                t
+            ))),
         }
     }
 }
 
-impl From<u8> for TriggerType {
-    /// It should not occur that call `from(3u8)`.
-    fn from(s: u8) -> Self {
+impl TryFrom<u8> for TriggerType {
+    type Error = ConsensusError;
+
+    fn try_from(s: u8) -> Result<Self, Self::Error> {
         match s {
-            0 => TriggerType::Proposal,
-            1 => TriggerType::PrevoteQC,
-            2 => TriggerType::PrecommitQC,
-            3 => unreachable!(),
-            _ => panic!("Invalid trigger type!"),
+            0 => Ok(TriggerType::Proposal),
+            1 => Ok(TriggerType::PrevoteQC),
+            2 => Ok(TriggerType::PrecommitQC),
+            _ => Err(ConsensusError::DecodeErr(format!(
+                "Invalid trigger type {}",
+                s
+            ))),
         }
     }
 }
@@ -327,7 +349,7 @@ pub struct SMRStatus {
     /// New height.
     pub height: u64,
     /// New height interval.
-    pub new_interval: Option<u64>,
+    pub new_interval: Option<Duration>,
     /// New timeout configuration.
     pub new_config: Option<DurationConfig>,
 }
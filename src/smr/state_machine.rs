@@ -1,15 +1,20 @@
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use derive_more::Display;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::stream::Stream;
 use hummer::coding::hex_encode;
-use log::{debug, info};
+use log::{debug, info, warn};
 
+use crate::replay::{TraceEntry, TraceRecorder};
+use crate::smr::pure::{SmrInput, SmrOutput, SmrState};
 use crate::smr::smr_types::{
     FromWhere, Lock, SMREvent, SMRStatus, SMRTrigger, Step, TriggerSource, TriggerType,
 };
+#[cfg(feature = "trace_check")]
+use crate::smr::trace::{from_hash, TraceLock, TraceState};
 use crate::wal::SMRBase;
 use crate::{error::ConsensusError, smr::Event, types::Hash};
 use crate::{ConsensusResult, INIT_HEIGHT, INIT_ROUND};
@@ -27,6 +32,7 @@ pub struct StateMachine {
 
     event:   (UnboundedSender<SMREvent>, UnboundedSender<SMREvent>),
     trigger: UnboundedReceiver<SMRTrigger>,
+    recorder: Option<Arc<TraceRecorder>>,
 }
 
 impl Stream for StateMachine {
@@ -39,50 +45,13 @@ impl Stream for StateMachine {
         match Stream::poll_next(Pin::new(&mut self.trigger), cx) {
             Poll::Pending => Poll::Pending,
 
-            Poll::Ready(msg) => {
-                if msg.is_none() {
-                    return Poll::Ready(Some(Err(ConsensusError::TriggerSMRErr(
-                        "Channel dropped".to_string(),
-                    ))));
-                }
+            Poll::Ready(None) => Poll::Ready(Some(Err(ConsensusError::TriggerSMRErr(
+                "Channel dropped".to_string(),
+            )))),
 
-                let msg = msg.unwrap();
-                let trigger_type = msg.trigger_type.clone();
-                let res = match trigger_type {
-                    TriggerType::NewHeight(status) => {
-                        Some(self.handle_new_height(status, msg.source))
-                    }
-                    TriggerType::Proposal => Some(self.handle_proposal(
-                        msg.hash,
-                        msg.round,
-                        msg.lock_round,
-                        msg.source,
-                        msg.height,
-                    )),
-                    TriggerType::PrevoteQC => Some(
-                        self.handle_prevote(msg.hash, msg.round, msg.source, msg.height),
-                    ),
-                    TriggerType::PrecommitQC => {
-                        Some(self.handle_precommit(
-                            msg.hash, msg.round, msg.source, msg.height,
-                        ))
-                    }
-                    TriggerType::BrakeTimeout => {
-                        assert!(msg.source == TriggerSource::Timer);
-                        Some(self.handle_brake_timeout(msg.height, msg.round))
-                    }
-                    TriggerType::ContinueRound => {
-                        assert!(msg.source == TriggerSource::State);
-                        Some(self.handle_continue_round(msg.height, msg.round))
-                    }
-                    TriggerType::WalInfo => Some(self.handle_wal(msg.wal_info.unwrap())),
-                    TriggerType::Stop => {
-                        let _ = self.throw_event(SMREvent::Stop);
-                        None
-                    }
-                };
-
-                Poll::Ready(res)
+            Poll::Ready(Some(msg)) => {
+                self.record_input(&msg);
+                Poll::Ready(self.handle_trigger(msg))
             }
         }
     }
@@ -102,11 +71,80 @@ impl StateMachine {
             lock: None,
             trigger: trigger_receiver,
             event: (tx_state, tx_timer),
+            recorder: None,
         };
 
         (state_machine, Event::new(rx_state), Event::new(rx_timer))
     }
 
+    /// Record the inputs and the outputs by the recorder.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn set_recorder(&mut self, recorder: Arc<TraceRecorder>) {
+        self.recorder = Some(recorder);
+    }
+
+    fn record_input(&self, msg: &SMRTrigger) {
+        if let Some(recorder) = self.recorder.as_ref() {
+            match SmrInput::try_from(msg.clone()) {
+                Ok(input) => recorder.record(TraceEntry::Input(input)),
+                Err(e) => warn!("Mlm: SMR skip recording {} {}", msg, e),
+            }
+        }
+    }
+
+    /// Record an output, which is an event that the timer receives.
+    fn record_output(&self, event: &SMREvent) {
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.record(TraceEntry::Output(SmrOutput::from(event.clone())));
+        }
+    }
+
+    /// Handle a trigger, return none if the state machine stops.
+    pub(crate) fn handle_trigger(
+        &mut self,
+        msg: SMRTrigger,
+    ) -> Option<ConsensusResult<()>> {
+        let trigger_type = msg.trigger_type.clone();
+        match trigger_type {
+            TriggerType::NewHeight(status) => {
+                Some(self.handle_new_height(status, msg.source))
+            }
+            TriggerType::Proposal => Some(self.handle_proposal(
+                msg.hash,
+                msg.round,
+                msg.lock_round,
+                msg.source,
+                msg.height,
+            )),
+            TriggerType::PrevoteQC => {
+                Some(self.handle_prevote(msg.hash, msg.round, msg.source, msg.height))
+            }
+            TriggerType::PrecommitQC => {
+                Some(self.handle_precommit(msg.hash, msg.round, msg.source, msg.height))
+            }
+            TriggerType::BrakeTimeout => Some(
+                check_source(&msg, TriggerSource::Timer)
+                    .and_then(|_| self.handle_brake_timeout(msg.height, msg.round)),
+            ),
+            TriggerType::ContinueRound => Some(
+                check_source(&msg, TriggerSource::State)
+                    .and_then(|_| self.handle_continue_round(msg.height, msg.round)),
+            ),
+            TriggerType::WalInfo => Some(match msg.wal_info {
+                Some(info) => self.handle_wal(info),
+                None => Err(ConsensusError::invariant("wal trigger without wal info")),
+            }),
+            TriggerType::UpdateConfig(config) => Some(
+                check_source(&msg, TriggerSource::State)
+                    .and_then(|_| self.throw_event(SMREvent::UpdateConfig(config))),
+            ),
+            TriggerType::Stop => {
+                let _ = self.throw_event(SMREvent::Stop);
+                None
+            }
+        }
+    }
+
     fn handle_brake_timeout(&mut self, height: u64, round: u64) -> ConsensusResult<()> {
         if height != self.height || round != self.round {
             Ok(())
@@ -170,11 +208,11 @@ impl StateMachine {
 
         let height = status.height;
         if source != TriggerSource::State {
-            return Err(ConsensusError::Other(
+            return Err(ConsensusError::StateErr(
                 "Rich status source error".to_string(),
             ));
         } else if height <= self.height {
-            return Err(ConsensusError::Other("Delayed status".to_string()));
+            return Err(ConsensusError::StateErr("Delayed status".to_string()));
         }
 
         self.goto_new_height(height);
@@ -441,6 +479,7 @@ impl StateMachine {
 
     fn throw_event(&mut self, event: SMREvent) -> ConsensusResult<()> {
         info!("Mlm: SMR throw {} event", event);
+        self.record_output(&event);
         self.event.0.unbounded_send(event.clone()).map_err(|err| {
             ConsensusError::ThrowEventErr(format!(
                 "event: {}, error: {:?}",
@@ -459,6 +498,7 @@ impl StateMachine {
     }
 
     fn throw_timer_event(&mut self, event: SMREvent) -> ConsensusResult<()> {
+        self.record_output(&event);
         self.event.1.unbounded_send(event.clone()).map_err(|err| {
             ConsensusError::ThrowEventErr(format!(
                 "event: {}, error: {:?}",
@@ -520,11 +560,42 @@ impl StateMachine {
                 round: self.round,
                 lock_round,
             },
-            _ => unreachable!(),
+            Step::Commit => {
+                return Err(ConsensusError::invariant("set timer after wal of commit"))
+            }
         };
         self.throw_timer_event(event)
     }
 
+    /// A snapshot of the state machine.
+    pub(crate) fn smr_state(&self) -> SmrState {
+        SmrState {
+            height: self.height,
+            round: self.round,
+            step: self.step.clone(),
+            lock: self
+                .lock
+                .as_ref()
+                .map(|lock| (lock.round, lock.hash.clone())),
+            block_hash: self.block_hash.clone(),
+        }
+    }
+
+    /// The abstract state of the state machine which is checked against the traces.
+    #[cfg(feature = "trace_check")]
+    pub(crate) fn trace_state(&self) -> TraceState {
+        TraceState {
+            height: self.height,
+            round: self.round,
+            step: self.step.clone(),
+            lock: self.lock.as_ref().map(|lock| TraceLock {
+                round: lock.round,
+                hash: from_hash(&lock.hash),
+            }),
+            block_hash: from_hash(&self.block_hash),
+        }
+    }
+
     /// Goto the given step.
     #[inline]
     fn goto_step(&mut self, step: Step) {
@@ -594,6 +665,17 @@ impl StateMachine {
     }
 }
 
+/// Check that the trigger is from the expected source.
+fn check_source(trigger: &SMRTrigger, source: TriggerSource) -> ConsensusResult<()> {
+    if trigger.source != source {
+        return Err(ConsensusError::invariant(format!(
+            "{:?} trigger from {:?}",
+            trigger.trigger_type, trigger.source
+        )));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use bytes::Bytes;
@@ -605,4 +687,39 @@ mod test {
         let right: Option<u64> = None;
         assert!(!left.is_empty().bitxor(&right.is_none()));
     }
+
+    #[cfg(not(feature = "strict-panic"))]
+    #[test]
+    fn test_invariant_error() {
+        use futures::channel::mpsc::unbounded;
+
+        use super::StateMachine;
+        use crate::error::{ConsensusError, ErrorSeverity};
+        use crate::smr::smr_types::{SMRTrigger, TriggerSource, TriggerType};
+        use crate::types::Hash;
+
+        let (_tx, rx) = unbounded();
+        let (mut smr, _evt_state, _evt_timer) = StateMachine::new(rx);
+        let trigger = |trigger_type, source| SMRTrigger {
+            trigger_type,
+            source,
+            hash: Hash::new(),
+            lock_round: None,
+            round: 0,
+            height: 0,
+            wal_info: None,
+        };
+
+        // The triggers from the wrong source or without the wal info are fatal errors instead
+        // of panics.
+        for msg in [
+            trigger(TriggerType::BrakeTimeout, TriggerSource::State),
+            trigger(TriggerType::ContinueRound, TriggerSource::Timer),
+            trigger(TriggerType::WalInfo, TriggerSource::State),
+        ] {
+            let err = smr.handle_trigger(msg).unwrap().unwrap_err();
+            assert!(matches!(err, ConsensusError::InvariantErr(_)));
+            assert_eq!(err.severity(), ErrorSeverity::Fatal);
+        }
+    }
 }
@@ -0,0 +1,497 @@
+use std::fs;
+use std::path::Path;
+
+use derive_more::Display;
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::{FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::smr::smr_types::{
+    SMREvent, SMRStatus, SMRTrigger, Step, TriggerSource, TriggerType,
+};
+use crate::smr::state_machine::StateMachine;
+use crate::smr::Event;
+use crate::types::Hash;
+
+/// An abstract trace of the state machine, such as a counterexample or a random walk exported
+/// from a TLA+ model of the protocol. Each step is an action applied to the state machine and the
+/// abstract state expected after it. The block hashes are the abstract values of the model, which
+/// are mapped to their UTF-8 bytes, and the empty string is the empty hash.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Trace {
+    /// Name of the trace.
+    #[serde(default)]
+    pub name: String,
+    /// Steps of the trace.
+    pub steps: Vec<TraceStep>,
+}
+
+/// A step of a trace.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The action applied to the state machine.
+    pub action: TraceAction,
+    /// The expected state after the action.
+    pub state: TraceState,
+    /// The expected events thrown by the action. The events are not checked if it is none.
+    #[serde(default)]
+    pub events: Option<Vec<TraceEvent>>,
+    /// Whether the state machine is expected to reject the action with an error.
+    #[serde(default)]
+    pub rejected: bool,
+}
+
+/// Actions of the model, each of which is a trigger of the state machine.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum TraceAction {
+    /// A rich status of the given height.
+    NewHeight {
+        /// Height of the status.
+        height: u64,
+    },
+    /// A proposal from state.
+    Proposal {
+        /// Height of the proposal.
+        height: u64,
+        /// Round of the proposal.
+        round: u64,
+        /// Block hash of the proposal.
+        hash: String,
+        /// Lock round of the proposal.
+        #[serde(default)]
+        lock_round: Option<u64>,
+    },
+    /// A propose step timeout.
+    ProposeTimeout {
+        /// Height of the timer.
+        height: u64,
+        /// Round of the timer.
+        round: u64,
+    },
+    /// A prevote QC from state.
+    PrevoteQC {
+        /// Height of the QC.
+        height: u64,
+        /// Round of the QC.
+        round: u64,
+        /// Block hash of the QC.
+        hash: String,
+    },
+    /// A prevote step timeout.
+    PrevoteTimeout {
+        /// Height of the timer.
+        height: u64,
+        /// Round of the timer.
+        round: u64,
+    },
+    /// A precommit QC from state.
+    PrecommitQC {
+        /// Height of the QC.
+        height: u64,
+        /// Round of the QC.
+        round: u64,
+        /// Block hash of the QC.
+        hash: String,
+    },
+    /// A precommit step timeout.
+    PrecommitTimeout {
+        /// Height of the timer.
+        height: u64,
+        /// Round of the timer.
+        round: u64,
+    },
+    /// A brake step timeout.
+    BrakeTimeout {
+        /// Height of the timer.
+        height: u64,
+        /// Round of the timer.
+        round: u64,
+    },
+    /// A choke QC of the previous round from state.
+    ContinueRound {
+        /// Height of the choke QC.
+        height: u64,
+        /// The round to continue, which is the round of the choke QC plus one.
+        round: u64,
+    },
+}
+
+/// The abstract state of the state machine.
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[display(
+    fmt = "height {}, round {}, step {:?}, lock {:?}, block hash {:?}",
+    height,
+    round,
+    step,
+    lock,
+    block_hash
+)]
+pub struct TraceState {
+    /// Height of the state machine.
+    pub height: u64,
+    /// Round of the state machine.
+    pub round: u64,
+    /// Step of the state machine.
+    pub step: Step,
+    /// Lock of the state machine.
+    #[serde(default)]
+    pub lock: Option<TraceLock>,
+    /// The proposal hash of the state machine.
+    #[serde(default)]
+    pub block_hash: String,
+}
+
+/// The abstract lock of the state machine.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TraceLock {
+    /// Lock round.
+    pub round: u64,
+    /// Lock hash.
+    pub hash: String,
+}
+
+/// The abstract events which the state machine throws to state.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum TraceEvent {
+    /// Goto a new round.
+    NewRound {
+        /// The new round.
+        round: u64,
+        /// Lock round of the state machine.
+        #[serde(default)]
+        lock_round: Option<u64>,
+    },
+    /// Prevote a block hash.
+    Prevote {
+        /// Round of the vote.
+        round: u64,
+        /// Block hash of the vote.
+        hash: String,
+    },
+    /// Precommit a block hash.
+    Precommit {
+        /// Round of the vote.
+        round: u64,
+        /// Block hash of the vote.
+        hash: String,
+    },
+    /// Commit a block hash.
+    Commit {
+        /// The committed block hash.
+        hash: String,
+    },
+    /// Broadcast a choke.
+    Brake {
+        /// Round of the choke.
+        round: u64,
+    },
+}
+
+/// A failed check of a trace.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[display(fmt = "Trace {} step {}: {}", name, step, reason)]
+pub struct TraceError {
+    /// Name of the trace.
+    pub name: String,
+    /// Index of the failed step.
+    pub step: usize,
+    /// The reason of the failure.
+    pub reason: String,
+}
+
+impl std::error::Error for TraceError {}
+
+impl Trace {
+    /// Parse a trace from a JSON string.
+    pub fn from_json(json: &str) -> Result<Self, TraceError> {
+        serde_json::from_str(json).map_err(|e| TraceError {
+            name: String::new(),
+            step: 0,
+            reason: format!("Parse trace error {:?}", e),
+        })
+    }
+
+    /// Read a trace from a JSON file. The name of the trace is the file name if it is not set.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, TraceError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let json = fs::read_to_string(path).map_err(|e| TraceError {
+            name: name.clone(),
+            step: 0,
+            reason: format!("Read trace error {:?}", e),
+        })?;
+
+        let mut trace = Trace::from_json(&json).map_err(|e| TraceError {
+            name: name.clone(),
+            ..e
+        })?;
+        if trace.name.is_empty() {
+            trace.name = name;
+        }
+        Ok(trace)
+    }
+
+    /// Replay the trace on a new state machine and check that every step produces the
+    /// expected state and events.
+    pub fn check(&self) -> Result<(), TraceError> {
+        let mut checker = TraceChecker::new();
+        for (index, step) in self.steps.iter().enumerate() {
+            checker.apply(step).map_err(|reason| TraceError {
+                name: self.name.clone(),
+                step: index,
+                reason,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Check all of the `.json` traces in the directory, and return the number of the checked
+/// traces.
+pub fn check_trace_dir<P: AsRef<Path>>(dir: P) -> Result<usize, TraceError> {
+    let dir = dir.as_ref();
+    let read_err = |e: std::io::Error| TraceError {
+        name: dir.display().to_string(),
+        step: 0,
+        reason: format!("Read trace directory error {:?}", e),
+    };
+
+    let mut paths = fs::read_dir(dir)
+        .map_err(read_err)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_err)?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+
+    for path in paths.iter() {
+        Trace::from_file(path)?.check()?;
+    }
+    Ok(paths.len())
+}
+
+struct TraceChecker {
+    state_machine: StateMachine,
+    trigger: UnboundedSender<SMRTrigger>,
+    event: Event,
+    // Keep the timer receiver alive, otherwise the state machine fails to throw events.
+    _timer: Event,
+}
+
+impl TraceChecker {
+    fn new() -> Self {
+        let (trigger, rx) = unbounded();
+        let (state_machine, event, timer) = StateMachine::new(rx);
+        TraceChecker {
+            state_machine,
+            trigger,
+            event,
+            _timer: timer,
+        }
+    }
+
+    fn apply(&mut self, step: &TraceStep) -> Result<(), String> {
+        self.trigger
+            .unbounded_send(to_trigger(&step.action))
+            .map_err(|e| format!("Trigger error {:?}", e))?;
+
+        let res = match self.state_machine.next().now_or_never() {
+            Some(Some(res)) => res,
+            _ => return Err(format!("Action {:?} is not handled", step.action)),
+        };
+        if res.is_err() != step.rejected {
+            return Err(format!(
+                "Action {:?} expect rejected {}, get result {:?}",
+                step.action, step.rejected, res
+            ));
+        }
+
+        let mut events = Vec::new();
+        while let Some(Some(event)) = self.event.next().now_or_never() {
+            if let Some(event) = to_trace_event(event) {
+                events.push(event);
+            }
+        }
+
+        let state = self.state_machine.trace_state();
+        if state != step.state {
+            return Err(format!("Expect state {}, get {}", step.state, state));
+        }
+
+        match &step.events {
+            Some(expect) if *expect != events => {
+                Err(format!("Expect events {:?}, get {:?}", expect, events))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn to_hash(hash: &str) -> Hash {
+    Hash::from_slice(hash.as_bytes())
+}
+
+pub(crate) fn from_hash(hash: &Hash) -> String {
+    String::from_utf8_lossy(hash).into_owned()
+}
+
+fn to_trigger(action: &TraceAction) -> SMRTrigger {
+    let (trigger_type, source, hash, lock_round, round, height) = match action.clone() {
+        TraceAction::NewHeight { height } => (
+            TriggerType::NewHeight(SMRStatus {
+                height,
+                new_interval: None,
+                new_config: None,
+            }),
+            TriggerSource::State,
+            Hash::new(),
+            None,
+            0,
+            height,
+        ),
+        TraceAction::Proposal {
+            height,
+            round,
+            hash,
+            lock_round,
+        } => (
+            TriggerType::Proposal,
+            TriggerSource::State,
+            to_hash(&hash),
+            lock_round,
+            round,
+            height,
+        ),
+        TraceAction::ProposeTimeout { height, round } => (
+            TriggerType::Proposal,
+            TriggerSource::Timer,
+            Hash::new(),
+            None,
+            round,
+            height,
+        ),
+        TraceAction::PrevoteQC {
+            height,
+            round,
+            hash,
+        } => (
+            TriggerType::PrevoteQC,
+            TriggerSource::State,
+            to_hash(&hash),
+            Some(round),
+            round,
+            height,
+        ),
+        TraceAction::PrevoteTimeout { height, round } => (
+            TriggerType::PrevoteQC,
+            TriggerSource::Timer,
+            Hash::new(),
+            None,
+            round,
+            height,
+        ),
+        TraceAction::PrecommitQC {
+            height,
+            round,
+            hash,
+        } => (
+            TriggerType::PrecommitQC,
+            TriggerSource::State,
+            to_hash(&hash),
+            Some(round),
+            round,
+            height,
+        ),
+        TraceAction::PrecommitTimeout { height, round } => (
+            TriggerType::PrecommitQC,
+            TriggerSource::Timer,
+            Hash::new(),
+            None,
+            round,
+            height,
+        ),
+        TraceAction::BrakeTimeout { height, round } => (
+            TriggerType::BrakeTimeout,
+            TriggerSource::Timer,
+            Hash::new(),
+            None,
+            round,
+            height,
+        ),
+        TraceAction::ContinueRound { height, round } => (
+            TriggerType::ContinueRound,
+            TriggerSource::State,
+            Hash::new(),
+            None,
+            round,
+            height,
+        ),
+    };
+
+    SMRTrigger {
+        trigger_type,
+        source,
+        hash,
+        lock_round,
+        round,
+        height,
+        wal_info: None,
+    }
+}
+
+fn to_trace_event(event: SMREvent) -> Option<TraceEvent> {
+    match event {
+        SMREvent::NewRoundInfo {
+            round, lock_round, ..
+        } => Some(TraceEvent::NewRound { round, lock_round }),
+        SMREvent::PrevoteVote {
+            round, block_hash, ..
+        } => Some(TraceEvent::Prevote {
+            round,
+            hash: from_hash(&block_hash),
+        }),
+        SMREvent::PrecommitVote {
+            round, block_hash, ..
+        } => Some(TraceEvent::Precommit {
+            round,
+            hash: from_hash(&block_hash),
+        }),
+        SMREvent::Commit(hash) => Some(TraceEvent::Commit {
+            hash: from_hash(&hash),
+        }),
+        SMREvent::Brake { round, .. } => Some(TraceEvent::Brake { round }),
+        SMREvent::UpdateConfig(_) | SMREvent::Stop => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{check_trace_dir, Trace};
+
+    fn trace_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/traces")
+    }
+
+    #[test]
+    fn test_check_traces() {
+        assert!(check_trace_dir(trace_dir()).unwrap() > 0);
+    }
+
+    #[test]
+    fn test_check_mismatch() {
+        let mut trace = Trace::from_file(trace_dir().join("commit.json")).unwrap();
+        trace.steps[1].state.block_hash = "B".to_string();
+        let err = trace.check().unwrap_err();
+        assert_eq!(err.step, 1);
+
+        let mut trace = Trace::from_file(trace_dir().join("commit.json")).unwrap();
+        trace.steps[0].rejected = true;
+        assert_eq!(trace.check().unwrap_err().step, 0);
+
+        assert!(Trace::from_json("{\"steps\": [{\"action\": {}}]}").is_err());
+    }
+}
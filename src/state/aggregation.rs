@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Address, Hash, Signature, SignedVote, VoteType};
+use crate::Crypto;
+
+/// The timing of the QCs aggregated by the node from the votes.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AggregationStats {
+    /// The QCs aggregated by the node.
+    pub qcs: u64,
+    /// The QCs whose signatures were folded as the votes arrived, the others are aggregated
+    /// at once when they are built.
+    pub folded: u64,
+    /// The average time in microseconds to build a QC after its votes are above the threshold.
+    pub average_build_micros: f64,
+    /// The max time in microseconds to build a QC after its votes are above the threshold.
+    pub max_build_micros: u64,
+}
+
+#[derive(Debug, Default)]
+struct Timing {
+    stats: AggregationStats,
+    total_micros: u64,
+}
+
+/// The aggregation stats, which are updated by the state and read by the instance.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AggregationAccount(Arc<Mutex<Timing>>);
+
+impl AggregationAccount {
+    pub(crate) fn new() -> Self {
+        AggregationAccount::default()
+    }
+
+    pub(crate) fn stats(&self) -> AggregationStats {
+        self.0.lock().stats.clone()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Running {
+    signature: Option<Signature>,
+    voters: HashSet<Address>,
+    failed: bool,
+}
+
+/// The running aggregated signatures of the votes of the current height, keyed by the round,
+/// the type and the block hash. The signature of each vote is folded as it arrives, so a QC
+/// is ready as soon as its votes are above the threshold. If the crypto does not fold the
+/// signatures, the folding stops and the QCs are aggregated at once.
+#[derive(Debug, Default)]
+pub struct RunningAggregates {
+    height: u64,
+    unsupported: bool,
+    running: HashMap<(u64, VoteType, Hash), Running>,
+    account: AggregationAccount,
+}
+
+impl RunningAggregates {
+    pub fn new() -> Self {
+        RunningAggregates::default()
+    }
+
+    pub(crate) fn set_account(&mut self, account: AggregationAccount) {
+        self.account = account;
+    }
+
+    /// Fold the signature of a verified vote into the running aggregated signature of its
+    /// block. The running ones of the lower heights are removed.
+    pub fn fold<C: Crypto + ?Sized>(&mut self, crypto: &C, vote: &SignedVote) {
+        if self.unsupported {
+            return;
+        }
+        if vote.vote.height != self.height {
+            self.height = vote.vote.height;
+            self.running.clear();
+        }
+
+        let key = (
+            vote.vote.round,
+            vote.vote.vote_type.clone(),
+            vote.vote.block_hash.clone(),
+        );
+        let running = self.running.entry(key).or_default();
+        if running.failed || running.voters.contains(&vote.voter) {
+            return;
+        }
+        match crypto.fold_signature(
+            running.signature.as_ref(),
+            &vote.signature,
+            &vote.voter,
+        ) {
+            Ok(Some(signature)) => {
+                running.signature = Some(signature);
+                running.voters.insert(vote.voter.clone());
+            }
+            Ok(None) => {
+                self.unsupported = true;
+                self.running.clear();
+            }
+            Err(err) => {
+                warn!("Mlm: state fold signature error {:?}", err);
+                running.failed = true;
+            }
+        }
+    }
+
+    /// Get the running aggregated signature of the block if it covers exactly the voters.
+    pub fn folded(
+        &self,
+        round: u64,
+        vote_type: VoteType,
+        block_hash: &Hash,
+        voters: &[Address],
+    ) -> Option<Signature> {
+        let running = self.running.get(&(round, vote_type, block_hash.clone()))?;
+        if running.failed
+            || running.voters.len() != voters.len()
+            || !voters.iter().all(|voter| running.voters.contains(voter))
+        {
+            return None;
+        }
+        running.signature.clone()
+    }
+
+    /// Count a QC aggregated by the node and the time to build it.
+    pub fn built(&self, elapsed: Duration, folded: bool) {
+        let micros = elapsed.as_micros() as u64;
+        let mut timing = self.account.0.lock();
+        let total = timing.total_micros.saturating_add(micros);
+        timing.total_micros = total;
+        let stats = &mut timing.stats;
+        stats.qcs += 1;
+        if folded {
+            stats.folded += 1;
+        }
+        stats.max_build_micros = stats.max_build_micros.max(micros);
+        stats.average_build_micros = total as f64 / stats.qcs as f64;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::time::Duration;
+
+    use bytes::{Bytes, BytesMut};
+
+    use super::{AggregationAccount, AggregationStats, RunningAggregates};
+    use crate::types::{
+        Address, AggregatedSignature, Hash, Signature, SignedVote, Vote, VoteType,
+    };
+    use crate::Crypto;
+
+    /// A crypto folding the signatures by concatenation, which rejects the empty ones.
+    struct ConcatCrypto(bool);
+
+    impl Crypto for ConcatCrypto {
+        type QcSignature = AggregatedSignature;
+
+        fn hash(&self, msg: Bytes) -> Hash {
+            Hash::from(msg)
+        }
+
+        fn sign(&self, hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+            Ok(hash.into_bytes())
+        }
+
+        fn aggregate_signatures(
+            &self,
+            signatures: Vec<Signature>,
+            _voters: Vec<Address>,
+        ) -> Result<Signature, Box<dyn Error + Send>> {
+            Ok(signatures.concat().into())
+        }
+
+        fn fold_signature(
+            &self,
+            aggregate: Option<&Signature>,
+            signature: &Signature,
+            _voter: &Address,
+        ) -> Result<Option<Signature>, Box<dyn Error + Send>> {
+            if !self.0 {
+                return Ok(None);
+            }
+            if signature.is_empty() {
+                return Err(Box::new(std::fmt::Error));
+            }
+            let mut folded =
+                BytesMut::from(aggregate.cloned().unwrap_or_default().as_ref());
+            folded.extend_from_slice(signature);
+            Ok(Some(folded.freeze()))
+        }
+
+        fn verify_signature(
+            &self,
+            _signature: Signature,
+            _hash: Hash,
+            _voter: Address,
+        ) -> Result<(), Box<dyn Error + Send>> {
+            Ok(())
+        }
+
+        fn verify_aggregated_signature(
+            &self,
+            _aggregate_signature: Signature,
+            _msg_hash: Hash,
+            _voters: Vec<Address>,
+        ) -> Result<(), Box<dyn Error + Send>> {
+            Ok(())
+        }
+    }
+
+    fn vote(height: u64, voter: u8, signature: &'static [u8]) -> SignedVote {
+        SignedVote {
+            signature: Bytes::from_static(signature),
+            vote: Vote {
+                height,
+                round: 0,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from(vec![1]),
+            },
+            voter: Address::from_slice(&[voter]).unwrap(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        }
+    }
+
+    fn folded(aggregates: &RunningAggregates, voters: &[u8]) -> Option<Signature> {
+        let voters = voters
+            .iter()
+            .map(|voter| Address::from_slice(&[*voter]).unwrap())
+            .collect::<Vec<_>>();
+        aggregates.folded(0, VoteType::Prevote, &Hash::from(vec![1]), &voters)
+    }
+
+    #[test]
+    fn test_running_aggregates() {
+        let crypto = ConcatCrypto(true);
+        let mut aggregates = RunningAggregates::new();
+        aggregates.fold(&crypto, &vote(1, 1, b"a"));
+        aggregates.fold(&crypto, &vote(1, 2, b"b"));
+        aggregates.fold(&crypto, &vote(1, 2, b"c"));
+        assert_eq!(folded(&aggregates, &[2, 1]), Some(Bytes::from("ab")));
+        assert_eq!(folded(&aggregates, &[1]), None);
+        assert_eq!(folded(&aggregates, &[1, 3]), None);
+
+        // A failed fold stops the folding of the block.
+        aggregates.fold(&crypto, &vote(1, 3, b""));
+        aggregates.fold(&crypto, &vote(1, 4, b"d"));
+        assert_eq!(folded(&aggregates, &[1, 2]), None);
+
+        // The running ones of the lower heights are removed.
+        aggregates.fold(&crypto, &vote(2, 1, b"e"));
+        assert_eq!(folded(&aggregates, &[1]), Some(Bytes::from("e")));
+
+        let mut aggregates = RunningAggregates::new();
+        aggregates.fold(&ConcatCrypto(false), &vote(1, 1, b"a"));
+        aggregates.fold(&crypto, &vote(1, 2, b"b"));
+        assert_eq!(folded(&aggregates, &[2]), None);
+    }
+
+    #[test]
+    fn test_aggregation_stats() {
+        let account = AggregationAccount::new();
+        let mut aggregates = RunningAggregates::new();
+        aggregates.set_account(account.clone());
+        aggregates.built(Duration::from_micros(30), true);
+        aggregates.built(Duration::from_micros(90), false);
+
+        assert_eq!(
+            account.stats(),
+            AggregationStats {
+                qcs: 2,
+                folded: 1,
+                average_build_micros: 60.0,
+                max_build_micros: 90,
+            }
+        );
+    }
+}
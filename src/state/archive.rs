@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::types::Proof;
+
+/// The commit proofs of the recent heights, which are archived by the state on each commit
+/// and served by the handlers. The proofs of the lowest heights are dropped over the
+/// capacity, and a zero capacity keeps none.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProofArchive(Arc<RwLock<ArchivedProofs>>);
+
+#[derive(Debug, Default)]
+struct ArchivedProofs {
+    proofs: BTreeMap<u64, Proof>,
+    capacity: usize,
+}
+
+impl ArchivedProofs {
+    fn prune(&mut self) {
+        while self.proofs.len() > self.capacity {
+            self.proofs.pop_first();
+        }
+    }
+}
+
+impl ProofArchive {
+    pub(crate) fn new() -> Self {
+        ProofArchive::default()
+    }
+
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        let mut archive = self.0.write();
+        archive.capacity = capacity;
+        archive.prune();
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.0.read().capacity > 0
+    }
+
+    pub(crate) fn insert(&self, proof: Proof) {
+        let mut archive = self.0.write();
+        archive.proofs.insert(proof.height, proof);
+        archive.prune();
+    }
+
+    /// Restore the proofs loaded from the wal, which are kept with the archived ones.
+    pub(crate) fn restore(&self, proofs: Vec<Proof>) {
+        let mut archive = self.0.write();
+        for proof in proofs {
+            archive.proofs.entry(proof.height).or_insert(proof);
+        }
+        archive.prune();
+    }
+
+    pub(crate) fn get(&self, height: u64) -> Option<Proof> {
+        self.0.read().proofs.get(&height).cloned()
+    }
+
+    /// The archived proofs in the order of their heights.
+    pub(crate) fn proofs(&self) -> Vec<Proof> {
+        self.0.read().proofs.values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::ProofArchive;
+    use crate::types::{AggregatedSignature, Hash, Proof};
+
+    fn gen_proof(height: u64, round: u64) -> Proof {
+        Proof {
+            height,
+            round,
+            block_hash: Hash::from(vec![height as u8; 32]),
+            signature: AggregatedSignature {
+                signature: Bytes::from(vec![1u8; 64]),
+                address_bitmap: Bytes::from(vec![0b1110_0000]),
+            },
+            extensions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_proof_archive() {
+        let archive = ProofArchive::new();
+        archive.insert(gen_proof(1, 0));
+        assert!(!archive.is_enabled());
+        assert_eq!(archive.get(1), None);
+
+        archive.set_capacity(3);
+        for height in 1..=5 {
+            archive.insert(gen_proof(height, 0));
+        }
+        assert_eq!(archive.get(2), None);
+        assert_eq!(archive.get(3), Some(gen_proof(3, 0)));
+        assert_eq!(
+            archive.proofs(),
+            (3..=5).map(|height| gen_proof(height, 0)).collect::<Vec<_>>()
+        );
+
+        // The restored proofs never replace the archived ones, and the lowest are dropped.
+        archive.restore(vec![gen_proof(5, 1), gen_proof(6, 1)]);
+        assert_eq!(archive.get(3), None);
+        assert_eq!(archive.get(5), Some(gen_proof(5, 0)));
+        assert_eq!(archive.get(6), Some(gen_proof(6, 1)));
+
+        archive.set_capacity(1);
+        assert_eq!(archive.proofs(), vec![gen_proof(6, 1)]);
+    }
+}
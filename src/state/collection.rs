@@ -3,12 +3,17 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use creep::Context;
 use hummer::coding::hex_encode;
 
+use crate::state::timing;
 use crate::types::{
     Address, AggregatedChoke, AggregatedVote, Hash, SignedChoke, SignedProposal,
     SignedVote, VoteType,
 };
+use crate::utils::auth_manage::AuthorityManage;
 use crate::{error::ConsensusError, Codec, ConsensusResult};
 
+/// The estimated bytes of the map entry and the fixed fields of a cached message.
+const ITEM_OVERHEAD: usize = 64;
+
 /// A struct to collect signed proposals in each height. It stores each height and the corresponding
 /// signed proposals in a `BTreeMap`.
 #[derive(Clone, Debug)]
@@ -64,6 +69,13 @@ where
         )))
     }
 
+    /// Remove the signed proposal of the given height and round.
+    pub fn remove(&mut self, height: u64, round: u64) {
+        if let Some(round_collector) = self.0.get_mut(&height) {
+            round_collector.remove(round);
+        }
+    }
+
     /// Get all proposals of the given height.
     pub fn get_height_proposals(
         &mut self,
@@ -71,27 +83,72 @@ where
     ) -> Option<Vec<(SignedProposal<T>, Context)>> {
         self.0.remove(&height).map_or_else(
             || None,
-            |map| Some(map.0.values().cloned().collect::<Vec<_>>()),
+            |map| Some(map.proposals.values().cloned().collect::<Vec<_>>()),
         )
     }
 
-    /// Remove items that height is less than `till`.
-    pub fn flush(&mut self, till: u64) {
-        self.0 = self.0.split_off(&till);
+    /// Remove the proposals of the heights less than `till`, and return them by the height in
+    /// the order of their rounds.
+    pub fn take_below(&mut self, till: u64) -> BTreeMap<u64, Vec<SignedProposal<T>>> {
+        let kept = self.0.split_off(&till);
+        std::mem::replace(&mut self.0, kept)
+            .into_iter()
+            .map(|(height, prc)| {
+                let mut proposals = prc.proposals.into_iter().collect::<Vec<_>>();
+                proposals.sort_unstable_by_key(|(round, _)| *round);
+                let proposals = proposals.into_iter().map(|(_, (sp, _))| sp).collect();
+                (height, proposals)
+            })
+            .collect()
+    }
+
+    /// Get the estimated bytes of the cached proposals.
+    pub fn mem_size(&self) -> usize {
+        self.0.values().map(|prc| prc.size).sum()
+    }
+
+    /// Evict the proposals of the heights above the given height from the highest one, until
+    /// the estimated bytes are not more than the limit. Return the number of evicted heights.
+    pub fn evict(&mut self, height: u64, limit: usize) -> usize {
+        let mut size = self.mem_size();
+        let mut evicted = 0;
+
+        while size > limit {
+            match self.0.keys().next_back().copied() {
+                Some(highest) if highest > height => {
+                    size -= self.0.remove(&highest).map_or(0, |prc| prc.size);
+                    evicted += 1;
+                }
+                _ => break,
+            }
+        }
+        evicted
+    }
+
+    /// Remove the proposals of the superseded rounds of the height, which are below the given
+    /// round. Return the number of the removed proposals.
+    pub fn prune_rounds(&mut self, height: u64, below: u64) -> usize {
+        self.0.get_mut(&height).map_or(0, |prc| prc.prune(below))
     }
 }
 
 /// A struct to collect signed proposals in each round. It stores each round and the corresponding
 /// signed proposals in a `HashMap`.
 #[derive(Clone, Debug)]
-struct ProposalRoundCollector<T: Codec>(HashMap<u64, (SignedProposal<T>, Context)>);
+struct ProposalRoundCollector<T: Codec> {
+    proposals: HashMap<u64, (SignedProposal<T>, Context)>,
+    size: usize,
+}
 
 impl<T> ProposalRoundCollector<T>
 where
     T: Codec,
 {
     fn new() -> Self {
-        ProposalRoundCollector(HashMap::new())
+        ProposalRoundCollector {
+            proposals: HashMap::new(),
+            size: 0,
+        }
     }
 
     fn insert(
@@ -100,21 +157,43 @@ where
         round: u64,
         proposal: SignedProposal<T>,
     ) -> ConsensusResult<()> {
-        if let Some((sp, _)) = self.0.get(&round) {
+        if let Some((sp, _)) = self.proposals.get(&round) {
             if sp == &proposal {
                 return Ok(());
             }
             return Err(ConsensusError::Other("_".to_string()));
         }
-        self.0.insert(round, (proposal, ctx));
+        self.size += proposal_size(&proposal);
+        self.proposals.insert(round, (proposal, ctx));
         Ok(())
     }
 
     fn get(&self, round: u64) -> ConsensusResult<&(SignedProposal<T>, Context)> {
-        self.0
+        self.proposals
             .get(&round)
             .ok_or_else(|| ConsensusError::StorageErr("_".to_string()))
     }
+
+    fn remove(&mut self, round: u64) {
+        if let Some((proposal, _)) = self.proposals.remove(&round) {
+            self.size -= proposal_size(&proposal);
+        }
+    }
+
+    fn prune(&mut self, below: u64) -> usize {
+        let mut pruned = 0;
+        let mut size = 0;
+        self.proposals.retain(|round, (proposal, _)| {
+            if *round >= below {
+                return true;
+            }
+            pruned += 1;
+            size += proposal_size(proposal);
+            false
+        });
+        self.size -= size;
+        pruned
+    }
 }
 
 /// A struct to collect votes in each height. It stores each height and the corresponding votes in a
@@ -239,6 +318,48 @@ impl VoteCollector {
         )
     }
 
+    /// Get the collected prevotes and precommits of all rounds of the given height, ordered by
+    /// the round and the voter. The votes are kept in the collector.
+    pub fn get_height_vote_set(&self, height: u64) -> (Vec<SignedVote>, Vec<SignedVote>) {
+        let mut prevotes = Vec::new();
+        let mut precommits = Vec::new();
+
+        if let Some(vrc) = self.0.get(&height) {
+            for rc in vrc.general.values() {
+                prevotes.extend(rc.prevote.by_address.values().map(|v| v.0.clone()));
+                precommits.extend(rc.precommit.by_address.values().map(|v| v.0.clone()));
+            }
+        }
+
+        for votes in [&mut prevotes, &mut precommits] {
+            votes.sort_by(|a, b| {
+                (a.vote.round, &a.voter).cmp(&(b.vote.round, &b.voter))
+            });
+        }
+        (prevotes, precommits)
+    }
+
+    /// Get the collected prevotes and precommits of the given height and round, the prevotes
+    /// first and each type ordered by the voter. The votes are kept in the collector.
+    pub fn get_round_votes(&self, height: u64, round: u64) -> Vec<SignedVote> {
+        let rc = match self.0.get(&height).and_then(|vrc| vrc.general.get(&round)) {
+            Some(rc) => rc,
+            None => return Vec::new(),
+        };
+
+        let mut votes = Vec::new();
+        for collected in [&rc.prevote, &rc.precommit] {
+            let mut typed = collected
+                .by_address
+                .values()
+                .map(|v| v.0.clone())
+                .collect::<Vec<_>>();
+            typed.sort_by(|a, b| a.voter.cmp(&b.voter));
+            votes.append(&mut typed);
+        }
+        votes
+    }
+
     pub fn vote_count(&self, height: u64, round: u64, vote_type: VoteType) -> usize {
         if let Some(vrc) = self.0.get(&height) {
             return vrc.vote_count(round, vote_type);
@@ -246,9 +367,76 @@ impl VoteCollector {
         0
     }
 
-    /// Remove items that height is less than `till`.
-    pub fn flush(&mut self, till: u64) {
-        self.0 = self.0.split_off(&till);
+    /// Remove the votes and the QCs of the heights less than `till`, and return them by the
+    /// height. The votes are ordered by the round, the type and the voter, and the QCs by the
+    /// round with the prevote QC first.
+    #[allow(clippy::type_complexity)]
+    pub fn take_below(
+        &mut self,
+        till: u64,
+    ) -> BTreeMap<u64, (Vec<SignedVote>, Vec<AggregatedVote>)> {
+        let kept = self.0.split_off(&till);
+        std::mem::replace(&mut self.0, kept)
+            .into_iter()
+            .map(|(height, vrc)| {
+                let mut rounds = vrc.general.into_iter().collect::<Vec<_>>();
+                rounds.sort_unstable_by_key(|(round, _)| *round);
+
+                let mut votes = Vec::new();
+                let mut qcs = Vec::new();
+                for (_, mut rc) in rounds {
+                    for type_votes in [&mut rc.prevote, &mut rc.precommit] {
+                        let mut round_votes = type_votes
+                            .by_address
+                            .drain()
+                            .map(|(_, (vote, _))| vote)
+                            .collect::<Vec<_>>();
+                        round_votes.sort_unstable_by(|a, b| a.voter.cmp(&b.voter));
+                        votes.append(&mut round_votes);
+                    }
+                    qcs.append(&mut rc.qc.get_all_qcs());
+                }
+                (height, (votes, qcs))
+            })
+            .collect()
+    }
+
+    /// Get the estimated bytes of the cached votes and QCs.
+    pub fn mem_size(&self) -> usize {
+        self.0.values().map(|vrc| vrc.size).sum()
+    }
+
+    /// Remove the signed votes of the superseded rounds of the height, which are below the
+    /// given round. The QCs of the rounds are kept. Return the number of the removed votes.
+    pub fn prune_rounds(&mut self, height: u64, below: u64) -> usize {
+        self.0.get_mut(&height).map_or(0, |vrc| vrc.prune(below))
+    }
+
+    /// Get the number of the signed votes of both types of the height and the round.
+    pub fn round_vote_count(&self, height: u64, round: u64) -> usize {
+        self.0.get(&height).map_or(0, |vrc| {
+            vrc.vote_count(round, VoteType::Prevote)
+                + vrc.vote_count(round, VoteType::Precommit)
+        })
+    }
+
+    /// Evict the votes and QCs of the heights above the given height from the highest one,
+    /// until the estimated bytes are not more than the limit. Return the number of evicted
+    /// heights.
+    pub fn evict(&mut self, height: u64, limit: usize) -> usize {
+        let mut size = self.mem_size();
+        let mut evicted = 0;
+
+        while size > limit {
+            match self.0.keys().next_back().copied() {
+                Some(highest) if highest > height => {
+                    size -= self.0.remove(&highest).map_or(0, |vrc| vrc.size);
+                    evicted += 1;
+                }
+                _ => break,
+            }
+        }
+        evicted
     }
 }
 
@@ -258,6 +446,7 @@ impl VoteCollector {
 struct VoteRoundCollector {
     general: HashMap<u64, RoundCollector>,
     qc_by_hash: HashMap<Hash, QuorumCertificate>,
+    size: usize,
 }
 
 impl VoteRoundCollector {
@@ -265,6 +454,7 @@ impl VoteRoundCollector {
         VoteRoundCollector {
             general: HashMap::new(),
             qc_by_hash: HashMap::new(),
+            size: 0,
         }
     }
 
@@ -275,22 +465,37 @@ impl VoteRoundCollector {
         vote: SignedVote,
         addr: Address,
     ) {
-        self.general
+        let size = vote_size(&vote);
+        if self
+            .general
             .entry(vote.get_round())
             .or_insert_with(RoundCollector::new)
-            .insert_vote(ctx, hash, vote, addr);
+            .insert_vote(ctx, hash, vote, addr)
+        {
+            self.size += size;
+        }
     }
 
     fn set_qc(&mut self, qc: AggregatedVote) {
+        let size = qc.signature.signature.len()
+            + qc.signature.address_bitmap.len()
+            + qc.block_hash.len()
+            + qc.leader.len()
+            + ITEM_OVERHEAD;
+
         self.qc_by_hash
             .entry(qc.block_hash.clone())
             .or_insert_with(QuorumCertificate::new)
             .set_quorum_certificate(qc.clone());
 
-        self.general
+        if self
+            .general
             .entry(qc.get_round())
             .or_insert_with(RoundCollector::new)
-            .set_qc(qc);
+            .set_qc(qc)
+        {
+            self.size += size;
+        }
     }
 
     fn get_vote_map(
@@ -337,6 +542,18 @@ impl VoteRoundCollector {
         }
         0
     }
+
+    fn prune(&mut self, below: u64) -> usize {
+        let mut pruned = 0;
+        for (_, rc) in self.general.iter_mut().filter(|(round, _)| **round < below) {
+            for votes in [&mut rc.prevote, &mut rc.precommit] {
+                let (count, size) = votes.clear();
+                pruned += count;
+                self.size -= size;
+            }
+        }
+        pruned
+    }
 }
 
 /// A round collector contains a qc and prevote votes and precommit votes.
@@ -356,22 +573,24 @@ impl RoundCollector {
         }
     }
 
+    /// Return whether the vote is a new one.
     fn insert_vote(
         &mut self,
         ctx: Context,
         hash: Hash,
         vote: SignedVote,
         addr: Address,
-    ) {
+    ) -> bool {
         if vote.is_prevote() {
-            self.prevote.insert(ctx, hash, addr, vote);
+            self.prevote.insert(ctx, hash, addr, vote)
         } else {
-            self.precommit.insert(ctx, hash, addr, vote);
+            self.precommit.insert(ctx, hash, addr, vote)
         }
     }
 
-    fn set_qc(&mut self, qc: AggregatedVote) {
-        self.qc.set_quorum_certificate(qc);
+    /// Return whether the QC is a new one.
+    fn set_qc(&mut self, qc: AggregatedVote) -> bool {
+        self.qc.set_quorum_certificate(qc)
     }
 
     fn get_vote_map(&self, vote_type: VoteType) -> &HashMap<Hash, HashSet<Address>> {
@@ -419,11 +638,12 @@ impl QuorumCertificate {
         }
     }
 
-    fn set_quorum_certificate(&mut self, qc: AggregatedVote) {
+    /// Return whether there is no QC of the type before.
+    fn set_quorum_certificate(&mut self, qc: AggregatedVote) -> bool {
         if qc.is_prevote_qc() {
-            self.prevote = Some(qc);
+            self.prevote.replace(qc).is_none()
         } else {
-            self.precommit = Some(qc);
+            self.precommit.replace(qc).is_none()
         }
     }
 
@@ -463,11 +683,17 @@ impl Votes {
         }
     }
 
-    fn insert(&mut self, ctx: Context, hash: Hash, addr: Address, vote: SignedVote) {
-        if self.by_address.contains_key(&addr) {
+    /// Return whether the vote is inserted.
+    fn insert(
+        &mut self,
+        ctx: Context,
+        hash: Hash,
+        addr: Address,
+        vote: SignedVote,
+    ) -> bool {
+        if let Some(exist) = self.by_address.get(&addr).cloned() {
             // the addr somehow has already inserted a Vote we ignore the incoming SignedVote no
             // matter it duplicates or differs(byzantine), reject the current request!
-            let exist = self.by_address.get(&addr).unwrap().clone();
             if vote.vote.block_hash != exist.0.vote.block_hash {
                 // this is a byzantine behaviour
                 log::error!(
@@ -476,7 +702,7 @@ impl Votes {
                     vote
                 );
             }
-            return;
+            return false;
         }
 
         self.by_hash
@@ -484,6 +710,7 @@ impl Votes {
             .or_insert_with(HashSet::new)
             .insert(addr.clone());
         self.by_address.entry(addr).or_insert((vote, ctx));
+        true
     }
 
     fn get_vote_map(&self) -> &HashMap<Hash, HashSet<Address>> {
@@ -506,6 +733,29 @@ impl Votes {
     fn vote_count(&self) -> usize {
         self.by_address.len()
     }
+
+    /// Remove all of the votes, and return their number and their estimated bytes.
+    fn clear(&mut self) -> (usize, usize) {
+        let count = self.by_address.len();
+        let size = self
+            .by_address
+            .values()
+            .map(|(vote, _)| vote_size(vote))
+            .sum();
+        self.by_hash.clear();
+        self.by_address.clear();
+        (count, size)
+    }
+}
+
+/// The estimated bytes of a cached signed proposal.
+fn proposal_size<T: Codec>(sp: &SignedProposal<T>) -> usize {
+    timing::proposal_size(sp) + ITEM_OVERHEAD
+}
+
+/// The estimated bytes of a cached signed vote.
+fn vote_size(vote: &SignedVote) -> usize {
+    vote.signature.len() + vote.voter.len() + vote.vote.block_hash.len() + ITEM_OVERHEAD
 }
 
 #[derive(Clone, Debug)]
@@ -539,13 +789,20 @@ impl ChokeCollector {
             .map(|map| map.values().cloned().collect::<Vec<_>>())
     }
 
+    pub fn choke_count(&self, round: u64) -> usize {
+        self.chokes.get(&round).map_or(0, |map| map.len())
+    }
+
     pub fn get_qc(&self, round: u64) -> Option<AggregatedChoke> {
         self.qcs.get(&round).cloned()
     }
 
-    pub fn max_round_above_threshold(&self, nodes_num: usize) -> Option<u64> {
+    /// Get the highest round whose choke voters are a quorum by the threshold policy of the
+    /// authority.
+    pub fn max_round_above_threshold(&self, authority: &AuthorityManage) -> Option<u64> {
         for (round, set) in self.chokes.iter().rev() {
-            if set.len() * 3 > nodes_num * 2 {
+            let voters = set.keys().cloned().collect::<Vec<_>>();
+            if authority.is_above_threshold_by_voters(&voters) {
                 return Some(*round);
             }
         }
@@ -566,6 +823,15 @@ impl ChokeCollector {
         }
     }
 
+    /// Remove the chokes of the rounds below the given round, while their QCs are kept.
+    /// Return the number of the removed chokes.
+    pub fn prune_rounds(&mut self, below: u64) -> usize {
+        let kept = self.chokes.split_off(&below);
+        let pruned = self.chokes.values().map(|map| map.len()).sum();
+        self.chokes = kept;
+        pruned
+    }
+
     pub fn clear(&mut self) {
         self.chokes.clear();
         self.qcs.clear();
@@ -575,7 +841,6 @@ impl ChokeCollector {
 #[cfg(test)]
 mod test {
     use std::collections::{HashMap, HashSet};
-    use std::error::Error;
 
     use bincode::{deserialize, serialize};
     use bytes::Bytes;
@@ -583,11 +848,13 @@ mod test {
     use rand::random;
     use serde::{Deserialize, Serialize};
 
-    use crate::state::collection::{ProposalCollector, VoteCollector};
+    use crate::state::collection::{ChokeCollector, ProposalCollector, VoteCollector};
+    use crate::test_utils::gen_address;
     use crate::types::{
-        Address, AggregatedSignature, AggregatedVote, Hash, Proposal, Signature,
-        SignedProposal, SignedVote, Vote, VoteType,
+        Address, AggregatedSignature, AggregatedVote, Choke, Hash, Node, Proposal,
+        Signature, SignedChoke, SignedProposal, SignedVote, UpdateFrom, Vote, VoteType,
     };
+    use crate::utils::auth_manage::AuthorityManage;
     use crate::Codec;
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -597,15 +864,14 @@ mod test {
     }
 
     impl Codec for Pill {
-        fn encode(&self) -> Result<Bytes, Box<dyn Error + Send>> {
-            let encode: Vec<u8> = serialize(&self).expect("Serialize Pill error");
-            Ok(Bytes::from(encode))
+        type Error = bincode::Error;
+
+        fn encode(&self) -> Result<Bytes, Self::Error> {
+            Ok(Bytes::from(serialize(&self)?))
         }
 
-        fn decode(data: Bytes) -> Result<Self, Box<dyn Error + Send>> {
-            let decode: Pill =
-                deserialize(data.as_ref()).expect("Deserialize Pill error.");
-            Ok(decode)
+        fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+            deserialize(data)
         }
     }
 
@@ -621,10 +887,6 @@ mod test {
         Hash::from((0..16).map(|_| random::<u8>()).collect::<Vec<_>>())
     }
 
-    fn gen_address() -> Address {
-        Address::from((0..32).map(|_| random::<u8>()).collect::<Vec<_>>())
-    }
-
     fn gen_signature() -> Signature {
         Signature::from((0..64).map(|_| random::<u8>()).collect::<Vec<_>>())
     }
@@ -647,11 +909,14 @@ mod test {
             block_hash: gen_hash(),
             lock: None,
             proposer: gen_address(),
+            timestamp: None,
         };
 
         SignedProposal {
             signature,
             proposal,
+            timeout_cert: None,
+            vrf_proof: Bytes::new(),
         }
     }
 
@@ -673,6 +938,8 @@ mod test {
             signature: gen_signature(),
             voter: addr,
             vote,
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
         }
     }
 
@@ -690,6 +957,7 @@ mod test {
             vote_type,
             block_hash: gen_hash(),
             leader: gen_address(),
+            extensions: Vec::new(),
         }
     }
 
@@ -721,7 +989,7 @@ mod test {
                 .is_ok()
         );
 
-        proposals.flush(2);
+        proposals.take_below(2);
         assert!(proposals.get(1, 0).is_err());
         assert_eq!(proposals.get(2, 0).unwrap().0, proposal_03);
         assert_eq!(proposals.get(3, 0).unwrap().0, proposal_04);
@@ -806,4 +1074,239 @@ mod test {
             .collect::<HashSet<_>>();
         assert_eq!(res, vec.iter().cloned().collect::<HashSet<_>>());
     }
+
+    #[test]
+    fn test_height_vote_set() {
+        let mut votes = VoteCollector::new();
+        let hash = gen_hash();
+        let mut expect = Vec::new();
+        for (round, vote_type) in [(1, VoteType::Precommit), (0, VoteType::Prevote)] {
+            let addr = gen_address();
+            let vote = gen_signed_vote(2, round, vote_type, hash.clone(), addr.clone());
+            votes.insert_vote(Context::new(), hash.clone(), vote.clone(), addr);
+            expect.push(vote);
+        }
+
+        let (prevotes, precommits) = votes.get_height_vote_set(2);
+        assert_eq!(prevotes, vec![expect[1].clone()]);
+        assert_eq!(precommits, vec![expect[0].clone()]);
+        assert_eq!(votes.vote_count(2, 0, VoteType::Prevote), 1);
+        assert_eq!(votes.get_height_vote_set(1), (Vec::new(), Vec::new()));
+    }
+
+    #[test]
+    fn test_round_votes() {
+        let mut votes = VoteCollector::new();
+        let hash = gen_hash();
+        let mut expect = Vec::new();
+        for (round, vote_type) in [
+            (1, VoteType::Precommit),
+            (1, VoteType::Prevote),
+            (1, VoteType::Prevote),
+            (0, VoteType::Prevote),
+        ] {
+            let addr = gen_address();
+            let vote = gen_signed_vote(2, round, vote_type, hash.clone(), addr.clone());
+            votes.insert_vote(Context::new(), hash.clone(), vote.clone(), addr);
+            if round == 1 {
+                expect.push(vote);
+            }
+        }
+
+        let mut prevotes = expect.split_off(1);
+        prevotes.sort_by(|a, b| a.voter.cmp(&b.voter));
+        prevotes.append(&mut expect);
+        assert_eq!(votes.get_round_votes(2, 1), prevotes);
+        assert!(votes.get_round_votes(2, 2).is_empty());
+        assert!(votes.get_round_votes(3, 1).is_empty());
+    }
+
+    #[test]
+    fn test_take_below() {
+        let mut proposals = ProposalCollector::<Pill>::new();
+        let mut votes = VoteCollector::new();
+        let mut expect_proposals = Vec::new();
+        let mut expect_votes = Vec::new();
+        let hash = gen_hash();
+        for (height, round, vote_type) in [
+            (1, 1, VoteType::Precommit),
+            (1, 0, VoteType::Precommit),
+            (1, 0, VoteType::Prevote),
+            (3, 0, VoteType::Prevote),
+        ] {
+            let proposal = gen_signed_proposal(height, round);
+            if proposals
+                .insert(Context::new(), height, round, proposal.clone())
+                .is_ok()
+                && height == 1
+            {
+                expect_proposals.push(proposal);
+            }
+            let addr = gen_address();
+            let vote =
+                gen_signed_vote(height, round, vote_type, hash.clone(), addr.clone());
+            votes.insert_vote(Context::new(), hash.clone(), vote.clone(), addr);
+            if height == 1 {
+                expect_votes.push(vote);
+            }
+        }
+        let qc = _gen_aggregated_vote(1, 0, VoteType::Prevote);
+        votes.set_qc(qc.clone());
+
+        // The heights below are taken in the order of the rounds, the types and the voters.
+        let taken = proposals.take_below(3);
+        assert_eq!(taken.keys().copied().collect::<Vec<_>>(), vec![1]);
+        expect_proposals.reverse();
+        assert_eq!(taken[&1], expect_proposals);
+        assert!(proposals.get(3, 0).is_ok());
+
+        let taken = votes.take_below(3);
+        expect_votes.reverse();
+        assert_eq!(taken[&1], (expect_votes, vec![qc]));
+        assert_eq!(votes.vote_count(3, 0, VoteType::Prevote), 1);
+        assert!(votes.take_below(3).is_empty());
+    }
+
+    #[test]
+    fn test_collector_evict() {
+        let mut proposals = ProposalCollector::<Pill>::new();
+        let mut votes = VoteCollector::new();
+        for height in 1..4 {
+            proposals
+                .insert(Context::new(), height, 0, gen_signed_proposal(height, 0))
+                .unwrap();
+            let addr = gen_address();
+            let vote = gen_signed_vote(
+                height,
+                0,
+                VoteType::Prevote,
+                gen_hash(),
+                addr.clone(),
+            );
+            votes.insert_vote(
+                Context::new(),
+                vote.get_hash(),
+                vote.clone(),
+                addr.clone(),
+            );
+            // A duplicate vote is not accounted.
+            let size = votes.mem_size();
+            votes.insert_vote(Context::new(), vote.get_hash(), vote, addr);
+            assert_eq!(votes.mem_size(), size);
+        }
+        assert!(proposals.mem_size() > 0);
+
+        // The messages of the current height are never evicted.
+        assert_eq!(proposals.evict(1, 0), 2);
+        assert!(proposals.get(1, 0).is_ok());
+        assert!(proposals.get(2, 0).is_err());
+        assert_eq!(proposals.evict(1, 0), 0);
+
+        let size = votes.mem_size();
+        assert_eq!(votes.evict(1, size), 0);
+        assert_eq!(votes.evict(1, size - 1), 1);
+        assert_eq!(votes.vote_count(3, 0, VoteType::Prevote), 0);
+        assert_eq!(votes.vote_count(2, 0, VoteType::Prevote), 1);
+    }
+
+    #[test]
+    fn test_prune_rounds() {
+        let mut proposals = ProposalCollector::<Pill>::new();
+        let mut votes = VoteCollector::new();
+        let mut chokes = ChokeCollector::new();
+        for round in 0..4 {
+            proposals
+                .insert(Context::new(), 1, round, gen_signed_proposal(1, round))
+                .unwrap();
+            for vote_type in [VoteType::Prevote, VoteType::Precommit] {
+                let addr = gen_address();
+                let vote = gen_signed_vote(1, round, vote_type, gen_hash(), addr.clone());
+                votes.insert_vote(Context::new(), vote.get_hash(), vote, addr);
+            }
+            chokes.insert(
+                round,
+                SignedChoke {
+                    signature: gen_signature(),
+                    choke: Choke {
+                        height: 1,
+                        round,
+                        from: UpdateFrom::PrevoteQC(_gen_aggregated_vote(
+                            1,
+                            round,
+                            VoteType::Prevote,
+                        )),
+                    },
+                    address: gen_address(),
+                },
+            );
+        }
+        votes.set_qc(_gen_aggregated_vote(1, 0, VoteType::Prevote));
+        assert_eq!(votes.round_vote_count(1, 0), 2);
+        let proposal_size = proposals.mem_size();
+        let vote_size = votes.mem_size();
+
+        // The rounds below the given one are pruned, and their QCs are kept.
+        assert_eq!(proposals.prune_rounds(1, 2), 2);
+        assert_eq!(votes.prune_rounds(1, 2), 4);
+        assert_eq!(chokes.prune_rounds(2), 2);
+        assert!(proposals.get(1, 1).is_err());
+        assert!(proposals.get(1, 2).is_ok());
+        assert!(proposals.mem_size() < proposal_size);
+        assert!(votes.mem_size() < vote_size);
+        assert_eq!(votes.round_vote_count(1, 1), 0);
+        assert_eq!(votes.round_vote_count(1, 3), 2);
+        assert!(votes.get_qc_by_id(1, 0, VoteType::Prevote).is_ok());
+        assert_eq!(chokes.choke_count(1), 0);
+        assert_eq!(chokes.choke_count(2), 1);
+
+        assert_eq!(proposals.prune_rounds(1, 2), 0);
+        assert_eq!(votes.prune_rounds(1, 2), 0);
+        assert_eq!(votes.prune_rounds(2, 4), 0);
+    }
+
+    #[test]
+    fn test_choke_threshold_by_weight() {
+        let mut authority_list = (0..4)
+            .map(|i| {
+                let mut node = Node::new(gen_address());
+                node.set_vote_weight(if i == 0 { 10 } else { 1 });
+                node
+            })
+            .collect::<Vec<_>>();
+        let heavy = authority_list[0].address.clone();
+        let lights = authority_list[1..]
+            .iter()
+            .map(|node| node.address.clone())
+            .collect::<Vec<_>>();
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list);
+
+        let gen_choke = |round: u64, address: Address| SignedChoke {
+            signature: gen_signature(),
+            choke: Choke {
+                height: 1,
+                round,
+                from: UpdateFrom::PrevoteQC(_gen_aggregated_vote(
+                    1,
+                    round,
+                    VoteType::Prevote,
+                )),
+            },
+            address,
+        };
+
+        // Three of the four nodes do not weigh a quorum, while the heavy node and another do.
+        let mut chokes = ChokeCollector::new();
+        for address in lights.iter() {
+            chokes.insert(1, gen_choke(1, address.clone()));
+        }
+        assert_eq!(chokes.max_round_above_threshold(&authority), None);
+
+        chokes.insert(0, gen_choke(0, heavy.clone()));
+        chokes.insert(0, gen_choke(0, lights[0].clone()));
+        assert_eq!(chokes.max_round_above_threshold(&authority), Some(0));
+
+        chokes.insert(1, gen_choke(1, heavy));
+        assert_eq!(chokes.max_round_above_threshold(&authority), Some(1));
+    }
 }
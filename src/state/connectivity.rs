@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use hummer::coding::hex_encode;
+use tokio::time::Instant;
+
+use crate::types::{Address, Node};
+use crate::utils::auth_manage::quorum;
+
+/// The connectivity of a node to the authorities when it has not heard from a third of the
+/// vote weight in the connectivity window. The operators tell a partitioned node, which
+/// misses many of the authorities, from a node waiting for a proposer that is down.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectivitySummary {
+    /// The current height.
+    pub height: u64,
+    /// The current round.
+    pub round: u64,
+    /// The window in which the silent authorities have sent nothing.
+    pub window: Duration,
+    /// The vote weight of the authorities heard from in the window, including the node.
+    pub heard_weight: u128,
+    /// The vote weight of the silent authorities.
+    pub silent_weight: u128,
+    /// The authorities in the order of the authority list.
+    pub peers: Vec<PeerConnectivity>,
+}
+
+/// The connectivity of a node to an authority.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerConnectivity {
+    /// The address of the authority.
+    pub address: Address,
+    /// The vote weight of the authority.
+    pub vote_weight: u128,
+    /// The time since the last message signed by the authority, which is empty if the node
+    /// has heard nothing from it since the instance started.
+    pub last_heard: Option<Duration>,
+    /// Whether the authority has sent nothing in the window.
+    pub silent: bool,
+}
+
+impl fmt::Display for ConnectivitySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "silent weight {}, heard weight {} in {:?} at height {}, round {}",
+            self.silent_weight, self.heard_weight, self.window, self.height, self.round
+        )?;
+        for peer in self.peers.iter().filter(|peer| peer.silent) {
+            match peer.last_heard {
+                Some(last_heard) => writeln!(
+                    f,
+                    "{} silent for {:?}",
+                    hex_encode(&peer.address),
+                    last_heard
+                )?,
+                None => writeln!(f, "{} never heard", hex_encode(&peer.address))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The time that each authority was last heard from. It measures the time by the tokio
+/// clock, so it follows the paused time of the simulations.
+#[derive(Debug)]
+pub struct ConnectivityTracker {
+    start: Instant,
+    last_heard: HashMap<Address, Instant>,
+}
+
+impl Default for ConnectivityTracker {
+    fn default() -> Self {
+        ConnectivityTracker::new()
+    }
+}
+
+impl ConnectivityTracker {
+    pub fn new() -> Self {
+        ConnectivityTracker {
+            start: Instant::now(),
+            last_heard: HashMap::new(),
+        }
+    }
+
+    /// Restart the window of the authorities never heard from, when the instance starts.
+    pub fn reset(&mut self) {
+        self.start = Instant::now();
+    }
+
+    /// Mark a message signed by the authority.
+    pub fn heard(&mut self, address: &Address) {
+        self.last_heard.insert(address.clone(), Instant::now());
+    }
+
+    /// Return the summary if the authorities heard from in the window, including the node
+    /// itself, can not reach a quorum. The records of the addresses out of the authority
+    /// list are removed.
+    pub fn check(
+        &mut self,
+        authority_list: &[Node],
+        address: &Address,
+        window: Duration,
+        height: u64,
+        round: u64,
+    ) -> Option<ConnectivitySummary> {
+        self.last_heard
+            .retain(|heard, _| authority_list.iter().any(|node| node.address == *heard));
+
+        let now = Instant::now();
+        let since_start = now.saturating_duration_since(self.start);
+        let mut heard_weight = 0u128;
+        let mut silent_weight = 0u128;
+        let peers = authority_list
+            .iter()
+            .map(|node| {
+                let last_heard = if node.address == *address {
+                    Some(Duration::ZERO)
+                } else {
+                    self.last_heard
+                        .get(&node.address)
+                        .map(|at| now.saturating_duration_since(*at))
+                };
+                let silent = last_heard.unwrap_or(since_start) >= window;
+                if silent {
+                    silent_weight = silent_weight.saturating_add(node.vote_weight);
+                } else {
+                    heard_weight = heard_weight.saturating_add(node.vote_weight);
+                }
+                PeerConnectivity {
+                    address: node.address.clone(),
+                    vote_weight: node.vote_weight,
+                    last_heard,
+                    silent,
+                }
+            })
+            .collect();
+
+        let sum = heard_weight.saturating_add(silent_weight);
+        if silent_weight == 0 || heard_weight >= quorum(sum) {
+            return None;
+        }
+        Some(ConnectivitySummary {
+            height,
+            round,
+            window,
+            heard_weight,
+            silent_weight,
+            peers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::ConnectivityTracker;
+    use crate::types::{Address, Node};
+
+    fn node(index: u8, vote_weight: u128) -> Node {
+        Node {
+            address: Address::from_slice(&[index]).unwrap(),
+            propose_weight: 1,
+            vote_weight,
+        }
+    }
+
+    fn ago(tracker: &mut ConnectivityTracker, index: u8, secs: u64) {
+        let at = Instant::now() - Duration::from_secs(secs);
+        tracker.last_heard.insert(Address::from_slice(&[index]).unwrap(), at);
+    }
+
+    #[test]
+    fn test_connectivity_tracker() {
+        let nodes = vec![node(0, 1), node(1, 1), node(2, 1), node(3, 1)];
+        let own = &nodes[0].address;
+        let window = Duration::from_secs(10);
+
+        // The authorities never heard from are not silent within the first window.
+        let mut tracker = ConnectivityTracker::new();
+        tracker.start = Instant::now() - Duration::from_secs(5);
+        tracker.heard(&nodes[1].address);
+        assert!(tracker.check(&nodes, own, window, 1, 0).is_none());
+
+        // One silent authority of four leaves a quorum.
+        tracker.start = Instant::now() - Duration::from_secs(18);
+        ago(&mut tracker, 1, 8);
+        ago(&mut tracker, 2, 3);
+        assert!(tracker.check(&nodes, own, window, 1, 0).is_none());
+
+        ago(&mut tracker, 1, 13);
+        ago(&mut tracker, 2, 8);
+        ago(&mut tracker, 9, 1);
+        let summary = tracker.check(&nodes, own, window, 2, 1).unwrap();
+        assert_eq!((summary.heard_weight, summary.silent_weight), (2, 2));
+        assert_eq!(
+            summary
+                .peers
+                .iter()
+                .map(|peer| (peer.silent, peer.last_heard.map(|heard| heard.as_secs())))
+                .collect::<Vec<_>>(),
+            vec![
+                (false, Some(0)),
+                (true, Some(13)),
+                (false, Some(8)),
+                (true, None)
+            ]
+        );
+        assert!(!tracker.last_heard.contains_key(&Address::from_slice(&[9]).unwrap()));
+
+        // The heavy authority heard from keeps a quorum.
+        let nodes = vec![node(0, 1), node(1, 1), node(2, 10), node(3, 1)];
+        assert!(tracker.check(&nodes, own, window, 2, 1).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{HashSet, VecDeque};
+use std::hash::BuildHasher;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::types::SignedVote;
+
+/// The counters of the duplicate votes dropped before the verification.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VoteDedupStats {
+    /// The duplicate votes dropped.
+    pub dropped: u64,
+}
+
+/// The stats of the vote dedup, which are updated by the state and read by the instance.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct VoteDedupAccount(Arc<Mutex<VoteDedupStats>>);
+
+impl VoteDedupAccount {
+    pub(crate) fn new() -> Self {
+        VoteDedupAccount::default()
+    }
+
+    pub(crate) fn stats(&self) -> VoteDedupStats {
+        self.0.lock().clone()
+    }
+}
+
+/// The seen-set of the votes of the current height, which drops the exact duplicates of a
+/// vote before they reach the verification pool. A vote is kept as a 64 bits fingerprint of
+/// its round, type, voter, block hash and signature, keyed by a random hasher, so a conflicting
+/// vote or a copy with another signature is still verified. The set is cleared at a new height,
+/// and the oldest fingerprints are evicted over the capacity.
+#[derive(Debug)]
+pub struct SeenVotes {
+    capacity: usize,
+    height: u64,
+    seen: HashSet<u64>,
+    order: VecDeque<u64>,
+    hasher: RandomState,
+    account: VoteDedupAccount,
+}
+
+impl SeenVotes {
+    /// Create a seen-set of the capacity, zero disables it.
+    pub fn new(capacity: usize) -> Self {
+        SeenVotes {
+            capacity,
+            height: 0,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            hasher: RandomState::new(),
+            account: VoteDedupAccount::new(),
+        }
+    }
+
+    pub(crate) fn set_account(&mut self, account: VoteDedupAccount) {
+        self.account = account;
+    }
+
+    /// Check a vote of the current height, return false and count it if the vote has been
+    /// seen.
+    pub fn check(&mut self, height: u64, sv: &SignedVote) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        if height != self.height {
+            self.height = height;
+            self.seen.clear();
+            self.order.clear();
+        }
+
+        let fingerprint = self.hasher.hash_one(sv);
+        if !self.seen.insert(fingerprint) {
+            self.account.0.lock().dropped += 1;
+            return false;
+        }
+        self.order.push_back(fingerprint);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{SeenVotes, VoteDedupAccount};
+    use crate::types::{Address, Hash, SignedVote, Vote, VoteType};
+
+    fn gen_vote(round: u64, voter: u8, hash: u8) -> SignedVote {
+        SignedVote {
+            signature: Bytes::from(vec![voter, hash]),
+            vote: Vote {
+                height: 1,
+                round,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from(vec![hash]),
+            },
+            voter: Address::from_slice(&[voter]).unwrap(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        }
+    }
+
+    #[test]
+    fn test_seen_votes() {
+        let account = VoteDedupAccount::new();
+        let mut seen = SeenVotes::new(3);
+        seen.set_account(account.clone());
+
+        assert!(seen.check(1, &gen_vote(0, 1, 1)));
+        assert!(!seen.check(1, &gen_vote(0, 1, 1)));
+        // A conflicting vote and a vote of another round are not duplicates.
+        assert!(seen.check(1, &gen_vote(0, 1, 2)));
+        assert!(seen.check(1, &gen_vote(1, 1, 1)));
+        let mut resigned = gen_vote(0, 1, 1);
+        resigned.signature = Bytes::from(vec![0]);
+        assert!(seen.check(1, &resigned));
+        assert_eq!(account.stats().dropped, 1);
+
+        // The oldest vote is evicted over the capacity, and a new height clears the set.
+        assert!(seen.check(1, &gen_vote(0, 1, 1)));
+        assert!(!seen.check(1, &resigned));
+        assert!(seen.check(2, &resigned));
+        assert_eq!(account.stats().dropped, 2);
+
+        let mut disabled = SeenVotes::new(0);
+        assert!(disabled.check(1, &gen_vote(0, 1, 1)));
+        assert!(disabled.check(1, &gen_vote(0, 1, 1)));
+    }
+}
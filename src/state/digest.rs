@@ -0,0 +1,224 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash as StdHash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use crate::types::{AggregatedVote, Hash, Vote};
+use crate::wire::WireCodec;
+use crate::{ConsensusResult, Crypto};
+
+/// The max number of the cached digests of each kind, over which the cache is cleared.
+const DIGEST_CAPACITY: usize = 4096;
+
+/// The digests of the signed preimages of the votes and the chokes, which are shared by the
+/// verification tasks and the state. The votes of a block in a round have one preimage, which
+/// is also the preimage of their QC, so it is encoded and hashed once for all of the voters.
+///
+/// The QCs of the active height verified are kept by the digests of their encodings as well,
+/// since a QC usually arrives as the lock of a proposal and again as a message of its own,
+/// and its aggregated signature is verified once for both.
+#[derive(Debug, Default)]
+pub(crate) struct DigestCache {
+    votes: Mutex<HashMap<Vote, Hash>>,
+    chokes: Mutex<HashMap<(u64, u64), Hash>>,
+    verified_qcs: Mutex<HashSet<Hash>>,
+    active_height: AtomicU64,
+}
+
+impl DigestCache {
+    pub(crate) fn new() -> Self {
+        DigestCache::default()
+    }
+
+    /// The digest of the preimage of the vote, which is the digest of the QC of it as well.
+    pub(crate) fn vote<C: Crypto + ?Sized>(
+        &self,
+        crypto: &C,
+        codec: &dyn WireCodec,
+        vote: &Vote,
+    ) -> ConsensusResult<Hash> {
+        if let Some(digest) = self.votes.lock().get(vote) {
+            return Ok(digest.clone());
+        }
+        let digest = crypto.hash(codec.vote_preimage(vote)?);
+        insert(&self.votes, vote.clone(), digest.clone());
+        Ok(digest)
+    }
+
+    /// The digest of the preimage of the choke of the height and the round, which is the
+    /// digest of the timeout certificate of it as well.
+    pub(crate) fn choke<C: Crypto + ?Sized>(
+        &self,
+        crypto: &C,
+        codec: &dyn WireCodec,
+        height: u64,
+        round: u64,
+    ) -> ConsensusResult<Hash> {
+        if let Some(digest) = self.chokes.lock().get(&(height, round)) {
+            return Ok(digest.clone());
+        }
+        let digest = crypto.hash(codec.choke_preimage(height, round)?);
+        insert(&self.chokes, (height, round), digest.clone());
+        Ok(digest)
+    }
+
+    /// The digest of the encoding of a QC of the active height, which keys its verification.
+    /// It is `None` for a QC of another height, whose verification is not kept since the
+    /// authority list of the height may be unknown yet.
+    pub(crate) fn qc<C: Crypto + ?Sized>(
+        &self,
+        crypto: &C,
+        qc: &AggregatedVote,
+    ) -> Option<Hash> {
+        (qc.height == self.active_height.load(Ordering::Acquire))
+            .then(|| crypto.hash(Bytes::from(rlp::encode(qc))))
+    }
+
+    /// Whether the QC of the digest is verified in the active height.
+    pub(crate) fn is_verified_qc(&self, digest: &Hash) -> bool {
+        self.verified_qcs.lock().contains(digest)
+    }
+
+    /// Keep the QC of the digest as verified until the active height changes.
+    pub(crate) fn insert_verified_qc(&self, digest: Hash) {
+        let mut verified = self.verified_qcs.lock();
+        if verified.len() >= DIGEST_CAPACITY {
+            verified.clear();
+        }
+        verified.insert(digest);
+    }
+
+    /// Remove the digests of the heights less than `till`, which becomes the active height.
+    pub(crate) fn flush(&self, till: u64) {
+        self.votes.lock().retain(|vote, _| vote.height >= till);
+        self.chokes.lock().retain(|(height, _), _| *height >= till);
+        self.active_height.store(till, Ordering::Release);
+        self.verified_qcs.lock().clear();
+    }
+}
+
+fn insert<K: Eq + StdHash>(map: &Mutex<HashMap<K, Hash>>, key: K, digest: Hash) {
+    let mut map = map.lock();
+    if map.len() >= DIGEST_CAPACITY {
+        map.clear();
+    }
+    map.insert(key, digest);
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::types::{Address, AggregatedSignature, Signature, VoteType};
+    use crate::wire::RlpCodec;
+
+    #[derive(Default)]
+    struct CountingHash(AtomicUsize);
+
+    impl Crypto for CountingHash {
+        type QcSignature = AggregatedSignature;
+
+        fn hash(&self, msg: Bytes) -> Hash {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Hash::from(msg)
+        }
+
+        fn sign(
+            &self,
+            _hash: Hash,
+        ) -> Result<Signature, Box<dyn std::error::Error + Send>> {
+            Ok(Signature::new())
+        }
+
+        fn aggregate_signatures(
+            &self,
+            _signatures: Vec<Signature>,
+            _voters: Vec<Address>,
+        ) -> Result<Signature, Box<dyn std::error::Error + Send>> {
+            Ok(Signature::new())
+        }
+
+        fn verify_signature(
+            &self,
+            _signature: Signature,
+            _hash: Hash,
+            _voter: Address,
+        ) -> Result<(), Box<dyn std::error::Error + Send>> {
+            Ok(())
+        }
+
+        fn verify_aggregated_signature(
+            &self,
+            _aggregate_signature: Signature,
+            _msg_hash: Hash,
+            _voters: Vec<Address>,
+        ) -> Result<(), Box<dyn std::error::Error + Send>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_digest_cache() {
+        let crypto = CountingHash::default();
+        let cache = DigestCache::new();
+        let vote = Vote {
+            height: 2,
+            round: 0,
+            vote_type: VoteType::Prevote,
+            block_hash: Hash::from(vec![1u8; 32]),
+        };
+
+        let digest = cache.vote(&crypto, &RlpCodec, &vote).unwrap();
+        assert_eq!(digest, Hash::from(RlpCodec.vote_preimage(&vote).unwrap()));
+        assert_eq!(cache.vote(&crypto, &RlpCodec, &vote).unwrap(), digest);
+        assert_eq!(crypto.0.load(Ordering::Relaxed), 1);
+
+        cache.choke(&crypto, &RlpCodec, 2, 1).unwrap();
+        cache.choke(&crypto, &RlpCodec, 2, 1).unwrap();
+        assert_eq!(crypto.0.load(Ordering::Relaxed), 2);
+
+        // The digests of the lower heights are removed.
+        cache.flush(3);
+        cache.vote(&crypto, &RlpCodec, &vote).unwrap();
+        assert_eq!(crypto.0.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_verified_qcs() {
+        let crypto = CountingHash::default();
+        let cache = DigestCache::new();
+        let mut qc = AggregatedVote {
+            signature: AggregatedSignature {
+                signature: Bytes::from(vec![1u8; 96]),
+                address_bitmap: Bytes::from(vec![0b1110_0000]),
+            },
+            vote_type: VoteType::Precommit,
+            height: 2,
+            round: 0,
+            block_hash: Hash::from(vec![1u8; 32]),
+            leader: Address::from_slice(&[2u8; 20]).unwrap(),
+            extensions: Vec::new(),
+        };
+
+        // The QCs out of the active height are not kept.
+        assert_eq!(cache.qc(&crypto, &qc), None);
+        cache.flush(2);
+        let digest = cache.qc(&crypto, &qc).unwrap();
+        assert!(!cache.is_verified_qc(&digest));
+        cache.insert_verified_qc(digest.clone());
+        assert!(cache.is_verified_qc(&digest));
+
+        // Another signature of the same vote is verified again.
+        qc.signature.address_bitmap = Bytes::from(vec![0b0111_0000]);
+        let other = cache.qc(&crypto, &qc).unwrap();
+        assert!(!cache.is_verified_qc(&other));
+
+        cache.flush(3);
+        assert!(!cache.is_verified_qc(&digest));
+    }
+}
@@ -0,0 +1,99 @@
+use futures::channel::oneshot;
+use serde::{Deserialize, Serialize};
+
+use crate::smr::smr_types::Step;
+use crate::state::snapshot::ConsensusSnapshot;
+use crate::types::{Address, Hash, Node, VoteType};
+use crate::{Codec, ConsensusResult, EffectiveConfig};
+
+/// A request of a dump of the state, which the state answers by the sender.
+pub(crate) enum DumpRequest<T: Codec> {
+    /// The dump for the incident debugging.
+    State(oneshot::Sender<StateDump>),
+    /// The snapshot of the consensus state.
+    Snapshot(oneshot::Sender<ConsensusResult<ConsensusSnapshot<T>>>),
+    /// The configuration that the state runs with.
+    Config(oneshot::Sender<EffectiveConfig>),
+}
+
+/// A snapshot of the state of an instance for the incident debugging, which is serializable
+/// to JSON for an admin endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StateDump {
+    /// The current height.
+    pub height: u64,
+    /// The current round.
+    pub round: u64,
+    /// The last step that the node entered in the round.
+    pub step: Step,
+    /// Whether the node is the leader of the round.
+    pub is_leader: bool,
+    /// Whether the node is an authority of the height.
+    pub consensus_power: bool,
+    /// The lock of the node.
+    pub lock: Option<LockDump>,
+    /// The authority list of the height.
+    pub authority_list: Vec<Node>,
+    /// The votes of the height collected by the node, ordered by the round and the voter.
+    pub votes: Vec<VoteDump>,
+    /// The QCs of the height known by the node.
+    pub qcs: Vec<QcDump>,
+    /// The chokes of the height collected by the node.
+    pub chokes: Vec<ChokeDump>,
+    /// The number of the buffered messages of the future heights and rounds.
+    pub future_messages: usize,
+    /// The milliseconds since the node entered the step.
+    pub step_elapsed_ms: u64,
+    /// The milliseconds of the timeout of the step, zero if the step has no timer.
+    pub step_timeout_ms: u64,
+    /// The milliseconds left until the deadline of the step by the timer, zero if it has
+    /// passed or the step has no timer.
+    pub step_remaining_ms: u64,
+}
+
+/// The lock of a node.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LockDump {
+    /// The round of the prevote QC that the node is locked on.
+    pub round: u64,
+    /// The locked block hash.
+    #[serde(with = "crate::serde_hex")]
+    pub block_hash: Hash,
+}
+
+/// A vote collected by a node.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VoteDump {
+    /// The round of the vote.
+    pub round: u64,
+    /// The type of the vote.
+    pub vote_type: VoteType,
+    /// The voted block hash, which is empty for nil.
+    #[serde(with = "crate::serde_hex")]
+    pub block_hash: Hash,
+    /// The voter.
+    #[serde(with = "crate::serde_hex")]
+    pub voter: Address,
+}
+
+/// A QC known by a node.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QcDump {
+    /// The round of the QC.
+    pub round: u64,
+    /// The type of the QC.
+    pub vote_type: VoteType,
+    /// The block hash of the QC, which is empty for nil.
+    #[serde(with = "crate::serde_hex")]
+    pub block_hash: Hash,
+}
+
+/// A choke collected by a node.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChokeDump {
+    /// The round of the choke.
+    pub round: u64,
+    /// The authority that chokes.
+    #[serde(with = "crate::serde_hex")]
+    pub address: Address,
+}
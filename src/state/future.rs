@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use creep::Context;
+use parking_lot::Mutex;
+
+use crate::types::{Address, MlmMsg};
+use crate::Codec;
+
+/// The counters of the buffer of the messages ahead of the node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FutureBufferStats {
+    /// The messages in the buffer.
+    pub buffered: usize,
+    /// The messages replayed when the node reaches their height and round.
+    pub replayed: u64,
+    /// The messages evicted by the caps.
+    pub evicted: u64,
+    /// The messages dropped since the node has passed their height without reaching it.
+    pub expired: u64,
+}
+
+/// The stats of the future buffer, which are updated by the state and read by the instance.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FutureAccount(Arc<Mutex<FutureBufferStats>>);
+
+impl FutureAccount {
+    pub(crate) fn new() -> Self {
+        FutureAccount::default()
+    }
+
+    pub(crate) fn stats(&self) -> FutureBufferStats {
+        self.0.lock().clone()
+    }
+}
+
+/// A bounded buffer of the messages of the future heights and rounds, keyed by the address of
+/// the signer. A peer over its cap evicts its own least recent message, and the buffer over the
+/// total cap evicts the least recent message of all. The messages are taken out to replay when
+/// the node reaches their height and round.
+#[derive(Debug)]
+pub struct FutureBuffer<T: Codec> {
+    per_peer: usize,
+    total: usize,
+    seq: u64,
+    entries: BTreeMap<u64, Entry<T>>,
+    by_view: BTreeSet<(u64, u64, u64)>,
+    by_peer: HashMap<Address, BTreeSet<u64>>,
+    account: FutureAccount,
+}
+
+#[derive(Debug)]
+struct Entry<T: Codec> {
+    height: u64,
+    round: u64,
+    peer: Address,
+    item: (Context, MlmMsg<T>),
+}
+
+impl<T: Codec> FutureBuffer<T> {
+    /// Create a buffer with the caps of each peer and of all, zero per peer means no cap of the
+    /// peers.
+    pub fn new(per_peer: usize, total: usize) -> Self {
+        FutureBuffer {
+            per_peer,
+            total,
+            seq: 0,
+            entries: BTreeMap::new(),
+            by_view: BTreeSet::new(),
+            by_peer: HashMap::new(),
+            account: FutureAccount::new(),
+        }
+    }
+
+    pub(crate) fn set_account(&mut self, account: FutureAccount) {
+        self.account = account;
+    }
+
+    /// The number of the buffered messages.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the buffer is enabled by a total cap.
+    pub fn is_enabled(&self) -> bool {
+        self.total > 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Buffer a message of the given height and round from the peer.
+    pub fn push(
+        &mut self,
+        height: u64,
+        round: u64,
+        peer: Address,
+        item: (Context, MlmMsg<T>),
+    ) {
+        let seq = self.seq;
+        self.seq += 1;
+        self.by_view.insert((height, round, seq));
+        self.by_peer.entry(peer.clone()).or_default().insert(seq);
+        self.entries.insert(
+            seq,
+            Entry {
+                height,
+                round,
+                peer: peer.clone(),
+                item,
+            },
+        );
+
+        let mut evicted = 0;
+        while self.per_peer > 0 {
+            let oldest = match self.by_peer.get(&peer) {
+                Some(seqs) if seqs.len() > self.per_peer => seqs.iter().next().copied(),
+                _ => None,
+            };
+            match oldest {
+                Some(seq) => {
+                    self.remove(seq);
+                    evicted += 1;
+                }
+                None => break,
+            }
+        }
+        while self.entries.len() > self.total {
+            match self.entries.keys().next().copied() {
+                Some(seq) => {
+                    self.remove(seq);
+                    evicted += 1;
+                }
+                None => break,
+            }
+        }
+
+        let mut stats = self.account.0.lock();
+        stats.buffered = self.entries.len();
+        stats.evicted += evicted;
+    }
+
+    /// Take the messages that are due at the given height and round in the order of their
+    /// height, round and arrival. The messages of the lower heights are dropped.
+    pub fn take(&mut self, height: u64, round: u64) -> Vec<(Context, MlmMsg<T>)> {
+        let mut due = Vec::new();
+        let mut expired = 0;
+        while let Some((msg_height, msg_round, seq)) =
+            self.by_view.iter().next().copied()
+        {
+            if (msg_height, msg_round) > (height, round) {
+                break;
+            }
+
+            match self.remove(seq) {
+                Some(_) if msg_height < height => expired += 1,
+                Some(entry) => due.push(entry.item),
+                None => {
+                    self.by_view.remove(&(msg_height, msg_round, seq));
+                }
+            }
+        }
+
+        if !due.is_empty() || expired > 0 {
+            let mut stats = self.account.0.lock();
+            stats.buffered = self.entries.len();
+            stats.replayed += due.len() as u64;
+            stats.expired += expired;
+        }
+        due
+    }
+
+    fn remove(&mut self, seq: u64) -> Option<Entry<T>> {
+        let entry = self.entries.remove(&seq)?;
+        self.by_view.remove(&(entry.height, entry.round, seq));
+        if let Some(seqs) = self.by_peer.get_mut(&entry.peer) {
+            seqs.remove(&seq);
+            if seqs.is_empty() {
+                self.by_peer.remove(&entry.peer);
+            }
+        }
+        Some(entry)
+    }
+}
+
+/// The signer of a message from the network.
+pub fn signer<T: Codec>(msg: &MlmMsg<T>) -> Option<&Address> {
+    match msg {
+        MlmMsg::SignedProposal(sp) => Some(&sp.proposal.proposer),
+        MlmMsg::SignedVote(sv) => Some(&sv.voter),
+        MlmMsg::AggregatedVote(av) => Some(&av.leader),
+        MlmMsg::SignedChoke(sc) => Some(&sc.address),
+        MlmMsg::SignedHeartbeat(sh) => Some(&sh.heartbeat.address),
+        MlmMsg::SignedKeyRotation(skr) => Some(&skr.rotation.address),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use creep::Context;
+
+    use super::{FutureAccount, FutureBuffer, FutureBufferStats};
+    use crate::types::{Address, Hash, MlmMsg, SignedVote, Vote, VoteType};
+
+    fn vote(height: u64, round: u64, voter: u8) -> (Context, MlmMsg<Bytes>) {
+        let msg = MlmMsg::SignedVote(SignedVote {
+            signature: Bytes::new(),
+            vote: Vote {
+                height,
+                round,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::new(),
+            },
+            voter: Address::from_slice(&[voter]).unwrap(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        });
+        (Context::new(), msg)
+    }
+
+    fn push(buffer: &mut FutureBuffer<Bytes>, height: u64, round: u64, voter: u8) {
+        buffer.push(
+            height,
+            round,
+            Address::from_slice(&[voter]).unwrap(),
+            vote(height, round, voter),
+        );
+    }
+
+    #[test]
+    fn test_future_buffer() {
+        let account = FutureAccount::new();
+        let mut buffer = FutureBuffer::new(2, 4);
+        buffer.set_account(account.clone());
+
+        // The first vote of the peer 1 is evicted by its cap, and then the first vote of the
+        // peer 2 by the total cap.
+        push(&mut buffer, 3, 0, 1);
+        push(&mut buffer, 2, 1, 2);
+        push(&mut buffer, 2, 0, 1);
+        push(&mut buffer, 3, 1, 1);
+        push(&mut buffer, 4, 0, 2);
+        push(&mut buffer, 2, 2, 3);
+        assert_eq!(account.stats().buffered, 4);
+
+        let due = buffer.take(2, 1);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].1, vote(2, 0, 1).1);
+        let due = buffer.take(3, 5);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].1, vote(3, 1, 1).1);
+        assert_eq!(
+            account.stats(),
+            FutureBufferStats {
+                buffered: 1,
+                replayed: 2,
+                evicted: 2,
+                expired: 1,
+            }
+        );
+        assert_eq!(buffer.take(4, 0).len(), 1);
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::event::now_millis;
+use crate::types::{Address, Heartbeat};
+
+/// The liveness of a validator by the heartbeats received from it, for the dashboards of the
+/// applications.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorLiveness {
+    /// The address of the validator.
+    #[serde(with = "crate::serde_hex")]
+    pub address: Address,
+    /// The height of the validator in its last heartbeat.
+    pub height: u64,
+    /// The round of the validator in its last heartbeat.
+    pub round: u64,
+    /// Milliseconds since the Unix epoch when the last heartbeat is signed.
+    pub signed_at: u64,
+    /// Milliseconds since the Unix epoch when the last heartbeat is received.
+    pub received_at: u64,
+    /// The heartbeats received from the validator.
+    pub heartbeats: u64,
+}
+
+/// The liveness of the validators, which is updated by the state and read by the instance.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LivenessAccount(Arc<Mutex<BTreeMap<Address, ValidatorLiveness>>>);
+
+impl LivenessAccount {
+    pub(crate) fn new() -> Self {
+        LivenessAccount::default()
+    }
+
+    pub(crate) fn stats(&self) -> Vec<ValidatorLiveness> {
+        self.0.lock().values().cloned().collect()
+    }
+
+    /// Record a verified heartbeat of a validator. Return false if it is not newer than the
+    /// last one of the validator, which is a replayed or duplicated heartbeat.
+    pub(crate) fn record(&self, heartbeat: &Heartbeat) -> bool {
+        let mut validators = self.0.lock();
+        let liveness =
+            validators
+                .entry(heartbeat.address.clone())
+                .or_insert_with(|| ValidatorLiveness {
+                    address: heartbeat.address.clone(),
+                    height: 0,
+                    round: 0,
+                    signed_at: 0,
+                    received_at: 0,
+                    heartbeats: 0,
+                });
+        if liveness.heartbeats > 0 && heartbeat.timestamp <= liveness.signed_at {
+            return false;
+        }
+
+        liveness.height = heartbeat.height;
+        liveness.round = heartbeat.round;
+        liveness.signed_at = heartbeat.timestamp;
+        liveness.received_at = now_millis();
+        liveness.heartbeats += 1;
+        true
+    }
+
+    /// Remove the validators out of the authority list.
+    pub(crate) fn retain(&self, authorities: &[Address]) {
+        self.0
+            .lock()
+            .retain(|address, _| authorities.contains(address));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LivenessAccount;
+    use crate::types::{Address, Heartbeat};
+
+    fn heartbeat(address: u8, height: u64, timestamp: u64) -> Heartbeat {
+        Heartbeat {
+            height,
+            round: 0,
+            timestamp,
+            address: Address::from_slice(&[address]).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_liveness_account() {
+        let account = LivenessAccount::new();
+        assert!(account.record(&heartbeat(2, 5, 1000)));
+        assert!(account.record(&heartbeat(1, 4, 900)));
+        assert!(account.record(&heartbeat(1, 6, 1100)));
+
+        // The replayed and the duplicated heartbeats are ignored.
+        assert!(!account.record(&heartbeat(1, 5, 1000)));
+        assert!(!account.record(&heartbeat(1, 6, 1100)));
+
+        let stats = account.stats();
+        assert_eq!(
+            stats
+                .iter()
+                .map(|liveness| (
+                    liveness.address[0],
+                    liveness.height,
+                    liveness.heartbeats
+                ))
+                .collect::<Vec<_>>(),
+            vec![(1, 6, 2), (2, 5, 1)]
+        );
+        assert_eq!(stats[0].signed_at, 1100);
+
+        account.retain(&[Address::from_slice(&[2]).unwrap()]);
+        assert_eq!(account.stats().len(), 1);
+    }
+}
@@ -0,0 +1,330 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+
+use creep::Context;
+use futures::stream::FusedStream;
+use futures::Stream;
+
+use crate::types::MlmMsg;
+use crate::Codec;
+
+/// The max number of messages drained from the inner stream in one poll.
+pub(crate) const DRAIN_LIMIT: usize = 256;
+
+/// A message sent to the instance, or a batch of the messages sent together with one context.
+/// A batch is a single item of the message channel, so that it is enqueued atomically and
+/// none of the other messages of the channel are in between.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Inbound<T: Codec> {
+    Msg(MlmMsg<T>),
+    Batch(Vec<MlmMsg<T>>),
+}
+
+impl<T: Codec> From<MlmMsg<T>> for Inbound<T> {
+    fn from(msg: MlmMsg<T>) -> Self {
+        Inbound::Msg(msg)
+    }
+}
+
+/// A stream of messages that yields the pending ones by priority instead of the arrival
+/// order. The control messages and the rich status go first. Then the QCs, the proposals, the
+/// votes and the chokes of the current round or above, and the same of the old rounds and the
+/// other heights, and the heartbeats at last. The messages of the same priority keep their order. The priority is
+/// given by the view when the message is drained from the inner stream. A batch of the inner
+/// stream is split into its messages, which share the context of the batch.
+pub struct PriorityInbox<T: Codec, S> {
+    inner: S,
+    inner_done: bool,
+    queue: BinaryHeap<Entry<T>>,
+    seq: u64,
+    height: u64,
+    round: u64,
+    drain_limit: usize,
+}
+
+impl<T, S, M> PriorityInbox<T, S>
+where
+    T: Codec,
+    S: Stream<Item = (Context, M)> + Unpin,
+    M: Into<Inbound<T>>,
+{
+    pub fn new(inner: S) -> Self {
+        PriorityInbox {
+            inner,
+            inner_done: false,
+            queue: BinaryHeap::new(),
+            seq: 0,
+            height: 0,
+            round: 0,
+            drain_limit: DRAIN_LIMIT,
+        }
+    }
+
+    /// Set the max number of messages drained in one poll, zero means the default.
+    pub fn set_drain_limit(&mut self, limit: usize) {
+        self.drain_limit = if limit == 0 { DRAIN_LIMIT } else { limit };
+    }
+
+    /// The inner stream, such as to close the channels that it receives from.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Set the current height and round of the state.
+    pub fn set_view(&mut self, height: u64, round: u64) {
+        self.height = height;
+        self.round = round;
+    }
+
+    fn push(&mut self, item: (Context, MlmMsg<T>)) {
+        let priority = priority(&item.1, self.height, self.round);
+        self.queue.push(Entry {
+            priority,
+            seq: self.seq,
+            item,
+        });
+        self.seq += 1;
+    }
+}
+
+impl<T, S, M> Stream for PriorityInbox<T, S>
+where
+    T: Codec,
+    S: Stream<Item = (Context, M)> + Unpin,
+    M: Into<Inbound<T>>,
+{
+    type Item = (Context, MlmMsg<T>);
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mut drained = 0;
+        while !this.inner_done && drained < this.drain_limit {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some((ctx, item))) => {
+                    match item.into() {
+                        Inbound::Msg(msg) => this.push((ctx, msg)),
+                        Inbound::Batch(msgs) => {
+                            for msg in msgs {
+                                this.push((ctx.clone(), msg));
+                            }
+                        }
+                    }
+                    drained += 1;
+                }
+                Poll::Ready(None) => this.inner_done = true,
+                Poll::Pending => break,
+            }
+        }
+
+        match this.queue.pop() {
+            Some(entry) => Poll::Ready(Some(entry.item)),
+            None if this.inner_done => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T, S, M> FusedStream for PriorityInbox<T, S>
+where
+    T: Codec,
+    S: Stream<Item = (Context, M)> + Unpin,
+    M: Into<Inbound<T>>,
+{
+    fn is_terminated(&self) -> bool {
+        self.inner_done && self.queue.is_empty()
+    }
+}
+
+// The messages are never pinned, only the inner stream is polled in place.
+impl<T: Codec, S: Unpin> Unpin for PriorityInbox<T, S> {}
+
+struct Entry<T: Codec> {
+    priority: u8,
+    seq: u64,
+    item: (Context, MlmMsg<T>),
+}
+
+impl<T: Codec> Entry<T> {
+    fn key(&self) -> (u8, u64) {
+        (self.priority, self.seq)
+    }
+}
+
+impl<T: Codec> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T: Codec> Eq for Entry<T> {}
+
+impl<T: Codec> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Codec> Ord for Entry<T> {
+    /// The heap pops the greatest entry, which is the one of the lowest key.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+/// The priority of a message, the lower the earlier.
+fn priority<T: Codec>(msg: &MlmMsg<T>, height: u64, round: u64) -> u8 {
+    let (class, msg_round) = match msg {
+        MlmMsg::AggregatedVote(qc) => (0, qc.round),
+        MlmMsg::SignedProposal(sp) => (1, sp.proposal.round),
+        MlmMsg::SignedVote(sv) => (2, sv.vote.round),
+        MlmMsg::SignedChoke(sc) => (3, sc.choke.round),
+        MlmMsg::CompactProposal(cp) => (1, cp.round),
+        MlmMsg::GetBlock(gb) => (1, gb.round),
+        MlmMsg::BlockResponse(br) => (1, br.round),
+        MlmMsg::PartedProposal(pp) => (1, pp.proposal.round),
+        MlmMsg::BlockPart(bp) => (1, bp.round),
+        // The heartbeats and the key rotations are taken after the consensus messages.
+        MlmMsg::SignedHeartbeat(_) | MlmMsg::SignedKeyRotation(_) => return 9,
+        _ => return 0,
+    };
+
+    if msg.get_height() == height && msg_round >= round {
+        1 + class
+    } else {
+        5 + class
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use creep::Context;
+    use futures::channel::mpsc::unbounded;
+    use futures::StreamExt;
+
+    use super::{Inbound, PriorityInbox};
+    use crate::types::{
+        Address, AggregatedSignature, AggregatedVote, Choke, Hash, MlmMsg, SignedChoke,
+        SignedVote, UpdateFrom, Vote, VoteType,
+    };
+
+    type Msg = MlmMsg<Bytes>;
+
+    fn vote(height: u64, round: u64) -> Msg {
+        MlmMsg::SignedVote(SignedVote {
+            signature: Bytes::new(),
+            vote: Vote {
+                height,
+                round,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::new(),
+            },
+            voter: Address::default(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        })
+    }
+
+    fn qc(height: u64, round: u64) -> Msg {
+        MlmMsg::AggregatedVote(AggregatedVote {
+            signature: AggregatedSignature {
+                signature: Bytes::new(),
+                address_bitmap: Bytes::new(),
+            },
+            vote_type: VoteType::Prevote,
+            height,
+            round,
+            block_hash: Hash::new(),
+            leader: Address::default(),
+            extensions: Vec::new(),
+        })
+    }
+
+    fn choke(height: u64, round: u64) -> Msg {
+        MlmMsg::SignedChoke(SignedChoke {
+            signature: Bytes::new(),
+            choke: Choke {
+                height,
+                round,
+                from: UpdateFrom::PrevoteQC(match qc(height, round) {
+                    MlmMsg::AggregatedVote(qc) => qc,
+                    _ => unreachable!(),
+                }),
+            },
+            address: Address::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_priority_inbox() {
+        let (tx, rx) = unbounded();
+        let mut inbox = PriorityInbox::new(rx);
+        inbox.set_view(2, 1);
+
+        let msgs = [
+            vote(2, 0),
+            choke(2, 1),
+            vote(3, 0),
+            vote(2, 1),
+            qc(2, 2),
+            MlmMsg::Stop,
+            vote(2, 1),
+        ];
+        for msg in msgs.iter() {
+            tx.unbounded_send((Context::new(), msg.clone())).unwrap();
+        }
+        drop(tx);
+
+        let mut order = Vec::new();
+        while let Some((_, msg)) = inbox.next().await {
+            order.push(msgs.iter().position(|m| *m == msg).unwrap());
+        }
+        // The duplicated vote keeps its order in the same priority.
+        assert_eq!(order, vec![5, 4, 3, 3, 1, 0, 2]);
+
+        // Draining one message in a poll keeps the arrival order.
+        let (tx, rx) = unbounded();
+        let mut inbox = PriorityInbox::new(rx);
+        inbox.set_view(2, 1);
+        inbox.set_drain_limit(1);
+        for msg in msgs.iter() {
+            tx.unbounded_send((Context::new(), msg.clone())).unwrap();
+        }
+        drop(tx);
+
+        let mut order = Vec::new();
+        while let Some((_, msg)) = inbox.next().await {
+            order.push(msgs.iter().position(|m| *m == msg).unwrap());
+        }
+        assert_eq!(order, vec![0, 1, 2, 3, 4, 5, 3]);
+
+        // A batch is split into its messages in order, which share the context of the batch.
+        let (tx, rx) = unbounded();
+        let mut inbox = PriorityInbox::new(rx);
+        inbox.set_view(2, 1);
+        let batch = vec![vote(2, 1), vote(2, 2), vote(2, 3)];
+        let ctx = Context::new().with_value("batch", 7usize);
+        tx.unbounded_send((ctx, Inbound::Batch(batch.clone())))
+            .unwrap();
+        tx.unbounded_send((Context::new(), Inbound::Msg(vote(2, 4))))
+            .unwrap();
+        drop(tx);
+
+        let mut received = Vec::new();
+        while let Some((ctx, msg)) = inbox.next().await {
+            received.push((ctx.get::<usize>("batch").copied(), msg));
+        }
+        let expected = batch
+            .into_iter()
+            .map(|msg| (Some(7), msg))
+            .chain(std::iter::once((None, vote(2, 4))))
+            .collect::<Vec<_>>();
+        assert_eq!(received, expected);
+    }
+}
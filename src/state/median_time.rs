@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+const DEFAULT_WINDOW: usize = 11;
+
+/// The median of the timestamps, which is the middle one of them in order, or the later of
+/// the two middle ones of an even number of them. It is none if there is no timestamp.
+pub fn median_time(timestamps: &[u64]) -> Option<u64> {
+    let mut sorted = timestamps.to_vec();
+    sorted.sort_unstable();
+    sorted.get(sorted.len() / 2).copied()
+}
+
+/// The timestamps of the recent commits, whose median time the timestamp of a proposal must
+/// be later than.
+#[derive(Debug)]
+pub(crate) struct MedianTime {
+    window: usize,
+    timestamps: VecDeque<u64>,
+}
+
+impl MedianTime {
+    pub(crate) fn new(window: usize) -> Self {
+        let window = if window == 0 { DEFAULT_WINDOW } else { window };
+        MedianTime {
+            window,
+            timestamps: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// The number of the recent commits in the window.
+    pub(crate) fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Record the timestamp of a commit, and forget the ones out of the window.
+    pub(crate) fn on_commit(&mut self, timestamp: u64) {
+        if self.timestamps.len() == self.window {
+            self.timestamps.pop_front();
+        }
+        self.timestamps.push_back(timestamp);
+    }
+
+    /// The median time of the recent commits.
+    pub(crate) fn median(&self) -> Option<u64> {
+        let timestamps = self.timestamps.iter().copied().collect::<Vec<_>>();
+        median_time(&timestamps)
+    }
+
+    /// The earliest valid timestamp of a proposal, which is zero before the first commit.
+    pub(crate) fn min_timestamp(&self) -> u64 {
+        self.median().map_or(0, |median| median + 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{median_time, MedianTime};
+
+    #[test]
+    fn test_median_time() {
+        assert_eq!(median_time(&[]), None);
+        assert_eq!(median_time(&[5, 1, 3]), Some(3));
+        assert_eq!(median_time(&[4, 1, 3, 2]), Some(3));
+
+        let mut median = MedianTime::new(3);
+        assert_eq!(median.min_timestamp(), 0);
+        for timestamp in [10, 30, 20, 100] {
+            median.on_commit(timestamp);
+        }
+        // The first commit is out of the window.
+        assert_eq!(median.median(), Some(30));
+        assert_eq!(median.min_timestamp(), 31);
+    }
+}
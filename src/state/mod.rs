@@ -1,6 +1,54 @@
+/// The running aggregated signatures of the votes and the timing of the QCs.
+pub mod aggregation;
+/// The archive of the commit proofs of the recent heights.
+pub mod archive;
 ///
 mod collection;
+/// The connectivity of the node to the authorities.
+pub mod connectivity;
+/// The seen-set of the votes dropping the duplicates before the verification.
+pub mod dedup;
+/// The digests of the signed preimages shared by the verification and the state.
+pub mod digest;
+/// The snapshot of the state for the incident debugging.
+pub mod dump;
+/// The bounded buffer of the messages ahead of the node.
+pub mod future;
+/// The liveness of the validators by their heartbeats.
+pub mod heartbeat;
+/// The priority inbox of the messages fed to the state.
+pub(crate) mod inbox;
+/// The median time of the recent commits validating the proposal timestamps.
+pub mod median_time;
+/// Candidate proposals of the redundant proposers and of the VRF leader mode.
+mod multi_proposal;
 ///
-mod parallel;
+pub mod parallel;
+/// The parts of the blocks of the parted proposals.
+mod parts;
 ///
 pub mod process;
+/// The cumulative counters of the protocol for the explorers.
+pub mod protocol;
+/// The re-broadcast of the QCs received from the network.
+pub mod qc_gossip;
+/// The rate limit of the messages of each signer before the verification.
+pub mod rate_limit;
+/// The consecutive proposal failures of the proposers and their skips.
+pub mod reliability;
+/// The views of the authorities proving a quorum ahead of the node.
+mod resync;
+/// The past heights evicted from the caches and handed to the adapter.
+pub mod retention;
+/// The retries of the failed adapter calls.
+mod retry;
+/// The snapshot of the consensus state to move a node.
+pub mod snapshot;
+/// The diagnostic dump of a node that makes no height progress.
+pub mod stall;
+/// The timing report of the committed heights.
+pub mod timing;
+/// The tuning of the effective interval by the recent block times.
+mod tuner;
+/// The acceptance window of the message heights before the verification.
+pub mod window;
@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use creep::Context;
+
+use crate::types::SignedProposal;
+use crate::Codec;
+
+/// The rank of a candidate proposal, the lower the preferred. The candidates of a round are
+/// all ranked in the same way.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rank {
+    /// The index of a designated proposer in the rank order of the round.
+    Designated(usize),
+    /// The VRF output of a proposer in the VRF leader mode.
+    Vrf(Bytes),
+}
+
+/// A collector of the signed proposals from the redundant proposers of the current round. The
+/// proposals are indexed by the rank of their proposers, so that the lowest ranked one is
+/// always preferred no matter in which order they arrive. Once a proposal has been handed to
+/// SMR, the round is decided and later proposals are ignored.
+#[derive(Debug)]
+pub struct CandidateProposals<T: Codec> {
+    height: u64,
+    round: u64,
+    candidates: BTreeMap<Rank, (SignedProposal<T>, Context)>,
+    decided: bool,
+}
+
+impl<T> CandidateProposals<T>
+where
+    T: Codec,
+{
+    /// Create a new candidate proposals collector.
+    pub fn new() -> Self {
+        CandidateProposals {
+            height: 0,
+            round: 0,
+            candidates: BTreeMap::new(),
+            decided: false,
+        }
+    }
+
+    /// Clear all the candidates and start collecting for the given height and round.
+    pub fn reset(&mut self, height: u64, round: u64) {
+        self.height = height;
+        self.round = round;
+        self.candidates.clear();
+        self.decided = false;
+    }
+
+    /// Insert a candidate proposal of the given rank. Return `false` if the round has been
+    /// decided, the proposal does not belong to the collecting round, or there is already a
+    /// proposal of the rank.
+    pub fn insert(
+        &mut self,
+        rank: Rank,
+        ctx: Context,
+        signed_proposal: SignedProposal<T>,
+    ) -> bool {
+        if self.decided
+            || signed_proposal.proposal.height != self.height
+            || signed_proposal.proposal.round != self.round
+            || self.candidates.contains_key(&rank)
+        {
+            return false;
+        }
+
+        self.candidates.insert(rank, (signed_proposal, ctx));
+        true
+    }
+
+    /// Get the lowest rank of the collected candidates.
+    pub fn best_rank(&self) -> Option<&Rank> {
+        if self.decided {
+            return None;
+        }
+        self.candidates.keys().next()
+    }
+
+    /// Take the lowest ranked candidate and mark the round as decided.
+    pub fn take_best(&mut self) -> Option<(SignedProposal<T>, Context)> {
+        let rank = self.best_rank()?.clone();
+        self.decided = true;
+        self.candidates.remove(&rank)
+    }
+
+    /// Mark the round as decided without a candidate, which happens when the primary proposal
+    /// is handed to SMR directly.
+    pub fn decide(&mut self) {
+        self.decided = true;
+    }
+
+    /// If a proposal of the collecting round has been handed to SMR.
+    pub fn is_decided(&self) -> bool {
+        self.decided
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::Infallible;
+
+    use bytes::Bytes;
+    use creep::Context;
+
+    use super::{CandidateProposals, Rank};
+    use crate::types::{Address, Hash, Proposal, SignedProposal};
+    use crate::Codec;
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Block(u8);
+
+    impl Codec for Block {
+        type Error = Infallible;
+
+        fn encode(&self) -> Result<Bytes, Self::Error> {
+            Ok(Bytes::from(vec![self.0]))
+        }
+
+        fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+            Ok(Block(data[0]))
+        }
+    }
+
+    fn gen_signed_proposal(
+        height: u64,
+        round: u64,
+        content: u8,
+    ) -> SignedProposal<Block> {
+        SignedProposal {
+            signature: Bytes::from(vec![content]),
+            proposal: Proposal {
+                height,
+                round,
+                content: Block(content),
+                block_hash: Hash::from(vec![content]),
+                lock: None,
+                proposer: Address::from_slice(&[content]).unwrap(),
+                timestamp: None,
+            },
+            timeout_cert: None,
+            vrf_proof: Bytes::new(),
+        }
+    }
+
+    fn insert_designated(
+        candidates: &mut CandidateProposals<Block>,
+        rank: usize,
+        signed_proposal: SignedProposal<Block>,
+    ) -> bool {
+        candidates.insert(Rank::Designated(rank), Context::new(), signed_proposal)
+    }
+
+    #[test]
+    fn test_prefer_lowest_rank() {
+        // Every arrival order of the same candidates must choose the same proposal, or honest
+        // nodes prevote for different blocks.
+        let orders = vec![vec![0, 1, 2], vec![2, 1, 0], vec![1, 2, 0], vec![2, 0, 1]];
+        for order in orders.into_iter() {
+            let mut candidates = CandidateProposals::new();
+            candidates.reset(1, 0);
+            for rank in order.into_iter() {
+                let proposal = gen_signed_proposal(1, 0, rank as u8);
+                assert!(insert_designated(&mut candidates, rank, proposal));
+            }
+
+            assert_eq!(candidates.best_rank(), Some(&Rank::Designated(0)));
+            let (best, _) = candidates.take_best().unwrap();
+            assert_eq!(best, gen_signed_proposal(1, 0, 0));
+            assert!(candidates.is_decided());
+        }
+
+        let mut candidates = CandidateProposals::new();
+        candidates.reset(1, 0);
+        assert!(insert_designated(&mut candidates, 2, gen_signed_proposal(1, 0, 2)));
+        assert!(insert_designated(&mut candidates, 1, gen_signed_proposal(1, 0, 1)));
+        let (best, _) = candidates.take_best().unwrap();
+        assert_eq!(best, gen_signed_proposal(1, 0, 1));
+    }
+
+    #[test]
+    fn test_prefer_lowest_vrf_output() {
+        // The VRF outputs are compared as bytes, whatever the order of the proposers.
+        let mut candidates = CandidateProposals::new();
+        candidates.reset(1, 0);
+        let outputs = [vec![0x02, 0x00], vec![0x01, 0xff], vec![0x01, 0xfe]];
+        for (i, output) in outputs.into_iter().enumerate() {
+            let rank = Rank::Vrf(Bytes::from(output));
+            let proposal = gen_signed_proposal(1, 0, i as u8 + 1);
+            assert!(candidates.insert(rank, Context::new(), proposal));
+        }
+
+        let (best, _) = candidates.take_best().unwrap();
+        assert_eq!(best, gen_signed_proposal(1, 0, 3));
+    }
+
+    #[test]
+    fn test_decided_round() {
+        // A decided round never hands a second proposal to SMR, otherwise a node could prevote
+        // twice in one round.
+        let mut candidates = CandidateProposals::new();
+        candidates.reset(1, 0);
+        assert!(insert_designated(&mut candidates, 1, gen_signed_proposal(1, 0, 1)));
+        assert!(candidates.take_best().is_some());
+        assert!(!insert_designated(&mut candidates, 0, gen_signed_proposal(1, 0, 0)));
+        assert!(candidates.take_best().is_none());
+
+        candidates.reset(1, 1);
+        candidates.decide();
+        assert!(!insert_designated(&mut candidates, 1, gen_signed_proposal(1, 1, 1)));
+        assert_eq!(candidates.best_rank(), None);
+    }
+
+    #[test]
+    fn test_reject_equivocation() {
+        // A proposer can only occupy its rank once, and proposals of other rounds are ignored.
+        let mut candidates = CandidateProposals::new();
+        candidates.reset(2, 3);
+        assert!(insert_designated(&mut candidates, 1, gen_signed_proposal(2, 3, 1)));
+        assert!(!insert_designated(&mut candidates, 1, gen_signed_proposal(2, 3, 5)));
+        assert!(!insert_designated(&mut candidates, 0, gen_signed_proposal(2, 4, 0)));
+        assert!(!insert_designated(&mut candidates, 0, gen_signed_proposal(3, 3, 0)));
+
+        let (best, _) = candidates.take_best().unwrap();
+        assert_eq!(best, gen_signed_proposal(2, 3, 1));
+    }
+}
@@ -1,130 +1,414 @@
 use std::sync::Arc;
 
-use bytes::Bytes;
 use creep::Context;
 use futures::channel::mpsc::UnboundedSender;
-use muta_apm::derive::tracing_span;
+use futures::future::BoxFuture;
 
-use crate::types::{Address, AggregatedVote, MlmMsg};
+use crate::error::ConsensusError;
+use crate::event_sink::{ConsensusEvent, ConsensusEventSink};
+use crate::multisig::{verify_qc_signature, verify_vote_extensions};
+use crate::state::digest::DigestCache;
+use crate::telemetry::{SpanAttrs, Trace};
+use crate::types::{
+    Address, AggregatedChoke, AggregatedVote, ContextExt, Hash, MlmMsg, Signature,
+    SignedVote, VoteType,
+};
 use crate::utils::auth_manage::AuthorityManage;
-use crate::{Codec, ConsensusResult, Crypto};
+use crate::wire::{proposal_preimage, WireCodec};
+use crate::{Codec, Consensus, ConsensusResult, Crypto};
 
-#[tracing_span(kind = "mlm.vreify_sig_pool")]
-pub async fn parallel_verify<T: Codec + 'static, C: Crypto + Sync + 'static>(
+/// The task verifying the signatures of the message, which is spawned on the runtime so the
+/// messages are verified in parallel. The preimages of the signatures are encoded by the
+/// codec. The message is moved into the task and sent back to the state by `tx` once it is
+/// verified, and the authority manage and the digests of the preimages are shared with the
+/// state. The message of an expired context is dropped without the verification, since the
+/// task may wait in the pool until the deadline passes. The extension of a precommit is
+/// verified by the adapter once its signature is verified. An invalid message is reported to
+/// both the event sink and the adapter.
+#[allow(clippy::too_many_arguments)]
+pub fn parallel_verify<T, C, F>(
     ctx: Context,
     msg: MlmMsg<T>,
+    trace: &dyn Trace,
+    sink: Arc<dyn ConsensusEventSink>,
+    adapter: Arc<F>,
     crypto: Arc<C>,
-    authority: AuthorityManage,
+    codec: Arc<dyn WireCodec>,
+    authority: Arc<AuthorityManage>,
+    digests: Arc<DigestCache>,
     tx: UnboundedSender<(Context, MlmMsg<T>)>,
-) {
-    let msg_clone = msg.clone();
-    tokio::spawn(async move {
+) -> BoxFuture<'static, ()>
+where
+    T: Codec + 'static,
+    C: Crypto + Sync + 'static,
+    F: Consensus<T> + 'static,
+{
+    let attrs = SpanAttrs {
+        height: Some(msg.get_height()),
+        round: msg.get_round(),
+        kind: Some(msg.to_string()),
+        ..Default::default()
+    };
+    let ctx = trace.span(ctx, "mlm.verify_sig_pool", attrs);
+    Box::pin(async move {
+        if ctx.is_expired() {
+            return;
+        }
+        let report_invalid = |kind: String, reason: String| {
+            adapter.report_invalid_msg(
+                ctx.clone(),
+                ctx.peer().map(String::from),
+                kind,
+                reason,
+            )
+        };
+        let verifier = Verifier {
+            ctx: &ctx,
+            sink: sink.as_ref(),
+            report_invalid: &report_invalid,
+            crypto: crypto.as_ref(),
+            codec: codec.as_ref(),
+            authority: &authority,
+            digests: &digests,
+        };
+        if !verifier.verify(&msg) {
+            return;
+        }
+        if let MlmMsg::SignedVote(sv) = &msg {
+            if !sv.extension.is_empty()
+                && !verifier
+                    .verify_by_adapter(sv.clone(), adapter.as_ref())
+                    .await
+            {
+                return;
+            }
+        }
+        let _ = tx.unbounded_send((ctx, msg));
+    })
+}
+
+/// The borrowed parts of a verification task, which verifies a message by reference and
+/// reports the invalid one to the event sink and the adapter.
+struct Verifier<'a, C> {
+    ctx: &'a Context,
+    sink: &'a dyn ConsensusEventSink,
+    report_invalid: &'a (dyn Fn(String, String) + Sync),
+    crypto: &'a C,
+    codec: &'a dyn WireCodec,
+    authority: &'a AuthorityManage,
+    digests: &'a DigestCache,
+}
+
+impl<C: Crypto> Verifier<'_, C> {
+    fn verify<T: Codec>(&self, msg: &MlmMsg<T>) -> bool {
         match msg {
             MlmMsg::SignedProposal(sp) => {
-                let hash = crypto.hash(Bytes::from(rlp::encode(&sp.proposal)));
-                if let Err(err) = crypto.verify_signature(
-                    sp.signature.clone(),
+                let proposal = &sp.proposal;
+                let Some(hash) = self.digest(
+                    proposal_preimage(self.codec, proposal)
+                        .map(|preimage| self.crypto.hash(preimage)),
+                ) else {
+                    return false;
+                };
+                if !self.verify_signature(
+                    msg,
+                    &sp.signature,
                     hash,
-                    sp.proposal.proposer.clone(),
+                    &proposal.proposer,
+                    (proposal.height, proposal.round),
                 ) {
-                    log::error!(
-                        "Mlm: verify {:?} proposal signature failed {:?}",
-                        sp,
-                        err
-                    );
-                    return;
+                    return false;
                 }
 
-                if let Some(polc) = sp.proposal.lock {
-                    verify_qc(
-                        ctx.clone(),
-                        crypto,
-                        polc.lock_votes,
-                        authority,
-                        tx.clone(),
-                        msg_clone.clone(),
-                    );
-                } else {
-                    let _ = tx.unbounded_send((ctx, msg_clone));
+                if let Some(tc) = sp.timeout_cert.as_ref() {
+                    let Some(hash) = self.digest(self.digests.choke(
+                        self.crypto,
+                        self.codec,
+                        tc.height,
+                        tc.round,
+                    )) else {
+                        return false;
+                    };
+                    if let Err(err) =
+                        verify_timeout_cert(self.crypto, hash, tc, self.authority)
+                    {
+                        let event = ConsensusEvent::TimeoutCertInvalid {
+                            height: proposal.height,
+                            round: proposal.round,
+                            proposer: proposal.proposer.clone(),
+                            error: err.to_string(),
+                        };
+                        self.report(msg, event);
+                        return false;
+                    }
                 }
+
+                proposal
+                    .lock
+                    .as_ref()
+                    .is_none_or(|polc| self.verify_qc(msg, &polc.lock_votes))
             }
 
             MlmMsg::SignedVote(sv) => {
-                let hash = crypto.hash(Bytes::from(rlp::encode(&sv.vote)));
-                crypto
-                    .verify_signature(sv.signature.clone(), hash, sv.voter.clone())
-                    .map_or_else(
-                        |err| {
-                            log::error!(
-                                "Mlm: verify {:?} vote signature failed {:?}",
-                                sv,
-                                err
-                            );
-                        },
-                        |_| {
-                            let _ = tx.unbounded_send((ctx, msg_clone));
-                        },
-                    );
+                let Some(hash) =
+                    self.digest(self.digests.vote(self.crypto, self.codec, &sv.vote))
+                else {
+                    return false;
+                };
+                self.verify_signature(
+                    msg,
+                    &sv.signature,
+                    hash,
+                    &sv.voter,
+                    (sv.vote.height, sv.vote.round),
+                ) && self.verify_extension(sv)
             }
 
-            MlmMsg::AggregatedVote(qc) => {
-                verify_qc(ctx, crypto, qc, authority, tx, msg_clone);
-            }
+            MlmMsg::AggregatedVote(qc) => self.verify_qc(msg, qc),
 
             MlmMsg::SignedChoke(sc) => {
-                let hash = crypto.hash(Bytes::from(rlp::encode(&sc.choke.to_hash())));
-                crypto
-                    .verify_signature(sc.signature.clone(), hash, sc.address.clone())
-                    .map_or_else(
-                        |err| {
-                            log::error!(
-                                "Mlm: verify {:?} choke signature failed {:?}",
-                                sc,
-                                err
-                            );
-                        },
-                        |_| {
-                            let _ = tx.unbounded_send((ctx, msg_clone));
-                        },
-                    )
+                let Some(hash) = self.digest(self.digests.choke(
+                    self.crypto,
+                    self.codec,
+                    sc.choke.height,
+                    sc.choke.round,
+                )) else {
+                    return false;
+                };
+                self.verify_signature(
+                    msg,
+                    &sc.signature,
+                    hash,
+                    &sc.address,
+                    (sc.choke.height, sc.choke.round),
+                )
+            }
+
+            MlmMsg::SignedHeartbeat(sh) => {
+                let heartbeat = &sh.heartbeat;
+                let Some(hash) = self.digest(
+                    self.codec
+                        .heartbeat_preimage(heartbeat)
+                        .map(|preimage| self.crypto.hash(preimage)),
+                ) else {
+                    return false;
+                };
+                self.verify_signature(
+                    msg,
+                    &sh.signature,
+                    hash,
+                    &heartbeat.address,
+                    (heartbeat.height, heartbeat.round),
+                )
             }
 
-            _ => (),
+            MlmMsg::SignedKeyRotation(skr) => {
+                let rotation = &skr.rotation;
+                let Some(hash) = self.digest(
+                    self.codec
+                        .key_rotation_preimage(rotation)
+                        .map(|preimage| self.crypto.hash(preimage)),
+                ) else {
+                    return false;
+                };
+                self.verify_signature(
+                    msg,
+                    &skr.signature,
+                    hash,
+                    &rotation.address,
+                    (rotation.epoch, 0),
+                )
+            }
+
+            _ => false,
         }
-    });
-}
+    }
+
+    /// Verify the signature of the extension of a vote, which only a precommit of a block
+    /// has.
+    fn verify_extension(&self, sv: &SignedVote) -> bool {
+        let vote = &sv.vote;
+        if sv.extension.is_empty() {
+            return true;
+        }
+
+        let extensible =
+            vote.vote_type == VoteType::Precommit && !vote.block_hash.is_empty();
+        let res = if !extensible {
+            Err(format!("extension of a {:?} vote", vote.vote_type))
+        } else {
+            let Some(preimage) =
+                self.digest(self.codec.vote_extension_preimage(vote, &sv.extension))
+            else {
+                return false;
+            };
+            self.crypto
+                .verify_signature(
+                    sv.extension_signature.clone(),
+                    self.crypto.hash(preimage),
+                    sv.voter.clone(),
+                )
+                .map_err(|err| format!("{:?}", err))
+        };
+        self.check_extension(sv, res)
+    }
+
+    /// Verify the extension of a vote by the application once its signature is verified.
+    async fn verify_by_adapter<T, F>(&self, sv: SignedVote, adapter: &F) -> bool
+    where
+        T: Codec,
+        F: Consensus<T>,
+    {
+        let res = adapter
+            .verify_vote_extension(
+                self.ctx.clone(),
+                sv.vote.height,
+                sv.vote.round,
+                sv.vote.block_hash.clone(),
+                sv.voter.clone(),
+                sv.extension.clone(),
+            )
+            .await
+            .map_err(|err| format!("{:?}", err));
+        self.check_extension(&sv, res)
+    }
 
-fn get_voters(
-    addr_bitmap: &Bytes,
-    authority_manage: AuthorityManage,
-) -> ConsensusResult<Vec<Address>> {
-    authority_manage.is_above_threshold(addr_bitmap)?;
-    authority_manage.get_voters(addr_bitmap)
+    /// Report the extension of a vote that fails the verification.
+    fn check_extension(&self, sv: &SignedVote, res: Result<(), String>) -> bool {
+        match res {
+            Ok(()) => true,
+            Err(error) => {
+                let event = ConsensusEvent::VoteExtensionInvalid {
+                    height: sv.vote.height,
+                    round: sv.vote.round,
+                    voter: sv.voter.clone(),
+                    error,
+                };
+                (self.report_invalid)("Signed Vote".to_string(), event.to_string());
+                self.sink.on_event(self.ctx.clone(), event);
+                false
+            }
+        }
+    }
+
+    /// Unwrap the digest or the preimage of a message. The message whose preimage the codec
+    /// fails to encode is dropped without being reported, since it is not the fault of the
+    /// sender.
+    fn digest<D>(&self, res: ConsensusResult<D>) -> Option<D> {
+        res.map_err(|err| log::warn!("Mlm: parallel verify encode error {:?}", err))
+            .ok()
+    }
+
+    fn verify_signature<T: Codec>(
+        &self,
+        msg: &MlmMsg<T>,
+        signature: &Signature,
+        hash: Hash,
+        signer: &Address,
+        (height, round): (u64, u64),
+    ) -> bool {
+        match self
+            .crypto
+            .verify_signature(signature.clone(), hash, signer.clone())
+        {
+            Ok(()) => true,
+            Err(err) => {
+                let event = ConsensusEvent::SignatureInvalid {
+                    kind: msg.to_string(),
+                    height,
+                    round,
+                    signer: signer.clone(),
+                    error: format!("{:?}", err),
+                };
+                self.report(msg, event);
+                false
+            }
+        }
+    }
+
+    fn verify_qc<T: Codec>(&self, msg: &MlmMsg<T>, qc: &AggregatedVote) -> bool {
+        let digest = self.digests.qc(self.crypto, qc);
+        if digest
+            .as_ref()
+            .is_some_and(|digest| self.digests.is_verified_qc(digest))
+        {
+            return true;
+        }
+
+        if !self
+            .authority
+            .is_qc_above_threshold::<C::QcSignature>(&qc.signature)
+            .unwrap_or(false)
+        {
+            let event = ConsensusEvent::QcBelowThreshold {
+                vote_type: qc.vote_type.clone(),
+                height: qc.height,
+                round: qc.round,
+                leader: qc.leader.clone(),
+            };
+            self.report(msg, event);
+            return false;
+        }
+
+        let vote = qc.to_vote();
+        let Some(hash) = self.digest(self.digests.vote(self.crypto, self.codec, &vote))
+        else {
+            return false;
+        };
+        let res = verify_qc_signature(self.crypto, &qc.signature, hash, self.authority)
+            .and_then(|()| {
+                verify_vote_extensions(
+                    self.crypto,
+                    self.codec,
+                    &vote,
+                    &qc.signature,
+                    &qc.extensions,
+                    self.authority,
+                )
+            });
+        match res {
+            Ok(()) => {
+                if let Some(digest) = digest {
+                    self.digests.insert_verified_qc(digest);
+                }
+                true
+            }
+            Err(err) => {
+                let event = ConsensusEvent::QcSignatureInvalid {
+                    vote_type: qc.vote_type.clone(),
+                    height: qc.height,
+                    round: qc.round,
+                    leader: qc.leader.clone(),
+                    error: format!("{:?}", err),
+                };
+                self.report(msg, event);
+                false
+            }
+        }
+    }
+
+    /// Report an invalid message to the event sink and the adapter.
+    fn report<T: Codec>(&self, msg: &MlmMsg<T>, event: ConsensusEvent) {
+        (self.report_invalid)(msg.to_string(), event.to_string());
+        self.sink.on_event(self.ctx.clone(), event);
+    }
 }
 
-fn verify_qc<T: Codec, C: Crypto>(
-    ctx: Context,
-    crypto: Arc<C>,
-    qc: AggregatedVote,
-    authority: AuthorityManage,
-    tx: UnboundedSender<(Context, MlmMsg<T>)>,
-    msg_clone: MlmMsg<T>,
-) {
-    let hash = crypto.hash(Bytes::from(rlp::encode(&qc.to_vote())));
-    if let Ok(voters) = get_voters(&qc.signature.address_bitmap, authority) {
-        crypto
-            .verify_aggregated_signature(qc.signature.signature.clone(), hash, voters)
-            .map_or_else(
-                |err| {
-                    log::error!(
-                        "Mlm: verify {:?} aggregated signature error {:?}",
-                        qc,
-                        err
-                    );
-                },
-                |_| {
-                    let _ = tx.unbounded_send((ctx, msg_clone));
-                },
-            );
+/// Verify a timeout certificate by its voters above the threshold and its aggregated signature
+/// of the choke, whose digest is `hash`.
+fn verify_timeout_cert<C: Crypto>(
+    crypto: &C,
+    hash: Hash,
+    tc: &AggregatedChoke,
+    authority: &AuthorityManage,
+) -> ConsensusResult<()> {
+    if !authority.is_above_threshold_by_voters(&tc.voters) {
+        return Err(ConsensusError::BrakeErr(
+            "timeout certificate is not above threshold".to_string(),
+        ));
     }
+
+    crypto
+        .verify_aggregated_signature(tc.signature.clone(), hash, tc.voters.clone())
+        .map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))
 }
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::batch::{batch_member_proof, batch_root, BatchMemberProof};
+use crate::types::{BlockPart, CompactProposal, Hash, PartedProposal};
+use crate::Crypto;
+
+/// The max number of the parted proposals waiting for their parts.
+const PENDING_SETS_LIMIT: usize = 64;
+/// The max number of the parts buffered for all of the parted proposals, which is also the max
+/// number of the parts of a block.
+const PENDING_PARTS_LIMIT: usize = 4096;
+
+/// Split an encoded block into the parts of the given size, and return the merkle root of the
+/// hashes of the parts with the parts and their proofs.
+pub fn split_block<C: Crypto + ?Sized>(
+    crypto: &C,
+    content: &Bytes,
+    part_size: usize,
+) -> (Hash, Vec<(Bytes, BatchMemberProof)>) {
+    let parts = content
+        .chunks(part_size.max(1))
+        .map(Bytes::copy_from_slice)
+        .collect::<Vec<_>>();
+    let hashes = parts
+        .iter()
+        .map(|part| crypto.hash(part.clone()))
+        .collect::<Vec<_>>();
+    let root = batch_root(crypto, &hashes);
+    let parts = parts
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, part)| {
+            batch_member_proof(crypto, &hashes, index).map(|proof| (part, proof))
+        })
+        .collect();
+    (root, parts)
+}
+
+/// The parts of the blocks of the parted proposals, keyed by the height, the round and the
+/// merkle root. Each part is verified against the root on arrival, so the parts may arrive
+/// before their proposal. The block of a proposal is reassembled when all of its parts arrive.
+#[derive(Debug, Default)]
+pub struct BlockParts {
+    sets: BTreeMap<(u64, u64, Hash), PartSet>,
+    parts: usize,
+}
+
+#[derive(Debug, Default)]
+struct PartSet {
+    proposal: Option<CompactProposal>,
+    total: usize,
+    parts: BTreeMap<usize, Bytes>,
+}
+
+impl BlockParts {
+    pub fn new() -> Self {
+        BlockParts::default()
+    }
+
+    /// Drop the parts of the heights below the given one.
+    pub fn prune(&mut self, height: u64) {
+        let parts = &mut self.parts;
+        self.sets.retain(|(h, _, _), set| {
+            if *h < height {
+                *parts -= set.parts.len();
+            }
+            *h >= height
+        });
+    }
+
+    /// Insert the proposal of a part set, return the proposal with the reassembled block if all
+    /// of the parts have arrived.
+    pub fn insert_proposal(
+        &mut self,
+        pp: PartedProposal,
+    ) -> Option<(CompactProposal, Bytes)> {
+        let total = pp.total as usize;
+        if total == 0 || total > PENDING_PARTS_LIMIT {
+            return None;
+        }
+
+        let key = (pp.proposal.height, pp.proposal.round, pp.root);
+        let set = self.entry(key.clone())?;
+        if set.proposal.is_some() {
+            return None;
+        }
+
+        // The parts of another number are not of this proposal.
+        let mut dropped = 0;
+        if set.total != total {
+            dropped = set.parts.len();
+            set.parts.clear();
+            set.total = total;
+        }
+        set.proposal = Some(pp.proposal);
+        self.parts -= dropped;
+        self.try_assemble(key)
+    }
+
+    /// Verify and insert a part, return the proposal with the reassembled block if it is the
+    /// last part of a set.
+    pub fn insert_part<C: Crypto + ?Sized>(
+        &mut self,
+        crypto: &C,
+        part: BlockPart,
+    ) -> Option<(CompactProposal, Bytes)> {
+        let (index, total) = (part.proof.index, part.proof.len);
+        if total > PENDING_PARTS_LIMIT || self.parts >= PENDING_PARTS_LIMIT {
+            return None;
+        }
+        if !part
+            .proof
+            .verify(crypto, &crypto.hash(part.content.clone()), &part.root)
+        {
+            return None;
+        }
+
+        let key = (part.height, part.round, part.root);
+        let set = self.entry(key.clone())?;
+        if set.total == 0 {
+            set.total = total;
+        }
+        if set.total != total || set.parts.contains_key(&index) {
+            return None;
+        }
+        set.parts.insert(index, part.content);
+        self.parts += 1;
+        self.try_assemble(key)
+    }
+
+    fn entry(&mut self, key: (u64, u64, Hash)) -> Option<&mut PartSet> {
+        if !self.sets.contains_key(&key) && self.sets.len() >= PENDING_SETS_LIMIT {
+            return None;
+        }
+        Some(self.sets.entry(key).or_default())
+    }
+
+    fn try_assemble(
+        &mut self,
+        key: (u64, u64, Hash),
+    ) -> Option<(CompactProposal, Bytes)> {
+        let set = self.sets.get(&key)?;
+        if set.proposal.is_none() || set.parts.len() < set.total {
+            return None;
+        }
+
+        let set = self.sets.remove(&key)?;
+        self.parts -= set.parts.len();
+        let mut content = BytesMut::new();
+        for part in set.parts.values() {
+            content.extend_from_slice(part);
+        }
+        set.proposal.map(|proposal| (proposal, content.freeze()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{split_block, BlockParts};
+    use crate::test_utils::KeccakCrypto;
+    use crate::types::{Address, BlockPart, CompactProposal, Hash, PartedProposal};
+
+    #[test]
+    fn test_block_parts() {
+        let crypto = KeccakCrypto;
+        let content = Bytes::from((0..100u8).collect::<Vec<_>>());
+        let (root, parts) = split_block(&crypto, &content, 30);
+        assert_eq!(parts.len(), 4);
+
+        let proposal = CompactProposal {
+            signature: Bytes::new(),
+            height: 1,
+            round: 0,
+            block_hash: Hash::from(vec![1]),
+            lock: None,
+            proposer: Address::from_slice(&[2]).unwrap(),
+            timestamp: None,
+            timeout_cert: None,
+            vrf_proof: Bytes::new(),
+        };
+        let to_part = |(content, proof)| BlockPart {
+            height: 1,
+            round: 0,
+            root: root.clone(),
+            content,
+            proof,
+        };
+        let mut parts = parts.into_iter().map(to_part).collect::<Vec<_>>();
+
+        // A forged part fails the proof, and the parts may arrive before the proposal.
+        let mut block_parts = BlockParts::new();
+        let mut forged = parts[0].clone();
+        forged.content = Bytes::from(vec![0; 30]);
+        assert!(block_parts.insert_part(&crypto, forged).is_none());
+        let last = parts.pop().unwrap();
+        for part in parts.iter().rev() {
+            assert!(block_parts.insert_part(&crypto, part.clone()).is_none());
+        }
+        assert!(block_parts.insert_part(&crypto, parts[0].clone()).is_none());
+        let pp = PartedProposal {
+            proposal: proposal.clone(),
+            total: 4,
+            root: root.clone(),
+        };
+        assert!(block_parts.insert_proposal(pp).is_none());
+        assert_eq!(
+            block_parts.insert_part(&crypto, last),
+            Some((proposal, content))
+        );
+        assert!(block_parts.sets.is_empty());
+        assert_eq!(block_parts.parts, 0);
+    }
+}
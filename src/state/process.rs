@@ -1,37 +1,257 @@
 use std::cmp::{Ord, Ordering};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
 use std::string::ToString;
 use std::time::{Duration, Instant};
 use std::{ops::BitXor, sync::Arc};
 
-use bit_vec::BitVec;
 use bytes::Bytes;
 use creep::Context;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use futures::{select, StreamExt};
+use futures::channel::oneshot;
+use futures::future::BoxFuture;
+use futures::stream::SelectAll;
+use futures::{select_biased, StreamExt};
 use hummer::coding::hex_encode;
-use log::{debug, error, info, warn};
-use muta_apm::derive::tracing_span;
-use tokio::time::sleep;
-
-use crate::error::ConsensusError;
+use tokio::sync::Semaphore;
+use tokio::task::yield_now;
+use tokio::time::{sleep, timeout};
+
+use crate::canonical;
+use crate::config::enabled_features;
+use crate::error::{
+    ConsensusError, ConsensusExit, ErrorKind, HashMismatch, MismatchStage,
+};
+use crate::event::{now_millis, ConfigSource, EventBus, MlmEventKind, RuntimeConfig};
+use crate::event_sink::{ConsensusEvent, ConsensusEventSink, LogEventSink};
+use crate::memory::{CacheKind, MemoryAccount};
+use crate::multisig::{verify_qc_signature, QcSignature};
+use crate::runtime::{Runtime, TokioRuntime};
 use crate::smr::smr_types::{
     FromWhere, SMREvent, SMRTrigger, Step, TriggerSource, TriggerType,
 };
 use crate::smr::{Event, SMRHandler};
+use crate::state::aggregation::{AggregationAccount, RunningAggregates};
+use crate::state::archive::ProofArchive;
 use crate::state::collection::{ChokeCollector, ProposalCollector, VoteCollector};
+use crate::state::connectivity::ConnectivityTracker;
+use crate::state::dedup::{SeenVotes, VoteDedupAccount};
+use crate::state::digest::DigestCache;
+use crate::state::dump::{
+    ChokeDump, DumpRequest, LockDump, QcDump, StateDump, VoteDump,
+};
+use crate::state::future::{signer, FutureAccount, FutureBuffer};
+use crate::state::heartbeat::LivenessAccount;
+use crate::state::inbox::{Inbound, PriorityInbox, DRAIN_LIMIT};
+use crate::state::median_time::MedianTime;
+use crate::state::multi_proposal::{CandidateProposals, Rank};
 use crate::state::parallel::parallel_verify;
+use crate::state::parts::{split_block, BlockParts};
+use crate::state::protocol::{
+    BrakeReport, HaltReport, ProtocolAccount, RoundLimitReport,
+};
+use crate::state::qc_gossip::{QcGossip, QcGossipAccount};
+use crate::state::rate_limit::{RateLimitAccount, RateLimiter};
+use crate::state::resync::QuorumViews;
+use crate::state::retention::expired_heights;
+use crate::state::retry::retry_call;
+use crate::state::snapshot::ConsensusSnapshot;
+use crate::state::stall::{StallDump, StallWatchdog, ValidatorVotes};
+use crate::state::timing::{HeightReport, HeightTiming};
+use crate::state::tuner::IntervalTuner;
+use crate::state::window::{HeightWindow, HeightWindowAccount};
+use crate::telemetry::{node_log, LogScope, NoopTrace, SpanAttrs, Trace};
+use crate::threshold::ThresholdPolicy;
+use crate::timer::StepDeadline;
 use crate::types::{
-    Address, AggregatedChoke, AggregatedSignature, AggregatedVote, Choke, Commit, Hash,
-    MlmMsg, Node, PoLC, Proof, Proposal, Signature, SignedChoke, SignedProposal,
-    SignedVote, Status, UpdateFrom, VerifyResp, ViewChangeReason, Vote, VoteType,
+    Address, AggregatedChoke, AggregatedSignature, AggregatedVote, AllowEmptyBlock,
+    Attestation, BlockPart, BlockResponse, Choke, Commit, CompactProposal, ContextExt,
+    CorrelationId, GetBlock, HandoverProof, Hash, Heartbeat, KeyRotation, MlmMsg, Node,
+    PartedProposal, PoLC, Proof, Proposal, RoundContext, Signature, SignedAttestation,
+    SignedChoke, SignedHeartbeat, SignedKeyRotation, SignedProposal, SignedVote, Status,
+    UpdateFrom, VerifyResp, ViewChangeInfo, ViewChangeReason, Vote, VoteType,
+};
+use crate::utils::auth_manage::{validate_authority_list, AuthorityManage};
+use crate::utils::timer_config::TimerConfig;
+use crate::vote_export::{VoteSet, VoteSink};
+use crate::wal::{
+    decode_record, encode_record, open_record, seal_record, LastSigned, SMRBase,
+    StepRecord, WalInfo, WalLock, WalPayload, WalRecordKind,
+};
+use crate::wire::{proposal_preimage, RlpCodec, WireCodec};
+use crate::{
+    AddressScheme, AsyncCrypto, Codec, Consensus, ConsensusResult, Crypto,
+    DurationConfig, EffectiveConfig, MlmConfig, ProofStore, Wal, WalCipher,
+    WalMigration, INIT_HEIGHT, INIT_ROUND,
 };
-use crate::utils::auth_manage::AuthorityManage;
-use crate::wal::{SMRBase, WalInfo, WalLock};
-use crate::{Codec, Consensus, ConsensusResult, Crypto, Wal, INIT_HEIGHT, INIT_ROUND};
 
 const FUTURE_HEIGHT_GAP: u64 = 5;
 const FUTURE_ROUND_GAP: u64 = 10;
+/// The max number of messages handled before the state loop yields to the other tasks, unless
+/// `MlmConfig::message_budget` is set.
+const MESSAGE_BUDGET: usize = 64;
+/// The interval of repeating `Consensus::get_block()` while there is nothing to propose.
+const EMPTY_BLOCK_POLL: Duration = Duration::from_millis(50);
+/// The interval to poll the readiness of the network before the first step.
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// The max number of the compact proposals waiting for their blocks.
+const COMPACT_PENDING_LIMIT: usize = 64;
+/// The message signed to probe the readiness of the signer, which is never a consensus message.
+const SIGNER_PROBE: &[u8] = b"mlm signer probe";
+
+/// A commit that is executing by the `commit()` interface in pipelined mode. The precommit QC
+/// and the block are kept in the wal until the status is applied.
+#[derive(Debug)]
+struct PendingCommit<T: Codec> {
+    height: u64,
+    lock: WalLock<T>,
+    status: oneshot::Receiver<ConsensusResult<Status>>,
+}
+
+/// The authority list of the next epoch, which is scheduled by the status of the last height of
+/// an epoch.
+#[derive(Clone, Debug)]
+struct NextEpoch {
+    /// The first height of the epoch.
+    height: u64,
+    /// The sorted authority list of the epoch.
+    authority_list: Vec<Node>,
+    /// The authority manage of the epoch, by which the messages of its first height are
+    /// verified before the node goes to the height.
+    authority: Arc<AuthorityManage>,
+}
+
+/// Events scheduled by the state itself, which are sent back to the state after a delay.
+#[derive(Clone, Debug)]
+pub(crate) enum DelayedEvent {
+    /// Stop waiting for lower ranked proposals of the given height and round.
+    ProposalWait { height: u64, round: u64 },
+    /// Resend the signed vote of self if the QC of its round has not formed.
+    ResendVote(SignedVote),
+    /// Route the signed vote of self to the relayer of the hop in the fallback chain if the QC
+    /// of its round has not formed.
+    FallbackVote { signed_vote: SignedVote, hop: usize },
+    /// Probe the signer again while it is unavailable.
+    ProbeSigner,
+    /// Check whether the node has made a height progress in the stall timeout.
+    StallCheck,
+    /// Check whether the node has heard from a quorum in the connectivity window.
+    ConnectivityCheck,
+    /// Broadcast a heartbeat of self.
+    Heartbeat,
+    /// Report a signed attestation of the state of self.
+    Attestation,
+    /// Sign and broadcast the key rotation metadata of self.
+    KeyRotation(KeyRotation),
+    /// The result of a signing task of a message of self, with the extension of a precommit
+    /// and its signature.
+    Signed {
+        id: u64,
+        signature: ConsensusResult<Signature>,
+        extension: (Bytes, Signature),
+    },
+}
+
+/// A message of self which waits for its signature.
+#[derive(Debug)]
+enum Unsigned<T: Codec> {
+    Proposal {
+        ctx: Context,
+        proposal: Proposal<T>,
+        lock_round: Option<u64>,
+    },
+    /// The proposal of a redundant proposer of the rank, or of an authority in the VRF leader
+    /// mode if the rank is none.
+    BackupProposal {
+        ctx: Context,
+        proposal: Proposal<T>,
+        rank: Option<usize>,
+    },
+    Vote {
+        vote: Vote,
+        lock_round: Option<u64>,
+    },
+    Choke {
+        choke: Choke,
+        lock_round: Option<u64>,
+    },
+    Heartbeat(Heartbeat),
+    Attestation(Attestation),
+    KeyRotation(KeyRotation),
+    Probe,
+}
+
+impl<T: Codec> Unsigned<T> {
+    fn name(&self) -> &'static str {
+        match self {
+            Unsigned::Proposal { .. } => "proposal",
+            Unsigned::BackupProposal { .. } => "backup proposal",
+            Unsigned::Vote { .. } => "vote",
+            Unsigned::Choke { .. } => "choke",
+            Unsigned::Heartbeat(_) => "heartbeat",
+            Unsigned::Attestation(_) => "attestation",
+            Unsigned::KeyRotation(_) => "key rotation",
+            Unsigned::Probe => "signer probe",
+        }
+    }
+}
+
+/// A message of self in a signing task, with the view it is signed in. A consensus message
+/// goes on once it is signed only if the node is still in the view.
+#[derive(Debug)]
+struct PendingSign<T: Codec> {
+    height: u64,
+    round: u64,
+    step: Step,
+    /// The context of the span that the message is signed in, which the spans of its
+    /// broadcasts are the children of.
+    span_ctx: Context,
+    message: Unsigned<T>,
+}
+
+/// The signer moved into the signing tasks, which signs by the async crypto if it is set,
+/// otherwise by the crypto.
+struct Signer<C> {
+    util: Arc<C>,
+    async_crypto: Option<Arc<dyn AsyncCrypto>>,
+    timeout: Duration,
+}
+
+impl<C: Crypto> Signer<C> {
+    /// A remote signing which does not return in the sign timeout is an error, so that the
+    /// timers of the step go on to change the view.
+    async fn sign(&self, hash: Hash) -> ConsensusResult<Signature> {
+        let signer = match &self.async_crypto {
+            Some(signer) => signer,
+            None => {
+                return self
+                    .util
+                    .sign(hash)
+                    .map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))
+            }
+        };
+
+        match timeout(self.timeout, signer.sign(hash)).await {
+            Ok(res) => {
+                res.map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))
+            }
+            Err(_) => Err(ConsensusError::CryptoErr(format!(
+                "Sign timeout {:?}",
+                self.timeout
+            ))),
+        }
+    }
+}
+
+/// The settings of a state beyond the protocol basics, which are taken from the instance when
+/// it runs: the config of the instance, which also switches the pipelined commit and the
+/// redundant proposers, the timer config, the store of the commit proofs and the event bus.
+pub(crate) struct StateConfig {
+    pub(crate) config: MlmConfig,
+    pub(crate) duration_config: Option<DurationConfig>,
+    pub(crate) proof_store: Option<Arc<dyn ProofStore>>,
+    pub(crate) events: EventBus,
+}
 
 /// Mlm state struct. It maintains the local state of the node, and monitor the SMR event. The
 /// `proposals` is used to cache the signed proposals that are with higher height or round. The
@@ -47,21 +267,96 @@ pub struct State<T: Codec, F: Consensus<T>, C: Crypto, W: Wal> {
     proposals: ProposalCollector<T>,
     votes: VoteCollector,
     chokes: ChokeCollector,
-    authority: AuthorityManage,
+    authority: Arc<AuthorityManage>,
+    digests: Arc<DigestCache>,
+    next_epoch: Option<NextEpoch>,
+    /// The latest epoch and its sorted authority list that the crypto is notified of.
+    key_epoch: Option<(u64, Vec<Node>)>,
     hash_with_block: HashMap<Hash, T>,
+    pre_checked: HashSet<Hash>,
+    round_timestamps: BTreeMap<u64, u64>,
+    median_time: MedianTime,
     is_full_transcation: HashMap<Hash, bool>,
     is_leader: bool,
     leader_address: Address,
     update_from_where: UpdateFrom,
     height_start: Instant,
-    block_interval: u64,
+    block_interval: Duration,
+    tuner: IntervalTuner,
+    timer_config: TimerConfig,
+    duration_config: Option<DurationConfig>,
     consensus_power: bool,
     stopped: bool,
+    config: MlmConfig,
+    pending_commit: Option<PendingCommit<T>>,
+    candidates: CandidateProposals<T>,
+    round_start: Instant,
+    step_starts: Vec<(Step, Instant)>,
+    timing: HeightTiming,
+    lock_round: Option<u64>,
+    forced_view_change: Option<(u64, u64, String)>,
+    /// The signed proposal and the signed votes of self in the current round, which are
+    /// resent by `MlmHandler::rebroadcast_current()`.
+    own_proposal: Option<SignedProposal<T>>,
+    own_votes: Vec<SignedVote>,
+    /// The height, the round and the tokio instant of the first choke of the node in the
+    /// round, which follows the paused time of the simulations.
+    brake_start: Option<(u64, u64, tokio::time::Instant)>,
+    brake_rounds: u64,
+    brake_streak_start: tokio::time::Instant,
+    round_escalations: u64,
+    stall: StallWatchdog,
+    connectivity: ConnectivityTracker,
+    quorum_views: QuorumViews,
+    /// The height and the round that the node moves to by a quorum ahead of it.
+    quorum_round: Option<(u64, u64)>,
 
     verify_sig_tx: UnboundedSender<(Context, MlmMsg<T>)>,
     resp_tx: UnboundedSender<VerifyResp>,
+    delay_tx: UnboundedSender<DelayedEvent>,
+    delay_rx: Option<UnboundedReceiver<DelayedEvent>>,
+    dump_rx: Option<UnboundedReceiver<DumpRequest<T>>>,
+    trusted_rx: Option<UnboundedReceiver<(Context, MlmMsg<T>)>>,
+    exit_rx: Option<UnboundedReceiver<ConsensusExit>>,
     function: Arc<F>,
     wal: Arc<W>,
+    proof_store: Option<Arc<dyn ProofStore>>,
+    address_scheme: Option<Arc<dyn AddressScheme>>,
+    async_crypto: Option<Arc<dyn AsyncCrypto>>,
+    last_signed: Option<LastSigned>,
+    wal_migration: Option<Arc<dyn WalMigration>>,
+    wal_cipher: Option<Arc<dyn WalCipher>>,
+    vote_sink: Option<Arc<dyn VoteSink>>,
+    trace: Arc<dyn Trace>,
+    event_sink: Arc<dyn ConsensusEventSink>,
+    runtime: Arc<dyn Runtime>,
+    /// The permits of the verifications running at the same time, which is unlimited if it
+    /// is empty.
+    verify_permits: Option<Arc<Semaphore>>,
+    wire_codec: Arc<dyn WireCodec>,
+    /// The context of the span of the message or the SMR event in handling, which the spans
+    /// of the broadcasts made in the handling are the children of.
+    span_ctx: Context,
+    future: FutureBuffer<T>,
+    rate_limit: RateLimiter,
+    window: HeightWindow,
+    seen_votes: SeenVotes,
+    compact: BTreeMap<(u64, u64, Hash), CompactProposal>,
+    parts: BlockParts,
+    qc_gossip: QcGossip,
+    aggregates: RunningAggregates,
+    events: EventBus,
+    memory: MemoryAccount,
+    protocol: ProtocolAccount,
+    liveness: LivenessAccount,
+    proof_archive: ProofArchive,
+    step_deadline: StepDeadline,
+    heartbeat_at: u64,
+    attestation_at: u64,
+    signer_ready: bool,
+    /// The messages of self in the signing tasks by the ids of the tasks.
+    unsigned: HashMap<u64, PendingSign<T>>,
+    next_sign_id: u64,
     util: Arc<C>,
 }
 
@@ -77,16 +372,37 @@ where
         smr: SMRHandler,
         addr: Address,
         init_height: u64,
-        interval: u64,
+        interval: Duration,
         mut authority_list: Vec<Node>,
         verify_tx: UnboundedSender<(Context, MlmMsg<T>)>,
         consensus: Arc<F>,
         crypto: Arc<C>,
         wal_engine: Arc<W>,
+        state_config: StateConfig,
     ) -> (Self, UnboundedReceiver<VerifyResp>) {
+        let StateConfig {
+            config,
+            duration_config,
+            proof_store,
+            events,
+        } = state_config;
         let (tx, rx) = unbounded();
+        let mut timer_config = TimerConfig::new(interval);
+        if let Some(config) = duration_config.clone() {
+            timer_config.update(config);
+        }
+        let (delay_tx, delay_rx) = unbounded();
         let mut auth = AuthorityManage::new();
         auth.update(&mut authority_list);
+        let future =
+            FutureBuffer::new(config.future_buffer_per_peer, config.future_buffer_total);
+        let rate_limit = RateLimiter::new(config.rate_limit, config.rate_limit_burst);
+        let window = HeightWindow::new(config.height_window);
+        let seen_votes = SeenVotes::new(config.vote_dedup_capacity);
+        let stall = StallWatchdog::new(config.stall_history);
+        let median_time = MedianTime::new(config.median_time_window);
+        let verify_permits = (config.verify_concurrency > 0)
+            .then(|| Arc::new(Semaphore::new(config.verify_concurrency)));
 
         let state = State {
             height: init_height,
@@ -97,180 +413,1081 @@ where
             proposals: ProposalCollector::new(),
             votes: VoteCollector::new(),
             chokes: ChokeCollector::new(),
-            authority: auth,
+            authority: Arc::new(auth),
+            digests: Arc::new(DigestCache::new()),
+            next_epoch: None,
+            key_epoch: None,
             hash_with_block: HashMap::new(),
+            pre_checked: HashSet::new(),
+            round_timestamps: BTreeMap::new(),
+            median_time,
             is_full_transcation: HashMap::new(),
             is_leader: false,
             leader_address: Address::default(),
             update_from_where: UpdateFrom::PrecommitQC(mock_init_qc()),
             height_start: Instant::now(),
             block_interval: interval,
+            tuner: IntervalTuner::new(config.interval_tuning),
+            timer_config,
+            duration_config,
             stopped: false,
+            config,
+            pending_commit: None,
+            candidates: CandidateProposals::new(),
+            round_start: Instant::now(),
+            step_starts: vec![(Step::Propose, Instant::now())],
+            timing: HeightTiming::new(),
+            lock_round: None,
+            forced_view_change: None,
+            own_proposal: None,
+            own_votes: Vec::new(),
+            brake_start: None,
+            brake_rounds: 0,
+            brake_streak_start: tokio::time::Instant::now(),
+            round_escalations: 0,
+            stall,
+            connectivity: ConnectivityTracker::new(),
+            quorum_views: QuorumViews::new(),
+            quorum_round: None,
 
             verify_sig_tx: verify_tx,
             resp_tx: tx,
+            delay_tx,
+            delay_rx: Some(delay_rx),
+            dump_rx: None,
+            trusted_rx: None,
+            exit_rx: None,
             function: consensus,
             util: crypto,
             wal: wal_engine,
+            proof_store,
+            address_scheme: None,
+            async_crypto: None,
+            last_signed: None,
+            wal_migration: None,
+            wal_cipher: None,
+            vote_sink: None,
+            trace: Arc::new(NoopTrace),
+            runtime: Arc::new(TokioRuntime),
+            verify_permits,
+            wire_codec: Arc::new(RlpCodec),
+            event_sink: Arc::new(LogEventSink),
+            span_ctx: Context::new(),
+            future,
+            rate_limit,
+            window,
+            seen_votes,
+            compact: BTreeMap::new(),
+            parts: BlockParts::new(),
+            qc_gossip: QcGossip::new(),
+            aggregates: RunningAggregates::new(),
+            memory: MemoryAccount::new(),
+            protocol: ProtocolAccount::new(),
+            liveness: LivenessAccount::new(),
+            proof_archive: ProofArchive::new(),
+            step_deadline: StepDeadline::default(),
+            heartbeat_at: 0,
+            attestation_at: 0,
+            signer_ready: true,
+            unsigned: HashMap::new(),
+            next_sign_id: 0,
+            events,
         };
 
         (state, rx)
     }
 
-    /// Run state module.
+    /// Set the async crypto which signs instead of the crypto.
+    pub(crate) fn set_async_crypto(&mut self, signer: Arc<dyn AsyncCrypto>) {
+        self.async_crypto = Some(signer);
+    }
+
+    pub(crate) fn set_memory_account(&mut self, memory: MemoryAccount) {
+        memory.set_budget(self.config.memory_budget, &self.config.cache_weights);
+        self.memory = memory;
+    }
+
+    pub(crate) fn set_wal_migration(&mut self, migration: Arc<dyn WalMigration>) {
+        self.wal_migration = Some(migration);
+    }
+
+    pub(crate) fn set_wal_cipher(&mut self, cipher: Arc<dyn WalCipher>) {
+        self.wal_cipher = Some(cipher);
+    }
+
+    pub(crate) fn set_future_account(&mut self, account: FutureAccount) {
+        self.future.set_account(account);
+    }
+
+    pub(crate) fn set_rate_limit_account(&mut self, account: RateLimitAccount) {
+        self.rate_limit.set_account(account);
+    }
+
+    pub(crate) fn set_window_account(&mut self, account: HeightWindowAccount) {
+        self.window.set_account(account);
+    }
+
+    pub(crate) fn set_dedup_account(&mut self, account: VoteDedupAccount) {
+        self.seen_votes.set_account(account);
+    }
+
+    pub(crate) fn set_qc_gossip_account(&mut self, account: QcGossipAccount) {
+        self.qc_gossip.set_account(account);
+    }
+
+    pub(crate) fn set_aggregation_account(&mut self, account: AggregationAccount) {
+        self.aggregates.set_account(account);
+    }
+
+    pub(crate) fn set_protocol_account(&mut self, account: ProtocolAccount) {
+        self.protocol = account;
+    }
+
+    pub(crate) fn set_liveness_account(&mut self, account: LivenessAccount) {
+        self.liveness = account;
+    }
+
+    pub(crate) fn set_proof_archive(&mut self, archive: ProofArchive) {
+        archive.set_capacity(self.config.proof_archive_size);
+        self.proof_archive = archive;
+    }
+
+    pub(crate) fn set_step_deadline(&mut self, deadline: StepDeadline) {
+        self.step_deadline = deadline;
+    }
+
+    pub(crate) fn set_dump_receiver(
+        &mut self,
+        dump_rx: UnboundedReceiver<DumpRequest<T>>,
+    ) {
+        self.dump_rx = Some(dump_rx);
+    }
+
+    /// Set the receiver of the panics of the tasks spawned by the supervised runtime.
+    pub(crate) fn set_exit_receiver(
+        &mut self,
+        exit_rx: UnboundedReceiver<ConsensusExit>,
+    ) {
+        self.exit_rx = Some(exit_rx);
+    }
+
+    pub(crate) fn set_trusted_receiver(
+        &mut self,
+        trusted_rx: UnboundedReceiver<(Context, MlmMsg<T>)>,
+    ) {
+        self.trusted_rx = Some(trusted_rx);
+    }
+
+    pub(crate) fn set_vote_sink(&mut self, sink: Arc<dyn VoteSink>) {
+        self.vote_sink = Some(sink);
+    }
+
+    pub(crate) fn set_trace(&mut self, trace: Arc<dyn Trace>) {
+        self.trace = trace;
+    }
+
+    pub(crate) fn set_runtime(&mut self, runtime: Arc<dyn Runtime>) {
+        self.runtime = runtime;
+    }
+
+    pub(crate) fn set_wire_codec(&mut self, codec: Arc<dyn WireCodec>) {
+        self.wire_codec = codec;
+    }
+
+    pub(crate) fn set_event_sink(&mut self, sink: Arc<dyn ConsensusEventSink>) {
+        self.event_sink = sink;
+    }
+
+    pub(crate) fn set_threshold_policy(&mut self, policy: Arc<dyn ThresholdPolicy>) {
+        Arc::make_mut(&mut self.authority).set_policy(policy);
+    }
+
+    /// Set the address scheme that the addresses of the authority list must be valid in, and
+    /// apply it to the current authority list.
+    pub(crate) fn set_address_scheme(&mut self, scheme: Arc<dyn AddressScheme>) {
+        self.address_scheme = Some(scheme);
+        let mut auth_list = self.authority.get_authority_list();
+        self.update_authority(&mut auth_list);
+    }
+
+    /// Run state module until it stops, and return the reason. A violated invariant stops it
+    /// with a fatal error, which is also delivered through the error channel, and a panicked
+    /// task of the supervised runtime stops it as well. Once it stops, the message channels are
+    /// closed, the wal is flushed and `MlmEventKind::Exit` is published in order.
     pub(crate) async fn run(
         &mut self,
-        mut raw_rx: UnboundedReceiver<(Context, MlmMsg<T>)>,
+        raw_rx: SelectAll<UnboundedReceiver<(Context, Inbound<T>)>>,
         mut event: Event,
         mut verify_resp: UnboundedReceiver<VerifyResp>,
-        mut verify_sig: UnboundedReceiver<(Context, MlmMsg<T>)>,
-    ) {
-        debug!("Mlm: state start running");
+        verify_sig: UnboundedReceiver<(Context, MlmMsg<T>)>,
+    ) -> ConsensusExit {
+        node_log!(debug, self.log_scope(), "Mlm: state start running");
+        let mut delay_rx = match self.delay_rx.take() {
+            Some(delay_rx) => delay_rx,
+            None => {
+                let e = self.report_invariant("state runs twice");
+                return ConsensusExit::Fatal(e);
+            }
+        };
+        // Without a receiver set, the closed channel is terminated after the first poll.
+        let mut dump_rx = self.dump_rx.take().unwrap_or_else(|| unbounded().1);
+        let mut trusted_rx = self.trusted_rx.take().unwrap_or_else(|| unbounded().1);
+        let mut exit_rx = self.exit_rx.take().unwrap_or_else(|| unbounded().1);
+        self.wait_ready().await;
+        self.probe_signer();
         if let Err(e) = self.start_with_wal().await {
-            error!("Mlm: start with wal error {:?}", e);
+            node_log!(error, self.log_scope(), "Mlm: start with wal error {:?}", e);
+            self.publish_error(e);
+        }
+        let config = self.effective_config();
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state starts with config {:?}",
+            config
+        );
+        self.events.publish(
+            self.height,
+            self.round,
+            MlmEventKind::ConfigResolved {
+                config: Box::new(config),
+            },
+        );
+        if self.config.stall_timeout > 0 {
+            self.stall.progress();
+            self.schedule(
+                Duration::from_millis(self.config.stall_timeout),
+                DelayedEvent::StallCheck,
+            );
+        }
+        if self.config.connectivity_window > 0 {
+            self.connectivity.reset();
+            self.schedule(
+                Duration::from_millis(self.config.connectivity_window),
+                DelayedEvent::ConnectivityCheck,
+            );
+        }
+        if self.config.heartbeat_interval > 0 {
+            self.schedule(Duration::ZERO, DelayedEvent::Heartbeat);
+        }
+        if self.config.attestation_interval > 0 {
+            self.schedule(Duration::ZERO, DelayedEvent::Attestation);
         }
 
-        loop {
-            select! {
-                raw = raw_rx.next() => {
-                    let (ctx, msg) = raw.expect("Mlm message handler dropped");
-
-                    if msg.is_rich_status() {
-                        let _ = self.verify_sig_tx.unbounded_send((ctx, msg));
-                    } else {
-                        match self.height.cmp(&msg.get_height()) {
-                            Ordering::Less => {
-                                let _ = self.verify_sig_tx.unbounded_send((ctx, msg));
-                            }
-                            Ordering::Equal => {
-                                parallel_verify(
-                                    ctx,
-                                    msg,
-                                    Arc::clone(&self.util),
-                                    self.authority.clone(),
-                                    self.verify_sig_tx.clone()
-                                )
-                                .await;
-                            }
-                            Ordering::Greater => (),
-                        };
+        // The SMR events and the delayed events are polled first, so a flood of messages can not
+        // starve the round changes and the timeouts. The loop also yields after a budget of
+        // messages to let the SMR and the timer tasks run. The messages are taken by priority,
+        // so the current round recovers from a burst before the stale and the future ones.
+        let mut raw_rx = PriorityInbox::new(raw_rx);
+        let mut verify_sig = PriorityInbox::new(verify_sig);
+        raw_rx.set_drain_limit(self.config.inbox_drain_limit);
+        verify_sig.set_drain_limit(self.config.inbox_drain_limit);
+        let message_budget = match self.config.message_budget {
+            0 => MESSAGE_BUDGET,
+            budget => budget,
+        };
+        let mut budget = message_budget;
+        let exit = loop {
+            if budget == 0 {
+                yield_now().await;
+                budget = message_budget;
+            }
+            raw_rx.set_view(self.height, self.round);
+            verify_sig.set_view(self.height, self.round);
+
+            select_biased! {
+                exit = exit_rx.next() => {
+                    if let Some(exit) = exit {
+                        node_log!(error, self.log_scope(), "Mlm: state exit, {}", exit);
+                        break exit;
                     }
                 }
 
                 evt = event.next() => {
                     if self.stopped {
-                        break;
+                        break ConsensusExit::Stopped;
                     }
 
                     if !self.consensus_power {
                         continue;
                     }
 
+                    if let Some(evt) = evt.as_ref() {
+                        let attrs =
+                            SpanAttrs::round(self.height, self.round).with_kind(evt);
+                        self.span_ctx =
+                            self.span(Context::new(), "mlm.smr_transition", attrs);
+                    }
                     if let Err(e) = self.handle_event(evt).await{
-                        error!("Mlm: state {:?} error", e);
+                        self.step_error(e);
                     }
+                    self.span_ctx = Context::new();
+                    self.check_memory();
                 }
 
+                delayed = delay_rx.next() => {
+                    // The watchdog keeps running while the node has no consensus power, and every
+                    // signing task reports back so its message is not left pending.
+                    if !self.consensus_power
+                        && !matches!(
+                            delayed,
+                            Some(DelayedEvent::StallCheck | DelayedEvent::Signed { .. })
+                        )
+                    {
+                        continue;
+                    }
+
+                    if let Some(event) = delayed {
+                        if let Err(e) = self.handle_delayed_event(event).await {
+                            self.step_error(e);
+                        }
+                    }
+                }
+
+                req = dump_rx.next() => match req {
+                    Some(DumpRequest::State(tx)) => {
+                        let _ = tx.send(self.state_dump());
+                    }
+                    Some(DumpRequest::Snapshot(tx)) => {
+                        let _ = tx.send(self.export_snapshot().await);
+                    }
+                    Some(DumpRequest::Config(tx)) => {
+                        let _ = tx.send(self.effective_config());
+                    }
+                    None => {}
+                },
+
                 res = verify_resp.next() => {
                     if !self.consensus_power {
                         continue;
                     }
 
                     if let Err(e) = self.handle_resp(res) {
-                        error!("Mlm: state {:?} error", e);
+                        self.step_error(e);
                     }
                 }
 
                 verified_msg = verify_sig.next() => {
-                    let (ctx, msg) = verified_msg.expect("Mlm message handler dropped");
-                    if let Err(e) = self.handle_msg(ctx.clone(), msg).await {
-                        self.report_error(ctx, e.clone());
-                        error!("Mlm: state {:?} error", e);
+                    let (ctx, msg) = match verified_msg {
+                        Some(item) => item,
+                        None => {
+                            let e = self.report_invariant("verified messages dropped");
+                            break ConsensusExit::Fatal(e);
+                        }
+                    };
+                    budget -= 1;
+                    if self.expired(&ctx, &msg) {
+                        continue;
+                    }
+                    match self.handle_msg(ctx.clone(), msg).await {
+                        Err(ConsensusError::SignerUnavailable(_)) | Ok(()) => (),
+                        Err(e) => {
+                            self.report_error(ctx, e.clone());
+                            node_log!(
+                                error,
+                                self.log_scope(),
+                                "Mlm: state {:?} error, id {}",
+                                e,
+                                self.correlation_id()
+                            );
+                        }
+                    }
+                    self.span_ctx = Context::new();
+                    self.check_memory();
+                }
+
+                trusted = trusted_rx.next() => {
+                    if let Some((ctx, msg)) = trusted {
+                        budget -= 1;
+                        self.dispatch_trusted(ctx, msg).await;
                     }
                 }
+
+                raw = raw_rx.next() => {
+                    let (ctx, msg) = match raw {
+                        Some(item) => item,
+                        None => {
+                            let e = self.report_invariant("message handlers dropped");
+                            break ConsensusExit::Fatal(e);
+                        }
+                    };
+                    budget -= 1;
+                    self.dispatch_raw(ctx, msg).await;
+                }
+            }
+
+            // No message is handled after the stop, even the pending ones of the round.
+            if self.stopped {
+                break ConsensusExit::Stopped;
+            }
+            if !self.future.is_empty() {
+                for (ctx, msg) in self.future.take(self.height, self.round) {
+                    self.dispatch_raw(ctx, msg).await;
+                }
             }
+        };
+
+        // The channels are closed before the exit is published, so that the handlers fail to
+        // send once the exit is observed.
+        for rx in raw_rx.get_mut().iter_mut() {
+            rx.close();
         }
+        trusted_rx.close();
+        dump_rx.close();
+        self.shutdown(&exit).await;
+        exit
     }
 
-    /// A function to handle message from the network. Public this in the crate to do unit tests.
-    #[tracing_span(kind = "mlm")]
-    pub(crate) async fn handle_msg(
-        &mut self,
-        ctx: Context,
-        raw: MlmMsg<T>,
-    ) -> ConsensusResult<()> {
-        if !self.consensus_power && !raw.is_rich_status() {
-            return Ok(());
+    /// Flush the wal and publish the exit of the state as its last event.
+    async fn shutdown(&mut self, exit: &ConsensusExit) {
+        if let Err(e) = self.wal.flush().await {
+            node_log!(
+                error,
+                self.log_scope(),
+                "Mlm: state flush wal error {:?}",
+                e
+            );
+        }
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state stop at height {}, round {}, {}",
+            self.height,
+            self.round,
+            exit
+        );
+        self.events.publish(
+            self.height,
+            self.round,
+            MlmEventKind::Exit {
+                committed_height: self.height.saturating_sub(1),
+                requested: exit.is_requested(),
+            },
+        );
+    }
+
+    /// Log and publish an error of a step, and report it to the adapter if it is fatal. The
+    /// steps failed for the unavailable signer are expected while the node observes, which is
+    /// published once when the signer fails.
+    fn step_error(&self, e: ConsensusError) {
+        if let ConsensusError::SignerUnavailable(_) = e {
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state skip a step without the signer, id {}",
+                self.correlation_id()
+            );
+            return;
+        }
+        node_log!(
+            error,
+            self.log_scope(),
+            "Mlm: state {:?} error, id {}",
+            e,
+            self.correlation_id()
+        );
+        if e.is_fatal() {
+            self.report_error(Context::new(), e);
+        } else {
+            self.publish_error(e);
         }
+    }
 
-        match raw {
-            MlmMsg::SignedProposal(sp) => {
-                if let Err(e) = self.handle_signed_proposal(ctx.clone(), sp).await {
-                    error!("Mlm: state handle signed proposal error {:?}", e);
+    /// Report a fatal error of a step to the adapter, which decides whether to halt, restart
+    /// or alert. The steps failed for the unavailable signer are reported once when the signer
+    /// fails.
+    fn report_fatal(&self, e: ConsensusError) {
+        if e.is_fatal() && !matches!(e, ConsensusError::SignerUnavailable(_)) {
+            self.report_error(Context::new(), e);
+        }
+    }
+
+    /// Dispatch a message generated by the local node. The proposal and the votes that the
+    /// state has signed in the round are sent to the handlers without the signature
+    /// verification, and the others are dispatched as the messages from the network.
+    async fn dispatch_trusted(&mut self, ctx: Context, msg: MlmMsg<T>) {
+        if self.is_own_signed(&msg) {
+            let _ = self.verify_sig_tx.unbounded_send((ctx, msg));
+        } else {
+            self.dispatch_raw(ctx, msg).await;
+        }
+    }
+
+    /// Whether the message is the very proposal or vote signed by self in the round, signature
+    /// included. The signer named by a message proves nothing before it is verified, so a
+    /// message naming self is not trusted by that.
+    fn is_own_signed(&self, msg: &MlmMsg<T>) -> bool {
+        match msg {
+            MlmMsg::SignedProposal(sp) => self.own_proposal.as_ref() == Some(sp),
+            MlmMsg::SignedVote(sv) => self.own_votes.contains(sv),
+            _ => false,
+        }
+    }
+
+    /// Dispatch a message from the network. A vote batch is fanned out, so that its votes are
+    /// verified in parallel and handled one by one as the votes sent alone.
+    async fn dispatch_raw(&mut self, ctx: Context, msg: MlmMsg<T>) {
+        match msg {
+            MlmMsg::SignedVoteBatch(votes) => {
+                for sv in votes {
+                    self.dispatch_msg(ctx.clone(), MlmMsg::SignedVote(sv)).await;
                 }
-                Ok(())
             }
+            msg => self.dispatch_msg(ctx, msg).await,
+        }
+    }
 
-            MlmMsg::AggregatedVote(av) => {
-                if let Err(e) = self.handle_aggregated_vote(ctx.clone(), av).await {
-                    error!("Mlm: state handle aggregated vote error {:?}", e);
-                }
-                Ok(())
+    /// Dispatch a single message from the network. The messages of the current height are
+    /// verified in parallel, and the messages ahead of the node are buffered if the future
+    /// buffer is enabled, or sent to the handlers unverified to be cached.
+    async fn dispatch_msg(&mut self, ctx: Context, msg: MlmMsg<T>) {
+        if msg.is_rich_status() || msg.is_control_msg() {
+            let _ = self.verify_sig_tx.unbounded_send((ctx, msg));
+            return;
+        }
+        if self.expired(&ctx, &msg) {
+            return;
+        }
+        self.record_heard(&msg);
+        if self.config.height_report {
+            self.timing.on_received(&msg);
+        }
+
+        // The heartbeats of any height are verified by the current authority list, and they
+        // are neither buffered nor rate limited with the consensus messages.
+        if let MlmMsg::SignedHeartbeat(sh) = &msg {
+            if !self.authority.contains(&sh.heartbeat.address) {
+                return;
             }
+            self.spawn_verify(parallel_verify(
+                ctx,
+                msg,
+                self.trace.as_ref(),
+                Arc::clone(&self.event_sink),
+                Arc::clone(&self.function),
+                Arc::clone(&self.util),
+                Arc::clone(&self.wire_codec),
+                Arc::clone(&self.authority),
+                Arc::clone(&self.digests),
+                self.verify_sig_tx.clone(),
+            ));
+            return;
+        }
 
-            MlmMsg::SignedVote(sv) => {
-                if let Err(e) = self.handle_signed_vote(ctx.clone(), sv).await {
-                    error!("Mlm: state handle signed vote error {:?}", e);
+        // The key rotations are verified by the authority list of the current or the next
+        // epoch that the signer is in, since the authorities joining the next epoch take part
+        // in the rotation of its keys.
+        if let MlmMsg::SignedKeyRotation(skr) = &msg {
+            let signer = &skr.rotation.address;
+            let authority = if self.authority.contains(signer) {
+                Arc::clone(&self.authority)
+            } else {
+                match self.next_epoch.as_ref() {
+                    Some(next) if next.authority.contains(signer) => {
+                        Arc::clone(&next.authority)
+                    }
+                    _ => return,
                 }
-                Ok(())
+            };
+            self.spawn_verify(parallel_verify(
+                ctx,
+                msg,
+                self.trace.as_ref(),
+                Arc::clone(&self.event_sink),
+                Arc::clone(&self.function),
+                Arc::clone(&self.util),
+                Arc::clone(&self.wire_codec),
+                authority,
+                Arc::clone(&self.digests),
+                self.verify_sig_tx.clone(),
+            ));
+            return;
+        }
+
+        if !self.window.check(self.height, msg.get_height()) {
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state drop {} out of the height window, id {}",
+                msg,
+                self.correlation_id()
+            );
+            if self.config.report_out_of_window {
+                let err = ConsensusError::OutOfWindow {
+                    local: self.height,
+                    msg: msg.get_height(),
+                };
+                self.report_error(ctx, err);
             }
+            return;
+        }
 
-            MlmMsg::SignedChoke(sc) => {
-                if let Err(e) = self.handle_signed_choke(ctx.clone(), sc).await {
-                    error!("Mlm: state handle signed choke error {:?}", e);
+        let msg = match msg {
+            MlmMsg::CompactProposal(cp) => {
+                match self.restore_compact(ctx.clone(), cp).await {
+                    Some(sp) => MlmMsg::SignedProposal(sp),
+                    None => return,
                 }
-                Ok(())
             }
+            MlmMsg::GetBlock(gb) => {
+                self.respond_block(ctx, gb).await;
+                return;
+            }
+            MlmMsg::BlockResponse(br) => match self.take_compact(br) {
+                Some(sp) => MlmMsg::SignedProposal(sp),
+                None => return,
+            },
+            MlmMsg::PartedProposal(pp) => match self.restore_parted(pp) {
+                Some(sp) => MlmMsg::SignedProposal(sp),
+                None => return,
+            },
+            MlmMsg::BlockPart(bp) => match self.take_part(bp) {
+                Some(sp) => MlmMsg::SignedProposal(sp),
+                None => return,
+            },
+            msg => msg,
+        };
 
-            MlmMsg::RichStatus(rs) => {
-                if let Err(e) = self.goto_new_height(ctx.clone(), rs).await {
-                    error!("Mlm: state handle rich status error {:?}", e);
-                }
-                Ok(())
+        if !self.check_hash_len(&ctx, &msg) {
+            return;
+        }
+
+        if let Some((height, round)) = self.future_view(&msg) {
+            if let Some(peer) = signer(&msg).cloned() {
+                self.future.push(height, round, peer, (ctx, msg));
+                return;
             }
+        }
 
-            MlmMsg::Stop => {
-                self.state_machine.trigger(SMRTrigger {
-                    trigger_type: TriggerType::Stop,
-                    source: TriggerSource::State,
-                    hash: Hash::new(),
-                    lock_round: None,
-                    round: self.round,
-                    height: self.height,
-                    wal_info: None,
-                })?;
-                self.stopped = true;
-                Ok(())
+        match self.height.cmp(&msg.get_height()) {
+            Ordering::Less => match self.next_epoch.as_ref() {
+                // The messages of the first height of the next epoch are verified by its
+                // scheduled authority list, the others are verified as the node goes to them.
+                Some(next) if next.height == msg.get_height() => {
+                    self.spawn_verify(parallel_verify(
+                        ctx,
+                        msg,
+                        self.trace.as_ref(),
+                        Arc::clone(&self.event_sink),
+                        Arc::clone(&self.function),
+                        Arc::clone(&self.util),
+                        Arc::clone(&self.wire_codec),
+                        Arc::clone(&next.authority),
+                        Arc::clone(&self.digests),
+                        self.verify_sig_tx.clone(),
+                    ));
+                }
+                _ => {
+                    let _ = self.verify_sig_tx.unbounded_send((ctx, msg));
+                }
+            },
+            Ordering::Equal => {
+                // A vote is marked as seen only once it passes the rate limit, so that its
+                // retransmission is not dropped as a duplicate of a vote never verified.
+                if !self.check_rate_limit(&ctx, &msg) {
+                    return;
+                }
+                if let MlmMsg::SignedVote(sv) = &msg {
+                    if !self.seen_votes.check(self.height, sv) {
+                        return;
+                    }
+                }
+                self.spawn_verify(parallel_verify(
+                    ctx,
+                    msg,
+                    self.trace.as_ref(),
+                    Arc::clone(&self.event_sink),
+                    Arc::clone(&self.function),
+                    Arc::clone(&self.util),
+                    Arc::clone(&self.wire_codec),
+                    Arc::clone(&self.authority),
+                    Arc::clone(&self.digests),
+                    self.verify_sig_tx.clone(),
+                ));
             }
+            Ordering::Greater => (),
+        };
+    }
 
-            // This is for unit tests.
-            #[cfg(test)]
-            MlmMsg::Commit(_) => Ok(()),
+    /// Whether the message is of an expired context, which is dropped since its work is
+    /// irrelevant. The statuses and the control messages never expire.
+    fn expired(&self, ctx: &Context, msg: &MlmMsg<T>) -> bool {
+        if msg.is_rich_status() || msg.is_control_msg() || !ctx.is_expired() {
+            return false;
         }
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state drop {} of an expired context, trace id {:?}, id {}",
+            msg,
+            ctx.trace_id(),
+            self.correlation_id()
+        );
+        true
     }
 
-    /// A function to handle event from the SMR. Public this function in the crate to do unit tests.
-    pub(crate) async fn handle_event(
-        &mut self,
-        event: Option<SMREvent>,
-    ) -> ConsensusResult<()> {
-        match event
-            .ok_or_else(|| ConsensusError::Other("Event sender dropped".to_string()))?
-        {
-            SMREvent::NewRoundInfo {
-                round,
-                lock_round,
-                lock_proposal,
+    /// Check the lengths of the block hashes carried by the message against the configured
+    /// hash length. A message of a hash of another length is dropped and reported to the
+    /// adapter before the verification, since its signatures can not be verified by the
+    /// crypto of the chain.
+    fn check_hash_len(&self, ctx: &Context, msg: &MlmMsg<T>) -> bool {
+        let len = self.config.hash_len;
+        if len == 0 {
+            return true;
+        }
+
+        let res = match msg {
+            MlmMsg::SignedProposal(sp) => {
+                sp.proposal.block_hash.check_len(len).and_then(|_| {
+                    sp.proposal
+                        .lock
+                        .as_ref()
+                        .map_or(Ok(()), |polc| polc.lock_votes.block_hash.check_len(len))
+                })
+            }
+            MlmMsg::SignedVote(sv) => sv.vote.block_hash.check_len(len),
+            MlmMsg::AggregatedVote(av) => av.block_hash.check_len(len),
+            _ => Ok(()),
+        };
+        if let Err(e) = res {
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state drop {} of an invalid hash {}, id {}",
+                msg,
+                e,
+                self.correlation_id()
+            );
+            self.function.report_invalid_msg(
+                ctx.clone(),
+                ctx.peer().map(String::from),
+                msg.to_string(),
+                e.to_string(),
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Take a token of the sender of the message before verifying it. The sender is the peer
+    /// of the transport that the message arrives from, since the signer that it claims is not
+    /// verified yet, and a forged message would drain the bucket of an honest signer. A
+    /// message without a peer is limited by its signer, and the signers out of the authority
+    /// list share one bucket, so the forged signers never grow the buckets.
+    fn check_rate_limit(&mut self, ctx: &Context, msg: &MlmMsg<T>) -> bool {
+        if !self.rate_limit.is_enabled() {
+            return true;
+        }
+
+        let peer = match (ctx.peer(), signer(msg)) {
+            (_, None) => return true,
+            (Some(peer), Some(_)) => peer.to_string(),
+            (None, Some(signer)) if self.authority.contains(signer) => hex_encode(signer),
+            (None, Some(_)) => String::new(),
+        };
+        if self.rate_limit.check(&peer) {
+            return true;
+        }
+
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state drop {} of {:?} by the rate limit, id {}",
+            msg,
+            peer,
+            self.correlation_id()
+        );
+        false
+    }
+
+    /// Restore the signed proposal of a compact proposal if the block is known, otherwise request
+    /// the block from the proposer and keep the compact proposal until the block arrives.
+    async fn restore_compact(
+        &mut self,
+        ctx: Context,
+        cp: CompactProposal,
+    ) -> Option<SignedProposal<T>> {
+        if cp.height < self.height {
+            return None;
+        }
+        if let Some(block) = self.hash_with_block.get(&cp.block_hash) {
+            return Some(cp.into_signed(block.clone()));
+        }
+
+        let height = self.height;
+        self.compact.retain(|(h, _, _), _| *h >= height);
+        let key = (cp.height, cp.round, cp.block_hash.clone());
+        if self.compact.contains_key(&key) || self.compact.len() >= COMPACT_PENDING_LIMIT
+        {
+            return None;
+        }
+
+        let proposer = cp.proposer.clone();
+        let get_block = GetBlock {
+            height: cp.height,
+            round: cp.round,
+            block_hash: cp.block_hash.clone(),
+            requester: self.address.clone(),
+        };
+        self.compact.insert(key, cp);
+        self.transmit_to(ctx, proposer, MlmMsg::GetBlock(get_block))
+            .await;
+        None
+    }
+
+    /// Respond the block of a compact proposal to the requester if the block is known.
+    async fn respond_block(&self, ctx: Context, gb: GetBlock) {
+        if !self.authority.contains(&gb.requester) {
+            return;
+        }
+
+        let content = match self.hash_with_block.get(&gb.block_hash) {
+            Some(block) => match block.encode() {
+                Ok(content) => content,
+                Err(e) => {
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state encode block error {:?}",
+                        e
+                    );
+                    return;
+                }
+            },
+            None => return,
+        };
+        let block_response = BlockResponse {
+            height: gb.height,
+            round: gb.round,
+            block_hash: gb.block_hash,
+            content,
+        };
+        self.transmit_to(ctx, gb.requester, MlmMsg::BlockResponse(block_response))
+            .await;
+    }
+
+    /// Restore the signed proposal of a pending compact proposal with the responded block. The
+    /// restored proposal is verified as a whole, so a wrong block fails the signature.
+    fn take_compact(&mut self, br: BlockResponse) -> Option<SignedProposal<T>> {
+        let cp = self.compact.remove(&(br.height, br.round, br.block_hash))?;
+        restore_block(self.log_scope(), cp, br.content)
+    }
+
+    /// Restore the signed proposal of a parted proposal if the block is known or all of the
+    /// parts of it have arrived, otherwise keep it until the parts arrive.
+    fn restore_parted(&mut self, pp: PartedProposal) -> Option<SignedProposal<T>> {
+        if pp.proposal.height < self.height {
+            return None;
+        }
+        if let Some(block) = self.hash_with_block.get(&pp.proposal.block_hash) {
+            return Some(pp.proposal.into_signed(block.clone()));
+        }
+
+        self.parts.prune(self.height);
+        let (cp, content) = self.parts.insert_proposal(pp)?;
+        restore_block(self.log_scope(), cp, content)
+    }
+
+    /// Verify a block part, and restore the signed proposal of it if it is the last part. The
+    /// restored proposal is verified as a whole, so a wrong proposal fails the signature.
+    fn take_part(&mut self, part: BlockPart) -> Option<SignedProposal<T>> {
+        if part.height < self.height {
+            return None;
+        }
+
+        self.parts.prune(self.height);
+        let (cp, content) = self.parts.insert_part(self.util.as_ref(), part)?;
+        restore_block(self.log_scope(), cp, content)
+    }
+
+    /// The height and round to replay a message at, if it should be buffered. The proposals and
+    /// the votes wait for their round, while the QCs and the chokes, which bring the node to
+    /// the higher rounds, only wait for their height. The messages beyond the future gaps are
+    /// left to the handlers, which drop them.
+    fn future_view(&self, msg: &MlmMsg<T>) -> Option<(u64, u64)> {
+        if !self.future.is_enabled() {
+            return None;
+        }
+
+        let (round, wait_round) = match msg {
+            MlmMsg::SignedProposal(sp) => (sp.proposal.round, true),
+            MlmMsg::SignedVote(sv) => (sv.get_round(), !self.config.fast_resync),
+            MlmMsg::AggregatedVote(av) => (av.get_round(), false),
+            MlmMsg::SignedChoke(sc) => (sc.choke.round, false),
+            _ => return None,
+        };
+        let height = msg.get_height();
+
+        if height > self.height
+            && height <= self.height + FUTURE_HEIGHT_GAP
+            && round <= FUTURE_ROUND_GAP
+        {
+            Some((height, if wait_round { round } else { INIT_ROUND }))
+        } else if height == self.height
+            && wait_round
+            && round > self.round
+            && round <= self.round + FUTURE_ROUND_GAP
+        {
+            Some((height, round))
+        } else {
+            None
+        }
+    }
+
+    /// A function to handle message from the network. Public this in the crate to do unit tests.
+    pub(crate) async fn handle_msg(
+        &mut self,
+        ctx: Context,
+        raw: MlmMsg<T>,
+    ) -> ConsensusResult<()> {
+        if !self.consensus_power && !raw.is_rich_status() && !raw.is_control_msg() {
+            return Ok(());
+        }
+        self.record_msg(&raw);
+        let attrs = SpanAttrs::default().with_kind(&raw);
+        let ctx = self.span(ctx, "mlm.handle_msg", attrs);
+        self.span_ctx = ctx.clone();
+
+        match raw {
+            MlmMsg::SignedProposal(sp) => {
+                if let Err(e) = self.handle_signed_proposal(ctx.clone(), sp).await {
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle signed proposal error {:?}",
+                        e
+                    );
+                }
+                Ok(())
+            }
+
+            MlmMsg::AggregatedVote(av) => {
+                if let Err(e) = self.handle_aggregated_vote(ctx.clone(), av).await {
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle aggregated vote error {:?}",
+                        e
+                    );
+                }
+                Ok(())
+            }
+
+            MlmMsg::SignedVote(sv) => {
+                if let Err(e) = self.handle_signed_vote(ctx.clone(), sv).await {
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle signed vote error {:?}",
+                        e
+                    );
+                }
+                Ok(())
+            }
+
+            MlmMsg::SignedChoke(sc) => {
+                if let Err(e) = self.handle_signed_choke(ctx.clone(), sc).await {
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle signed choke error {:?}",
+                        e
+                    );
+                }
+                Ok(())
+            }
+
+            MlmMsg::RichStatus(rs) => {
+                if let Err(e) = self.goto_new_height(ctx.clone(), rs).await {
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle rich status error {:?}",
+                        e
+                    );
+                }
+                Ok(())
+            }
+
+            MlmMsg::Stop => {
+                self.state_machine.trigger(SMRTrigger {
+                    trigger_type: TriggerType::Stop,
+                    source: TriggerSource::State,
+                    hash: Hash::new(),
+                    lock_round: None,
+                    round: self.round,
+                    height: self.height,
+                    wal_info: None,
+                })?;
+                self.stopped = true;
+                Ok(())
+            }
+
+            MlmMsg::UpdateConfig(config) => {
+                config.validate().map_err(ConsensusError::InvalidConfig)?;
+                node_log!(
+                    info,
+                    self.log_scope(),
+                    "Mlm: state update timer config {:?}",
+                    config
+                );
+                let before = self.runtime_config();
+                self.timer_config.update(config.clone());
+                self.duration_config = Some(config.clone());
+                self.publish_config_change(before, ConfigSource::TimerUpdate);
+                self.state_machine.trigger(SMRTrigger {
+                    trigger_type: TriggerType::UpdateConfig(config),
+                    source: TriggerSource::State,
+                    hash: Hash::new(),
+                    lock_round: None,
+                    round: self.round,
+                    height: self.height,
+                    wal_info: None,
+                })
+            }
+
+            MlmMsg::ForceViewChange(reason) => self.force_view_change(reason),
+
+            MlmMsg::RebroadcastCurrent => {
+                self.rebroadcast_current().await;
+                Ok(())
+            }
+
+            // The compact and parted proposal messages are handled before the verification.
+            MlmMsg::CompactProposal(_)
+            | MlmMsg::GetBlock(_)
+            | MlmMsg::BlockResponse(_)
+            | MlmMsg::PartedProposal(_)
+            | MlmMsg::BlockPart(_) => Ok(()),
+
+            // The vote batches are fanned out before the verification.
+            MlmMsg::SignedVoteBatch(_) => Ok(()),
+
+            MlmMsg::SignedHeartbeat(sh) => self.handle_heartbeat(sh),
+
+            MlmMsg::SignedKeyRotation(skr) => {
+                self.handle_key_rotation(skr.rotation);
+                Ok(())
+            }
+
+            // This is for unit tests.
+            #[cfg(test)]
+            MlmMsg::Commit(_) => Ok(()),
+        }
+    }
+
+    /// A function to handle event from the SMR. Public this function in the crate to do unit tests.
+    pub(crate) async fn handle_event(
+        &mut self,
+        event: Option<SMREvent>,
+    ) -> ConsensusResult<()> {
+        match event.ok_or_else(|| {
+            ConsensusError::ChannelErr("Event sender dropped".to_string())
+        })? {
+            SMREvent::NewRoundInfo {
+                round,
+                lock_round,
+                lock_proposal,
                 from_where,
                 ..
             } => {
@@ -278,7 +1495,13 @@ where
                     .handle_new_round(round, lock_round, lock_proposal, from_where)
                     .await
                 {
-                    error!("Mlm: state handle new round error {:?}", e);
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle new round error {:?}",
+                        e
+                    );
+                    self.report_fatal(e);
                 }
                 Ok(())
             }
@@ -292,7 +1515,13 @@ where
                     .handle_vote_event(block_hash, VoteType::Prevote, lock_round)
                     .await
                 {
-                    error!("Mlm: state handle prevote vote error {:?}", e);
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle prevote vote error {:?}",
+                        e
+                    );
+                    self.report_fatal(e);
                 }
                 Ok(())
             }
@@ -306,14 +1535,26 @@ where
                     .handle_vote_event(block_hash, VoteType::Precommit, lock_round)
                     .await
                 {
-                    error!("Mlm: state handle precommit vote error {:?}", e);
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle precommit vote error {:?}",
+                        e
+                    );
+                    self.report_fatal(e);
                 }
                 Ok(())
             }
 
             SMREvent::Commit(hash) => {
                 if let Err(e) = self.handle_commit(hash).await {
-                    error!("Mlm: state handle commit error {:?}", e);
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle commit error {:?}",
+                        e
+                    );
+                    self.report_fatal(e);
                 }
                 Ok(())
             }
@@ -328,24 +1569,35 @@ where
                 }
 
                 if let Err(e) = self.handle_brake(round, lock_round).await {
-                    error!("Mlm: state handle brake error {:?}", e);
+                    node_log!(
+                        error,
+                        self.log_scope(),
+                        "Mlm: state handle brake error {:?}",
+                        e
+                    );
+                    self.report_fatal(e);
                 }
                 Ok(())
             }
 
-            _ => unreachable!(),
+            // The timeout configuration only concerns the timer, and the state stops by its
+            // own flag.
+            SMREvent::UpdateConfig(_) | SMREvent::Stop => Ok(()),
         }
     }
 
     fn handle_resp(&mut self, msg: Option<VerifyResp>) -> ConsensusResult<()> {
-        let resp = msg
-            .ok_or_else(|| ConsensusError::Other("Event sender dropped".to_string()))?;
+        let resp = msg.ok_or_else(|| {
+            ConsensusError::ChannelErr("Event sender dropped".to_string())
+        })?;
         if resp.height != self.height {
             return Ok(());
         }
 
         let block_hash = resp.block_hash.clone();
-        info!(
+        node_log!(
+            info,
+            self.log_scope(),
             "Mlm: state receive a verify response true, height {}, round {}, hash {:?}",
             resp.height,
             resp.round,
@@ -388,6 +1640,137 @@ where
         Ok(())
     }
 
+    async fn handle_delayed_event(
+        &mut self,
+        event: DelayedEvent,
+    ) -> ConsensusResult<()> {
+        match event {
+            DelayedEvent::ProbeSigner => {
+                self.probe_signer();
+                Ok(())
+            }
+
+            DelayedEvent::StallCheck => {
+                self.check_stall();
+                Ok(())
+            }
+
+            DelayedEvent::ConnectivityCheck => {
+                self.check_connectivity();
+                Ok(())
+            }
+
+            DelayedEvent::Heartbeat => {
+                self.send_heartbeat();
+                Ok(())
+            }
+
+            DelayedEvent::Attestation => {
+                self.send_attestation();
+                Ok(())
+            }
+
+            DelayedEvent::KeyRotation(rotation) => {
+                self.send_key_rotation(rotation);
+                Ok(())
+            }
+
+            DelayedEvent::Signed {
+                id,
+                signature,
+                extension,
+            } => self.handle_signed(id, signature, extension).await,
+
+            DelayedEvent::ResendVote(signed_vote) => {
+                let vote = &signed_vote.vote;
+                if vote.height != self.height || vote.round != self.round {
+                    return Ok(());
+                }
+
+                if self
+                    .votes
+                    .get_qc_by_id(vote.height, vote.round, vote.vote_type.clone())
+                    .is_ok()
+                {
+                    return Ok(());
+                }
+
+                node_log!(
+                    info,
+                    self.log_scope(),
+                    "Mlm: state resend a signed {:?} vote, height {}, round {}",
+                    vote.vote_type,
+                    vote.height,
+                    vote.round
+                );
+                self.transmit(self.span_ctx.clone(), MlmMsg::SignedVote(signed_vote))
+                    .await;
+                Ok(())
+            }
+
+            DelayedEvent::FallbackVote { signed_vote, hop } => {
+                let vote = &signed_vote.vote;
+                if vote.height != self.height || vote.round != self.round {
+                    return Ok(());
+                }
+
+                let vote_type = vote.vote_type.clone();
+                if self
+                    .votes
+                    .get_qc_by_id(vote.height, vote.round, vote_type.clone())
+                    .is_ok()
+                {
+                    return Ok(());
+                }
+
+                let relayer = self.authority.get_relayer(&self.leader_address, hop)?;
+                node_log!(
+                    info,
+                    self.log_scope(),
+                    "Mlm: state route a signed {:?} vote to the fallback relayer {:?}, height {}, round {}, id {}",
+                    vote_type,
+                    hex_encode(relayer.clone()),
+                    vote.height,
+                    vote.round,
+                    self.correlation_id()
+                );
+                self.schedule_fallback(&vote_type, signed_vote.clone(), hop + 1);
+
+                if relayer == self.address {
+                    self.handle_signed_vote(Context::new(), signed_vote).await
+                } else {
+                    let ctx = self.correlation_id().attach_to(Context::new());
+                    let msg = MlmMsg::SignedVote(signed_vote);
+                    self.record_sent(&msg);
+                    let _ = self
+                        .function
+                        .transmit_to_relayer(ctx, relayer, msg)
+                        .await
+                        .map_err(|err| {
+                            node_log!(
+                                error,
+                                self.log_scope(),
+                                "Mlm: state transmit vote to fallback relayer failed {:?}",
+                                err
+                            );
+                        });
+                    Ok(())
+                }
+            }
+
+            DelayedEvent::ProposalWait { height, round } => {
+                if height != self.height || round != self.round {
+                    return Ok(());
+                }
+
+                if let Some((signed_proposal, ctx)) = self.candidates.take_best() {
+                    self.trigger_proposal(ctx, signed_proposal).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
     /// On receiving a rich status will call this method. This status can be either the return value
     /// of the `commit()` interface, or lastest status after the synchronization is completed send
     /// by the mlm handler.
@@ -399,48 +1782,119 @@ where
     /// Finally, trigger SMR to goto new height.
     async fn goto_new_height(
         &mut self,
-        _ctx: Context,
+        ctx: Context,
         status: Status,
     ) -> ConsensusResult<()> {
         if status.height <= self.height {
-            warn!(
+            node_log!(
+                warn,
+                self.log_scope(),
                 "Mlm: state receive an outdated status, height {}, self height {}",
-                status.height, self.height
+                status.height,
+                self.height
             );
             return Ok(());
         }
+        self.enter_height(ctx, status, false).await
+    }
+
+    /// Restart the current height by the status of the pipelined commit, which differs from the
+    /// provisional status that the height is running with. The status is applied as the one of
+    /// a new height, and the SMR which is at the height already is reset to the current round,
+    /// or to the one after the last signed round of the height, since the rounds that have been
+    /// signed in cannot be entered again.
+    async fn restart_height(
+        &mut self,
+        ctx: Context,
+        status: Status,
+    ) -> ConsensusResult<()> {
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state restart height {} by the status of pipelined commit",
+            self.height
+        );
+        self.enter_height(ctx, status, true).await
+    }
+
+    /// Enter the height of the status, or restart the current height by it if `restart`.
+    async fn enter_height(
+        &mut self,
+        ctx: Context,
+        status: Status,
+        restart: bool,
+    ) -> ConsensusResult<()> {
+        self.check_timer_config(ctx.clone(), &status)?;
+        self.check_authority_update(ctx, &status)?;
 
+        self.end_brake();
+        self.brake_rounds = 0;
+        self.round_escalations = 0;
         let new_height = status.height;
+        if new_height > self.height + 1 {
+            self.abandon_height(new_height);
+        }
+        let new_round = match self.last_signed.as_ref() {
+            Some(last) if restart => last.restart_round(new_height, self.round),
+            _ if restart => self.round,
+            _ => INIT_ROUND,
+        };
         self.height = new_height;
-        self.round = INIT_ROUND;
+        self.round = new_round;
+        self.quorum_views.prune(new_height);
+        let status = self.epoch_status(status);
+        self.notify_authority_change(status.height, &status.authority_list);
+        self.schedule_epoch(&status);
 
         // Check the consensus power.
         self.consensus_power = status.is_consensus_node(&self.address);
         if !self.consensus_power {
-            info!(
+            node_log!(
+                info,
+                self.log_scope(),
                 "Mlm: self does not have consensus power height {}",
                 new_height
             );
             return Ok(());
         }
 
-        info!("Mlm: state goto new height {}", self.height);
-
-        self.save_wal(Step::Propose, None).await?;
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state goto new height {}",
+            self.height
+        );
 
-        // Update height and authority list.
+        // Update height and authority list. The status is applied before saving the wal, so
+        // that the wal carries it.
         self.height_start = Instant::now();
+        self.stall.progress();
+        let before = self.runtime_config();
         let mut auth_list = status.authority_list.clone();
-        self.authority.update(&mut auth_list);
+        self.update_authority(&mut auth_list);
+        self.update_proposer_seed(&status);
+        self.update_skipped_proposers(&status);
 
         if let Some(interval) = status.interval {
             self.block_interval = interval;
+            self.timer_config.set_interval(interval);
         }
+        if let Some(config) = status.timer_config.clone() {
+            self.timer_config.update(config.clone());
+            self.duration_config = Some(config);
+        }
+        self.publish_config_change(before, ConfigSource::Status);
+
+        self.save_wal(Step::Propose, None).await?;
+        self.save_checkpoint().await;
 
         // Clear outdated proposals and votes.
-        self.proposals.flush(new_height - 1);
-        self.votes.flush(new_height - 1);
+        self.digests.flush(new_height);
+        let retention = self.config.height_retention.saturating_add(1);
+        self.expire_heights(new_height.saturating_sub(retention));
         self.hash_with_block.clear();
+        self.pre_checked.clear();
+        self.round_timestamps.clear();
         self.chokes.clear();
 
         // Re-check proposals that have been in the proposal collector, of the current height.
@@ -454,31 +1908,99 @@ where
             self.re_check_qcs(qcs).await?;
         }
 
-        self.state_machine.new_height_status(status.into())?;
-        Ok(())
-    }
-
-    /// Handle `NewRoundInfo` event from SMR. Firstly, goto new round and check the `XOR`
-    /// relationship between the lock round type and the lock proposal type. Secondly, check if self
-    /// is a proposer. If is not a proposer, return `Ok(())` and wait for a signed proposal from the
-    /// network. Otherwise, make up a proposal, broadcast it and touch off SMR trigger.
-    async fn handle_new_round(
-        &mut self,
+        if !restart {
+            return self.state_machine.new_height_status(status.into());
+        }
+        if let Some(config) = status.timer_config {
+            self.state_machine.trigger(SMRTrigger {
+                trigger_type: TriggerType::UpdateConfig(config),
+                source: TriggerSource::State,
+                hash: Hash::new(),
+                lock_round: None,
+                round: self.round,
+                height: self.height,
+                wal_info: None,
+            })?;
+        }
+        self.reset_smr()
+    }
+
+    /// Abandon the current height for a status far ahead of it, which is usually synchronized
+    /// by the application. The in-flight rounds, the pending commit and the messages of the
+    /// heights lower than the new one are dropped, and the wal is overwritten by the one of
+    /// the new height afterwards.
+    fn abandon_height(&mut self, new_height: u64) {
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state jump from height {}, round {} to height {}",
+            self.height,
+            self.round,
+            new_height
+        );
+
+        self.pending_commit = None;
+        self.forced_view_change = None;
+        self.is_full_transcation.clear();
+        self.digests.flush(new_height);
+        self.expire_heights(new_height);
+        self.events.publish(
+            new_height,
+            INIT_ROUND,
+            MlmEventKind::HeightJump { from: self.height },
+        );
+    }
+
+    /// Handle `NewRoundInfo` event from SMR. Firstly, goto new round and check the `XOR`
+    /// relationship between the lock round type and the lock proposal type. Secondly, check if self
+    /// is a proposer. If is not a proposer, return `Ok(())` and wait for a signed proposal from the
+    /// network. Otherwise, make up a proposal, broadcast it and touch off SMR trigger.
+    async fn handle_new_round(
+        &mut self,
         new_round: u64,
         lock_round: Option<u64>,
         lock_proposal: Option<Hash>,
         from_where: FromWhere,
     ) -> ConsensusResult<()> {
-        info!("Mlm: state goto new round {}", new_round);
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state goto new round {}, id {}",
+            new_round,
+            CorrelationId::new(self.height, new_round)
+        );
+        if self.brake_start.is_none() {
+            self.brake_rounds = 0;
+        }
+        self.end_brake();
 
         if new_round != INIT_ROUND {
             let last_round = self.round;
             let reason = self.view_change_reason(last_round, &from_where);
+            self.events.publish(
+                self.height,
+                last_round,
+                MlmEventKind::ViewChange {
+                    reason: reason.clone(),
+                },
+            );
+            self.protocol.on_view_change(&reason);
             self.report_view_change(last_round, reason);
         }
 
         self.round = new_round;
+        self.protocol.on_new_round();
+        self.check_max_rounds();
         self.is_leader = false;
+        self.events
+            .publish(self.height, new_round, MlmEventKind::NewRound);
+        self.round_start = Instant::now();
+        self.step_starts = vec![(Step::Propose, self.round_start)];
+        self.timing.on_new_round(new_round);
+        self.lock_round = lock_round;
+        self.own_proposal = None;
+        self.own_votes.clear();
+        self.candidates.reset(self.height, new_round);
 
         if lock_round.is_some().bitxor(lock_proposal.is_some()) {
             return Err(ConsensusError::ProposalErr(
@@ -489,10 +2011,19 @@ where
         self.set_update_from(from_where)?;
         self.save_wal_with_lock_round(Step::Propose, lock_round)
             .await?;
+        self.append_step(StepRecord::NewRound {
+            height: self.height,
+            round: new_round,
+        })
+        .await;
 
         // If self is not proposer, check whether it has received current signed proposal before. If
         // has, then handle it.
         if !self.is_proposer()? {
+            if lock_round.is_none() {
+                self.propose_as_backup().await?;
+            }
+
             if let Ok((signed_proposal, ctx)) =
                 self.proposals.get(self.height, self.round)
             {
@@ -514,19 +2045,19 @@ where
         // done by doing this. These things consititute a Proposal. Then sign it and broadcast it to
         // other nodes.
         self.is_leader = true;
-        let ctx = Context::new();
-        let (block, hash, polc) = if lock_round.is_none() {
-            let (new_block, new_hash) = self
-                .function
-                .get_block(ctx.clone(), self.height)
-                .await
-                .map_err(|err| {
-                    ConsensusError::Other(format!("get block error {:?}", err))
-                })?;
-            (new_block, new_hash, None)
-        } else {
-            let round = lock_round.unwrap();
-            let hash = lock_proposal.unwrap();
+        let ctx = self
+            .round_context(
+                self.address.clone(),
+                self.timer_config.get_propose_timeout(),
+            )
+            .attach_to(Context::new());
+        let (block, hash, polc) = if let Some(round) = lock_round {
+            let hash = lock_proposal.ok_or_else(|| {
+                ConsensusError::invariant(format!(
+                    "lock round {} without the lock proposal",
+                    round
+                ))
+            })?;
             let block = self.hash_with_block.get(&hash).ok_or_else(|| {
                 ConsensusError::ProposalErr(format!(
                     "Lose whole block that hash is {:?}",
@@ -546,12 +2077,19 @@ where
                 lock_votes: qc,
             };
             (block.to_owned(), hash, Some(polc))
+        } else {
+            let (new_block, new_hash) = match self.get_block(ctx.clone()).await {
+                Some(res) => res,
+                None => return Ok(()),
+            };
+            (new_block, new_hash, None)
         };
 
         self.hash_with_block
             .entry(hash.clone())
             .or_insert_with(|| block.clone());
 
+        let timestamp = self.proposal_timestamp();
         let proposal = Proposal {
             height: self.height,
             round: self.round,
@@ -559,20 +2097,68 @@ where
             block_hash: hash.clone(),
             lock: polc.clone(),
             proposer: self.address.clone(),
+            timestamp,
         };
 
-        info!(
-            "Mlm: state broadcast a signed proposal height {}, round {}, hash {:?} and trigger SMR",
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state broadcast a signed proposal height {}, round {}, id {}, hash {:?} and trigger SMR",
             self.height,
             self.round,
+            self.correlation_id(),
             hex_encode(hash.clone())
         );
 
-        self.broadcast(
-            Context::new(),
-            MlmMsg::SignedProposal(self.sign_proposal(proposal)?),
-        )
-        .await;
+        match self.sign_proposal(ctx, proposal, lock_round).await {
+            Ok(()) => Ok(()),
+            Err(e) => self.skip_propose(e),
+        }
+    }
+
+    /// Prevote as the propose step timeout to change the view quickly if the proposal of self
+    /// fails to be signed.
+    fn skip_propose(&mut self, e: ConsensusError) -> ConsensusResult<()> {
+        node_log!(
+            error,
+            self.log_scope(),
+            "Mlm: state sign proposal error {:?}, skip propose",
+            e
+        );
+        self.state_machine.trigger(SMRTrigger {
+            trigger_type: TriggerType::Proposal,
+            source: TriggerSource::Timer,
+            hash: Hash::new(),
+            lock_round: None,
+            round: self.round,
+            height: self.height,
+            wal_info: None,
+        })
+    }
+
+    /// Broadcast the signed proposal of self as the leader, and trigger SMR by it.
+    async fn send_proposal(
+        &mut self,
+        ctx: Context,
+        signed_proposal: SignedProposal<T>,
+        lock_round: Option<u64>,
+    ) -> ConsensusResult<()> {
+        let proposal = &signed_proposal.proposal;
+        let (hash, block, timestamp) = (
+            proposal.block_hash.clone(),
+            proposal.content.clone(),
+            proposal.timestamp,
+        );
+        if self.config.vrf_leader && proposal.lock.is_none() {
+            self.propose_by_vrf(ctx, signed_proposal).await?;
+            if let Ok((signed_proposal, ctx)) =
+                self.proposals.get(self.height, self.round)
+            {
+                return self.handle_signed_proposal(ctx, signed_proposal).await;
+            }
+            return Ok(());
+        }
+        self.broadcast_proposal(signed_proposal).await;
 
         self.state_machine.trigger(SMRTrigger {
             trigger_type: TriggerType::Proposal,
@@ -584,23 +2170,16 @@ where
             wal_info: None,
         })?;
 
-        self.check_block(ctx, hash, block).await;
+        if let Some(timestamp) = timestamp {
+            self.round_timestamps.insert(self.round, timestamp);
+        }
+        self.check_block(ctx, self.address.clone(), hash, block, timestamp)
+            .await;
         Ok(())
     }
 
     /// This function only handle signed proposals which height and round are equal to current.
     /// Others will be ignored or stored in the proposal collector.
-    #[tracing_span(
-        kind = "mlm",
-        tags = "{
-            'height': 'signed_proposal.proposal.height', 
-            'round': 'signed_proposal.proposal.round'
-        }",
-        logs = "{
-            'proposal_hash': 'hex_encode(signed_proposal.proposal.block_hash.clone())',
-            'proposer': 'hex_encode(signed_proposal.proposal.proposer.clone())'
-        }"
-    )]
     async fn handle_signed_proposal(
         &mut self,
         ctx: Context,
@@ -608,8 +2187,14 @@ where
     ) -> ConsensusResult<()> {
         let proposal_height = signed_proposal.proposal.height;
         let proposal_round = signed_proposal.proposal.round;
-
-        info!(
+        let attrs = SpanAttrs::round(proposal_height, proposal_round)
+            .with_hash(signed_proposal.proposal.block_hash.clone())
+            .with_from(signed_proposal.proposal.proposer.clone());
+        let ctx = self.span(ctx, "mlm.handle_signed_proposal", attrs);
+
+        node_log!(
+            info,
+            self.log_scope(),
             "Mlm: state receive a signed proposal height {}, round {}, from {:?}, hash {:?}",
             proposal_height,
             proposal_round,
@@ -618,11 +2203,42 @@ where
         );
 
         // Verify proposer before filter proposal.
-        self.verify_proposer(
-            proposal_height,
-            proposal_round,
-            &signed_proposal.proposal.proposer,
-        )?;
+        let proposer = &signed_proposal.proposal.proposer;
+        let rank = self
+            .proposal_rank(&signed_proposal)
+            .inspect_err(|_| {
+                let event = if self.authority.contains(proposer) {
+                    ConsensusEvent::ProposalFromWrongProposer {
+                        height: proposal_height,
+                        round: proposal_round,
+                        proposer: proposer.clone(),
+                    }
+                } else {
+                    ConsensusEvent::UnknownSigner {
+                        kind: "Signed Proposal".to_string(),
+                        height: proposal_height,
+                        round: proposal_round,
+                        signer: proposer.clone(),
+                    }
+                };
+                self.event_sink.on_event(ctx.clone(), event);
+            })?;
+
+        if proposal_height == self.height
+            && !self.check_timestamp(&ctx, &signed_proposal)
+        {
+            return Ok(());
+        }
+
+        if let Some(tc) = signed_proposal.timeout_cert.as_ref() {
+            self.jump_by_timeout_cert(proposal_height, proposal_round, tc)?;
+        }
+
+        if self.multi_proposal_enabled()
+            && self.keep_cached_candidate(proposal_height, proposal_round, &rank)
+        {
+            return Ok(());
+        }
 
         if self.filter_signed_proposal(
             ctx.clone(),
@@ -633,13 +2249,32 @@ where
             return Ok(());
         }
 
+        if self.multi_proposal_enabled() {
+            return self.handle_candidate(ctx, rank, signed_proposal).await;
+        }
+
+        self.trigger_proposal(ctx, signed_proposal).await
+    }
+
+    /// Save the signed proposal of the current height and round, then trigger it to SMR and
+    /// check the block.
+    async fn trigger_proposal(
+        &mut self,
+        ctx: Context,
+        signed_proposal: SignedProposal<T>,
+    ) -> ConsensusResult<()> {
+        let proposal_height = signed_proposal.proposal.height;
+        let proposal_round = signed_proposal.proposal.round;
         let proposal = signed_proposal.proposal.clone();
-        let signature = signed_proposal.signature.clone();
 
         // If the signed proposal is with a lock, check the lock round and the QC then trigger it to
         // SMR. Otherwise, touch off SMR directly.
         let lock_round = if let Some(polc) = proposal.lock.clone() {
-            debug!("Mlm: state receive a signed proposal with a lock");
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state receive a signed proposal with a lock"
+            );
             Some(polc.lock_round)
         } else {
             None
@@ -648,6 +2283,10 @@ where
         let hash = proposal.block_hash.clone();
         let block = proposal.content.clone();
         self.hash_with_block.insert(hash.clone(), proposal.content);
+        // The candidate cached before the round may not be the preferred one.
+        if self.multi_proposal_enabled() {
+            self.proposals.remove(self.height, self.round);
+        }
         self.proposals.insert(
             ctx.clone(),
             self.height,
@@ -655,13 +2294,24 @@ where
             signed_proposal.clone(),
         )?;
 
-        info!(
-            "Mlm: state trigger SMR proposal height {}, round {}, hash {:?}",
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state trigger SMR proposal height {}, round {}, id {}, hash {:?}",
             self.height,
             self.round,
+            self.correlation_id(),
             hex_encode(hash.clone())
         );
 
+        self.events.publish(
+            self.height,
+            self.round,
+            MlmEventKind::ProposalReceived {
+                proposer: signed_proposal.proposal.proposer.clone(),
+                block_hash: hash.clone(),
+            },
+        );
         self.state_machine.trigger(SMRTrigger {
             trigger_type: TriggerType::Proposal,
             source: TriggerSource::State,
@@ -672,8 +2322,14 @@ where
             wal_info: None,
         })?;
 
-        debug!("Mlm: state check the whole block");
-        self.check_block(ctx, hash, block).await;
+        node_log!(debug, self.log_scope(), "Mlm: state check the whole block");
+        let proposer = signed_proposal.proposal.proposer.clone();
+        let timestamp = signed_proposal.proposal.timestamp;
+        if let Some(timestamp) = timestamp {
+            self.round_timestamps.insert(proposal_round, timestamp);
+        }
+        self.check_block(ctx, proposer, hash, block, timestamp)
+            .await;
         Ok(())
     }
 
@@ -683,41 +2339,94 @@ where
         vote_type: VoteType,
         lock_round: Option<u64>,
     ) -> ConsensusResult<()> {
-        info!(
-            "Mlm: state receive {:?} vote event height {}, round {}, hash {:?}",
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state receive {:?} vote event height {}, round {}, id {}, hash {:?}",
             vote_type.clone(),
             self.height,
             self.round,
+            self.correlation_id(),
             hex_encode(hash.clone())
         );
 
-        let signed_vote = self.sign_vote(Vote {
+        self.enter_step(vote_type.clone().into());
+        self.lock_round = lock_round;
+
+        // In pipelined mode, the status of the last height must be applied before precommit.
+        if vote_type == VoteType::Precommit
+            && self.pending_commit.is_some()
+            && (self.apply_pending_commit().await? || !self.consensus_power)
+        {
+            return Ok(());
+        }
+
+        // A lock is taken by the precommit of the round of its prevote QC.
+        if vote_type == VoteType::Precommit
+            && lock_round == Some(self.round)
+            && !hash.is_empty()
+        {
+            self.append_step(StepRecord::Locked {
+                height: self.height,
+                round: self.round,
+                block_hash: hash.clone(),
+            })
+            .await;
+        }
+
+        let hash = if vote_type == VoteType::Prevote
+            && self.config.compact_proposal
+            && !hash.is_empty()
+        {
+            self.check_availability(hash).await
+        } else {
+            hash
+        };
+
+        let vote = Vote {
             height: self.height,
             round: self.round,
-            vote_type: vote_type.clone(),
-            block_hash: hash.clone(),
-        })?;
+            vote_type,
+            block_hash: hash,
+        };
+        self.sign_vote(vote, lock_round).await
+    }
 
+    /// Send the signed vote of self, to the leader or to self if self is the leader, and
+    /// trigger SMR if the QC of the step is formed.
+    async fn send_vote(
+        &mut self,
+        signed_vote: SignedVote,
+        lock_round: Option<u64>,
+    ) -> ConsensusResult<()> {
+        let vote_type = signed_vote.vote.vote_type.clone();
+        let hash = signed_vote.vote.block_hash.clone();
         self.save_wal_with_lock_round(vote_type.clone().into(), lock_round)
             .await?;
 
+        self.own_votes.push(signed_vote.clone());
+        self.schedule_fallback(&vote_type, signed_vote.clone(), 1);
+        self.transmit_to_backup_relayers(&signed_vote).await?;
         if self.is_leader {
-            self.votes.insert_vote(
-                Context::new(),
-                signed_vote.get_hash(),
-                signed_vote,
-                self.address.clone(),
-            );
+            self.collect_vote(Context::new(), signed_vote);
+            self.save_round_votes(self.height, self.round).await;
         } else {
-            info!(
-                "Mlm: state transmit a signed vote, height {}, round {}, hash {:?}",
+            node_log!(
+                info,
+                self.log_scope(),
+                "Mlm: state transmit a signed vote, height {}, round {}, id {}, hash {:?}",
                 self.height,
                 self.round,
+                self.correlation_id(),
                 hex_encode(hash)
             );
 
-            self.transmit(Context::new(), MlmMsg::SignedVote(signed_vote))
-                .await;
+            self.transmit(
+                self.span_ctx.clone(),
+                MlmMsg::SignedVote(signed_vote.clone()),
+            )
+            .await;
+            self.schedule_resend(&vote_type, signed_vote);
         }
 
         self.vote_process(vote_type).await?;
@@ -736,52 +2445,99 @@ where
             )));
         }
 
+        if self.timer_config.is_choke_free() {
+            node_log!(
+                info,
+                self.log_scope(),
+                "Mlm: state go on round {} without a choke, height {}, id {}",
+                self.round + 1,
+                self.height,
+                self.correlation_id()
+            );
+            self.state_machine.trigger(SMRTrigger {
+                trigger_type: TriggerType::ContinueRound,
+                source: TriggerSource::State,
+                hash: Hash::new(),
+                lock_round: None,
+                round: self.round + 1,
+                height: self.height,
+                wal_info: None,
+            })?;
+            return Ok(());
+        }
+
         let choke = Choke {
             height: self.height,
             round: self.round,
             from: self.update_from_where.clone(),
         };
 
-        let signature = self
-            .util
-            .sign(self.util.hash(Bytes::from(rlp::encode(&choke.to_hash()))))
-            .map_err(|err| {
-                ConsensusError::CryptoErr(format!("sign choke error {:?}", err))
-            })?;
-        let signed_choke = SignedChoke {
-            signature,
-            choke,
-            address: self.address.clone(),
-        };
+        let hash = self.digests.choke(
+            self.util.as_ref(),
+            self.wire_codec.as_ref(),
+            choke.height,
+            choke.round,
+        )?;
+        self.sign(Step::Brake, hash, Unsigned::Choke { choke, lock_round })
+            .await
+    }
 
-        info!(
-            "Mlm: state broadcast a signed brake in height {}, round {}",
-            self.height, self.round
+    /// Broadcast the signed choke of self and go on the next round if the chokes are above
+    /// the threshold.
+    async fn send_choke(
+        &mut self,
+        signed_choke: SignedChoke,
+        lock_round: Option<u64>,
+    ) -> ConsensusResult<()> {
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state broadcast a signed brake in height {}, round {}, id {}",
+            self.height,
+            self.round,
+            self.correlation_id()
         );
 
+        self.protocol.on_brake(self.height, self.round);
+        if self.brake_start.is_none() {
+            self.brake_start =
+                Some((self.height, self.round, tokio::time::Instant::now()));
+        }
+        self.count_brake_round();
+        self.enter_step(Step::Brake);
+        self.lock_round = lock_round;
         self.chokes.insert(self.round, signed_choke.clone());
         self.save_wal_with_lock_round(Step::Brake, lock_round)
             .await?;
-        self.broadcast(Context::new(), MlmMsg::SignedChoke(signed_choke))
+        self.broadcast(self.span_ctx.clone(), MlmMsg::SignedChoke(signed_choke))
             .await;
         self.check_choke_above_threshold()?;
         Ok(())
     }
 
     async fn handle_commit(&mut self, hash: Hash) -> ConsensusResult<()> {
-        info!(
-            "Mlm: state receive commit event height {}, round {}, hash {:?}",
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state receive commit event height {}, round {}, id {}, hash {:?}",
             self.height,
             self.round,
+            self.correlation_id(),
             hex_encode(hash.clone())
         );
 
-        debug!("Mlm: state get origin block");
+        // The height is restarted by the status of the pending commit, and the block of the
+        // hash is dropped with the height.
+        if self.apply_pending_commit().await? {
+            return Ok(());
+        }
+
+        node_log!(debug, self.log_scope(), "Mlm: state get origin block");
         let height = self.height;
         let content = if let Some(tmp) = self.hash_with_block.get(&hash) {
             tmp.to_owned()
         } else {
-            return Err(ConsensusError::Other(format!(
+            return Err(ConsensusError::StateErr(format!(
                 "Lose whole block height {}, round {}",
                 self.height, self.round
             )));
@@ -795,150 +2551,432 @@ where
         } else {
             return Err(ConsensusError::StorageErr("Lose precommit QC".to_string()));
         };
+        self.check_commit_hash(height, qc.round, &hash, &content)?;
 
-        let polc = Some(WalLock {
+        let polc = WalLock {
             lock_round: self.round,
             lock_votes: qc.clone(),
             content: content.clone(),
-        });
-        self.save_wal(Step::Commit, polc).await?;
+        };
+        self.save_wal(Step::Commit, Some(polc.clone())).await?;
+        self.append_step(StepRecord::Committed {
+            height,
+            round: qc.round,
+            block_hash: hash.clone(),
+        })
+        .await;
 
-        debug!("Mlm: state generate proof");
+        node_log!(debug, self.log_scope(), "Mlm: state generate proof");
 
         let proof = Proof {
             height,
             round: qc.round,
             block_hash: hash.clone(),
             signature: qc.signature.clone(),
+            extensions: qc.extensions.clone(),
         };
+        self.save_proof(&proof).await;
+        self.save_handover(&proof).await;
+        self.archive_proof(&proof).await;
+        self.export_votes(&proof).await;
+
+        let committed_at = now_millis();
+        let since = |start: Instant| {
+            committed_at.saturating_sub(start.elapsed().as_millis() as u64)
+        };
+        let proof_round = proof.round;
+        let timestamp = self.round_timestamps.get(&proof_round).copied();
+        if let Some(timestamp) = timestamp {
+            self.median_time.on_commit(timestamp);
+        }
         let commit = Commit {
             height,
             content,
+            round: proof.round,
             proof,
+            height_start: since(self.height_start),
+            round_start: since(self.round_start),
+            committed_at,
+            timestamp,
         };
 
-        let ctx = Context::new();
-        let status = self
-            .function
-            .commit(ctx.clone(), height, commit)
-            .await
-            .map_err(|err| ConsensusError::Other(format!("commit error {:?}", err)))?;
+        let ctx = self.correlation_id().attach_to(Context::new());
+        let status = if self.config.pipelined {
+            self.spawn_commit(ctx.clone(), height, commit, polc)
+        } else {
+            let status = self
+                .function
+                .commit(ctx.clone(), height, commit)
+                .await
+                .map_err(|err| {
+                    ConsensusError::with_boxed_source(ErrorKind::Adapter, "commit", err)
+                })?;
 
-        let mut auth_list = status.authority_list.clone();
-        self.authority.update(&mut auth_list);
-        let cost = Instant::now() - self.height_start;
+            let status = self.epoch_status(status);
+            self.notify_authority_change(status.height, &status.authority_list);
+            let mut auth_list = status.authority_list.clone();
+            self.update_authority(&mut auth_list);
+            self.update_proposer_seed(&status);
+            self.update_skipped_proposers(&status);
+            status
+        };
+        let now = Instant::now();
+        let cost = now - self.height_start;
+        self.tuner.on_commit(height, now, self.block_interval);
+        self.protocol.on_commit(self.round);
+        self.report_height(height, proof_round, cost);
+        self.events.publish(
+            height,
+            self.round,
+            MlmEventKind::Committed {
+                block_hash: hash.clone(),
+            },
+        );
 
-        info!(
-            "Mlm: achieve consensus in height {}, costs {} round {:?} time",
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: achieve consensus in height {}, costs {} round {:?} time, id {}",
             self.height,
             self.round + 1,
-            cost
+            cost,
+            self.correlation_id()
         );
 
-        if self.next_proposer(status.height, INIT_ROUND)?
-            && cost < Duration::from_millis(self.block_interval)
-        {
-            sleep(Duration::from_millis(self.block_interval) - cost).await;
+        let interval = self.tuner.interval(self.block_interval);
+        if self.proposes_first(&status)? && cost < interval {
+            sleep(interval - cost).await;
         }
 
         self.goto_new_height(ctx, status).await?;
         Ok(())
     }
 
-    /// The main process of handle signed vote is that only handle those height and round are both
-    /// equal to the current. The lower votes will be ignored directly even if the height is equal
-    /// to the `current height - 1` and the round is higher than the current round. The reason is
-    /// that the effective leader must in the lower height, and the task of handling signed votes
-    /// will be done by the leader. For the higher votes, check the signature and save them in
-    /// the vote collector. Whenevet the current vote is received, a statistic is made to check
-    /// if the sum of the voting weights corresponding to the hash exceeds the threshold.
-    #[tracing_span(
-        kind = "mlm",
-        tags = "{
-            'height': 'signed_vote.vote.height', 
-            'round': 'signed_vote.vote.round', 
-            'vote_type': 'signed_vote.vote.vote_type'
-        }",
-        logs = "{
-            'vote_hash': 'hex_encode(signed_vote.vote.block_hash.clone())',
-            'voter': 'hex_encode(signed_vote.voter.clone())'
-        }"
-    )]
-    async fn handle_signed_vote(
-        &mut self,
-        ctx: Context,
-        signed_vote: SignedVote,
+    /// Check the hash of the block to commit against the one computed by the adapter, and
+    /// refuse to commit a block that is not of the decided hash.
+    fn check_commit_hash(
+        &self,
+        height: u64,
+        round: u64,
+        hash: &Hash,
+        block: &T,
     ) -> ConsensusResult<()> {
-        let height = signed_vote.get_height();
-        let round = signed_vote.get_round();
-        let vote_type = if signed_vote.is_prevote() {
-            VoteType::Prevote
-        } else {
-            VoteType::Precommit
+        let (computed_hash, block_digest) = match block_hash_mismatch(
+            self.function.as_ref(),
+            self.util.as_ref(),
+            height,
+            hash,
+            block,
+        ) {
+            Some(mismatch) => mismatch,
+            None => return Ok(()),
         };
-
-        info!(
-            "Mlm: state receive a signed {:?} vote height {}, round {}, from {:?}, hash {:?}",
-            vote_type,
+        let proposer = match self.proposals.get(height, round) {
+            Ok((signed_proposal, _)) => signed_proposal.proposal.proposer,
+            Err(_) => self
+                .authority
+                .get_proposer(height, round)
+                .unwrap_or_default(),
+        };
+        Err(ConsensusError::BlockHashMismatch(Box::new(HashMismatch {
+            stage: MismatchStage::Commit,
             height,
             round,
-            hex_encode(signed_vote.voter.clone()),
-            hex_encode(signed_vote.vote.block_hash.clone())
-        );
-
-        if self.filter_message(height, round) {
-            return Ok(());
-        }
+            proposer,
+            proposal_hash: hash.clone(),
+            computed_hash,
+            block_digest,
+        })))
+    }
 
-        let tmp_type: String = vote_type.to_string();
+    /// Execute the commit in the background for pipelined mode. Return a provisional status of
+    /// the next height which keeps the current authority list and skipped proposers, or takes
+    /// the scheduled authority list if the height is the last of an epoch. The real status is
+    /// applied by `apply_pending_commit()` before precommit, and the commit is kept in the wal
+    /// until then.
+    fn spawn_commit(
+        &mut self,
+        ctx: Context,
+        height: u64,
+        commit: Commit<T>,
+        lock: WalLock<T>,
+    ) -> Status {
+        let (tx, rx) = oneshot::channel();
+        let function = Arc::clone(&self.function);
 
-        // All the votes must pass the verification of signature and address before be saved into
-        // vote collector.
-        let signature = signed_vote.signature.clone();
-        let voter = signed_vote.voter.clone();
-        let vote = signed_vote.vote.clone();
-        self.verify_address(&voter)?;
+        self.runtime.spawn(Box::pin(async move {
+            let status = function.commit(ctx, height, commit).await.map_err(|err| {
+                ConsensusError::with_boxed_source(ErrorKind::Adapter, "commit", err)
+            });
+            let _ = tx.send(status);
+        }));
 
-        // Check if the quorum certificate has generated before check whether there is a hash that
-        // vote weight is above the threshold. If no hash achieved this, return directly.
-        if self
-            .votes
-            .get_qc_by_id(height, round, vote_type.clone())
-            .is_ok()
-        {
-            return Ok(());
+        self.pending_commit = Some(PendingCommit {
+            height,
+            lock,
+            status: rx,
+        });
+        let authority_list = match self.next_epoch.as_ref() {
+            Some(next) if next.height == height + 1 => next.authority_list.clone(),
+            _ => self.authority.get_authority_list(),
+        };
+        Status {
+            height: height + 1,
+            interval: None,
+            timer_config: None,
+            authority_list,
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: self.authority.skipped_proposers().to_vec(),
         }
+    }
 
-        self.votes.insert_vote(
-            ctx.clone(),
-            signed_vote.get_hash(),
-            signed_vote.clone(),
-            voter,
+    /// Wait for the pending commit of pipelined mode and apply the returned status. If the
+    /// status is higher than the current height, goto the new height directly. If its authority
+    /// list, its next epoch, its proposer seed or its skipped proposers differ from the
+    /// provisional ones, the current height is restarted by it, since the messages of the
+    /// height may have been checked against the provisional authority list. Its interval and
+    /// its timer config are applied to the running height otherwise. Return whether the
+    /// current height is left or restarted.
+    ///
+    /// The wal saved while the commit is executing carries it, so that a node restarted before
+    /// the status returns commits it again. The pending commit is dropped once its status or
+    /// its error returns, since its receiver is consumed then.
+    async fn apply_pending_commit(&mut self) -> ConsensusResult<bool> {
+        let res = match self.pending_commit.as_mut() {
+            Some(pending) => (&mut pending.status).await,
+            None => return Ok(false),
+        };
+        let pending_height = self.pending_commit.as_ref().map_or(0, |p| p.height);
+        let status = match res {
+            Ok(Ok(status)) => status,
+            Ok(Err(e)) => {
+                self.pending_commit = None;
+                return Err(e);
+            }
+            Err(_) => {
+                self.pending_commit = None;
+                return Err(ConsensusError::ChannelErr(
+                    "Pending commit dropped".to_string(),
+                ));
+            }
+        };
+
+        match status.height.cmp(&self.height) {
+            Ordering::Greater => {
+                self.pending_commit = None;
+                self.goto_new_height(Context::new(), status).await?;
+                Ok(true)
+            }
+            Ordering::Equal if pending_height + 1 == self.height => {
+                self.check_timer_config(Context::new(), &status)?;
+                self.check_authority_update(Context::new(), &status)?;
+                self.pending_commit = None;
+                if self.differs_from_provisional(&self.epoch_status(status.clone())) {
+                    self.restart_height(Context::new(), status).await?;
+                    return Ok(true);
+                }
+
+                node_log!(
+                    info,
+                    self.log_scope(),
+                    "Mlm: state apply the status of pipelined commit height {}",
+                    pending_height
+                );
+                let status = self.epoch_status(status);
+                self.notify_authority_change(status.height, &status.authority_list);
+                self.schedule_epoch(&status);
+                self.consensus_power = status.is_consensus_node(&self.address);
+                let before = self.runtime_config();
+                self.update_proposer_seed(&status);
+                let mut auth_list = status.authority_list;
+                self.update_authority(&mut auth_list);
+
+                if let Some(interval) = status.interval {
+                    self.block_interval = interval;
+                    self.timer_config.set_interval(interval);
+                }
+                if let Some(config) = status.timer_config.clone() {
+                    self.timer_config.update(config.clone());
+                    self.duration_config = Some(config);
+                }
+                self.publish_config_change(before, ConfigSource::Status);
+
+                // The SMR keeps its own copy of the timer config, which is updated as by a
+                // status of a new height.
+                if let Some(config) = status.timer_config {
+                    self.state_machine.trigger(SMRTrigger {
+                        trigger_type: TriggerType::UpdateConfig(config),
+                        source: TriggerSource::State,
+                        hash: Hash::new(),
+                        lock_round: None,
+                        round: self.round,
+                        height: self.height,
+                        wal_info: None,
+                    })?;
+                }
+                Ok(false)
+            }
+            _ => {
+                self.pending_commit = None;
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state receive an outdated pipelined status, height {}, self height {}",
+                    status.height, self.height
+                );
+                Ok(false)
+            }
+        }
+    }
+
+    /// Whether the status returned by the pending commit differs from the provisional one that
+    /// the current height is running with, in the authority list, the next epoch, the proposer
+    /// seed or the skipped proposers.
+    fn differs_from_provisional(&self, status: &Status) -> bool {
+        let mut authority_list = status.authority_list.clone();
+        if let Some(scheme) = self.address_scheme.as_ref() {
+            authority_list.retain(|node| scheme.is_valid_address(&node.address));
+        }
+        authority_list.sort();
+        if authority_list != self.authority.get_authority_list() {
+            return true;
+        }
+
+        if let Some(mut next) = status.next_authority_list.clone() {
+            next.sort();
+            let scheduled = self
+                .next_epoch
+                .as_ref()
+                .filter(|epoch| epoch.height == status.height + 1)
+                .map(|epoch| &epoch.authority_list);
+            if scheduled != Some(&next) {
+                return true;
+            }
+        }
+
+        if status
+            .proposer_seed
+            .is_some_and(|seed| seed != self.authority.proposer_seed())
+        {
+            return true;
+        }
+
+        status.skipped_proposers.as_slice() != self.authority.skipped_proposers()
+    }
+
+    /// The main process of handle signed vote is that only handle those height and round are both
+    /// equal to the current. The lower votes will be ignored directly even if the height is equal
+    /// to the `current height - 1` and the round is higher than the current round. The reason is
+    /// that the effective leader must in the lower height, and the task of handling signed votes
+    /// will be done by the leader. For the higher votes, check the signature and save them in
+    /// the vote collector. Whenevet the current vote is received, a statistic is made to check
+    /// if the sum of the voting weights corresponding to the hash exceeds the threshold.
+    async fn handle_signed_vote(
+        &mut self,
+        ctx: Context,
+        signed_vote: SignedVote,
+    ) -> ConsensusResult<()> {
+        let height = signed_vote.get_height();
+        let round = signed_vote.get_round();
+        let vote_type = if signed_vote.is_prevote() {
+            VoteType::Prevote
+        } else {
+            VoteType::Precommit
+        };
+        let attrs = SpanAttrs::round(height, round)
+            .with_kind(&vote_type)
+            .with_hash(signed_vote.vote.block_hash.clone())
+            .with_from(signed_vote.voter.clone());
+        let ctx = self.span(ctx, "mlm.handle_signed_vote", attrs);
+
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state receive a signed {:?} vote height {}, round {}, from {:?}, hash {:?}",
+            vote_type,
+            height,
+            round,
+            hex_encode(signed_vote.voter.clone()),
+            hex_encode(signed_vote.vote.block_hash.clone())
         );
 
+        if self.filter_message(height, round) {
+            return Ok(());
+        }
+
+        // All the votes must pass the verification of signature and address before be saved into
+        // vote collector.
+        let voter = signed_vote.voter.clone();
+        self.verify_address(&voter).inspect_err(|_| {
+            let event = ConsensusEvent::UnknownSigner {
+                kind: "Signed Vote".to_string(),
+                height,
+                round,
+                signer: voter.clone(),
+            };
+            self.event_sink.on_event(ctx.clone(), event);
+        })?;
+        // The votes of the higher heights reach here before their signatures are verified, so
+        // they never prove a view of their voters.
+        if height <= self.height {
+            self.resync_by_quorum(&voter, height, round)?;
+        }
+
+        // Check if the quorum certificate has generated before check whether there is a hash that
+        // vote weight is above the threshold. If no hash achieved this, return directly.
+        if self
+            .votes
+            .get_qc_by_id(height, round, vote_type.clone())
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        let count = self.votes.round_vote_count(height, round);
+        if self.over_round_cap(count, height, round) {
+            return Ok(());
+        }
+        self.collect_vote(ctx.clone(), signed_vote.clone());
+        self.save_round_votes(height, round).await;
+
         if height > self.height {
             return Ok(());
         }
 
         let block_hash = self.counting_vote(vote_type.clone())?;
         if block_hash.is_none() {
-            debug!("Mlm: state counting of vote and no one above threshold");
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state counting of vote and no one above threshold"
+            );
             return Ok(());
         }
 
         // Build the quorum certificate needs to aggregate signatures into an aggregate
         // signature besides the address bitmap.
         let block_hash = block_hash.unwrap();
-        let qc = self.generate_qc(block_hash.clone(), vote_type.clone())?;
+        let qc = self
+            .generate_qc(block_hash.clone(), vote_type.clone())
+            .await?;
 
-        debug!(
-            "Mlm: state set QC height {}, round {}",
-            self.height, self.round
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state set QC height {}, round {}, id {}",
+            self.height,
+            self.round,
+            self.correlation_id()
         );
 
         self.votes.set_qc(qc.clone());
+        self.qc_gossip.aggregated(&qc);
+        self.publish_qc(&qc);
 
-        info!(
+        node_log!(
+            info,
+            self.log_scope(),
             "Mlm: state broadcast a {:?} QC, height {}, round {}, hash {:?}",
             vote_type,
             qc.height,
@@ -953,11 +2991,14 @@ where
             return Ok(());
         }
 
-        info!(
-            "Mlm: state trigger SMR {:?} QC height {}, round {}, hash {:?}",
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state trigger SMR {:?} QC height {}, round {}, id {}, hash {:?}",
             vote_type,
             self.height,
             self.round,
+            self.correlation_id(),
             hex_encode(block_hash.clone())
         );
 
@@ -987,18 +3028,6 @@ where
     /// is precommit, ignore it. Otherwise, retransmit precommit QC.
     ///
     /// 4. Other cases, return `Ok(())` directly.
-    #[tracing_span(
-        kind = "mlm",
-        tags = "{
-            'height': 'aggregated_vote.height', 
-            'round': 'aggregated_vote.round', 
-            'qc_type': 'aggregated_vote.vote_type'
-        }",
-        logs = "{
-            'qc_hash': 'hex_encode(aggregated_vote.block_hash.clone())',
-            'leader': 'hex_encode(aggregated_vote.leader.clone())'
-        }"
-    )]
     async fn handle_aggregated_vote(
         &mut self,
         ctx: Context,
@@ -1011,8 +3040,15 @@ where
         } else {
             VoteType::Precommit
         };
-
-        info!(
+        let attrs = SpanAttrs::round(vote_height, vote_round)
+            .with_kind(&qc_type)
+            .with_hash(aggregated_vote.block_hash.clone())
+            .with_from(aggregated_vote.leader.clone());
+        let ctx = self.span(ctx, "mlm.handle_aggregated_vote", attrs);
+
+        node_log!(
+            info,
+            self.log_scope(),
             "Mlm: state receive an {:?} QC height {}, round {}, from {:?}, hash {:?}",
             qc_type,
             vote_height,
@@ -1025,9 +3061,12 @@ where
         // height is higher than current height, save it and return Ok;
         match vote_height.cmp(&self.height) {
             Ordering::Less => {
-                debug!(
+                node_log!(
+                    debug,
+                    self.log_scope(),
                     "Mlm: state receive an outdated QC, height {}, round {}",
-                    vote_height, vote_round,
+                    vote_height,
+                    vote_round,
                 );
                 return Ok(());
             }
@@ -1036,13 +3075,20 @@ where
                 if self.height + FUTURE_HEIGHT_GAP > vote_height
                     && vote_round < FUTURE_ROUND_GAP
                 {
-                    debug!(
+                    node_log!(
+                        debug,
+                        self.log_scope(),
                         "Mlm: state receive a future QC, height {}, round {}",
-                        vote_height, vote_round,
+                        vote_height,
+                        vote_round,
                     );
                     self.votes.set_qc(aggregated_vote);
                 } else {
-                    warn!("Mlm: state receive a much higher aggregated vote");
+                    node_log!(
+                        warn,
+                        self.log_scope(),
+                        "Mlm: state receive a much higher aggregated vote"
+                    );
                 }
                 return Ok(());
             }
@@ -1052,7 +3098,11 @@ where
 
         // State do not handle outdated prevote QC.
         if qc_type == VoteType::Prevote && vote_round < self.round {
-            debug!("Mlm: state receive a outdated prevote qc.");
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state receive a outdated prevote qc."
+            );
             return Ok(());
         } else if qc_type == VoteType::Precommit
             && aggregated_vote.block_hash.is_empty()
@@ -1061,19 +3111,52 @@ where
             return Ok(());
         }
 
+        // The backup relayers and the gossip bring the copies of a QC, so only the first QC of
+        // a block hash is handled. The QCs of the higher rounds still go on to bring the node
+        // to their rounds.
+        let first = !self.config.gossip_qc || self.qc_gossip.learned(&aggregated_vote);
+        if (self.config.backup_relayers > 0 || !first) && vote_round <= self.round {
+            if let Ok(qc) =
+                self.votes
+                    .get_qc_by_id(vote_height, vote_round, qc_type.clone())
+            {
+                if qc.block_hash == aggregated_vote.block_hash {
+                    node_log!(
+                        debug,
+                        self.log_scope(),
+                        "Mlm: state drop a duplicated {:?} QC height {}, round {}",
+                        qc_type,
+                        vote_height,
+                        vote_round
+                    );
+                    return Ok(());
+                }
+            }
+        }
+
+        if self.config.gossip_qc && first {
+            self.qc_gossip.gossiped();
+            self.broadcast(ctx, MlmMsg::AggregatedVote(aggregated_vote.clone()))
+                .await;
+        }
+
         // Check if the block hash has been verified.
         let qc_hash = aggregated_vote.block_hash.clone();
+        self.publish_qc(&aggregated_vote);
         self.votes.set_qc(aggregated_vote);
 
         if !qc_hash.is_empty() && !self.try_get_full_txs(&qc_hash) {
             return Ok(());
         }
 
-        info!(
-            "Mlm: state trigger SMR {:?} QC height {}, round {}, hash {:?}",
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state trigger SMR {:?} QC height {}, round {}, id {}, hash {:?}",
             qc_type,
             self.height,
             self.round,
+            self.correlation_id(),
             hex_encode(qc_hash.clone())
         );
 
@@ -1106,10 +3189,13 @@ where
                     return Ok(());
                 }
 
-                info!(
-                    "Mlm: state trigger SMR height {}, round {}, type {:?}, hash {:?}",
+                node_log!(
+                    info,
+                    self.log_scope(),
+                    "Mlm: state trigger SMR height {}, round {}, id {}, type {:?}, hash {:?}",
                     self.height,
                     self.round,
+                    self.correlation_id(),
                     qc.vote_type,
                     hex_encode(block_hash.clone())
                 );
@@ -1126,10 +3212,16 @@ where
                 return Ok(());
             }
         } else if let Some(block_hash) = self.counting_vote(vote_type.clone())? {
-            let qc = self.generate_qc(block_hash.clone(), vote_type.clone())?;
+            let qc = self
+                .generate_qc(block_hash.clone(), vote_type.clone())
+                .await?;
             self.votes.set_qc(qc.clone());
+            self.qc_gossip.aggregated(&qc);
+            self.publish_qc(&qc);
 
-            info!(
+            node_log!(
+                info,
+                self.log_scope(),
                 "Mlm: state broadcast a {:?} QC, height {}, round {}, hash {:?}",
                 vote_type,
                 qc.height,
@@ -1137,18 +3229,21 @@ where
                 hex_encode(block_hash.clone())
             );
 
-            self.broadcast(Context::new(), MlmMsg::AggregatedVote(qc))
+            self.broadcast(self.span_ctx.clone(), MlmMsg::AggregatedVote(qc))
                 .await;
 
             if !self.try_get_full_txs(&block_hash) {
                 return Ok(());
             }
 
-            info!(
-                "Mlm: state trigger SMR {:?} QC height {}, round {}, hash {:?}",
+            node_log!(
+                info,
+                self.log_scope(),
+                "Mlm: state trigger SMR {:?} QC height {}, round {}, id {}, hash {:?}",
                 vote_type,
                 self.height,
                 self.round,
+                self.correlation_id(),
                 hex_encode(block_hash.clone())
             );
 
@@ -1169,36 +3264,31 @@ where
         let len = self
             .votes
             .vote_count(self.height, self.round, vote_type.clone());
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state round {}, {:?} vote pool length {}",
+            self.round,
+            vote_type,
+            len
+        );
+
         let vote_map =
             self.votes
                 .get_vote_map(self.height, self.round, vote_type.clone())?;
-        let threshold = self.authority.get_vote_weight_sum() * 2;
-
-        info!(
-            "Mlm: state round {}, {:?} vote pool length {}",
-            self.round, vote_type, len
-        );
 
         for (hash, set) in vote_map.iter() {
-            let mut acc = 0u32;
+            let mut acc = 0u128;
             for addr in set.iter() {
-                acc += self.authority.get_vote_weight(addr)?;
+                acc = acc.saturating_add(*self.authority.get_vote_weight(addr)?);
             }
-            if u64::from(acc) * 3 > threshold {
+            if self.authority.is_quorum(acc, set.len()) {
                 return Ok(Some(hash.to_owned()));
             }
         }
         Ok(None)
     }
 
-    #[tracing_span(
-        kind = "mlm",
-        tags = "{
-            'height': 'signed_choke.choke.height',
-            'round': 'signed_choke.choke.round'
-        }",
-        logs = "{'choke_from': 'hex_encode(signed_choke.address.clone())'}"
-    )]
     async fn handle_signed_choke(
         &mut self,
         ctx: Context,
@@ -1207,6 +3297,9 @@ where
         let choke = signed_choke.choke.clone();
         let choke_height = choke.height;
         let choke_round = choke.round;
+        let attrs = SpanAttrs::round(choke_height, choke_round)
+            .with_from(signed_choke.address.clone());
+        let ctx = self.span(ctx, "mlm.handle_signed_choke", attrs);
 
         // filter choke height ne self.height
         if choke_height != self.height {
@@ -1217,12 +3310,15 @@ where
             return Ok(());
         }
 
-        info!(
+        node_log!(
+            info,
+            self.log_scope(),
             "Mlm: state receive a choke of height {}, round {}, from {:?}",
             choke_height,
             choke_round,
             hex_encode(signed_choke.address.clone())
         );
+        self.resync_by_quorum(&signed_choke.address, choke_height, choke_round)?;
 
         if choke_round > self.round {
             match choke.from {
@@ -1236,17 +3332,107 @@ where
             }
         }
 
+        let count = self.chokes.choke_count(choke_round);
+        if self.over_round_cap(count, choke_height, choke_round) {
+            return Ok(());
+        }
         self.chokes.insert(choke_round, signed_choke);
         self.check_choke_above_threshold()?;
         Ok(())
     }
 
+    /// Record the view of an authority by its signed message, and move to the round of
+    /// the current height that a quorum is at or above if the node is behind it. The SMR
+    /// continues to the round as by a choke QC, which the node may not have.
+    fn resync_by_quorum(
+        &mut self,
+        address: &Address,
+        height: u64,
+        round: u64,
+    ) -> ConsensusResult<()> {
+        if !self.config.fast_resync
+            || !self.authority.contains(address)
+            || !self.quorum_views.record(address, height, round)
+        {
+            return Ok(());
+        }
+        let round = match self.quorum_views.quorum_view(&self.authority) {
+            Some((height, round)) if height == self.height && round > self.round => {
+                round
+            }
+            _ => return Ok(()),
+        };
+        if self.quorum_round >= Some((self.height, round))
+            || matches!(self.step_starts.last(), Some((Step::Commit, _)))
+        {
+            return Ok(());
+        }
+
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state move to round {} of a quorum ahead, height {}, round {}, id {}",
+            round,
+            self.height,
+            self.round,
+            self.correlation_id()
+        );
+        self.quorum_round = Some((self.height, round));
+        self.state_machine.trigger(SMRTrigger {
+            trigger_type: TriggerType::ContinueRound,
+            source: TriggerSource::State,
+            hash: Hash::new(),
+            lock_round: None,
+            round,
+            height: self.height,
+            wal_info: None,
+        })
+    }
+
+    /// Whether the node moves to the round after the given one by a quorum ahead, without
+    /// the choke QC of the given round.
+    fn moved_by_quorum(&self, round: u64) -> bool {
+        self.quorum_round == Some((self.height, round + 1))
+            && self.chokes.get_qc(round).is_none()
+    }
+
+    /// Jump to the round of a proposal at once by the timeout certificate of its previous
+    /// round, which is verified with the proposal. The proposal is cached until the round.
+    fn jump_by_timeout_cert(
+        &mut self,
+        height: u64,
+        round: u64,
+        tc: &AggregatedChoke,
+    ) -> ConsensusResult<()> {
+        if height != self.height
+            || round <= self.round
+            || self.filter_message(height, round)
+            || tc.height != height
+            || tc.round + 1 != round
+        {
+            return Ok(());
+        }
+
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state jump to round {} by a timeout certificate, height {}, id {}",
+            round,
+            height,
+            self.correlation_id()
+        );
+        self.handle_aggregated_choke(tc.clone())
+    }
+
     fn handle_aggregated_choke(
         &mut self,
         aggregated_choke: AggregatedChoke,
     ) -> ConsensusResult<()> {
         // verify is above threshold.
-        if aggregated_choke.len() * 3 <= self.authority.len() * 2 {
+        if !self
+            .authority
+            .is_above_threshold_by_voters(&aggregated_choke.voters)
+        {
             return Err(ConsensusError::BrakeErr(
                 "choke qc is not above threshold".to_string(),
             ));
@@ -1267,11 +3453,12 @@ where
         Ok(())
     }
 
-    fn generate_qc(
+    async fn generate_qc(
         &mut self,
         block_hash: Hash,
         vote_type: VoteType,
     ) -> ConsensusResult<AggregatedVote> {
+        let start = Instant::now();
         let mut votes = self
             .votes
             .get_votes(self.height, self.round, vote_type.clone(), &block_hash)?
@@ -1280,8 +3467,19 @@ where
             .collect::<Vec<_>>();
         votes.sort();
 
-        debug!("Mlm: state build aggregated signature");
+        if self.config.external_aggregation {
+            return self
+                .generate_qc_externally(block_hash, vote_type, votes)
+                .await;
+        }
+
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state build aggregated signature"
+        );
 
+        let extensions = votes.iter().filter_map(SignedVote::extension).collect();
         let len = votes.len();
         let mut signatures = Vec::with_capacity(len);
         let mut voters = Vec::with_capacity(len);
@@ -1290,26 +3488,84 @@ where
             voters.push(vote.voter);
         }
 
-        let set = voters.iter().cloned().collect::<HashSet<_>>();
-        let mut bit_map = BitVec::from_elem(self.authority.len(), false);
-        for (index, addr) in self.authority.get_addres_ref().iter().enumerate() {
-            if set.contains(addr) {
-                bit_map.set(index, true);
-            }
+        let address_bitmap = self.authority.get_bitmap(&voters);
+        let folded =
+            self.aggregates
+                .folded(self.round, vote_type.clone(), &block_hash, &voters);
+        let is_folded = folded.is_some();
+        let signature = match folded {
+            Some(signature) => signature,
+            None => self.aggregate_signatures(signatures, voters)?,
+        };
+        self.aggregates.built(start.elapsed(), is_folded);
+        let qc = AggregatedVote {
+            signature: C::QcSignature::new(signature, address_bitmap).to_wire(),
+            vote_type,
+            height: self.height,
+            round: self.round,
+            block_hash,
+            leader: self.address.clone(),
+            extensions,
+        };
+        Ok(qc)
+    }
+
+    /// Build the QC by the aggregation service of the adapter. The returned aggregated signature
+    /// must be above the threshold and pass the verification as a QC from the network does.
+    async fn generate_qc_externally(
+        &self,
+        block_hash: Hash,
+        vote_type: VoteType,
+        votes: Vec<SignedVote>,
+    ) -> ConsensusResult<AggregatedVote> {
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state aggregate {} votes externally",
+            votes.len()
+        );
+
+        let extensions = votes.iter().filter_map(SignedVote::extension).collect();
+        let signature = self
+            .function
+            .aggregate_votes(Context::new(), votes)
+            .await
+            .map_err(|err| {
+                ConsensusError::AggregatedSignatureErr(format!(
+                    "external aggregation error {:?}",
+                    err
+                ))
+            })?;
+        if !self
+            .authority
+            .is_qc_above_threshold::<C::QcSignature>(&signature)?
+        {
+            return Err(ConsensusError::AggregatedSignatureErr(
+                "external aggregation is not above threshold".to_string(),
+            ));
         }
 
-        let aggregated_signature = AggregatedSignature {
-            signature: self.aggregate_signatures(signatures, voters)?,
-            address_bitmap: Bytes::from(bit_map.to_bytes()),
-        };
         let qc = AggregatedVote {
-            signature: aggregated_signature,
+            signature,
             vote_type,
             height: self.height,
             round: self.round,
             block_hash,
             leader: self.address.clone(),
+            extensions,
         };
+        let hash = self.digests.vote(
+            self.util.as_ref(),
+            self.wire_codec.as_ref(),
+            &qc.to_vote(),
+        )?;
+        verify_qc_signature(self.util.as_ref(), &qc.signature, hash, &self.authority)
+            .map_err(|err| {
+                ConsensusError::AggregatedSignatureErr(format!(
+                    "verify external aggregation error {:?}",
+                    err
+                ))
+            })?;
         Ok(qc)
     }
 
@@ -1317,17 +3573,25 @@ where
         &mut self,
         proposals_and_ctxs: Vec<(SignedProposal<T>, Context)>,
     ) -> ConsensusResult<()> {
-        debug!("Mlm: state re-check future signed proposals");
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state re-check future signed proposals"
+        );
 
         for item in proposals_and_ctxs.into_iter() {
-            parallel_verify(
+            self.spawn_verify(parallel_verify(
                 item.1,
                 MlmMsg::SignedProposal(item.0),
+                self.trace.as_ref(),
+                Arc::clone(&self.event_sink),
+                Arc::clone(&self.function),
                 Arc::clone(&self.util),
-                self.authority.clone(),
+                Arc::clone(&self.wire_codec),
+                Arc::clone(&self.authority),
+                Arc::clone(&self.digests),
                 self.verify_sig_tx.clone(),
-            )
-            .await;
+            ));
         }
 
         Ok(())
@@ -1337,34 +3601,46 @@ where
         &mut self,
         votes_and_ctxs: Vec<(SignedVote, Context)>,
     ) -> ConsensusResult<()> {
-        debug!("Mlm: state re-check future signed votes");
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state re-check future signed votes"
+        );
 
         for item in votes_and_ctxs.into_iter() {
-            parallel_verify(
+            self.spawn_verify(parallel_verify(
                 item.1,
                 MlmMsg::SignedVote(item.0),
+                self.trace.as_ref(),
+                Arc::clone(&self.event_sink),
+                Arc::clone(&self.function),
                 Arc::clone(&self.util),
-                self.authority.clone(),
+                Arc::clone(&self.wire_codec),
+                Arc::clone(&self.authority),
+                Arc::clone(&self.digests),
                 self.verify_sig_tx.clone(),
-            )
-            .await;
+            ));
         }
 
         Ok(())
     }
 
     async fn re_check_qcs(&mut self, qcs: Vec<AggregatedVote>) -> ConsensusResult<()> {
-        debug!("Mlm: state re-check future QCs");
+        node_log!(debug, self.log_scope(), "Mlm: state re-check future QCs");
 
         for item in qcs.into_iter() {
-            parallel_verify(
+            self.spawn_verify(parallel_verify(
                 Context::new(),
                 MlmMsg::AggregatedVote(item),
+                self.trace.as_ref(),
+                Arc::clone(&self.event_sink),
+                Arc::clone(&self.function),
                 Arc::clone(&self.util),
-                self.authority.clone(),
+                Arc::clone(&self.wire_codec),
+                Arc::clone(&self.authority),
+                Arc::clone(&self.digests),
                 self.verify_sig_tx.clone(),
-            )
-            .await;
+            ));
         }
 
         Ok(())
@@ -1376,20 +3652,27 @@ where
         let proposer = self.authority.get_proposer(self.height, self.round)?;
 
         if proposer == self.address {
-            info!(
-                "Mlm: state self become leader, height {}, round {}",
-                self.height, self.round
+            node_log!(
+                info,
+                self.log_scope(),
+                "Mlm: state self become leader, height {}, round {}, id {}",
+                self.height,
+                self.round,
+                self.correlation_id()
             );
             self.is_leader = true;
             self.leader_address = self.address.clone();
             return Ok(true);
         }
 
-        info!(
-            "Mlm: {:?} become leader, height {}, round {}",
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: {:?} become leader, height {}, round {}, id {}",
             hex_encode(proposer.clone()),
             self.height,
-            self.round
+            self.round,
+            self.correlation_id()
         );
         self.leader_address = proposer;
         self.is_leader = false;
@@ -1401,117 +3684,2041 @@ where
         Ok(self.address == proposer)
     }
 
-    fn sign_proposal(
-        &self,
-        proposal: Proposal<T>,
-    ) -> ConsensusResult<SignedProposal<T>> {
-        debug!("Mlm: state sign a proposal");
-        let signature = self
-            .util
-            .sign(self.util.hash(Bytes::from(rlp::encode(&proposal))))
-            .map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))?;
-
-        Ok(SignedProposal {
-            signature,
-            proposal,
-        })
+    /// If self proposes at the first round of the height of the status, which waits for the
+    /// block interval before the height. Every authority proposes in the VRF leader mode.
+    fn proposes_first(&self, status: &Status) -> ConsensusResult<bool> {
+        if self.config.vrf_leader {
+            return Ok(status.is_consensus_node(&self.address));
+        }
+        self.next_proposer(status.height, INIT_ROUND)
     }
 
-    fn sign_vote(&self, vote: Vote) -> ConsensusResult<SignedVote> {
-        debug!("Mlm: state sign a vote");
-        let signature = self
-            .util
-            .sign(self.util.hash(Bytes::from(rlp::encode(&vote))))
-            .map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))?;
+    /// Save the record of a consensus message of self and sign it in a task, so the state
+    /// goes on handling the messages and the timers while a remote signer works. The task
+    /// sends the signature back by `DelayedEvent::Signed`, and the message goes on by
+    /// `handle_signed()`.
+    async fn sign(
+        &mut self,
+        step: Step,
+        hash: Hash,
+        message: Unsigned<T>,
+    ) -> ConsensusResult<()> {
+        if !self.signer_ready {
+            return Err(ConsensusError::SignerUnavailable(
+                "waiting for the signer".to_string(),
+            ));
+        }
 
-        Ok(SignedVote {
-            voter: self.address.clone(),
-            signature,
-            vote,
-        })
+        self.save_last_signed(step, &hash).await?;
+        self.spawn_sign(hash, message);
+        Ok(())
     }
 
-    fn aggregate_signatures(
-        &self,
-        signatures: Vec<Signature>,
-        voters: Vec<Address>,
-    ) -> ConsensusResult<Signature> {
-        let pretty_voter = voters
-            .iter()
-            .map(|addr| hex_encode(addr.clone()))
-            .collect::<Vec<_>>();
-
-        info!(
-            "Mlm: state aggregate signatures height {}, round {}, voters {:?}",
-            self.height, self.round, pretty_voter
+    /// Sign the hash of a message of self in a task. The extension of a precommit is made up
+    /// and signed in the same task.
+    fn spawn_sign(&mut self, hash: Hash, message: Unsigned<T>) {
+        let id = self.next_sign_id;
+        self.next_sign_id += 1;
+        let extend = match &message {
+            Unsigned::Vote { vote, .. } => self.extend_vote(vote),
+            _ => None,
+        };
+        self.unsigned.insert(
+            id,
+            PendingSign {
+                height: self.height,
+                round: self.round,
+                step: self.current_step().clone(),
+                span_ctx: self.span_ctx.clone(),
+                message,
+            },
         );
 
-        let signature = self
-            .util
-            .aggregate_signatures(signatures, voters)
-            .map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))?;
-        Ok(signature)
+        let signer = self.signer();
+        let tx = self.delay_tx.clone();
+        self.runtime.spawn(Box::pin(async move {
+            let signature = signer.sign(hash).await;
+            let extension = match extend {
+                Some(extend) if signature.is_ok() => extend.await,
+                _ => (Bytes::new(), Signature::new()),
+            };
+            let _ = tx.unbounded_send(DelayedEvent::Signed {
+                id,
+                signature,
+                extension,
+            });
+        }));
     }
 
-    fn verify_proposer(
+    fn signer(&self) -> Signer<C> {
+        let timeout = if self.config.sign_timeout == 0 {
+            self.timer_config.get_prevote_timeout()
+        } else {
+            Duration::from_millis(self.config.sign_timeout)
+        };
+        Signer {
+            util: Arc::clone(&self.util),
+            async_crypto: self.async_crypto.clone(),
+            timeout,
+        }
+    }
+
+    /// Go on with a message of self once its signing task reports back. A consensus message
+    /// is dropped if the node has left the view it is signed in, and a failed signing of it
+    /// turns the node into an observer until the signer is ready.
+    async fn handle_signed(
+        &mut self,
+        id: u64,
+        signature: ConsensusResult<Signature>,
+        extension: (Bytes, Signature),
+    ) -> ConsensusResult<()> {
+        let pending = match self.unsigned.remove(&id) {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let span_ctx = std::mem::replace(&mut self.span_ctx, pending.span_ctx.clone());
+        let res = self.send_signed(pending, signature, extension).await;
+        self.span_ctx = span_ctx;
+        res
+    }
+
+    async fn send_signed(
+        &mut self,
+        pending: PendingSign<T>,
+        signature: ConsensusResult<Signature>,
+        extension: (Bytes, Signature),
+    ) -> ConsensusResult<()> {
+        let name = pending.message.name();
+        let in_view = self.consensus_power
+            && pending.height == self.height
+            && pending.round == self.round
+            && &pending.step == self.current_step();
+
+        let (message, signature) = match (pending.message, signature) {
+            (Unsigned::Probe, res) => {
+                self.on_probe(res);
+                return Ok(());
+            }
+            (Unsigned::Heartbeat(heartbeat), Ok(signature)) => {
+                let msg = MlmMsg::SignedHeartbeat(SignedHeartbeat {
+                    signature,
+                    heartbeat,
+                });
+                self.broadcast(Context::new(), msg).await;
+                return Ok(());
+            }
+            (Unsigned::Attestation(attestation), Ok(signature)) => {
+                self.function.report_attestation(
+                    Context::new(),
+                    SignedAttestation {
+                        signature,
+                        attestation,
+                    },
+                );
+                return Ok(());
+            }
+            (Unsigned::KeyRotation(rotation), Ok(signature)) => {
+                let msg = MlmMsg::SignedKeyRotation(SignedKeyRotation {
+                    signature,
+                    rotation,
+                });
+                self.broadcast(Context::new(), msg).await;
+                return Ok(());
+            }
+            (
+                Unsigned::Heartbeat(_)
+                | Unsigned::Attestation(_)
+                | Unsigned::KeyRotation(_),
+                Err(e),
+            ) => {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state sign {} error {:?}",
+                    name,
+                    e
+                );
+                return Ok(());
+            }
+            (_, _) if !in_view => {
+                node_log!(
+                    info,
+                    self.log_scope(),
+                    "Mlm: state drop the signed {} of height {}, round {}, {} out of the view",
+                    name,
+                    pending.height,
+                    pending.round,
+                    pending.step
+                );
+                return Ok(());
+            }
+            (message, Err(e)) => {
+                self.wait_for_signer(e.to_string());
+                let e = ConsensusError::SignerUnavailable(e.to_string());
+                if let Unsigned::Proposal { .. } = message {
+                    return self.skip_propose(e);
+                }
+                return Err(e);
+            }
+            (message, Ok(signature)) => (message, signature),
+        };
+
+        match message {
+            Unsigned::Proposal {
+                ctx,
+                proposal,
+                lock_round,
+            } => {
+                let signed_proposal = self.signed_proposal(proposal, signature);
+                self.send_proposal(ctx, signed_proposal, lock_round).await
+            }
+            Unsigned::BackupProposal {
+                ctx,
+                proposal,
+                rank,
+            } => {
+                let signed_proposal = self.signed_proposal(proposal, signature);
+                self.send_backup_proposal(ctx, signed_proposal, rank).await
+            }
+            Unsigned::Vote { vote, lock_round } => {
+                self.append_step(StepRecord::Voted {
+                    height: vote.height,
+                    round: vote.round,
+                    vote_type: vote.vote_type.clone(),
+                    block_hash: vote.block_hash.clone(),
+                })
+                .await;
+                let (extension, extension_signature) = extension;
+                let signed_vote = SignedVote {
+                    voter: self.address.clone(),
+                    signature,
+                    vote,
+                    extension,
+                    extension_signature,
+                };
+                self.send_vote(signed_vote, lock_round).await
+            }
+            Unsigned::Choke { choke, lock_round } => {
+                let signed_choke = SignedChoke {
+                    signature,
+                    choke,
+                    address: self.address.clone(),
+                };
+                self.send_choke(signed_choke, lock_round).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Probe the signer by a message that is never a consensus message. The node waits for
+    /// the signer if it fails, and participates again if it succeeds.
+    fn probe_signer(&mut self) {
+        let hash = self.util.hash(Bytes::from_static(SIGNER_PROBE));
+        self.spawn_sign(hash, Unsigned::Probe);
+    }
+
+    fn on_probe(&mut self, res: ConsensusResult<Signature>) {
+        match res {
+            Ok(_) if !self.signer_ready => {
+                node_log!(
+                    info,
+                    self.log_scope(),
+                    "Mlm: state signer is ready, participate from the next step, id {}",
+                    self.correlation_id()
+                );
+                self.signer_ready = true;
+                self.events
+                    .publish(self.height, self.round, MlmEventKind::SignerReady);
+            }
+            Ok(_) => (),
+            Err(e) if self.signer_ready => self.wait_for_signer(e.to_string()),
+            Err(_) => {
+                self.schedule(self.signer_retry_interval(), DelayedEvent::ProbeSigner)
+            }
+        }
+    }
+
+    /// Turn the node into an observer until the signer is ready, which keeps verifying and
+    /// tracking the consensus without proposing and voting.
+    fn wait_for_signer(&mut self, reason: String) {
+        if !self.signer_ready {
+            return;
+        }
+
+        node_log!(
+            warn,
+            self.log_scope(),
+            "Mlm: state signer is unavailable {}, observe until it is ready, id {}",
+            reason,
+            self.correlation_id()
+        );
+        self.signer_ready = false;
+        self.events
+            .publish(self.height, self.round, MlmEventKind::SignerUnavailable);
+        self.report_error(Context::new(), ConsensusError::SignerUnavailable(reason));
+        self.schedule(self.signer_retry_interval(), DelayedEvent::ProbeSigner);
+    }
+
+    fn signer_retry_interval(&self) -> Duration {
+        if self.config.signer_retry_interval == 0 {
+            self.block_interval
+        } else {
+            Duration::from_millis(self.config.signer_retry_interval)
+        }
+    }
+
+    /// Return the hash to prevote for, which is nil if the data of the block is not available
+    /// in time.
+    async fn check_availability(&self, hash: Hash) -> Hash {
+        let wait = if self.config.availability_timeout == 0 {
+            self.timer_config.get_prevote_timeout()
+        } else {
+            Duration::from_millis(self.config.availability_timeout)
+        };
+        let check =
+            self.function
+                .check_availability(Context::new(), self.height, hash.clone());
+        match timeout(wait, check).await {
+            Ok(true) => hash,
+            Ok(false) => {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state prevote nil for the unavailable block height {}, round {}, hash {:?}",
+                    self.height,
+                    self.round,
+                    hex_encode(hash)
+                );
+                Hash::new()
+            }
+            Err(_) => {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state prevote nil after the availability check timeout {:?}, height {}, round {}",
+                    wait, self.height, self.round
+                );
+                Hash::new()
+            }
+        }
+    }
+
+    async fn sign_proposal(
+        &mut self,
+        ctx: Context,
+        proposal: Proposal<T>,
+        lock_round: Option<u64>,
+    ) -> ConsensusResult<()> {
+        node_log!(debug, self.log_scope(), "Mlm: state sign a proposal");
+        let hash = self
+            .util
+            .hash(proposal_preimage(self.wire_codec.as_ref(), &proposal)?);
+        let message = Unsigned::Proposal {
+            ctx,
+            proposal,
+            lock_round,
+        };
+        self.sign(Step::Propose, hash, message).await
+    }
+
+    fn signed_proposal(
+        &self,
+        proposal: Proposal<T>,
+        signature: Signature,
+    ) -> SignedProposal<T> {
+        let timeout_cert = if self.config.timeout_cert && proposal.round > INIT_ROUND {
+            self.chokes.get_qc(proposal.round - 1)
+        } else {
+            None
+        };
+        SignedProposal {
+            signature,
+            proposal,
+            timeout_cert,
+            vrf_proof: Bytes::new(),
+        }
+    }
+
+    async fn sign_vote(
+        &mut self,
+        vote: Vote,
+        lock_round: Option<u64>,
+    ) -> ConsensusResult<()> {
+        node_log!(debug, self.log_scope(), "Mlm: state sign a vote");
+        let hash =
+            self.digests
+                .vote(self.util.as_ref(), self.wire_codec.as_ref(), &vote)?;
+        self.sign(
+            vote.vote_type.clone().into(),
+            hash,
+            Unsigned::Vote { vote, lock_round },
+        )
+        .await
+    }
+
+    /// Extend a precommit of a block by the adapter and sign the extension in the signing task
+    /// of the precommit if the vote extensions are enabled. The precommit goes without an
+    /// extension if the adapter fails or does not return in the precommit timeout, or the
+    /// extension fails to be signed, so the extensions never hold the consensus back.
+    fn extend_vote(
+        &self,
+        vote: &Vote,
+    ) -> Option<BoxFuture<'static, (Bytes, Signature)>> {
+        if !self.config.vote_extensions
+            || vote.vote_type != VoteType::Precommit
+            || vote.block_hash.is_empty()
+        {
+            return None;
+        }
+
+        let ctx = self.correlation_id().attach_to(Context::new());
+        let wait = self.timer_config.get_precommit_timeout();
+        let function = Arc::clone(&self.function);
+        let wire_codec = Arc::clone(&self.wire_codec);
+        let signer = self.signer();
+        let scope = self.log_scope();
+        let vote = vote.clone();
+        Some(Box::pin(async move {
+            let extend = function.extend_vote(
+                ctx,
+                vote.height,
+                vote.round,
+                vote.block_hash.clone(),
+            );
+            let extension = match timeout(wait, extend).await {
+                Ok(Ok(extension)) => extension,
+                Ok(Err(e)) => {
+                    node_log!(
+                        warn,
+                        scope,
+                        "Mlm: state extend vote error {:?}, height {}, round {}",
+                        e,
+                        vote.height,
+                        vote.round
+                    );
+                    Bytes::new()
+                }
+                Err(_) => {
+                    node_log!(
+                        warn,
+                        scope,
+                        "Mlm: state extend vote timeout {:?}, height {}, round {}",
+                        wait,
+                        vote.height,
+                        vote.round
+                    );
+                    Bytes::new()
+                }
+            };
+            if extension.is_empty() {
+                return (Bytes::new(), Signature::new());
+            }
+
+            let preimage = match wire_codec.vote_extension_preimage(&vote, &extension) {
+                Ok(preimage) => preimage,
+                Err(e) => {
+                    node_log!(
+                        warn,
+                        scope,
+                        "Mlm: state encode vote extension error {:?}",
+                        e
+                    );
+                    return (Bytes::new(), Signature::new());
+                }
+            };
+            let hash = signer.util.hash(preimage);
+            match signer.sign(hash).await {
+                Ok(signature) => (extension, signature),
+                Err(e) => {
+                    node_log!(
+                        warn,
+                        scope,
+                        "Mlm: state sign vote extension error {:?}",
+                        e
+                    );
+                    (Bytes::new(), Signature::new())
+                }
+            }
+        }))
+    }
+
+    fn aggregate_signatures(
+        &self,
+        signatures: Vec<Signature>,
+        voters: Vec<Address>,
+    ) -> ConsensusResult<Signature> {
+        let pretty_voter = voters
+            .iter()
+            .map(|addr| hex_encode(addr.clone()))
+            .collect::<Vec<_>>();
+
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state aggregate signatures height {}, round {}, id {}, voters {:?}",
+            self.height,
+            self.round,
+            self.correlation_id(),
+            pretty_voter
+        );
+
+        let signature = self
+            .util
+            .aggregate_signatures(signatures, voters)
+            .map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))?;
+        Ok(signature)
+    }
+
+    /// Verify the proposer of the given height and round, return the rank of the proposer.
+    fn verify_proposer(
         &self,
         height: u64,
         round: u64,
         address: &Address,
-    ) -> ConsensusResult<()> {
-        debug!("Mlm: state verify a proposer");
+    ) -> ConsensusResult<usize> {
+        node_log!(debug, self.log_scope(), "Mlm: state verify a proposer");
         self.verify_address(address)?;
-        if address != &self.authority.get_proposer(height, round)? {
-            return Err(ConsensusError::ProposalErr("Invalid proposer".to_string()));
+        if address == &self.authority.get_proposer(height, round)? {
+            return Ok(0);
+        }
+
+        #[cfg(feature = "multi_proposal")]
+        if self.multi_proposal_enabled() {
+            let proposers = self.authority.get_proposers(
+                height,
+                round,
+                self.config.proposer_count,
+            )?;
+            if let Some(rank) = proposers.iter().position(|addr| addr == address) {
+                return Ok(rank);
+            }
+        }
+
+        Err(ConsensusError::ProposalErr("Invalid proposer".to_string()))
+    }
+
+    /// Verify the proposer of the signed proposal, return the rank of the proposal. In the
+    /// VRF leader mode any authority proposes, and the proposal is ranked by the VRF output
+    /// of its proof, except that the lock re-proposed by the rotation leader is preferred to
+    /// all of them.
+    fn proposal_rank(
+        &self,
+        signed_proposal: &SignedProposal<T>,
+    ) -> ConsensusResult<Rank> {
+        let proposal = &signed_proposal.proposal;
+        let (height, round) = (proposal.height, proposal.round);
+        if !self.config.vrf_leader {
+            return self
+                .verify_proposer(height, round, &proposal.proposer)
+                .map(Rank::Designated);
+        }
+
+        self.verify_address(&proposal.proposer)?;
+        if proposal.lock.is_some()
+            && proposal.proposer == self.authority.get_proposer(height, round)?
+        {
+            return Ok(Rank::Designated(0));
+        }
+        let input = self
+            .wire_codec
+            .vrf_input(height, round, &self.config.vrf_seed)?;
+        self.util
+            .vrf_verify(
+                input,
+                signed_proposal.vrf_proof.clone(),
+                proposal.proposer.clone(),
+            )
+            .map(Rank::Vrf)
+            .map_err(|err| ConsensusError::ProposalErr(format!("Invalid VRF {:?}", err)))
+    }
+
+    fn multi_proposal_enabled(&self) -> bool {
+        #[cfg(feature = "multi_proposal")]
+        if self.config.proposer_count > 1 {
+            return true;
+        }
+        self.config.vrf_leader
+    }
+
+    /// Handle a proposal of the current round in multiple proposals mode. The primary proposal
+    /// is triggered directly, while a lower ranked proposal is held until the waits of all the
+    /// higher ranks expire.
+    async fn handle_candidate(
+        &mut self,
+        ctx: Context,
+        rank: Rank,
+        signed_proposal: SignedProposal<T>,
+    ) -> ConsensusResult<()> {
+        if self.candidates.is_decided() {
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state ignore a candidate proposal of rank {:?}",
+                rank
+            );
+            return Ok(());
+        }
+
+        if rank == Rank::Designated(0) {
+            self.candidates.decide();
+            return self.trigger_proposal(ctx, signed_proposal).await;
+        }
+
+        if !self.candidates.insert(rank, ctx, signed_proposal) {
+            return Ok(());
+        }
+
+        // The lowest VRF output is only known when the wait of the round expires.
+        let wait = match self.candidates.best_rank() {
+            Some(Rank::Designated(best)) => self.proposal_wait() * *best as u32,
+            Some(Rank::Vrf(_)) => self.proposal_wait(),
+            None => return Ok(()),
+        };
+        let deadline = self.round_start + wait;
+        let now = Instant::now();
+
+        if deadline <= now {
+            if let Some((signed_proposal, ctx)) = self.candidates.take_best() {
+                return self.trigger_proposal(ctx, signed_proposal).await;
+            }
+        } else {
+            self.schedule(
+                deadline - now,
+                DelayedEvent::ProposalWait {
+                    height: self.height,
+                    round: self.round,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// The rank of self among the redundant proposers of the current round, which is none if
+    /// self is not one of them or is the primary one.
+    #[cfg(feature = "multi_proposal")]
+    fn backup_rank(&self) -> ConsensusResult<Option<usize>> {
+        let proposers = self.authority.get_proposers(
+            self.height,
+            self.round,
+            self.config.proposer_count,
+        )?;
+        Ok(proposers
+            .iter()
+            .position(|addr| addr == &self.address)
+            .filter(|rank| *rank > 0))
+    }
+
+    #[cfg(not(feature = "multi_proposal"))]
+    fn backup_rank(&self) -> ConsensusResult<Option<usize>> {
+        Ok(None)
+    }
+
+    /// Make up a proposal if self is a redundant proposer of the current round, or if self is
+    /// an authority in the VRF leader mode. The proposal is broadcast to others and collected
+    /// as a candidate of self.
+    async fn propose_as_backup(&mut self) -> ConsensusResult<()> {
+        if !self.multi_proposal_enabled() || !self.consensus_power {
+            return Ok(());
+        }
+
+        let rank = if self.config.vrf_leader {
+            None
+        } else {
+            match self.backup_rank()? {
+                Some(rank) => Some(rank),
+                None => return Ok(()),
+            }
+        };
+
+        let ctx = self
+            .round_context(
+                self.address.clone(),
+                self.timer_config.get_propose_timeout(),
+            )
+            .attach_to(Context::new());
+        let (block, hash) = match self.get_block(ctx.clone()).await {
+            Some(res) => res,
+            None => return Ok(()),
+        };
+        self.hash_with_block
+            .entry(hash.clone())
+            .or_insert_with(|| block.clone());
+
+        let proposal = Proposal {
+            height: self.height,
+            round: self.round,
+            content: block,
+            block_hash: hash,
+            lock: None,
+            proposer: self.address.clone(),
+            timestamp: self.proposal_timestamp(),
+        };
+        let hash = self
+            .util
+            .hash(proposal_preimage(self.wire_codec.as_ref(), &proposal)?);
+        let message = Unsigned::BackupProposal {
+            ctx,
+            proposal,
+            rank,
+        };
+        self.sign(Step::Propose, hash, message).await
+    }
+
+    /// Broadcast the signed proposal of self as a redundant proposer and collect it as a
+    /// candidate of self, or propose it by the VRF if the rank is none.
+    async fn send_backup_proposal(
+        &mut self,
+        ctx: Context,
+        signed_proposal: SignedProposal<T>,
+        rank: Option<usize>,
+    ) -> ConsensusResult<()> {
+        let rank = match rank {
+            Some(rank) => rank,
+            None => return self.propose_by_vrf(ctx, signed_proposal).await,
+        };
+        let proposal = &signed_proposal.proposal;
+        let (hash, block, timestamp) = (
+            proposal.block_hash.clone(),
+            proposal.content.clone(),
+            proposal.timestamp,
+        );
+
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state broadcast a signed proposal of rank {} height {}, round {}, id {}, hash {:?}",
+            rank,
+            self.height,
+            self.round,
+            self.correlation_id(),
+            hex_encode(hash.clone())
+        );
+
+        self.broadcast_proposal(signed_proposal.clone()).await;
+        self.check_block(ctx.clone(), self.address.clone(), hash, block, timestamp)
+            .await;
+        self.handle_candidate(ctx, Rank::Designated(rank), signed_proposal)
+            .await
+    }
+
+    /// Attach the VRF proof of self over the current height and round to the signed proposal
+    /// in the VRF leader mode, then broadcast it and collect it as a candidate of self ranked
+    /// by the VRF output.
+    async fn propose_by_vrf(
+        &mut self,
+        ctx: Context,
+        mut signed_proposal: SignedProposal<T>,
+    ) -> ConsensusResult<()> {
+        let input =
+            self.wire_codec
+                .vrf_input(self.height, self.round, &self.config.vrf_seed)?;
+        let (output, proof) = self
+            .util
+            .vrf_prove(input)
+            .map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))?;
+        signed_proposal.vrf_proof = proof;
+
+        let proposal = signed_proposal.proposal.clone();
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state broadcast a signed proposal of VRF output {:?} height {}, round {}, id {}, hash {:?}",
+            hex_encode(output.clone()),
+            self.height,
+            self.round,
+            self.correlation_id(),
+            hex_encode(proposal.block_hash.clone())
+        );
+
+        self.broadcast_proposal(signed_proposal.clone()).await;
+        self.check_block(
+            ctx.clone(),
+            self.address.clone(),
+            proposal.block_hash,
+            proposal.content,
+            proposal.timestamp,
+        )
+        .await;
+        self.handle_candidate(ctx, Rank::Vrf(output), signed_proposal)
+            .await
+    }
+
+    /// Only one proposal of a round is cached until the round, so keep the lowest ranked one
+    /// of the candidates of a later round. Return whether the cached one is kept and the
+    /// given one of the rank is dropped.
+    fn keep_cached_candidate(&mut self, height: u64, round: u64, rank: &Rank) -> bool {
+        if height == self.height && round == self.round {
+            return false;
+        }
+        let cached = match self.proposals.get(height, round) {
+            Ok((cached, _)) => cached,
+            Err(_) => return false,
+        };
+        match self.proposal_rank(&cached) {
+            Ok(cached_rank) if cached_rank <= *rank => true,
+            _ => {
+                self.proposals.remove(height, round);
+                false
+            }
+        }
+    }
+
+    fn proposal_wait(&self) -> Duration {
+        if self.config.proposal_wait == 0 {
+            self.block_interval / 4
+        } else {
+            Duration::from_millis(self.config.proposal_wait)
+        }
+    }
+
+    /// Schedule to resend the signed vote once after a proportion of the step timeout. Each
+    /// vote is resent at most once, and only if the QC has not formed by then.
+    fn schedule_resend(&self, vote_type: &VoteType, signed_vote: SignedVote) {
+        let ratio = self.config.vote_resend_ratio;
+        if ratio == 0 || ratio >= 10 {
+            return;
+        }
+
+        let timeout = match vote_type {
+            VoteType::Prevote => self.timer_config.get_prevote_timeout(),
+            VoteType::Precommit => self.timer_config.get_precommit_timeout(),
+        };
+        let timeout = self.timer_config.get_backoff_timeout(timeout, self.round);
+        self.schedule(
+            timeout * ratio as u32 / 10,
+            DelayedEvent::ResendVote(signed_vote),
+        );
+    }
+
+    /// Send the signed vote of self to the backup relayers besides the leader at once. If self
+    /// is a backup relayer, the vote is collected as the leader does.
+    /// Insert a verified vote to the collector. The signature of a vote of the current height
+    /// is folded into the running aggregated signature of its block, unless the votes are
+    /// aggregated externally.
+    fn collect_vote(&mut self, ctx: Context, signed_vote: SignedVote) {
+        if signed_vote.get_height() == self.height && !self.config.external_aggregation {
+            self.aggregates.fold(self.util.as_ref(), &signed_vote);
+        }
+        let voter = signed_vote.voter.clone();
+        if self.config.height_report && signed_vote.get_height() == self.height {
+            self.timing.on_vote(
+                signed_vote.get_round(),
+                &signed_vote.vote.vote_type,
+                voter.clone(),
+                self.round_start.elapsed(),
+            );
+        }
+        self.votes
+            .insert_vote(ctx, signed_vote.get_hash(), signed_vote, voter);
+    }
+
+    async fn transmit_to_backup_relayers(
+        &mut self,
+        signed_vote: &SignedVote,
+    ) -> ConsensusResult<()> {
+        if self.config.backup_relayers == 0 {
+            return Ok(());
+        }
+
+        let relayers = self.authority.get_backup_relayers(
+            self.height,
+            self.round,
+            &self.leader_address,
+            self.config.backup_relayers,
+            self.config.relayer_policy,
+        )?;
+        for relayer in relayers {
+            if relayer == self.address {
+                self.collect_vote(Context::new(), signed_vote.clone());
+                self.save_round_votes(self.height, self.round).await;
+            } else {
+                self.transmit_to(
+                    self.span_ctx.clone(),
+                    relayer,
+                    MlmMsg::SignedVote(signed_vote.clone()),
+                )
+                .await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Schedule to route the signed vote to the relayer of the hop in the fallback chain after
+    /// a proportion of the step timeout for each hop. No hop is scheduled after the step times
+    /// out or after the chain comes back to the leader.
+    fn schedule_fallback(
+        &self,
+        vote_type: &VoteType,
+        signed_vote: SignedVote,
+        hop: usize,
+    ) {
+        let ratio = self.config.relayer_fallback_ratio;
+        if ratio == 0 || hop as u64 * ratio >= 10 || hop >= self.authority.len() {
+            return;
+        }
+
+        let timeout = match vote_type {
+            VoteType::Prevote => self.timer_config.get_prevote_timeout(),
+            VoteType::Precommit => self.timer_config.get_precommit_timeout(),
+        };
+        let timeout = self.timer_config.get_backoff_timeout(timeout, self.round);
+        self.schedule(
+            timeout * ratio as u32 / 10,
+            DelayedEvent::FallbackVote { signed_vote, hop },
+        );
+    }
+
+    /// Send the event back to the state after the delay.
+    fn schedule(&self, delay: Duration, event: DelayedEvent) {
+        let tx = self.delay_tx.clone();
+        let sleep = self.runtime.sleep(delay);
+        self.runtime.spawn(Box::pin(async move {
+            sleep.await;
+            let _ = tx.unbounded_send(event);
+        }));
+    }
+
+    fn runtime_config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            interval: self.block_interval,
+            timer_config: self.duration_config.clone(),
+            authority_list: self.authority.get_authority_list(),
+            vote_weight_sum: self.authority.get_vote_weight_sum(),
+        }
+    }
+
+    /// The configuration in effect, with the timeouts of the current interval and timeout
+    /// ratios and the defaults of the zero options.
+    fn effective_config(&self) -> EffectiveConfig {
+        let millis = |duration: Duration| duration.as_millis() as u64;
+        let or = |value: u64, default: Duration| match value {
+            0 => millis(default),
+            value => value,
+        };
+        let prevote_timeout = self.timer_config.get_prevote_timeout();
+        let timer_config = self
+            .duration_config
+            .clone()
+            .unwrap_or_else(DurationConfig::classic);
+        EffectiveConfig {
+            interval_ms: millis(self.block_interval),
+            tuned_interval_ms: millis(self.tuner.interval(self.block_interval)),
+            backoff: timer_config.backoff.clone().unwrap_or_default(),
+            timer_config,
+            propose_timeout_ms: millis(self.timer_config.get_propose_timeout()),
+            prevote_timeout_ms: millis(prevote_timeout),
+            precommit_timeout_ms: millis(self.timer_config.get_precommit_timeout()),
+            brake_timeout_ms: millis(self.timer_config.get_brake_timeout()),
+            timer_jitter: self.config.timer_jitter.min(100),
+            proposal_wait_ms: or(self.config.proposal_wait, self.block_interval / 4),
+            sign_timeout_ms: or(self.config.sign_timeout, prevote_timeout),
+            signer_retry_interval_ms: millis(self.signer_retry_interval()),
+            availability_timeout_ms: or(
+                self.config.availability_timeout,
+                prevote_timeout,
+            ),
+            message_budget: match self.config.message_budget {
+                0 => MESSAGE_BUDGET,
+                budget => budget,
+            },
+            inbox_drain_limit: match self.config.inbox_drain_limit {
+                0 => DRAIN_LIMIT,
+                limit => limit,
+            },
+            median_time_window: self.median_time.window(),
+            features: enabled_features(),
+            mlm_config: self.config.clone(),
+        }
+    }
+
+    /// Publish the items of the runtime configuration changed from the given one.
+    fn publish_config_change(&self, before: RuntimeConfig, source: ConfigSource) {
+        let diff = before.diff(&self.runtime_config());
+        if diff.is_empty() {
+            return;
+        }
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state config changed by {:?}, height {}, diff {:?}",
+            source,
+            self.height,
+            diff
+        );
+        self.events.publish(
+            self.height,
+            self.round,
+            MlmEventKind::ConfigChanged { source, diff },
+        );
+    }
+
+    /// Take the authority list of the status from the schedule, if the height of the status is
+    /// the first one of the scheduled epoch, since the handover proof of the last epoch is
+    /// committed to the scheduled list.
+    fn epoch_status(&self, mut status: Status) -> Status {
+        let next = match self.next_epoch.as_ref() {
+            Some(next) if next.height == status.height => next,
+            _ => return status,
+        };
+
+        status.authority_list.sort();
+        if status.authority_list != next.authority_list {
+            node_log!(
+                warn,
+                self.log_scope(),
+                "Mlm: state status of height {} mismatches the scheduled authority list",
+                status.height
+            );
+            status.authority_list = next.authority_list.clone();
+        }
+        status
+    }
+
+    /// Schedule the authority list of the next epoch carried by the status, which takes effect
+    /// from the next height of the status. A schedule of a passed height is dropped.
+    fn schedule_epoch(&mut self, status: &Status) {
+        let mut authority_list = match status.next_authority_list.clone() {
+            Some(list) => list,
+            None => {
+                if self
+                    .next_epoch
+                    .as_ref()
+                    .is_some_and(|next| next.height < status.height)
+                {
+                    self.next_epoch = None;
+                }
+                return;
+            }
+        };
+
+        authority_list.sort();
+        let mut authority = AuthorityManage::clone(&self.authority);
+        match self.address_scheme.as_ref() {
+            Some(scheme) => {
+                authority
+                    .update_with_scheme(&mut authority_list.clone(), scheme.as_ref());
+            }
+            None => authority.update(&mut authority_list.clone()),
+        }
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state schedule the authority list of the next epoch from height {}",
+            status.height + 1
+        );
+        self.notify_authority_change(status.height + 1, &authority_list);
+        self.next_epoch = Some(NextEpoch {
+            height: status.height + 1,
+            authority_list,
+            authority: Arc::new(authority),
+        });
+    }
+
+    /// Check the authority lists of a status before it is applied. An invalid one, or one that
+    /// the threshold policy is unsafe for, is reported and the status is rejected, so the node
+    /// stays at its height instead of running with a broken authority list.
+    fn check_authority_update(
+        &self,
+        ctx: Context,
+        status: &Status,
+    ) -> ConsensusResult<()> {
+        let lists = std::iter::once(&status.authority_list)
+            .chain(status.next_authority_list.as_ref());
+        for authority_list in lists {
+            let checked = validate_authority_list(authority_list)
+                .and_then(|_| self.authority.check_threshold(authority_list));
+            if let Err(error) = checked {
+                let err = ConsensusError::InvalidAuthorityUpdate {
+                    height: status.height,
+                    error,
+                };
+                self.report_error(ctx, err.clone());
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the timer config of a status before it is applied. An invalid one is reported and
+    /// the status is rejected like of an invalid authority list, since it would time out the
+    /// steps at once.
+    fn check_timer_config(&self, ctx: Context, status: &Status) -> ConsensusResult<()> {
+        match status.timer_config.as_ref().map(DurationConfig::validate) {
+            Some(Err(error)) => {
+                let err = ConsensusError::InvalidConfig(error);
+                self.report_error(ctx, err.clone());
+                Err(err)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply the proposer seed of the status if it has one, otherwise the seed is kept.
+    fn update_proposer_seed(&mut self, status: &Status) {
+        if let Some(seed) = status.proposer_seed {
+            Arc::make_mut(&mut self.authority).set_proposer_seed(seed);
+        }
+    }
+
+    /// Apply the proposers skipped at the height of the status.
+    fn update_skipped_proposers(&mut self, status: &Status) {
+        if status.skipped_proposers.as_slice() != self.authority.skipped_proposers() {
+            Arc::make_mut(&mut self.authority)
+                .set_skipped_proposers(status.skipped_proposers.clone());
+        }
+    }
+
+    /// Check whether the given address is included in the corresponding authority list.
+    fn update_authority(&mut self, authority_list: &mut Vec<Node>) {
+        let scheme = match self.address_scheme.as_ref() {
+            Some(scheme) => Arc::clone(scheme),
+            None => {
+                Arc::make_mut(&mut self.authority).update(authority_list);
+                return;
+            }
+        };
+
+        for node in Arc::make_mut(&mut self.authority)
+            .update_with_scheme(authority_list, scheme.as_ref())
+            .iter()
+        {
+            node_log!(
+                warn,
+                self.log_scope(),
+                "Mlm: state exclude an authority of invalid address {:?}",
+                hex_encode(node.address.clone())
+            );
+        }
+    }
+
+    fn verify_address(&self, address: &Address) -> ConsensusResult<()> {
+        if !self.authority.contains(address) {
+            return Err(ConsensusError::InvalidAddress);
+        }
+        Ok(())
+    }
+
+    async fn transmit(&self, ctx: Context, msg: MlmMsg<T>) {
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state transmit a message to leader height {}, round {}, id {}",
+            self.height,
+            self.round,
+            self.correlation_id()
+        );
+
+        let attrs = SpanAttrs::round(self.height, self.round)
+            .with_kind(&msg)
+            .with_from(self.address.clone());
+        let ctx = self.span(ctx, "mlm.transmit", attrs);
+        let ctx = self.correlation_id().attach_to(ctx);
+        self.record_sent(&msg);
+        let _ = self
+            .function
+            .transmit_to_relayer(ctx, self.leader_address.clone(), msg.clone())
+            .await
+            .map_err(|err| {
+                node_log!(
+                    error,
+                    self.log_scope(),
+                    "Mlm: state transmit message to leader failed {:?}",
+                    err
+                );
+            });
+    }
+
+    async fn transmit_to(&self, ctx: Context, addr: Address, msg: MlmMsg<T>) {
+        let attrs = SpanAttrs::round(self.height, self.round)
+            .with_kind(&msg)
+            .with_from(self.address.clone());
+        let ctx = self.span(ctx, "mlm.transmit", attrs);
+        let ctx = self.correlation_id().attach_to(ctx);
+        self.record_sent(&msg);
+        let _ = self
+            .function
+            .transmit_to_relayer(ctx, addr, msg)
+            .await
+            .map_err(|err| {
+                node_log!(
+                    error,
+                    self.log_scope(),
+                    "Mlm: state transmit message failed {:?}",
+                    err
+                );
+            });
+    }
+
+    /// Broadcast a signed proposal, or the compact one of it in the compact proposal mode. A
+    /// block over the part size is broadcast in parts after the parted proposal.
+    async fn broadcast_proposal(&mut self, signed_proposal: SignedProposal<T>) {
+        self.own_proposal = Some(signed_proposal.clone());
+        // A block whose size hint is within the part size is not encoded to be measured.
+        let fits = signed_proposal
+            .proposal
+            .content
+            .size_hint()
+            .is_some_and(|len| len <= self.config.block_part_size);
+        if self.config.block_part_size > 0 && !fits {
+            match signed_proposal.proposal.content.encode() {
+                Ok(content) if content.len() > self.config.block_part_size => {
+                    self.broadcast_parts(signed_proposal, content).await;
+                    return;
+                }
+                Ok(_) => (),
+                Err(e) => node_log!(
+                    error,
+                    self.log_scope(),
+                    "Mlm: state encode block error {:?}",
+                    e
+                ),
+            }
+        }
+
+        let msg = if self.config.compact_proposal {
+            MlmMsg::CompactProposal(CompactProposal::from_signed(&signed_proposal))
+        } else {
+            MlmMsg::SignedProposal(signed_proposal)
+        };
+        self.broadcast(self.span_ctx.clone(), msg).await;
+    }
+
+    async fn broadcast_parts(&self, signed_proposal: SignedProposal<T>, content: Bytes) {
+        let (height, round) = (
+            signed_proposal.proposal.height,
+            signed_proposal.proposal.round,
+        );
+        let (root, parts) =
+            split_block(self.util.as_ref(), &content, self.config.block_part_size);
+        let parted_proposal = PartedProposal {
+            proposal: CompactProposal::from_signed(&signed_proposal),
+            total: parts.len() as u64,
+            root: root.clone(),
+        };
+        self.broadcast(
+            self.span_ctx.clone(),
+            MlmMsg::PartedProposal(parted_proposal),
+        )
+        .await;
+
+        for (content, proof) in parts {
+            let part = BlockPart {
+                height,
+                round,
+                root: root.clone(),
+                content,
+                proof,
+            };
+            self.broadcast(self.span_ctx.clone(), MlmMsg::BlockPart(part))
+                .await;
+        }
+    }
+
+    async fn broadcast(&self, ctx: Context, msg: MlmMsg<T>) {
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state broadcast a message to others height {}, round {}, id {}",
+            self.height,
+            self.round,
+            self.correlation_id()
+        );
+
+        let attrs = SpanAttrs::round(self.height, self.round)
+            .with_kind(&msg)
+            .with_from(self.address.clone());
+        let ctx = self.span(ctx, "mlm.broadcast", attrs);
+        let ctx = self.correlation_id().attach_to(ctx);
+        self.record_sent(&msg);
+        let _ = self
+            .function
+            .broadcast_to_other(ctx, msg.clone())
+            .await
+            .map_err(|err| {
+                node_log!(
+                    error,
+                    self.log_scope(),
+                    "Mlm: state broadcast message failed {:?}",
+                    err
+                );
+            });
+    }
+
+    /// Count the bytes of a message sent by the node for the height report.
+    fn record_sent(&self, msg: &MlmMsg<T>) {
+        if self.config.height_report {
+            self.timing.on_sent(msg);
+        }
+    }
+
+    fn report_error(&self, ctx: Context, err: ConsensusError) {
+        let peer = ctx.peer().map(String::from);
+        self.events
+            .publish_peer_error(self.height, self.round, peer, err.clone());
+        let ctx = self.correlation_id().attach_to(ctx);
+        self.function.report_error(ctx, err);
+    }
+
+    /// Evict the proposals, the votes and the QCs of the heights below `till` from the caches,
+    /// and hand each of the heights to the adapter before if
+    /// `MlmConfig::archive_expired_heights` is enabled.
+    fn expire_heights(&mut self, till: u64) {
+        let proposals = self.proposals.take_below(till);
+        let votes = self.votes.take_below(till);
+        let expired = expired_heights(proposals, votes);
+        if expired.is_empty() {
+            return;
+        }
+
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state expire {} heights below height {}",
+            expired.len(),
+            till
+        );
+        self.memory.add_expired_heights(expired.len());
+        if !self.config.archive_expired_heights {
+            return;
+        }
+        for expired in expired {
+            let ctx = CorrelationId::new(expired.height, INIT_ROUND);
+            self.function
+                .archive_height(ctx.attach_to(Context::new()), expired);
+        }
+    }
+
+    /// Evict the messages of the superseded rounds of the current height and the messages of
+    /// the future heights from the caches over their limits in the memory budget, and update
+    /// the memory account.
+    fn check_memory(&mut self) {
+        let retention = self.config.round_retention;
+        if retention > 0 && self.round > retention {
+            let below = self.round - retention;
+            let pruned = self.proposals.prune_rounds(self.height, below)
+                + self.votes.prune_rounds(self.height, below)
+                + self.chokes.prune_rounds(below);
+            self.memory.add_pruned(pruned);
+        }
+
+        let limit = self.memory.limit(CacheKind::Proposals);
+        let evicted = if limit > 0 {
+            self.proposals.evict(self.height, limit)
+        } else {
+            0
+        };
+        self.memory
+            .update(CacheKind::Proposals, self.proposals.mem_size(), evicted);
+
+        let limit = self.memory.limit(CacheKind::Votes);
+        let evicted = if limit > 0 {
+            self.votes.evict(self.height, limit)
+        } else {
+            0
+        };
+        self.memory
+            .update(CacheKind::Votes, self.votes.mem_size(), evicted);
+    }
+
+    /// Check whether a round already holds the max number of the signed messages, and count
+    /// the message to be dropped if so.
+    fn over_round_cap(&self, count: usize, height: u64, round: u64) -> bool {
+        let cap = self.config.round_message_cap;
+        if cap == 0 || count < cap {
+            return false;
+        }
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state drop a message over the cap of height {}, round {}",
+            height,
+            round
+        );
+        self.memory.add_capped();
+        true
+    }
+
+    fn publish_error(&self, err: ConsensusError) {
+        self.events.publish_error(self.height, self.round, err);
+    }
+
+    /// Report an error of the violated invariant to the adapter and the error channel.
+    fn report_invariant(&self, msg: &str) -> ConsensusError {
+        let err = ConsensusError::invariant(msg);
+        node_log!(
+            error,
+            self.log_scope(),
+            "Mlm: state {}, id {}",
+            err,
+            self.correlation_id()
+        );
+        self.report_error(Context::new(), err.clone());
+        err
+    }
+
+    fn publish_qc(&self, qc: &AggregatedVote) {
+        self.events.publish(
+            qc.height,
+            qc.round,
+            MlmEventKind::QcFormed {
+                vote_type: qc.vote_type.clone(),
+                block_hash: qc.block_hash.clone(),
+            },
+        );
+    }
+
+    /// Export a snapshot of the consensus state from the wal of the current height, with the
+    /// last signed record and the QCs of the height.
+    pub(crate) async fn export_snapshot(
+        &mut self,
+    ) -> ConsensusResult<ConsensusSnapshot<T>> {
+        let wal_info = self
+            .load_wal()
+            .await?
+            .filter(|info| info.height == self.height && info.status.is_some())
+            .ok_or_else(|| {
+                ConsensusError::LoadWalErr(format!(
+                    "no wal of height {} to snapshot",
+                    self.height
+                ))
+            })?;
+
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state export a snapshot of height {}, round {}, step {:?}",
+            wal_info.height,
+            wal_info.round,
+            wal_info.step
+        );
+        Ok(ConsensusSnapshot {
+            wal_info,
+            last_signed: self.last_signed.clone(),
+            qcs: self.height_qcs(),
+        })
+    }
+
+    /// The QCs of the current height known by the node.
+    fn height_qcs(&mut self) -> Vec<AggregatedVote> {
+        let mut qcs = Vec::new();
+        for round in 0..=self.round {
+            for vote_type in [VoteType::Prevote, VoteType::Precommit] {
+                if let Ok(qc) = self.votes.get_qc_by_id(self.height, round, vote_type) {
+                    qcs.push(qc);
+                }
+            }
+        }
+        qcs
+    }
+
+    fn state_dump(&mut self) -> StateDump {
+        let height = self.height;
+        let (prevotes, precommits) = self.votes.get_height_vote_set(height);
+        let votes = prevotes
+            .into_iter()
+            .chain(precommits)
+            .map(|sv| VoteDump {
+                round: sv.vote.round,
+                vote_type: sv.vote.vote_type,
+                block_hash: sv.vote.block_hash,
+                voter: sv.voter,
+            })
+            .collect();
+        let mut qcs = Vec::new();
+        let mut chokes = Vec::new();
+        for round in 0..=self.round {
+            for vote_type in [VoteType::Prevote, VoteType::Precommit] {
+                if let Ok(qc) = self.votes.get_qc_by_id(height, round, vote_type.clone())
+                {
+                    qcs.push(QcDump {
+                        round,
+                        vote_type,
+                        block_hash: qc.block_hash,
+                    });
+                }
+            }
+            let mut addresses = self
+                .chokes
+                .get_chokes(round)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|sc| sc.address)
+                .collect::<Vec<_>>();
+            addresses.sort();
+            chokes.extend(
+                addresses
+                    .into_iter()
+                    .map(|address| ChokeDump { round, address }),
+            );
+        }
+        let lock = self.lock_round.and_then(|round| {
+            self.votes
+                .get_qc_by_id(height, round, VoteType::Prevote)
+                .ok()
+                .map(|qc| LockDump {
+                    round,
+                    block_hash: qc.block_hash,
+                })
+        });
+
+        let (step, start) = self
+            .step_starts
+            .last()
+            .cloned()
+            .unwrap_or((Step::Propose, self.round_start));
+        let backoff =
+            |timeout| self.timer_config.get_backoff_timeout(timeout, self.round);
+        let step_timeout = match step {
+            Step::Propose => backoff(self.timer_config.get_propose_timeout()),
+            Step::Prevote => backoff(self.timer_config.get_prevote_timeout()),
+            Step::Precommit => backoff(self.timer_config.get_precommit_timeout()),
+            Step::Brake => self.timer_config.get_brake_timeout(),
+            Step::Commit => Duration::ZERO,
+        };
+
+        let remaining = self.step_deadline.remaining(height, self.round);
+
+        StateDump {
+            height,
+            round: self.round,
+            step,
+            is_leader: self.is_leader,
+            consensus_power: self.consensus_power,
+            lock,
+            authority_list: self.authority.get_authority_list(),
+            votes,
+            qcs,
+            chokes,
+            future_messages: self.future.len(),
+            step_elapsed_ms: start.elapsed().as_millis() as u64,
+            step_timeout_ms: step_timeout.as_millis() as u64,
+            step_remaining_ms: remaining.as_millis() as u64,
         }
-        Ok(())
     }
 
-    /// Check whether the given address is included in the corresponding authority list.
-    fn verify_address(&self, address: &Address) -> ConsensusResult<()> {
-        if !self.authority.contains(address) {
-            return Err(ConsensusError::InvalidAddress);
+    /// Record a consensus message for the stall dump.
+    fn record_msg(&mut self, msg: &MlmMsg<T>) {
+        if !self.stall.is_recording() {
+            return;
         }
-        Ok(())
+        let round = match msg {
+            MlmMsg::SignedProposal(sp) => sp.proposal.round,
+            MlmMsg::SignedVote(sv) => sv.get_round(),
+            MlmMsg::AggregatedVote(av) => av.get_round(),
+            MlmMsg::SignedChoke(sc) => sc.choke.round,
+            MlmMsg::RichStatus(_) => INIT_ROUND,
+            _ => return,
+        };
+        let from = signer(msg).cloned().unwrap_or_default();
+        self.stall
+            .record(msg.to_string(), msg.get_height(), round, from);
     }
 
-    async fn transmit(&self, ctx: Context, msg: MlmMsg<T>) {
-        debug!(
-            "Mlm: state transmit a message to leader height {}, round {}",
-            self.height, self.round
+    /// Report a dump of the state if the node has made no height progress in the stall
+    /// timeout, and check again after the timeout.
+    fn check_stall(&mut self) {
+        let stall_timeout = Duration::from_millis(self.config.stall_timeout);
+        let stalled_for = self.stall.stalled_for();
+        if stalled_for < stall_timeout {
+            self.schedule(stall_timeout - stalled_for, DelayedEvent::StallCheck);
+            return;
+        }
+        self.schedule(stall_timeout, DelayedEvent::StallCheck);
+        if !self.consensus_power {
+            return;
+        }
+
+        node_log!(
+            warn,
+            self.log_scope(),
+            "Mlm: state stalled for {:?}, height {}, round {}, id {}",
+            stalled_for,
+            self.height,
+            self.round,
+            self.correlation_id()
         );
+        let dump = self.stall_dump(stalled_for);
+        let ctx = self.correlation_id().attach_to(Context::new());
+        self.function.report_stall(ctx, dump);
+    }
 
-        let _ = self
-            .function
-            .transmit_to_relayer(ctx, self.leader_address.clone(), msg.clone())
-            .await
-            .map_err(|err| {
-                error!("Mlm: state transmit message to leader failed {:?}", err);
-            });
+    /// Record the signer of a message from the network as heard from, if it is an authority.
+    fn record_heard(&mut self, msg: &MlmMsg<T>) {
+        if self.config.connectivity_window == 0 {
+            return;
+        }
+        if let Some(from) = signer(msg) {
+            if self.authority.contains(from) {
+                self.connectivity.heard(from);
+            }
+        }
     }
 
-    async fn broadcast(&self, ctx: Context, msg: MlmMsg<T>) {
-        debug!(
-            "Mlm: state broadcast a message to others height {}, round {}",
-            self.height, self.round
+    /// Report the connectivity if the authorities heard from in the connectivity window can
+    /// not reach a quorum, and check again after the window.
+    fn check_connectivity(&mut self) {
+        let window = Duration::from_millis(self.config.connectivity_window);
+        self.schedule(window, DelayedEvent::ConnectivityCheck);
+        if !self.consensus_power {
+            return;
+        }
+
+        let summary = match self.connectivity.check(
+            &self.authority.get_authority_list(),
+            &self.address,
+            window,
+            self.height,
+            self.round,
+        ) {
+            Some(summary) => summary,
+            None => return,
+        };
+        node_log!(
+            warn,
+            self.log_scope(),
+            "Mlm: state heard from weight {} only in {:?}, silent weight {}, id {}",
+            summary.heard_weight,
+            window,
+            summary.silent_weight,
+            self.correlation_id()
         );
+        let ctx = self.correlation_id().attach_to(Context::new());
+        self.function.report_connectivity(ctx, summary);
+    }
 
-        let _ = self
-            .function
-            .broadcast_to_other(ctx, msg.clone())
-            .await
-            .map_err(|err| {
-                error!("Mlm: state broadcast message failed {:?}", err);
-            });
+    /// Sign and broadcast a heartbeat of self, and send the next one after the heartbeat
+    /// interval. The liveness of the validators out of the authority list is removed.
+    fn send_heartbeat(&mut self) {
+        self.schedule(
+            Duration::from_millis(self.config.heartbeat_interval),
+            DelayedEvent::Heartbeat,
+        );
+        let authorities = self
+            .authority
+            .get_authority_list()
+            .into_iter()
+            .map(|node| node.address)
+            .collect::<Vec<_>>();
+        self.liveness.retain(&authorities);
+        if !self.consensus_power || !self.signer_ready {
+            return;
+        }
+
+        // The timestamps of the heartbeats of self increase even if the clock goes back.
+        let timestamp = now_millis().max(self.heartbeat_at + 1);
+        let heartbeat = Heartbeat {
+            height: self.height,
+            round: self.round,
+            timestamp,
+            address: self.address.clone(),
+        };
+        let preimage = match self.wire_codec.heartbeat_preimage(&heartbeat) {
+            Ok(preimage) => preimage,
+            Err(e) => {
+                self.report_error(Context::new(), e);
+                return;
+            }
+        };
+        let hash = self.util.hash(preimage);
+        self.heartbeat_at = timestamp;
+        self.spawn_sign(hash, Unsigned::Heartbeat(heartbeat));
     }
 
-    fn report_error(&self, ctx: Context, err: ConsensusError) {
-        self.function.report_error(ctx, err);
+    /// Sign an attestation of the height, the round and the lock of self and report it to
+    /// the adapter, and send the next one after the attestation interval.
+    fn send_attestation(&mut self) {
+        self.schedule(
+            Duration::from_millis(self.config.attestation_interval),
+            DelayedEvent::Attestation,
+        );
+        if !self.consensus_power || !self.signer_ready {
+            return;
+        }
+
+        let lock_hash = self
+            .lock_round
+            .and_then(|round| {
+                self.votes
+                    .get_qc_by_id(self.height, round, VoteType::Prevote)
+                    .ok()
+            })
+            .map(|qc| qc.block_hash)
+            .unwrap_or_default();
+        let attestation = Attestation {
+            height: self.height,
+            round: self.round,
+            lock_hash,
+            timestamp: now_millis().max(self.attestation_at + 1),
+            address: self.address.clone(),
+        };
+        let hash = self
+            .util
+            .hash(canonical::attestation_preimage(&attestation));
+        self.attestation_at = attestation.timestamp;
+        self.spawn_sign(hash, Unsigned::Attestation(attestation));
+    }
+
+    /// Record the liveness and the view of a validator by its verified heartbeat.
+    fn handle_heartbeat(
+        &mut self,
+        signed_heartbeat: SignedHeartbeat,
+    ) -> ConsensusResult<()> {
+        let heartbeat = signed_heartbeat.heartbeat;
+        if heartbeat.address == self.address
+            || !self.authority.contains(&heartbeat.address)
+        {
+            return Ok(());
+        }
+        if !self.liveness.record(&heartbeat) {
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state ignore an outdated heartbeat from {}, timestamp {}",
+                hex_encode(&heartbeat.address),
+                heartbeat.timestamp
+            );
+            return Ok(());
+        }
+        self.resync_by_quorum(&heartbeat.address, heartbeat.height, heartbeat.round)
+    }
+
+    /// Notify the crypto of the authority list of an epoch if it differs from the one of the
+    /// latest notified epoch, and schedule the broadcast of the key rotation metadata of self
+    /// that the crypto returns.
+    fn notify_authority_change(&mut self, epoch: u64, authority_list: &[Node]) {
+        let mut authority_list = authority_list.to_vec();
+        authority_list.sort();
+        if let Some((key_epoch, list)) = self.key_epoch.as_ref() {
+            if *key_epoch >= epoch || *list == authority_list {
+                return;
+            }
+        }
+
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state notify the authority change of epoch {}",
+            epoch
+        );
+        let res = self.util.on_authority_change(epoch, &authority_list);
+        self.key_epoch = Some((epoch, authority_list));
+        self.schedule_key_rotation(epoch, res);
+    }
+
+    /// Handle the verified key rotation metadata of an authority by the crypto.
+    fn handle_key_rotation(&mut self, rotation: KeyRotation) {
+        if rotation.address == self.address {
+            return;
+        }
+        let res = self.util.on_key_rotation(&rotation);
+        self.schedule_key_rotation(rotation.epoch, res);
+    }
+
+    fn schedule_key_rotation(
+        &self,
+        epoch: u64,
+        res: Result<Option<Bytes>, Box<dyn Error + Send>>,
+    ) {
+        match res {
+            Ok(Some(payload)) => {
+                let rotation = KeyRotation {
+                    epoch,
+                    address: self.address.clone(),
+                    payload,
+                };
+                self.schedule(Duration::ZERO, DelayedEvent::KeyRotation(rotation));
+            }
+            Ok(None) => (),
+            Err(e) => node_log!(
+                warn,
+                self.log_scope(),
+                "Mlm: state key rotation of epoch {} error {:?}",
+                epoch,
+                e
+            ),
+        }
+    }
+
+    fn send_key_rotation(&mut self, rotation: KeyRotation) {
+        let preimage = match self.wire_codec.key_rotation_preimage(&rotation) {
+            Ok(preimage) => preimage,
+            Err(e) => {
+                self.report_error(Context::new(), e);
+                return;
+            }
+        };
+        let hash = self.util.hash(preimage);
+        self.spawn_sign(hash, Unsigned::KeyRotation(rotation));
+    }
+
+    fn stall_dump(&mut self, stalled_for: Duration) -> StallDump {
+        let (height, round) = (self.height, self.round);
+        let lock = self.lock_round.and_then(|lock_round| {
+            self.votes
+                .get_qc_by_id(height, lock_round, VoteType::Prevote)
+                .ok()
+                .map(|qc| (lock_round, qc.block_hash))
+        });
+        let prevotes = self
+            .votes
+            .get_vote_map(height, round, VoteType::Prevote)
+            .cloned()
+            .unwrap_or_default();
+        let precommits = self
+            .votes
+            .get_vote_map(height, round, VoteType::Precommit)
+            .cloned()
+            .unwrap_or_default();
+        let chokers = self
+            .chokes
+            .get_chokes(round)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|sc| sc.address)
+            .collect::<HashSet<_>>();
+        let voted = |votes: &HashMap<Hash, HashSet<Address>>, address: &Address| {
+            votes
+                .iter()
+                .find(|(_, voters)| voters.contains(address))
+                .map(|(hash, _)| hash.clone())
+        };
+        let votes = self
+            .authority
+            .get_authority_list()
+            .into_iter()
+            .map(|node| ValidatorVotes {
+                prevote: voted(&prevotes, &node.address),
+                precommit: voted(&precommits, &node.address),
+                choked: chokers.contains(&node.address),
+                address: node.address,
+            })
+            .collect();
+
+        StallDump {
+            height,
+            round,
+            stalled_for,
+            round_escalations: self.round_escalations,
+            step: self.current_step().clone(),
+            lock,
+            votes,
+            recent_messages: self.stall.recent(),
+        }
+    }
+
+    /// Record the time that the node enters a step of the current round, once for each step.
+    fn enter_step(&mut self, step: Step) {
+        if self.step_starts.last().map(|(last, _)| last) != Some(&step) {
+            self.step_starts.push((step, Instant::now()));
+        }
+    }
+
+    /// The step of the current round that the node is in.
+    fn current_step(&self) -> &Step {
+        self.step_starts
+            .last()
+            .map_or(&Step::Propose, |(step, _)| step)
+    }
+
+    /// Start a span of the message handling labeled with the step of the round that the node
+    /// is in.
+    fn span(&self, ctx: Context, name: &'static str, attrs: SpanAttrs) -> Context {
+        let attrs = attrs.with_step(self.current_step().clone());
+        self.trace.span(ctx, name, attrs)
+    }
+
+    /// Spawn a verification, which waits for a permit first if the verifications running at
+    /// the same time are limited by `MlmConfig::verify_concurrency`.
+    fn spawn_verify(&self, task: BoxFuture<'static, ()>) {
+        match self.verify_permits.clone() {
+            Some(permits) => self.runtime.spawn(Box::pin(async move {
+                let _permit = permits.acquire_owned().await;
+                task.await
+            })),
+            None => self.runtime.spawn(task),
+        }
+    }
+
+    /// The scope of the node that the logs of the state are labeled with.
+    fn log_scope(&self) -> LogScope {
+        LogScope {
+            address: self.address.clone(),
+            height: self.height,
+            round: self.round,
+            step: self.current_step().clone(),
+        }
+    }
+
+    /// Count a brake round, which is a choke broadcast by the brake step or a brake timeout,
+    /// and escalate the halt every `MlmConfig::max_brake_rounds` brake rounds in a row.
+    fn count_brake_round(&mut self) {
+        if self.brake_rounds == 0 {
+            self.brake_streak_start = tokio::time::Instant::now();
+        }
+        self.brake_rounds += 1;
+        let max = self.config.max_brake_rounds;
+        if max == 0 || !self.brake_rounds.is_multiple_of(max) {
+            return;
+        }
+
+        let halted_for = self.brake_streak_start.elapsed();
+        node_log!(
+            error,
+            self.log_scope(),
+            "Mlm: state brakes {} rounds in a row for {:?}, height {}, round {}",
+            self.brake_rounds,
+            halted_for,
+            self.height,
+            self.round
+        );
+        let ctx = self.correlation_id().attach_to(Context::new());
+        self.function.on_consensus_halted(
+            ctx,
+            HaltReport {
+                height: self.height,
+                round: self.round,
+                brake_rounds: self.brake_rounds,
+                halted_for,
+            },
+        );
+    }
+
+    /// Escalate the height when the node enters a round of a multiple of
+    /// `MlmConfig::max_rounds`. A node jumping over such rounds escalates once for them.
+    fn check_max_rounds(&mut self) {
+        let max = self.config.max_rounds;
+        if max == 0 || self.round / max <= self.round_escalations {
+            return;
+        }
+
+        self.round_escalations = self.round / max;
+        self.protocol.on_round_escalation();
+        let stalled_for = self.stall.stalled_for();
+        node_log!(
+            error,
+            self.log_scope(),
+            "Mlm: state runs {} rounds without a commit for {:?}, height {}",
+            self.round,
+            stalled_for,
+            self.height
+        );
+        let ctx = self.correlation_id().attach_to(Context::new());
+        self.function.on_max_rounds(
+            ctx,
+            RoundLimitReport {
+                height: self.height,
+                round: self.round,
+                escalations: self.round_escalations,
+                stalled_for,
+            },
+        );
+    }
+
+    /// Count and report the brake of the round that the node leaves, with the chokes of the
+    /// round collected so far.
+    fn end_brake(&mut self) {
+        let (height, round, start) = match self.brake_start.take() {
+            Some(brake) => brake,
+            None => return,
+        };
+        let duration = start.elapsed();
+        let mut chokers = self
+            .chokes
+            .get_chokes(round)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|signed_choke| signed_choke.address)
+            .collect::<Vec<_>>();
+        chokers.sort();
+        let choke_weight = chokers
+            .iter()
+            .filter_map(|address| self.authority.get_vote_weight(address).ok())
+            .fold(0u128, |sum, weight| sum.saturating_add(*weight));
+        let choke_qc = self.chokes.get_qc(round).is_some();
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state release the brake of height {}, round {} after {:?}, choke weight {}, choke qc {}",
+            height, round, duration, choke_weight, choke_qc
+        );
+
+        self.protocol.on_brake_end(duration);
+        let ctx = CorrelationId::new(height, round).attach_to(Context::new());
+        self.function.report_brake(
+            ctx,
+            BrakeReport {
+                height,
+                round,
+                duration,
+                chokers,
+                choke_weight,
+                choke_qc,
+            },
+        );
     }
 
     fn report_view_change(&self, round: u64, reason: ViewChangeReason) {
-        self.function
-            .report_view_change(Context::new(), self.height, round, reason)
+        let ctx = CorrelationId::new(self.height, round).attach_to(Context::new());
+        let info = ViewChangeInfo {
+            height: self.height,
+            round,
+            reason,
+            expected_proposer: self
+                .authority
+                .get_proposer(self.height, round)
+                .unwrap_or_default(),
+            step_elapsed: self.step_elapsed(),
+            prevotes: self.votes.vote_count(self.height, round, VoteType::Prevote),
+            precommits: self
+                .votes
+                .vote_count(self.height, round, VoteType::Precommit),
+            chokes: self.chokes.choke_count(round),
+        };
+        self.function.report_view_change_info(ctx, info)
+    }
+
+    /// The elapsed time of each step that the node entered in the current round in order. The
+    /// last step lasts until now.
+    fn step_elapsed(&self) -> Vec<(Step, Duration)> {
+        let now = Instant::now();
+        self.step_starts
+            .iter()
+            .enumerate()
+            .map(|(index, (step, start))| {
+                let end = self
+                    .step_starts
+                    .get(index + 1)
+                    .map(|(_, next)| *next)
+                    .unwrap_or(now);
+                (step.clone(), end.saturating_duration_since(*start))
+            })
+            .collect()
+    }
+
+    /// Report the timing of the committed height in the decided round.
+    fn report_height(&mut self, height: u64, round: u64, duration: Duration) {
+        let (prevotes, precommits, bytes_sent, bytes_received) = self.timing.take();
+        if !self.config.height_report {
+            return;
+        }
+
+        let proposer = match self.proposals.get(height, round) {
+            Ok((sp, _)) => sp.proposal.proposer,
+            Err(_) => self.authority.get_proposer(height, round).unwrap_or_default(),
+        };
+        let report = HeightReport {
+            height,
+            rounds: self.round + 1,
+            proposer,
+            duration,
+            step_elapsed: self.step_elapsed(),
+            prevotes,
+            precommits,
+            bytes_sent,
+            bytes_received,
+        };
+        let ctx = CorrelationId::new(height, round).attach_to(Context::new());
+        self.function.report_height(ctx, report);
     }
 
     fn view_change_reason(
@@ -1519,11 +5726,23 @@ where
         round: u64,
         update_from: &FromWhere,
     ) -> ViewChangeReason {
+        if let FromWhere::ChokeQC(from_round) = update_from {
+            if self.moved_by_quorum(*from_round) {
+                return ViewChangeReason::QuorumAtHigherRound(round, from_round + 1);
+            }
+        }
         if round != update_from.get_round() {
             return update_from.to_reason(round);
         }
 
         let height = self.height;
+        if let Some((forced_height, forced_round, reason)) =
+            self.forced_view_change.take()
+        {
+            if (forced_height, forced_round) == (height, round) {
+                return ViewChangeReason::OperatorTriggered(reason);
+            }
+        }
 
         // Leader condition
         if self.is_leader {
@@ -1573,18 +5792,100 @@ where
         }
     }
 
+    /// Force the current round to time out for the reason of the operator. The timeouts of the
+    /// steps before the brake are triggered at once, so the node goes to the brake step and
+    /// broadcasts its choke. In the brake step, the brake timeout is triggered to broadcast the
+    /// choke again. The triggers of the passed steps are ignored by the SMR.
+    fn force_view_change(&mut self, reason: String) -> ConsensusResult<()> {
+        node_log!(
+            warn,
+            self.log_scope(),
+            "Mlm: state force a view change by the operator, height {}, round {}, id {}, reason {}",
+            self.height,
+            self.round,
+            self.correlation_id(),
+            reason
+        );
+        if !self.consensus_power {
+            return Ok(());
+        }
+
+        self.forced_view_change = Some((self.height, self.round, reason));
+        let triggers = match self.step_starts.last().map(|(step, _)| step) {
+            Some(Step::Commit) => return Ok(()),
+            Some(Step::Brake) => vec![TriggerType::BrakeTimeout],
+            _ => vec![
+                TriggerType::Proposal,
+                TriggerType::PrevoteQC,
+                TriggerType::PrecommitQC,
+            ],
+        };
+        for trigger_type in triggers {
+            self.state_machine.trigger(SMRTrigger {
+                trigger_type,
+                source: TriggerSource::Timer,
+                hash: Hash::new(),
+                lock_round: None,
+                round: self.round,
+                height: self.height,
+                wal_info: None,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Resend the signed proposal and the signed votes of self in the current round. The
+    /// leader collects its own votes, so it rebroadcasts the QCs of the round instead.
+    async fn rebroadcast_current(&mut self) {
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state rebroadcast the current round, height {}, round {}, id {}",
+            self.height,
+            self.round,
+            self.correlation_id()
+        );
+        if !self.consensus_power {
+            return;
+        }
+
+        if let Some(signed_proposal) = self.own_proposal.clone() {
+            self.broadcast_proposal(signed_proposal).await;
+        }
+        if self.is_leader {
+            for vote_type in [VoteType::Prevote, VoteType::Precommit] {
+                let qc = self.votes.get_qc_by_id(self.height, self.round, vote_type);
+                if let Ok(qc) = qc {
+                    self.broadcast(self.span_ctx.clone(), MlmMsg::AggregatedVote(qc))
+                        .await;
+                }
+            }
+            return;
+        }
+        for signed_vote in self.own_votes.clone() {
+            self.transmit(self.span_ctx.clone(), MlmMsg::SignedVote(signed_vote))
+                .await;
+        }
+    }
+
     fn check_choke_above_threshold(&mut self) -> ConsensusResult<()> {
         self.chokes.print_round_choke_log(self.round);
-        if let Some(round) = self.chokes.max_round_above_threshold(self.authority.len())
-        {
+        if let Some(round) = self.chokes.max_round_above_threshold(&self.authority) {
             if round < self.round {
                 return Ok(());
             }
 
-            info!("Mlm: round {} chokes above threshold", round);
+            node_log!(
+                info,
+                self.log_scope(),
+                "Mlm: round {} chokes above threshold",
+                round
+            );
 
             // aggregate chokes.
-            let signed_chokes = self.chokes.get_chokes(round).unwrap();
+            let signed_chokes = self.chokes.get_chokes(round).ok_or_else(|| {
+                ConsensusError::invariant(format!("no chokes of round {}", round))
+            })?;
             let mut sigs = Vec::with_capacity(signed_chokes.len());
             let mut voters = Vec::with_capacity(signed_chokes.len());
             for sc in signed_chokes.iter() {
@@ -1602,7 +5903,9 @@ where
                 },
             );
 
-            info!(
+            node_log!(
+                info,
+                self.log_scope(),
                 "Mlm: state trigger SMR go on {} round of height {}",
                 round + 1,
                 self.height
@@ -1618,34 +5921,339 @@ where
                 wal_info: None,
             })?;
         }
-        Ok(())
+        Ok(())
+    }
+
+    /// The correlation ID of the current round.
+    fn correlation_id(&self) -> CorrelationId {
+        CorrelationId::new(self.height, self.round)
+    }
+
+    /// The round context of the current round with the proposer of the block, whose deadline
+    /// is the end of the given timeout of the round with the backoff.
+    fn round_context(&self, proposer: Address, timeout: Duration) -> RoundContext {
+        let timeout = self.timer_config.get_backoff_timeout(timeout, self.round);
+        RoundContext {
+            height: self.height,
+            round: self.round,
+            proposer,
+            is_retry: self.round != INIT_ROUND,
+            deadline: self.round_start + timeout,
+            timestamp: None,
+        }
+    }
+
+    /// Get a block of the current height by the retry policy. The call is cancelled at the
+    /// deadline of the round context, and the node misses the proposal of the round if the
+    /// call fails terminally, which is reported.
+    ///
+    /// In the empty block suppression, the call is repeated while the application has nothing
+    /// to propose until the wait passes, then an empty block is asked for unless the empty
+    /// blocks are skipped.
+    async fn get_block(&self, ctx: Context) -> Option<(T, Hash)> {
+        if self.config.empty_block_wait == 0 {
+            return self.call_get_block(AllowEmptyBlock.attach_to(ctx)).await;
+        }
+
+        let wait = Duration::from_millis(self.config.empty_block_wait).min(
+            RoundContext::from_context(&ctx).map_or_else(
+                || self.retry_deadline(),
+                |round_ctx| round_ctx.remaining(),
+            ),
+        );
+        let start = tokio::time::Instant::now();
+        loop {
+            match self.try_get_block(ctx.clone()).await {
+                Ok(res) => return Some(res),
+                Err(ConsensusError::NothingToPropose) => (),
+                Err(e) => {
+                    self.get_block_error(ctx, e);
+                    return None;
+                }
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= wait {
+                break;
+            }
+            sleep(EMPTY_BLOCK_POLL.min(wait - elapsed)).await;
+        }
+
+        if self.config.skip_empty_blocks {
+            node_log!(
+                info,
+                self.log_scope(),
+                "Mlm: state skip an empty block of height {}, round {}, id {}",
+                self.height,
+                self.round,
+                self.correlation_id()
+            );
+            return None;
+        }
+        self.call_get_block(AllowEmptyBlock.attach_to(ctx)).await
+    }
+
+    async fn call_get_block(&self, ctx: Context) -> Option<(T, Hash)> {
+        match self.try_get_block(ctx.clone()).await {
+            Ok(res) => Some(res),
+            Err(e) => {
+                self.get_block_error(ctx, e);
+                None
+            }
+        }
+    }
+
+    async fn try_get_block(&self, ctx: Context) -> ConsensusResult<(T, Hash)> {
+        let height = self.height;
+        let function = Arc::clone(&self.function);
+        let cancel = RoundContext::from_context(&ctx)
+            .map_or_else(|| self.retry_deadline(), |round_ctx| round_ctx.remaining());
+        retry_call(
+            &self.config.adapter_retry,
+            self.retry_deadline(),
+            cancel,
+            "get_block",
+            || function.get_block(ctx.clone(), height),
+        )
+        .await
+        .and_then(|(block, hash)| {
+            hash.check_len(self.config.hash_len)?;
+            Ok((block, hash))
+        })
+    }
+
+    fn get_block_error(&self, ctx: Context, e: ConsensusError) {
+        node_log!(
+            error,
+            self.log_scope(),
+            "Mlm: state get block error {:?}, skip propose, id {}",
+            e,
+            self.correlation_id()
+        );
+        self.report_error(ctx, e);
+    }
+
+    /// The deadline of the retries of the adapter calls of the round, which is bounded by the
+    /// propose timeout of the round.
+    fn retry_deadline(&self) -> Duration {
+        let propose = self
+            .timer_config
+            .get_backoff_timeout(self.timer_config.get_propose_timeout(), self.round);
+        match self.config.adapter_retry.deadline {
+            0 => propose,
+            deadline => Duration::from_millis(deadline).min(propose),
+        }
     }
 
-    #[tracing_span(
-        kind = "mlm",
-        tags = "{'height': 'self.height', 'round': 'self.round'}"
-    )]
-    async fn check_block(&mut self, ctx: Context, hash: Hash, block: T) {
+    async fn check_block(
+        &mut self,
+        ctx: Context,
+        proposer: Address,
+        hash: Hash,
+        block: T,
+        timestamp: Option<u64>,
+    ) {
+        let attrs = SpanAttrs::round(self.height, self.round)
+            .with_hash(hash.clone())
+            .with_from(proposer.clone());
+        let ctx = self.span(ctx, "mlm.check_block", attrs);
+        let block_proposer = proposer.clone();
+        // The check is cancelled at the end of the round, and the block is checked again if
+        // it is proposed in a later round.
+        let timeout = self.timer_config.get_propose_timeout()
+            + self.timer_config.get_prevote_timeout()
+            + self.timer_config.get_precommit_timeout();
+        let mut round_ctx = self.round_context(proposer, timeout);
+        round_ctx.timestamp = timestamp;
+        let cancel = round_ctx.remaining();
+        let ctx = round_ctx.attach_to(ctx);
         let height = self.height;
         let round = self.round;
         let function = Arc::clone(&self.function);
+        let util = Arc::clone(&self.util);
         let resp_tx = self.resp_tx.clone();
+        let retry = self.config.adapter_retry;
+        let deadline = self.retry_deadline();
+        let events = self.events.clone();
+        let scope = self.log_scope();
+
+        self.runtime.spawn(Box::pin(async move {
+            let mismatch = block_hash_mismatch(
+                function.as_ref(),
+                util.as_ref(),
+                height,
+                &hash,
+                &block,
+            );
+            // The block is moved into the call since it may not be shared between threads.
+            let (call_fn, call_ctx, call_hash) = (&function, &ctx, &hash);
+            let res = retry_call(&retry, deadline, cancel, "check_block", move || {
+                call_fn.check_block(
+                    call_ctx.clone(),
+                    height,
+                    call_hash.clone(),
+                    block.clone(),
+                )
+            })
+            .await;
+            if let Err(e) = res {
+                node_log!(error, scope, "Mlm: state check block failed: {:?}", e);
+                let peer = ctx.peer().map(String::from);
+                events.publish_peer_error(height, round, peer, e.clone());
+                function.report_error(ctx, e);
+                return;
+            }
+            // The block passes the check, but the proposal is not of it.
+            if let Some((computed_hash, block_digest)) = mismatch {
+                let e = ConsensusError::BlockHashMismatch(Box::new(HashMismatch {
+                    stage: MismatchStage::Check,
+                    height,
+                    round,
+                    proposer: block_proposer,
+                    proposal_hash: hash,
+                    computed_hash,
+                    block_digest,
+                }));
+                node_log!(error, scope, "Mlm: state check block {}", e);
+                let peer = ctx.peer().map(String::from);
+                events.publish_peer_error(height, round, peer, e.clone());
+                function.report_error(ctx, e);
+                return;
+            }
 
-        tokio::spawn(async move {
-            if let Err(e) = check_current_block(
-                ctx,
-                function,
+            node_log!(debug, scope, "Mlm: state check block {}", true);
+            let resp = VerifyResp {
                 height,
                 round,
-                hash.clone(),
-                block,
-                resp_tx,
-            )
-            .await
-            {
-                error!("Mlm: state check block failed: {:?}", e);
+                block_hash: hash,
+                is_pass: true,
+            };
+            if let Err(e) = resp_tx.unbounded_send(resp) {
+                node_log!(
+                    error,
+                    scope,
+                    "Mlm: state send check block response failed: {:?}",
+                    e
+                );
             }
-        });
+        }));
+    }
+
+    /// The timestamp of a proposal of self, which is later than the median time of the recent
+    /// commits even if the local clock is behind it. None if the timestamps are disabled.
+    fn proposal_timestamp(&self) -> Option<u64> {
+        (self.config.timestamp_drift > 0)
+            .then(|| now_millis().max(self.median_time.min_timestamp()))
+    }
+
+    /// Check the timestamp of a proposal of the current height against the local clock and
+    /// the median time of the recent commits. A proposal of an invalid timestamp is dropped
+    /// and reported to the adapter.
+    fn check_timestamp(
+        &self,
+        ctx: &Context,
+        signed_proposal: &SignedProposal<T>,
+    ) -> bool {
+        let drift = self.config.timestamp_drift;
+        if drift == 0 {
+            return true;
+        }
+
+        let reason = match signed_proposal.proposal.timestamp {
+            None => "No timestamp".to_string(),
+            Some(timestamp) if timestamp > now_millis().saturating_add(drift) => {
+                format!("Timestamp {} ahead of the clock", timestamp)
+            }
+            Some(timestamp) if timestamp < self.median_time.min_timestamp() => format!(
+                "Timestamp {} not after the median time {:?}",
+                timestamp,
+                self.median_time.median()
+            ),
+            Some(_) => return true,
+        };
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state drop a signed proposal of {:?}, {}, id {}",
+            hex_encode(&signed_proposal.proposal.proposer),
+            reason,
+            self.correlation_id()
+        );
+        self.function.report_invalid_msg(
+            ctx.clone(),
+            ctx.peer().map(String::from),
+            "Signed Proposal".to_string(),
+            reason,
+        );
+        false
+    }
+
+    /// Hand the block of a proposal of the current height to `Consensus::pre_check_block()`
+    /// as soon as the proposal arrives, including the one of a later round which is cached.
+    /// The proposals of the higher heights are not verified yet, so they are pre-checked as
+    /// they are re-checked in their height.
+    fn pre_check_block(&mut self, ctx: Context, signed_proposal: &SignedProposal<T>) {
+        let proposal = &signed_proposal.proposal;
+        if proposal.height != self.height
+            || !self.pre_checked.insert(proposal.block_hash.clone())
+        {
+            return;
+        }
+
+        let attrs = SpanAttrs::round(proposal.height, proposal.round)
+            .with_hash(proposal.block_hash.clone())
+            .with_from(proposal.proposer.clone());
+        let ctx = self.span(ctx, "mlm.pre_check_block", attrs);
+        self.function.pre_check_block(
+            ctx,
+            proposal.height,
+            proposal.block_hash.clone(),
+            &proposal.content,
+        );
+    }
+
+    fn wal_info(&self, step: Step, lock: Option<WalLock<T>>) -> WalInfo<T> {
+        WalInfo {
+            height: self.height,
+            round: self.round,
+            step,
+            from: self.update_from_where.clone(),
+            lock,
+            status: Some(self.current_status()),
+            pending_commit: self.pending_commit.as_ref().map(|p| p.lock.clone()),
+        }
+    }
+
+    /// Encode a wal record of the kind, which is encrypted if there is a cipher.
+    fn wal_record<E: WalPayload>(
+        &self,
+        kind: WalRecordKind,
+        item: &E,
+    ) -> Result<Bytes, Box<dyn Error + Send>> {
+        let record = encode_record(item, self.wire_codec.as_ref(), &self.config.run_id)
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        seal_record(kind, record, self.wal_cipher.as_deref())
+    }
+
+    /// Decode a loaded wal record of the kind, which is decrypted first if it is encrypted.
+    fn decode_wal_record<D: WalPayload>(
+        &self,
+        kind: WalRecordKind,
+        record: Bytes,
+    ) -> Result<D, String> {
+        let record = open_record(
+            kind,
+            record,
+            self.wal_cipher.as_deref(),
+            self.wal_migration.as_deref(),
+        )?;
+        decode_record(
+            kind,
+            record.as_ref(),
+            self.wire_codec.as_ref(),
+            self.wal_migration.as_deref(),
+            Some(&self.config.run_id),
+        )
     }
 
     async fn save_wal(
@@ -1653,28 +6261,239 @@ where
         step: Step,
         lock: Option<WalLock<T>>,
     ) -> ConsensusResult<()> {
-        let wal_info = WalInfo {
+        let wal_info = self.wal_info(step.clone(), lock);
+
+        let res = match self.wal_record(WalRecordKind::WalInfo, &wal_info) {
+            Ok(record) if wal_info.lock.is_some() || wal_info.pending_commit.is_some() => {
+                self.wal.save_durable(record).await
+            }
+            Ok(record) => self.wal.save(record).await,
+            Err(e) => Err(e),
+        };
+        res.map_err(|e| {
+            node_log!(error, self.log_scope(), "Mlm: state save wal error {:?}", e);
+            ConsensusError::SaveWalErr {
+                height: self.height,
+                round: self.round,
+                step: step.to_string(),
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Save a checkpoint of the new height if it is a multiple of the checkpoint interval. A
+    /// failure is only logged since the wal information of the height is saved already.
+    async fn save_checkpoint(&mut self) {
+        let interval = self.config.checkpoint_interval;
+        if interval == 0 || !self.height.is_multiple_of(interval) {
+            return;
+        }
+
+        let checkpoint = ConsensusSnapshot {
+            wal_info: self.wal_info(Step::Propose, None),
+            last_signed: self.last_signed.clone(),
+            qcs: self.height_qcs(),
+        };
+        let res = match self.wal_record(WalRecordKind::Checkpoint, &checkpoint) {
+            Ok(record) => self.wal.save_checkpoint(record).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            node_log!(
+                error,
+                self.log_scope(),
+                "Mlm: state save checkpoint of height {} error {:?}",
+                self.height,
+                e
+            );
+            return;
+        }
+        self.append_step(StepRecord::Checkpoint {
+            height: self.height,
+        })
+        .await;
+    }
+
+    /// Append a step record to the wal. A failure is only logged since the recovery never
+    /// reads the records.
+    async fn append_step(&self, step: StepRecord) {
+        let res = match self.wal_record(WalRecordKind::Step, &step) {
+            Ok(record) => self.wal.append_step(record).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            node_log!(
+                error,
+                self.log_scope(),
+                "Mlm: state append the {} error {:?}",
+                step,
+                e
+            );
+        }
+    }
+
+    /// Check the message to sign against the last signed one, and save the record of it before
+    /// signing. A message that conflicts with the record is refused, so the node never signs
+    /// two messages of the same height, round and step even if it restarts with a stale wal.
+    async fn save_last_signed(
+        &mut self,
+        step: Step,
+        hash: &Hash,
+    ) -> ConsensusResult<()> {
+        let next = LastSigned {
             height: self.height,
             round: self.round,
-            step: step.clone(),
-            from: self.update_from_where.clone(),
-            lock,
+            step,
+            hash: hash.clone(),
         };
 
-        self.wal
-            .save(Bytes::from(rlp::encode(&wal_info)))
-            .await
-            .map_err(|e| {
-                error!("Mlm: state save wal error {:?}", e);
-                ConsensusError::SaveWalErr {
-                    height: self.height,
-                    round: self.round,
-                    step: step.to_string(),
-                }
+        if let Some(last) = self.last_signed.as_ref() {
+            let update = last.check(&next).map_err(|e| {
+                ConsensusError::DoubleSignErr(format!("refuse to sign {}, {}", next, e))
             })?;
+            if !update {
+                return Ok(());
+            }
+        }
+
+        let res = match self.wal_record(WalRecordKind::LastSigned, &next) {
+            Ok(record) => self.wal.save_last_signed(record).await,
+            Err(e) => Err(e),
+        };
+        res.map_err(|e| {
+            node_log!(
+                error,
+                self.log_scope(),
+                "Mlm: state save last signed error {:?}",
+                e
+            );
+            ConsensusError::SaveWalErr {
+                height: self.height,
+                round: self.round,
+                step: next.step.to_string(),
+            }
+        })?;
+        self.last_signed = Some(next);
         Ok(())
     }
 
+    /// Save the proof to the proof store if there is one. A failure is only logged since the
+    /// proof is also delivered by the `commit()` interface.
+    async fn save_proof(&self, proof: &Proof) {
+        if let Some(store) = self.proof_store.as_ref() {
+            if let Err(e) = store.save(proof.clone()).await {
+                node_log!(
+                    error,
+                    self.log_scope(),
+                    "Mlm: state save proof of height {} error {:?}",
+                    proof.height,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Save the handover proof of the committed height after its proof, if the height is the
+    /// last one of an epoch. A failure is only logged, like the one of saving the proof.
+    async fn save_handover(&self, proof: &Proof) {
+        let next = match self.next_epoch.as_ref() {
+            Some(next) if next.height == proof.height + 1 => next,
+            _ => return,
+        };
+
+        if let Some(store) = self.proof_store.as_ref() {
+            let handover = HandoverProof {
+                proof: proof.clone(),
+                next_authority_list: next.authority_list.clone(),
+            };
+            if let Err(e) = store.save_handover(handover).await {
+                node_log!(
+                    error,
+                    self.log_scope(),
+                    "Mlm: state save handover proof of height {} error {:?}",
+                    proof.height,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Archive the proof for the handlers, and save the archive to the wal if it is
+    /// persisted. A failure of saving is only logged, like the one of saving the proof.
+    async fn archive_proof(&self, proof: &Proof) {
+        if !self.proof_archive.is_enabled() {
+            return;
+        }
+
+        self.proof_archive.insert(proof.clone());
+        if !self.config.persist_proof_archive {
+            return;
+        }
+        let proofs = self.proof_archive.proofs();
+        let res = match self.wal_record(WalRecordKind::ProofArchive, &proofs) {
+            Ok(record) => self.wal.save_proof_archive(record).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            node_log!(
+                error,
+                self.log_scope(),
+                "Mlm: state save proof archive of height {} error {:?}",
+                proof.height,
+                e
+            );
+        }
+    }
+
+    /// Save the signed votes collected in the round to the wal if they are persisted and the
+    /// round is the current one. A failure is only logged, like the one of saving the proof
+    /// archive.
+    async fn save_round_votes(&self, height: u64, round: u64) {
+        if !self.config.persist_round_votes
+            || (height, round) != (self.height, self.round)
+        {
+            return;
+        }
+
+        let votes = self.votes.get_round_votes(height, round);
+        let res = match self.wal_record(WalRecordKind::RoundVotes, &votes) {
+            Ok(record) => self.wal.save_round_votes(record).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            node_log!(
+                error,
+                self.log_scope(),
+                "Mlm: state save votes of height {}, round {} error {:?}",
+                height,
+                round,
+                e
+            );
+        }
+    }
+
+    /// Export the collected votes of the committed height to the vote sink if there is one. A
+    /// failure is only logged, like the one of saving the proof.
+    async fn export_votes(&self, proof: &Proof) {
+        if let Some(sink) = self.vote_sink.as_ref() {
+            let (prevotes, precommits) = self.votes.get_height_vote_set(proof.height);
+            let votes = VoteSet {
+                proof: proof.clone(),
+                prevotes,
+                precommits,
+            };
+            if let Err(e) = sink.export(votes).await {
+                node_log!(
+                    error,
+                    self.log_scope(),
+                    "Mlm: state export votes of height {} error {:?}",
+                    proof.height,
+                    e
+                );
+            }
+        }
+    }
+
     async fn save_wal_with_lock_round(
         &mut self,
         step: Step,
@@ -1687,7 +6506,7 @@ where
             {
                 let block =
                     self.hash_with_block.get(&qc.block_hash).ok_or_else(|| {
-                        ConsensusError::Other("lose whole block".to_string())
+                        ConsensusError::StateErr("lose whole block".to_string())
                     })?;
 
                 Some(WalLock {
@@ -1696,7 +6515,7 @@ where
                     content: block.clone(),
                 })
             } else {
-                return Err(ConsensusError::Other("no qc".to_string()));
+                return Err(ConsensusError::StateErr("no qc".to_string()));
             }
         } else {
             None
@@ -1706,7 +6525,27 @@ where
         Ok(())
     }
 
-    fn wal_lost(&mut self) -> ConsensusResult<()> {
+    /// The status currently applied to the state, which is saved in the wal so that a restarted
+    /// node resumes with it.
+    fn current_status(&self) -> Status {
+        Status {
+            height: self.height,
+            interval: Some(self.block_interval),
+            timer_config: self.duration_config.clone(),
+            authority_list: self.authority.get_authority_list(),
+            next_authority_list: self
+                .next_epoch
+                .as_ref()
+                .filter(|next| next.height == self.height + 1)
+                .map(|next| next.authority_list.clone()),
+            proposer_seed: Some(self.authority.proposer_seed()),
+            skipped_proposers: self.authority.skipped_proposers().to_vec(),
+        }
+    }
+
+    /// Reset the SMR to the propose step of the current round, when the wal is lost or the
+    /// height is restarted.
+    fn reset_smr(&mut self) -> ConsensusResult<()> {
         let smr_base = SMRBase {
             height: self.height,
             round: self.round,
@@ -1725,35 +6564,133 @@ where
         })
     }
 
+    /// Wait for the startup delay and the readiness gate of the config before the first step.
+    async fn wait_ready(&self) {
+        if self.config.startup_delay > 0 {
+            sleep(Duration::from_millis(self.config.startup_delay)).await;
+        }
+        if !self.config.ready_gate {
+            return;
+        }
+
+        let wait = async {
+            loop {
+                let ctx = self.correlation_id().attach_to(Context::new());
+                let authority_list = self.authority.get_authority_list();
+                if self.function.network_ready(ctx, authority_list).await {
+                    break;
+                }
+                sleep(READY_POLL_INTERVAL).await;
+            }
+        };
+
+        if self.config.ready_timeout == 0 {
+            wait.await;
+        } else if timeout(Duration::from_millis(self.config.ready_timeout), wait)
+            .await
+            .is_err()
+        {
+            node_log!(
+                warn,
+                self.log_scope(),
+                "Mlm: state start before the network is ready, ready timeout {}ms",
+                self.config.ready_timeout
+            );
+            return;
+        }
+        node_log!(info, self.log_scope(), "Mlm: state network ready");
+    }
+
     async fn start_with_wal(&mut self) -> ConsensusResult<()> {
+        self.last_signed = self.load_last_signed().await?;
+        let checkpoint = self.load_checkpoint().await;
+        if let Some(last_signed) =
+            checkpoint.as_ref().and_then(|c| c.last_signed.as_ref())
+        {
+            let newer = self
+                .last_signed
+                .as_ref()
+                .is_none_or(|last| last.check(last_signed) == Ok(true));
+            if newer {
+                self.last_signed = Some(last_signed.clone());
+            }
+        }
+        self.load_proof_archive().await;
+
         if !self.consensus_power {
             return Ok(());
         }
 
-        let wal_info = self.load_wal().await?;
+        let wal_info = match self.load_wal().await {
+            Ok(info) => info,
+            Err(e) if checkpoint.is_some() => {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state load wal error {:?}, recover from the checkpoint",
+                    e
+                );
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        let wal_info = match (wal_info, checkpoint) {
+            (Some(info), Some(checkpoint)) if info.height >= checkpoint.height() => {
+                Some(info)
+            }
+            (_, Some(checkpoint)) => {
+                node_log!(
+                    info,
+                    self.log_scope(),
+                    "Mlm: state recover from the checkpoint of height {}",
+                    checkpoint.height()
+                );
+                for qc in checkpoint.qcs {
+                    self.votes.set_qc(qc);
+                }
+                Some(checkpoint.wal_info)
+            }
+            (info, None) => info,
+        };
         if wal_info.is_none() {
             if self.height != INIT_HEIGHT {
-                return self.wal_lost();
+                return self.reset_smr();
             } else {
                 return Ok(());
             }
         }
 
         let wal_info = wal_info.unwrap();
-        info!("mlm: start from wal {}", wal_info);
+        node_log!(info, self.log_scope(), "mlm: start from wal {}", wal_info);
+
+        // The pipelined commit of the last height had not returned, so the commit is delivered
+        // again from the commit step of that height.
+        let wal_info = match wal_info.pending_commit.clone() {
+            Some(lock) => WalInfo {
+                height: lock.lock_votes.height,
+                round: lock.lock_round,
+                step: Step::Commit,
+                from: UpdateFrom::PrecommitQC(lock.lock_votes.clone()),
+                lock: Some(lock),
+                status: None,
+                pending_commit: None,
+            },
+            None => wal_info,
+        };
 
         // recover basic state
         self.height = wal_info.height;
         self.round = wal_info.round;
         self.is_leader = self.is_proposer()?;
         self.update_from_where = wal_info.from.clone();
+        if let Some(status) = wal_info.status.as_ref() {
+            self.schedule_epoch(status);
+        }
 
-        // recover lock state
-        if wal_info.lock.is_some() {
-            let lock = wal_info.lock.clone().unwrap();
-            let qc = lock.lock_votes.clone();
-            self.votes.set_qc(qc.clone());
-            self.hash_with_block.insert(qc.block_hash, lock.content);
+        // Recover the lock state before any message is handled, so that the node keeps the
+        // lock across the restart.
+        if let Some(lock) = wal_info.lock.clone() {
+            self.recover_lock(lock, &wal_info.step).await?;
         }
 
         if wal_info.step == Step::Commit {
@@ -1777,6 +6714,40 @@ where
             height: self.height,
             wal_info: Some(wal_info.into_smr_base()),
         })?;
+        self.restore_round_votes().await;
+        Ok(())
+    }
+
+    /// Restore the QC and the block of the lock. The locked block is checked again unless the
+    /// height is to be committed, since the result of the check is lost with the restart and
+    /// the block can not be committed without it.
+    async fn recover_lock(
+        &mut self,
+        lock: WalLock<T>,
+        step: &Step,
+    ) -> ConsensusResult<()> {
+        lock.check(self.height, self.round, step)
+            .map_err(ConsensusError::LoadWalErr)?;
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state recover lock round {}, hash {:?}",
+            lock.lock_round,
+            hex_encode(lock.lock_votes.block_hash.clone())
+        );
+
+        let hash = lock.lock_votes.block_hash.clone();
+        self.votes.set_qc(lock.lock_votes);
+        self.hash_with_block
+            .insert(hash.clone(), lock.content.clone());
+        if *step != Step::Commit {
+            let proposer = self
+                .authority
+                .get_proposer(self.height, lock.lock_round)
+                .unwrap_or_default();
+            self.check_block(Context::new(), proposer, hash, lock.content, None)
+                .await;
+        }
         Ok(())
     }
 
@@ -1791,14 +6762,158 @@ where
             return Ok(None);
         }
 
-        let info: WalInfo<T> = rlp::decode(tmp.unwrap().as_ref())
-            .map_err(|e| ConsensusError::LoadWalErr(e.to_string()))?;
+        let info: WalInfo<T> = self
+            .decode_wal_record(WalRecordKind::WalInfo, tmp.unwrap())
+            .map_err(ConsensusError::LoadWalErr)?;
         Ok(Some(info))
     }
 
+    /// Load the latest checkpoint. A failure is only logged since the checkpoint is only
+    /// needed if the wal information is lost.
+    async fn load_checkpoint(&self) -> Option<ConsensusSnapshot<T>> {
+        let record = match self.wal.load_checkpoint().await {
+            Ok(record) => record?,
+            Err(e) => {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state load checkpoint error {:?}",
+                    e
+                );
+                return None;
+            }
+        };
+
+        self.decode_wal_record(WalRecordKind::Checkpoint, record)
+            .map_err(|e| {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state load checkpoint error {}",
+                    e
+                )
+            })
+            .ok()
+    }
+
+    /// Restore the archive of the commit proofs saved to the wal. A failure is only logged,
+    /// and the archive is refilled by the next commits.
+    async fn load_proof_archive(&self) {
+        if !self.proof_archive.is_enabled() || !self.config.persist_proof_archive {
+            return;
+        }
+
+        let record = match self.wal.load_proof_archive().await {
+            Ok(Some(record)) => record,
+            Ok(None) => return,
+            Err(e) => {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state load proof archive error {:?}",
+                    e
+                );
+                return;
+            }
+        };
+        match self.decode_wal_record(WalRecordKind::ProofArchive, record) {
+            Ok(proofs) => self.proof_archive.restore(proofs),
+            Err(e) => node_log!(
+                warn,
+                self.log_scope(),
+                "Mlm: state load proof archive error {}",
+                e
+            ),
+        }
+    }
+
+    /// Restore the signed votes of the recovered round saved to the wal, which are handled as
+    /// the received ones, so the QC is formed as soon as the votes after the restart reach
+    /// the quorum. A failure is only logged, and the round goes on without the votes.
+    async fn restore_round_votes(&mut self) {
+        if !self.config.persist_round_votes {
+            return;
+        }
+
+        let record = match self.wal.load_round_votes().await {
+            Ok(Some(record)) => record,
+            Ok(None) => return,
+            Err(e) => {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state load round votes error {:?}",
+                    e
+                );
+                return;
+            }
+        };
+        let votes: Vec<SignedVote> =
+            match self.decode_wal_record(WalRecordKind::RoundVotes, record) {
+                Ok(votes) => votes,
+                Err(e) => {
+                    node_log!(
+                        warn,
+                        self.log_scope(),
+                        "Mlm: state load round votes error {}",
+                        e
+                    );
+                    return;
+                }
+            };
+
+        // The votes of the other rounds are stale, since the round has moved on before the
+        // restart.
+        let votes = votes
+            .into_iter()
+            .filter(|vote| {
+                vote.get_height() == self.height && vote.get_round() == self.round
+            })
+            .collect::<Vec<_>>();
+        if votes.is_empty() {
+            return;
+        }
+        node_log!(
+            info,
+            self.log_scope(),
+            "Mlm: state restore {} votes of height {}, round {}",
+            votes.len(),
+            self.height,
+            self.round
+        );
+        for vote in votes {
+            if let Err(e) = self.handle_signed_vote(Context::new(), vote).await {
+                node_log!(
+                    warn,
+                    self.log_scope(),
+                    "Mlm: state restore vote error {:?}",
+                    e
+                );
+            }
+        }
+    }
+
+    async fn load_last_signed(&self) -> ConsensusResult<Option<LastSigned>> {
+        let tmp = self
+            .wal
+            .load_last_signed()
+            .await
+            .map_err(|e| ConsensusError::LoadWalErr(e.to_string()))?;
+
+        tmp.map(|info| {
+            self.decode_wal_record(WalRecordKind::LastSigned, info)
+                .map_err(ConsensusError::LoadWalErr)
+        })
+        .transpose()
+    }
+
     /// When block hash is empty, return true directly.
     fn try_get_full_txs(&self, hash: &Hash) -> bool {
-        debug!("Mlm: state check if get full transcations");
+        node_log!(
+            debug,
+            self.log_scope(),
+            "Mlm: state check if get full transcations"
+        );
         if hash.is_empty() {
             return true;
         } else if let Some(res) = self.is_full_transcation.get(hash) {
@@ -1826,15 +6941,22 @@ where
                 UpdateFrom::PrecommitQC(qc)
             }
 
-            FromWhere::ChokeQC(round) => {
-                let qc = self.chokes.get_qc(round).ok_or_else(|| {
-                    ConsensusError::BrakeErr(format!(
+            FromWhere::ChokeQC(round) => match self.chokes.get_qc(round) {
+                Some(qc) => UpdateFrom::ChokeQC(qc),
+                // The round changed on the timeout or by a quorum ahead without a choke QC
+                // keeps the QC that the node updated from before.
+                None if self.timer_config.is_choke_free()
+                    || self.moved_by_quorum(round) =>
+                {
+                    return Ok(())
+                }
+                None => {
+                    return Err(ConsensusError::BrakeErr(format!(
                         "no choke qc height {} round {}",
                         self.height, round
-                    ))
-                })?;
-                UpdateFrom::ChokeQC(qc)
-            }
+                    )))
+                }
+            },
         };
         self.update_from_where = update_from;
         Ok(())
@@ -1854,14 +6976,18 @@ where
         if self.filter_message(height, round) {
             return Ok(true);
         }
+        self.pre_check_block(ctx.clone(), signed_proposal);
 
         // If the proposal height is higher than the current height or proposal height is
         // equal to the current height and the proposal round is ne the current round, cache it
         // until that height.
         if (height == self.height && round != self.round) || height > self.height {
-            debug!(
+            node_log!(
+                debug,
+                self.log_scope(),
                 "Mlm: state receive a future signed proposal, height {}, round {}",
-                height, round,
+                height,
+                round,
             );
             self.proposals
                 .insert(ctx, height, round, signed_proposal.clone())?;
@@ -1872,21 +6998,31 @@ where
 
     fn filter_message(&self, height: u64, round: u64) -> bool {
         if height < self.height || (height == self.height && round < self.round) {
-            debug!(
+            node_log!(
+                debug,
+                self.log_scope(),
                 "Mlm: state receive an outdated message height {}, self height {}",
-                height, self.height
+                height,
+                self.height
             );
             return true;
         } else if self.height + FUTURE_HEIGHT_GAP < height {
-            debug!(
+            node_log!(
+                debug,
+                self.log_scope(),
                 "Mlm: state receive a future message height {}, self height {}",
-                height, self.height
+                height,
+                self.height
             );
             return true;
         } else if (height == self.height && self.round + FUTURE_ROUND_GAP < round)
             || (height > self.height && round > FUTURE_ROUND_GAP)
         {
-            debug!("Mlm: state receive a much higher round message");
+            node_log!(
+                debug,
+                self.log_scope(),
+                "Mlm: state receive a much higher round message"
+            );
             return true;
         }
 
@@ -1894,31 +7030,44 @@ where
     }
 }
 
-#[tracing_span(kind = "mlm", tags = "{'height': 'height', 'round': 'round'}")]
-async fn check_current_block<U: Consensus<T>, T: Codec>(
-    ctx: Context,
-    function: Arc<U>,
+/// The hash of the block computed by the adapter and the digest of the encoded block, if the
+/// computed hash differs from the hash of its proposal. The digest is empty if the block fails
+/// to encode.
+fn block_hash_mismatch<T: Codec, F: Consensus<T>, C: Crypto>(
+    function: &F,
+    crypto: &C,
     height: u64,
-    round: u64,
-    hash: Hash,
-    block: T,
-    tx: UnboundedSender<VerifyResp>,
-) -> ConsensusResult<()> {
-    function
-        .check_block(ctx, height, hash.clone(), block)
-        .await
-        .map_err(|err| {
-            ConsensusError::Other(format!("check {} block error {:?}", height, err))
-        })?;
+    hash: &Hash,
+    block: &T,
+) -> Option<(Hash, Hash)> {
+    let computed = function.block_hash(height, block)?;
+    if &computed == hash {
+        return None;
+    }
+    let digest = block
+        .encode()
+        .map_or_else(|_| Hash::new(), |bytes| crypto.hash(bytes));
+    Some((computed, digest))
+}
 
-    debug!("Mlm: state check block {}", true);
-    tx.unbounded_send(VerifyResp {
-        height,
-        round,
-        block_hash: hash,
-        is_pass: true,
-    })
-    .map_err(|e| ConsensusError::ChannelErr(e.to_string()))
+/// Restore the signed proposal of a proposal without the block by the encoded block.
+fn restore_block<T: Codec>(
+    scope: LogScope,
+    cp: CompactProposal,
+    content: Bytes,
+) -> Option<SignedProposal<T>> {
+    match T::decode(content) {
+        Ok(block) => Some(cp.into_signed(block)),
+        Err(e) => {
+            node_log!(
+                warn,
+                scope,
+                "Mlm: state decode restored block error {:?}",
+                e
+            );
+            None
+        }
+    }
 }
 
 fn mock_init_qc() -> AggregatedVote {
@@ -1934,5 +7083,6 @@ fn mock_init_qc() -> AggregatedVote {
         round: 0u64,
         block_hash: Hash::default(),
         leader: Address::default(),
+        extensions: Vec::new(),
     }
 }
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Address, ViewChangeReason};
+
+/// The cumulative counters of the protocol since the instance starts, which explorers can
+/// display as the consensus health.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProtocolStats {
+    /// The number of the committed heights.
+    pub heights_committed: u64,
+    /// The number of the rounds run, including the first round of each height.
+    pub rounds: u64,
+    /// The number of the view changes keyed by the name of the reason.
+    pub view_changes: BTreeMap<String, u64>,
+    /// The average number of the rounds that a committed height costs, zero before the first
+    /// commit.
+    pub average_rounds_per_height: f64,
+    /// The number of the rounds that the node brakes in.
+    pub brakes: u64,
+    /// The total milliseconds that the node stays in the brake step, from its first choke of
+    /// a round until it leaves the round.
+    pub brake_millis: u64,
+    /// The max milliseconds that the node stays in the brake step of a round.
+    pub max_brake_millis: u64,
+    /// The number of the escalations of the heights running `MlmConfig::max_rounds` rounds.
+    pub round_escalations: u64,
+}
+
+/// A brake of a round, reported by `Consensus::report_brake()` when the node leaves the
+/// round, which tells how long the brake timeouts of `TimerConfig` keep the node choked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BrakeReport {
+    /// The height of the brake.
+    pub height: u64,
+    /// The round of the brake.
+    pub round: u64,
+    /// The time from the first choke of the node in the round until it leaves the round.
+    pub duration: Duration,
+    /// The authorities whose chokes of the round are collected, including the node.
+    pub chokers: Vec<Address>,
+    /// The vote weight of the chokers.
+    pub choke_weight: u128,
+    /// Whether the QC of the chokes of the round is collected, otherwise the brake is
+    /// released by a higher QC or a new height.
+    pub choke_qc: bool,
+}
+
+/// The halt of the consensus escalated by `Consensus::on_consensus_halted()` when the node
+/// brakes `MlmConfig::max_brake_rounds` rounds in a row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HaltReport {
+    /// The current height.
+    pub height: u64,
+    /// The current round, which the node brakes in.
+    pub round: u64,
+    /// The number of the brake rounds in a row, including the brake timeouts of a round.
+    pub brake_rounds: u64,
+    /// The time since the first of the brake rounds.
+    pub halted_for: Duration,
+}
+
+/// A height escalated by `Consensus::on_max_rounds()` when the node enters a round of a
+/// multiple of `MlmConfig::max_rounds` without a commit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundLimitReport {
+    /// The current height.
+    pub height: u64,
+    /// The round that the node enters.
+    pub round: u64,
+    /// The number of the multiples of `MlmConfig::max_rounds` that the rounds of the height
+    /// reach, which is the number of the escalations of the height if no round is skipped.
+    pub escalations: u64,
+    /// The time since the node entered the height.
+    pub stalled_for: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    stats: ProtocolStats,
+    committed_rounds: u64,
+    last_brake: Option<(u64, u64)>,
+}
+
+/// The protocol stats, which are updated by the state and read by the instance and the
+/// handlers.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProtocolAccount(Arc<Mutex<Counters>>);
+
+impl ProtocolAccount {
+    pub(crate) fn new() -> Self {
+        ProtocolAccount::default()
+    }
+
+    pub(crate) fn stats(&self) -> ProtocolStats {
+        self.0.lock().stats.clone()
+    }
+
+    pub(crate) fn on_new_round(&self) {
+        self.0.lock().stats.rounds += 1;
+    }
+
+    pub(crate) fn on_view_change(&self, reason: &ViewChangeReason) {
+        let mut counters = self.0.lock();
+        *counters
+            .stats
+            .view_changes
+            .entry(reason_name(reason).to_string())
+            .or_default() += 1;
+    }
+
+    /// Count a brake, the brakes repeated in the same round are one episode.
+    pub(crate) fn on_brake(&self, height: u64, round: u64) {
+        let mut counters = self.0.lock();
+        if counters.last_brake != Some((height, round)) {
+            counters.last_brake = Some((height, round));
+            counters.stats.brakes += 1;
+        }
+    }
+
+    /// Count the time that the node stays in the brake step of a round.
+    pub(crate) fn on_brake_end(&self, elapsed: Duration) {
+        let millis = elapsed.as_millis() as u64;
+        let mut counters = self.0.lock();
+        counters.stats.brake_millis = counters.stats.brake_millis.saturating_add(millis);
+        counters.stats.max_brake_millis = counters.stats.max_brake_millis.max(millis);
+    }
+
+    pub(crate) fn on_round_escalation(&self) {
+        self.0.lock().stats.round_escalations += 1;
+    }
+
+    /// Count a height committed in the given round.
+    pub(crate) fn on_commit(&self, round: u64) {
+        let mut counters = self.0.lock();
+        counters.committed_rounds += round + 1;
+        counters.stats.heights_committed += 1;
+        counters.stats.average_rounds_per_height =
+            counters.committed_rounds as f64 / counters.stats.heights_committed as f64;
+    }
+}
+
+fn reason_name(reason: &ViewChangeReason) -> &'static str {
+    match reason {
+        ViewChangeReason::NoProposalFromNetwork => "NoProposalFromNetwork",
+        ViewChangeReason::NoPrevoteQCFromNetwork => "NoPrevoteQCFromNetwork",
+        ViewChangeReason::NoPrecommitQCFromNetwork => "NoPrecommitQCFromNetwork",
+        ViewChangeReason::CheckBlockNotPass => "CheckBlockNotPass",
+        ViewChangeReason::UpdateFromHigherPrevoteQC(..) => "UpdateFromHigherPrevoteQC",
+        ViewChangeReason::UpdateFromHigherPrecommitQC(..) => {
+            "UpdateFromHigherPrecommitQC"
+        }
+        ViewChangeReason::UpdateFromHigherChokeQC(..) => "UpdateFromHigherChokeQC",
+        ViewChangeReason::LeaderReceivedVoteBelowThreshold(..) => {
+            "LeaderReceivedVoteBelowThreshold"
+        }
+        ViewChangeReason::Others => "Others",
+        ViewChangeReason::OperatorTriggered(_) => "OperatorTriggered",
+        ViewChangeReason::QuorumAtHigherRound(..) => "QuorumAtHigherRound",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::ProtocolAccount;
+    use crate::types::{ViewChangeReason, VoteType};
+
+    #[test]
+    fn test_protocol_stats() {
+        let account = ProtocolAccount::new();
+        account.on_new_round();
+        account.on_commit(0);
+
+        account.on_new_round();
+        account.on_view_change(&ViewChangeReason::NoProposalFromNetwork);
+        account.on_new_round();
+        account.on_brake(2, 1);
+        account.on_brake(2, 1);
+        account.on_brake_end(Duration::from_millis(300));
+        account.on_view_change(&ViewChangeReason::LeaderReceivedVoteBelowThreshold(
+            VoteType::Prevote,
+        ));
+        account.on_new_round();
+        account.on_round_escalation();
+        account.on_commit(2);
+
+        let stats = account.stats();
+        assert_eq!(stats.heights_committed, 2);
+        assert_eq!(stats.rounds, 4);
+        assert_eq!(stats.brakes, 1);
+        assert_eq!((stats.brake_millis, stats.max_brake_millis), (300, 300));
+        assert_eq!(stats.average_rounds_per_height, 2.0);
+        assert_eq!(stats.round_escalations, 1);
+        assert_eq!(
+            serde_json::to_string(&stats).unwrap(),
+            r#"{"heights_committed":2,"rounds":4,"view_changes":{"LeaderReceivedVoteBelowThreshold":1,"NoProposalFromNetwork":1},"average_rounds_per_height":2.0,"brakes":1,"brake_millis":300,"max_brake_millis":300,"round_escalations":1}"#
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::types::{AggregatedVote, Hash, VoteType};
+
+/// The counters of the QCs learned from the network and aggregated by the node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QcGossipStats {
+    /// The QCs aggregated by the node from the votes.
+    pub aggregated: u64,
+    /// The QCs first learned from the network.
+    pub learned: u64,
+    /// The QCs re-broadcast by the node.
+    pub gossiped: u64,
+    /// The copies of the known QCs received from the network.
+    pub duplicated: u64,
+}
+
+/// The stats of the QC gossip, which are updated by the state and read by the instance.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct QcGossipAccount(Arc<Mutex<QcGossipStats>>);
+
+impl QcGossipAccount {
+    pub(crate) fn new() -> Self {
+        QcGossipAccount::default()
+    }
+
+    pub(crate) fn stats(&self) -> QcGossipStats {
+        self.0.lock().clone()
+    }
+}
+
+/// The QCs known by the node in the current height, keyed by the round, the type and the
+/// block hash, so each QC is re-broadcast at most once.
+#[derive(Debug, Default)]
+pub struct QcGossip {
+    height: u64,
+    known: HashSet<(u64, VoteType, Hash)>,
+    account: QcGossipAccount,
+}
+
+impl QcGossip {
+    pub fn new() -> Self {
+        QcGossip::default()
+    }
+
+    pub(crate) fn set_account(&mut self, account: QcGossipAccount) {
+        self.account = account;
+    }
+
+    /// Mark a QC aggregated by the node, which is broadcast by the node itself.
+    pub fn aggregated(&mut self, qc: &AggregatedVote) {
+        if self.insert(qc) {
+            self.account.0.lock().aggregated += 1;
+        }
+    }
+
+    /// Mark a QC received from the network, return false and count it if it is known.
+    pub fn learned(&mut self, qc: &AggregatedVote) -> bool {
+        let first = self.insert(qc);
+        let mut stats = self.account.0.lock();
+        if first {
+            stats.learned += 1;
+        } else {
+            stats.duplicated += 1;
+        }
+        first
+    }
+
+    /// Count a QC re-broadcast by the node.
+    pub fn gossiped(&self) {
+        self.account.0.lock().gossiped += 1;
+    }
+
+    fn insert(&mut self, qc: &AggregatedVote) -> bool {
+        if qc.height != self.height {
+            self.height = qc.height;
+            self.known.clear();
+        }
+        self.known
+            .insert((qc.round, qc.vote_type.clone(), qc.block_hash.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{QcGossip, QcGossipAccount, QcGossipStats};
+    use crate::types::{Address, AggregatedSignature, AggregatedVote, Hash, VoteType};
+
+    fn qc(height: u64, round: u64) -> AggregatedVote {
+        AggregatedVote {
+            signature: AggregatedSignature {
+                signature: Bytes::new(),
+                address_bitmap: Bytes::new(),
+            },
+            vote_type: VoteType::Prevote,
+            height,
+            round,
+            block_hash: Hash::from(vec![1]),
+            leader: Address::default(),
+            extensions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_qc_gossip() {
+        let account = QcGossipAccount::new();
+        let mut gossip = QcGossip::new();
+        gossip.set_account(account.clone());
+
+        gossip.aggregated(&qc(1, 0));
+        assert!(!gossip.learned(&qc(1, 0)));
+        assert!(gossip.learned(&qc(1, 1)));
+        gossip.gossiped();
+        assert!(!gossip.learned(&qc(1, 1)));
+        assert!(gossip.learned(&qc(2, 0)));
+        assert!(gossip.learned(&qc(1, 0)));
+        assert_eq!(
+            account.stats(),
+            QcGossipStats {
+                aggregated: 1,
+                learned: 3,
+                gossiped: 1,
+                duplicated: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+/// The counters of the messages dropped by the rate limit of the peers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RateLimitStats {
+    /// The messages dropped in total.
+    pub dropped: u64,
+    /// The messages dropped of each peer of the transport. The messages without a peer are
+    /// counted under the hex of their signers, and the signers out of the authority list
+    /// together under the empty string.
+    pub dropped_by_peer: HashMap<String, u64>,
+}
+
+/// The stats of the rate limit, which are updated by the state and read by the instance.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RateLimitAccount(Arc<Mutex<RateLimitStats>>);
+
+impl RateLimitAccount {
+    pub(crate) fn new() -> Self {
+        RateLimitAccount::default()
+    }
+
+    pub(crate) fn stats(&self) -> RateLimitStats {
+        self.0.lock().clone()
+    }
+}
+
+/// A token bucket rate limiter keyed by the peer that the messages arrive from. Each peer
+/// starts with a full bucket of `burst` tokens, which are refilled by `rate` tokens per
+/// second. A message takes a token, and is dropped if the bucket of its peer is empty.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: u64,
+    burst: u64,
+    buckets: HashMap<String, Bucket>,
+    account: RateLimitAccount,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter of the messages per second and the burst of each peer, zero
+    /// rate disables it. The burst is at least one.
+    pub fn new(rate: u64, burst: u64) -> Self {
+        RateLimiter {
+            rate,
+            burst: burst.max(1),
+            buckets: HashMap::new(),
+            account: RateLimitAccount::new(),
+        }
+    }
+
+    pub(crate) fn set_account(&mut self, account: RateLimitAccount) {
+        self.account = account;
+    }
+
+    /// Whether the rate limiter is enabled by a rate.
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0
+    }
+
+    /// Take a token of the peer, return false if the message should be dropped.
+    pub fn check(&mut self, peer: &str) -> bool {
+        self.check_at(peer, Instant::now())
+    }
+
+    fn check_at(&mut self, peer: &str, now: Instant) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+
+        let burst = self.burst as f64;
+        let bucket = self.buckets.entry(peer.to_string()).or_insert(Bucket {
+            tokens: burst,
+            last: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate as f64).min(burst);
+        bucket.last = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return true;
+        }
+
+        let mut stats = self.account.0.lock();
+        stats.dropped += 1;
+        *stats.dropped_by_peer.entry(peer.to_string()).or_default() += 1;
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{RateLimitAccount, RateLimiter};
+
+    #[test]
+    fn test_rate_limiter() {
+        let account = RateLimitAccount::new();
+        let mut limiter = RateLimiter::new(10, 3);
+        limiter.set_account(account.clone());
+        let (a, b) = ("peer-a", "peer-b");
+        let start = Instant::now();
+
+        // The burst of each peer is taken at once, and the bucket of one peer does not
+        // affect the other.
+        for _ in 0..3 {
+            assert!(limiter.check_at(a, start));
+        }
+        assert!(!limiter.check_at(a, start));
+        assert!(limiter.check_at(b, start));
+
+        // A token is refilled every 100 milliseconds up to the burst.
+        assert!(!limiter.check_at(a, start + Duration::from_millis(50)));
+        assert!(limiter.check_at(a, start + Duration::from_millis(100)));
+        assert!(!limiter.check_at(a, start + Duration::from_millis(150)));
+        let later = start + Duration::from_secs(10);
+        for _ in 0..3 {
+            assert!(limiter.check_at(a, later));
+        }
+        assert!(!limiter.check_at(a, later));
+
+        let stats = account.stats();
+        assert_eq!(stats.dropped, 4);
+        assert_eq!(stats.dropped_by_peer.get(a), Some(&4));
+        assert_eq!(stats.dropped_by_peer.get(b), None);
+
+        assert!(RateLimiter::new(0, 0).check_at(a, start));
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use crate::types::{Address, Node};
+use crate::utils::auth_manage::AuthorityManage;
+use crate::ConsensusResult;
+
+/// The consecutive proposal failures of the proposers, and the heights until which the
+/// repeatedly failing ones are skipped, by which an application decides the
+/// `Status::skipped_proposers`. The failures must be counted by the data that every node
+/// shares, such as the rounds of the proofs in the chain, so that the nodes skip the same
+/// proposers. The local commit proofs of the nodes may be of different rounds.
+#[derive(Debug, Default)]
+pub struct ProposerReliability {
+    max_failures: u64,
+    skip_heights: u64,
+    failures: HashMap<Address, u64>,
+    skipped_until: HashMap<Address, u64>,
+}
+
+impl ProposerReliability {
+    /// Create the account of the proposers, which skips a proposer failing `max_failures`
+    /// heights in a row in the next `skip_heights` heights. Zero `max_failures` skips no
+    /// proposer.
+    pub fn new(max_failures: u64, skip_heights: u64) -> Self {
+        ProposerReliability {
+            max_failures,
+            skip_heights,
+            ..ProposerReliability::default()
+        }
+    }
+
+    /// Count a height committed by the proof of the round. The proposers of the rounds before
+    /// the proof round failed, and the one of the proof round succeeded, in the schedule of the
+    /// authority list, the proposer seed and the proposers skipped at the height. The records
+    /// of the addresses out of the authority list are removed. Return the newly skipped
+    /// proposers.
+    pub fn commit(
+        &mut self,
+        height: u64,
+        round: u64,
+        authority_list: &[Node],
+        proposer_seed: u64,
+    ) -> ConsensusResult<Vec<Address>> {
+        let authorities = authority_list
+            .iter()
+            .map(|node| node.address.clone())
+            .collect::<Vec<_>>();
+        self.failures
+            .retain(|address, _| authorities.contains(address));
+        self.skipped_until
+            .retain(|address, _| authorities.contains(address));
+        if self.max_failures == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list.to_vec());
+        authority.set_proposer_seed(proposer_seed);
+        authority.set_skipped_proposers(self.skipped(height));
+
+        let mut failed = Vec::new();
+        for i in 0..round.min(authority.len() as u64) {
+            let proposer = authority.get_proposer(height, i)?;
+            if !failed.contains(&proposer) {
+                failed.push(proposer);
+            }
+        }
+        let proposer = authority.get_proposer(height, round)?;
+        Ok(self.count(height, &failed, &proposer))
+    }
+
+    fn count(
+        &mut self,
+        height: u64,
+        failed: &[Address],
+        proposer: &Address,
+    ) -> Vec<Address> {
+        self.failures.remove(proposer);
+        let mut skipped = Vec::new();
+        for address in failed.iter().filter(|address| *address != proposer) {
+            let failures = self.failures.entry(address.clone()).or_insert(0);
+            *failures += 1;
+            if *failures >= self.max_failures {
+                self.failures.remove(address);
+                let until = height.saturating_add(self.skip_heights);
+                self.skipped_until.insert(address.clone(), until);
+                skipped.push(address.clone());
+            }
+        }
+        self.skipped_until.retain(|_, until| *until > height);
+        skipped
+    }
+
+    /// The proposers skipped at the height, in the order of their addresses.
+    pub fn skipped(&self, height: u64) -> Vec<Address> {
+        let mut skipped = self
+            .skipped_until
+            .iter()
+            .filter(|(_, until)| **until >= height)
+            .map(|(address, _)| address.clone())
+            .collect::<Vec<_>>();
+        skipped.sort();
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProposerReliability;
+    use crate::types::{Address, Node};
+    use crate::utils::auth_manage::proposer_at;
+
+    #[test]
+    fn test_proposer_reliability() {
+        let (a, b, c) = (
+            Address::from_slice(&[1]).unwrap(),
+            Address::from_slice(&[2]).unwrap(),
+            Address::from_slice(&[3]).unwrap(),
+        );
+        let mut reliability = ProposerReliability::new(2, 3);
+        assert!(reliability
+            .count(1, std::slice::from_ref(&a), &b)
+            .is_empty());
+
+        // A success in between resets the failures.
+        assert!(reliability.count(2, &[], &a).is_empty());
+        assert!(reliability
+            .count(3, &[a.clone(), c.clone()], &b)
+            .is_empty());
+        assert_eq!(
+            reliability.count(4, std::slice::from_ref(&a), &c),
+            vec![a.clone()]
+        );
+
+        // The proposer is skipped in the next heights only.
+        assert_eq!(reliability.skipped(7), vec![a.clone()]);
+        assert!(reliability.skipped(8).is_empty());
+    }
+
+    #[test]
+    fn test_commit_by_proof_round() {
+        let authority_list = (1..=4)
+            .map(|i| Node::new(Address::from_slice(&[i]).unwrap()))
+            .collect::<Vec<_>>();
+        let mut reliability = ProposerReliability::new(1, 10);
+
+        // The proposer of the round before the proof round failed. At the height, it is the
+        // last authority by both the rotation and the random leader, and differs from the
+        // proposer of the proof round.
+        let failed = Address::from_slice(&[4]).unwrap();
+        assert_eq!(proposer_at(1, 0, &authority_list).unwrap(), failed);
+        assert_ne!(proposer_at(1, 1, &authority_list).unwrap(), failed);
+        let skipped = reliability.commit(1, 1, &authority_list, 0).unwrap();
+        assert_eq!(skipped, vec![failed.clone()]);
+        assert_eq!(reliability.skipped(2), skipped);
+
+        // The records of the authorities out of the list are removed.
+        let others = authority_list
+            .iter()
+            .filter(|node| node.address != failed)
+            .cloned()
+            .collect::<Vec<_>>();
+        reliability.commit(2, 0, &others, 0).unwrap();
+        assert!(reliability.skipped(3).is_empty());
+
+        // Zero failures skips no proposer.
+        let mut reliability = ProposerReliability::new(0, 10);
+        assert!(reliability
+            .commit(1, 3, &authority_list, 0)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::types::Address;
+use crate::utils::auth_manage::AuthorityManage;
+
+/// The highest height and round of each authority proven by the signed votes and chokes it
+/// sends. A quorum of the vote weight at or above a view means that the honest part of the
+/// quorum has moved there, so a node behind it skips its own timeouts up to that view instead
+/// of serving them out.
+#[derive(Debug, Default)]
+pub(crate) struct QuorumViews {
+    views: HashMap<Address, (u64, u64)>,
+}
+
+impl QuorumViews {
+    pub(crate) fn new() -> Self {
+        QuorumViews::default()
+    }
+
+    /// Record a signed message of the authority at the height and round, return whether its
+    /// view moves ahead.
+    pub(crate) fn record(&mut self, address: &Address, height: u64, round: u64) -> bool {
+        let view = self.views.entry(address.clone()).or_insert((0, 0));
+        if *view >= (height, round) {
+            return false;
+        }
+        *view = (height, round);
+        true
+    }
+
+    /// The highest view that a quorum of the current authorities is at or above. Views out of
+    /// the authority list neither weigh nor count.
+    pub(crate) fn quorum_view(&self, authority: &AuthorityManage) -> Option<(u64, u64)> {
+        let mut views = self
+            .views
+            .iter()
+            .filter_map(|(address, view)| {
+                let weight = authority.get_vote_weight(address).ok()?;
+                Some((*view, *weight))
+            })
+            .collect::<Vec<_>>();
+        views.sort_unstable_by_key(|(view, _)| Reverse(*view));
+
+        let mut acc = 0u128;
+        for (count, (view, weight)) in views.into_iter().enumerate() {
+            acc = acc.saturating_add(weight);
+            if authority.is_quorum(acc, count + 1) {
+                return Some(view);
+            }
+        }
+        None
+    }
+
+    /// Forget the views below the height.
+    pub(crate) fn prune(&mut self, height: u64) {
+        self.views
+            .retain(|_, (view_height, _)| *view_height >= height);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::QuorumViews;
+    use crate::test_utils::index_address;
+    use crate::types::Node;
+    use crate::utils::auth_manage::AuthorityManage;
+
+    #[test]
+    fn test_quorum_view() {
+        let mut authority = AuthorityManage::new();
+        let mut nodes = (1..=4).map(|i| Node::new(index_address(i))).collect();
+        authority.update(&mut nodes);
+        let mut views = QuorumViews::new();
+        assert_eq!(views.quorum_view(&authority), None);
+
+        assert!(views.record(&index_address(1), 2, 3));
+        assert!(views.record(&index_address(2), 2, 5));
+        assert!(!views.record(&index_address(2), 2, 4));
+        assert_eq!(views.quorum_view(&authority), None);
+
+        // A non-authority neither weighs nor counts.
+        views.record(&index_address(9), 3, 0);
+        assert_eq!(views.quorum_view(&authority), None);
+
+        // The third view is the highest one that a quorum is at or above.
+        assert!(views.record(&index_address(3), 3, 0));
+        assert_eq!(views.quorum_view(&authority), Some((2, 3)));
+        assert!(views.record(&index_address(1), 2, 6));
+        assert_eq!(views.quorum_view(&authority), Some((2, 5)));
+
+        views.prune(3);
+        assert_eq!(views.quorum_view(&authority), None);
+    }
+}
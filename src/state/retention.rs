@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+
+use crate::types::{AggregatedVote, SignedProposal, SignedVote};
+use crate::Codec;
+
+/// The proposals, the votes and the QCs of a past height evicted from the caches by
+/// `MlmConfig::height_retention`, which are handed to `Consensus::archive_height()` before they
+/// are dropped if `MlmConfig::archive_expired_heights` is enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpiredHeight<T: Codec> {
+    /// The expired height.
+    pub height: u64,
+    /// The signed proposals of the height in the order of their rounds.
+    pub proposals: Vec<SignedProposal<T>>,
+    /// The signed votes of the height in the order of the round, the type and the voter.
+    pub votes: Vec<SignedVote>,
+    /// The QCs of the height in the order of the round, the prevote QC of a round first.
+    pub qcs: Vec<AggregatedVote>,
+}
+
+/// Merge the proposals and the votes taken from the caches into the expired heights in the
+/// order of the heights.
+#[allow(clippy::type_complexity)]
+pub(crate) fn expired_heights<T: Codec>(
+    proposals: BTreeMap<u64, Vec<SignedProposal<T>>>,
+    votes: BTreeMap<u64, (Vec<SignedVote>, Vec<AggregatedVote>)>,
+) -> Vec<ExpiredHeight<T>> {
+    let mut expired = proposals
+        .into_iter()
+        .map(|(height, proposals)| {
+            let expired = ExpiredHeight {
+                height,
+                proposals,
+                votes: Vec::new(),
+                qcs: Vec::new(),
+            };
+            (height, expired)
+        })
+        .collect::<BTreeMap<_, _>>();
+    for (height, (votes, qcs)) in votes {
+        let entry = expired.entry(height).or_insert_with(|| ExpiredHeight {
+            height,
+            proposals: Vec::new(),
+            votes: Vec::new(),
+            qcs: Vec::new(),
+        });
+        entry.votes = votes;
+        entry.qcs = qcs;
+    }
+    expired.into_values().collect()
+}
@@ -0,0 +1,166 @@
+use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+
+use log::warn;
+use tokio::time::{sleep, timeout_at, Instant};
+
+use crate::config::AdapterRetry;
+use crate::error::ConsensusError;
+use crate::ConsensusResult;
+
+/// Call an adapter function until it succeeds, the attempts of the policy run out or the next
+/// retry would start after the deadline, which is the duration from now. The error of the
+/// last attempt is returned as an `AdapterErr`. The in-flight call is cancelled at the
+/// `cancel` duration from now, and an `AdapterTimeout` is returned, so a late result is never
+/// seen by the state. A `NothingToPropose` of the adapter is returned as is without a retry.
+pub(crate) async fn retry_call<R, F, Fut>(
+    policy: &AdapterRetry,
+    deadline: Duration,
+    cancel: Duration,
+    call: &str,
+    mut f: F,
+) -> ConsensusResult<R>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<R, Box<dyn Error + Send>>>,
+{
+    let now = Instant::now();
+    let (deadline, cancel) = (now + deadline, now + cancel);
+    let mut backoff = Duration::from_millis(policy.backoff);
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let err = match timeout_at(cancel, f()).await {
+            Ok(Ok(res)) => return Ok(res),
+            Ok(Err(err)) => err,
+            Err(_) => return Err(ConsensusError::AdapterTimeout(call.to_string())),
+        };
+        if let Some(ConsensusError::NothingToPropose) = err.downcast_ref() {
+            return Err(ConsensusError::NothingToPropose);
+        }
+
+        if attempts >= policy.attempts || Instant::now() + backoff >= deadline {
+            return Err(ConsensusError::AdapterErr {
+                call: call.to_string(),
+                attempts,
+                reason: format!("{:?}", err),
+            });
+        }
+
+        warn!(
+            "Mlm: state {} attempt {} error {:?}, retry in {:?}",
+            call, attempts, err, backoff
+        );
+        sleep(backoff).await;
+        backoff = backoff.saturating_mul(2);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::time::{Duration, Instant};
+
+    use tokio::time::sleep;
+
+    use super::retry_call;
+    use crate::config::AdapterRetry;
+    use crate::error::ConsensusError;
+
+    async fn flaky(
+        calls: &AtomicU32,
+        failures: u32,
+    ) -> Result<u32, Box<dyn Error + Send>> {
+        let call = calls.fetch_add(1, Ordering::SeqCst);
+        if call < failures {
+            Err(Box::new(ConsensusError::Other("unavailable".to_string())))
+        } else {
+            Ok(call)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_call() {
+        let deadline = Duration::from_millis(300);
+
+        // A single attempt by default.
+        let calls = AtomicU32::new(0);
+        let res = retry_call(
+            &AdapterRetry::default(),
+            deadline,
+            deadline,
+            "get_block",
+            || flaky(&calls, 1),
+        )
+        .await;
+        match res {
+            Err(ConsensusError::AdapterErr { call, attempts, .. }) => {
+                assert_eq!(call, "get_block");
+                assert_eq!(attempts, 1);
+            }
+            _ => panic!("expect an adapter error"),
+        }
+
+        // Succeed on the third attempt after the backoff of 10 and 20 millis.
+        let calls = AtomicU32::new(0);
+        let policy = AdapterRetry::new(3, 10, 0);
+        let start = Instant::now();
+        let res = retry_call(&policy, deadline, deadline, "get_block", || {
+            flaky(&calls, 2)
+        })
+        .await;
+        assert_eq!(res.unwrap(), 2);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        // The attempts run out.
+        let calls = AtomicU32::new(0);
+        let res = retry_call(&policy, deadline, deadline, "check_block", || {
+            flaky(&calls, 5)
+        })
+        .await;
+        assert!(matches!(
+            res,
+            Err(ConsensusError::AdapterErr { attempts: 3, .. })
+        ));
+
+        // No retry starts after the deadline.
+        let calls = AtomicU32::new(0);
+        let policy = AdapterRetry::new(10, 100, 0);
+        let res = retry_call(&policy, deadline, deadline, "check_block", || {
+            flaky(&calls, 10)
+        })
+        .await;
+        assert!(matches!(
+            res,
+            Err(ConsensusError::AdapterErr { attempts: 2, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_call() {
+        // The slow call is dropped at the deadline, and its result is never seen.
+        let done = AtomicBool::new(false);
+        let policy = AdapterRetry::new(3, 10, 0);
+        let res = retry_call(
+            &policy,
+            Duration::from_millis(300),
+            Duration::from_millis(50),
+            "get_block",
+            || async {
+                sleep(Duration::from_millis(200)).await;
+                done.store(true, Ordering::SeqCst);
+                Ok(())
+            },
+        )
+        .await;
+        assert_eq!(
+            res,
+            Err(ConsensusError::AdapterTimeout("get_block".to_string()))
+        );
+        sleep(Duration::from_millis(200)).await;
+        assert!(!done.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{AggregatedVote, Status};
+use crate::wal::{LastSigned, WalInfo};
+use crate::Codec;
+
+/// A snapshot of the consensus state of an instance, which moves a node to new hardware or
+/// duplicates it into a staging environment by `Mlm::run_from_snapshot()`, without the wal of
+/// the old node. It is serializable, so it can be shipped as JSON or any serde format. It is
+/// also the checkpoint saved by `Wal::save_checkpoint()` every
+/// `MlmConfig::checkpoint_interval` heights.
+///
+/// To migrate a validator, the old node must not sign anything after the export, such as by
+/// being stopped or cut off the network right after it, otherwise the new node may sign a
+/// message conflicting with one signed by the old node.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConsensusSnapshot<T: Codec> {
+    /// The wal info of the current height, with the round, the step, the lock and the status
+    /// that carries the authority list, the interval and the timer config.
+    pub wal_info: WalInfo<T>,
+    /// The last message signed by the node, which keeps the new node from signing a
+    /// conflicting one.
+    pub last_signed: Option<LastSigned>,
+    /// The QCs of the current height known by the node, which the new node handles once it
+    /// runs.
+    pub qcs: Vec<AggregatedVote>,
+}
+
+impl<T: Codec> ConsensusSnapshot<T> {
+    /// The height of the snapshot.
+    pub fn height(&self) -> u64 {
+        self.wal_info.height
+    }
+
+    /// The round of the snapshot.
+    pub fn round(&self) -> u64 {
+        self.wal_info.round
+    }
+
+    /// The status of the height of the snapshot.
+    pub fn status(&self) -> Option<&Status> {
+        self.wal_info.status.as_ref()
+    }
+}
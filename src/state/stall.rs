@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use hummer::coding::hex_encode;
+use tokio::time::Instant;
+
+use crate::smr::smr_types::Step;
+use crate::types::{Address, Hash};
+
+/// The state of a node that makes no height progress, which the operators compare across the
+/// nodes to find out why a cluster is wedged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StallDump {
+    /// The height that the node is stalled at.
+    pub height: u64,
+    /// The current round.
+    pub round: u64,
+    /// The time since the node entered the height.
+    pub stalled_for: Duration,
+    /// The number of the multiples of `MlmConfig::max_rounds` that the rounds of the height
+    /// reach, zero if the height is not escalated.
+    pub round_escalations: u64,
+    /// The last step that the node entered in the round.
+    pub step: Step,
+    /// The round and the block hash that the node is locked on.
+    pub lock: Option<(u64, Hash)>,
+    /// The votes of each authority in the current round collected by the node.
+    pub votes: Vec<ValidatorVotes>,
+    /// The last messages processed by the node, from the oldest.
+    pub recent_messages: Vec<MessageRecord>,
+}
+
+/// The votes of an authority in a round collected by a node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorVotes {
+    /// The address of the authority.
+    pub address: Address,
+    /// The block hash of the prevote, which is empty for a nil prevote.
+    pub prevote: Option<Hash>,
+    /// The block hash of the precommit, which is empty for a nil precommit.
+    pub precommit: Option<Hash>,
+    /// Whether the authority has choked in the round.
+    pub choked: bool,
+}
+
+/// A message processed by a node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageRecord {
+    /// The kind of the message.
+    pub kind: String,
+    /// The height of the message.
+    pub height: u64,
+    /// The round of the message.
+    pub round: u64,
+    /// The signer of the message, which is empty if it is not signed by a node.
+    pub from: Address,
+    /// The time since the message was processed.
+    pub age: Duration,
+}
+
+impl fmt::Display for StallDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "stalled for {:?} at height {}, round {}, {}",
+            self.stalled_for, self.height, self.round, self.step
+        )?;
+        if self.round_escalations > 0 {
+            writeln!(
+                f,
+                "escalated {} times by max rounds",
+                self.round_escalations
+            )?;
+        }
+        match &self.lock {
+            Some((round, hash)) => {
+                writeln!(f, "lock round {}, hash {}", round, hex(hash))?
+            }
+            None => writeln!(f, "no lock")?,
+        }
+        for votes in self.votes.iter() {
+            writeln!(
+                f,
+                "{} prevote {}, precommit {}, choked {}",
+                hex_encode(&votes.address),
+                vote(&votes.prevote),
+                vote(&votes.precommit),
+                votes.choked
+            )?;
+        }
+        for msg in self.recent_messages.iter() {
+            writeln!(
+                f,
+                "{:?} ago {} height {}, round {}, from {}",
+                msg.age,
+                msg.kind,
+                msg.height,
+                msg.round,
+                hex_encode(&msg.from)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn hex(hash: &Hash) -> String {
+    if hash.is_empty() {
+        "nil".to_string()
+    } else {
+        hex_encode(hash)
+    }
+}
+
+fn vote(hash: &Option<Hash>) -> String {
+    hash.as_ref().map_or_else(|| "none".to_string(), hex)
+}
+
+/// The watchdog of the height progress, which keeps the last messages processed by the state
+/// for the dump. It measures the time by the tokio clock, so it follows the paused time of the
+/// simulations.
+#[derive(Debug)]
+pub struct StallWatchdog {
+    progress: Instant,
+    capacity: usize,
+    records: VecDeque<(Instant, MessageRecord)>,
+}
+
+impl StallWatchdog {
+    pub fn new(capacity: usize) -> Self {
+        StallWatchdog {
+            progress: Instant::now(),
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Mark a height progress.
+    pub fn progress(&mut self) {
+        self.progress = Instant::now();
+    }
+
+    /// The time since the last height progress.
+    pub fn stalled_for(&self) -> Duration {
+        self.progress.elapsed()
+    }
+
+    /// Whether the processed messages are recorded.
+    pub fn is_recording(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Record a processed message, the oldest record is evicted over the capacity.
+    pub fn record(&mut self, kind: String, height: u64, round: u64, from: Address) {
+        if !self.is_recording() {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        let record = MessageRecord {
+            kind,
+            height,
+            round,
+            from,
+            age: Duration::ZERO,
+        };
+        self.records.push_back((Instant::now(), record));
+    }
+
+    /// The recorded messages from the oldest with their ages.
+    pub fn recent(&self) -> Vec<MessageRecord> {
+        let now = Instant::now();
+        self.records
+            .iter()
+            .map(|(at, record)| MessageRecord {
+                age: now.saturating_duration_since(*at),
+                ..record.clone()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StallWatchdog;
+    use crate::types::Address;
+
+    #[test]
+    fn test_stall_watchdog() {
+        let mut watchdog = StallWatchdog::new(2);
+        for round in 0..3 {
+            let address = Address::from_slice(&[1]).unwrap();
+            watchdog.record("Signed Vote".to_string(), 1, round, address);
+        }
+        let recent = watchdog.recent();
+        assert_eq!(
+            recent.iter().map(|record| record.round).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(recent[0].age >= recent[1].age);
+
+        let mut watchdog = StallWatchdog::new(0);
+        watchdog.record("Signed Vote".to_string(), 1, 0, Address::default());
+        assert!(watchdog.recent().is_empty());
+    }
+}
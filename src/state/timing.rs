@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::smr::smr_types::Step;
+use crate::types::{Address, MlmMsg, SignedProposal, VoteType};
+use crate::Codec;
+
+/// The timing of a committed height, reported by `Consensus::report_height()` after the
+/// commit if `MlmConfig::height_report` is enabled, by which a chain tunes the timeouts of
+/// its `DurationConfig`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeightReport {
+    /// The committed height.
+    pub height: u64,
+    /// The number of the rounds run in the height, including the decided round.
+    pub rounds: u64,
+    /// The proposer of the decided round.
+    pub proposer: Address,
+    /// The time since the node entered the height until the commit.
+    pub duration: Duration,
+    /// The elapsed time of each step that the node entered in the decided round in order. The
+    /// last step lasts until the commit.
+    pub step_elapsed: Vec<(Step, Duration)>,
+    /// The arrival of the prevotes of the decided round.
+    pub prevotes: VoteArrival,
+    /// The arrival of the precommits of the decided round.
+    pub precommits: VoteArrival,
+    /// The estimated bytes of the messages sent by the node in the height.
+    pub bytes_sent: u64,
+    /// The estimated bytes of the messages received by the node in the height.
+    pub bytes_received: u64,
+}
+
+/// The arrival times of the votes of a round since the start of the round, by the first vote
+/// of each voter. The votes are collected only by the relayers of them, so the arrival of a
+/// node relaying no vote is empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VoteArrival {
+    /// The number of the voters.
+    pub count: usize,
+    /// The median arrival time.
+    pub p50: Duration,
+    /// The 90th percentile of the arrival times.
+    pub p90: Duration,
+    /// The arrival time of the last vote.
+    pub max: Duration,
+}
+
+impl VoteArrival {
+    fn new(arrivals: &BTreeMap<Address, Duration>) -> Self {
+        let mut times = arrivals.values().copied().collect::<Vec<_>>();
+        times.sort();
+        VoteArrival {
+            count: times.len(),
+            p50: percentile(&times, 50),
+            p90: percentile(&times, 90),
+            max: times.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// The nearest rank percentile of the sorted times, zero if there is none.
+fn percentile(times: &[Duration], percent: usize) -> Duration {
+    let rank = (times.len() * percent).div_ceil(100);
+    times.get(rank.saturating_sub(1)).copied().unwrap_or_default()
+}
+
+/// The vote arrivals of the current round and the bytes transferred in the current height,
+/// which are taken into the report of the height when it is committed.
+#[derive(Debug, Default)]
+pub(crate) struct HeightTiming {
+    round: u64,
+    prevotes: BTreeMap<Address, Duration>,
+    precommits: BTreeMap<Address, Duration>,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl HeightTiming {
+    pub(crate) fn new() -> Self {
+        HeightTiming::default()
+    }
+
+    /// Clear the vote arrivals for a new round.
+    pub(crate) fn on_new_round(&mut self, round: u64) {
+        self.round = round;
+        self.prevotes.clear();
+        self.precommits.clear();
+    }
+
+    /// Record the arrival of a vote of the round, only the first vote of a voter counts.
+    pub(crate) fn on_vote(
+        &mut self,
+        round: u64,
+        vote_type: &VoteType,
+        voter: Address,
+        elapsed: Duration,
+    ) {
+        if round != self.round {
+            return;
+        }
+        let arrivals = match vote_type {
+            VoteType::Prevote => &mut self.prevotes,
+            VoteType::Precommit => &mut self.precommits,
+        };
+        arrivals.entry(voter).or_insert(elapsed);
+    }
+
+    pub(crate) fn on_sent<T: Codec>(&self, msg: &MlmMsg<T>) {
+        self.bytes_sent
+            .fetch_add(msg_size(msg) as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_received<T: Codec>(&self, msg: &MlmMsg<T>) {
+        self.bytes_received
+            .fetch_add(msg_size(msg) as u64, Ordering::Relaxed);
+    }
+
+    /// Take the prevote and the precommit arrivals of the round and the bytes sent and
+    /// received in the height, and start counting the bytes of the next height.
+    pub(crate) fn take(&mut self) -> (VoteArrival, VoteArrival, u64, u64) {
+        let prevotes = VoteArrival::new(&self.prevotes);
+        let precommits = VoteArrival::new(&self.precommits);
+        self.on_new_round(0);
+        (
+            prevotes,
+            precommits,
+            self.bytes_sent.swap(0, Ordering::Relaxed),
+            self.bytes_received.swap(0, Ordering::Relaxed),
+        )
+    }
+}
+
+/// The estimated bytes of a message on the wire, by its rlp encoding. The local messages are
+/// not transferred, so they are measured as zero.
+pub(crate) fn msg_size<T: Codec>(msg: &MlmMsg<T>) -> usize {
+    match msg {
+        MlmMsg::SignedProposal(sp) => proposal_size(sp),
+        MlmMsg::SignedVote(sv) => rlp::encode(sv).len(),
+        MlmMsg::AggregatedVote(av) => rlp::encode(av).len(),
+        MlmMsg::SignedChoke(sc) => rlp::encode(sc).len(),
+        MlmMsg::CompactProposal(cp) => rlp::encode(cp).len(),
+        MlmMsg::GetBlock(gb) => rlp::encode(gb).len(),
+        MlmMsg::BlockResponse(br) => rlp::encode(br).len(),
+        MlmMsg::PartedProposal(pp) => rlp::encode(pp).len(),
+        MlmMsg::BlockPart(bp) => rlp::encode(bp).len(),
+        MlmMsg::SignedHeartbeat(sh) => rlp::encode(sh).len(),
+        MlmMsg::SignedVoteBatch(votes) => rlp::encode_list(votes).len(),
+        MlmMsg::SignedKeyRotation(skr) => rlp::encode(skr).len(),
+        _ => 0,
+    }
+}
+
+/// The estimated bytes of a signed proposal. The block is measured by its size hint if it
+/// has one, so the block is not encoded only to be measured.
+pub(crate) fn proposal_size<T: Codec>(sp: &SignedProposal<T>) -> usize {
+    let proposal = &sp.proposal;
+    match proposal.content.size_hint() {
+        Some(len) => {
+            let lock = proposal.lock.as_ref().map_or(0, |polc| {
+                polc.lock_votes.signature.signature.len()
+                    + polc.lock_votes.signature.address_bitmap.len()
+                    + polc.lock_votes.block_hash.len()
+            });
+            len + lock
+                + sp.signature.len()
+                + proposal.block_hash.len()
+                + proposal.proposer.len()
+        }
+        None => rlp::encode(sp).len(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{percentile, HeightTiming};
+    use crate::types::{Address, VoteType};
+
+    #[test]
+    fn test_vote_arrival() {
+        let millis = |ms| Duration::from_millis(ms);
+        let times = (1..=10).map(millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&times, 50), millis(5));
+        assert_eq!(percentile(&times, 90), millis(9));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+
+        let mut timing = HeightTiming::new();
+        timing.on_new_round(1);
+        let voter = |i: u8| Address::from_slice(&[i; 20]).unwrap();
+        for i in 0..4u8 {
+            timing.on_vote(1, &VoteType::Prevote, voter(i), millis(10 * i as u64));
+        }
+        // Only the first vote of a voter of the round counts.
+        timing.on_vote(1, &VoteType::Prevote, voter(0), millis(100));
+        timing.on_vote(0, &VoteType::Precommit, voter(0), millis(100));
+        timing.on_vote(1, &VoteType::Precommit, voter(1), millis(50));
+
+        let (prevotes, precommits, sent, received) = timing.take();
+        assert_eq!(prevotes.count, 4);
+        assert_eq!(prevotes.p50, millis(10));
+        assert_eq!(prevotes.max, millis(30));
+        assert_eq!(precommits.count, 1);
+        assert_eq!(precommits.p90, millis(50));
+        assert_eq!((sent, received), (0, 0));
+    }
+}
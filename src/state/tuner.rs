@@ -0,0 +1,120 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::config::IntervalTuning;
+
+/// The default number of the recent block times that the interval is tuned by.
+const DEFAULT_WINDOW: usize = 10;
+
+/// The controller of the effective interval, which the proposer of the next height waits for
+/// after a commit. It measures the time between the commits of the consecutive heights, which
+/// includes the rounds and the execution of the commits, and moves the effective interval by
+/// half of the difference between the target and the average block time of the window.
+#[derive(Debug)]
+pub(crate) struct IntervalTuner {
+    tuning: IntervalTuning,
+    effective: Option<Duration>,
+    block_times: VecDeque<Duration>,
+    last_commit: Option<(u64, Instant)>,
+}
+
+impl IntervalTuner {
+    pub(crate) fn new(tuning: IntervalTuning) -> Self {
+        IntervalTuner {
+            tuning,
+            effective: None,
+            block_times: VecDeque::new(),
+            last_commit: None,
+        }
+    }
+
+    /// The effective interval for the target interval, which is the target itself if the
+    /// tuning is disabled or there is no measure yet.
+    pub(crate) fn interval(&self, target: Duration) -> Duration {
+        if self.tuning.max == 0 {
+            return target;
+        }
+        self.effective.unwrap_or(target)
+    }
+
+    /// Measure the commit of the height at the instant, and tune the effective interval for
+    /// the target interval.
+    pub(crate) fn on_commit(&mut self, height: u64, now: Instant, target: Duration) {
+        if self.tuning.max == 0 {
+            return;
+        }
+
+        let last = self.last_commit.replace((height, now));
+        let block_time = match last {
+            Some((last_height, last_at)) if last_height + 1 == height => {
+                now.saturating_duration_since(last_at)
+            }
+            _ => return,
+        };
+
+        let window = if self.tuning.window == 0 {
+            DEFAULT_WINDOW
+        } else {
+            self.tuning.window
+        };
+        self.block_times.push_back(block_time);
+        while self.block_times.len() > window {
+            self.block_times.pop_front();
+        }
+
+        let average =
+            self.block_times.iter().sum::<Duration>() / self.block_times.len() as u32;
+        let effective = self.interval(target);
+        let effective = if average > target {
+            effective.saturating_sub((average - target) / 2)
+        } else {
+            effective + (target - average) / 2
+        };
+        self.effective = Some(effective.clamp(
+            Duration::from_millis(self.tuning.min),
+            Duration::from_millis(self.tuning.max.max(self.tuning.min)),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::IntervalTuner;
+    use crate::config::IntervalTuning;
+
+    #[test]
+    fn test_interval_tuner() {
+        let target = Duration::from_millis(1000);
+        let start = Instant::now();
+        let at = |millis: u64| start + Duration::from_millis(millis);
+
+        // Disabled.
+        let mut tuner = IntervalTuner::new(IntervalTuning::default());
+        tuner.on_commit(1, at(0), target);
+        tuner.on_commit(2, at(1400), target);
+        assert_eq!(tuner.interval(target), target);
+
+        // The blocks of 1400 millis shorten the interval by 200 millis.
+        let mut tuner = IntervalTuner::new(IntervalTuning::new(500, 1500, 1));
+        tuner.on_commit(1, at(0), target);
+        assert_eq!(tuner.interval(target), target);
+        tuner.on_commit(2, at(1400), target);
+        assert_eq!(tuner.interval(target), Duration::from_millis(800));
+
+        // The jump of the heights is not measured.
+        tuner.on_commit(5, at(9000), target);
+        assert_eq!(tuner.interval(target), Duration::from_millis(800));
+
+        // The fast blocks lengthen it within the bounds.
+        tuner.on_commit(6, at(9100), target);
+        assert_eq!(tuner.interval(target), Duration::from_millis(1250));
+        tuner.on_commit(7, at(9200), target);
+        assert_eq!(tuner.interval(target), Duration::from_millis(1500));
+
+        // Slow blocks shorten it to the lower bound.
+        tuner.on_commit(8, at(19200), target);
+        assert_eq!(tuner.interval(target), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// The counters of the messages dropped since their heights are out of the acceptance window.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeightWindowStats {
+    /// The messages of the heights below the window.
+    pub below: u64,
+    /// The messages of the heights above the window.
+    pub above: u64,
+}
+
+/// The stats of the height window, which are updated by the state and read by the instance.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HeightWindowAccount(Arc<Mutex<HeightWindowStats>>);
+
+impl HeightWindowAccount {
+    pub(crate) fn new() -> Self {
+        HeightWindowAccount::default()
+    }
+
+    pub(crate) fn stats(&self) -> HeightWindowStats {
+        self.0.lock().clone()
+    }
+}
+
+/// The acceptance window of the message heights around the current height. A message of a
+/// height more than `size` away from the current height is dropped before the verification.
+#[derive(Debug)]
+pub struct HeightWindow {
+    size: u64,
+    account: HeightWindowAccount,
+}
+
+impl HeightWindow {
+    /// Create a window of the given size, zero disables it.
+    pub fn new(size: u64) -> Self {
+        HeightWindow {
+            size,
+            account: HeightWindowAccount::new(),
+        }
+    }
+
+    pub(crate) fn set_account(&mut self, account: HeightWindowAccount) {
+        self.account = account;
+    }
+
+    /// Check the height of a message at the current height, return false and count it if the
+    /// message is out of the window.
+    pub fn check(&self, current: u64, height: u64) -> bool {
+        if self.size == 0 {
+            return true;
+        }
+
+        if height < current.saturating_sub(self.size) {
+            self.account.0.lock().below += 1;
+            false
+        } else if height > current.saturating_add(self.size) {
+            self.account.0.lock().above += 1;
+            false
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HeightWindow, HeightWindowAccount, HeightWindowStats};
+
+    #[test]
+    fn test_height_window() {
+        let account = HeightWindowAccount::new();
+        let mut window = HeightWindow::new(3);
+        window.set_account(account.clone());
+
+        assert!(window.check(10, 7));
+        assert!(window.check(10, 13));
+        assert!(!window.check(10, 6));
+        assert!(!window.check(10, 10_000_000));
+        assert!(!window.check(10, u64::MAX));
+        assert!(window.check(1, 0));
+        assert_eq!(account.stats(), HeightWindowStats { below: 1, above: 2 });
+
+        assert!(HeightWindow::new(0).check(10, u64::MAX));
+    }
+}
@@ -0,0 +1,458 @@
+use std::fmt::Debug;
+#[cfg(feature = "runtime")]
+use std::sync::Arc;
+
+use creep::Context;
+
+use crate::smr::smr_types::Step;
+use crate::types::{Address, Hash};
+
+/// The attributes of a span of the message handling, which are empty if they are unknown to
+/// the span.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpanAttrs {
+    /// The height of the handled message.
+    pub height: Option<u64>,
+    /// The round of the handled message.
+    pub round: Option<u64>,
+    /// The kind of the handled message, such as the vote type.
+    pub kind: Option<String>,
+    /// The block hash of the handled message.
+    pub hash: Option<Hash>,
+    /// The signer of the handled message.
+    pub from: Option<Address>,
+    /// The id of the instance that handles the message.
+    pub instance: Option<String>,
+    /// The step of the round that the node is in when the span starts.
+    pub step: Option<Step>,
+    /// The address of the node that handles the message.
+    pub address: Option<Address>,
+}
+
+impl SpanAttrs {
+    /// Create the attributes of a span of the round.
+    pub fn round(height: u64, round: u64) -> Self {
+        SpanAttrs {
+            height: Some(height),
+            round: Some(round),
+            ..Default::default()
+        }
+    }
+
+    /// Set the kind of the handled message.
+    pub fn with_kind(mut self, kind: impl ToString) -> Self {
+        self.kind = Some(kind.to_string());
+        self
+    }
+
+    /// Set the block hash of the handled message.
+    pub fn with_hash(mut self, hash: Hash) -> Self {
+        self.hash = Some(hash);
+        self
+    }
+
+    /// Set the signer of the handled message.
+    pub fn with_from(mut self, from: Address) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// Set the id of the instance that handles the message.
+    pub fn with_instance(mut self, instance: impl ToString) -> Self {
+        self.instance = Some(instance.to_string());
+        self
+    }
+
+    /// Set the step of the round that the node is in.
+    pub fn with_step(mut self, step: Step) -> Self {
+        self.step = Some(step);
+        self
+    }
+
+    /// Set the address of the node that handles the message.
+    pub fn with_address(mut self, address: Address) -> Self {
+        self.address = Some(address);
+        self
+    }
+}
+
+/// The scope of a node that its logs are labeled with, which is taken from the state or the
+/// timer for each log record, or before a task is spawned for the logs of the task.
+#[cfg(feature = "runtime")]
+#[derive(Clone, Debug)]
+pub(crate) struct LogScope {
+    pub(crate) address: Address,
+    pub(crate) height: u64,
+    pub(crate) round: u64,
+    pub(crate) step: Step,
+}
+
+/// Log a record of the level, such as `debug`, with the structured fields of the scope of
+/// the node, which are the `address`, `height`, `round` and `step` keys of the record. The
+/// scope is only evaluated if the level is enabled.
+#[cfg(feature = "runtime")]
+macro_rules! node_log {
+    ($level:ident, $scope:expr, $($arg:tt)+) => {
+        log::$level!(
+            address:% = $scope.address,
+            height = $scope.height,
+            round = $scope.round,
+            step:? = $scope.step;
+            $($arg)+
+        )
+    };
+}
+
+#[cfg(feature = "runtime")]
+pub(crate) use node_log;
+
+/// The telemetry that the spans of the message handling are reported to. A span is carried
+/// by the `Context` of the handling, so the spans of the nested steps and the adapter calls
+/// made in the handling are the children of it.
+pub trait Trace: Debug + Send + Sync {
+    /// Start a span of the named step as a child of the span carried by the context, and
+    /// return the context carrying the new span. The span ends when the returned context and
+    /// all of its clones are dropped.
+    fn span(&self, ctx: Context, name: &'static str, attrs: SpanAttrs) -> Context;
+}
+
+/// The telemetry that reports nothing, which is the default of an instance.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopTrace;
+
+impl Trace for NoopTrace {
+    fn span(&self, ctx: Context, _name: &'static str, _attrs: SpanAttrs) -> Context {
+        ctx
+    }
+}
+
+/// The telemetry that labels the spans of an instance with its id and the address of the
+/// node.
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+pub(crate) struct InstanceTrace {
+    inner: Arc<dyn Trace>,
+    instance: String,
+    address: Option<Address>,
+}
+
+#[cfg(feature = "runtime")]
+impl InstanceTrace {
+    /// Label the spans reported to the telemetry with the instance id and the address, the
+    /// telemetry is kept as is if the id is empty and the address is unknown.
+    pub(crate) fn wrap(
+        inner: Arc<dyn Trace>,
+        instance: &str,
+        address: Option<Address>,
+    ) -> Arc<dyn Trace> {
+        if instance.is_empty() && address.is_none() {
+            return inner;
+        }
+        Arc::new(InstanceTrace {
+            inner,
+            instance: instance.to_string(),
+            address,
+        })
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl Trace for InstanceTrace {
+    fn span(&self, ctx: Context, name: &'static str, mut attrs: SpanAttrs) -> Context {
+        if attrs.instance.is_none() && !self.instance.is_empty() {
+            attrs.instance = Some(self.instance.clone());
+        }
+        if attrs.address.is_none() {
+            attrs.address = self.address.clone();
+        }
+        self.inner.span(ctx, name, attrs)
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub use self::tracing_trace::TracingTrace;
+
+#[cfg(feature = "tracing")]
+mod tracing_trace {
+    use creep::Context;
+    use hummer::coding::hex_encode;
+    use tracing::{field, info_span, Span};
+
+    use super::{SpanAttrs, Trace};
+    use crate::types::CorrelationId;
+
+    const SPAN_KEY: &str = "mlm_tracing_span";
+
+    macro_rules! mlm_span {
+        ($name:expr $(, $parent:expr)?) => {
+            info_span!(
+                target: "mlm",
+                $(parent: $parent,)?
+                "mlm",
+                step = $name,
+                height = field::Empty,
+                round = field::Empty,
+                correlation_id = field::Empty,
+                kind = field::Empty,
+                hash = field::Empty,
+                from = field::Empty,
+                instance = field::Empty,
+                round_step = field::Empty,
+                address = field::Empty,
+            )
+        };
+    }
+
+    /// The telemetry that reports the spans to the `tracing` crate. Each span is named `mlm`
+    /// with the step as its `step` field, and its parent is the span carried by the context,
+    /// or the current span of the subscriber if the context carries none. The step of the
+    /// round that the node is in is the `round_step` field.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct TracingTrace;
+
+    impl Trace for TracingTrace {
+        fn span(&self, ctx: Context, name: &'static str, attrs: SpanAttrs) -> Context {
+            let span = match ctx.get::<Span>(SPAN_KEY) {
+                Some(parent) => mlm_span!(name, parent),
+                None => mlm_span!(name),
+            };
+            if let Some(height) = attrs.height {
+                span.record("height", height);
+            }
+            if let Some(round) = attrs.round {
+                span.record("round", round);
+            }
+            if let (Some(height), Some(round)) = (attrs.height, attrs.round) {
+                span.record(
+                    "correlation_id",
+                    field::display(CorrelationId::new(height, round)),
+                );
+            }
+            if let Some(kind) = attrs.kind.as_ref() {
+                span.record("kind", kind.as_str());
+            }
+            if let Some(hash) = attrs.hash.as_ref() {
+                span.record("hash", hex_encode(hash).as_str());
+            }
+            if let Some(from) = attrs.from.as_ref() {
+                span.record("from", hex_encode(from).as_str());
+            }
+            if let Some(instance) = attrs.instance.as_ref() {
+                span.record("instance", instance.as_str());
+            }
+            if let Some(step) = attrs.step.as_ref() {
+                span.record("round_step", field::debug(step));
+            }
+            if let Some(address) = attrs.address.as_ref() {
+                span.record("address", hex_encode(address).as_str());
+            }
+            ctx.with_value(SPAN_KEY, span)
+        }
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+pub use self::otel_trace::{extract_context, inject_context, OtelTrace};
+
+#[cfg(feature = "opentelemetry")]
+mod otel_trace {
+    use std::collections::HashMap;
+
+    use creep::Context;
+    use hummer::coding::hex_encode;
+    use opentelemetry::global::{self, BoxedTracer};
+    use opentelemetry::trace::{TraceContextExt, Tracer};
+    use opentelemetry::{Context as OtelContext, KeyValue};
+
+    use super::{SpanAttrs, Trace};
+    use crate::types::CorrelationId;
+
+    const OTEL_CONTEXT_KEY: &str = "mlm_otel_context";
+
+    /// The telemetry that exports the spans by OpenTelemetry. The span of each step is a child
+    /// of the OpenTelemetry context carried by the `Context`, so a message keeps one trace from
+    /// the receipt, through the signature verification and the SMR transition, to the
+    /// broadcast of the votes and the QCs of it. The trace crosses the nodes if the transport
+    /// sends the carrier of `inject_context()` along with the messages, and the receiver
+    /// passes the context of `extract_context()` to `MlmHandler::send_msg()`.
+    #[derive(Debug)]
+    pub struct OtelTrace {
+        tracer: BoxedTracer,
+    }
+
+    impl OtelTrace {
+        /// Create the telemetry with the tracer of the global tracer provider.
+        pub fn new() -> Self {
+            OtelTrace::with_tracer(global::tracer("mlm"))
+        }
+
+        /// Create the telemetry with the tracer.
+        pub fn with_tracer(tracer: BoxedTracer) -> Self {
+            OtelTrace { tracer }
+        }
+    }
+
+    impl Default for OtelTrace {
+        fn default() -> Self {
+            OtelTrace::new()
+        }
+    }
+
+    impl Trace for OtelTrace {
+        fn span(&self, ctx: Context, name: &'static str, attrs: SpanAttrs) -> Context {
+            let parent = otel_context(&ctx);
+            let mut attributes = Vec::new();
+            if let Some(height) = attrs.height {
+                attributes.push(KeyValue::new("mlm.height", height as i64));
+            }
+            if let Some(round) = attrs.round {
+                attributes.push(KeyValue::new("mlm.round", round as i64));
+            }
+            if let (Some(height), Some(round)) = (attrs.height, attrs.round) {
+                let id = CorrelationId::new(height, round).to_string();
+                attributes.push(KeyValue::new("mlm.correlation_id", id));
+            }
+            if let Some(kind) = attrs.kind {
+                attributes.push(KeyValue::new("mlm.kind", kind));
+            }
+            if let Some(hash) = attrs.hash.as_ref() {
+                attributes.push(KeyValue::new("mlm.hash", hex_encode(hash)));
+            }
+            if let Some(from) = attrs.from.as_ref() {
+                attributes.push(KeyValue::new("mlm.from", hex_encode(from)));
+            }
+            if let Some(instance) = attrs.instance {
+                attributes.push(KeyValue::new("mlm.instance", instance));
+            }
+            if let Some(step) = attrs.step {
+                attributes.push(KeyValue::new("mlm.step", format!("{:?}", step)));
+            }
+            if let Some(address) = attrs.address.as_ref() {
+                attributes.push(KeyValue::new("mlm.address", hex_encode(address)));
+            }
+
+            let span = self
+                .tracer
+                .span_builder(name)
+                .with_attributes(attributes)
+                .start_with_context(&self.tracer, &parent);
+            ctx.with_value(OTEL_CONTEXT_KEY, parent.with_span(span))
+        }
+    }
+
+    /// Inject the trace context carried by the `Context` into a carrier by the global text
+    /// map propagator, which the transport sends along with a message.
+    pub fn inject_context(ctx: &Context) -> HashMap<String, String> {
+        let mut carrier = HashMap::new();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&otel_context(ctx), &mut carrier)
+        });
+        carrier
+    }
+
+    /// Extract the trace context from the carrier received along with a message by the global
+    /// text map propagator, and return the `Context` carrying it, which the transport passes
+    /// to `MlmHandler::send_msg()`.
+    pub fn extract_context(ctx: Context, carrier: &HashMap<String, String>) -> Context {
+        let remote =
+            global::get_text_map_propagator(|propagator| propagator.extract(carrier));
+        ctx.with_value(OTEL_CONTEXT_KEY, remote)
+    }
+
+    fn otel_context(ctx: &Context) -> OtelContext {
+        ctx.get::<OtelContext>(OTEL_CONTEXT_KEY)
+            .cloned()
+            .unwrap_or_else(OtelContext::current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "runtime")]
+    use std::sync::Arc;
+
+    #[cfg(feature = "runtime")]
+    use creep::Context;
+    #[cfg(feature = "runtime")]
+    use parking_lot::Mutex;
+
+    use super::SpanAttrs;
+    #[cfg(feature = "runtime")]
+    use super::{InstanceTrace, Trace};
+    use crate::smr::smr_types::Step;
+    use crate::types::{Address, Hash};
+
+    #[test]
+    fn test_span_attrs() {
+        let attrs = SpanAttrs::round(3, 1)
+            .with_kind("Prevote")
+            .with_hash(Hash::from(vec![1]))
+            .with_from(Address::from_slice(&[2]).unwrap());
+        assert_eq!(attrs.height, Some(3));
+        assert_eq!(attrs.round, Some(1));
+        assert_eq!(attrs.kind.as_deref(), Some("Prevote"));
+        assert_eq!(attrs.hash, Some(Hash::from(vec![1])));
+        assert_eq!(attrs.from, Some(Address::from_slice(&[2]).unwrap()));
+        assert_eq!(attrs.instance, None);
+
+        let attrs = SpanAttrs::default()
+            .with_step(Step::Prevote)
+            .with_address(Address::from_slice(&[3]).unwrap());
+        assert_eq!(attrs.step, Some(Step::Prevote));
+        assert_eq!(attrs.address, Some(Address::from_slice(&[3]).unwrap()));
+    }
+
+    #[cfg(feature = "runtime")]
+    #[derive(Debug, Default)]
+    struct RecordTrace(Mutex<Vec<SpanAttrs>>);
+
+    #[cfg(feature = "runtime")]
+    impl Trace for RecordTrace {
+        fn span(&self, ctx: Context, _name: &'static str, attrs: SpanAttrs) -> Context {
+            self.0.lock().push(attrs);
+            ctx
+        }
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_instance_trace() {
+        let record = Arc::new(RecordTrace::default());
+        let address = Address::from_slice(&[1]).unwrap();
+        let trace = InstanceTrace::wrap(
+            Arc::clone(&record) as Arc<dyn Trace>,
+            "shard-1",
+            Some(address.clone()),
+        );
+        trace.span(Context::new(), "mlm.test", SpanAttrs::round(1, 0));
+        trace.span(
+            Context::new(),
+            "mlm.test",
+            SpanAttrs::default().with_instance("shard-2"),
+        );
+
+        // A single instance is labeled with the address only.
+        let trace = InstanceTrace::wrap(
+            Arc::clone(&record) as Arc<dyn Trace>,
+            "",
+            Some(address),
+        );
+        trace.span(Context::new(), "mlm.test", SpanAttrs::round(1, 0));
+
+        let labels = record
+            .0
+            .lock()
+            .iter()
+            .map(|attrs| (attrs.instance.clone(), attrs.address.clone()))
+            .collect::<Vec<_>>();
+        let address = Some(Address::from_slice(&[1]).unwrap());
+        assert_eq!(
+            labels,
+            vec![
+                (Some("shard-1".to_string()), address.clone()),
+                (Some("shard-2".to_string()), address.clone()),
+                (None, address),
+            ]
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::error::Error;
+
+use bytes::Bytes;
+use rand::random;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::error::ConsensusError;
+use crate::types::{Address, AggregatedSignature, Hash, Signature};
+use crate::Crypto;
+
+/// A random address of 32 bytes.
+pub(crate) fn gen_address() -> Address {
+    Address::try_from((0..32).map(|_| random::<u8>()).collect::<Vec<_>>()).unwrap()
+}
+
+/// The address of 20 bytes of the index, which is the same for the same index.
+#[cfg(feature = "runtime")]
+pub(crate) fn index_address(index: u8) -> Address {
+    Address::from_slice(&[index; 20]).unwrap()
+}
+
+/// The crypto of the unit tests that only hash, by keccak256. The signatures are refused.
+#[derive(Debug)]
+pub(crate) struct KeccakCrypto;
+
+impl Crypto for KeccakCrypto {
+    type QcSignature = AggregatedSignature;
+
+    fn hash(&self, msg: Bytes) -> Hash {
+        let mut hasher = Keccak::v256();
+        let mut output = [0u8; 32];
+        hasher.update(&msg);
+        hasher.finalize(&mut output);
+        Hash::from(output.to_vec())
+    }
+
+    fn sign(&self, _hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+        Err(only_hash())
+    }
+
+    fn aggregate_signatures(
+        &self,
+        _signatures: Vec<Signature>,
+        _voters: Vec<Address>,
+    ) -> Result<Signature, Box<dyn Error + Send>> {
+        Err(only_hash())
+    }
+
+    fn verify_signature(
+        &self,
+        _signature: Signature,
+        _hash: Hash,
+        _voter: Address,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Err(only_hash())
+    }
+
+    fn verify_aggregated_signature(
+        &self,
+        _aggregate_signature: Signature,
+        _msg_hash: Hash,
+        _voters: Vec<Address>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        Err(only_hash())
+    }
+}
+
+fn only_hash() -> Box<dyn Error + Send> {
+    Box::new(ConsensusError::CryptoErr("KeccakCrypto only hashes".to_string()))
+}
@@ -0,0 +1,682 @@
+use std::error::Error;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use rand_core::{RngCore, SeedableRng};
+use rand_pcg::Pcg64;
+
+use crate::error::ConsensusError;
+use crate::types::{
+    Address, AggregatedVote, Commit, Hash, MlmMsg, Node, SignedProposal, SignedVote,
+    Status, ViewChangeInfo, ViewChangeReason,
+};
+use crate::{
+    BrakeReport, Codec, ConnectivitySummary, Consensus, Context, Crypto, HaltReport,
+    RoundLimitReport, StallDump,
+};
+
+/// The byzantine behaviors of a node. All of the behaviors are off by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ByzantineConfig {
+    /// Send a conflicting proposal for a random hash along with each proposal, and a
+    /// conflicting vote for a random hash along with each vote.
+    pub equivocate: bool,
+    /// Withhold the proposals, so that the rounds of the node time out.
+    pub withhold_proposals: bool,
+    /// Send a conflicting proposal for a random hash to the nodes of the addresses instead of
+    /// each proposal, which the other nodes receive only, so that the network is split by
+    /// the two proposals of the node. Empty sends the proposals to all.
+    pub split_proposals: Vec<Address>,
+    /// Send the QCs with a corrupted signature and an empty voter bitmap.
+    pub invalid_qcs: bool,
+    /// Vote for a random hash instead of the voted one.
+    pub random_votes: bool,
+    /// The seed of the random hashes.
+    pub seed: u64,
+}
+
+/// The counters of the messages tampered by a byzantine node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ByzantineStats {
+    /// The conflicting proposals and votes sent.
+    pub equivocations: u64,
+    /// The proposals withheld.
+    pub withheld_proposals: u64,
+    /// The invalid QCs sent.
+    pub invalid_qcs: u64,
+    /// The votes for a random hash sent.
+    pub random_votes: u64,
+}
+
+/// A participant turned byzantine. It wraps the `Consensus` adapter of a node and tampers
+/// the messages that the node sends by the config, which are signed again by the crypto of
+/// the node, so that the other nodes receive the malicious traffic as it would be on a
+/// real network. The node itself runs the honest engine, and the other calls are passed to
+/// the wrapped adapter as they are.
+pub struct Byzantine<T: Codec, F: Consensus<T>, C: Crypto> {
+    inner: Arc<F>,
+    crypto: Arc<C>,
+    config: ByzantineConfig,
+    rng: Mutex<Pcg64>,
+    stats: Mutex<ByzantineStats>,
+    pin_block: PhantomData<T>,
+}
+
+impl<T, F, C> Byzantine<T, F, C>
+where
+    T: Codec,
+    F: Consensus<T>,
+    C: Crypto,
+{
+    /// Wrap the adapter of a node with the byzantine behaviors. The crypto must be the one
+    /// of the node.
+    pub fn new(inner: Arc<F>, crypto: Arc<C>, config: ByzantineConfig) -> Self {
+        Byzantine {
+            rng: Mutex::new(Pcg64::seed_from_u64(config.seed)),
+            inner,
+            crypto,
+            config,
+            stats: Mutex::new(ByzantineStats::default()),
+            pin_block: PhantomData,
+        }
+    }
+
+    /// The counters of the tampered messages.
+    pub fn stats(&self) -> ByzantineStats {
+        self.stats.lock().clone()
+    }
+
+    /// Turn a message to send into the messages that are actually sent.
+    fn tamper(&self, msg: MlmMsg<T>) -> Result<Vec<MlmMsg<T>>, Box<dyn Error + Send>> {
+        let msgs = match msg {
+            MlmMsg::SignedProposal(sp) => self.tamper_proposal(sp)?,
+            MlmMsg::SignedVote(sv) => self.tamper_vote(sv)?,
+            MlmMsg::AggregatedVote(qc) => {
+                vec![MlmMsg::AggregatedVote(self.tamper_qc(qc))]
+            }
+            msg => vec![msg],
+        };
+        Ok(msgs)
+    }
+
+    fn tamper_proposal(
+        &self,
+        sp: SignedProposal<T>,
+    ) -> Result<Vec<MlmMsg<T>>, Box<dyn Error + Send>> {
+        if self.config.withhold_proposals {
+            self.stats.lock().withheld_proposals += 1;
+            return Ok(Vec::new());
+        }
+
+        let mut msgs = Vec::new();
+        if self.config.equivocate {
+            msgs.push(MlmMsg::SignedProposal(self.propose_random_hash(&sp)?));
+            self.stats.lock().equivocations += 1;
+        }
+        msgs.push(MlmMsg::SignedProposal(sp));
+        Ok(msgs)
+    }
+
+    /// Send the proposal to the nodes out of the split, and a conflicting one to the nodes in
+    /// it.
+    async fn split_proposal(
+        &self,
+        ctx: Context,
+        sp: SignedProposal<T>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let conflict = MlmMsg::SignedProposal(self.propose_random_hash(&sp)?);
+        self.stats.lock().equivocations += 1;
+        let (height, proposer) = (sp.proposal.height, sp.proposal.proposer.clone());
+        let msg = MlmMsg::SignedProposal(sp);
+        let authority_list = self.inner.get_authority_list(ctx.clone(), height).await?;
+        for node in authority_list.into_iter() {
+            if node.address == proposer {
+                continue;
+            }
+            let msg = if self.config.split_proposals.contains(&node.address) {
+                conflict.clone()
+            } else {
+                msg.clone()
+            };
+            self.inner
+                .transmit_to_relayer(ctx.clone(), node.address, msg)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn propose_random_hash(
+        &self,
+        sp: &SignedProposal<T>,
+    ) -> Result<SignedProposal<T>, Box<dyn Error + Send>> {
+        let mut proposal = sp.proposal.clone();
+        proposal.block_hash = self.random_hash();
+        let signature = self
+            .crypto
+            .sign(self.crypto.hash(Bytes::from(rlp::encode(&proposal))))?;
+        Ok(SignedProposal {
+            signature,
+            proposal,
+            timeout_cert: sp.timeout_cert.clone(),
+            vrf_proof: sp.vrf_proof.clone(),
+        })
+    }
+
+    fn tamper_vote(
+        &self,
+        sv: SignedVote,
+    ) -> Result<Vec<MlmMsg<T>>, Box<dyn Error + Send>> {
+        let mut msgs = Vec::new();
+        if self.config.equivocate {
+            msgs.push(MlmMsg::SignedVote(self.vote_random_hash(&sv)?));
+            self.stats.lock().equivocations += 1;
+        }
+
+        if self.config.random_votes {
+            msgs.push(MlmMsg::SignedVote(self.vote_random_hash(&sv)?));
+            self.stats.lock().random_votes += 1;
+        } else {
+            msgs.push(MlmMsg::SignedVote(sv));
+        }
+        Ok(msgs)
+    }
+
+    fn tamper_qc(&self, mut qc: AggregatedVote) -> AggregatedVote {
+        if self.config.invalid_qcs {
+            qc.signature.signature = self.random_hash().into_bytes();
+            qc.signature.address_bitmap =
+                Bytes::from(vec![0u8; qc.signature.address_bitmap.len()]);
+            self.stats.lock().invalid_qcs += 1;
+        }
+        qc
+    }
+
+    fn vote_random_hash(
+        &self,
+        sv: &SignedVote,
+    ) -> Result<SignedVote, Box<dyn Error + Send>> {
+        let mut vote = sv.vote.clone();
+        vote.block_hash = self.random_hash();
+        let signature = self
+            .crypto
+            .sign(self.crypto.hash(Bytes::from(rlp::encode(&vote))))?;
+        Ok(SignedVote {
+            signature,
+            vote,
+            voter: sv.voter.clone(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        })
+    }
+
+    fn random_hash(&self) -> Hash {
+        let mut hash = vec![0u8; 32];
+        self.rng.lock().fill_bytes(&mut hash);
+        Hash::from(hash)
+    }
+}
+
+#[async_trait]
+impl<T, F, C> Consensus<T> for Byzantine<T, F, C>
+where
+    T: Codec + Send + Sync + 'static,
+    F: Consensus<T> + 'static,
+    C: Crypto + Send + Sync + 'static,
+{
+    async fn get_block(
+        &self,
+        ctx: Context,
+        height: u64,
+    ) -> Result<(T, Hash), Box<dyn Error + Send>> {
+        self.inner.get_block(ctx, height).await
+    }
+
+    async fn check_block(
+        &self,
+        ctx: Context,
+        height: u64,
+        hash: Hash,
+        block: T,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        self.inner.check_block(ctx, height, hash, block).await
+    }
+
+    fn block_hash(&self, height: u64, block: &T) -> Option<Hash> {
+        self.inner.block_hash(height, block)
+    }
+
+    async fn commit(
+        &self,
+        ctx: Context,
+        height: u64,
+        commit: Commit<T>,
+    ) -> Result<Status, Box<dyn Error + Send>> {
+        self.inner.commit(ctx, height, commit).await
+    }
+
+    async fn get_authority_list(
+        &self,
+        ctx: Context,
+        height: u64,
+    ) -> Result<Vec<Node>, Box<dyn Error + Send>> {
+        self.inner.get_authority_list(ctx, height).await
+    }
+
+    async fn broadcast_to_other(
+        &self,
+        ctx: Context,
+        msg: MlmMsg<T>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        match msg {
+            MlmMsg::SignedProposal(sp)
+                if !self.config.split_proposals.is_empty()
+                    && !self.config.withhold_proposals =>
+            {
+                return self.split_proposal(ctx, sp).await;
+            }
+            _ => (),
+        }
+
+        for msg in self.tamper(msg)? {
+            self.inner.broadcast_to_other(ctx.clone(), msg).await?;
+        }
+        Ok(())
+    }
+
+    async fn transmit_to_relayer(
+        &self,
+        ctx: Context,
+        addr: Address,
+        msg: MlmMsg<T>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        for msg in self.tamper(msg)? {
+            self.inner
+                .transmit_to_relayer(ctx.clone(), addr.clone(), msg)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn network_ready(&self, ctx: Context, authority_list: Vec<Node>) -> bool {
+        self.inner.network_ready(ctx, authority_list).await
+    }
+
+    async fn check_availability(&self, ctx: Context, height: u64, hash: Hash) -> bool {
+        self.inner.check_availability(ctx, height, hash).await
+    }
+
+    async fn extend_vote(
+        &self,
+        ctx: Context,
+        height: u64,
+        round: u64,
+        hash: Hash,
+    ) -> Result<Bytes, Box<dyn Error + Send>> {
+        self.inner.extend_vote(ctx, height, round, hash).await
+    }
+
+    async fn verify_vote_extension(
+        &self,
+        ctx: Context,
+        height: u64,
+        round: u64,
+        hash: Hash,
+        voter: Address,
+        extension: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        self.inner
+            .verify_vote_extension(ctx, height, round, hash, voter, extension)
+            .await
+    }
+
+    fn report_error(&self, ctx: Context, error: ConsensusError) {
+        self.inner.report_error(ctx, error)
+    }
+
+    fn report_view_change(
+        &self,
+        ctx: Context,
+        height: u64,
+        round: u64,
+        reason: ViewChangeReason,
+    ) {
+        self.inner.report_view_change(ctx, height, round, reason)
+    }
+
+    fn report_stall(&self, ctx: Context, dump: StallDump) {
+        self.inner.report_stall(ctx, dump)
+    }
+
+    fn report_connectivity(&self, ctx: Context, summary: ConnectivitySummary) {
+        self.inner.report_connectivity(ctx, summary)
+    }
+
+    fn report_brake(&self, ctx: Context, report: BrakeReport) {
+        self.inner.report_brake(ctx, report)
+    }
+
+    fn on_consensus_halted(&self, ctx: Context, report: HaltReport) {
+        self.inner.on_consensus_halted(ctx, report)
+    }
+
+    fn on_max_rounds(&self, ctx: Context, report: RoundLimitReport) {
+        self.inner.on_max_rounds(ctx, report)
+    }
+
+    fn report_view_change_info(&self, ctx: Context, info: ViewChangeInfo) {
+        self.inner.report_view_change_info(ctx, info)
+    }
+
+    fn report_invalid_msg(
+        &self,
+        ctx: Context,
+        peer_hint: Option<String>,
+        kind: String,
+        reason: String,
+    ) {
+        self.inner.report_invalid_msg(ctx, peer_hint, kind, reason)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use parking_lot::Mutex;
+
+    use super::ByzantineConfig;
+    use crate::testing::{SimConfig, Simulator};
+    use crate::types::ViewChangeReason;
+    use crate::{ConsensusEvent, ConsensusEventSink, Context, Step};
+
+    #[derive(Debug, Default)]
+    struct RecordingSink(Mutex<Vec<ConsensusEvent>>);
+
+    impl ConsensusEventSink for RecordingSink {
+        fn on_event(&self, _ctx: Context, event: ConsensusEvent) {
+            self.0.lock().push(event);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_byzantine_node() {
+        let mut sim = Simulator::new(SimConfig {
+            seed: 3,
+            byzantine: vec![(
+                3,
+                ByzantineConfig {
+                    equivocate: true,
+                    invalid_qcs: true,
+                    random_votes: true,
+                    seed: 3,
+                    ..ByzantineConfig::default()
+                },
+            )],
+            ..SimConfig::default()
+        });
+        sim.start();
+
+        // The honest nodes are a quorum, and they never commit different blocks.
+        assert!(sim.run_until(&[0, 1, 2], 5, Duration::from_secs(120)).await);
+        assert!(sim.check_safety().is_ok());
+
+        let stats = sim.byzantine_stats(3).unwrap();
+        assert!(stats.equivocations > 0);
+        assert!(stats.random_votes > 0);
+        assert!(stats.invalid_qcs > 0);
+        assert!(sim.byzantine_stats(0).is_none());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_event_sink() {
+        let sink = Arc::new(RecordingSink::default());
+        let mut sim = Simulator::new(SimConfig {
+            seed: 5,
+            byzantine: vec![(
+                3,
+                ByzantineConfig {
+                    invalid_qcs: true,
+                    seed: 5,
+                    ..ByzantineConfig::default()
+                },
+            )],
+            event_sinks: vec![(0, sink.clone())],
+            ..SimConfig::default()
+        });
+        sim.start();
+        assert!(sim.run_until(&[0, 1, 2], 5, Duration::from_secs(120)).await);
+        sim.stop();
+
+        // The QCs of the byzantine leader have no voters.
+        let byzantine = sim.nodes()[3].address.clone();
+        let events = sink.0.lock().clone();
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|event| matches!(
+            event,
+            ConsensusEvent::QcBelowThreshold { leader, .. } if *leader == byzantine
+        )));
+
+        // The adapter learns the peer sending them.
+        let reports = sim.invalid_msg_reports(0);
+        assert_eq!(reports.len(), events.len());
+        assert!(reports.iter().all(
+            |(peer, kind)| peer.as_deref() == Some("3") && kind == "Aggregated Vote"
+        ));
+    }
+
+    #[cfg(feature = "multi_proposal")]
+    #[tokio::test(start_paused = true)]
+    async fn test_conflicting_backup_proposals() {
+        let mut config = SimConfig {
+            seed: 13,
+            byzantine: vec![(
+                0,
+                ByzantineConfig {
+                    withhold_proposals: true,
+                    ..ByzantineConfig::default()
+                },
+            )],
+            ..SimConfig::default()
+        };
+        config.mlm_config.set_multi_proposal(4, 0);
+        config.mlm_config.set_height_report(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // Each of the backups proposes its own block in the rounds of the silent proposer, and
+        // the nodes prevote for the one of the lowest rank that they have got by the wait. No
+        // round times out for the missing proposal.
+        assert!(sim.run_until(&[1, 2, 3], 8, Duration::from_secs(120)).await);
+        assert!(sim.byzantine_stats(0).unwrap().withheld_proposals > 0);
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        let silent = &sim.nodes()[0].address;
+        let backup = &sim.nodes()[1].address;
+        for index in 1..4 {
+            let reports = sim.height_reports(index);
+            assert!(reports.iter().any(|report| &report.proposer == backup));
+            assert!(reports.iter().all(|report| &report.proposer != silent));
+            assert!(sim
+                .view_change_infos(index)
+                .iter()
+                .all(|info| info.reason != ViewChangeReason::NoProposalFromNetwork));
+        }
+    }
+
+    #[cfg(feature = "multi_proposal")]
+    #[tokio::test(start_paused = true)]
+    async fn test_split_backup_proposals() {
+        // The addresses of the nodes depend on their indexes only.
+        let nodes = Simulator::new(SimConfig::default()).nodes().to_vec();
+        let mut config = SimConfig {
+            seed: 17,
+            byzantine: vec![(
+                1,
+                ByzantineConfig {
+                    split_proposals: vec![nodes[2].address.clone()],
+                    ..ByzantineConfig::default()
+                },
+            )],
+            ..SimConfig::default()
+        };
+        config.mlm_config.set_multi_proposal(4, 0);
+        config.mlm_config.set_height_report(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The isolated proposer leaves its rounds to the byzantine backup, which sends two
+        // candidates of its rank to the two other nodes. The node of the conflicting one fails
+        // to check its block, and the rounds of the split never commit two blocks.
+        sim.partition(&[&[1, 2, 3]]);
+        assert!(sim.run_until(&[2, 3], 6, Duration::from_secs(300)).await);
+        assert!(sim.byzantine_stats(1).unwrap().equivocations > 0);
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        let failed_checks = |index| {
+            sim.view_change_infos(index)
+                .into_iter()
+                .filter(|info| info.reason == ViewChangeReason::CheckBlockNotPass)
+                .count()
+        };
+        assert!(failed_checks(2) > 0);
+        assert_eq!(failed_checks(3), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_withhold_proposals() {
+        let mut sim = Simulator::new(SimConfig {
+            seed: 5,
+            byzantine: vec![(
+                0,
+                ByzantineConfig {
+                    withhold_proposals: true,
+                    ..ByzantineConfig::default()
+                },
+            )],
+            ..SimConfig::default()
+        });
+        sim.start();
+
+        // The rounds of the silent proposer time out and are taken over by the others.
+        assert!(sim.run_until(&[1, 2, 3], 6, Duration::from_secs(120)).await);
+        assert!(sim.byzantine_stats(0).unwrap().withheld_proposals > 0);
+        assert!(sim.view_changes(1) > 0);
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // The others time out in the propose step waiting for the silent proposer.
+        let silent = sim
+            .view_change_infos(1)
+            .into_iter()
+            .filter(|info| info.reason == ViewChangeReason::NoProposalFromNetwork)
+            .collect::<Vec<_>>();
+        assert!(!silent.is_empty());
+        for info in silent {
+            assert_eq!(info.expected_proposer, sim.nodes()[0].address);
+            assert_eq!(info.step_elapsed[0].0, Step::Propose);
+            assert!(info.step_elapsed[0].1 > Duration::ZERO);
+        }
+    }
+
+    // The heights that the silent proposer fails in are of the rotation only.
+    #[cfg(not(feature = "random_leader"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_skip_failing_proposer() {
+        let config = SimConfig {
+            seed: 5,
+            byzantine: vec![(
+                0,
+                ByzantineConfig {
+                    withhold_proposals: true,
+                    ..ByzantineConfig::default()
+                },
+            )],
+            proposer_skip: (2, 100),
+            ..SimConfig::default()
+        };
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[1, 2, 3], 30, Duration::from_secs(300))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // The silent proposer times out two heights, and proposes after the others since.
+        let silent = sim
+            .view_change_infos(1)
+            .into_iter()
+            .filter(|info| info.expected_proposer == sim.nodes()[0].address)
+            .map(|info| info.height)
+            .collect::<Vec<_>>();
+        assert_eq!(silent.len(), 2);
+        assert!(sim.view_changes(1) <= 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_force_view_change() {
+        let silent = ByzantineConfig {
+            withhold_proposals: true,
+            ..ByzantineConfig::default()
+        };
+        let mut sim = Simulator::new(SimConfig {
+            seed: 5,
+            byzantine: (0..4).map(|index| (index, silent.clone())).collect(),
+            ..SimConfig::default()
+        });
+        sim.start();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        for index in 0..4 {
+            sim.force_view_change(index, "dead proposer");
+        }
+
+        // The round changes by the chokes long before the propose timeout.
+        tokio::time::sleep(Duration::from_millis(900)).await;
+        sim.stop();
+        let infos = sim.view_change_infos(1);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].round, 0);
+        assert_eq!(
+            infos[0].reason,
+            ViewChangeReason::OperatorTriggered("dead proposer".to_string())
+        );
+        assert_eq!(
+            sim.protocol_stats(1).view_changes.get("OperatorTriggered"),
+            Some(&1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_cert() {
+        let mut config = SimConfig {
+            seed: 7,
+            drop_rate: 0.2,
+            byzantine: vec![(
+                0,
+                ByzantineConfig {
+                    withhold_proposals: true,
+                    ..ByzantineConfig::default()
+                },
+            )],
+            ..SimConfig::default()
+        };
+        config.mlm_config.set_timeout_cert(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The nodes missing the chokes of a silent round jump by the certificate of the next
+        // proposal.
+        assert!(sim.run_until(&[1, 2, 3], 6, Duration::from_secs(300)).await);
+        assert!(sim.byzantine_stats(0).unwrap().withheld_proposals > 0);
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+}
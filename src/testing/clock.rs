@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+const PPM: i128 = 1_000_000;
+
+/// The local clock of a simulated node, which disagrees with the virtual time of the
+/// simulator. The clock reads the virtual time plus the skew, and runs faster by the drift, so
+/// the timeouts and the height interval of the node are measured by its own clock and the
+/// block timestamps of the node are taken from it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimClock {
+    /// Milliseconds that the clock is ahead of the virtual time at the start, negative if it
+    /// is behind.
+    pub skew: i64,
+    /// The parts per million that the clock runs faster than the virtual time, negative if it
+    /// runs slower. It must be above minus one million.
+    pub drift_ppm: i64,
+}
+
+impl SimClock {
+    /// The milliseconds read by the clock, which starts at zero plus the skew when the
+    /// simulator starts at `start`.
+    pub fn now(&self, start: Instant) -> i64 {
+        let elapsed = Instant::now().saturating_duration_since(start).as_nanos() as i128;
+        let local = elapsed * (PPM + self.drift_ppm as i128) / PPM;
+        self.skew + (local / 1_000_000) as i64
+    }
+
+    /// The virtual time that passes while the clock measures the duration.
+    pub fn to_virtual(&self, local: Duration) -> Duration {
+        let rate = (PPM + self.drift_ppm as i128).max(1);
+        let nanos = local.as_nanos() as i128 * PPM / rate;
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::time::{advance, Instant};
+
+    use super::SimClock;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sim_clock() {
+        let start = Instant::now();
+        let clock = SimClock {
+            skew: -300,
+            drift_ppm: 250_000,
+        };
+        assert_eq!(clock.now(start), -300);
+        advance(Duration::from_secs(4)).await;
+        assert_eq!(clock.now(start), 4700);
+        assert_eq!(
+            clock.to_virtual(Duration::from_millis(1000)),
+            Duration::from_millis(800)
+        );
+
+        let clock = SimClock::default();
+        assert_eq!(clock.now(start), 4000);
+        assert_eq!(
+            clock.to_virtual(Duration::from_millis(1000)),
+            Duration::from_millis(1000)
+        );
+    }
+}
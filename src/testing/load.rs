@@ -0,0 +1,148 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::{Latency, SimConfig, Simulator};
+
+/// The virtual time that a height of a load run is committed in, or the run fails.
+const HEIGHT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The options of a load run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadConfig {
+    /// The number of the validators.
+    pub validators: usize,
+    /// The number of the heights measured, after the first height that warms up the caches
+    /// and the channels of the nodes.
+    pub heights: u64,
+    /// The fixed latency of the simulated network in milliseconds.
+    pub latency: u64,
+}
+
+impl LoadConfig {
+    /// Create the options of the validators and the heights over a network of 10ms.
+    pub fn new(validators: usize, heights: u64) -> Self {
+        LoadConfig {
+            validators,
+            heights,
+            latency: 10,
+        }
+    }
+}
+
+/// The throughput of a load run. The network and the timers run in the paused virtual time,
+/// so the wall time of a run is spent by the engines and the mock crypto of the nodes, and a
+/// regression of the state or of the verification shows as a lower throughput.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadReport {
+    /// The number of the validators.
+    pub validators: usize,
+    /// The number of the heights measured.
+    pub heights: u64,
+    /// The number of the rounds run by the first node in the measured heights.
+    pub rounds: u64,
+    /// The wall time of the measured heights.
+    pub elapsed: Duration,
+    /// The 99th percentile of the wall time of the rounds, where a height of several rounds
+    /// counts its time evenly to each of them.
+    pub p99_round: Duration,
+    /// The number of the signature verifications of all of the nodes in the measured heights.
+    pub verifications: u64,
+}
+
+impl LoadReport {
+    /// The committed heights per second of the wall time.
+    pub fn commits_per_sec(&self) -> f64 {
+        self.heights as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// The signature verifications per second of the wall time.
+    pub fn verifications_per_sec(&self) -> f64 {
+        self.verifications as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} validators, {} heights of {} rounds in {:?}: {:.1} commits/s, \
+             p99 round {:?}, {:.0} verifications/s",
+            self.validators,
+            self.heights,
+            self.rounds,
+            self.elapsed,
+            self.commits_per_sec(),
+            self.p99_round,
+            self.verifications_per_sec()
+        )
+    }
+}
+
+/// Run the validators of the config in a simulator until they commit the heights, and measure
+/// them in the wall time. As the simulator, it runs in a current thread runtime with the
+/// paused time. Return `None` if a height is not committed in ten minutes of virtual time.
+pub async fn run(config: &LoadConfig) -> Option<LoadReport> {
+    let mut sim = Simulator::new(SimConfig {
+        nodes: config.validators,
+        latency: Latency::Fixed(config.latency),
+        ..SimConfig::default()
+    });
+    sim.start();
+    let report = measure(&sim, config).await;
+    sim.stop();
+    report
+}
+
+async fn measure(sim: &Simulator, config: &LoadConfig) -> Option<LoadReport> {
+    let nodes = (0..config.validators).collect::<Vec<_>>();
+    if !sim.run_until(&nodes, 1, HEIGHT_TIMEOUT).await {
+        return None;
+    }
+
+    let verifications = sim.verifications();
+    let first_rounds = sim.protocol_stats(0).rounds;
+    let mut rounds = first_rounds;
+    let mut round_times = Vec::new();
+    let start = Instant::now();
+    for height in 2..=config.heights + 1 {
+        let height_start = Instant::now();
+        if !sim.run_until(&nodes, height, HEIGHT_TIMEOUT).await {
+            return None;
+        }
+        let elapsed = height_start.elapsed();
+        let total = sim.protocol_stats(0).rounds;
+        let height_rounds = total.saturating_sub(rounds).max(1);
+        rounds = total;
+        let round_time = elapsed / height_rounds as u32;
+        round_times.extend((0..height_rounds).map(|_| round_time));
+    }
+    let elapsed = start.elapsed();
+
+    round_times.sort();
+    let p99 = (round_times.len() * 99).div_ceil(100).saturating_sub(1);
+    Some(LoadReport {
+        validators: config.validators,
+        heights: config.heights,
+        rounds: rounds - first_rounds,
+        elapsed,
+        p99_round: round_times.get(p99).copied().unwrap_or_default(),
+        verifications: sim.verifications() - verifications,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{run, LoadConfig};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_load_run() {
+        let report = run(&LoadConfig::new(4, 3)).await.unwrap();
+        assert_eq!(report.validators, 4);
+        assert_eq!(report.heights, 3);
+        assert!(report.rounds >= 3);
+        assert!(report.p99_round <= report.elapsed);
+        // Each height verifies the votes of the validators at least.
+        assert!(report.verifications >= 3 * 4);
+        assert!(report.commits_per_sec() > 0.0);
+    }
+}
@@ -0,0 +1,3620 @@
+/// The byzantine behaviors of a node to test the evidence handling against.
+pub mod byzantine;
+/// The local clocks of the nodes which disagree with the virtual time.
+pub mod clock;
+/// The throughput of the simulated validators in the wall time.
+pub mod load;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::Infallible;
+use std::error::Error;
+#[cfg(feature = "rocksdb")]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::channel::mpsc::Receiver;
+use parking_lot::Mutex;
+use rand_core::{RngCore, SeedableRng};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
+use tokio::time::{sleep, Instant};
+
+use crate::error::{ConsensusError, ConsensusExit};
+use crate::event_sink::ConsensusEventSink;
+use crate::proof::ProofStore;
+use crate::telemetry::Trace;
+use crate::types::{
+    Address, AggregatedSignature, AllowEmptyBlock, Commit, Hash, MlmMsg, Node, Proof,
+    Signature, SignedAttestation, Status, ViewChangeInfo, ViewChangeReason,
+};
+use crate::{
+    AsyncCrypto, Codec, Consensus, ConsensusResult, Context, Crypto, DurationConfig,
+    MlmBuilder, MlmConfig, MlmHandler, SeededRng, Wal, WalCipher,
+};
+use crate::{
+    BrakeReport, ConnectivitySummary, ConsensusSnapshot, ExpiredHeight, HaltReport,
+    HeightReport, MlmError, MlmEvent, ProposerReliability, ProtocolStats, QcGossipStats,
+    RoundLimitReport, StallDump, StateDump, ValidatorLiveness,
+};
+#[cfg(feature = "rocksdb")]
+use crate::{RocksWal, RocksWalDB};
+
+use self::byzantine::{Byzantine, ByzantineConfig, ByzantineStats};
+use self::clock::SimClock;
+
+/// The heights and the hashes committed by each node.
+type Commits = Vec<Vec<(u64, Hash)>>;
+
+type SimByzantine = Byzantine<SimBlock, SimAdapter, SimCrypto>;
+/// The peer hints and the kinds of the invalid messages reported by each node.
+type InvalidMsgs = Arc<Mutex<Vec<Vec<(Option<String>, String)>>>>;
+
+/// The latency distribution of the simulated network, in milliseconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Latency {
+    /// Every message takes the same latency.
+    Fixed(u64),
+    /// The latency of a message is uniform in `[min, max]`.
+    Uniform {
+        /// The min latency.
+        min: u64,
+        /// The max latency.
+        max: u64,
+    },
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut Pcg64) -> Duration {
+        let millis = match self {
+            Latency::Fixed(latency) => *latency,
+            Latency::Uniform { min, max } if max > min => {
+                min + rng.next_u64() % (max - min + 1)
+            }
+            Latency::Uniform { min, .. } => *min,
+        };
+        Duration::from_millis(millis)
+    }
+}
+
+/// The configuration of a simulation.
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    /// The number of the nodes, which are all authorities.
+    pub nodes: usize,
+    /// The seed of the network randomness, and of the engine randomness of each node offset
+    /// by its index.
+    pub seed: u64,
+    /// The height interval in milliseconds.
+    pub interval: u64,
+    /// The latency distribution of the messages.
+    pub latency: Latency,
+    /// The probability that a message is dropped.
+    pub drop_rate: f64,
+    /// The probability that a message is delivered twice.
+    pub duplicate_rate: f64,
+    /// The config of the nodes.
+    pub mlm_config: MlmConfig,
+    /// The indexes of the byzantine nodes with their behaviors.
+    pub byzantine: Vec<(usize, ByzantineConfig)>,
+    /// The indexes of the nodes with their local clocks. The other nodes keep the virtual
+    /// time.
+    pub clocks: Vec<(usize, SimClock)>,
+    /// The max milliseconds that the timestamp of a block can be ahead of the clock of a node
+    /// checking it. Zero accepts any timestamp.
+    pub timestamp_tolerance: u64,
+    /// The indexes of the nodes whose signers are locked at the start with the milliseconds
+    /// of the virtual time until they unlock.
+    pub locked_signers: Vec<(usize, u64)>,
+    /// The indexes of the nodes that sign by a remote signer with the milliseconds of the
+    /// virtual time that each signature takes.
+    pub slow_signers: Vec<(usize, u64)>,
+    /// The indexes of the nodes that never publish the data of their blocks, whose blocks
+    /// the other nodes refuse to prevote for in the compact proposal mode.
+    pub withheld_data: Vec<usize>,
+    /// The indexes of the nodes that send each of their votes in a vote batch.
+    pub vote_batches: Vec<usize>,
+    /// The indexes of the nodes that extend their precommits with the payloads of the wrong
+    /// heights, which the other nodes reject.
+    pub bad_extensions: Vec<usize>,
+    /// The indexes of the nodes with the telemetry that their spans are reported to.
+    pub traces: Vec<(usize, Arc<dyn Trace>)>,
+    /// The indexes of the nodes with the sinks that their events of the invalid messages are
+    /// sent to.
+    pub event_sinks: Vec<(usize, Arc<dyn ConsensusEventSink>)>,
+    /// The indexes of the nodes with the stores that their proofs are saved to.
+    pub proof_stores: Vec<(usize, Arc<dyn ProofStore>)>,
+    /// The indexes of the nodes with the ciphers that their wal records are encrypted by.
+    pub wal_ciphers: Vec<(usize, Arc<dyn WalCipher>)>,
+    /// The path of a rocksdb that the wals of the nodes keep their records in besides the
+    /// memory, and load them from. The wal of a node is opened again when it restarts, which
+    /// must keep the wal information.
+    #[cfg(feature = "rocksdb")]
+    pub rocks_wal: Option<PathBuf>,
+    /// The epochs after the first one with their first heights and the indexes of their
+    /// authorities. The first epoch is of all the nodes, and the nodes out of an epoch keep
+    /// running without the consensus power.
+    pub epochs: Vec<(u64, Vec<usize>)>,
+    /// The milliseconds of the virtual time until which the nodes have nothing to propose,
+    /// and return `ConsensusError::NothingToPropose` unless an empty block is allowed.
+    pub idle_until: u64,
+    /// The indexes of the nodes whose signature verifications panic.
+    pub panicking_verifiers: Vec<usize>,
+    /// The timer config of the nodes. The classic ratios are used without it.
+    pub timer_config: Option<DurationConfig>,
+    /// The heights with the timer configs that the statuses returned by their commits carry.
+    pub timer_updates: Vec<(u64, DurationConfig)>,
+    /// The number of the heights in a row that a proposer fails in, after which the nodes
+    /// skip it in the next number of the heights, by a `ProposerReliability` over the rounds
+    /// of the first proofs of the heights, as the proofs in a chain. Zero failures skips no
+    /// proposer.
+    pub proposer_skip: (u64, u64),
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            nodes: 4,
+            seed: 0,
+            interval: 1000,
+            latency: Latency::Uniform { min: 10, max: 100 },
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+            mlm_config: MlmConfig::default(),
+            byzantine: Vec::new(),
+            clocks: Vec::new(),
+            timestamp_tolerance: 0,
+            locked_signers: Vec::new(),
+            slow_signers: Vec::new(),
+            withheld_data: Vec::new(),
+            vote_batches: Vec::new(),
+            bad_extensions: Vec::new(),
+            traces: Vec::new(),
+            event_sinks: Vec::new(),
+            proof_stores: Vec::new(),
+            wal_ciphers: Vec::new(),
+            #[cfg(feature = "rocksdb")]
+            rocks_wal: None,
+            epochs: Vec::new(),
+            idle_until: 0,
+            panicking_verifiers: Vec::new(),
+            timer_config: None,
+            timer_updates: Vec::new(),
+            proposer_skip: (0, 0),
+        }
+    }
+}
+
+/// The authority lists of the epochs of a simulation, and the proposers skipped at the
+/// heights.
+#[derive(Clone, Debug)]
+struct SimEpochs {
+    nodes: Vec<Node>,
+    epochs: Vec<(u64, Vec<usize>)>,
+    skips: Arc<Mutex<SimSkips>>,
+}
+
+/// The proposers skipped at the heights, which are decided once for each height by its
+/// first proof, so that all of the nodes skip the same proposers.
+#[derive(Debug)]
+struct SimSkips {
+    reliability: ProposerReliability,
+    skipped: BTreeMap<u64, Vec<Address>>,
+}
+
+impl SimEpochs {
+    /// The authority list of the epoch of the height.
+    fn authority_list(&self, height: u64) -> Vec<Node> {
+        match self
+            .epochs
+            .iter()
+            .filter(|(start, _)| *start <= height)
+            .max_by_key(|(start, _)| *start)
+        {
+            Some((_, indexes)) => indexes
+                .iter()
+                .map(|index| self.nodes[*index].clone())
+                .collect(),
+            None => self.nodes.clone(),
+        }
+    }
+
+    /// Count the proof of the height if it is the first one, which decides the proposers
+    /// skipped at the next height.
+    fn commit(&self, height: u64, round: u64) {
+        let mut skips = self.skips.lock();
+        if skips.skipped.contains_key(&(height + 1)) {
+            return;
+        }
+        let authority_list = self.authority_list(height);
+        let _ = skips.reliability.commit(height, round, &authority_list, 0);
+        let skipped = skips.reliability.skipped(height + 1);
+        skips.skipped.insert(height + 1, skipped);
+    }
+
+    /// Set the authority list of the height of the status, the one of the next epoch if the
+    /// height is the last of an epoch, and the proposers skipped at the height.
+    fn apply(&self, status: &mut Status) {
+        status.authority_list = self.authority_list(status.height);
+        status.skipped_proposers = self
+            .skips
+            .lock()
+            .skipped
+            .get(&status.height)
+            .cloned()
+            .unwrap_or_default();
+        status.next_authority_list = self
+            .epochs
+            .iter()
+            .any(|(start, _)| *start == status.height + 1)
+            .then(|| self.authority_list(status.height + 1));
+    }
+}
+
+/// The counters of the simulated network.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    /// The messages delivered, including the duplicates.
+    pub delivered: u64,
+    /// The messages dropped by the drop rate.
+    pub dropped: u64,
+    /// The messages duplicated by the duplicate rate.
+    pub duplicated: u64,
+    /// The messages blocked by the partition.
+    pub partitioned: u64,
+    /// The times that a node behind its group is synchronized.
+    pub synced: u64,
+}
+
+/// A deterministic simulator of mlm nodes in one process. The nodes talk over a simulated
+/// network with a seeded randomness, which delays, drops and duplicates the messages and
+/// blocks them across a partition. As the block sync of an application does, a node behind
+/// the other nodes of its group is synchronized to their height every half an interval. The
+/// byzantine nodes send their messages through a `Byzantine` adapter. The simulator is meant
+/// to run in a current thread runtime with the paused time, such as
+/// `#[tokio::test(start_paused = true)]`, where the timers of the nodes and the latencies of
+/// the network run in virtual time, and a run is reproduced by its seed. The nodes measure
+/// their timeouts and stamp their blocks by the local clocks of `SimConfig::clocks`, which
+/// disagree with the virtual time.
+pub struct Simulator {
+    config: SimConfig,
+    nodes: Vec<Node>,
+    epochs: SimEpochs,
+    network: Arc<Network>,
+    commits: Arc<Mutex<Commits>>,
+    view_changes: Arc<Mutex<Vec<Vec<ViewChangeInfo>>>>,
+    rejected_timestamps: Arc<Mutex<Vec<u64>>>,
+    published: Arc<Mutex<HashSet<Hash>>>,
+    stalls: Arc<Mutex<Vec<Vec<StallDump>>>>,
+    connectivity: Arc<Mutex<Vec<Vec<ConnectivitySummary>>>>,
+    brakes: Arc<Mutex<Vec<Vec<BrakeReport>>>>,
+    halts: Arc<Mutex<Vec<Vec<HaltReport>>>>,
+    round_limits: Arc<Mutex<Vec<Vec<RoundLimitReport>>>>,
+    invalid_msgs: InvalidMsgs,
+    pre_checks: Arc<Mutex<Commits>>,
+    height_reports: Arc<Mutex<Vec<Vec<HeightReport>>>>,
+    archived: Arc<Mutex<Vec<Vec<ExpiredHeight<SimBlock>>>>>,
+    attestations: Arc<Mutex<Vec<Vec<SignedAttestation>>>>,
+    exits: Arc<Mutex<Vec<Option<ConsensusExit>>>>,
+    verifications: Arc<AtomicU64>,
+    wals: Vec<Arc<SimWal>>,
+    #[cfg(feature = "rocksdb")]
+    rocks_db: Option<Arc<RocksWalDB>>,
+    byzantine: Vec<Option<Arc<SimByzantine>>>,
+    clocks: Vec<SimClock>,
+    start: Instant,
+    started: bool,
+}
+
+impl Simulator {
+    /// Create a simulator by the config.
+    pub fn new(config: SimConfig) -> Self {
+        let nodes = (0..config.nodes)
+            .map(|i| {
+                let address = Address::try_from(format!("sim-node-{}", i).into_bytes());
+                Node::new(address.unwrap())
+            })
+            .collect::<Vec<_>>();
+        let network = Arc::new(Network {
+            inner: Mutex::new(NetworkInner {
+                rng: Pcg64::seed_from_u64(config.seed),
+                groups: vec![0; config.nodes],
+                handlers: Vec::new(),
+                stats: NetworkStats::default(),
+                stopped: false,
+            }),
+            latency: config.latency.clone(),
+            drop_rate: config.drop_rate,
+            duplicate_rate: config.duplicate_rate,
+        });
+        let mut clocks = vec![SimClock::default(); config.nodes];
+        for (index, clock) in config.clocks.iter() {
+            assert!(
+                clock.drift_ppm > -1_000_000,
+                "Simulated clock runs backward"
+            );
+            clocks[*index] = *clock;
+        }
+
+        let (failures, heights) = config.proposer_skip;
+        let epochs = SimEpochs {
+            nodes: nodes.clone(),
+            epochs: config.epochs.clone(),
+            skips: Arc::new(Mutex::new(SimSkips {
+                reliability: ProposerReliability::new(failures, heights),
+                skipped: BTreeMap::new(),
+            })),
+        };
+
+        let mut sim = Simulator {
+            commits: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            view_changes: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            rejected_timestamps: Arc::new(Mutex::new(vec![0; config.nodes])),
+            published: Arc::new(Mutex::new(HashSet::new())),
+            stalls: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            connectivity: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            brakes: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            halts: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            round_limits: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            invalid_msgs: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            pre_checks: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            height_reports: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            archived: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            attestations: Arc::new(Mutex::new(vec![Vec::new(); config.nodes])),
+            exits: Arc::new(Mutex::new(vec![None; config.nodes])),
+            verifications: Arc::new(AtomicU64::new(0)),
+            wals: Vec::new(),
+            #[cfg(feature = "rocksdb")]
+            rocks_db: config.rocks_wal.as_ref().map(|path| {
+                let mut opts = rocksdb::Options::default();
+                opts.create_if_missing(true);
+                Arc::new(RocksWalDB::open(&opts, path).expect("Open the rocksdb"))
+            }),
+            byzantine: vec![None; config.nodes],
+            clocks,
+            start: Instant::now(),
+            config,
+            nodes,
+            epochs,
+            network,
+            started: false,
+        };
+        sim.wals = (0..sim.config.nodes)
+            .map(|index| sim.new_wal(index, true))
+            .collect();
+        sim
+    }
+
+    /// The authority list of the nodes.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Start all of the nodes from the height 1. This must be called in a tokio runtime.
+    pub fn start(&mut self) {
+        assert!(!self.started, "Simulator started twice");
+        self.started = true;
+        self.start = Instant::now();
+
+        let handlers = (0..self.nodes.len())
+            .map(|index| self.launch(index, None))
+            .collect();
+        self.network.inner.lock().handlers = handlers;
+
+        let network = Arc::clone(&self.network);
+        let commits = Arc::clone(&self.commits);
+        let statuses = (0..self.nodes.len())
+            .map(|index| Status {
+                height: 0,
+                interval: Some(self.interval(index)),
+                timer_config: None,
+                authority_list: self.nodes.clone(),
+                next_authority_list: None,
+                proposer_seed: None,
+                skipped_proposers: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let epochs = self.epochs.clone();
+        let period = Duration::from_millis(self.config.interval / 2 + 1);
+        tokio::spawn(async move {
+            while !network.inner.lock().stopped {
+                sleep(period).await;
+                network.sync(&commits, &statuses, &epochs);
+            }
+        });
+    }
+
+    /// The counters of the tampered messages of the node, if it is byzantine.
+    pub fn byzantine_stats(&self, index: usize) -> Option<ByzantineStats> {
+        self.byzantine[index]
+            .as_ref()
+            .map(|byzantine| byzantine.stats())
+    }
+
+    /// Stop all of the nodes.
+    pub fn stop(&self) {
+        let mut inner = self.network.inner.lock();
+        inner.stopped = true;
+        for handler in inner.handlers.iter() {
+            let _ = handler.stop();
+        }
+    }
+
+    /// Stop the node, as an application does by `MlmHandler::stop()`, while the others keep
+    /// running. Return `Err()` if the node is stopped already.
+    pub fn stop_node(&self, index: usize) -> ConsensusResult<()> {
+        self.network.inner.lock().handlers[index].stop()
+    }
+
+    /// Partition the nodes into the groups of their indexes. The messages across the groups
+    /// are blocked, and a node in no group is isolated.
+    pub fn partition(&self, groups: &[&[usize]]) {
+        let mut inner = self.network.inner.lock();
+        let isolated = groups.len();
+        for (index, group) in inner.groups.iter_mut().enumerate() {
+            *group = isolated + index;
+        }
+        for (id, group) in groups.iter().enumerate() {
+            for index in group.iter() {
+                inner.groups[*index] = id;
+            }
+        }
+    }
+
+    /// Force the current round of the node to time out, as an operator does by
+    /// `MlmHandler::force_view_change()`.
+    pub fn force_view_change(&self, index: usize, reason: &str) {
+        let inner = self.network.inner.lock();
+        let _ = inner.handlers[index].force_view_change(reason.to_string());
+    }
+
+    /// Resend the signed proposal and the signed votes of the node in the current round, as
+    /// an application does by `MlmHandler::rebroadcast_current()`.
+    pub fn rebroadcast_current(&self, index: usize) {
+        let inner = self.network.inner.lock();
+        let _ = inner.handlers[index].rebroadcast_current();
+    }
+
+    /// The commit proof of the height in the proof archive of the node.
+    pub fn get_proof(&self, index: usize, height: u64) -> Option<Proof> {
+        self.network.inner.lock().handlers[index].get_proof(height)
+    }
+
+    /// Move the node to a new instance with a fresh wal by a snapshot of its consensus state,
+    /// as an operator moves a node to new hardware. The node is cut off the network until the
+    /// old instance stops, so nothing that it signs after the export is delivered.
+    pub async fn migrate(&mut self, index: usize) -> ConsensusSnapshot<SimBlock> {
+        let (handler, group) = {
+            let mut inner = self.network.inner.lock();
+            let group = inner.groups[index];
+            inner.groups[index] = usize::MAX;
+            (inner.handlers[index].clone(), group)
+        };
+        let snapshot = handler
+            .export_snapshot()
+            .await
+            .expect("Export the snapshot");
+        let _ = handler.stop();
+        sleep(Duration::from_millis(100)).await;
+
+        let handler = self.launch(index, Some(snapshot.clone()));
+        let mut inner = self.network.inner.lock();
+        inner.handlers[index] = handler;
+        inner.groups[index] = group;
+        snapshot
+    }
+
+    /// Restart the node on its wal, as after a crash, and return the dump of the new instance
+    /// taken before it rejoins the network. If the wal information is lost, the node recovers
+    /// from its latest checkpoint.
+    pub async fn restart(&mut self, index: usize, lose_wal: bool) -> StateDump {
+        let (handler, group) = {
+            let mut inner = self.network.inner.lock();
+            let group = inner.groups[index];
+            inner.groups[index] = usize::MAX;
+            (inner.handlers[index].clone(), group)
+        };
+        let _ = handler.stop();
+        sleep(Duration::from_millis(100)).await;
+        if lose_wal {
+            *self.wals[index].info.lock() = None;
+        }
+        #[cfg(feature = "rocksdb")]
+        if self.rocks_db.is_some() {
+            assert!(!lose_wal, "Lose the wal information in the rocksdb");
+            self.wals[index] = self.new_wal(index, false);
+        }
+
+        let handler = self.launch(index, None);
+        sleep(Duration::from_millis(100)).await;
+        let dump = handler.dump_state().await.expect("Dump the state");
+        let mut inner = self.network.inner.lock();
+        inner.handlers[index] = handler;
+        inner.groups[index] = group;
+        dump
+    }
+
+    /// Heal the partition.
+    pub fn heal(&self) {
+        let mut inner = self.network.inner.lock();
+        inner.groups.iter_mut().for_each(|group| *group = 0);
+    }
+
+    /// Wait until all of the given nodes commit the height, or the timeout passes in virtual
+    /// time. Return whether the height is reached.
+    pub async fn run_until(
+        &self,
+        nodes: &[usize],
+        height: u64,
+        timeout: Duration,
+    ) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if nodes.iter().all(|index| self.height(*index) >= height) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            sleep(Duration::from_millis(self.config.interval / 10 + 1)).await;
+        }
+    }
+
+    /// The latest height committed by the node.
+    pub fn height(&self, index: usize) -> u64 {
+        self.commits.lock()[index]
+            .last()
+            .map(|(height, _)| *height)
+            .unwrap_or(0)
+    }
+
+    /// The heights and the hashes committed by the node.
+    pub fn commits(&self, index: usize) -> Vec<(u64, Hash)> {
+        self.commits.lock()[index].clone()
+    }
+
+    /// The number of the view changes of the node.
+    pub fn view_changes(&self, index: usize) -> u64 {
+        self.view_changes.lock()[index].len() as u64
+    }
+
+    /// The details of the view changes of the node.
+    pub fn view_change_infos(&self, index: usize) -> Vec<ViewChangeInfo> {
+        self.view_changes.lock()[index].clone()
+    }
+
+    /// The milliseconds read by the local clock of the node.
+    pub fn local_time(&self, index: usize) -> i64 {
+        self.clocks[index].now(self.start)
+    }
+
+    /// The milliseconds between the most ahead and the most behind local clocks of the nodes.
+    pub fn clock_disagreement(&self) -> u64 {
+        let times = (0..self.nodes.len())
+            .map(|index| self.local_time(index))
+            .collect::<Vec<_>>();
+        let max = times.iter().max().copied().unwrap_or(0);
+        let min = times.iter().min().copied().unwrap_or(0);
+        max.abs_diff(min)
+    }
+
+    /// The number of the blocks refused by the node since their timestamps are too far ahead
+    /// of its local clock.
+    pub fn rejected_timestamps(&self, index: usize) -> u64 {
+        self.rejected_timestamps.lock()[index]
+    }
+
+    /// The stall dumps reported by the node.
+    pub fn stall_dumps(&self, index: usize) -> Vec<StallDump> {
+        self.stalls.lock()[index].clone()
+    }
+
+    /// The connectivity summaries reported by the node.
+    pub fn connectivity_reports(&self, index: usize) -> Vec<ConnectivitySummary> {
+        self.connectivity.lock()[index].clone()
+    }
+
+    /// The brakes reported by the node.
+    pub fn brake_reports(&self, index: usize) -> Vec<BrakeReport> {
+        self.brakes.lock()[index].clone()
+    }
+
+    /// The peer hints and the kinds of the invalid messages reported by the node.
+    pub fn invalid_msg_reports(&self, index: usize) -> Vec<(Option<String>, String)> {
+        self.invalid_msgs.lock()[index].clone()
+    }
+
+    /// The heights and the hashes of the blocks pre-checked by the node in order.
+    pub fn pre_checked_blocks(&self, index: usize) -> Vec<(u64, Hash)> {
+        self.pre_checks.lock()[index].clone()
+    }
+
+    /// The timing reports of the heights committed by the node.
+    pub fn height_reports(&self, index: usize) -> Vec<HeightReport> {
+        self.height_reports.lock()[index].clone()
+    }
+
+    /// The past heights archived by the node before they are evicted, in order.
+    pub fn archived_heights(&self, index: usize) -> Vec<ExpiredHeight<SimBlock>> {
+        self.archived.lock()[index].clone()
+    }
+
+    /// The signed attestations reported by the node in order.
+    pub fn attestations(&self, index: usize) -> Vec<SignedAttestation> {
+        self.attestations.lock()[index].clone()
+    }
+
+    /// The halts escalated by the node.
+    pub fn halt_reports(&self, index: usize) -> Vec<HaltReport> {
+        self.halts.lock()[index].clone()
+    }
+
+    /// The heights escalated by the node for running too many rounds.
+    pub fn round_limit_reports(&self, index: usize) -> Vec<RoundLimitReport> {
+        self.round_limits.lock()[index].clone()
+    }
+
+    /// The number of the times that the wal of the node is flushed, which is once each time
+    /// the engine stops.
+    pub fn wal_flushes(&self, index: usize) -> u64 {
+        self.wals[index].flushes.load(Ordering::Relaxed)
+    }
+
+    /// The reason that the engine of the node stopped, if it has.
+    pub fn exit(&self, index: usize) -> Option<ConsensusExit> {
+        self.exits.lock()[index].clone()
+    }
+
+    /// Subscribe the consensus events of the node. This must be called after `start()`.
+    pub fn subscribe(&self, index: usize, capacity: usize) -> Receiver<MlmEvent> {
+        self.network.inner.lock().handlers[index].subscribe(capacity)
+    }
+
+    /// Subscribe the errors of the node. This must be called after `start()`.
+    pub fn subscribe_errors(&self, index: usize, capacity: usize) -> Receiver<MlmError> {
+        self.network.inner.lock().handlers[index].subscribe_errors(capacity)
+    }
+
+    /// Send a status to the node, as the application does after a sync.
+    pub fn send_status(&self, index: usize, status: Status) {
+        let inner = self.network.inner.lock();
+        let _ =
+            inner.handlers[index].send_msg(Context::new(), MlmMsg::RichStatus(status));
+    }
+
+    /// Send a message to the node as if it is from the peer of the given index.
+    pub fn send_msg(&self, index: usize, from: usize, msg: MlmMsg<SimBlock>) {
+        let inner = self.network.inner.lock();
+        let _ =
+            inner.handlers[index].send_msg_from(Context::new(), from.to_string(), msg);
+    }
+
+    /// The protocol stats of the node.
+    pub fn protocol_stats(&self, index: usize) -> ProtocolStats {
+        self.network.inner.lock().handlers[index].protocol_stats()
+    }
+
+    /// The counters of the QCs aggregated by the node and learned from the network.
+    pub fn qc_gossip_stats(&self, index: usize) -> QcGossipStats {
+        self.network.inner.lock().handlers[index].qc_gossip_stats()
+    }
+
+    /// The liveness of the other nodes by the heartbeats received by the node.
+    pub fn validator_liveness(&self, index: usize) -> Vec<ValidatorLiveness> {
+        self.network.inner.lock().handlers[index].validator_liveness()
+    }
+
+    /// Check that no two nodes commit different blocks in a height.
+    pub fn check_safety(&self) -> Result<(), String> {
+        let mut committed: HashMap<u64, (usize, Hash)> = HashMap::new();
+        for (index, commits) in self.commits.lock().iter().enumerate() {
+            for (height, hash) in commits.iter() {
+                match committed.get(height) {
+                    Some((other, other_hash)) if other_hash != hash => {
+                        return Err(format!(
+                            "node {} and node {} commit different blocks in height {}",
+                            other, index, height
+                        ));
+                    }
+                    Some(_) => (),
+                    None => {
+                        committed.insert(*height, (index, hash.clone()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The counters of the network.
+    pub fn network_stats(&self) -> NetworkStats {
+        self.network.inner.lock().stats.clone()
+    }
+
+    /// The number of the signature verifications of all of the nodes, including the
+    /// aggregated ones.
+    pub fn verifications(&self) -> u64 {
+        self.verifications.load(Ordering::Relaxed)
+    }
+
+    /// The virtual time of the height interval measured by the local clock of the node.
+    fn interval(&self, index: usize) -> Duration {
+        self.clocks[index].to_virtual(Duration::from_millis(self.config.interval))
+    }
+
+    /// A wal of the node, which keeps its records in the rocksdb of the simulation if any. A
+    /// fresh wal drops the records of the node in the rocksdb.
+    #[cfg(feature = "rocksdb")]
+    fn new_wal(&self, index: usize, fresh: bool) -> Arc<SimWal> {
+        let db = match &self.rocks_db {
+            Some(db) => db,
+            None => return Arc::new(SimWal::default()),
+        };
+        let instance = format!("sim-node-{}", index);
+        if fresh {
+            let _ = db.drop_cf(&instance);
+        }
+        let rocks = RocksWal::with_db(Arc::clone(db), &instance).expect("Open the wal");
+        Arc::new(SimWal {
+            rocks: Some(rocks),
+            ..SimWal::default()
+        })
+    }
+
+    #[cfg(not(feature = "rocksdb"))]
+    fn new_wal(&self, _index: usize, _fresh: bool) -> Arc<SimWal> {
+        Arc::new(SimWal::default())
+    }
+
+    /// Launch the node with its wal, or with a fresh one from the snapshot if any.
+    fn launch(
+        &mut self,
+        index: usize,
+        snapshot: Option<ConsensusSnapshot<SimBlock>>,
+    ) -> MlmHandler<SimBlock> {
+        let node = self.nodes[index].clone();
+        if snapshot.is_some() {
+            self.wals[index] = self.new_wal(index, true);
+        }
+        let wal = Arc::clone(&self.wals[index]);
+        let adapter = Arc::new(SimAdapter {
+            index,
+            interval: self.interval(index),
+            authority_list: self.nodes.clone(),
+            epochs: self.epochs.clone(),
+            network: Arc::clone(&self.network),
+            commits: Arc::clone(&self.commits),
+            view_changes: Arc::clone(&self.view_changes),
+            clock: self.clocks[index],
+            start: self.start,
+            timestamp_tolerance: self.config.timestamp_tolerance,
+            rejected_timestamps: Arc::clone(&self.rejected_timestamps),
+            withhold_data: self.config.withheld_data.contains(&index),
+            batch_votes: self.config.vote_batches.contains(&index),
+            bad_extension: self.config.bad_extensions.contains(&index),
+            timer_updates: self.config.timer_updates.clone(),
+            published: Arc::clone(&self.published),
+            stalls: Arc::clone(&self.stalls),
+            connectivity: Arc::clone(&self.connectivity),
+            brakes: Arc::clone(&self.brakes),
+            halts: Arc::clone(&self.halts),
+            round_limits: Arc::clone(&self.round_limits),
+            invalid_msgs: Arc::clone(&self.invalid_msgs),
+            pre_checks: Arc::clone(&self.pre_checks),
+            height_reports: Arc::clone(&self.height_reports),
+            archived: Arc::clone(&self.archived),
+            attestations: Arc::clone(&self.attestations),
+            idle_until: Duration::from_millis(self.config.idle_until),
+        });
+        let unlock_at = self
+            .config
+            .locked_signers
+            .iter()
+            .find(|(locked, _)| *locked == index)
+            .map(|(_, millis)| self.start + Duration::from_millis(*millis));
+        let crypto = Arc::new(SimCrypto {
+            address: node.address.clone(),
+            unlock_at,
+            panics: self.config.panicking_verifiers.contains(&index),
+            verifications: Arc::clone(&self.verifications),
+        });
+
+        let byzantine = self
+            .config
+            .byzantine
+            .iter()
+            .find(|(byzantine, _)| *byzantine == index)
+            .map(|(_, config)| config.clone());
+        match byzantine {
+            Some(config) => {
+                let adapter =
+                    Arc::new(Byzantine::new(adapter, Arc::clone(&crypto), config));
+                self.byzantine[index] = Some(Arc::clone(&adapter));
+                self.spawn_node(index, &node, adapter, crypto, wal, snapshot)
+            }
+            None => self.spawn_node(index, &node, adapter, crypto, wal, snapshot),
+        }
+    }
+
+    fn spawn_node<F: Consensus<SimBlock> + 'static>(
+        &self,
+        index: usize,
+        node: &Node,
+        adapter: Arc<F>,
+        crypto: Arc<SimCrypto>,
+        wal: Arc<SimWal>,
+        snapshot: Option<ConsensusSnapshot<SimBlock>>,
+    ) -> MlmHandler<SimBlock> {
+        let mut builder = MlmBuilder::new(node.address.clone(), adapter, crypto, wal)
+            .config(self.config.mlm_config.clone())
+            .interval(self.interval(index))
+            .authority_list(self.epochs.authority_list(1))
+            .rng(Arc::new(SeededRng::new(
+                self.config.seed.wrapping_add(index as u64),
+            )));
+        if let Some(timer_config) = self.config.timer_config.clone() {
+            builder = builder.timer_config(timer_config);
+        }
+        let mlm = builder.build().expect("Invalid simulated config");
+        if let Some((_, trace)) = self.config.traces.iter().find(|(i, _)| *i == index) {
+            mlm.set_trace(Arc::clone(trace));
+        }
+        if let Some((_, sink)) =
+            self.config.event_sinks.iter().find(|(i, _)| *i == index)
+        {
+            mlm.set_event_sink(Arc::clone(sink));
+        }
+        if let Some((_, store)) =
+            self.config.proof_stores.iter().find(|(i, _)| *i == index)
+        {
+            mlm.set_proof_store(Arc::clone(store));
+        }
+        if let Some((_, cipher)) =
+            self.config.wal_ciphers.iter().find(|(i, _)| *i == index)
+        {
+            mlm.set_wal_cipher(Arc::clone(cipher));
+        }
+        if let Some((_, millis)) =
+            self.config.slow_signers.iter().find(|(i, _)| *i == index)
+        {
+            mlm.set_async_crypto(Arc::new(SlowSigner {
+                address: node.address.clone(),
+                delay: Duration::from_millis(*millis),
+            }));
+        }
+
+        let handler = mlm.get_handler();
+        let exits = Arc::clone(&self.exits);
+        if let Some(snapshot) = snapshot {
+            tokio::spawn(async move {
+                if let Ok(exit) = mlm.run_from_snapshot(snapshot).await {
+                    exits.lock()[index] = Some(exit);
+                }
+            });
+            return handler;
+        }
+
+        let mut status = Status {
+            height: 1,
+            interval: Some(self.interval(index)),
+            timer_config: self.config.timer_config.clone(),
+            authority_list: Vec::new(),
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: Vec::new(),
+        };
+        self.epochs.apply(&mut status);
+        handler
+            .send_msg(Context::new(), MlmMsg::RichStatus(status))
+            .expect("Simulated node stopped");
+
+        tokio::spawn(async move {
+            if let Ok(exit) = mlm.start(0).await {
+                exits.lock()[index] = Some(exit);
+            }
+        });
+        handler
+    }
+}
+
+struct Network {
+    inner: Mutex<NetworkInner>,
+    latency: Latency,
+    drop_rate: f64,
+    duplicate_rate: f64,
+}
+
+struct NetworkInner {
+    rng: Pcg64,
+    groups: Vec<usize>,
+    handlers: Vec<MlmHandler<SimBlock>>,
+    stats: NetworkStats,
+    stopped: bool,
+}
+
+impl Network {
+    fn send(&self, from: usize, to: usize, msg: MlmMsg<SimBlock>) {
+        let mut deliveries = Vec::new();
+        {
+            let mut inner = self.inner.lock();
+            if inner.groups[from] != inner.groups[to] {
+                inner.stats.partitioned += 1;
+                return;
+            }
+            if happens(&mut inner.rng, self.drop_rate) {
+                inner.stats.dropped += 1;
+                return;
+            }
+
+            let copies = if happens(&mut inner.rng, self.duplicate_rate) {
+                inner.stats.duplicated += 1;
+                2
+            } else {
+                1
+            };
+            for _ in 0..copies {
+                let latency = self.latency.sample(&mut inner.rng);
+                deliveries.push((latency, inner.handlers[to].clone()));
+            }
+            inner.stats.delivered += copies;
+        }
+
+        for (latency, handler) in deliveries {
+            let msg = msg.clone();
+            tokio::spawn(async move {
+                sleep(latency).await;
+                let _ = handler.send_msg_from(Context::new(), from.to_string(), msg);
+            });
+        }
+    }
+}
+
+impl Network {
+    /// Synchronize each node behind the highest node of its group with the committed blocks
+    /// and the status of the next height.
+    fn sync(&self, commits: &Mutex<Commits>, statuses: &[Status], epochs: &SimEpochs) {
+        let mut inner = self.inner.lock();
+        let mut commits = commits.lock();
+        for index in 0..commits.len() {
+            let height = commits[index]
+                .last()
+                .map(|(height, _)| *height)
+                .unwrap_or(0);
+            let source = (0..commits.len())
+                .filter(|other| inner.groups[*other] == inner.groups[index])
+                .max_by_key(|other| commits[*other].last().map(|(height, _)| *height));
+            let source = match source {
+                Some(source) if source != index => source,
+                _ => continue,
+            };
+
+            let blocks = commits[source]
+                .iter()
+                .filter(|(other_height, _)| *other_height > height)
+                .cloned()
+                .collect::<Vec<_>>();
+            let target = match blocks.last() {
+                Some((target, _)) => *target,
+                None => continue,
+            };
+            commits[index].extend(blocks);
+            inner.stats.synced += 1;
+
+            let mut status = statuses[index].clone();
+            status.height = target + 1;
+            epochs.apply(&mut status);
+            let _ = inner.handlers[index]
+                .send_msg(Context::new(), MlmMsg::RichStatus(status));
+        }
+    }
+}
+
+fn happens(rng: &mut Pcg64, rate: f64) -> bool {
+    rate > 0.0 && (rng.next_u64() as f64) < rate * u64::MAX as f64
+}
+
+fn keccak(data: &[u8]) -> Hash {
+    let mut hasher = Keccak::v256();
+    let mut output = [0u8; 32];
+    hasher.update(data);
+    hasher.finalize(&mut output);
+    Hash::from(output.to_vec())
+}
+
+/// The block of the simulated nodes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SimBlock(pub Bytes);
+
+impl Codec for SimBlock {
+    type Error = Infallible;
+
+    fn encode(&self) -> Result<Bytes, Self::Error> {
+        Ok(self.0.clone())
+    }
+
+    fn decode(data: Bytes) -> Result<Self, Self::Error> {
+        Ok(SimBlock(data))
+    }
+
+    fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+        Ok(SimBlock(Bytes::copy_from_slice(data)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct SimAdapter {
+    index: usize,
+    interval: Duration,
+    authority_list: Vec<Node>,
+    epochs: SimEpochs,
+    network: Arc<Network>,
+    commits: Arc<Mutex<Commits>>,
+    view_changes: Arc<Mutex<Vec<Vec<ViewChangeInfo>>>>,
+    clock: SimClock,
+    start: Instant,
+    timestamp_tolerance: u64,
+    rejected_timestamps: Arc<Mutex<Vec<u64>>>,
+    withhold_data: bool,
+    batch_votes: bool,
+    bad_extension: bool,
+    timer_updates: Vec<(u64, DurationConfig)>,
+    published: Arc<Mutex<HashSet<Hash>>>,
+    stalls: Arc<Mutex<Vec<Vec<StallDump>>>>,
+    connectivity: Arc<Mutex<Vec<Vec<ConnectivitySummary>>>>,
+    brakes: Arc<Mutex<Vec<Vec<BrakeReport>>>>,
+    halts: Arc<Mutex<Vec<Vec<HaltReport>>>>,
+    round_limits: Arc<Mutex<Vec<Vec<RoundLimitReport>>>>,
+    invalid_msgs: InvalidMsgs,
+    pre_checks: Arc<Mutex<Commits>>,
+    height_reports: Arc<Mutex<Vec<Vec<HeightReport>>>>,
+    archived: Arc<Mutex<Vec<Vec<ExpiredHeight<SimBlock>>>>>,
+    attestations: Arc<Mutex<Vec<Vec<SignedAttestation>>>>,
+    idle_until: Duration,
+}
+
+impl SimAdapter {
+    /// Pack a vote into a vote batch if the node sends its votes in batches.
+    fn batch_vote(&self, msg: MlmMsg<SimBlock>) -> MlmMsg<SimBlock> {
+        match msg {
+            MlmMsg::SignedVote(sv) if self.batch_votes => {
+                MlmMsg::SignedVoteBatch(vec![sv])
+            }
+            msg => msg,
+        }
+    }
+}
+
+#[async_trait]
+impl Consensus<SimBlock> for SimAdapter {
+    async fn get_block(
+        &self,
+        ctx: Context,
+        height: u64,
+    ) -> Result<(SimBlock, Hash), Box<dyn Error + Send>> {
+        if !AllowEmptyBlock::in_context(&ctx) && self.start.elapsed() < self.idle_until {
+            return Err(Box::new(ConsensusError::NothingToPropose));
+        }
+
+        let mut content = BytesMut::with_capacity(24);
+        content.put_u64(height);
+        content.put_u64(self.index as u64);
+        content.put_i64(self.clock.now(self.start));
+        let content = content.freeze();
+        let hash = keccak(&content);
+        if !self.withhold_data {
+            self.published.lock().insert(hash.clone());
+        }
+        Ok((SimBlock(content), hash))
+    }
+
+    async fn check_block(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        hash: Hash,
+        block: SimBlock,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        if keccak(&block.0) != hash {
+            return Err(Box::new(ConsensusError::Other(
+                "Block hash mismatch".to_string(),
+            )));
+        }
+
+        // The timestamp follows the height and the proposer index.
+        if self.timestamp_tolerance > 0 && block.0.len() >= 24 {
+            let timestamp = (&block.0[16..24]).get_i64();
+            let now = self.clock.now(self.start);
+            if timestamp > now.saturating_add(self.timestamp_tolerance as i64) {
+                self.rejected_timestamps.lock()[self.index] += 1;
+                return Err(Box::new(ConsensusError::Other(format!(
+                    "Block timestamp {} ahead of the clock {}",
+                    timestamp, now
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    fn block_hash(&self, _height: u64, block: &SimBlock) -> Option<Hash> {
+        Some(keccak(&block.0))
+    }
+
+    async fn commit(
+        &self,
+        _ctx: Context,
+        height: u64,
+        commit: Commit<SimBlock>,
+    ) -> Result<Status, Box<dyn Error + Send>> {
+        self.commits.lock()[self.index].push((height, commit.proof.block_hash));
+        self.epochs.commit(height, commit.proof.round);
+        let timer_config = self
+            .timer_updates
+            .iter()
+            .find(|(update_height, _)| *update_height == height)
+            .map(|(_, config)| config.clone());
+        let mut status = Status {
+            height: height + 1,
+            interval: Some(self.interval),
+            timer_config,
+            authority_list: Vec::new(),
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: Vec::new(),
+        };
+        self.epochs.apply(&mut status);
+        Ok(status)
+    }
+
+    async fn get_authority_list(
+        &self,
+        _ctx: Context,
+        height: u64,
+    ) -> Result<Vec<Node>, Box<dyn Error + Send>> {
+        Ok(self.epochs.authority_list(height))
+    }
+
+    async fn broadcast_to_other(
+        &self,
+        _ctx: Context,
+        msg: MlmMsg<SimBlock>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let msg = self.batch_vote(msg);
+        for to in (0..self.authority_list.len()).filter(|to| *to != self.index) {
+            self.network.send(self.index, to, msg.clone());
+        }
+        Ok(())
+    }
+
+    async fn transmit_to_relayer(
+        &self,
+        _ctx: Context,
+        addr: Address,
+        msg: MlmMsg<SimBlock>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let msg = self.batch_vote(msg);
+        if let Some(to) = self
+            .authority_list
+            .iter()
+            .position(|node| node.address == addr)
+        {
+            self.network.send(self.index, to, msg);
+        }
+        Ok(())
+    }
+
+    async fn network_ready(&self, _ctx: Context, authority_list: Vec<Node>) -> bool {
+        let inner = self.network.inner.lock();
+        let group = inner.groups[self.index];
+        let peers = inner.groups.iter().filter(|other| **other == group).count();
+        peers * 3 > authority_list.len() * 2
+    }
+
+    async fn check_availability(&self, _ctx: Context, _height: u64, hash: Hash) -> bool {
+        // The data may be published late, so wait an interval before giving up.
+        if self.published.lock().contains(&hash) {
+            return true;
+        }
+        sleep(self.interval).await;
+        self.published.lock().contains(&hash)
+    }
+
+    async fn extend_vote(
+        &self,
+        _ctx: Context,
+        height: u64,
+        _round: u64,
+        _hash: Hash,
+    ) -> Result<Bytes, Box<dyn Error + Send>> {
+        let height = if self.bad_extension { height + 1 } else { height };
+        Ok(sim_extension(height, self.index))
+    }
+
+    async fn verify_vote_extension(
+        &self,
+        _ctx: Context,
+        height: u64,
+        _round: u64,
+        _hash: Hash,
+        _voter: Address,
+        extension: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        if extension.len() != 16 || (&extension[..8]).get_u64() != height {
+            return Err(Box::new(ConsensusError::Other(
+                "Vote extension of another height".to_string(),
+            )));
+        }
+        Ok(())
+    }
+
+    fn pre_check_block(
+        &self,
+        _ctx: Context,
+        height: u64,
+        hash: Hash,
+        _block: &SimBlock,
+    ) {
+        self.pre_checks.lock()[self.index].push((height, hash));
+    }
+
+    fn report_error(&self, _ctx: Context, _error: ConsensusError) {}
+
+    fn report_stall(&self, _ctx: Context, dump: StallDump) {
+        self.stalls.lock()[self.index].push(dump);
+    }
+
+    fn report_connectivity(&self, _ctx: Context, summary: ConnectivitySummary) {
+        self.connectivity.lock()[self.index].push(summary);
+    }
+
+    fn report_brake(&self, _ctx: Context, report: BrakeReport) {
+        self.brakes.lock()[self.index].push(report);
+    }
+
+    fn report_height(&self, _ctx: Context, report: HeightReport) {
+        self.height_reports.lock()[self.index].push(report);
+    }
+
+    fn archive_height(&self, _ctx: Context, expired: ExpiredHeight<SimBlock>) {
+        self.archived.lock()[self.index].push(expired);
+    }
+
+    fn report_attestation(&self, _ctx: Context, attestation: SignedAttestation) {
+        self.attestations.lock()[self.index].push(attestation);
+    }
+
+    fn on_consensus_halted(&self, _ctx: Context, report: HaltReport) {
+        self.halts.lock()[self.index].push(report);
+    }
+
+    fn on_max_rounds(&self, _ctx: Context, report: RoundLimitReport) {
+        self.round_limits.lock()[self.index].push(report);
+    }
+
+    fn report_invalid_msg(
+        &self,
+        _ctx: Context,
+        peer_hint: Option<String>,
+        kind: String,
+        _reason: String,
+    ) {
+        self.invalid_msgs.lock()[self.index].push((peer_hint, kind));
+    }
+
+    fn report_view_change(
+        &self,
+        _ctx: Context,
+        _height: u64,
+        _round: u64,
+        _reason: ViewChangeReason,
+    ) {
+    }
+
+    fn report_view_change_info(&self, _ctx: Context, info: ViewChangeInfo) {
+        self.view_changes.lock()[self.index].push(info);
+    }
+}
+
+/// The signature of a node is its address, which binds a signature to the signer without
+/// the cost of a real crypto.
+struct SimCrypto {
+    address: Address,
+    unlock_at: Option<Instant>,
+    panics: bool,
+    verifications: Arc<AtomicU64>,
+}
+
+impl Crypto for SimCrypto {
+    type QcSignature = AggregatedSignature;
+
+    fn hash(&self, msg: Bytes) -> Hash {
+        keccak(&msg)
+    }
+
+    fn sign(&self, _hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+        match self.unlock_at {
+            Some(unlock_at) if Instant::now() < unlock_at => Err(Box::new(
+                ConsensusError::CryptoErr("Simulated signer locked".to_string()),
+            )),
+            _ => Ok(self.address.as_bytes().clone()),
+        }
+    }
+
+    fn aggregate_signatures(
+        &self,
+        _signatures: Vec<Signature>,
+        _voters: Vec<Address>,
+    ) -> Result<Signature, Box<dyn Error + Send>> {
+        Ok(Signature::new())
+    }
+
+    fn verify_signature(
+        &self,
+        signature: Signature,
+        _hash: Hash,
+        voter: Address,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        assert!(!self.panics, "Simulated verification panic");
+        self.verifications.fetch_add(1, Ordering::Relaxed);
+        if signature != voter.as_bytes() {
+            return Err(Box::new(ConsensusError::CryptoErr(
+                "Invalid simulated signature".to_string(),
+            )));
+        }
+        Ok(())
+    }
+
+    fn verify_aggregated_signature(
+        &self,
+        _aggregate_signature: Signature,
+        _msg_hash: Hash,
+        _voters: Vec<Address>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        self.verifications.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn vrf_prove(&self, input: Bytes) -> Result<(Bytes, Bytes), Box<dyn Error + Send>> {
+        Ok((sim_vrf_output(&input, &self.address), self.address.as_bytes().clone()))
+    }
+
+    fn vrf_verify(
+        &self,
+        input: Bytes,
+        proof: Bytes,
+        address: Address,
+    ) -> Result<Bytes, Box<dyn Error + Send>> {
+        if proof != address.as_bytes() {
+            return Err(Box::new(ConsensusError::CryptoErr(
+                "Invalid simulated VRF proof".to_string(),
+            )));
+        }
+        Ok(sim_vrf_output(&input, &address))
+    }
+}
+
+/// The remote signer of a node, which signs as the simulated crypto after a delay.
+#[derive(Debug)]
+struct SlowSigner {
+    address: Address,
+    delay: Duration,
+}
+
+#[async_trait]
+impl AsyncCrypto for SlowSigner {
+    async fn sign(&self, _hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
+        sleep(self.delay).await;
+        Ok(self.address.as_bytes().clone())
+    }
+}
+
+/// The simulated vote extension of a node, which is the height and the index of the node
+/// like a price reported by an oracle.
+fn sim_extension(height: u64, index: usize) -> Bytes {
+    let mut extension = BytesMut::with_capacity(16);
+    extension.put_u64(height);
+    extension.put_u64(index as u64);
+    extension.freeze()
+}
+
+/// The simulated VRF output of the prover, whose proof is its address like the signatures.
+fn sim_vrf_output(input: &[u8], address: &Address) -> Bytes {
+    keccak(&[input, address.as_bytes()].concat()).into_bytes()
+}
+
+#[derive(Default)]
+struct SimWal {
+    info: Mutex<Option<Bytes>>,
+    last_signed: Mutex<Option<Bytes>>,
+    checkpoint: Mutex<Option<Bytes>>,
+    proof_archive: Mutex<Option<Bytes>>,
+    round_votes: Mutex<Option<Bytes>>,
+    steps: Mutex<Vec<Bytes>>,
+    flushes: AtomicU64,
+    #[cfg(feature = "rocksdb")]
+    rocks: Option<RocksWal>,
+}
+
+#[async_trait]
+impl Wal for SimWal {
+    async fn save(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            rocks.save(info.clone()).await?;
+        }
+        *self.info.lock() = Some(info);
+        Ok(())
+    }
+
+    async fn save_durable(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            rocks.save_durable(info.clone()).await?;
+        }
+        *self.info.lock() = Some(info);
+        Ok(())
+    }
+
+    async fn load(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            return rocks.load().await;
+        }
+        Ok(self.info.lock().clone())
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            rocks.flush().await?;
+        }
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn save_last_signed(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            rocks.save_last_signed(info.clone()).await?;
+        }
+        *self.last_signed.lock() = Some(info);
+        Ok(())
+    }
+
+    async fn load_last_signed(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            return rocks.load_last_signed().await;
+        }
+        Ok(self.last_signed.lock().clone())
+    }
+
+    async fn save_checkpoint(
+        &self,
+        checkpoint: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            rocks.save_checkpoint(checkpoint.clone()).await?;
+        }
+        *self.checkpoint.lock() = Some(checkpoint);
+        Ok(())
+    }
+
+    async fn load_checkpoint(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            return rocks.load_checkpoint().await;
+        }
+        Ok(self.checkpoint.lock().clone())
+    }
+
+    async fn save_proof_archive(
+        &self,
+        archive: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            rocks.save_proof_archive(archive.clone()).await?;
+        }
+        *self.proof_archive.lock() = Some(archive);
+        Ok(())
+    }
+
+    async fn load_proof_archive(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            return rocks.load_proof_archive().await;
+        }
+        Ok(self.proof_archive.lock().clone())
+    }
+
+    async fn save_round_votes(&self, votes: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            rocks.save_round_votes(votes.clone()).await?;
+        }
+        *self.round_votes.lock() = Some(votes);
+        Ok(())
+    }
+
+    async fn load_round_votes(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            return rocks.load_round_votes().await;
+        }
+        Ok(self.round_votes.lock().clone())
+    }
+
+    async fn append_step(&self, record: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            rocks.append_step(record.clone()).await?;
+        }
+        self.steps.lock().push(record);
+        Ok(())
+    }
+
+    async fn load_steps(&self) -> Result<Vec<Bytes>, Box<dyn Error + Send>> {
+        #[cfg(feature = "rocksdb")]
+        if let Some(rocks) = &self.rocks {
+            return rocks.load_steps().await;
+        }
+        Ok(self.steps.lock().clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::error::Error;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use futures::channel::mpsc::Receiver;
+    use futures::StreamExt;
+    use parking_lot::Mutex;
+    use tokio::time::{sleep, Instant};
+
+    use super::clock::SimClock;
+    use super::{
+        sim_extension, Latency, NetworkStats, SimBlock, SimConfig, SimCrypto, Simulator,
+    };
+    use crate::canonical;
+    use crate::config::RelayerPolicy;
+    use crate::error::{
+        AuthorityListError, ConfigError, ConsensusError, ConsensusExit, ErrorSeverity,
+    };
+    use crate::proof::{verify_handover, verify_proof, MemoryProofStore, ProofStore};
+    use crate::telemetry::{SpanAttrs, Trace};
+    use crate::types::{
+        Address, Hash, MlmMsg, Proposal, SignedProposal, SignedVote, Status,
+        ViewChangeReason, Vote, VoteType,
+    };
+    use crate::wal::decode_record;
+    use crate::wire::RlpCodec;
+    use crate::{
+        BackoffConfig, ConfigDiff, ConfigSource, ConsensusSnapshot, Context, Crypto,
+        DurationConfig, EffectiveConfig, MlmEvent, MlmEventKind, StateDump, StepRecord,
+        StepRecords, Wal, WalCipher, WalRecordKind, WAL_VERSION,
+    };
+
+    /// The distinct QCs formed by the nodes by the events received from them. A QC formed by
+    /// several nodes is counted once.
+    fn formed_qcs(
+        events: &mut [Receiver<MlmEvent>],
+    ) -> HashSet<(u64, u64, VoteType, Hash)> {
+        let mut qcs = HashSet::new();
+        for events in events.iter_mut() {
+            while let Ok(event) = events.try_recv() {
+                assert_eq!(event.dropped, 0);
+                if let MlmEventKind::QcFormed {
+                    vote_type,
+                    block_hash,
+                } = event.kind
+                {
+                    qcs.insert((event.height, event.round, vote_type, block_hash));
+                }
+            }
+        }
+        qcs
+    }
+
+    /// The QCs aggregated by all of the nodes from the votes.
+    fn aggregated_qcs(sim: &Simulator) -> u64 {
+        (0..4)
+            .map(|index| sim.qc_gossip_stats(index).aggregated)
+            .sum()
+    }
+
+    fn gen_config(seed: u64) -> SimConfig {
+        SimConfig {
+            seed,
+            latency: Latency::Uniform { min: 5, max: 50 },
+            drop_rate: 0.01,
+            duplicate_rate: 0.05,
+            ..SimConfig::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_simulator() {
+        let mut sim = Simulator::new(gen_config(7));
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        assert!(sim.network_stats().duplicated > 0);
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pre_check_block() {
+        let mut sim = Simulator::new(SimConfig {
+            seed: 3,
+            duplicate_rate: 0.3,
+            ..SimConfig::default()
+        });
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(60))
+                .await
+        );
+        sim.stop();
+
+        // A block is pre-checked once by a node, and by the nodes other than the proposer.
+        let pre_checks = (0..4).map(|i| sim.pre_checked_blocks(i)).collect::<Vec<_>>();
+        for blocks in pre_checks.iter() {
+            let set = blocks.iter().collect::<HashSet<_>>();
+            assert_eq!(set.len(), blocks.len());
+        }
+        for block in sim.commits(0).into_iter().filter(|(height, _)| *height < 5) {
+            let count = pre_checks.iter().filter(|b| b.contains(&block)).count();
+            assert!(count >= 2);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_height_report() {
+        let mut config = SimConfig {
+            seed: 5,
+            ..SimConfig::default()
+        };
+        config.mlm_config.set_height_report(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(60))
+                .await
+        );
+        sim.stop();
+
+        let reports = (0..4).map(|i| sim.height_reports(i)).collect::<Vec<_>>();
+        for report in reports.iter().flatten() {
+            assert!(report.rounds >= 1);
+            assert!(report.duration > Duration::ZERO);
+            assert!(!report.step_elapsed.is_empty());
+            assert!(report.bytes_sent > 0 && report.bytes_received > 0);
+            assert!(sim.nodes().iter().any(|node| node.address == report.proposer));
+        }
+        // The votes of a height are collected by its relayer.
+        for height in 1..4 {
+            let relayed = reports
+                .iter()
+                .flatten()
+                .filter(|report| report.height == height)
+                .map(|report| report.prevotes.count)
+                .max();
+            assert!(relayed >= Some(3));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_proposal_timestamp() {
+        let mut config = gen_config(37);
+        config.mlm_config.set_proposal_timestamp(1000, 0);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+
+        // A proposal stamped ahead of the clock beyond the drift is refused.
+        let height = sim.height(0) + 1;
+        let nodes = sim.nodes();
+        for (i, node) in nodes.iter().enumerate() {
+            let proposal = Proposal {
+                height,
+                round: 0,
+                content: SimBlock(Bytes::from_static(b"future")),
+                block_hash: Hash::from([9u8; 32]),
+                lock: None,
+                proposer: node.address.clone(),
+                timestamp: Some(u64::MAX / 2),
+            };
+            let sp = SignedProposal {
+                signature: node.address.as_bytes().clone(),
+                proposal,
+                timeout_cert: None,
+                vrf_proof: Bytes::new(),
+            };
+            sim.send_msg(0, i, MlmMsg::SignedProposal(sp));
+        }
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height + 1, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        assert!(sim
+            .invalid_msg_reports(0)
+            .iter()
+            .any(|(_, kind)| kind == "Signed Proposal"));
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_epoch_handover() {
+        let store = Arc::new(MemoryProofStore::new());
+        let mut config = gen_config(23);
+        config.nodes = 5;
+        config.epochs = vec![(4, vec![0, 1, 2, 3]), (7, vec![1, 2, 3, 4])];
+        config.proof_stores = vec![(1, store.clone())];
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(sim.run_until(&[1, 2, 3], 9, Duration::from_secs(120)).await);
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // A light client follows the handovers from the authority list of the first epoch.
+        let crypto = SimCrypto {
+            address: Address::default(),
+            unlock_at: None,
+            panics: false,
+            verifications: Arc::default(),
+        };
+        let nodes = sim.nodes().to_vec();
+        let mut authority_list = nodes.clone();
+        for (height, indexes) in [(3, vec![0, 1, 2, 3]), (6, vec![1, 2, 3, 4])] {
+            let handover = store.get_handover(height).await.unwrap().unwrap();
+            authority_list =
+                verify_handover(&crypto, &handover, authority_list).unwrap();
+            let mut expected = indexes
+                .into_iter()
+                .map(|index| nodes[index].clone())
+                .collect::<Vec<_>>();
+            expected.sort();
+            assert_eq!(authority_list, expected);
+        }
+        assert_eq!(store.get_handover(5).await.unwrap(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ready_gate() {
+        let mut config = SimConfig {
+            seed: 4,
+            ..SimConfig::default()
+        };
+        config.mlm_config.set_ready_gate(true, 0);
+        let mut sim = Simulator::new(config);
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        sim.start();
+
+        // No node is connected to a quorum, so none of them starts a round.
+        assert!(!sim.run_until(&[0], 1, Duration::from_secs(10)).await);
+        assert_eq!(sim.network_stats(), NetworkStats::default());
+
+        sim.heal();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_relayer_fallback() {
+        let mut config = SimConfig {
+            seed: 9,
+            latency: Latency::Fixed(20),
+            drop_rate: 0.3,
+            ..SimConfig::default()
+        };
+        config.mlm_config.set_relayer_fallback_ratio(3);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        let mut events = (0..4)
+            .map(|index| sim.subscribe(index, 4096))
+            .collect::<Vec<_>>();
+
+        // The votes lost on the way to the leader reach the fallback relayers. The heights
+        // reached under the loss depend on the seed and the features, so the fallback is
+        // checked over a span of the time rather than by the heights.
+        sleep(Duration::from_secs(120)).await;
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // The QCs are aggregated by the leaders of their rounds only, so the ones aggregated
+        // by more than one node are from the votes routed to the fallback relayers.
+        let formed = formed_qcs(&mut events).len() as u64;
+        assert!(formed > 0);
+        assert!(aggregated_qcs(&sim) > formed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_backup_relayers() {
+        let mut config = SimConfig {
+            seed: 14,
+            latency: Latency::Fixed(20),
+            drop_rate: 0.3,
+            ..SimConfig::default()
+        };
+        config
+            .mlm_config
+            .set_backup_relayers(2, RelayerPolicy::NextProposers);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        let mut events = (0..4)
+            .map(|index| sim.subscribe(index, 4096))
+            .collect::<Vec<_>>();
+
+        // The votes lost on the way to the leader reach the backup relayers at once.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(600))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // The leader and the backup relayers aggregate the same QCs from the copies of the
+        // votes, which the leader alone would aggregate once each.
+        let formed = formed_qcs(&mut events).len() as u64;
+        assert!(aggregated_qcs(&sim) > formed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gossip_qc() {
+        let mut config = SimConfig {
+            seed: 21,
+            latency: Latency::Fixed(20),
+            drop_rate: 0.2,
+            ..SimConfig::default()
+        };
+        config.mlm_config.set_gossip_qc(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The QCs lost on the way from the leader reach the nodes through the others.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(600))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // Each node re-broadcasts every QC that it learns first, and drops the copies of the
+        // known ones, which come from the leader and the other nodes.
+        for index in 0..4 {
+            let stats = sim.qc_gossip_stats(index);
+            assert!(stats.learned > 0);
+            assert_eq!(stats.gossiped, stats.learned);
+            assert!(stats.duplicated > 0);
+        }
+    }
+
+    /// The VRF leader mode runs with or without the `multi_proposal` feature.
+    #[tokio::test(start_paused = true)]
+    async fn test_vrf_leader() {
+        let mut config = SimConfig {
+            seed: 11,
+            ..SimConfig::default()
+        };
+        let seed = Bytes::from_static(b"genesis");
+        config.mlm_config.set_vrf_leader(true, seed.clone());
+        config.mlm_config.set_height_report(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 6, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // Every authority proposes, and the proposal of the lowest VRF output wins.
+        let reports = sim.height_reports(0);
+        assert!(reports.iter().any(|report| report.rounds == 1));
+        for report in reports.iter().filter(|report| report.rounds == 1) {
+            let input = crate::canonical::vrf_input(report.height, 0, &seed);
+            let winner = sim
+                .nodes()
+                .iter()
+                .map(|node| &node.address)
+                .min_by_key(|address| super::sim_vrf_output(&input, address))
+                .unwrap();
+            assert_eq!(&report.proposer, winner);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_protocol_stats() {
+        let config = SimConfig {
+            seed: 5,
+            latency: Latency::Fixed(20),
+            drop_rate: 0.1,
+            ..SimConfig::default()
+        };
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(600))
+                .await
+        );
+        sim.stop();
+        // The heights synchronized from the others are not committed by the node.
+        for index in 0..4 {
+            let stats = sim.protocol_stats(index);
+            let view_changes = stats.view_changes.values().sum::<u64>();
+            assert_eq!(view_changes, sim.view_changes(index));
+            assert!(stats.heights_committed > 0);
+            assert!(stats.rounds >= stats.heights_committed + view_changes);
+            assert!(stats.average_rounds_per_height >= 1.0);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_clock_drift() {
+        let mut config = gen_config(17);
+        config.clocks = vec![
+            (
+                0,
+                SimClock {
+                    skew: 800,
+                    drift_ppm: 50_000,
+                },
+            ),
+            (
+                2,
+                SimClock {
+                    skew: -500,
+                    drift_ppm: -40_000,
+                },
+            ),
+        ];
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The timeouts of the nodes disagree by the drifts, and the nodes still agree.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(600))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        assert!(sim.local_time(0) > sim.local_time(1));
+        assert!(sim.local_time(2) < sim.local_time(3));
+        assert!(sim.clock_disagreement() > 1300);
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_clock_skew_timestamps() {
+        let mut config = gen_config(19);
+        config.timestamp_tolerance = 500;
+        config.clocks = vec![(
+            3,
+            SimClock {
+                skew: 2000,
+                drift_ppm: 0,
+            },
+        )];
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The blocks of the node ahead are refused by the others, whose proposals go on.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(600))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        assert!((0..3).all(|index| sim.rejected_timestamps(index) > 0));
+        assert_eq!(sim.rejected_timestamps(3), 0);
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_locked_signer() {
+        let mut config = gen_config(23);
+        config.locked_signers = vec![(3, 5000)];
+        config.mlm_config.set_signer_retry_interval(500);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        let mut events = sim.subscribe(3, 1024);
+
+        // The node observes the commits of the others until its signer unlocks.
+        assert!(sim.run_until(&[0, 1, 2], 3, Duration::from_secs(600)).await);
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(600))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        let mut kinds = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            match event.kind {
+                MlmEventKind::SignerUnavailable | MlmEventKind::SignerReady => {
+                    kinds.push(event.kind)
+                }
+                _ => (),
+            }
+        }
+        assert_eq!(
+            kinds,
+            vec![MlmEventKind::SignerUnavailable, MlmEventKind::SignerReady]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_signer() {
+        let mut config = gen_config(53);
+        config.slow_signers = vec![(0, 3000)];
+        config.mlm_config.set_sign_timeout(5000);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The state of the node goes on handling the requests while its signatures are on
+        // the way, far within the time that a signature takes.
+        let handler = sim.network.inner.lock().handlers[0].clone();
+        for _ in 0..5 {
+            sleep(Duration::from_millis(700)).await;
+            let start = Instant::now();
+            handler.effective_config().await.unwrap();
+            assert!(start.elapsed() < Duration::from_millis(1000));
+        }
+
+        // The node keeps up with the others by the QCs, and votes once it is signed.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(600))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_compact_proposal() {
+        let mut config = SimConfig {
+            latency: Latency::Fixed(20),
+            ..gen_config(10)
+        };
+        config.mlm_config.set_compact_proposal(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The blocks are fetched from the proposers and the proposals are restored.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_data_availability() {
+        let mut config = gen_config(14);
+        config.withheld_data = vec![0];
+        config.mlm_config.set_compact_proposal(true);
+        config.mlm_config.set_availability_timeout(200);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The blocks of node 0 are never published, so the others prevote nil after the
+        // availability check times out and none of them is committed.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(600))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        let published = sim.published.lock();
+        for commits in sim.commits.lock().iter() {
+            assert!(commits.iter().all(|(_, hash)| published.contains(hash)));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_empty_block_suppression() {
+        // The proposers wait for something to propose, then propose the empty blocks.
+        let mut config = SimConfig {
+            idle_until: u64::MAX,
+            ..gen_config(15)
+        };
+        config.mlm_config.set_empty_block_suppression(200, false);
+        let mut sim = Simulator::new(config.clone());
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // No height is produced until there is something to propose.
+        config.idle_until = 10000;
+        config.mlm_config.set_empty_block_suppression(200, true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            !sim.run_until(&[0, 1, 2, 3], 1, Duration::from_secs(9))
+                .await
+        );
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(600))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_block_parts() {
+        let mut config = gen_config(12);
+        config.mlm_config.set_block_part_size(5);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The blocks of 16 bytes are broadcast in 4 parts and reassembled by the others.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 10, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_invalid_authority_update() {
+        let mut sim = Simulator::new(gen_config(33));
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // A status with a duplicate authority is rejected and reported as fatal.
+        let mut errors = sim.subscribe_errors(0, 16);
+        let mut authority_list = sim.nodes().to_vec();
+        authority_list.push(authority_list[1].clone());
+        let height = sim.height(0) + 100;
+        sim.send_status(
+            0,
+            Status {
+                height,
+                interval: None,
+                timer_config: None,
+                authority_list,
+                next_authority_list: None,
+                proposer_seed: None,
+                skipped_proposers: Vec::new(),
+            },
+        );
+        let err = errors.next().await.unwrap();
+        assert!(matches!(
+            err.error,
+            ConsensusError::InvalidAuthorityUpdate {
+                height: h,
+                error: AuthorityListError::DuplicateAddress(_),
+            } if h == height
+        ));
+        assert_eq!(err.severity, ErrorSeverity::Fatal);
+
+        // The node stays at its height and goes on with the others.
+        let next = sim.height(0) + 2;
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], next, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.height(0) < height);
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_invalid_timer_config() {
+        let mut sim = Simulator::new(gen_config(52));
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // A backoff of zero base would time out the steps at once.
+        let mut timer_config = DurationConfig::classic();
+        timer_config.backoff = Some(BackoffConfig {
+            base: 0,
+            multiplier: 20,
+            cap: 0,
+        });
+        let invalid = |err: &ConsensusError| {
+            matches!(
+                err,
+                ConsensusError::InvalidConfig(ConfigError::InvalidBackoff {
+                    base: 0,
+                    cap: 0
+                })
+            )
+        };
+        let handler = sim.network.inner.lock().handlers[0].clone();
+        assert!(invalid(
+            &handler
+                .update_timer_config(timer_config.clone())
+                .unwrap_err()
+        ));
+
+        // The state rejects it from a status and from a raw update as well.
+        let mut errors = sim.subscribe_errors(0, 16);
+        handler
+            .send_msg(Context::new(), MlmMsg::UpdateConfig(timer_config.clone()))
+            .unwrap();
+        assert!(invalid(&errors.next().await.unwrap().error));
+
+        let height = sim.height(0) + 100;
+        sim.send_status(
+            0,
+            Status {
+                height,
+                interval: None,
+                timer_config: Some(timer_config),
+                authority_list: sim.nodes().to_vec(),
+                next_authority_list: None,
+                proposer_seed: None,
+                skipped_proposers: Vec::new(),
+            },
+        );
+        assert!(invalid(&errors.next().await.unwrap().error));
+
+        // The node keeps its timer config and goes on with the others.
+        let next = sim.height(0) + 2;
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], next, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.height(0) < height);
+        let effective = handler.effective_config().await.unwrap();
+        assert_eq!(effective.timer_config, DurationConfig::classic());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hash_len() {
+        let mut config = gen_config(34);
+        config.mlm_config.set_hash_len(32);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // A signed vote of a hash of another length is dropped before the verification.
+        let voter = sim.nodes()[1].address.clone();
+        let vote = SignedVote {
+            signature: voter.as_bytes().clone(),
+            vote: Vote {
+                height: sim.height(0),
+                round: 0,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from([1u8; 20]),
+            },
+            voter,
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        };
+        sim.send_msg(0, 1, MlmMsg::SignedVote(vote));
+        let next = sim.height(0) + 2;
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], next, Duration::from_secs(60))
+                .await
+        );
+        assert_eq!(
+            sim.invalid_msg_reports(0),
+            vec![(Some("1".to_string()), "Signed Vote".to_string())]
+        );
+        sim.stop();
+
+        // The blocks of the adapter are refused if their hashes are of another length.
+        let mut config = gen_config(34);
+        config.mlm_config.set_hash_len(20);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        let mut errors = (0..4)
+            .map(|i| sim.subscribe_errors(i, 16))
+            .collect::<Vec<_>>();
+        assert!(
+            !sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(10))
+                .await
+        );
+        assert!(errors.iter_mut().any(|rx| {
+            matches!(
+                rx.try_recv(),
+                Ok(err) if matches!(
+                    err.error,
+                    ConsensusError::InvalidHashLength {
+                        expect: 20,
+                        actual: 32
+                    }
+                )
+            )
+        }));
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_partition() {
+        let mut sim = Simulator::new(gen_config(11));
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // Two halves can not reach the quorum.
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        let stalled = (0..4).map(|index| sim.height(index)).max().unwrap() + 2;
+        assert!(!sim.run_until(&[0], stalled, Duration::from_secs(20)).await);
+        assert!(sim.network_stats().partitioned > 0);
+
+        // The quorum is back after the partition heals.
+        sim.heal();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], stalled, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rebroadcast_current() {
+        let mut config = SimConfig {
+            seed: 3,
+            latency: Latency::Fixed(20),
+            ..SimConfig::default()
+        };
+        config.mlm_config.set_height_report(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+
+        // The proposal of the next height is lost in a blip of the network.
+        sim.partition(&[]);
+        sleep(Duration::from_millis(1100)).await;
+        let height = sim.height(0) + 1;
+        sim.heal();
+        for index in 0..4 {
+            sim.rebroadcast_current(index);
+        }
+
+        // The round goes on with the resent messages without a view change.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height + 1, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+        let report = sim
+            .height_reports(0)
+            .into_iter()
+            .find(|report| report.height == height)
+            .unwrap();
+        assert_eq!(report.rounds, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_proof_archive() {
+        let mut config = gen_config(41);
+        config.mlm_config.set_proof_archive(3, true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(60))
+                .await
+        );
+
+        // The proofs of the latest heights are archived for the committed blocks.
+        let commits = sim.commits(0);
+        let (height, _) = *commits.last().unwrap();
+        for (committed, hash) in commits.iter().rev().take(3) {
+            let proof = sim.get_proof(0, *committed).unwrap();
+            assert_eq!(&proof.block_hash, hash);
+        }
+        assert_eq!(sim.get_proof(0, height - 3), None);
+
+        // The restarted node keeps serving the proofs saved to its wal.
+        sim.restart(0, false).await;
+        assert_eq!(
+            sim.get_proof(0, height).map(|proof| proof.block_hash),
+            Some(commits.last().unwrap().1.clone())
+        );
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height + 2, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.get_proof(0, height + 2).is_some());
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_round_votes() {
+        let mut config = gen_config(46);
+        config.mlm_config.set_persist_round_votes(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // No QC is formed in the halves, so the relayer of the round keeps the votes of its
+        // half.
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        sleep(Duration::from_secs(5)).await;
+        let saved = |index: usize| {
+            let record = sim.wals[index].round_votes.lock().clone().unwrap_or_default();
+            let kind = WalRecordKind::RoundVotes;
+            decode_record::<Vec<SignedVote>>(kind, &record, &RlpCodec, None, None)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|vote| vote.vote.height > sim.height(index))
+                .collect::<Vec<_>>()
+        };
+        let relayer = (0..4).max_by_key(|index| saved(*index).len()).unwrap();
+        let votes = saved(relayer);
+        assert!(votes.len() >= 2);
+
+        // The restarted relayer restores the votes before it rejoins the network.
+        let dump = sim.restart(relayer, false).await;
+        for vote in votes.iter() {
+            assert!(dump.votes.iter().any(|restored| {
+                restored.round == vote.vote.round
+                    && restored.vote_type == vote.vote.vote_type
+                    && restored.voter == vote.voter
+            }));
+        }
+
+        sim.heal();
+        let height = (0..4).map(|index| sim.height(index)).max().unwrap();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height + 2, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fast_resync() {
+        let mut config = SimConfig {
+            idle_until: 60000,
+            ..gen_config(48)
+        };
+        config.mlm_config.set_empty_block_suppression(200, true);
+        config.mlm_config.set_heartbeat_interval(500);
+        config.mlm_config.set_fast_resync(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The rounds of the first height time out while the node is isolated, and it restarts
+        // rounds behind the others.
+        sim.partition(&[&[0, 1, 2]]);
+        sleep(Duration::from_secs(15)).await;
+        let restarted = sim.restart(3, false).await;
+        let handler = |index: usize| sim.network.inner.lock().handlers[index].clone();
+        let ahead = handler(0).dump_state().await.unwrap();
+        assert_eq!(restarted.height, ahead.height);
+        assert!(restarted.round < ahead.round);
+
+        // The heartbeats of the quorum bring the node to their round before it ends.
+        sim.heal();
+        sleep(Duration::from_secs(1)).await;
+        let dump = handler(3).dump_state().await.unwrap();
+        assert_eq!((dump.height, dump.round), (ahead.height, ahead.round));
+        assert_eq!(handler(0).dump_state().await.unwrap().round, ahead.round);
+        assert_eq!(
+            sim.view_change_infos(3).last().unwrap().reason,
+            ViewChangeReason::QuorumAtHigherRound(restarted.round, ahead.round)
+        );
+
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fast_resync_forged_votes() {
+        let mut config = gen_config(48);
+        config.mlm_config.set_fast_resync(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // Two votes of a higher height are forged in the names of two validators. They reach
+        // the node before their signatures are verified.
+        let height = sim.height(0) + 2;
+        let vote = |index: usize, signature: Bytes| SignedVote {
+            signature,
+            vote: Vote {
+                height,
+                round: 10,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from([1u8; 32]),
+            },
+            voter: sim.nodes()[index].address.clone(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        };
+        for index in [1, 2] {
+            let forged = vote(index, Bytes::from_static(b"forged"));
+            sim.send_msg(0, 3, MlmMsg::SignedVote(forged));
+        }
+
+        // A genuine vote of the third validator at the height completes no quorum with them.
+        assert!(sim.run_until(&[0], height, Duration::from_secs(60)).await);
+        sim.partition(&[&[1, 2, 3]]);
+        assert_eq!(sim.height(0), height);
+        let signature = sim.nodes()[3].address.as_bytes().clone();
+        sim.send_msg(0, 3, MlmMsg::SignedVote(vote(3, signature)));
+        sleep(Duration::from_millis(100)).await;
+        assert!(!sim.view_change_infos(0).iter().any(|info| matches!(
+            info.reason,
+            ViewChangeReason::QuorumAtHigherRound(..)
+        )));
+        assert_eq!(sim.height(0), height);
+
+        sim.heal();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height + 2, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_vote_extensions() {
+        let store = Arc::new(MemoryProofStore::new());
+        let mut config = gen_config(49);
+        config.mlm_config.set_vote_extensions(true);
+        config.bad_extensions = vec![3];
+        config.proof_stores = vec![(1, store.clone())];
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        // The precommits with the extensions of the wrong heights are rejected.
+        assert!((0..3).any(|index| {
+            sim.invalid_msg_reports(index)
+                .iter()
+                .any(|(_, kind)| kind == "Signed Vote")
+        }));
+
+        // The proofs carry the extensions of all of the voters of their QCs, which are
+        // verified with the proofs.
+        let crypto = SimCrypto {
+            address: Address::default(),
+            unlock_at: None,
+            panics: false,
+            verifications: Arc::default(),
+        };
+        let nodes = sim.nodes().to_vec();
+        for height in 1..=5 {
+            let proof = store.get(height).await.unwrap().unwrap();
+            verify_proof(&crypto, &proof, nodes.clone()).unwrap();
+            assert!(proof.extensions.len() >= 3);
+            for extension in proof.extensions.iter() {
+                let index = nodes
+                    .iter()
+                    .position(|node| node.address == extension.voter)
+                    .unwrap();
+                if index != 3 {
+                    assert_eq!(extension.payload, sim_extension(height, index));
+                }
+            }
+        }
+
+        let mut proof = store.get(1).await.unwrap().unwrap();
+        let mut forged = proof.clone();
+        forged.extensions[0].signature = Bytes::from_static(b"forged");
+        assert!(verify_proof(&crypto, &forged, nodes.clone()).is_err());
+        proof.extensions.push(proof.extensions[0].clone());
+        assert!(verify_proof(&crypto, &proof, nodes).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_height_retention() {
+        let mut config = gen_config(50);
+        config.mlm_config.set_height_retention(2, true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 8, Duration::from_secs(120))
+                .await
+        );
+        sim.stop();
+
+        // Each node archives the heights in order once they fall behind the last committed
+        // one by more than the retention, with the QCs of the committed blocks.
+        for index in 0..4 {
+            let archived = sim.archived_heights(index);
+            let commits = sim.commits(index);
+            assert!(!archived.is_empty());
+            assert!(archived.windows(2).all(|pair| pair[0].height < pair[1].height));
+            for expired in archived.iter() {
+                assert!(expired.height + 2 < sim.height(index));
+                let (_, hash) = commits
+                    .iter()
+                    .find(|(height, _)| *height == expired.height)
+                    .unwrap();
+                assert!(expired.qcs.iter().any(|qc| {
+                    qc.vote_type == VoteType::Precommit && &qc.block_hash == hash
+                }));
+                assert!(expired.votes.iter().all(|sv| sv.vote.height == expired.height));
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stop_mid_round() {
+        let mut sim = Simulator::new(gen_config(51));
+        sim.start();
+        let mut events = sim.subscribe(0, 1024);
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+
+        // The node stops in the middle of the round of the next height.
+        sleep(Duration::from_millis(50)).await;
+        assert!(sim.stop_node(0).is_ok());
+        for _ in 0..100 {
+            if sim.exit(0).is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert!(sim.exit(0).unwrap().is_requested());
+        assert_eq!(sim.wal_flushes(0), 1);
+        assert!(sim.stop_node(0).is_err());
+
+        // The exit with the last committed height is the last event of the node.
+        let mut last = None;
+        while let Ok(event) = events.try_recv() {
+            last = Some(event);
+        }
+        let last = last.unwrap();
+        assert_eq!(
+            last.kind,
+            MlmEventKind::Exit {
+                committed_height: sim.height(0),
+                requested: true,
+            }
+        );
+        assert_eq!(last.height, sim.height(0) + 1);
+
+        // The others go on without it.
+        let height = sim.height(1) + 2;
+        assert!(
+            sim.run_until(&[1, 2, 3], height, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+        assert_eq!(sim.wal_flushes(0), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_height_jump() {
+        let mut sim = Simulator::new(gen_config(24));
+        sim.start();
+        let mut events = sim.subscribe(3, 1024);
+
+        // The isolated node stays in its height while the others move far ahead.
+        sim.partition(&[&[0, 1, 2]]);
+        assert!(
+            sim.run_until(&[0, 1, 2], 12, Duration::from_secs(600))
+                .await
+        );
+        let from = sim.height(3) + 1;
+
+        // The node jumps to the synchronized status and takes part in the next heights.
+        sim.heal();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 15, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        let mut jumps = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let MlmEventKind::HeightJump { from } = event.kind {
+                jumps.push((from, event.height, event.round));
+            }
+        }
+        assert_eq!(jumps.len(), 1);
+        assert_eq!(jumps[0].0, from);
+        assert!(jumps[0].1 > 12);
+        assert_eq!(jumps[0].2, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_connectivity_report() {
+        let mut config = gen_config(25);
+        config.mlm_config.set_connectivity_window(5000);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+        assert!((0..4).all(|index| sim.connectivity_reports(index).is_empty()));
+
+        // The isolated node reports the silent authorities, and the others still hear from a
+        // quorum without it.
+        sim.partition(&[&[0, 1, 2]]);
+        sleep(Duration::from_secs(20)).await;
+        assert!((0..3).all(|index| sim.connectivity_reports(index).is_empty()));
+        let reports = sim.connectivity_reports(3);
+        assert!(reports.len() >= 2);
+        let summary = reports.last().unwrap();
+        assert_eq!((summary.heard_weight, summary.silent_weight), (1, 3));
+        assert!(summary
+            .peers
+            .iter()
+            .all(|peer| peer.silent == (peer.address != sim.nodes()[3].address)));
+
+        // The reports stop after the partition heals.
+        sim.heal();
+        let height = (0..4).map(|index| sim.height(index)).max().unwrap() + 2;
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height, Duration::from_secs(120))
+                .await
+        );
+        let reported = sim.connectivity_reports(3).len();
+        sleep(Duration::from_secs(20)).await;
+        assert_eq!(sim.connectivity_reports(3).len(), reported);
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_brake_report() {
+        let mut sim = Simulator::new(gen_config(27));
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+
+        // The halves brake without a quorum of the chokes until the partition heals.
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        sleep(Duration::from_secs(10)).await;
+        sim.heal();
+        let height = (0..4).map(|index| sim.height(index)).max().unwrap() + 2;
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height, Duration::from_secs(120))
+                .await
+        );
+        sim.stop();
+
+        let reports = sim.brake_reports(0);
+        assert!(!reports.is_empty());
+        assert!(reports
+            .iter()
+            .all(|report| report.chokers.contains(&sim.nodes()[0].address)));
+        let longest = reports.iter().max_by_key(|report| report.duration).unwrap();
+        assert!(longest.duration >= Duration::from_secs(5));
+        assert!(longest.choke_weight >= 2);
+
+        let stats = sim.protocol_stats(0);
+        assert_eq!(stats.brakes, reports.len() as u64);
+        assert_eq!(
+            stats.brake_millis,
+            reports
+                .iter()
+                .map(|report| report.duration.as_millis() as u64)
+                .sum::<u64>()
+        );
+        assert_eq!(stats.max_brake_millis, longest.duration.as_millis() as u64);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consensus_halted() {
+        let mut config = gen_config(28);
+        config.mlm_config.set_max_brake_rounds(3);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+        assert!((0..4).all(|index| sim.halt_reports(index).is_empty()));
+
+        // The halves brake without a quorum of the chokes and escalate every three brakes.
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        sleep(Duration::from_secs(30)).await;
+        let reports = sim.halt_reports(0);
+        assert!(reports.len() >= 2);
+        for (index, report) in reports.iter().enumerate() {
+            assert_eq!(report.brake_rounds, 3 * (index as u64 + 1));
+        }
+        assert!(reports[1].halted_for > reports[0].halted_for);
+
+        // The escalations stop after the partition heals.
+        sim.heal();
+        let height = (0..4).map(|index| sim.height(index)).max().unwrap() + 2;
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height, Duration::from_secs(120))
+                .await
+        );
+        let escalated = sim.halt_reports(0).len();
+        sleep(Duration::from_secs(20)).await;
+        assert_eq!(sim.halt_reports(0).len(), escalated);
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_rounds() {
+        let mut config = SimConfig {
+            idle_until: 30000,
+            ..gen_config(42)
+        };
+        config.mlm_config.set_empty_block_suppression(200, true);
+        config.mlm_config.set_max_rounds(2);
+        config.mlm_config.set_stall_watchdog(5000, 8);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The empty blocks are skipped for a while, so the rounds of the first height time
+        // out and escalate every two rounds.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(300))
+                .await
+        );
+        let reports = sim.round_limit_reports(0);
+        assert!(!reports.is_empty());
+        for report in reports.iter() {
+            assert_eq!(report.height, 1);
+            assert_eq!(report.escalations, report.round / 2);
+        }
+        assert!(reports.last().unwrap().stalled_for >= Duration::from_secs(5));
+        assert_eq!(
+            sim.protocol_stats(0).round_escalations,
+            reports.len() as u64
+        );
+        assert!(sim
+            .stall_dumps(0)
+            .iter()
+            .any(|dump| dump.height == 1 && dump.round_escalations > 0));
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_choke_free() {
+        let mut timer_config = DurationConfig::new(24, 10, 5, 0);
+        timer_config.set_choke_free(true);
+        let config = SimConfig {
+            timer_config: Some(timer_config),
+            ..gen_config(44)
+        };
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The rounds of the isolated proposer time out and change without the chokes.
+        sim.partition(&[&[0, 1, 2]]);
+        assert!(
+            sim.run_until(&[0, 1, 2], 6, Duration::from_secs(120))
+                .await
+        );
+        let stats = sim.protocol_stats(0);
+        assert!(stats.rounds > stats.heights_committed);
+        assert_eq!(stats.brakes, 0);
+        assert!(sim.brake_reports(0).is_empty());
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_task_panic() {
+        let config = SimConfig {
+            panicking_verifiers: vec![3],
+            ..gen_config(43)
+        };
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The panicked verification stops the engine of the node, and the others go on.
+        assert!(
+            sim.run_until(&[0, 1, 2], 3, Duration::from_secs(60))
+                .await
+        );
+        match sim.exit(3) {
+            Some(ConsensusExit::Panicked(message)) => {
+                assert_eq!(message, "Simulated verification panic")
+            }
+            exit => panic!("unexpected exit {:?}", exit),
+        }
+        assert!(sim.exit(0).is_none());
+
+        sim.stop();
+        for _ in 0..100 {
+            if (0..3).all(|index| sim.exit(index).is_some()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        for index in 0..3 {
+            assert!(sim.exit(index).unwrap().is_requested());
+        }
+        assert!(sim.check_safety().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_migrate_by_snapshot() {
+        let mut sim = Simulator::new(gen_config(29));
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+
+        let snapshot = sim.migrate(0).await;
+        assert!(snapshot.height() > 3);
+        assert_eq!(snapshot.status().unwrap().authority_list.len(), 4);
+        assert!(snapshot.last_signed.is_some());
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ConsensusSnapshot<SimBlock>>(&json).unwrap(),
+            snapshot
+        );
+
+        // The new instance resumes the height of the snapshot and keeps committing.
+        let height = snapshot.height() + 3;
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recover_from_checkpoint() {
+        let mut config = gen_config(30);
+        config.mlm_config.set_checkpoint_interval(4);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(60))
+                .await
+        );
+
+        // The node loses its wal information, and resumes the height of the latest checkpoint
+        // instead of starting over.
+        let dump = sim.restart(0, true).await;
+        assert!(dump.height >= 4);
+        assert_eq!(dump.height % 4, 0);
+        assert!(dump.consensus_power);
+
+        let height = sim.height(1) + 3;
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[derive(Debug)]
+    struct XorCipher;
+
+    impl WalCipher for XorCipher {
+        fn encrypt(
+            &self,
+            _kind: WalRecordKind,
+            record: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            Ok(record.iter().map(|b| b ^ 0x5a).collect())
+        }
+
+        fn decrypt(
+            &self,
+            kind: WalRecordKind,
+            record: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            self.encrypt(kind, record)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_encrypted_wal() {
+        let mut config = gen_config(31);
+        config.mlm_config.set_checkpoint_interval(2);
+        config.wal_ciphers = vec![(0, Arc::new(XorCipher) as Arc<dyn WalCipher>)];
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+
+        // All of the records of the node are encrypted, and the others are plain.
+        let sealed = |index: usize| {
+            let wal = &sim.wals[index];
+            [&wal.info, &wal.last_signed, &wal.checkpoint]
+                .iter()
+                .all(|record| record.lock().as_ref().unwrap()[0] != WAL_VERSION)
+        };
+        assert!(sealed(0));
+        assert!(!sealed(1));
+
+        // The node restarts from its encrypted wal.
+        let height = sim.height(0);
+        let dump = sim.restart(0, false).await;
+        assert!(dump.height > height);
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], dump.height + 2, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_step_records() {
+        let mut config = gen_config(33);
+        config.mlm_config.set_checkpoint_interval(2);
+        config.wal_ciphers = vec![(0, Arc::new(XorCipher) as Arc<dyn WalCipher>)];
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+        sim.stop();
+
+        let records = sim.wals[1].load_steps().await.unwrap();
+        let steps = StepRecords::new(records)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(steps.windows(2).all(|w| w[0].height() <= w[1].height()));
+        assert!(steps.contains(&StepRecord::Checkpoint { height: 2 }));
+        for height in 1..=2 {
+            assert!(steps.iter().any(|step| matches!(
+                step,
+                StepRecord::NewRound { height: h, round: 0 } if *h == height
+            )));
+            assert!(steps.iter().any(|step| matches!(
+                step,
+                StepRecord::Voted { height: h, .. } if *h == height
+            )));
+            assert!(steps.iter().any(|step| matches!(
+                step,
+                StepRecord::Committed { height: h, .. } if *h == height
+            )));
+        }
+
+        // The encrypted records are decoded by the cipher only.
+        let records = sim.wals[0].load_steps().await.unwrap();
+        assert!(StepRecords::new(records.clone()).all(|step| step.is_err()));
+        assert!(StepRecords::new(records)
+            .with_cipher(Arc::new(XorCipher))
+            .all(|step| step.is_ok()));
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[tokio::test(start_paused = true)]
+    async fn test_rocks_wal() {
+        let path =
+            std::env::temp_dir().join(format!("mlm-sim-{}", rand::random::<u64>()));
+        let mut config = SimConfig {
+            rocks_wal: Some(path.clone()),
+            ..gen_config(54)
+        };
+        config.mlm_config.set_checkpoint_interval(2);
+        config.mlm_config.set_proof_archive(3, true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+
+        // The step records of each node are loaded from its column family.
+        let records = sim.wals[1].load_steps().await.unwrap();
+        let steps = StepRecords::new(records)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(steps.contains(&StepRecord::Checkpoint { height: 2 }));
+        assert!(steps
+            .iter()
+            .any(|step| matches!(step, StepRecord::Committed { height: 1, .. })));
+
+        // The node restarts from its wal opened again in the rocksdb, and keeps serving the
+        // proofs archived in it.
+        let (height, hash) = sim.commits(0).last().cloned().unwrap();
+        let dump = sim.restart(0, false).await;
+        assert!(dump.height > height);
+        assert_eq!(
+            sim.get_proof(0, height).map(|proof| proof.block_hash),
+            Some(hash)
+        );
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], dump.height + 2, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+        drop(sim);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_vote_batches() {
+        let mut config = gen_config(32);
+        config.vote_batches = vec![0, 1, 2, 3];
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 5, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat() {
+        let mut config = gen_config(26);
+        config.mlm_config.set_heartbeat_interval(1000);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+        let others = |index: usize| {
+            (0..4)
+                .filter(|other| *other != index)
+                .map(|other| sim.nodes()[other].address.clone())
+                .collect::<Vec<_>>()
+        };
+        for index in 0..4 {
+            let liveness = sim.validator_liveness(index);
+            let mut addresses = liveness
+                .iter()
+                .map(|validator| validator.address.clone())
+                .collect::<Vec<_>>();
+            addresses.sort();
+            let mut expected = others(index);
+            expected.sort();
+            assert_eq!(addresses, expected);
+        }
+
+        // The heartbeats keep coming from the own half while the rounds make no progress.
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        sleep(Duration::from_secs(1)).await;
+        let before = sim.validator_liveness(0);
+        sleep(Duration::from_secs(10)).await;
+        let after = sim.validator_liveness(0);
+        for (before, after) in before.iter().zip(after.iter()) {
+            if after.address == sim.nodes()[1].address {
+                assert!(after.heartbeats >= before.heartbeats + 8);
+                assert!(after.signed_at > before.signed_at);
+            } else {
+                assert_eq!(after, before);
+            }
+        }
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_attestations() {
+        let mut config = gen_config(45);
+        config.mlm_config.set_attestation_interval(500);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 4, Duration::from_secs(60))
+                .await
+        );
+        sim.stop();
+
+        // A monitor verifies the attestations of every node by their signatures.
+        let crypto = SimCrypto {
+            address: Address::default(),
+            unlock_at: None,
+            panics: false,
+            verifications: Arc::default(),
+        };
+        for index in 0..4 {
+            let attestations = sim.attestations(index);
+            assert!(attestations.len() >= 2);
+            for signed in attestations.iter() {
+                let attestation = &signed.attestation;
+                assert_eq!(attestation.address, sim.nodes()[index].address);
+                let hash = crypto.hash(canonical::attestation_preimage(attestation));
+                assert!(crypto
+                    .verify_signature(
+                        signed.signature.clone(),
+                        hash,
+                        attestation.address.clone()
+                    )
+                    .is_ok());
+            }
+            assert!(attestations.windows(2).all(|pair| {
+                let (first, second) = (&pair[0].attestation, &pair[1].attestation);
+                first.timestamp < second.timestamp
+                    && (first.height, first.round) <= (second.height, second.round)
+            }));
+        }
+
+        // A forged attestation fails the verification.
+        let mut forged = sim.attestations(0)[0].clone();
+        forged.attestation.address = sim.nodes()[1].address.clone();
+        let hash = crypto.hash(canonical::attestation_preimage(&forged.attestation));
+        assert!(crypto
+            .verify_signature(forged.signature, hash, forged.attestation.address)
+            .is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stall_watchdog() {
+        let mut config = gen_config(16);
+        config.mlm_config.set_stall_watchdog(5000, 8);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+        assert!((0..4).all(|index| sim.stall_dumps(index).is_empty()));
+
+        // The halves stall and dump the votes and the messages of their own half.
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        let stalled = (0..4).map(|index| sim.height(index)).max().unwrap() + 2;
+        assert!(!sim.run_until(&[0], stalled, Duration::from_secs(20)).await);
+        let dumps = sim.stall_dumps(0);
+        assert!(dumps.len() >= 2);
+        let dump = dumps.last().unwrap();
+        assert!(dump.stalled_for >= Duration::from_secs(5));
+        assert_eq!(dump.votes.len(), 4);
+        assert!(!dump.recent_messages.is_empty() && dump.recent_messages.len() <= 8);
+        let others = [&sim.nodes()[2].address, &sim.nodes()[3].address];
+        assert!(dump
+            .recent_messages
+            .iter()
+            .all(|msg| !others.contains(&&msg.from)));
+        assert!(dump
+            .votes
+            .iter()
+            .filter(|votes| others.contains(&&votes.address))
+            .all(|votes| votes.prevote.is_none() && votes.precommit.is_none()));
+
+        sim.heal();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], stalled, Duration::from_secs(120))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_config_changed() {
+        let mut sim = Simulator::new(gen_config(17));
+        sim.start();
+        let mut events = sim.subscribe(0, 1024);
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // The same status of each height changes nothing, and the timer update does.
+        let config = DurationConfig::new(20, 10, 10, 10);
+        let handler = sim.network.inner.lock().handlers[0].clone();
+        handler.update_timer_config(config.clone()).unwrap();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 4, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+
+        let mut changes = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let MlmEventKind::ConfigChanged { source, diff } = event.kind {
+                changes.push((source, diff));
+            }
+        }
+        assert_eq!(
+            changes,
+            vec![(
+                ConfigSource::TimerUpdate,
+                vec![ConfigDiff::TimerConfig {
+                    before: None,
+                    after: Some(config),
+                }]
+            )]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pipelined_timer_update() {
+        let timer_config = DurationConfig::new(20, 10, 10, 10);
+        let mut config = SimConfig {
+            timer_updates: vec![(3, timer_config.clone())],
+            ..gen_config(17)
+        };
+        config.mlm_config.set_pipelined(true);
+        let mut sim = Simulator::new(config);
+        sim.start();
+
+        // The timer config returned by the pipelined commit of a height takes effect from the
+        // height after the next.
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 6, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        for index in 0..4 {
+            let handler = sim.network.inner.lock().handlers[index].clone();
+            let effective = handler.effective_config().await.unwrap();
+            assert_eq!(effective.timer_config, timer_config);
+        }
+        sim.stop();
+    }
+
+    /// The flood is sent from another thread in the real time, since the virtual time only
+    /// advances once the state has drained its messages.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_message_flood() {
+        let mut config = SimConfig {
+            interval: 200,
+            ..gen_config(55)
+        };
+        config.mlm_config.set_message_buffers(16, 0);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // A node of the quorum is flooded by the stale votes, while the rounds of the isolated
+        // proposer time out and the quorum changes the view by the chokes.
+        sim.partition(&[&[0, 1, 2]]);
+        let handler = sim.network.inner.lock().handlers[0].clone();
+        let voter = sim.nodes()[1].address.clone();
+        let stale = MlmMsg::SignedVote(SignedVote {
+            signature: voter.as_bytes().clone(),
+            vote: Vote {
+                height: 1,
+                round: 0,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from([1u8; 32]),
+            },
+            voter,
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        });
+        let flooding = Arc::new(AtomicBool::new(true));
+        let flood = {
+            let flooding = Arc::clone(&flooding);
+            std::thread::spawn(move || {
+                while flooding.load(Ordering::Relaxed) {
+                    for _ in 0..64 {
+                        let _ = handler.send_msg(Context::new(), stale.clone());
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            })
+        };
+
+        // The timeouts and the SMR triggers are still handled within the budget of the
+        // messages, so the flooded node changes the view and goes on with the quorum.
+        let view_changes = sim.view_change_infos(0).len();
+        let height = sim.height(0);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while sim.view_change_infos(0).len() <= view_changes
+            && Instant::now() < deadline
+        {
+            sleep(Duration::from_millis(100)).await;
+        }
+        flooding.store(false, Ordering::Relaxed);
+        flood.join().unwrap();
+        assert!(sim.view_change_infos(0).len() > view_changes);
+        assert!(sim.height(0) > height);
+
+        sim.heal();
+        let height = (0..4).map(|index| sim.height(index)).max().unwrap();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], height + 2, Duration::from_secs(60))
+                .await
+        );
+        assert!(sim.check_safety().is_ok());
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_effective_config() {
+        let mut config = gen_config(47);
+        config.mlm_config.set_sign_timeout(300);
+        config.mlm_config.set_message_buffers(32, 0);
+        let mut sim = Simulator::new(config);
+        sim.start();
+        let mut events = sim.subscribe(0, 1024);
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 2, Duration::from_secs(60))
+                .await
+        );
+
+        // The state publishes the configuration it starts with, which is the one in effect
+        // until the timer update.
+        let handler = sim.network.inner.lock().handlers[0].clone();
+        let effective = handler.effective_config().await.unwrap();
+        let mut resolved = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let MlmEventKind::ConfigResolved { config } = event.kind {
+                resolved.push(*config);
+            }
+        }
+        assert_eq!(resolved, vec![effective.clone()]);
+        assert_eq!(effective.interval_ms, 1000);
+        assert_eq!(effective.timer_config, DurationConfig::classic());
+        assert_eq!(effective.propose_timeout_ms, 2400);
+        assert_eq!(effective.prevote_timeout_ms, 1000);
+        assert_eq!(effective.sign_timeout_ms, 300);
+        assert_eq!(effective.availability_timeout_ms, 1000);
+        assert_eq!(effective.message_budget, 32);
+        assert_eq!(effective.inbox_drain_limit, 256);
+        assert_eq!(effective.median_time_window, 11);
+        assert!(effective.features.contains(&"testing".to_string()));
+        let json = serde_json::to_string(&effective).unwrap();
+        assert_eq!(serde_json::from_str::<EffectiveConfig>(&json).unwrap(), effective);
+
+        handler
+            .update_timer_config(DurationConfig::new(20, 10, 10, 10))
+            .unwrap();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 4, Duration::from_secs(60))
+                .await
+        );
+        let updated = handler.effective_config().await.unwrap();
+        assert_eq!(updated.propose_timeout_ms, 2000);
+        assert_eq!(updated.precommit_timeout_ms, 1000);
+        assert_eq!(updated.availability_timeout_ms, 1000);
+        sim.stop();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dump_state() {
+        let mut sim = Simulator::new(gen_config(18));
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 3, Duration::from_secs(60))
+                .await
+        );
+
+        let handler = sim.network.inner.lock().handlers[0].clone();
+        let dump = handler.dump_state().await.unwrap();
+        assert!(dump.height > 3);
+        assert!(dump.consensus_power);
+        assert_eq!(dump.authority_list, sim.nodes().to_vec());
+        assert!(dump.step_timeout_ms > 0);
+        assert!(dump.step_remaining_ms <= dump.step_timeout_ms);
+        let json = serde_json::to_string(&dump).unwrap();
+        assert_eq!(serde_json::from_str::<StateDump>(&json).unwrap(), dump);
+
+        // The halves stall with the chokes of their own halves.
+        sim.partition(&[&[0, 1], &[2, 3]]);
+        let stalled = (0..4).map(|index| sim.height(index)).max().unwrap() + 2;
+        assert!(!sim.run_until(&[0], stalled, Duration::from_secs(20)).await);
+        let dump = handler.dump_state().await.unwrap();
+        assert!(!dump.chokes.is_empty());
+        let others = [&sim.nodes()[2].address, &sim.nodes()[3].address];
+        assert!(dump
+            .chokes
+            .iter()
+            .all(|choke| !others.contains(&&choke.address)));
+
+        sim.stop();
+    }
+
+    type RecordedSpan = (&'static str, Option<&'static str>, SpanAttrs);
+
+    /// Record the spans with the names of their parents.
+    #[derive(Debug, Default)]
+    struct RecordingTrace(Mutex<Vec<RecordedSpan>>);
+
+    impl Trace for RecordingTrace {
+        fn span(&self, ctx: Context, name: &'static str, attrs: SpanAttrs) -> Context {
+            let parent = ctx.get::<&'static str>("test_span").copied();
+            self.0.lock().push((name, parent, attrs));
+            ctx.with_value("test_span", name)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_trace_spans() {
+        let trace = Arc::new(RecordingTrace::default());
+        let mut config = gen_config(19);
+        config.traces = vec![(0, trace.clone())];
+        let mut sim = Simulator::new(config);
+        sim.start();
+        assert!(
+            sim.run_until(&[0, 1, 2, 3], 6, Duration::from_secs(60))
+                .await
+        );
+        sim.stop();
+
+        // A message is traced from the receipt to the broadcasts made in its handling, and
+        // the votes are broadcast in the spans of the SMR transitions.
+        let spans = trace.0.lock().clone();
+        let parents = spans
+            .iter()
+            .map(|(name, parent, _)| (*name, *parent))
+            .collect::<Vec<_>>();
+        for span in [
+            ("mlm.verify_sig_pool", Some("mlm.send_msg_to_inner")),
+            ("mlm.handle_msg", Some("mlm.verify_sig_pool")),
+            ("mlm.handle_signed_vote", Some("mlm.handle_msg")),
+            ("mlm.broadcast", Some("mlm.handle_signed_vote")),
+            ("mlm.broadcast", Some("mlm.smr_transition")),
+        ] {
+            assert!(parents.contains(&span), "{:?}", span);
+        }
+
+        // The spans are labeled with the address of the node, and the ones of the state with
+        // the step of the round.
+        let address = &sim.nodes()[0].address;
+        assert!(spans
+            .iter()
+            .all(|(_, _, attrs)| attrs.address.as_ref() == Some(address)));
+        assert!(spans
+            .iter()
+            .filter(|(name, _, _)| name.starts_with("mlm.handle_"))
+            .all(|(_, _, attrs)| attrs.step.is_some()));
+        assert!(spans.iter().any(|(name, _, attrs)| *name == "mlm.verify_sig_pool"
+            && attrs.round.is_some()));
+    }
+}
@@ -0,0 +1,138 @@
+#[cfg(feature = "runtime")]
+use std::io;
+#[cfg(feature = "runtime")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "runtime")]
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "runtime")]
+use tokio::runtime::{Builder, Runtime};
+
+/// The hints of the threads that run the consensus critical tasks, which are the state loop,
+/// the verification pool, the SMR and the timer. If the number of threads is set, the tasks
+/// run on a dedicated runtime of the instance instead of the runtime of the caller, so that the
+/// busy tasks of the application cannot delay a step. The worker threads of the runtime are
+/// pinned to the cores in turn and get the nice value. Pinning and the nice value need the
+/// `affinity` feature on Linux, and are skipped with a warning elsewhere.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThreadHints {
+    /// The number of the worker threads of the dedicated runtime. Zero runs the tasks on the
+    /// runtime of the caller and ignores the other hints.
+    pub threads: usize,
+    /// The cores that the worker threads are pinned to in turn. Empty means no pinning.
+    pub cores: Vec<usize>,
+    /// The nice value of the worker threads, the lower the higher the priority. Zero keeps the
+    /// nice value of the process.
+    pub nice: i32,
+}
+
+impl ThreadHints {
+    /// Whether the hints are enabled by a number of threads.
+    pub fn is_enabled(&self) -> bool {
+        self.threads > 0
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl ThreadHints {
+    /// Build the dedicated runtime of the hints, or none if the hints are disabled.
+    pub fn build_runtime(&self) -> io::Result<Option<Runtime>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+
+        let hints = self.clone();
+        let next = Arc::new(AtomicUsize::new(0));
+        Builder::new_multi_thread()
+            .worker_threads(self.threads)
+            .thread_name("mlm-consensus")
+            .enable_all()
+            .on_thread_start(move || hints.apply(next.fetch_add(1, Ordering::Relaxed)))
+            .build()
+            .map(Some)
+    }
+
+    /// Apply the hints to the current thread, which is the `index`th thread of the runtime.
+    fn apply(&self, index: usize) {
+        if !self.cores.is_empty() {
+            let core = self.cores[index % self.cores.len()];
+            if let Err(e) = pin_to_core(core) {
+                log::warn!("Mlm: pin the thread to core {} error {:?}", core, e);
+            }
+        }
+
+        if self.nice != 0 {
+            if let Err(e) = set_nice(self.nice) {
+                log::warn!("Mlm: set the nice value {} error {:?}", self.nice, e);
+            }
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "affinity", feature = "runtime"))]
+fn pin_to_core(core: usize) -> io::Result<()> {
+    // SAFETY: the cpu set is a plain bit mask owned by this frame, and zero pid refers to the
+    // calling thread.
+    let res = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", feature = "affinity", feature = "runtime"))]
+fn set_nice(nice: i32) -> io::Result<()> {
+    // SAFETY: on Linux the nice value is per thread, and zero who refers to the calling thread.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "runtime", not(all(target_os = "linux", feature = "affinity"))))]
+fn pin_to_core(_core: usize) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(all(feature = "runtime", not(all(target_os = "linux", feature = "affinity"))))]
+fn set_nice(_nice: i32) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(all(feature = "runtime", not(all(target_os = "linux", feature = "affinity"))))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "thread hints need the affinity feature on linux",
+    )
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod test {
+    use super::ThreadHints;
+
+    #[test]
+    fn test_build_runtime() {
+        assert!(ThreadHints::default().build_runtime().unwrap().is_none());
+
+        let hints = ThreadHints {
+            threads: 2,
+            cores: vec![0],
+            nice: 0,
+        };
+        let runtime = hints.build_runtime().unwrap().unwrap();
+        let name = runtime.block_on(async {
+            tokio::spawn(async { std::thread::current().name().map(String::from) })
+                .await
+                .unwrap()
+        });
+        assert_eq!(name.as_deref(), Some("mlm-consensus"));
+        runtime.shutdown_background();
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt::Debug;
+
+use crate::error::ConfigError;
+use crate::utils::auth_manage::quorum;
+
+/// The policy of the quorum threshold, which decides whether the voters of a QC, a proof or
+/// a vote count are a quorum of the authority list. All of the nodes of a network must use the
+/// same policy, and the safety of the consensus holds only if any two quorums of the policy
+/// intersect in an honest voter.
+pub trait ThresholdPolicy: Debug + Send + Sync {
+    /// Whether the voters of the vote weight and the count are a quorum of the authority list
+    /// of the total vote weight and the total count.
+    fn is_quorum(
+        &self,
+        weight: u128,
+        count: usize,
+        total_weight: u128,
+        total_count: usize,
+    ) -> bool;
+
+    /// Whether any two quorums of the policy intersect in an honest voter for the authority
+    /// list of the total vote weight and the total count. An authority list that the policy
+    /// is unsafe for is rejected when it is applied.
+    fn is_safe_for(&self, _total_weight: u128, _total_count: usize) -> bool {
+        true
+    }
+}
+
+/// The BFT threshold, which is above two thirds of the vote weights and tolerates the faulty
+/// authorities below one third of the weights. It is the default policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BftThreshold;
+
+impl ThresholdPolicy for BftThreshold {
+    fn is_quorum(
+        &self,
+        weight: u128,
+        _count: usize,
+        total_weight: u128,
+        _: usize,
+    ) -> bool {
+        weight >= quorum(total_weight)
+    }
+}
+
+/// The threshold of a fixed number of the voters regardless of their vote weights. The
+/// number must be above two thirds of the authorities for the quorums to intersect in an
+/// honest voter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedCountThreshold(pub usize);
+
+impl ThresholdPolicy for FixedCountThreshold {
+    fn is_quorum(&self, _weight: u128, count: usize, _: u128, _: usize) -> bool {
+        count >= self.0
+    }
+
+    fn is_safe_for(&self, _total_weight: u128, total_count: usize) -> bool {
+        self.0 as u128 * 3 > total_count as u128 * 2
+    }
+}
+
+/// The threshold above a ratio of the vote weights, such as a supermajority of three quarters
+/// of the stake. The ratio must be above two thirds for the quorums to intersect in an honest
+/// voter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StakeRatioThreshold {
+    /// The numerator of the ratio.
+    pub numerator: u64,
+    /// The denominator of the ratio, which must be above zero.
+    pub denominator: u64,
+}
+
+impl StakeRatioThreshold {
+    /// Create the threshold above the ratio, which fails if the ratio is not above two thirds
+    /// or the denominator is zero.
+    pub fn new(numerator: u64, denominator: u64) -> Result<Self, ConfigError> {
+        if denominator == 0 || u128::from(numerator) * 3 <= u128::from(denominator) * 2 {
+            return Err(ConfigError::UnsafeThresholdRatio {
+                numerator,
+                denominator,
+            });
+        }
+        Ok(StakeRatioThreshold {
+            numerator,
+            denominator,
+        })
+    }
+
+    /// The least vote weight above the ratio of the total, which is computed without overflow
+    /// and saturates at `u128::MAX`.
+    pub fn threshold(&self, total_weight: u128) -> u128 {
+        let (numerator, denominator) = (
+            u128::from(self.numerator),
+            u128::from(self.denominator.max(1)),
+        );
+        (total_weight / denominator)
+            .saturating_mul(numerator)
+            .saturating_add(total_weight % denominator * numerator / denominator)
+            .saturating_add(1)
+    }
+}
+
+impl ThresholdPolicy for StakeRatioThreshold {
+    fn is_quorum(
+        &self,
+        weight: u128,
+        _count: usize,
+        total_weight: u128,
+        _: usize,
+    ) -> bool {
+        weight >= self.threshold(total_weight)
+    }
+
+    fn is_safe_for(&self, _total_weight: u128, _total_count: usize) -> bool {
+        u128::from(self.numerator) * 3 > u128::from(self.denominator) * 2
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        BftThreshold, FixedCountThreshold, StakeRatioThreshold, ThresholdPolicy,
+    };
+    use crate::error::ConfigError;
+
+    #[test]
+    fn test_threshold_policy() {
+        assert!(!BftThreshold.is_quorum(6, 2, 9, 3));
+        assert!(BftThreshold.is_quorum(7, 2, 9, 3));
+
+        assert!(!FixedCountThreshold(3).is_quorum(100, 2, 100, 4));
+        assert!(FixedCountThreshold(3).is_quorum(1, 3, 100, 4));
+
+        let ratio = StakeRatioThreshold::new(3, 4).unwrap();
+        assert_eq!(ratio.threshold(100), 76);
+        assert_eq!(ratio.threshold(7), 6);
+        assert!(!ratio.is_quorum(75, 1, 100, 4));
+        assert!(ratio.is_quorum(76, 1, 100, 4));
+        assert_eq!(
+            StakeRatioThreshold::new(7, 10).unwrap().threshold(u128::MAX),
+            u128::MAX / 10 * 7 + u128::MAX % 10 * 7 / 10 + 1
+        );
+        assert_eq!(
+            StakeRatioThreshold::new(1, 1).unwrap().threshold(u128::MAX),
+            u128::MAX
+        );
+    }
+
+    #[test]
+    fn test_unsafe_threshold() {
+        for (numerator, denominator) in [(1, 2), (2, 3), (4, 6), (1, 0)] {
+            assert_eq!(
+                StakeRatioThreshold::new(numerator, denominator),
+                Err(ConfigError::UnsafeThresholdRatio {
+                    numerator,
+                    denominator
+                })
+            );
+        }
+        assert!(StakeRatioThreshold::new(u64::MAX, u64::MAX).is_ok());
+
+        // A fixed count must be above two thirds of the authorities.
+        assert!(BftThreshold.is_safe_for(1, 1));
+        assert!(FixedCountThreshold(3).is_safe_for(100, 4));
+        assert!(!FixedCountThreshold(2).is_safe_for(100, 3));
+        assert!(!FixedCountThreshold(3).is_safe_for(100, 5));
+    }
+}
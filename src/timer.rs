@@ -1,22 +1,25 @@
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 use std::{future::Future, pin::Pin};
 
-use derive_more::Display;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::future::{AbortHandle, Abortable, BoxFuture, FutureExt};
 use futures::stream::{Stream, StreamExt};
 use futures::SinkExt;
-use log::{debug, error, info};
-use tokio::time::{sleep, Sleep};
+use parking_lot::Mutex;
+use tokio::time::Instant;
 
-use crate::smr::smr_types::{SMREvent, SMRTrigger, TriggerSource, TriggerType};
+use crate::rng::Rng;
+use crate::runtime::{Runtime, TokioRuntime};
+use crate::smr::smr_types::{SMREvent, SMRTrigger, Step, TriggerSource, TriggerType};
 use crate::smr::{Event, SMRHandler};
+use crate::telemetry::{node_log, LogScope};
+use crate::types::Address;
 use crate::DurationConfig;
 use crate::{error::ConsensusError, ConsensusResult, INIT_HEIGHT, INIT_ROUND};
 use crate::{types::Hash, utils::timer_config::TimerConfig};
 
-const MAX_TIMEOUT_COEF: u32 = 5;
-
 /// Mlm timer used futures timer which is powered by a timer heap. When monitor a SMR event,
 /// timer will get timeout interval from timer config, then set a delay. When the timeout expires,
 #[derive(Debug)]
@@ -28,6 +31,40 @@ pub struct Timer {
     state_machine: SMRHandler,
     height: u64,
     round: u64,
+    instance: String,
+    address: Address,
+    runtime: Arc<dyn Runtime>,
+    /// The pending timeouts with their height and round, which are cancelled once the timer
+    /// goes to a later round since they are irrelevant after the deadline of their round.
+    pending: Vec<(u64, u64, AbortHandle)>,
+    /// The start of the current round by the monotonic clock, which the deadlines of the
+    /// steps of the round count from.
+    round_start: Instant,
+    /// The deadlines of the propose, prevote and precommit steps of the current round.
+    step_deadlines: [Option<Instant>; 3],
+    deadline: StepDeadline,
+}
+
+/// The deadline of the timeout of the latest step of the timer, which is shared with the
+/// state for its dump.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StepDeadline(Arc<Mutex<Option<(u64, u64, Instant)>>>);
+
+impl StepDeadline {
+    fn set(&self, height: u64, round: u64, deadline: Instant) {
+        *self.0.lock() = Some((height, round, deadline));
+    }
+
+    /// The time left until the deadline of the step of the height and the round, which is
+    /// zero if the deadline has passed or the timer is not in the round.
+    pub(crate) fn remaining(&self, height: u64, round: u64) -> Duration {
+        match *self.0.lock() {
+            Some((h, r, deadline)) if (h, r) == (height, round) => {
+                deadline.saturating_duration_since(Instant::now())
+            }
+            _ => Duration::ZERO,
+        }
+    }
 }
 
 ///
@@ -42,14 +79,11 @@ impl Stream for Timer {
             let mut is_pending = match self.event.poll_next_unpin(cx) {
                 Poll::Pending => true,
 
-                Poll::Ready(event) => {
-                    if event.is_none() {
-                        return Poll::Ready(Some(ConsensusError::TimerErr(
-                            "Channel dropped".to_string(),
-                        )));
-                    }
+                // The SMR ends without the stop event to the timer if the state has stopped
+                // before it, and the timer ends with it.
+                Poll::Ready(None) => return Poll::Ready(None),
 
-                    let event = event.unwrap();
+                Poll::Ready(Some(event)) => {
                     if event == SMREvent::Stop {
                         return Poll::Ready(None);
                     }
@@ -63,14 +97,13 @@ impl Stream for Timer {
             is_pending &= match self.notify.poll_next_unpin(cx) {
                 Poll::Pending => true,
 
-                Poll::Ready(event) => {
-                    if event.is_none() {
-                        return Poll::Ready(Some(ConsensusError::TimerErr(
-                            "Channel terminated".to_string(),
-                        )));
-                    }
+                Poll::Ready(None) => {
+                    return Poll::Ready(Some(ConsensusError::TimerErr(
+                        "Channel terminated".to_string(),
+                    )));
+                }
 
-                    let event = event.unwrap();
+                Poll::Ready(Some(event)) => {
                     if let Err(e) = self.trigger(event) {
                         return Poll::Ready(Some(e));
                     }
@@ -88,7 +121,7 @@ impl Timer {
     pub fn new(
         event: Event,
         state_machine: SMRHandler,
-        interval: u64,
+        interval: Duration,
         config: Option<DurationConfig>,
     ) -> Self {
         let (tx, rx) = unbounded();
@@ -105,15 +138,54 @@ impl Timer {
             notify: rx,
             event,
             state_machine,
+            instance: String::new(),
+            address: Address::default(),
+            runtime: Arc::new(TokioRuntime),
+            pending: Vec::new(),
+            round_start: Instant::now(),
+            step_deadlines: [None; 3],
+            deadline: StepDeadline::default(),
         }
     }
 
+    /// Set the runtime that the timer and its timeouts run on.
+    pub fn set_runtime(&mut self, runtime: Arc<dyn Runtime>) {
+        self.runtime = runtime;
+    }
+
+    /// Set the id of the instance that labels the logs of the timer.
+    pub fn set_instance_id(&mut self, instance: String) {
+        self.instance = instance;
+    }
+
+    /// Set the address of the node that labels the logs of the timer.
+    pub fn set_address(&mut self, address: Address) {
+        self.address = address;
+    }
+
+    /// The deadline of the timeout of the latest step, which is shared with the state.
+    pub(crate) fn step_deadline(&self) -> StepDeadline {
+        self.deadline.clone()
+    }
+
+    /// Set the max percentage of the jitter of the timeouts and the rng of it.
+    pub fn set_jitter(&mut self, jitter: u64, rng: Arc<dyn Rng>) {
+        self.config.set_jitter(jitter, rng);
+    }
+
     pub fn run(mut self) {
-        tokio::spawn(async move {
+        let runtime = Arc::clone(&self.runtime);
+        runtime.spawn(Box::pin(async move {
             while let Some(err) = self.next().await {
-                error!("Mlm: timer error {:?}", err);
+                node_log!(
+                    error,
+                    self.log_scope(None),
+                    "Mlm: timer error {:?}, instance {:?}",
+                    err,
+                    self.instance
+                );
             }
-        });
+        }));
     }
 
     fn set_timer(&mut self, event: SMREvent) -> ConsensusResult<()> {
@@ -130,6 +202,9 @@ impl Timer {
                     self.height = height;
                 }
                 self.round = round;
+                self.round_start = Instant::now();
+                self.step_deadlines = [None; 3];
+                self.cancel_outdated();
 
                 if let Some(interval) = new_interval {
                     self.config.set_interval(interval);
@@ -138,27 +213,126 @@ impl Timer {
                     self.config.update(config);
                 }
             }
+            // The state goes on to the next round at once without the chokes.
+            SMREvent::Brake { .. } if self.config.is_choke_free() => return Ok(()),
             SMREvent::Brake { .. } => is_brake_timer = true,
             SMREvent::Commit(_) => return Ok(()),
+            SMREvent::UpdateConfig(config) => {
+                // Only the timers set later use the new configuration.
+                self.config.update(config);
+                return Ok(());
+            }
             _ => (),
         };
 
         let mut interval = self.config.get_timeout(event.clone())?;
         if !is_brake_timer {
-            let mut coef = self.round as u32;
-            if coef > MAX_TIMEOUT_COEF {
-                coef = MAX_TIMEOUT_COEF;
+            interval = self.config.get_backoff_timeout(interval, self.round);
+        }
+
+        node_log!(
+            info,
+            self.log_scope(Some(&event)),
+            "Mlm: timer set {} timer, instance {:?}",
+            event,
+            self.instance
+        );
+        let view = match &event {
+            SMREvent::NewRoundInfo { height, round, .. }
+            | SMREvent::PrevoteVote { height, round, .. }
+            | SMREvent::PrecommitVote { height, round, .. }
+            | SMREvent::Brake { height, round, .. } => Some((*height, *round)),
+            _ => None,
+        };
+        if view == Some((self.height, self.round)) {
+            interval = self.schedule(&event, interval);
+        }
+        let smr_timer = TimeoutInfo {
+            timeout: self.runtime.sleep(interval),
+            info: event,
+            sender: self.sender.clone(),
+            runtime: Arc::clone(&self.runtime),
+        };
+        match view {
+            Some((height, round)) => {
+                let (handle, registration) = AbortHandle::new_pair();
+                self.pending.push((height, round, handle));
+                let timer = Abortable::new(smr_timer, registration);
+                self.runtime.spawn(Box::pin(timer.map(|_| ())));
+            }
+            None => self.runtime.spawn(Box::pin(smr_timer)),
+        }
+        Ok(())
+    }
+
+    /// The scope of the logs of the timeout of the event, which is the step that times out,
+    /// or the propose step of the current round of the timer without an event.
+    fn log_scope(&self, event: Option<&SMREvent>) -> LogScope {
+        let (height, round, step) = match event {
+            Some(SMREvent::NewRoundInfo { height, round, .. }) => {
+                (*height, *round, Step::Propose)
+            }
+            Some(SMREvent::PrevoteVote { height, round, .. }) => {
+                (*height, *round, Step::Prevote)
+            }
+            Some(SMREvent::PrecommitVote { height, round, .. }) => {
+                (*height, *round, Step::Precommit)
             }
-            interval *= 2u32.pow(coef);
+            Some(SMREvent::Brake { height, round, .. }) => {
+                (*height, *round, Step::Brake)
+            }
+            _ => (self.height, self.round, Step::Propose),
+        };
+        LogScope {
+            address: self.address.clone(),
+            height,
+            round,
+            step,
         }
+    }
 
-        info!("Mlm: timer set {} timer", event);
-        let smr_timer = TimeoutInfo::new(interval, event, self.sender.clone());
+    /// Schedule the timeout of a step of the current round by its deadline. A step entered
+    /// after the deadline of the earlier step counts from that deadline rather than from
+    /// now, so the delays of handling the timeouts do not accumulate across the steps, and
+    /// the propose step counts from the start of the round. The retries of the brake step
+    /// count from now. Return the time left until the deadline.
+    fn schedule(&mut self, event: &SMREvent, timeout: Duration) -> Duration {
+        let now = Instant::now();
+        let index = match event {
+            SMREvent::NewRoundInfo { .. } => Some(0),
+            SMREvent::PrevoteVote { .. } => Some(1),
+            SMREvent::PrecommitVote { .. } => Some(2),
+            _ => None,
+        };
+        let deadline = match index {
+            Some(index) => {
+                let base = self.step_deadlines[..index]
+                    .iter()
+                    .flatten()
+                    .max()
+                    .copied()
+                    .unwrap_or(self.round_start)
+                    .min(now);
+                let deadline = base + timeout;
+                self.step_deadlines[index] = Some(deadline);
+                deadline
+            }
+            None => now + timeout,
+        };
+        self.deadline.set(self.height, self.round, deadline);
+        deadline.saturating_duration_since(now)
+    }
 
-        tokio::spawn(async move {
-            smr_timer.await;
+    /// Cancel the pending timeouts of the heights and rounds before the current one.
+    fn cancel_outdated(&mut self) {
+        let current = (self.height, self.round);
+        self.pending.retain(|(height, round, handle)| {
+            let outdated = (*height, *round) < current;
+            if outdated {
+                handle.abort();
+            }
+            !outdated
         });
-        Ok(())
     }
 
     #[rustfmt::skip]
@@ -199,7 +373,13 @@ impl Timer {
             _ => return Err(ConsensusError::TimerErr("No commit timer".to_string())),
         };
 
-        debug!("Mlm: timer {:?} time out", event);
+        node_log!(
+            debug,
+            self.log_scope(Some(&event)),
+            "Mlm: timer {:?} time out, instance {:?}",
+            event,
+            self.instance
+        );
 
         self.state_machine.trigger(SMRTrigger {
             source: TriggerSource::Timer,
@@ -213,61 +393,69 @@ impl Timer {
     }
 }
 
-/// Timeout info which is a future consists of a `futures-timer Delay`, timeout info and a sender.
-/// When the timeout expires, future will send timeout info by sender.
-#[derive(Debug, Display)]
-#[display(fmt = "{:?}", info)]
+/// Timeout info which is a future consists of a sleep of the runtime, timeout info and a
+/// sender. When the timeout expires, future will send timeout info by sender in a new task of
+/// the runtime.
 struct TimeoutInfo {
-    timeout: Sleep,
+    timeout: BoxFuture<'static, ()>,
     info: SMREvent,
     sender: UnboundedSender<SMREvent>,
+    runtime: Arc<dyn Runtime>,
 }
 
 impl Future for TimeoutInfo {
     type Output = ();
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        let msg = self.info.clone();
-        let mut tx = self.sender.clone();
-
-        // Safety: we just poll it and didn't move it.
-        let fut = unsafe {
-            let this = self.get_unchecked_mut();
-            Pin::new_unchecked(&mut this.timeout)
-        };
-
-        match fut.poll(cx) {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match self.timeout.poll_unpin(cx) {
             Poll::Pending => Poll::Pending,
             Poll::Ready(_) => {
-                tokio::spawn(async move {
+                let msg = self.info.clone();
+                let mut tx = self.sender.clone();
+                self.runtime.spawn(Box::pin(async move {
                     let _ = tx.send(msg).await;
-                });
+                }));
                 Poll::Ready(())
             }
         }
     }
 }
 
-impl TimeoutInfo {
-    fn new(interval: Duration, event: SMREvent, tx: UnboundedSender<SMREvent>) -> Self {
-        TimeoutInfo {
-            timeout: sleep(interval),
-            info: event,
-            sender: tx,
-        }
-    }
-}
-
 #[cfg(test)]
 mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
     use futures::channel::mpsc::unbounded;
+    use futures::future::{self, BoxFuture};
     use futures::stream::StreamExt;
 
+    use crate::runtime::Runtime;
     use crate::smr::smr_types::{
         FromWhere, SMREvent, SMRTrigger, TriggerSource, TriggerType,
     };
     use crate::smr::{Event, SMRHandler};
-    use crate::{timer::Timer, types::Hash};
+    use crate::{timer::Timer, types::Hash, DurationConfig};
+
+    /// The runtime whose sleeps complete at once, counting the spawned tasks and the sleeps.
+    #[derive(Debug, Default)]
+    struct InstantRuntime {
+        spawns: AtomicUsize,
+        sleeps: AtomicUsize,
+    }
+
+    impl Runtime for InstantRuntime {
+        fn spawn(&self, task: BoxFuture<'static, ()>) {
+            self.spawns.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(task);
+        }
+
+        fn sleep(&self, _duration: Duration) -> BoxFuture<'static, ()> {
+            self.sleeps.fetch_add(1, Ordering::SeqCst);
+            Box::pin(future::ready(()))
+        }
+    }
 
     async fn test_timer_trigger(input: SMREvent, output: SMRTrigger) {
         let (trigger_tx, mut trigger_rx) = unbounded();
@@ -275,7 +463,7 @@ mod test {
         let mut timer = Timer::new(
             Event::new(event_rx),
             SMRHandler::new(trigger_tx),
-            3000,
+            Duration::from_millis(3000),
             None,
         );
         event_tx.unbounded_send(input).unwrap();
@@ -356,7 +544,7 @@ mod test {
         let mut timer = Timer::new(
             Event::new(event_rx),
             SMRHandler::new(trigger_tx),
-            3000,
+            Duration::from_millis(3000),
             None,
         );
 
@@ -416,4 +604,183 @@ mod test {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_update_config() {
+        let (trigger_tx, mut trigger_rx) = unbounded();
+        let (event_tx, event_rx) = unbounded();
+        let mut timer = Timer::new(
+            Event::new(event_rx),
+            SMRHandler::new(trigger_tx),
+            Duration::from_millis(3000),
+            None,
+        );
+
+        tokio::spawn(async move {
+            assert!(timer.next().await.is_none());
+        });
+
+        // The default propose timeout is longer than the prevote timeout, the updated config
+        // reverses it.
+        event_tx
+            .unbounded_send(SMREvent::UpdateConfig(DurationConfig::new(1, 30, 30, 30)))
+            .unwrap();
+        event_tx
+            .unbounded_send(SMREvent::PrevoteVote {
+                height: 0u64,
+                round: 0u64,
+                block_hash: Hash::new(),
+                lock_round: None,
+            })
+            .unwrap();
+        event_tx
+            .unbounded_send(SMREvent::NewRoundInfo {
+                height: 0,
+                round: 0,
+                lock_round: None,
+                lock_proposal: None,
+                new_interval: None,
+                new_config: None,
+                from_where: FromWhere::PrecommitQC(0),
+            })
+            .unwrap();
+
+        let res = trigger_rx.next().await.unwrap();
+        assert_eq!(res, gen_output(TriggerType::Proposal, 0, 0));
+        event_tx.unbounded_send(SMREvent::Stop).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancel_outdated() {
+        let (trigger_tx, mut trigger_rx) = unbounded();
+        let (event_tx, event_rx) = unbounded();
+        let mut timer = Timer::new(
+            Event::new(event_rx),
+            SMRHandler::new(trigger_tx),
+            Duration::from_millis(300),
+            None,
+        );
+
+        tokio::spawn(async move {
+            assert!(timer.next().await.is_none());
+        });
+
+        // The prevote timeout of the round 0 is shorter than the propose timeout of the round
+        // 1, but it is cancelled as the timer goes to the round 1.
+        event_tx
+            .unbounded_send(SMREvent::PrevoteVote {
+                height: 1u64,
+                round: 0u64,
+                block_hash: Hash::new(),
+                lock_round: None,
+            })
+            .unwrap();
+        event_tx
+            .unbounded_send(SMREvent::NewRoundInfo {
+                height: 1,
+                round: 1,
+                lock_round: None,
+                lock_proposal: None,
+                new_interval: None,
+                new_config: None,
+                from_where: FromWhere::PrecommitQC(0),
+            })
+            .unwrap();
+
+        let res = trigger_rx.next().await.unwrap();
+        assert_eq!(res, gen_output(TriggerType::Proposal, 1, 1));
+        event_tx.unbounded_send(SMREvent::Stop).unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test(start_paused = true)]
+    async fn test_round_deadlines() {
+        let (trigger_tx, mut trigger_rx) = unbounded();
+        let (event_tx, event_rx) = unbounded();
+        let mut timer = Timer::new(
+            Event::new(event_rx),
+            SMRHandler::new(trigger_tx),
+            Duration::from_millis(1000),
+            None,
+        );
+        let deadline = timer.step_deadline();
+
+        tokio::spawn(async move {
+            assert!(timer.next().await.is_none());
+        });
+
+        let start = tokio::time::Instant::now();
+        event_tx
+            .unbounded_send(SMREvent::UpdateConfig(DurationConfig::new(10, 10, 10, 10)))
+            .unwrap();
+        event_tx
+            .unbounded_send(SMREvent::NewRoundInfo {
+                height: 0,
+                round: 0,
+                lock_round: None,
+                lock_proposal: None,
+                new_interval: None,
+                new_config: None,
+                from_where: FromWhere::PrecommitQC(0),
+            })
+            .unwrap();
+
+        let res = trigger_rx.next().await.unwrap();
+        assert_eq!(res, gen_output(TriggerType::Proposal, 0, 0));
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+
+        // The prevote step starts late after the propose timeout, but its deadline counts
+        // from the propose deadline rather than from the start of the step.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        event_tx
+            .unbounded_send(SMREvent::PrevoteVote {
+                height: 0u64,
+                round: 0u64,
+                block_hash: Hash::new(),
+                lock_round: None,
+            })
+            .unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(deadline.remaining(0, 0), Duration::from_millis(700));
+        assert_eq!(deadline.remaining(0, 1), Duration::ZERO);
+
+        let res = trigger_rx.next().await.unwrap();
+        assert_eq!(res, gen_output(TriggerType::PrevoteQC, 0, 0));
+        assert_eq!(start.elapsed(), Duration::from_millis(2000));
+        assert_eq!(deadline.remaining(0, 0), Duration::ZERO);
+        event_tx.unbounded_send(SMREvent::Stop).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_custom_runtime() {
+        let (trigger_tx, mut trigger_rx) = unbounded();
+        let (event_tx, event_rx) = unbounded();
+        let mut timer = Timer::new(
+            Event::new(event_rx),
+            SMRHandler::new(trigger_tx),
+            Duration::from_secs(3600),
+            None,
+        );
+        let runtime = Arc::new(InstantRuntime::default());
+        timer.set_runtime(runtime.clone());
+        timer.run();
+
+        // The timeout of an hour fires at once by the sleep of the runtime.
+        event_tx
+            .unbounded_send(SMREvent::PrevoteVote {
+                height: 0u64,
+                round: 0u64,
+                block_hash: Hash::new(),
+                lock_round: None,
+            })
+            .unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(5), trigger_rx.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, gen_output(TriggerType::PrevoteQC, 0, 0));
+        assert_eq!(runtime.sleeps.load(Ordering::SeqCst), 1);
+        assert_eq!(runtime.spawns.load(Ordering::SeqCst), 3);
+        event_tx.unbounded_send(SMREvent::Stop).unwrap();
+    }
 }
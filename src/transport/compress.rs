@@ -0,0 +1,257 @@
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::sync::Arc;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tiny_keccak::{Hasher, Keccak};
+use zstd::bulk;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+use crate::transport::COMPRESSED_FLAG;
+
+/// The default zstd compression level.
+pub const DEFAULT_LEVEL: i32 = 3;
+/// The payloads shorter than this are sent as they are, since votes gain nothing from
+/// compression.
+pub const MIN_COMPRESS_LEN: usize = 256;
+/// The max length of a decompressed payload.
+pub const MAX_DECOMPRESSED_LEN: usize = 16 * 1024 * 1024;
+
+/// A zstd compressor of the payloads. A dictionary trained on the consensus messages of the
+/// chain by `train_dictionary()` improves the ratio of the small messages a lot. The
+/// dictionary is used only between the nodes of the same dictionary id, the others fall back
+/// to the compression without a dictionary.
+#[derive(Clone)]
+pub struct Compressor {
+    level: i32,
+    dictionary_id: u64,
+    dictionary: Option<(
+        Arc<EncoderDictionary<'static>>,
+        Arc<DecoderDictionary<'static>>,
+    )>,
+}
+
+impl Debug for Compressor {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.debug_struct("Compressor")
+            .field("level", &self.level)
+            .field("dictionary_id", &self.dictionary_id)
+            .finish()
+    }
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Compressor::new(DEFAULT_LEVEL)
+    }
+}
+
+impl Compressor {
+    /// Create a compressor of the level without a dictionary.
+    pub fn new(level: i32) -> Self {
+        Compressor {
+            level,
+            dictionary_id: 0,
+            dictionary: None,
+        }
+    }
+
+    /// Create a compressor of the level with the dictionary.
+    pub fn with_dictionary(level: i32, dictionary: &[u8]) -> Self {
+        Compressor {
+            level,
+            dictionary_id: dictionary_id(dictionary),
+            dictionary: Some((
+                Arc::new(EncoderDictionary::copy(dictionary, level)),
+                Arc::new(DecoderDictionary::copy(dictionary)),
+            )),
+        }
+    }
+
+    /// The id of the dictionary, which is zero without a dictionary.
+    pub fn dictionary_id(&self) -> u64 {
+        self.dictionary_id
+    }
+
+    /// The compressor of the same level without the dictionary.
+    pub fn without_dictionary(&self) -> Self {
+        Compressor::new(self.level)
+    }
+
+    /// Compress a payload of `encode_payload()`. The message tag is kept with the compressed
+    /// flag set, and the payload is returned as it is if the compression does not pay off.
+    pub fn compress_payload(&self, payload: Bytes) -> Bytes {
+        if payload.len() < MIN_COMPRESS_LEN || payload[0] & COMPRESSED_FLAG != 0 {
+            return payload;
+        }
+
+        match self.compress(&payload[1..]) {
+            Ok(data) if data.len() + 1 < payload.len() => {
+                let mut res = BytesMut::with_capacity(data.len() + 1);
+                res.put_u8(payload[0] | COMPRESSED_FLAG);
+                res.extend_from_slice(&data);
+                res.freeze()
+            }
+            _ => payload,
+        }
+    }
+
+    /// Decompress a payload if its compressed flag is set, which can be decoded by
+    /// `decode_payload()` then.
+    pub fn decompress_payload(&self, payload: &[u8]) -> io::Result<Bytes> {
+        match payload.split_first() {
+            Some((tag, data)) if tag & COMPRESSED_FLAG != 0 => {
+                let data = self.decompress(data)?;
+                let mut res = BytesMut::with_capacity(data.len() + 1);
+                res.put_u8(tag & !COMPRESSED_FLAG);
+                res.extend_from_slice(&data);
+                Ok(res.freeze())
+            }
+            _ => Ok(Bytes::copy_from_slice(payload)),
+        }
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match &self.dictionary {
+            Some((dict, _)) => {
+                bulk::Compressor::with_prepared_dictionary(dict)?.compress(data)
+            }
+            None => bulk::compress(data, self.level),
+        }
+    }
+
+    /// Decompress a frame into a buffer of its content size in the frame header, so that a
+    /// small frame does not allocate the max length.
+    fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut decompressor = match &self.dictionary {
+            Some((_, dict)) => bulk::Decompressor::with_prepared_dictionary(dict)?,
+            None => bulk::Decompressor::new()?,
+        };
+        let mut res = Vec::with_capacity(frame_content_size(data)?);
+        decompressor.decompress_to_buffer(data, &mut res)?;
+        Ok(res)
+    }
+}
+
+/// The id of a dictionary by the first 8 bytes of its keccak hash, which is never zero.
+fn dictionary_id(dictionary: &[u8]) -> u64 {
+    let mut hash = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(dictionary);
+    hasher.finalize(&mut hash);
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(id).max(1)
+}
+
+/// Get the content size of a zstd frame from its header. A frame without the content size or
+/// of a content size over `MAX_DECOMPRESSED_LEN` is rejected.
+fn frame_content_size(data: &[u8]) -> io::Result<usize> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    match zstd::zstd_safe::get_frame_content_size(data) {
+        Ok(Some(len)) if len <= MAX_DECOMPRESSED_LEN as u64 => Ok(len as usize),
+        Ok(Some(len)) => Err(invalid(format!(
+            "decompressed length {} is over the max {}",
+            len, MAX_DECOMPRESSED_LEN
+        ))),
+        Ok(None) => Err(invalid("missing content size of zstd frame".to_string())),
+        Err(_) => Err(invalid("invalid zstd frame header".to_string())),
+    }
+}
+
+/// Train a zstd dictionary of at most `max_size` bytes on the sample payloads, such as the
+/// payloads of the recent proposals, votes and QCs of the chain.
+pub fn train_dictionary(samples: &[Bytes], max_size: usize) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size)
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{
+        Compressor, DEFAULT_LEVEL, MAX_DECOMPRESSED_LEN, MIN_COMPRESS_LEN,
+        train_dictionary,
+    };
+    use crate::transport::COMPRESSED_FLAG;
+
+    fn gen_payload(tag: u8, seed: u8, len: usize) -> Bytes {
+        let mut payload = vec![tag];
+        payload.extend((0..len).map(|i| (i % 16) as u8 ^ seed));
+        Bytes::from(payload)
+    }
+
+    #[test]
+    fn test_compress_payload() {
+        let compressor = Compressor::default();
+
+        // A short payload is kept as it is.
+        let payload = gen_payload(1, 0, MIN_COMPRESS_LEN / 2);
+        assert_eq!(compressor.compress_payload(payload.clone()), payload);
+
+        let payload = gen_payload(0, 0, 4096);
+        let compressed = compressor.compress_payload(payload.clone());
+        assert!(compressed.len() < payload.len());
+        assert_eq!(compressed[0], COMPRESSED_FLAG);
+        assert_eq!(compressor.decompress_payload(&compressed).unwrap(), payload);
+        assert_eq!(compressor.decompress_payload(&payload).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let samples = (0..64)
+            .map(|i| gen_payload(2, i as u8, 512))
+            .collect::<Vec<_>>();
+        let dict = train_dictionary(&samples, 4096).unwrap();
+        let compressor = Compressor::with_dictionary(DEFAULT_LEVEL, &dict);
+
+        let payload = gen_payload(2, 7, 512);
+        let compressed = compressor.compress_payload(payload.clone());
+        assert!(compressed.len() < payload.len());
+        assert_eq!(compressor.decompress_payload(&compressed).unwrap(), payload);
+
+        // The dictionaries are told apart by their ids.
+        assert_eq!(Compressor::default().dictionary_id(), 0);
+        assert_ne!(compressor.dictionary_id(), 0);
+        let same = Compressor::with_dictionary(DEFAULT_LEVEL + 1, &dict);
+        assert_eq!(same.dictionary_id(), compressor.dictionary_id());
+        let other = Compressor::with_dictionary(DEFAULT_LEVEL, &dict[1..]);
+        assert_ne!(other.dictionary_id(), compressor.dictionary_id());
+
+        // The fallback without the dictionary works with any plain compressor.
+        let plain = compressor.without_dictionary();
+        assert_eq!(plain.dictionary_id(), 0);
+        let compressed = plain.compress_payload(payload.clone());
+        assert_eq!(
+            Compressor::default().decompress_payload(&compressed).unwrap(),
+            payload
+        );
+    }
+
+    #[test]
+    fn test_decompressed_len() {
+        let compressor = Compressor::default();
+
+        // A small frame allocates its content size only.
+        let payload = gen_payload(0, 0, 4096);
+        let compressed = compressor.compress_payload(payload.clone());
+        let data = compressor.decompress(&compressed[1..]).unwrap();
+        assert_eq!(data.capacity(), payload.len() - 1);
+        assert_eq!(data, payload[1..]);
+
+        // A frame over the max length is rejected before the decompression.
+        let data =
+            zstd::bulk::compress(&vec![0u8; MAX_DECOMPRESSED_LEN + 1], 1).unwrap();
+        assert!(compressor.decompress(&data).is_err());
+        let data = zstd::bulk::compress(&vec![0u8; MAX_DECOMPRESSED_LEN], 1).unwrap();
+        assert_eq!(
+            compressor.decompress(&data).unwrap().len(),
+            MAX_DECOMPRESSED_LEN
+        );
+
+        // So is a frame without the content size, such as a streaming one.
+        let data = zstd::stream::encode_all(&payload[1..], DEFAULT_LEVEL).unwrap();
+        assert!(compressor.decompress(&data).is_err());
+        assert!(compressor.decompress(&[0u8; 4]).is_err());
+    }
+}
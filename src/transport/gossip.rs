@@ -0,0 +1,345 @@
+use std::error::Error;
+use std::pin::Pin;
+use std::time::Duration;
+
+use bytes::Bytes;
+use creep::Context;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::{select, FutureExt, StreamExt};
+use libp2p::gossipsub::{
+    self, IdentTopic, MessageAcceptance, MessageAuthenticity, MessageId,
+    PeerScoreParams, PeerScoreThresholds, TopicScoreParams, ValidationMode,
+};
+use libp2p::identity::Keypair;
+use libp2p::swarm::dial_opts::DialOpts;
+use libp2p::swarm::{Swarm, SwarmEvent};
+use libp2p::{noise, tcp, yamux, Multiaddr, PeerId, SwarmBuilder};
+use log::{debug, info, warn};
+use tiny_keccak::{Hasher, Keccak};
+use tokio::time::{sleep, Sleep};
+
+use crate::error::ConsensusError;
+use crate::transport::{
+    decode_payload, encode_payload, is_unsupported, unversioned_payload,
+    COMPRESSED_FLAG, VERSIONED_FLAG,
+};
+use crate::types::{Address, MlmMsg};
+use crate::{Codec, MlmHandler};
+
+/// The max length of a gossip message.
+pub const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+const DUPLICATE_CACHE_TIME: Duration = Duration::from_secs(120);
+const IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
+const REDIAL_INTERVAL: Duration = Duration::from_secs(10);
+const INVALID_MESSAGE_WEIGHT: f64 = -100.0;
+
+/// The topic names, indexed by the message tags of the payloads.
+const TOPICS: [&str; 12] = [
+    "signed_proposal",
+    "signed_vote",
+    "aggregated_vote",
+    "signed_choke",
+    "compact_proposal",
+    "get_block",
+    "block_response",
+    "parted_proposal",
+    "block_part",
+    "signed_heartbeat",
+    "signed_vote_batch",
+    "signed_key_rotation",
+];
+
+/// The config of a gossip transport.
+#[derive(Clone, Debug)]
+pub struct GossipConfig {
+    /// The identity of the node.
+    pub keypair: Keypair,
+    /// The address to listen on.
+    pub listen_addr: Multiaddr,
+    /// The peers which are dialed at start and redialed when disconnected.
+    pub bootstrap: Vec<(PeerId, Multiaddr)>,
+    /// The network name that prefixes the topics, so that several chains can share the
+    /// peers.
+    pub network: String,
+}
+
+impl GossipConfig {
+    /// The topics of the network, indexed by the message tags of the payloads.
+    pub fn topics(&self) -> Vec<IdentTopic> {
+        TOPICS
+            .iter()
+            .map(|name| IdentTopic::new(format!("/mlm/{}/{}", self.network, name)))
+            .collect()
+    }
+}
+
+/// A transport over libp2p gossipsub. Each type of the messages is published on its own topic,
+/// and the messages are deduplicated by the keccak hash of the topic and the payload.
+///
+/// The inbound messages are validated before they are forwarded. A message which can not be
+/// decoded or arrives on a wrong topic is rejected, and the peer score of its source is
+/// penalized, so a peer that keeps sending invalid messages is graylisted.
+#[derive(Clone, Debug)]
+pub struct GossipTransport {
+    outbound: UnboundedSender<Bytes>,
+}
+
+impl GossipTransport {
+    /// Create a transport, which listens on the address of the config and pumps the inbound
+    /// messages into the mlm handler. This must be called in a tokio runtime.
+    pub fn new<T: Codec + 'static>(
+        config: GossipConfig,
+        handler: MlmHandler<T>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let topics = config.topics();
+        let mut swarm = build_swarm(&config, &topics)?;
+        swarm.listen_on(config.listen_addr.clone())?;
+        dial_peers(&mut swarm, &config.bootstrap);
+
+        let (tx, rx) = unbounded();
+        tokio::spawn(run_swarm(swarm, topics, config.bootstrap, rx, handler));
+        Ok(GossipTransport { outbound: tx })
+    }
+
+    /// Broadcast a message to all of the peers. This can be called by the
+    /// `Consensus::broadcast_to_other()` of an adapter.
+    pub async fn broadcast_to_other<T: Codec>(
+        &self,
+        _ctx: Context,
+        msg: MlmMsg<T>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        // The payloads are published without the protocol version, so the nodes before the
+        // versioning accept them.
+        let payload = unversioned_payload(encode_payload(&msg)?);
+        self.outbound.unbounded_send(payload).map_err(|_| {
+            Box::new(ConsensusError::ChannelErr(
+                "Gossip transport stopped".to_string(),
+            )) as Box<dyn Error + Send>
+        })
+    }
+
+    /// Transmit a message to the relayer. This can be called by the
+    /// `Consensus::transmit_to_relayer()` of an adapter. Gossipsub has no direct messages, so
+    /// the message is published on its topic and the nodes other than the relayer ignore it.
+    pub async fn transmit_to_relayer<T: Codec>(
+        &self,
+        ctx: Context,
+        _addr: Address,
+        msg: MlmMsg<T>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        self.broadcast_to_other(ctx, msg).await
+    }
+}
+
+fn build_swarm(
+    config: &GossipConfig,
+    topics: &[IdentTopic],
+) -> Result<Swarm<gossipsub::Behaviour>, Box<dyn Error + Send + Sync>> {
+    let gossip_config = gossipsub::ConfigBuilder::default()
+        .heartbeat_interval(HEARTBEAT_INTERVAL)
+        .validation_mode(ValidationMode::Strict)
+        .validate_messages()
+        .message_id_fn(message_id)
+        .duplicate_cache_time(DUPLICATE_CACHE_TIME)
+        .max_transmit_size(MAX_MESSAGE_LEN)
+        .build()
+        .map_err(|e| {
+            ConsensusError::ChannelErr(format!("Gossip config error {:?}", e))
+        })?;
+
+    let mut behaviour = gossipsub::Behaviour::new(
+        MessageAuthenticity::Signed(config.keypair.clone()),
+        gossip_config,
+    )
+    .map_err(|e| ConsensusError::ChannelErr(e.to_string()))?;
+
+    let mut score_params = PeerScoreParams::default();
+    for topic in topics.iter() {
+        score_params.topics.insert(
+            topic.hash(),
+            TopicScoreParams {
+                invalid_message_deliveries_weight: INVALID_MESSAGE_WEIGHT,
+                ..Default::default()
+            },
+        );
+        behaviour.subscribe(topic).map_err(|e| {
+            ConsensusError::ChannelErr(format!("Subscribe error {:?}", e))
+        })?;
+    }
+    behaviour
+        .with_peer_score(score_params, PeerScoreThresholds::default())
+        .map_err(ConsensusError::Other)?;
+
+    let swarm = SwarmBuilder::with_existing_identity(config.keypair.clone())
+        .with_tokio()
+        .with_tcp(
+            tcp::Config::default().nodelay(true),
+            noise::Config::new,
+            yamux::Config::default,
+        )?
+        .with_behaviour(|_| behaviour)?
+        .with_swarm_config(|c| c.with_idle_connection_timeout(IDLE_CONNECTION_TIMEOUT))
+        .build();
+    Ok(swarm)
+}
+
+fn message_id(message: &gossipsub::Message) -> MessageId {
+    let mut hash = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(message.topic.as_str().as_bytes());
+    hasher.update(&message.data);
+    hasher.finalize(&mut hash);
+    MessageId::from(hash.to_vec())
+}
+
+/// The index of the topic of a payload by its message tag.
+fn topic_index(tag: u8) -> usize {
+    (tag & !(VERSIONED_FLAG | COMPRESSED_FLAG)) as usize
+}
+
+fn dial_peers(swarm: &mut Swarm<gossipsub::Behaviour>, peers: &[(PeerId, Multiaddr)]) {
+    for (peer_id, addr) in peers.iter() {
+        if swarm.is_connected(peer_id) {
+            continue;
+        }
+
+        let opts = DialOpts::peer_id(*peer_id)
+            .addresses(vec![addr.clone()])
+            .build();
+        if let Err(e) = swarm.dial(opts) {
+            debug!("Mlm: gossip transport dial {} error {:?}", addr, e);
+        }
+    }
+}
+
+async fn run_swarm<T: Codec>(
+    mut swarm: Swarm<gossipsub::Behaviour>,
+    topics: Vec<IdentTopic>,
+    bootstrap: Vec<(PeerId, Multiaddr)>,
+    mut outbound: UnboundedReceiver<Bytes>,
+    handler: MlmHandler<T>,
+) {
+    let new_redial = || -> Pin<Box<Sleep>> { Box::pin(sleep(REDIAL_INTERVAL)) };
+    let mut redial = new_redial().fuse();
+
+    loop {
+        select! {
+            payload = outbound.next() => {
+                let payload = match payload {
+                    Some(payload) => payload,
+                    // The transport is dropped.
+                    None => return,
+                };
+
+                let topic = topics[topic_index(payload[0])].clone();
+                if let Err(e) = swarm.behaviour_mut().publish(topic, payload.to_vec()) {
+                    debug!("Mlm: gossip transport publish error {:?}", e);
+                }
+            }
+
+            event = swarm.select_next_some() => {
+                handle_swarm_event(&mut swarm, &topics, event, &handler);
+            }
+
+            _ = redial => {
+                dial_peers(&mut swarm, &bootstrap);
+                redial = new_redial().fuse();
+            }
+        }
+    }
+}
+
+fn handle_swarm_event<T: Codec>(
+    swarm: &mut Swarm<gossipsub::Behaviour>,
+    topics: &[IdentTopic],
+    event: SwarmEvent<gossipsub::Event>,
+    handler: &MlmHandler<T>,
+) {
+    match event {
+        SwarmEvent::Behaviour(gossipsub::Event::Message {
+            propagation_source,
+            message_id,
+            message,
+        }) => {
+            let acceptance = match decode_payload::<T>(&message.data) {
+                Ok(msg)
+                    if topics
+                        .get(topic_index(message.data[0]))
+                        .is_some_and(|topic| topic.hash() == message.topic) =>
+                {
+                    let peer = propagation_source.to_string();
+                    if let Err(e) = handler.send_msg_from(Context::new(), peer, msg) {
+                        warn!("Mlm: gossip transport send message error {:?}", e);
+                    }
+                    MessageAcceptance::Accept
+                }
+                Err(e) if is_unsupported(e.as_ref()) => {
+                    debug!(
+                        "Mlm: gossip transport ignore a message from {} on {}, {}",
+                        propagation_source, message.topic, e
+                    );
+                    MessageAcceptance::Ignore
+                }
+                res => {
+                    warn!(
+                        "Mlm: gossip transport reject a message from {} on {}, {:?}",
+                        propagation_source,
+                        message.topic,
+                        res.err()
+                    );
+                    MessageAcceptance::Reject
+                }
+            };
+
+            let _ = swarm.behaviour_mut().report_message_validation_result(
+                &message_id,
+                &propagation_source,
+                acceptance,
+            );
+        }
+        SwarmEvent::NewListenAddr { address, .. } => {
+            info!("Mlm: gossip transport listen on {}", address);
+        }
+        SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+            debug!("Mlm: gossip transport connect to {}", peer_id);
+        }
+        SwarmEvent::ConnectionClosed { peer_id, cause, .. } => {
+            debug!(
+                "Mlm: gossip transport disconnect from {}, {:?}",
+                peer_id, cause
+            );
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libp2p::identity::Keypair;
+
+    use super::{topic_index, GossipConfig, TOPICS};
+    use crate::transport::{COMPRESSED_FLAG, TAG_SIGNED_KEY_ROTATION, VERSIONED_FLAG};
+
+    #[test]
+    fn test_topics() {
+        let config = GossipConfig {
+            keypair: Keypair::generate_ed25519(),
+            listen_addr: "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            bootstrap: Vec::new(),
+            network: "test".to_string(),
+        };
+        let topics = config.topics();
+        assert_eq!(topics.len(), TOPICS.len());
+        assert_eq!(topics[1].to_string(), "/mlm/test/signed_vote");
+        assert_eq!(
+            topics[topic_index(TAG_SIGNED_KEY_ROTATION | VERSIONED_FLAG)].to_string(),
+            "/mlm/test/signed_key_rotation"
+        );
+        assert_eq!(
+            topic_index(TAG_SIGNED_KEY_ROTATION | VERSIONED_FLAG | COMPRESSED_FLAG),
+            topic_index(TAG_SIGNED_KEY_ROTATION)
+        );
+    }
+}
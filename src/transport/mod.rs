@@ -0,0 +1,462 @@
+/// The zstd compression of the payloads.
+#[cfg(feature = "compression")]
+pub mod compress;
+/// A transport over libp2p gossipsub with a topic per message type.
+#[cfg(feature = "mlm-libp2p")]
+pub mod gossip;
+/// A transport over TCP with length prefixed frames.
+#[cfg(feature = "tcp")]
+pub mod tcp;
+
+use std::error::Error;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::Codec;
+use crate::error::ConsensusError;
+use crate::types::MlmMsg;
+
+const TAG_SIGNED_PROPOSAL: u8 = 0;
+const TAG_SIGNED_VOTE: u8 = 1;
+const TAG_AGGREGATED_VOTE: u8 = 2;
+const TAG_SIGNED_CHOKE: u8 = 3;
+const TAG_COMPACT_PROPOSAL: u8 = 4;
+const TAG_GET_BLOCK: u8 = 5;
+const TAG_BLOCK_RESPONSE: u8 = 6;
+const TAG_PARTED_PROPOSAL: u8 = 7;
+const TAG_BLOCK_PART: u8 = 8;
+const TAG_SIGNED_HEARTBEAT: u8 = 9;
+const TAG_SIGNED_VOTE_BATCH: u8 = 10;
+const TAG_SIGNED_KEY_ROTATION: u8 = 11;
+
+/// The flag of the message tag which marks a compressed payload.
+pub const COMPRESSED_FLAG: u8 = 0x80;
+/// The flag of the message tag which marks a payload with the protocol version, which is the
+/// byte after the tag. A payload without it is from a node before the versioning, and is
+/// decoded as of the version 1.
+pub const VERSIONED_FLAG: u8 = 0x40;
+/// The version of the wire protocol of the payloads. A payload of a newer version is skipped
+/// with an `UnsupportedMessage` error, so the nodes of the different versions run together
+/// while the validators upgrade one by one.
+pub const PROTOCOL_VERSION: u8 = 1;
+/// The version of the payloads without the versioned flag, whose layout is unchanged by it.
+const UNVERSIONED: u8 = 1;
+
+/// The capabilities of a node on the wire as a bitset. The peers exchange their capabilities
+/// when a connection is set up, and only use the common ones on the connection.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities(pub u64);
+
+impl Capabilities {
+    /// No capabilities.
+    pub const NONE: Capabilities = Capabilities(0);
+    /// Decompress the payloads with the compressed flag.
+    pub const COMPRESSION: Capabilities = Capabilities(1);
+    /// Decode the payloads with the versioned flag.
+    pub const VERSIONED: Capabilities = Capabilities(2);
+    /// Declare the address of the connecting side after the capabilities.
+    pub const IDENTITY: Capabilities = Capabilities(4);
+    /// Declare the id of the compression dictionary after the identity.
+    pub const DICTIONARY: Capabilities = Capabilities(8);
+
+    /// Whether all of the capabilities of the other are supported.
+    pub fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The common capabilities of the two.
+    pub fn intersect(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 & other.0)
+    }
+
+    /// The capabilities of either of the two.
+    pub fn union(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 | other.0)
+    }
+
+    /// The capabilities without the ones of the other.
+    pub fn difference(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 & !other.0)
+    }
+}
+
+/// Encode a message into a payload, which is a message tag with the versioned flag followed by
+/// the protocol version and the rlp encoded message. Only the messages between replicas are
+/// transmittable, the local messages such as `RichStatus` are rejected.
+pub fn encode_payload<T: Codec>(
+    msg: &MlmMsg<T>,
+) -> Result<Bytes, Box<dyn Error + Send>> {
+    let (tag, payload) = match msg {
+        MlmMsg::SignedProposal(sp) => (TAG_SIGNED_PROPOSAL, rlp::encode(sp)),
+        MlmMsg::SignedVote(sv) => (TAG_SIGNED_VOTE, rlp::encode(sv)),
+        MlmMsg::AggregatedVote(av) => (TAG_AGGREGATED_VOTE, rlp::encode(av)),
+        MlmMsg::SignedChoke(sc) => (TAG_SIGNED_CHOKE, rlp::encode(sc)),
+        MlmMsg::CompactProposal(cp) => (TAG_COMPACT_PROPOSAL, rlp::encode(cp)),
+        MlmMsg::GetBlock(gb) => (TAG_GET_BLOCK, rlp::encode(gb)),
+        MlmMsg::BlockResponse(br) => (TAG_BLOCK_RESPONSE, rlp::encode(br)),
+        MlmMsg::PartedProposal(pp) => (TAG_PARTED_PROPOSAL, rlp::encode(pp)),
+        MlmMsg::BlockPart(bp) => (TAG_BLOCK_PART, rlp::encode(bp)),
+        MlmMsg::SignedHeartbeat(sh) => (TAG_SIGNED_HEARTBEAT, rlp::encode(sh)),
+        MlmMsg::SignedVoteBatch(votes) => {
+            (TAG_SIGNED_VOTE_BATCH, rlp::encode_list(votes))
+        }
+        MlmMsg::SignedKeyRotation(skr) => (TAG_SIGNED_KEY_ROTATION, rlp::encode(skr)),
+        _ => {
+            return Err(Box::new(ConsensusError::ChannelErr(format!(
+                "{} is not transmittable",
+                msg
+            ))));
+        }
+    };
+
+    let mut res = BytesMut::with_capacity(payload.len() + 2);
+    res.put_u8(tag | VERSIONED_FLAG);
+    res.put_u8(PROTOCOL_VERSION);
+    res.extend_from_slice(&payload);
+    Ok(res.freeze())
+}
+
+/// Strip the protocol version of a payload for a peer before the versioning. A payload
+/// without the version or of a newer version is returned as it is.
+pub fn unversioned_payload(payload: Bytes) -> Bytes {
+    match payload.first() {
+        Some(tag)
+            if tag & (VERSIONED_FLAG | COMPRESSED_FLAG) == VERSIONED_FLAG
+                && payload.get(1) == Some(&UNVERSIONED) =>
+        {
+            let mut res = BytesMut::with_capacity(payload.len() - 1);
+            res.put_u8(tag & !VERSIONED_FLAG);
+            res.extend_from_slice(&payload[2..]);
+            res.freeze()
+        }
+        _ => payload,
+    }
+}
+
+/// Decode a message from a payload. A compressed payload must be decompressed before. A
+/// payload of a newer protocol version or an unknown message tag is an `UnsupportedMessage`
+/// error, which the transports skip without dropping the peer.
+pub fn decode_payload<T: Codec>(
+    payload: &[u8],
+) -> Result<MlmMsg<T>, Box<dyn Error + Send>> {
+    let (tag, data) = payload.split_first().ok_or_else(|| {
+        Box::new(ConsensusError::DecodeErr("Empty payload".to_string()))
+            as Box<dyn Error + Send>
+    })?;
+    let (tag, version, data) = if tag & VERSIONED_FLAG == 0 {
+        (*tag, UNVERSIONED, data)
+    } else {
+        let (version, data) = data.split_first().ok_or_else(|| {
+            Box::new(ConsensusError::DecodeErr(
+                "Missing protocol version".to_string(),
+            )) as Box<dyn Error + Send>
+        })?;
+        (tag & !VERSIONED_FLAG, *version, data)
+    };
+    let unsupported = || {
+        Box::new(ConsensusError::UnsupportedMessage { version, tag })
+            as Box<dyn Error + Send>
+    };
+    if version > PROTOCOL_VERSION {
+        return Err(unsupported());
+    }
+    let to_box = |e: rlp::DecoderError| Box::new(e) as Box<dyn Error + Send>;
+
+    let msg = match tag {
+        TAG_SIGNED_PROPOSAL => {
+            MlmMsg::SignedProposal(rlp::decode(data).map_err(to_box)?)
+        }
+        TAG_SIGNED_VOTE => MlmMsg::SignedVote(rlp::decode(data).map_err(to_box)?),
+        TAG_AGGREGATED_VOTE => {
+            MlmMsg::AggregatedVote(rlp::decode(data).map_err(to_box)?)
+        }
+        TAG_SIGNED_CHOKE => MlmMsg::SignedChoke(rlp::decode(data).map_err(to_box)?),
+        TAG_COMPACT_PROPOSAL => {
+            MlmMsg::CompactProposal(rlp::decode(data).map_err(to_box)?)
+        }
+        TAG_GET_BLOCK => MlmMsg::GetBlock(rlp::decode(data).map_err(to_box)?),
+        TAG_BLOCK_RESPONSE => MlmMsg::BlockResponse(rlp::decode(data).map_err(to_box)?),
+        TAG_PARTED_PROPOSAL => {
+            MlmMsg::PartedProposal(rlp::decode(data).map_err(to_box)?)
+        }
+        TAG_BLOCK_PART => MlmMsg::BlockPart(rlp::decode(data).map_err(to_box)?),
+        TAG_SIGNED_HEARTBEAT => {
+            MlmMsg::SignedHeartbeat(rlp::decode(data).map_err(to_box)?)
+        }
+        TAG_SIGNED_VOTE_BATCH => {
+            // `as_list()` takes a truncated payload as an empty batch.
+            let rlp = rlp::Rlp::new(data);
+            if !rlp.is_list() {
+                return Err(to_box(rlp::DecoderError::RlpExpectedToBeList));
+            }
+            MlmMsg::SignedVoteBatch(rlp.as_list().map_err(to_box)?)
+        }
+        TAG_SIGNED_KEY_ROTATION => {
+            MlmMsg::SignedKeyRotation(rlp::decode(data).map_err(to_box)?)
+        }
+        _ => return Err(unsupported()),
+    };
+    Ok(msg)
+}
+
+/// Whether the error of `decode_payload()` is of an unsupported message, rather than of a
+/// malformed payload.
+pub fn is_unsupported(err: &(dyn Error + Send + 'static)) -> bool {
+    matches!(
+        err.downcast_ref::<ConsensusError>(),
+        Some(ConsensusError::UnsupportedMessage { .. })
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use rand::{Rng, random, thread_rng};
+
+    use super::{
+        PROTOCOL_VERSION, TAG_SIGNED_VOTE_BATCH, VERSIONED_FLAG, decode_payload,
+        encode_payload, is_unsupported, unversioned_payload,
+    };
+    use crate::batch::BatchMemberProof;
+    use crate::types::{
+        Address, AggregatedChoke, AggregatedSignature, AggregatedVote, BlockPart,
+        BlockResponse, Choke, CompactProposal, GetBlock, Hash, Heartbeat, KeyRotation,
+        MlmMsg, PartedProposal, PoLC, Proposal, SignedChoke, SignedHeartbeat,
+        SignedKeyRotation, SignedProposal, SignedVote, UpdateFrom, Vote, VoteType,
+    };
+    use crate::wire::{RlpCodec, WireCodec};
+
+    fn gen_bytes(max_len: usize) -> Bytes {
+        let len = thread_rng().gen_range(0, max_len + 1);
+        Bytes::from((0..len).map(|_| random::<u8>()).collect::<Vec<_>>())
+    }
+
+    fn gen_hash() -> Hash {
+        Hash::from(gen_bytes(32))
+    }
+
+    fn gen_address() -> Address {
+        let len = thread_rng().gen_range(1, 21);
+        Address::try_from((0..len).map(|_| random::<u8>()).collect::<Vec<_>>()).unwrap()
+    }
+
+    fn gen_vote_type() -> VoteType {
+        if random() {
+            VoteType::Prevote
+        } else {
+            VoteType::Precommit
+        }
+    }
+
+    fn gen_qc() -> AggregatedVote {
+        AggregatedVote {
+            signature: AggregatedSignature {
+                signature: gen_bytes(96),
+                address_bitmap: gen_bytes(8),
+            },
+            vote_type: gen_vote_type(),
+            height: random(),
+            round: random(),
+            block_hash: gen_hash(),
+            leader: gen_address(),
+            extensions: Vec::new(),
+        }
+    }
+
+    fn gen_proposal() -> SignedProposal<Bytes> {
+        SignedProposal {
+            signature: gen_bytes(64),
+            proposal: Proposal {
+                height: random(),
+                round: random(),
+                content: gen_bytes(256),
+                block_hash: gen_hash(),
+                lock: if random() {
+                    Some(PoLC {
+                        lock_round: random(),
+                        lock_votes: gen_qc(),
+                    })
+                } else {
+                    None
+                },
+                proposer: gen_address(),
+                timestamp: None,
+            },
+            timeout_cert: if random() {
+                Some(AggregatedChoke {
+                    height: random(),
+                    round: random(),
+                    signature: gen_bytes(64),
+                    voters: vec![gen_address(), gen_address()],
+                })
+            } else {
+                None
+            },
+            vrf_proof: if random() {
+                gen_bytes(80)
+            } else {
+                Bytes::new()
+            },
+        }
+    }
+
+    fn gen_msg() -> MlmMsg<Bytes> {
+        match random::<u8>() % 12 {
+            0 => MlmMsg::SignedProposal(gen_proposal()),
+            1 => MlmMsg::SignedVote(gen_vote()),
+            2 => MlmMsg::AggregatedVote(gen_qc()),
+            3 => MlmMsg::CompactProposal(CompactProposal::from_signed(&gen_proposal())),
+            4 => MlmMsg::GetBlock(GetBlock {
+                height: random(),
+                round: random(),
+                block_hash: gen_hash(),
+                requester: gen_address(),
+            }),
+            5 => MlmMsg::BlockResponse(BlockResponse {
+                height: random(),
+                round: random(),
+                block_hash: gen_hash(),
+                content: gen_bytes(256),
+            }),
+            6 => MlmMsg::PartedProposal(PartedProposal {
+                proposal: CompactProposal::from_signed(&gen_proposal()),
+                total: random(),
+                root: gen_hash(),
+            }),
+            7 => MlmMsg::BlockPart(BlockPart {
+                height: random(),
+                round: random(),
+                root: gen_hash(),
+                content: gen_bytes(256),
+                proof: BatchMemberProof {
+                    index: random::<u32>() as usize,
+                    len: random::<u32>() as usize,
+                    siblings: (0..random::<u8>() % 8).map(|_| gen_hash()).collect(),
+                },
+            }),
+            8 => MlmMsg::SignedHeartbeat(SignedHeartbeat {
+                signature: gen_bytes(64),
+                heartbeat: Heartbeat {
+                    height: random(),
+                    round: random(),
+                    timestamp: random(),
+                    address: gen_address(),
+                },
+            }),
+            9 => MlmMsg::SignedVoteBatch(
+                (0..random::<u8>() % 8).map(|_| gen_vote()).collect(),
+            ),
+            10 => MlmMsg::SignedKeyRotation(SignedKeyRotation {
+                signature: gen_bytes(64),
+                rotation: KeyRotation {
+                    epoch: random(),
+                    address: gen_address(),
+                    payload: gen_bytes(256),
+                },
+            }),
+            _ => MlmMsg::SignedChoke(SignedChoke {
+                signature: gen_bytes(64),
+                choke: Choke {
+                    height: random(),
+                    round: random(),
+                    from: if random() {
+                        UpdateFrom::PrevoteQC(gen_qc())
+                    } else {
+                        UpdateFrom::PrecommitQC(gen_qc())
+                    },
+                },
+                address: gen_address(),
+            }),
+        }
+    }
+
+    fn gen_vote() -> SignedVote {
+        SignedVote {
+            signature: gen_bytes(64),
+            vote: Vote {
+                height: random(),
+                round: random(),
+                vote_type: gen_vote_type(),
+                block_hash: gen_hash(),
+            },
+            voter: gen_address(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        }
+    }
+
+    /// The bytes that the signature of a message is made over.
+    fn signing_bytes(msg: &MlmMsg<Bytes>) -> Vec<u8> {
+        match msg {
+            MlmMsg::SignedProposal(sp) => rlp::encode(&sp.proposal).to_vec(),
+            MlmMsg::SignedVote(sv) => rlp::encode(&sv.vote).to_vec(),
+            MlmMsg::SignedVoteBatch(votes) => votes
+                .iter()
+                .flat_map(|sv| rlp::encode(&sv.vote).to_vec())
+                .collect(),
+            MlmMsg::AggregatedVote(av) => rlp::encode(&av.to_vote()).to_vec(),
+            MlmMsg::SignedChoke(sc) => RlpCodec
+                .choke_preimage(sc.choke.height, sc.choke.round)
+                .unwrap()
+                .to_vec(),
+            MlmMsg::SignedHeartbeat(sh) => {
+                RlpCodec.heartbeat_preimage(&sh.heartbeat).unwrap().to_vec()
+            }
+            MlmMsg::SignedKeyRotation(skr) => RlpCodec
+                .key_rotation_preimage(&skr.rotation)
+                .unwrap()
+                .to_vec(),
+            // A compact or parted proposal is verified by the restored proposal, and the others
+            // are not signed.
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_protocol_version() {
+        let msg = gen_msg();
+        let payload = encode_payload(&msg).unwrap();
+        assert_eq!(payload[0] & VERSIONED_FLAG, VERSIONED_FLAG);
+        assert_eq!(payload[1], PROTOCOL_VERSION);
+
+        // A payload of a node before the versioning.
+        let legacy = unversioned_payload(payload.clone());
+        assert_eq!(legacy.len(), payload.len() - 1);
+        assert_eq!(decode_payload::<Bytes>(&legacy).unwrap(), msg);
+        assert_eq!(unversioned_payload(legacy.clone()), legacy);
+
+        // A newer version and an unknown tag are unsupported, a truncated payload is not.
+        let mut newer = payload.to_vec();
+        newer[1] = PROTOCOL_VERSION + 1;
+        let err = decode_payload::<Bytes>(&newer).unwrap_err();
+        assert!(is_unsupported(err.as_ref()));
+        assert_eq!(unversioned_payload(Bytes::from(newer.clone())), newer);
+
+        let err = decode_payload::<Bytes>(&[VERSIONED_FLAG | 0x3f, 1]).unwrap_err();
+        assert!(is_unsupported(err.as_ref()));
+        let err = decode_payload::<Bytes>(&payload[..2]).unwrap_err();
+        assert!(!is_unsupported(err.as_ref()));
+        let truncated = [VERSIONED_FLAG | TAG_SIGNED_VOTE_BATCH, PROTOCOL_VERSION];
+        assert!(decode_payload::<Bytes>(&truncated).is_err());
+        assert!(!is_unsupported(
+            decode_payload::<Bytes>(&[VERSIONED_FLAG])
+                .unwrap_err()
+                .as_ref()
+        ));
+    }
+
+    /// Every wire encoding must decode a random message to an equal one with the same signing
+    /// bytes, or the nodes of the different encodings would split on the signatures.
+    #[test]
+    fn test_payload_roundtrip() {
+        for _ in 0..1000 {
+            let msg = gen_msg();
+            let decoded: MlmMsg<Bytes> =
+                decode_payload(&encode_payload(&msg).unwrap()).unwrap();
+            assert_eq!(decoded, msg);
+            assert_eq!(signing_bytes(&decoded), signing_bytes(&msg));
+        }
+        assert!(decode_payload::<Bytes>(&[]).is_err());
+        assert!(decode_payload::<Bytes>(&[0x7f]).is_err());
+    }
+}
@@ -0,0 +1,1016 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use creep::Context;
+use futures::StreamExt;
+use futures::channel::mpsc::{Receiver, Sender, channel};
+use log::{debug, error, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, timeout};
+
+use crate::error::ConsensusError;
+#[cfg(feature = "compression")]
+use crate::transport::compress::Compressor;
+use crate::transport::{
+    Capabilities, decode_payload, encode_payload, is_unsupported, unversioned_payload,
+};
+use crate::types::{Address, MlmMsg};
+use crate::{Codec, MlmHandler};
+
+/// The max length of a frame. A peer sending a longer frame is disconnected.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+const OUTBOUND_QUEUE_LEN: usize = 1024;
+const MIN_RECONNECT_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A TCP transport between the authorities. Each frame on the wire is a 4 bytes big endian
+/// length followed by the payload of `encode_payload()`.
+///
+/// A connection starts with the exchange of the 8 bytes capabilities of the two sides, and
+/// the payloads on it are compressed only if both sides support compression. The connecting
+/// side declares its address then, by a 1 byte length followed by the address bytes. The
+/// listening side rejects a peer that is not in its peers or declares no address. The
+/// declared address is not authenticated, so the messages of a connection are attributed
+/// to its socket address, which the network layer can ban, instead. At last the two sides
+/// exchange the 8 bytes ids of their compression dictionaries, and the payloads are
+/// compressed with the dictionary only if the ids are the same, or without a dictionary
+/// otherwise.
+///
+/// The outbound connection of each peer is kept by a background task which reconnects with
+/// an exponential interval. The messages sent while the peer is unreachable are queued up to a
+/// limit and dropped beyond it, the protocol recovers them by timeouts and resends.
+#[derive(Debug)]
+pub struct TcpTransport {
+    peers: HashMap<Address, Sender<Bytes>>,
+}
+
+impl TcpTransport {
+    /// Create a transport of the local address that connects to the given peers. This must
+    /// be called in a tokio runtime.
+    pub fn new(address: Address, peers: HashMap<Address, SocketAddr>) -> Self {
+        TcpTransport::with_codec(address, peers, WireCodec::default())
+    }
+
+    /// Create a transport that compresses the payloads to the peers which support
+    /// compression. This must be called in a tokio runtime.
+    #[cfg(feature = "compression")]
+    pub fn with_compressor(
+        address: Address,
+        peers: HashMap<Address, SocketAddr>,
+        compressor: Compressor,
+    ) -> Self {
+        TcpTransport::with_codec(address, peers, WireCodec::new(compressor))
+    }
+
+    fn with_codec(
+        address: Address,
+        peers: HashMap<Address, SocketAddr>,
+        codec: WireCodec,
+    ) -> Self {
+        let peers = peers
+            .into_iter()
+            .map(|(peer, socket)| {
+                let (tx, rx) = channel(OUTBOUND_QUEUE_LEN);
+                tokio::spawn(run_outbound(socket, rx, codec.clone(), address.clone()));
+                (peer, tx)
+            })
+            .collect();
+        TcpTransport { peers }
+    }
+
+    /// Broadcast a message to all of the peers. This can be called by the
+    /// `Consensus::broadcast_to_other()` of an adapter.
+    pub async fn broadcast_to_other<T: Codec>(
+        &self,
+        _ctx: Context,
+        msg: MlmMsg<T>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let payload = encode_checked_payload(&msg)?;
+        for (address, tx) in self.peers.iter() {
+            send_payload(address, tx, payload.clone());
+        }
+        Ok(())
+    }
+
+    /// Transmit a message to the relayer. This can be called by the
+    /// `Consensus::transmit_to_relayer()` of an adapter.
+    pub async fn transmit_to_relayer<T: Codec>(
+        &self,
+        _ctx: Context,
+        addr: Address,
+        msg: MlmMsg<T>,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let tx = self.peers.get(&addr).ok_or_else(|| {
+            Box::new(ConsensusError::ChannelErr(format!(
+                "Unknown peer {:?}",
+                addr
+            ))) as Box<dyn Error + Send>
+        })?;
+        send_payload(&addr, tx, encode_checked_payload(&msg)?);
+        Ok(())
+    }
+}
+
+/// Listen on the address and pump the inbound messages of the peers into the mlm handler,
+/// the connections of the other addresses are rejected. Return the local address of the
+/// listener, which is useful when listening on port zero.
+pub async fn listen<T: Codec + 'static>(
+    addr: SocketAddr,
+    handler: MlmHandler<T>,
+    peers: HashSet<Address>,
+) -> io::Result<SocketAddr> {
+    listen_with_codec(addr, handler, peers, WireCodec::default()).await
+}
+
+/// Listen on the address like `listen()`, and accept the compressed payloads from the peers.
+#[cfg(feature = "compression")]
+pub async fn listen_with_compressor<T: Codec + 'static>(
+    addr: SocketAddr,
+    handler: MlmHandler<T>,
+    peers: HashSet<Address>,
+    compressor: Compressor,
+) -> io::Result<SocketAddr> {
+    listen_with_codec(addr, handler, peers, WireCodec::new(compressor)).await
+}
+
+async fn listen_with_codec<T: Codec + 'static>(
+    addr: SocketAddr,
+    handler: MlmHandler<T>,
+    peers: HashSet<Address>,
+    codec: WireCodec,
+) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    let peers = Arc::new(peers);
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, remote)) => {
+                    debug!("Mlm: tcp transport accept a connection from {}", remote);
+                    tokio::spawn(run_inbound(
+                        stream,
+                        remote,
+                        handler.clone(),
+                        Arc::clone(&peers),
+                        codec.clone(),
+                    ));
+                }
+                Err(e) => {
+                    error!("Mlm: tcp transport accept error {:?}", e);
+                    sleep(MIN_RECONNECT_INTERVAL).await;
+                }
+            }
+        }
+    });
+    Ok(local_addr)
+}
+
+/// The payload codec of the connections, which compresses the payloads when the peer supports
+/// it.
+#[derive(Clone, Debug, Default)]
+struct WireCodec {
+    #[cfg(feature = "compression")]
+    compressor: Option<Compressor>,
+}
+
+impl WireCodec {
+    #[cfg(feature = "compression")]
+    fn new(compressor: Compressor) -> Self {
+        WireCodec {
+            compressor: Some(compressor),
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn capabilities(&self) -> Capabilities {
+        let capabilities = Capabilities::VERSIONED.union(Capabilities::IDENTITY);
+        if self.compressor.is_some() {
+            capabilities
+                .union(Capabilities::COMPRESSION)
+                .union(Capabilities::DICTIONARY)
+        } else {
+            capabilities
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::VERSIONED.union(Capabilities::IDENTITY)
+    }
+
+    /// The id of the compression dictionary, which is zero without a dictionary.
+    #[cfg(feature = "compression")]
+    fn dictionary_id(&self) -> u64 {
+        self.compressor
+            .as_ref()
+            .map_or(0, Compressor::dictionary_id)
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn dictionary_id(&self) -> u64 {
+        0
+    }
+
+    /// The codec and the capabilities of a connection by the dictionary id of the peer, which
+    /// is `None` if the peer declares none. The dictionary is used only if the peer has the
+    /// same one, and the compression goes without a dictionary if the peer has another one.
+    /// A peer before the dictionary ids may have any dictionary, so the payloads to it are
+    /// not compressed if the local side has a dictionary.
+    #[cfg(feature = "compression")]
+    fn negotiate(
+        &self,
+        negotiated: Capabilities,
+        remote_dictionary: Option<u64>,
+    ) -> (WireCodec, Capabilities) {
+        let compressor = match self.compressor.as_ref() {
+            Some(compressor) => compressor,
+            None => return (self.clone(), negotiated),
+        };
+        match remote_dictionary {
+            Some(id) if id != compressor.dictionary_id() => {
+                let codec = WireCodec::new(compressor.without_dictionary());
+                (codec, negotiated)
+            }
+            None if compressor.dictionary_id() != 0 => (
+                self.clone(),
+                negotiated.difference(Capabilities::COMPRESSION),
+            ),
+            _ => (self.clone(), negotiated),
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn negotiate(
+        &self,
+        negotiated: Capabilities,
+        _remote_dictionary: Option<u64>,
+    ) -> (WireCodec, Capabilities) {
+        (self.clone(), negotiated)
+    }
+
+    /// Encode the payload for the peer, which is without the protocol version if the peer is
+    /// before the versioning.
+    fn encode(&self, payload: Bytes, negotiated: Capabilities) -> Bytes {
+        let payload = if negotiated.contains(Capabilities::VERSIONED) {
+            payload
+        } else {
+            unversioned_payload(payload)
+        };
+        self.compress(payload, negotiated)
+    }
+
+    #[cfg(feature = "compression")]
+    fn compress(&self, payload: Bytes, negotiated: Capabilities) -> Bytes {
+        match self.compressor.as_ref() {
+            Some(compressor) if negotiated.contains(Capabilities::COMPRESSION) => {
+                compressor.compress_payload(payload)
+            }
+            _ => payload,
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn compress(&self, payload: Bytes, _negotiated: Capabilities) -> Bytes {
+        payload
+    }
+
+    #[cfg(feature = "compression")]
+    fn decode<T: Codec>(
+        &self,
+        payload: &[u8],
+    ) -> Result<MlmMsg<T>, Box<dyn Error + Send>> {
+        match self.compressor.as_ref() {
+            Some(compressor) => {
+                let payload = compressor
+                    .decompress_payload(payload)
+                    .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+                decode_payload(&payload)
+            }
+            None => decode_payload(payload),
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn decode<T: Codec>(
+        &self,
+        payload: &[u8],
+    ) -> Result<MlmMsg<T>, Box<dyn Error + Send>> {
+        decode_payload(payload)
+    }
+}
+
+/// Exchange the capabilities on a new connection, the connecting side speaks first. Return
+/// the common capabilities of the two sides.
+async fn handshake(
+    stream: &mut TcpStream,
+    local: Capabilities,
+    connecting: bool,
+) -> io::Result<Capabilities> {
+    let remote = exchange(stream, local.0, connecting).await?;
+    Ok(local.intersect(Capabilities(remote)))
+}
+
+/// Exchange the dictionary ids after the identity if both sides support it, the connecting
+/// side speaks first. Return the codec and the capabilities of the connection.
+async fn negotiate_dictionary(
+    stream: &mut TcpStream,
+    codec: &WireCodec,
+    negotiated: Capabilities,
+    connecting: bool,
+) -> io::Result<(WireCodec, Capabilities)> {
+    let remote_dictionary = if negotiated.contains(Capabilities::DICTIONARY) {
+        Some(exchange(stream, codec.dictionary_id(), connecting).await?)
+    } else {
+        None
+    };
+    Ok(codec.negotiate(negotiated, remote_dictionary))
+}
+
+/// Exchange an 8 bytes value of the handshake, the connecting side speaks first. Return the
+/// value of the remote side.
+async fn exchange(
+    stream: &mut TcpStream,
+    local: u64,
+    connecting: bool,
+) -> io::Result<u64> {
+    let exchange = async {
+        if connecting {
+            stream.write_u64(local).await?;
+            stream.read_u64().await
+        } else {
+            let remote = stream.read_u64().await?;
+            stream.write_u64(local).await?;
+            Ok(remote)
+        }
+    };
+
+    timeout(HANDSHAKE_TIMEOUT, exchange)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "handshake timeout"))?
+}
+
+/// Declare the address of the connecting side, if both sides support it.
+async fn write_identity(stream: &mut TcpStream, address: &Address) -> io::Result<()> {
+    let mut buf = BytesMut::with_capacity(address.len() + 1);
+    buf.put_u8(address.len() as u8);
+    buf.extend_from_slice(address);
+    stream.write_all(&buf).await
+}
+
+/// Read the address declared by the connecting side.
+async fn read_identity(stream: &mut TcpStream) -> io::Result<Address> {
+    let read = async {
+        let len = stream.read_u8().await? as usize;
+        let mut buf = vec![0u8; len];
+        stream.read_exact(&mut buf).await?;
+        Address::try_from(buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    };
+
+    timeout(HANDSHAKE_TIMEOUT, read)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "handshake timeout"))?
+}
+
+fn send_payload(address: &Address, tx: &Sender<Bytes>, payload: Bytes) {
+    if tx.clone().try_send(payload).is_err() {
+        warn!(
+            "Mlm: tcp transport queue of peer {:?} is full, drop a message",
+            address
+        );
+    }
+}
+
+async fn connect(
+    socket: SocketAddr,
+    codec: &WireCodec,
+    address: &Address,
+) -> io::Result<(TcpStream, Capabilities, WireCodec)> {
+    let mut stream = TcpStream::connect(socket).await?;
+    let _ = stream.set_nodelay(true);
+    let negotiated = handshake(&mut stream, codec.capabilities(), true).await?;
+    // A peer before the identity accepts the connections without it.
+    if negotiated.contains(Capabilities::IDENTITY) {
+        write_identity(&mut stream, address).await?;
+    }
+    let (codec, negotiated) =
+        negotiate_dictionary(&mut stream, codec, negotiated, true).await?;
+    Ok((stream, negotiated, codec))
+}
+
+async fn run_outbound(
+    socket: SocketAddr,
+    mut rx: Receiver<Bytes>,
+    codec: WireCodec,
+    address: Address,
+) {
+    let mut interval = MIN_RECONNECT_INTERVAL;
+    let mut stream: Option<(TcpStream, Capabilities, WireCodec)> = None;
+    let mut pending: Option<Bytes> = None;
+
+    loop {
+        let payload = match pending.take() {
+            Some(payload) => payload,
+            None => match rx.next().await {
+                Some(payload) => payload,
+                // The transport is dropped.
+                None => return,
+            },
+        };
+
+        if stream.is_none() {
+            match connect(socket, &codec, &address).await {
+                Ok(s) => {
+                    interval = MIN_RECONNECT_INTERVAL;
+                    stream = Some(s);
+                }
+                Err(e) => {
+                    debug!("Mlm: tcp transport connect {} error {:?}", socket, e);
+                    pending = Some(payload);
+                    sleep(interval).await;
+                    interval = (interval * 2).min(MAX_RECONNECT_INTERVAL);
+                    continue;
+                }
+            }
+        }
+
+        let (s, negotiated, conn_codec) = stream.as_mut().unwrap();
+        let frame = frame(conn_codec.encode(payload.clone(), *negotiated));
+        if let Err(e) = s.write_all(&frame).await {
+            warn!("Mlm: tcp transport write to {} error {:?}", socket, e);
+            // Retry the payload on a new connection.
+            stream = None;
+            pending = Some(payload);
+        }
+    }
+}
+
+async fn run_inbound<T: Codec>(
+    mut stream: TcpStream,
+    remote: SocketAddr,
+    handler: MlmHandler<T>,
+    peers: Arc<HashSet<Address>>,
+    codec: WireCodec,
+) {
+    let negotiated = match handshake(&mut stream, codec.capabilities(), false).await {
+        Ok(negotiated) => negotiated,
+        Err(e) => {
+            warn!("Mlm: tcp transport handshake with {} error {:?}", remote, e);
+            return;
+        }
+    };
+    if !negotiated.contains(Capabilities::IDENTITY) {
+        warn!("Mlm: tcp transport reject {} without an identity", remote);
+        return;
+    }
+    match read_identity(&mut stream).await {
+        Ok(address) if peers.contains(&address) => {
+            debug!("Mlm: tcp transport accept peer {} of {}", address, remote);
+        }
+        Ok(address) => {
+            warn!(
+                "Mlm: tcp transport reject unknown peer {} of {}",
+                address, remote
+            );
+            return;
+        }
+        Err(e) => {
+            warn!(
+                "Mlm: tcp transport read identity of {} error {:?}",
+                remote, e
+            );
+            return;
+        }
+    }
+    let peer = remote.to_string();
+    let negotiation = negotiate_dictionary(&mut stream, &codec, negotiated, false);
+    let codec = match negotiation.await {
+        Ok((codec, _)) => codec,
+        Err(e) => {
+            warn!(
+                "Mlm: tcp transport negotiate dictionary with {} error {:?}",
+                remote, e
+            );
+            return;
+        }
+    };
+
+    loop {
+        let len = match stream.read_u32().await {
+            Ok(len) => len as usize,
+            Err(_) => return,
+        };
+        if len == 0 || len > MAX_FRAME_LEN {
+            warn!(
+                "Mlm: tcp transport receive a frame of invalid length {} from {}",
+                len, remote
+            );
+            return;
+        }
+
+        let mut buf = vec![0u8; len];
+        if stream.read_exact(&mut buf).await.is_err() {
+            return;
+        }
+
+        match codec.decode::<T>(&buf) {
+            Ok(msg) => {
+                if handler
+                    .send_msg_from(Context::new(), peer.clone(), msg)
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            Err(e) if is_unsupported(e.as_ref()) => {
+                debug!("Mlm: tcp transport skip a message from {}, {}", remote, e);
+            }
+            Err(e) => {
+                warn!(
+                    "Mlm: tcp transport decode a message from {} error {:?}",
+                    remote, e
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Encode a message into a frame with the length prefix.
+pub fn encode_frame<T: Codec>(msg: &MlmMsg<T>) -> Result<Bytes, Box<dyn Error + Send>> {
+    Ok(frame(encode_checked_payload(msg)?))
+}
+
+fn encode_checked_payload<T: Codec>(
+    msg: &MlmMsg<T>,
+) -> Result<Bytes, Box<dyn Error + Send>> {
+    let payload = encode_payload(msg)?;
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(Box::new(ConsensusError::ChannelErr(format!(
+            "Frame length {} exceeds the limit",
+            payload.len()
+        ))));
+    }
+    Ok(payload)
+}
+
+fn frame(payload: Bytes) -> Bytes {
+    let mut frame = BytesMut::with_capacity(payload.len() + 4);
+    frame.put_u32(payload.len() as u32);
+    frame.extend_from_slice(&payload);
+    frame.freeze()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{HashMap, HashSet};
+    use std::net::SocketAddr;
+
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use futures::channel::mpsc::unbounded;
+
+    use super::{TcpTransport, encode_frame, listen, write_identity};
+    use crate::event::EventBus;
+    use crate::state::inbox::Inbound;
+    use crate::transport::decode_payload;
+    use crate::types::{Address, Hash, MlmMsg, SignedVote, Vote, VoteType};
+    use crate::{Context, MlmHandler};
+
+    /// The address of the local node, which is the only peer of the listeners.
+    fn local() -> Address {
+        Address::from_slice(&[6u8; 20]).unwrap()
+    }
+
+    fn local_peers() -> HashSet<Address> {
+        HashSet::from([local()])
+    }
+
+    fn gen_vote_msg(height: u64) -> MlmMsg<Bytes> {
+        MlmMsg::SignedVote(SignedVote {
+            signature: Bytes::from(vec![1u8; 8]),
+            vote: Vote {
+                height,
+                round: 1,
+                vote_type: VoteType::Prevote,
+                block_hash: Hash::from(vec![2u8; 32]),
+            },
+            voter: Address::from_slice(&[3u8; 20]).unwrap(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        })
+    }
+
+    #[test]
+    fn test_frame_codec() {
+        let msg = gen_vote_msg(1);
+        let frame = encode_frame(&msg).unwrap();
+        let len = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+        assert_eq!(len, frame.len() - 4);
+        assert_eq!(decode_payload::<Bytes>(&frame[4..]).unwrap(), msg);
+
+        assert!(encode_frame(&MlmMsg::<Bytes>::Stop).is_err());
+        assert!(decode_payload::<Bytes>(&[]).is_err());
+        assert!(decode_payload::<Bytes>(&[9, 1, 2]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transport() {
+        let (tx, mut rx) = unbounded();
+        let handler = MlmHandler::new(tx, EventBus::new());
+        let addr = listen("127.0.0.1:0".parse().unwrap(), handler, local_peers())
+            .await
+            .unwrap();
+
+        let peer = Address::from_slice(&[4u8; 20]).unwrap();
+        let mut peers = HashMap::new();
+        peers.insert(peer.clone(), addr);
+        let transport = TcpTransport::new(local(), peers);
+
+        transport
+            .broadcast_to_other(Context::new(), gen_vote_msg(1))
+            .await
+            .unwrap();
+        transport
+            .transmit_to_relayer(Context::new(), peer, gen_vote_msg(2))
+            .await
+            .unwrap();
+        assert_eq!(rx.next().await.unwrap().1, Inbound::Msg(gen_vote_msg(1)));
+        assert_eq!(rx.next().await.unwrap().1, Inbound::Msg(gen_vote_msg(2)));
+
+        let unknown = Address::from_slice(&[5u8; 20]).unwrap();
+        assert!(
+            transport
+                .transmit_to_relayer(Context::new(), unknown, gen_vote_msg(3))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_protocol_version() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        use crate::transport::{Capabilities, VERSIONED_FLAG};
+
+        // A message of a newer version is skipped without dropping the connection.
+        let (tx, mut rx) = unbounded();
+        let addr = listen(
+            "127.0.0.1:0".parse().unwrap(),
+            MlmHandler::new(tx, EventBus::new()),
+            local_peers(),
+        )
+        .await
+        .unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let capabilities = Capabilities::VERSIONED.union(Capabilities::IDENTITY);
+        stream.write_u64(capabilities.0).await.unwrap();
+        stream.read_u64().await.unwrap();
+        write_identity(&mut stream, &local()).await.unwrap();
+        stream
+            .write_all(&[0, 0, 0, 4, VERSIONED_FLAG | 1, 2, 0xc0, 0xc0])
+            .await
+            .unwrap();
+        stream
+            .write_all(&encode_frame(&gen_vote_msg(1)).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rx.next().await.unwrap().1, Inbound::Msg(gen_vote_msg(1)));
+
+        // A peer before the versioning gets the payloads without the version.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut peers = HashMap::new();
+        let peer = Address::from_slice(&[4u8; 20]).unwrap();
+        peers.insert(peer, listener.local_addr().unwrap());
+        let transport = TcpTransport::new(local(), peers);
+        transport
+            .broadcast_to_other(Context::new(), gen_vote_msg(2))
+            .await
+            .unwrap();
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        stream.read_u64().await.unwrap();
+        stream.write_u64(Capabilities::NONE.0).await.unwrap();
+        let len = stream.read_u32().await.unwrap() as usize;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).await.unwrap();
+        assert_eq!(payload[0] & VERSIONED_FLAG, 0);
+        assert_eq!(decode_payload::<Bytes>(&payload).unwrap(), gen_vote_msg(2));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        // Take a free port, then send before anyone listens on it.
+        let addr: SocketAddr = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+
+        let mut peers = HashMap::new();
+        peers.insert(Address::from_slice(&[4u8; 20]).unwrap(), addr);
+        let transport = TcpTransport::new(local(), peers);
+        transport
+            .broadcast_to_other(Context::new(), gen_vote_msg(1))
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = unbounded();
+        listen(addr, MlmHandler::new(tx, EventBus::new()), local_peers())
+            .await
+            .unwrap();
+        assert_eq!(rx.next().await.unwrap().1, Inbound::Msg(gen_vote_msg(1)));
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_compression() {
+        use super::listen_with_compressor;
+        use crate::transport::compress::Compressor;
+
+        let (tx, mut rx) = unbounded();
+        let addr = listen_with_compressor(
+            "127.0.0.1:0".parse().unwrap(),
+            MlmHandler::new(tx, EventBus::new()),
+            local_peers(),
+            Compressor::default(),
+        )
+        .await
+        .unwrap();
+        // A peer without compression gets the payloads as they are.
+        let (tx, mut plain_rx) = unbounded();
+        let plain_addr = listen(
+            "127.0.0.1:0".parse().unwrap(),
+            MlmHandler::new(tx, EventBus::new()),
+            local_peers(),
+        )
+        .await
+        .unwrap();
+
+        let mut peers = HashMap::new();
+        peers.insert(Address::from_slice(&[4u8; 20]).unwrap(), addr);
+        peers.insert(Address::from_slice(&[5u8; 20]).unwrap(), plain_addr);
+        let transport =
+            TcpTransport::with_compressor(local(), peers, Compressor::default());
+
+        let mut msg = gen_vote_msg(1);
+        if let MlmMsg::SignedVote(sv) = &mut msg {
+            sv.signature = Bytes::from(vec![1u8; 4096]);
+        }
+        transport
+            .broadcast_to_other(Context::new(), msg.clone())
+            .await
+            .unwrap();
+        assert_eq!(rx.next().await.unwrap().1, Inbound::Msg(msg.clone()));
+        assert_eq!(plain_rx.next().await.unwrap().1, Inbound::Msg(msg.clone()));
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_compression_handshake() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        use super::listen_with_compressor;
+        use crate::transport::compress::Compressor;
+        use crate::transport::{COMPRESSED_FLAG, Capabilities};
+
+        let mut msg = gen_vote_msg(1);
+        if let MlmMsg::SignedVote(sv) = &mut msg {
+            sv.signature = Bytes::from(vec![1u8; 4096]);
+        }
+        let compression = Capabilities::VERSIONED.union(Capabilities::COMPRESSION);
+
+        // The payloads are compressed only to the peer which supports compression.
+        for (remote, compressed) in
+            [(compression, true), (Capabilities::VERSIONED, false)]
+        {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut peers = HashMap::new();
+            let peer = Address::from_slice(&[4u8; 20]).unwrap();
+            peers.insert(peer, listener.local_addr().unwrap());
+            let transport =
+                TcpTransport::with_compressor(local(), peers, Compressor::default());
+            transport
+                .broadcast_to_other(Context::new(), msg.clone())
+                .await
+                .unwrap();
+
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let local = Capabilities(stream.read_u64().await.unwrap());
+            assert!(local.contains(Capabilities::COMPRESSION));
+            stream.write_u64(remote.0).await.unwrap();
+            let len = stream.read_u32().await.unwrap() as usize;
+            let mut payload = vec![0u8; len];
+            stream.read_exact(&mut payload).await.unwrap();
+            assert_eq!(payload[0] & COMPRESSED_FLAG != 0, compressed);
+            let payload = Compressor::default().decompress_payload(&payload).unwrap();
+            assert_eq!(decode_payload::<Bytes>(&payload).unwrap(), msg);
+        }
+
+        // A listener with compression announces it, and takes both of the compressed
+        // payloads and the plain ones.
+        let (tx, mut rx) = unbounded();
+        let addr = listen_with_compressor(
+            "127.0.0.1:0".parse().unwrap(),
+            MlmHandler::new(tx, EventBus::new()),
+            local_peers(),
+            Compressor::default(),
+        )
+        .await
+        .unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let capabilities = Capabilities::VERSIONED.union(Capabilities::IDENTITY);
+        stream.write_u64(capabilities.0).await.unwrap();
+        let remote = Capabilities(stream.read_u64().await.unwrap());
+        assert!(remote.contains(Capabilities::COMPRESSION));
+        write_identity(&mut stream, &local()).await.unwrap();
+
+        let payload = encode_frame(&msg).unwrap();
+        let compressed = Compressor::default().compress_payload(payload.slice(4..));
+        assert!(compressed.len() + 4 < payload.len());
+        stream.write_all(&payload).await.unwrap();
+        stream.write_u32(compressed.len() as u32).await.unwrap();
+        stream.write_all(&compressed).await.unwrap();
+        assert_eq!(rx.next().await.unwrap().1, Inbound::Msg(msg.clone()));
+        assert_eq!(rx.next().await.unwrap().1, Inbound::Msg(msg.clone()));
+
+        // A listener without compression does not announce it.
+        let (tx, _rx) = unbounded::<(Context, Inbound<Bytes>)>();
+        let addr = listen(
+            "127.0.0.1:0".parse().unwrap(),
+            MlmHandler::new(tx, EventBus::new()),
+            local_peers(),
+        )
+        .await
+        .unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_u64(compression.0).await.unwrap();
+        let remote = Capabilities(stream.read_u64().await.unwrap());
+        assert_eq!(
+            remote,
+            Capabilities::VERSIONED.union(Capabilities::IDENTITY)
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_compression_dictionary() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        use super::{listen_with_compressor, read_identity};
+        use crate::transport::compress::{Compressor, DEFAULT_LEVEL, train_dictionary};
+        use crate::transport::{COMPRESSED_FLAG, Capabilities};
+
+        let gen_dictionary = |seed: u8| {
+            let samples = (0..64u8)
+                .map(|i| (0..512).map(move |j| (j % 16) as u8 ^ i ^ seed))
+                .map(|payload| Bytes::from(payload.collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            let dict = train_dictionary(&samples, 4096).unwrap();
+            Compressor::with_dictionary(DEFAULT_LEVEL, &dict)
+        };
+        let (compressor, other) = (gen_dictionary(0), gen_dictionary(0x5a));
+        assert_ne!(compressor.dictionary_id(), other.dictionary_id());
+
+        let mut msg = gen_vote_msg(1);
+        if let MlmMsg::SignedVote(sv) = &mut msg {
+            sv.signature = Bytes::from(vec![1u8; 4096]);
+        }
+
+        // The peers of the different dictionaries fall back to the compression without a
+        // dictionary.
+        let (tx, mut rx) = unbounded();
+        let addr = listen_with_compressor(
+            "127.0.0.1:0".parse().unwrap(),
+            MlmHandler::new(tx, EventBus::new()),
+            local_peers(),
+            other.clone(),
+        )
+        .await
+        .unwrap();
+        let mut peers = HashMap::new();
+        peers.insert(Address::from_slice(&[4u8; 20]).unwrap(), addr);
+        let transport =
+            TcpTransport::with_compressor(local(), peers, compressor.clone());
+        transport
+            .broadcast_to_other(Context::new(), msg.clone())
+            .await
+            .unwrap();
+        assert_eq!(rx.next().await.unwrap().1, Inbound::Msg(msg.clone()));
+
+        // The payloads are compressed with the dictionary only to the peer of the same id,
+        // and not compressed to a peer which declares no id.
+        let full = Capabilities::VERSIONED
+            .union(Capabilities::IDENTITY)
+            .union(Capabilities::COMPRESSION);
+        for (remote, remote_dictionary, compressed, decompressor) in [
+            (
+                full.union(Capabilities::DICTIONARY),
+                compressor.dictionary_id(),
+                true,
+                compressor.clone(),
+            ),
+            (
+                full.union(Capabilities::DICTIONARY),
+                other.dictionary_id(),
+                true,
+                Compressor::default(),
+            ),
+            (full, 0, false, Compressor::default()),
+        ] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut peers = HashMap::new();
+            peers.insert(
+                Address::from_slice(&[4u8; 20]).unwrap(),
+                listener.local_addr().unwrap(),
+            );
+            let transport =
+                TcpTransport::with_compressor(local(), peers, compressor.clone());
+            transport
+                .broadcast_to_other(Context::new(), msg.clone())
+                .await
+                .unwrap();
+
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let local_capabilities = Capabilities(stream.read_u64().await.unwrap());
+            assert!(local_capabilities.contains(Capabilities::DICTIONARY));
+            stream.write_u64(remote.0).await.unwrap();
+            assert_eq!(read_identity(&mut stream).await.unwrap(), local());
+            if remote.contains(Capabilities::DICTIONARY) {
+                let id = stream.read_u64().await.unwrap();
+                assert_eq!(id, compressor.dictionary_id());
+                stream.write_u64(remote_dictionary).await.unwrap();
+            }
+            let len = stream.read_u32().await.unwrap() as usize;
+            let mut payload = vec![0u8; len];
+            stream.read_exact(&mut payload).await.unwrap();
+            assert_eq!(payload[0] & COMPRESSED_FLAG != 0, compressed);
+            let payload = decompressor.decompress_payload(&payload).unwrap();
+            assert_eq!(decode_payload::<Bytes>(&payload).unwrap(), msg);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_peer_identity() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpStream;
+
+        use crate::transport::Capabilities;
+        use crate::types::ContextExt;
+
+        let (tx, mut rx) = unbounded();
+        let addr = listen(
+            "127.0.0.1:0".parse().unwrap(),
+            MlmHandler::new(tx, EventBus::new()),
+            local_peers(),
+        )
+        .await
+        .unwrap();
+
+        // An unknown peer and a peer without an identity are disconnected after the
+        // capabilities.
+        let capabilities = Capabilities::VERSIONED.union(Capabilities::IDENTITY);
+        let unknown = Address::from_slice(&[5u8; 20]).unwrap();
+        for (capabilities, address) in [
+            (capabilities, Some(unknown)),
+            (Capabilities::VERSIONED, None),
+        ] {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_u64(capabilities.0).await.unwrap();
+            stream.read_u64().await.unwrap();
+            if let Some(address) = address {
+                write_identity(&mut stream, &address).await.unwrap();
+            }
+            let _ = stream
+                .write_all(&encode_frame(&gen_vote_msg(1)).unwrap())
+                .await;
+            assert_eq!(stream.read(&mut [0u8; 1]).await.unwrap_or(0), 0);
+        }
+
+        // The messages of a peer are attributed to the socket of its connection instead of the
+        // address it declares, which is not authenticated.
+        let mut peers = HashMap::new();
+        peers.insert(Address::from_slice(&[4u8; 20]).unwrap(), addr);
+        let transport = TcpTransport::new(local(), peers);
+        transport
+            .broadcast_to_other(Context::new(), gen_vote_msg(3))
+            .await
+            .unwrap();
+        let (ctx, msg) = rx.next().await.unwrap();
+        assert_eq!(msg, Inbound::Msg(gen_vote_msg(3)));
+        let peer = ctx.peer().unwrap().parse::<SocketAddr>().unwrap();
+        assert_eq!(peer.ip(), addr.ip());
+        assert_ne!(peer, addr);
+    }
+}
@@ -1,24 +1,240 @@
 use std::cmp::{Ord, Ordering, PartialOrd};
 use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Deref;
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
+use creep::Context;
 use derive_more::Display;
+use hummer::coding::hex_encode;
 use serde::{Deserialize, Serialize};
 
+use crate::batch::BatchMemberProof;
 use crate::error::ConsensusError;
 use crate::smr::smr_types::{SMRStatus, Step, TriggerType};
 use crate::{Codec, DurationConfig};
 
-/// Address type.
-pub type Address = Bytes;
-/// Hash type.
-pub type Hash = Bytes;
+/// The maximum length of an address in bytes.
+pub const MAX_ADDRESS_LEN: usize = 128;
+
+/// Address type. An address is not empty and at most `MAX_ADDRESS_LEN` bytes, which is checked
+/// whenever it is built from bytes, so a malformed address from the network is rejected when
+/// the message is decoded instead of at the signature verification. The `AddressScheme` of
+/// the network may restrict the length further. The default address is empty, which is only
+/// used as a placeholder for no address. An address is displayed as hex.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "Bytes", into = "Bytes")]
+pub struct Address(Bytes);
+
+impl Address {
+    /// Create an address from the bytes, which fails with `InvalidAddress` if the bytes are
+    /// empty or longer than `MAX_ADDRESS_LEN`.
+    pub fn new(bytes: Bytes) -> Result<Self, ConsensusError> {
+        if bytes.is_empty() || bytes.len() > MAX_ADDRESS_LEN {
+            return Err(ConsensusError::InvalidAddress);
+        }
+        Ok(Address(bytes))
+    }
+
+    /// Create an address by copying the bytes.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, ConsensusError> {
+        Address::new(Bytes::copy_from_slice(bytes))
+    }
+
+    /// The bytes of the address.
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.0
+    }
+
+    /// Convert the address into its bytes.
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+impl Deref for Address {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Address {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<Bytes> for Address {
+    type Error = ConsensusError;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        Address::new(bytes)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Address {
+    type Error = ConsensusError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        Address::new(Bytes::from(bytes))
+    }
+}
+
+impl From<Address> for Bytes {
+    fn from(address: Address) -> Bytes {
+        address.0
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex_encode(&self.0))
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address({})", hex_encode(&self.0))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Address {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.int_in_range(1..=MAX_ADDRESS_LEN)?;
+        Ok(Address(Bytes::copy_from_slice(u.bytes(len)?)))
+    }
+}
+
+/// Hash type, the digest of a block or of a signed message. The empty hash stands for no
+/// block, such as the hash of a vote for nil. Since the length of a digest is up to the crypto
+/// of the chain, a hash is built from any bytes and its length is checked against
+/// `MlmConfig::hash_len` by `check_len()` as it comes from the network or the adapter, so a
+/// digest of another length is rejected before the signature verification. A hash is
+/// displayed as hex.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(from = "Bytes", into = "Bytes")]
+pub struct Hash(Bytes);
+
+impl Hash {
+    /// Create an empty hash, which stands for no block.
+    pub fn new() -> Self {
+        Hash(Bytes::new())
+    }
+
+    /// Create a hash from the bytes, which fails with `InvalidHashLength` if the bytes are not
+    /// empty and not of the given length. A zero length means any length.
+    pub fn with_len(bytes: Bytes, len: usize) -> Result<Self, ConsensusError> {
+        let hash = Hash(bytes);
+        hash.check_len(len)?;
+        Ok(hash)
+    }
+
+    /// Create a hash by copying the bytes.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        Hash(Bytes::copy_from_slice(bytes))
+    }
+
+    /// Check that the hash is empty or of the given length. A zero length means any length.
+    pub fn check_len(&self, len: usize) -> Result<(), ConsensusError> {
+        if len == 0 || self.0.is_empty() || self.0.len() == len {
+            return Ok(());
+        }
+        Err(ConsensusError::InvalidHashLength {
+            expect: len,
+            actual: self.0.len(),
+        })
+    }
+
+    /// The bytes of the hash.
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.0
+    }
+
+    /// Convert the hash into its bytes.
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+
+    /// Copy the hash into an array of `N` bytes, or `None` if the hash is not of `N` bytes.
+    pub fn to_array<const N: usize>(&self) -> Option<[u8; N]> {
+        <[u8; N]>::try_from(self.0.as_ref()).ok()
+    }
+}
+
+impl Deref for Hash {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Hash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Bytes> for Hash {
+    fn from(bytes: Bytes) -> Self {
+        Hash(bytes)
+    }
+}
+
+impl From<Vec<u8>> for Hash {
+    fn from(bytes: Vec<u8>) -> Self {
+        Hash(Bytes::from(bytes))
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Hash {
+    fn from(bytes: [u8; N]) -> Self {
+        Hash::from_slice(&bytes)
+    }
+}
+
+impl From<Hash> for Bytes {
+    fn from(hash: Hash) -> Bytes {
+        hash.0
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex_encode(&self.0))
+    }
+}
+
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash({})", hex_encode(&self.0))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Hash {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_bytes(u).map(Hash)
+    }
+}
+
 /// Signature type.
 pub type Signature = Bytes;
 
+/// Arbitrary bytes of the fields of the wire messages, since `Bytes` is not `Arbitrary`.
+#[cfg(feature = "arbitrary")]
+pub(crate) fn arbitrary_bytes(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Bytes> {
+    Ok(Bytes::copy_from_slice(u.arbitrary()?))
+}
+
 /// Vote or QC types. Prevote and precommit QC will promise the rightness and the final consistency
 /// of mlm consensus protocol.
 #[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum VoteType {
     /// Prevote vote or QC.
     #[display(fmt = "Prevote")]
@@ -62,14 +278,17 @@ impl TryFrom<u8> for VoteType {
         match s {
             1 => Ok(VoteType::Prevote),
             2 => Ok(VoteType::Precommit),
-            _ => Err(ConsensusError::Other("".to_string())),
+            _ => Err(ConsensusError::DecodeErr(format!(
+                "Invalid vote type {}",
+                s
+            ))),
         }
     }
 }
 
 /// Mlm messages.
 #[allow(clippy::large_enum_variant)]
-#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
 pub enum MlmMsg<T: Codec> {
     /// Signed proposal message.
     #[display(fmt = "Signed Proposal")]
@@ -86,20 +305,73 @@ pub enum MlmMsg<T: Codec> {
     /// Signed choke message
     #[display(fmt = "Choke Message")]
     SignedChoke(SignedChoke),
-    /// Stop consensus process.
+    /// A signed proposal without the block, which is broadcast in the compact proposal mode.
+    #[display(fmt = "Compact Proposal")]
+    CompactProposal(CompactProposal),
+    /// A request of the block of a compact proposal.
+    #[display(fmt = "Get Block")]
+    GetBlock(GetBlock),
+    /// The block requested by a `GetBlock`.
+    #[display(fmt = "Block Response")]
+    BlockResponse(BlockResponse),
+    /// A signed proposal whose block is broadcast in parts.
+    #[display(fmt = "Parted Proposal")]
+    PartedProposal(PartedProposal),
+    /// A part of the block of a parted proposal.
+    #[display(fmt = "Block Part")]
+    BlockPart(BlockPart),
+    /// A signed heartbeat of a validator.
+    #[display(fmt = "Heartbeat")]
+    SignedHeartbeat(SignedHeartbeat),
+    /// Multiple signed votes packed in one message, such as the retransmissions of a prevote
+    /// and a precommit, or the votes collected by a relayer. The votes are fanned out before
+    /// the verification, and each of them is handled as a `SignedVote`. A node before it skips
+    /// the batch as an unsupported message.
+    #[display(fmt = "Signed Vote Batch")]
+    SignedVoteBatch(Vec<SignedVote>),
+    /// The signed key rotation metadata of a validator, which is handed to its `Crypto`.
+    #[display(fmt = "Key Rotation")]
+    SignedKeyRotation(SignedKeyRotation),
+    /// Stop consensus process. The state handles it before the other pending messages, and
+    /// stops without handling any message after it, see `MlmHandler::stop()`.
     #[display(fmt = "Stop Mlm")]
     Stop,
+    /// Update the timeout configuration of the consensus process. The new configuration takes
+    /// effect from the next timer, without waiting for a commit.
+    #[display(fmt = "Update Config")]
+    UpdateConfig(DurationConfig),
+    /// Force the current round to time out for the reason of the operator, instead of waiting
+    /// for the timeouts of its steps.
+    #[display(fmt = "Force View Change")]
+    ForceViewChange(String),
+    /// Resend the signed proposal and the signed votes of the node in the current round, for
+    /// the copies lost in the network.
+    #[display(fmt = "Rebroadcast Current")]
+    RebroadcastCurrent,
 
     /// This is only for easier testing.
     #[cfg(test)]
     Commit(Commit<T>),
 }
 
+#[cfg(feature = "runtime")]
 impl<T: Codec> MlmMsg<T> {
     pub(crate) fn is_rich_status(&self) -> bool {
         matches!(self, MlmMsg::RichStatus(_))
     }
 
+    /// If the message is a local control message which is not from the network. A control
+    /// message is neither verified nor filtered by height.
+    pub(crate) fn is_control_msg(&self) -> bool {
+        matches!(
+            self,
+            MlmMsg::Stop
+                | MlmMsg::UpdateConfig(_)
+                | MlmMsg::ForceViewChange(_)
+                | MlmMsg::RebroadcastCurrent
+        )
+    }
+
     pub(crate) fn get_height(&self) -> u64 {
         match self {
             MlmMsg::SignedProposal(sp) => sp.proposal.height,
@@ -107,13 +379,66 @@ impl<T: Codec> MlmMsg<T> {
             MlmMsg::AggregatedVote(av) => av.get_height(),
             MlmMsg::RichStatus(s) => s.height,
             MlmMsg::SignedChoke(sc) => sc.choke.height,
+            MlmMsg::CompactProposal(cp) => cp.height,
+            MlmMsg::GetBlock(gb) => gb.height,
+            MlmMsg::BlockResponse(br) => br.height,
+            MlmMsg::PartedProposal(pp) => pp.proposal.height,
+            MlmMsg::BlockPart(bp) => bp.height,
+            MlmMsg::SignedHeartbeat(sh) => sh.heartbeat.height,
+            MlmMsg::SignedVoteBatch(votes) => {
+                votes.first().map_or(0, |sv| sv.get_height())
+            }
+            MlmMsg::SignedKeyRotation(skr) => skr.rotation.epoch,
             _ => unreachable!(),
         }
     }
+
+    /// The round of the message, which is `None` for the messages of no round such as a rich
+    /// status.
+    pub(crate) fn get_round(&self) -> Option<u64> {
+        match self {
+            MlmMsg::SignedProposal(sp) => Some(sp.proposal.round),
+            MlmMsg::SignedVote(sv) => Some(sv.get_round()),
+            MlmMsg::AggregatedVote(av) => Some(av.get_round()),
+            MlmMsg::SignedChoke(sc) => Some(sc.choke.round),
+            MlmMsg::CompactProposal(cp) => Some(cp.round),
+            MlmMsg::GetBlock(gb) => Some(gb.round),
+            MlmMsg::BlockResponse(br) => Some(br.round),
+            MlmMsg::PartedProposal(pp) => Some(pp.proposal.round),
+            MlmMsg::BlockPart(bp) => Some(bp.round),
+            MlmMsg::SignedHeartbeat(sh) => Some(sh.heartbeat.round),
+            MlmMsg::SignedVoteBatch(votes) => votes.first().map(|sv| sv.get_round()),
+            _ => None,
+        }
+    }
+}
+
+/// Only the messages between the replicas are generated, the local messages such as `Stop`
+/// never come from the network.
+#[cfg(feature = "arbitrary")]
+impl<'a, T: Codec + arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for MlmMsg<T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let msg = match u.int_in_range(0..=11u8)? {
+            0 => MlmMsg::SignedProposal(u.arbitrary()?),
+            1 => MlmMsg::SignedVote(u.arbitrary()?),
+            2 => MlmMsg::AggregatedVote(u.arbitrary()?),
+            3 => MlmMsg::SignedChoke(u.arbitrary()?),
+            4 => MlmMsg::CompactProposal(u.arbitrary()?),
+            5 => MlmMsg::GetBlock(u.arbitrary()?),
+            6 => MlmMsg::BlockResponse(u.arbitrary()?),
+            7 => MlmMsg::PartedProposal(u.arbitrary()?),
+            8 => MlmMsg::BlockPart(u.arbitrary()?),
+            9 => MlmMsg::SignedHeartbeat(u.arbitrary()?),
+            10 => MlmMsg::SignedKeyRotation(u.arbitrary()?),
+            _ => MlmMsg::SignedVoteBatch(u.arbitrary()?),
+        };
+        Ok(msg)
+    }
 }
 
 /// How does state goto the current round.
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum UpdateFrom {
     /// From a prevote quorum certificate.
     PrevoteQC(AggregatedVote),
@@ -124,7 +449,7 @@ pub enum UpdateFrom {
 }
 
 /// The reason of mlm view change.
-#[derive(Serialize, Deserialize, Clone, Debug, Display)]
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
 pub enum ViewChangeReason {
     ///
     #[display(fmt = "Do not receive proposal from network")]
@@ -161,20 +486,69 @@ pub enum ViewChangeReason {
     ///
     #[display(fmt = "other reasons")]
     Others,
+
+    /// The round is forced to time out by the operator for the reason.
+    #[display(fmt = "operator triggered: {}", _0)]
+    OperatorTriggered(String),
+
+    /// The signed votes and chokes prove that a quorum is at a higher round.
+    #[display(fmt = "A quorum is at a higher round from {} to {}", _0, _1)]
+    QuorumAtHigherRound(u64, u64),
+}
+
+/// The details of a view change for the operators, which tell the proposer that the round
+/// waits for and where the round spends its time.
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[display(fmt = "View change height {}, round {}, {}", height, round, reason)]
+pub struct ViewChangeInfo {
+    /// The height of the view change.
+    pub height: u64,
+    /// The changed round.
+    pub round: u64,
+    /// The reason of the view change.
+    pub reason: ViewChangeReason,
+    /// The proposer of the changed round, which is the primary one if there are multiple
+    /// proposers.
+    #[serde(with = "super::serde_hex")]
+    pub expected_proposer: Address,
+    /// The elapsed time of each step that the node entered in the round in order. The last
+    /// step lasts until the view change.
+    pub step_elapsed: Vec<(Step, Duration)>,
+    /// The number of the prevotes of the round collected by the node.
+    pub prevotes: usize,
+    /// The number of the precommits of the round collected by the node.
+    pub precommits: usize,
+    /// The number of the chokes of the round collected by the node.
+    pub chokes: usize,
 }
 
 /// A signed proposal.
-#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[display(fmt = "Signed Proposal {:?}", proposal)]
 pub struct SignedProposal<T: Codec> {
     /// Signature of the proposal.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
     pub signature: Bytes,
     /// A proposal.
     pub proposal: Proposal<T>,
+    /// Optional field. The timeout certificate of the previous round, which justifies the nodes
+    /// behind to jump to the round of the proposal. It is verified by its own aggregated
+    /// signature, so it is not signed by the proposer.
+    pub timeout_cert: Option<AggregatedChoke>,
+    /// The proof of the VRF output of the proposer over the height, the round and the seed in
+    /// the VRF leader mode, which ranks the proposal among the others of the round. It is
+    /// verified by `Crypto::vrf_verify()`, so it is not signed by the proposer. Empty without
+    /// the VRF leader mode.
+    #[serde(default, with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub vrf_proof: Bytes,
 }
 
 /// A proposal
-#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[display(fmt = "Proposal height {}, round {}", height, round)]
 pub struct Proposal<T: Codec> {
     /// Height of the proposal.
@@ -184,15 +558,22 @@ pub struct Proposal<T: Codec> {
     /// Proposal content.
     pub content: T,
     /// Proposal block hash.
+    #[serde(with = "super::serde_hex")]
     pub block_hash: Hash,
     /// Optional field. If the proposal has a PoLC, this contains the lock round and lock votes.
     pub lock: Option<PoLC>,
     /// Proposer address.
+    #[serde(with = "super::serde_hex")]
     pub proposer: Address,
+    /// Optional field. The milliseconds since the Unix epoch when the proposal is made, which
+    /// is stamped and validated only if `MlmConfig::timestamp_drift` is enabled.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
 }
 
 /// A PoLC.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct PoLC {
     /// Lock round of the proposal.
     pub lock_round: u64,
@@ -200,16 +581,174 @@ pub struct PoLC {
     pub lock_votes: AggregatedVote,
 }
 
+/// A signed proposal without the block content. The signature is of the whole proposal, so it
+/// is verified after the block is fetched and the signed proposal is restored.
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[display(fmt = "Compact proposal height {}, round {}", height, round)]
+pub struct CompactProposal {
+    /// Signature of the whole proposal.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub signature: Signature,
+    /// Height of the proposal.
+    pub height: u64,
+    /// Round of the proposal.
+    pub round: u64,
+    /// Proposal block hash.
+    #[serde(with = "super::serde_hex")]
+    pub block_hash: Hash,
+    /// Optional field. If the proposal has a PoLC, this contains the lock round and lock votes.
+    pub lock: Option<PoLC>,
+    /// Proposer address.
+    #[serde(with = "super::serde_hex")]
+    pub proposer: Address,
+    /// Optional field. The timeout certificate of the previous round.
+    pub timeout_cert: Option<AggregatedChoke>,
+    /// Optional field. The timestamp of the proposal.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    /// The VRF proof of the proposer, which is empty without the VRF leader mode.
+    #[serde(default, with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub vrf_proof: Bytes,
+}
+
+impl CompactProposal {
+    /// Strip the block content of a signed proposal.
+    pub fn from_signed<T: Codec>(sp: &SignedProposal<T>) -> Self {
+        CompactProposal {
+            signature: sp.signature.clone(),
+            height: sp.proposal.height,
+            round: sp.proposal.round,
+            block_hash: sp.proposal.block_hash.clone(),
+            lock: sp.proposal.lock.clone(),
+            proposer: sp.proposal.proposer.clone(),
+            timeout_cert: sp.timeout_cert.clone(),
+            timestamp: sp.proposal.timestamp,
+            vrf_proof: sp.vrf_proof.clone(),
+        }
+    }
+
+    /// Restore the signed proposal with the block content.
+    pub fn into_signed<T: Codec>(self, content: T) -> SignedProposal<T> {
+        SignedProposal {
+            signature: self.signature,
+            proposal: Proposal {
+                height: self.height,
+                round: self.round,
+                content,
+                block_hash: self.block_hash,
+                lock: self.lock,
+                proposer: self.proposer,
+                timestamp: self.timestamp,
+            },
+            timeout_cert: self.timeout_cert,
+            vrf_proof: self.vrf_proof,
+        }
+    }
+}
+
+/// A request of the block of a compact proposal, which is sent to the proposer.
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[display(fmt = "Get block height {}, round {}", height, round)]
+pub struct GetBlock {
+    /// Height of the proposal.
+    pub height: u64,
+    /// Round of the proposal.
+    pub round: u64,
+    /// Hash of the requested block.
+    #[serde(with = "super::serde_hex")]
+    pub block_hash: Hash,
+    /// Address of the requester to respond to.
+    #[serde(with = "super::serde_hex")]
+    pub requester: Address,
+}
+
+/// The block of a compact proposal, which is encoded by the `Codec` of the block.
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[display(fmt = "Block response height {}, round {}", height, round)]
+pub struct BlockResponse {
+    /// Height of the proposal.
+    pub height: u64,
+    /// Round of the proposal.
+    pub round: u64,
+    /// Hash of the block.
+    #[serde(with = "super::serde_hex")]
+    pub block_hash: Hash,
+    /// The encoded block.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub content: Bytes,
+}
+
+/// A signed proposal without the block content, whose encoded block is split into the parts
+/// of the merkle root. The signature is of the whole proposal, so it is verified after the
+/// parts are reassembled and the signed proposal is restored.
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[display(
+    fmt = "Parted proposal height {}, round {}",
+    "proposal.height",
+    "proposal.round"
+)]
+pub struct PartedProposal {
+    /// The proposal without the block.
+    pub proposal: CompactProposal,
+    /// The number of the parts of the block.
+    pub total: u64,
+    /// The merkle root of the hashes of the parts.
+    #[serde(with = "super::serde_hex")]
+    pub root: Hash,
+}
+
+/// A part of the encoded block of a parted proposal with the merkle proof of it to the root.
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[display(fmt = "Block part height {}, round {}", height, round)]
+pub struct BlockPart {
+    /// Height of the proposal.
+    pub height: u64,
+    /// Round of the proposal.
+    pub round: u64,
+    /// The merkle root of the parts of the block.
+    #[serde(with = "super::serde_hex")]
+    pub root: Hash,
+    /// The bytes of the part.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub content: Bytes,
+    /// The proof of the part to the root, which carries the index of the part and the number
+    /// of the parts.
+    pub proof: BatchMemberProof,
+}
+
 /// A signed vote.
-#[derive(Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[display(fmt = "Signed vote {:?}", vote)]
 pub struct SignedVote {
     /// Signature of the vote.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
     pub signature: Bytes,
     /// A vote.
     pub vote: Vote,
     /// Voter address.
+    #[serde(with = "super::serde_hex")]
     pub voter: Address,
+    /// The extension of a precommit of a block returned by `Consensus::extend_vote()`, which
+    /// is empty without one.
+    #[serde(default, with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub extension: Bytes,
+    /// The signature of the voter over the vote and the extension, which is empty without an
+    /// extension.
+    #[serde(default, with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub extension_signature: Signature,
 }
 
 impl PartialOrd for SignedVote {
@@ -244,21 +783,54 @@ impl SignedVote {
     pub fn is_prevote(&self) -> bool {
         self.vote.vote_type == VoteType::Prevote
     }
+
+    /// The extension of the vote with its voter, if it has one.
+    pub fn extension(&self) -> Option<VoteExtension> {
+        (!self.extension.is_empty()).then(|| VoteExtension {
+            voter: self.voter.clone(),
+            payload: self.extension.clone(),
+            signature: self.extension_signature.clone(),
+        })
+    }
+}
+
+/// The extension of a precommit, which is a payload defined by the application, such as an
+/// oracle price or a share of a threshold decryption, bound to the consensus by the signature
+/// of the voter over `canonical::vote_extension_preimage()`. The extensions of the precommits
+/// aggregated in a QC are carried by the QC and the proof of the commit.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct VoteExtension {
+    /// The voter of the precommit.
+    #[serde(with = "super::serde_hex")]
+    pub voter: Address,
+    /// The payload returned by `Consensus::extend_vote()` of the voter.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub payload: Bytes,
+    /// The signature of the voter over the vote and the payload.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub signature: Signature,
 }
 
 /// An aggregate signature.
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct AggregatedSignature {
     /// Aggregated signature.
     #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
     pub signature: Signature,
     /// Voter address bit map.
     #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
     pub address_bitmap: Bytes,
 }
 
 /// An aggregated vote.
 #[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[rustfmt::skip]
 #[display(fmt = "{:?} aggregated vote height {}, round {}", vote_type, height, round)]
 pub struct AggregatedVote {
@@ -273,9 +845,16 @@ pub struct AggregatedVote {
     /// Proposal hash of the vote.
     #[serde(with = "super::serde_hex")]
     pub block_hash: Hash,
-    /// The leader that aggregate the signed votes.
-    #[serde(with = "super::serde_hex")]
+    /// The leader that aggregate the signed votes. The initial QC has no leader.
+    #[serde(
+        serialize_with = "super::serde_hex::serialize",
+        deserialize_with = "super::serde_hex::deserialize_or_default"
+    )]
     pub leader: Address,
+    /// The extensions of the precommits aggregated in a precommit QC, sorted by the voters.
+    /// It is empty for a prevote QC.
+    #[serde(default)]
+    pub extensions: Vec<VoteExtension>,
 }
 
 impl AggregatedVote {
@@ -306,7 +885,8 @@ impl AggregatedVote {
 }
 
 /// A vote.
-#[derive(Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[display(fmt = "{:?} vote height {}, round {}", vote_type, height, round)]
 pub struct Vote {
     /// Height of the vote.
@@ -316,11 +896,12 @@ pub struct Vote {
     /// Type of the vote.
     pub vote_type: VoteType,
     /// Block hash of the vote.
+    #[serde(with = "super::serde_hex")]
     pub block_hash: Hash,
 }
 
 /// A commit.
-#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
 #[display(fmt = "Commit height {}", height)]
 pub struct Commit<T: Codec> {
     /// Height of the commit.
@@ -329,33 +910,92 @@ pub struct Commit<T: Codec> {
     pub content: T,
     /// The consensus proof.
     pub proof: Proof,
+    /// The round in which the block is decided, which is the round of the proof.
+    pub round: u64,
+    /// The milliseconds since the Unix epoch when the node entered the height.
+    pub height_start: u64,
+    /// The milliseconds since the Unix epoch when the node entered the round in which it
+    /// commits the block, which is the decided round unless the node catches up later.
+    pub round_start: u64,
+    /// The milliseconds since the Unix epoch when the block is decided.
+    pub committed_at: u64,
+    /// The timestamp of the proposal of the decided round, which is known only if the
+    /// timestamps are enabled and the node has received the proposal.
+    pub timestamp: Option<u64>,
 }
 
-/// A Proof.
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl<T: Codec> Commit<T> {
+    /// The finality latency of the block measured by the node, from entering the height to
+    /// deciding the block.
+    pub fn finality_latency(&self) -> Duration {
+        Duration::from_millis(self.committed_at.saturating_sub(self.height_start))
+    }
+}
+
+/// A Proof, which is assembled at the commit of a height and handed to the `commit()`
+/// interface. It is verified by the authority list of the height alone with `verify_proof()`,
+/// so a bridge or a light client ships it by rlp or serde without the consensus state.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Proof {
     /// Height of the proof.
     pub height: u64,
     /// Round of the proof.
     pub round: u64,
     /// Block hash of the proof.
+    #[serde(with = "super::serde_hex")]
     pub block_hash: Hash,
     /// Aggregated signature of the proof.
     pub signature: AggregatedSignature,
+    /// The extensions of the precommits carried by the precommit QC of the proof, sorted by
+    /// the voters.
+    #[serde(default)]
+    pub extensions: Vec<VoteExtension>,
+}
+
+/// The proof of an epoch handover, which is the proof of the last height of an epoch signed by
+/// the authority list of the epoch, with the authority list of the next epoch. A light client
+/// that trusts the list of an epoch follows the handovers to the lists of the later epochs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HandoverProof {
+    /// The proof of the last height of the epoch.
+    pub proof: Proof,
+    /// The authority list of the next epoch, which is sorted.
+    pub next_authority_list: Vec<Node>,
 }
 
 /// A rich status.
-#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
 #[display(fmt = "Rich status height {}", height)]
 pub struct Status {
     /// New height.
     pub height: u64,
-    /// New block interval.
-    pub interval: Option<u64>,
+    /// New block interval. It is serialized in milliseconds as before, and a sub-millisecond
+    /// interval is a float in the human readable formats.
+    #[serde(with = "super::serde_millis")]
+    pub interval: Option<Duration>,
     /// New timeout configuration.
     pub timer_config: Option<DurationConfig>,
     /// New authority list.
     pub authority_list: Vec<Node>,
+    /// The authority list of the next epoch, which is set in the status of the last height of
+    /// an epoch. The height is still decided by the authority list of the status, and the
+    /// list of the next epoch takes effect from the next height.
+    #[serde(default)]
+    pub next_authority_list: Option<Vec<Node>>,
+    /// New seed of the proposer schedule, which offsets the rotation of the proposers, or
+    /// seeds the random proposers with the `random_leader` feature. The chains of the same
+    /// authority list elect different leaders by different seeds, and governance reshuffles
+    /// the schedule by a new seed. The seed is kept if it is `None`, and it is zero before
+    /// the first one.
+    #[serde(default)]
+    pub proposer_seed: Option<u64>,
+    /// The proposers that propose after all of the other authorities in the rounds of the
+    /// height, such as the ones failing repeatedly by `ProposerReliability`. The proposers are
+    /// decided by the application from the data that every node shares, such as the proofs
+    /// in the chain, so that the nodes elect the same proposers. Empty means no proposer is
+    /// skipped.
+    #[serde(default, with = "super::serde_multi_hex")]
+    pub skipped_proposers: Vec<Address>,
 }
 
 impl From<Status> for SMRStatus {
@@ -369,10 +1009,181 @@ impl From<Status> for SMRStatus {
 }
 
 impl Status {
+    /// Set the new block interval in milliseconds.
+    pub fn set_interval_ms(&mut self, interval: u64) {
+        self.interval = Some(Duration::from_millis(interval));
+    }
+
+    #[cfg(feature = "runtime")]
     pub(crate) fn is_consensus_node(&self, address: &Address) -> bool {
         self.authority_list
             .iter()
-            .any(|node| node.address == address)
+            .any(|node| &node.address == address)
+    }
+}
+
+const ROUND_CONTEXT_KEY: &str = "mlm_round_context";
+const CORRELATION_ID_KEY: &str = "mlm_correlation_id";
+const ALLOW_EMPTY_BLOCK_KEY: &str = "mlm_allow_empty_block";
+const DEADLINE_KEY: &str = "mlm_deadline";
+const TRACE_ID_KEY: &str = "mlm_trace_id";
+const PEER_KEY: &str = "mlm_peer";
+
+/// The typed accessors of the standard keys of a `Context`. The deadline is the instant after
+/// which the work of the context is irrelevant: a message of an expired context is dropped
+/// before it is verified or handled, and the adapter calls carry the deadline of their round.
+/// The trace id is the one of the application, and the peer is the transport address of the
+/// peer that a message is received from.
+pub trait ContextExt {
+    /// Attach the deadline.
+    fn with_deadline(&self, deadline: Instant) -> Context;
+
+    /// The deadline of the context.
+    fn deadline(&self) -> Option<Instant>;
+
+    /// Whether the deadline of the context has passed. A context without a deadline never
+    /// expires.
+    #[cfg(feature = "runtime")]
+    fn is_expired(&self) -> bool {
+        self.deadline()
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    /// Attach the trace id.
+    fn with_trace_id(&self, trace_id: String) -> Context;
+
+    /// The trace id of the context.
+    fn trace_id(&self) -> Option<&str>;
+
+    /// Attach the identity of the peer on the transport, such as the socket address of a TCP
+    /// connection or the id of a libp2p peer.
+    fn with_peer(&self, peer: String) -> Context;
+
+    /// The identity of the peer of the context on the transport.
+    fn peer(&self) -> Option<&str>;
+}
+
+impl ContextExt for Context {
+    fn with_deadline(&self, deadline: Instant) -> Context {
+        self.with_value(DEADLINE_KEY, deadline)
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.get::<Instant>(DEADLINE_KEY).copied()
+    }
+
+    fn with_trace_id(&self, trace_id: String) -> Context {
+        self.with_value(TRACE_ID_KEY, trace_id)
+    }
+
+    fn trace_id(&self) -> Option<&str> {
+        self.get::<String>(TRACE_ID_KEY).map(String::as_str)
+    }
+
+    fn with_peer(&self, peer: String) -> Context {
+        self.with_value(PEER_KEY, peer)
+    }
+
+    fn peer(&self) -> Option<&str> {
+        self.get::<String>(PEER_KEY).map(String::as_str)
+    }
+}
+
+/// The correlation ID of a round, which is derived from the height and the round only. So it
+/// is the same on every node and in every subsystem, and is attached to the spans, logs,
+/// events, errors and adapter contexts of the round.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+#[display(fmt = "mlm-{}-{}", height, round)]
+pub struct CorrelationId {
+    /// The height of the round.
+    pub height: u64,
+    /// The round.
+    pub round: u64,
+}
+
+impl CorrelationId {
+    /// Create the correlation ID of the round.
+    pub fn new(height: u64, round: u64) -> Self {
+        CorrelationId { height, round }
+    }
+
+    /// Get the correlation ID from the context of an adapter call.
+    pub fn from_context(ctx: &Context) -> Option<CorrelationId> {
+        ctx.get::<CorrelationId>(CORRELATION_ID_KEY).copied()
+    }
+
+    #[cfg(feature = "runtime")]
+    pub(crate) fn attach_to(self, ctx: Context) -> Context {
+        ctx.with_value(CORRELATION_ID_KEY, self)
+    }
+}
+
+/// The round that a call of `Consensus::get_block()` or `Consensus::check_block()` is made in,
+/// which is carried by the `Context` of the call. The application can adapt to it, such as
+/// to build a smaller block on a retry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundContext {
+    /// The height of the round.
+    pub height: u64,
+    /// The round.
+    pub round: u64,
+    /// The proposer of the block to get or to check.
+    pub proposer: Address,
+    /// Whether the round is a retry of the height, that is not its first round.
+    pub is_retry: bool,
+    /// The deadline of the call. It is the end of the propose step of the round for
+    /// `get_block()`, after which the node votes without the block, and the end of the round
+    /// for `check_block()`. The call is cancelled at the deadline and its late result is
+    /// discarded, so the application should stop the work by then.
+    pub deadline: Instant,
+    /// The timestamp of the proposal of the block to check, which has been validated against
+    /// the local clock and the median time of the recent commits. It is none for
+    /// `get_block()` and if the timestamps are disabled.
+    pub timestamp: Option<u64>,
+}
+
+impl RoundContext {
+    /// Get the round context from the context of an adapter call.
+    pub fn from_context(ctx: &Context) -> Option<RoundContext> {
+        ctx.get::<RoundContext>(ROUND_CONTEXT_KEY).cloned()
+    }
+
+    /// The time left before the deadline of the call.
+    #[cfg(feature = "runtime")]
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// The correlation ID of the round.
+    pub fn correlation_id(&self) -> CorrelationId {
+        CorrelationId::new(self.height, self.round)
+    }
+
+    #[cfg(feature = "runtime")]
+    pub(crate) fn attach_to(self, ctx: Context) -> Context {
+        let ctx = self
+            .correlation_id()
+            .attach_to(ctx)
+            .with_deadline(self.deadline);
+        ctx.with_value(ROUND_CONTEXT_KEY, self)
+    }
+}
+
+/// The mark of a `Consensus::get_block()` call that accepts an empty block. Every call is
+/// marked unless the empty block suppression is enabled, in which case the application returns
+/// `ConsensusError::NothingToPropose` for an unmarked call if it has nothing to propose.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllowEmptyBlock;
+
+impl AllowEmptyBlock {
+    /// Whether the context of an adapter call is marked.
+    pub fn in_context(ctx: &Context) -> bool {
+        ctx.get::<AllowEmptyBlock>(ALLOW_EMPTY_BLOCK_KEY).is_some()
+    }
+
+    #[cfg(feature = "runtime")]
+    pub(crate) fn attach_to(self, ctx: Context) -> Context {
+        ctx.with_value(ALLOW_EMPTY_BLOCK_KEY, self)
     }
 }
 
@@ -385,8 +1196,9 @@ pub struct Node {
     /// The propose weight of the node. The field is only effective in `features =
     /// "random_leader"`.
     pub propose_weight: u32,
-    /// The vote weight of the node.
-    pub vote_weight: u32,
+    /// The vote weight of the node, which can be the raw stake amount of the node since the
+    /// thresholds are computed without overflow.
+    pub vote_weight: u128,
 }
 
 impl PartialOrd for Node {
@@ -407,7 +1219,7 @@ impl Node {
         Node {
             address: addr,
             propose_weight: 1u32,
-            vote_weight: 1u32,
+            vote_weight: 1u128,
         }
     }
 
@@ -418,12 +1230,13 @@ impl Node {
     }
 
     /// Set a new vote weight of the node.
-    pub fn set_vote_weight(&mut self, vote_weight: u32) {
+    pub fn set_vote_weight(&mut self, vote_weight: u128) {
         self.vote_weight = vote_weight;
     }
 }
 
 /// A verify response.
+#[cfg(feature = "runtime")]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct VerifyResp {
     /// The height of the verified block.
@@ -438,6 +1251,7 @@ pub(crate) struct VerifyResp {
 
 /// An aggregated choke.
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct AggregatedChoke {
     /// The height of the aggregated choke.
     pub height: u64,
@@ -445,18 +1259,15 @@ pub struct AggregatedChoke {
     pub round: u64,
     /// The aggregated signature of the aggregated choke.
     #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
     pub signature: Signature,
     /// The voters of the aggregated choke.
     #[serde(with = "super::serde_multi_hex")]
     pub voters: Vec<Address>,
 }
 
-#[allow(clippy::len_without_is_empty)]
 impl AggregatedChoke {
-    pub(crate) fn len(&self) -> usize {
-        self.voters.len()
-    }
-
+    #[cfg(feature = "runtime")]
     pub(crate) fn to_hash(&self) -> HashChoke {
         HashChoke {
             height: self.height,
@@ -466,18 +1277,23 @@ impl AggregatedChoke {
 }
 
 /// A signed choke.
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct SignedChoke {
     /// The signature of the choke.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
     pub signature: Signature,
     /// The choke message.
     pub choke: Choke,
     /// The choke address.
+    #[serde(with = "super::serde_hex")]
     pub address: Address,
 }
 
 /// A choke.
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Choke {
     /// The height of the choke.
     pub height: u64,
@@ -487,13 +1303,96 @@ pub struct Choke {
     pub from: UpdateFrom,
 }
 
-impl Choke {
-    pub(crate) fn to_hash(&self) -> HashChoke {
-        HashChoke {
-            height: self.height,
-            round: self.round,
-        }
-    }
+/// A signed heartbeat.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SignedHeartbeat {
+    /// The signature of the heartbeat.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub signature: Signature,
+    /// The heartbeat message.
+    pub heartbeat: Heartbeat,
+}
+
+/// A heartbeat of a validator, which is broadcast every `MlmConfig::heartbeat_interval` even
+/// if the rounds make no progress, such as in a long brake.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Heartbeat {
+    /// The height of the validator.
+    pub height: u64,
+    /// The round of the validator.
+    pub round: u64,
+    /// Milliseconds since the Unix epoch when the heartbeat is signed. It increases for each
+    /// heartbeat of a validator, so a replayed heartbeat is ignored.
+    pub timestamp: u64,
+    /// The address of the validator.
+    #[serde(with = "super::serde_hex")]
+    pub address: Address,
+}
+
+/// An attestation of the consensus state of a node signed by it, which is delivered to
+/// `Consensus::report_attestation()` every `MlmConfig::attestation_interval`. An external
+/// monitor collects the attestations of a fleet of validators and checks that they agree on
+/// the state by the signatures, without trusting the metrics of the nodes.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct SignedAttestation {
+    /// The signature of the attestation, whose preimage is
+    /// `canonical::attestation_preimage()` whatever the wire codec is.
+    #[serde(with = "super::serde_hex")]
+    pub signature: Signature,
+    /// The attestation message.
+    pub attestation: Attestation,
+}
+
+/// The consensus state of a node at the time of an attestation.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Attestation {
+    /// The height of the node.
+    pub height: u64,
+    /// The round of the node.
+    pub round: u64,
+    /// The hash of the block locked by the node, which is empty without a lock.
+    #[serde(with = "super::serde_hex")]
+    pub lock_hash: Hash,
+    /// Milliseconds since the Unix epoch when the attestation is signed. It increases for
+    /// each attestation of a node.
+    pub timestamp: u64,
+    /// The address of the node.
+    #[serde(with = "super::serde_hex")]
+    pub address: Address,
+}
+
+/// A signed key rotation.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SignedKeyRotation {
+    /// The signature of the key rotation.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub signature: Signature,
+    /// The key rotation message.
+    pub rotation: KeyRotation,
+}
+
+/// The key rotation metadata of a validator for the group keys of an epoch, such as the
+/// dealing of a distributed key generation or a new public key. It is returned by
+/// `Crypto::on_authority_change()` and broadcast to the authorities of the current and the
+/// next epoch, then handed to `Crypto::on_key_rotation()` of each of them, so the rotation is
+/// coordinated in band.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct KeyRotation {
+    /// The epoch of the keys, which is the first height of the authority list of the epoch.
+    pub epoch: u64,
+    /// The address of the validator.
+    #[serde(with = "super::serde_hex")]
+    pub address: Address,
+    /// The metadata, which is opaque to the engine.
+    #[serde(with = "super::serde_hex")]
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = arbitrary_bytes))]
+    pub payload: Bytes,
 }
 
 #[derive(Clone, Debug)]
@@ -504,26 +1403,50 @@ pub(crate) struct HashChoke {
 
 #[cfg(test)]
 mod test {
+    use std::convert::Infallible;
+
     use super::*;
+    use crate::test_utils::gen_address;
     use rand::random;
 
-    fn gen_address() -> Address {
-        Address::from((0..32).map(|_| random::<u8>()).collect::<Vec<_>>())
-    }
-
+    #[cfg(feature = "runtime")]
     fn mock_node() -> Node {
         Node::new(gen_address())
     }
 
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+    struct Pill {
+        inner: Vec<u8>,
+    }
+
+    impl Codec for Pill {
+        type Error = Infallible;
+
+        fn encode(&self) -> Result<Bytes, Self::Error> {
+            Ok(Bytes::from(self.inner.clone()))
+        }
+
+        fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+            Ok(Pill {
+                inner: data.to_vec(),
+            })
+        }
+    }
+
+    #[cfg(feature = "runtime")]
     fn mock_status() -> Status {
         Status {
             height: random::<u64>(),
             interval: None,
             timer_config: None,
             authority_list: vec![mock_node(), mock_node()],
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: Vec::new(),
         }
     }
 
+    #[cfg(feature = "runtime")]
     #[test]
     fn test_consensus_power() {
         let status = mock_status();
@@ -533,4 +1456,177 @@ mod test {
         assert!(status.is_consensus_node(&consensus_node));
         assert!(!status.is_consensus_node(&sync_node));
     }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_round_context() {
+        let round_context = RoundContext {
+            height: 1,
+            round: 2,
+            proposer: gen_address(),
+            is_retry: true,
+            deadline: Instant::now(),
+            timestamp: None,
+        };
+        assert_eq!(RoundContext::from_context(&Context::new()), None);
+        assert!(!AllowEmptyBlock::in_context(&Context::new()));
+        assert!(AllowEmptyBlock::in_context(
+            &AllowEmptyBlock.attach_to(Context::new())
+        ));
+        assert_eq!(round_context.remaining(), Duration::ZERO);
+
+        let ctx = round_context.clone().attach_to(Context::new());
+        assert_eq!(RoundContext::from_context(&ctx), Some(round_context));
+        assert_eq!(
+            CorrelationId::from_context(&ctx),
+            Some(CorrelationId::new(1, 2))
+        );
+    }
+
+    #[test]
+    fn test_json_wire_format() {
+        let signed_vote = SignedVote {
+            signature: Bytes::from(vec![1, 2]),
+            vote: Vote {
+                height: 10,
+                round: 1,
+                vote_type: VoteType::Precommit,
+                block_hash: Hash::from(vec![0xab, 0xcd]),
+            },
+            voter: Address::from_slice(&[0xff]).unwrap(),
+            extension: Bytes::new(),
+            extension_signature: Bytes::new(),
+        };
+        let json = serde_json::to_string(&signed_vote).unwrap();
+        assert_eq!(
+            json,
+            r#"{"signature":"0102","vote":{"height":10,"round":1,"vote_type":"Precommit","block_hash":"abcd"},"voter":"ff","extension":"","extension_signature":""}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<SignedVote>(&json).unwrap(),
+            signed_vote
+        );
+        // The votes without the extensions decode as before.
+        let json = r#"{"signature":"0102","vote":{"height":10,"round":1,"vote_type":"Precommit","block_hash":"abcd"},"voter":"ff"}"#;
+        assert_eq!(
+            serde_json::from_str::<SignedVote>(json).unwrap(),
+            signed_vote
+        );
+
+        let lock_votes = AggregatedVote {
+            signature: AggregatedSignature {
+                signature: gen_address().into(),
+                address_bitmap: Bytes::from(vec![0b1110_0000]),
+            },
+            vote_type: VoteType::Prevote,
+            height: 10,
+            round: 0,
+            block_hash: Hash::from(gen_address().into_bytes()),
+            leader: gen_address(),
+            extensions: Vec::new(),
+        };
+        let msg = MlmMsg::SignedProposal(SignedProposal {
+            signature: gen_address().into(),
+            proposal: Proposal {
+                height: 10,
+                round: 1,
+                content: Pill {
+                    inner: vec![1, 2, 3],
+                },
+                block_hash: Hash::from(gen_address().into_bytes()),
+                lock: Some(PoLC {
+                    lock_round: 0,
+                    lock_votes,
+                }),
+                proposer: gen_address(),
+                timestamp: None,
+            },
+            timeout_cert: None,
+            vrf_proof: Bytes::new(),
+        });
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(serde_json::from_str::<MlmMsg<Pill>>(&json).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_address() {
+        let address = Address::from_slice(&[0xab, 0x01]).unwrap();
+        assert_eq!(address.to_string(), "ab01");
+        assert_eq!(format!("{:?}", address), "Address(ab01)");
+        assert_eq!(Bytes::from(address.clone()), Bytes::from(vec![0xab, 0x01]));
+
+        // The empty and the oversized addresses are rejected.
+        assert!(Address::new(Bytes::new()).is_err());
+        assert!(Address::from_slice(&[1u8; MAX_ADDRESS_LEN]).is_ok());
+        assert!(Address::from_slice(&[1u8; MAX_ADDRESS_LEN + 1]).is_err());
+
+        // So are they in the messages.
+        let json = r#"{"signature":"0102","vote":{"height":10,"round":1,"vote_type":"Precommit","block_hash":"abcd"},"voter":""}"#;
+        assert!(serde_json::from_str::<SignedVote>(json).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let hash = Hash::from([0xab, 0x01]);
+        assert_eq!(hash.to_string(), "ab01");
+        assert_eq!(format!("{:?}", hash), "Hash(ab01)");
+        assert_eq!(hash.to_array::<2>(), Some([0xab, 0x01]));
+        assert_eq!(hash.to_array::<32>(), None);
+
+        // The empty hash and the hashes of the configured length pass the check.
+        assert!(Hash::new().check_len(32).is_ok());
+        assert!(Hash::from([1u8; 32]).check_len(32).is_ok());
+        assert!(Hash::from([1u8; 20]).check_len(0).is_ok());
+        assert!(matches!(
+            Hash::with_len(Bytes::from(vec![1u8; 20]), 32),
+            Err(ConsensusError::InvalidHashLength {
+                expect: 32,
+                actual: 20
+            })
+        ));
+    }
+
+    #[test]
+    fn test_correlation_id() {
+        let id = CorrelationId::new(10, 2);
+        assert_eq!(id.to_string(), "mlm-10-2");
+        assert_eq!(id, CorrelationId::new(10, 2));
+        assert_ne!(id, CorrelationId::new(10, 3));
+        assert_eq!(CorrelationId::from_context(&Context::new()), None);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_context_ext() {
+        let ctx = Context::new();
+        assert_eq!(ctx.deadline(), None);
+        assert!(!ctx.is_expired());
+        assert_eq!(ctx.trace_id(), None);
+        assert_eq!(ctx.peer(), None);
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let ctx = ctx
+            .with_deadline(deadline)
+            .with_trace_id("trace-1".to_string())
+            .with_peer("127.0.0.1:3000".to_string());
+        assert_eq!(ctx.deadline(), Some(deadline));
+        assert!(!ctx.is_expired());
+        assert_eq!(ctx.trace_id(), Some("trace-1"));
+        assert_eq!(ctx.peer(), Some("127.0.0.1:3000"));
+        assert!(ctx.with_deadline(Instant::now()).is_expired());
+
+        // The adapter calls carry the deadline of their round.
+        let round_context = RoundContext {
+            height: 1,
+            round: 0,
+            proposer: gen_address(),
+            is_retry: false,
+            deadline,
+            timestamp: None,
+        };
+        assert_eq!(
+            round_context.attach_to(Context::new()).deadline(),
+            Some(deadline)
+        );
+    }
 }
@@ -1,26 +1,39 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use bit_vec::BitVec;
+use bytes::Bytes;
 use derive_more::Display;
 use prime_tools::get_primes_less_than_x;
 
-use crate::error::ConsensusError;
-use crate::types::{Address, Node};
+#[cfg(feature = "runtime")]
+use crate::config::RelayerPolicy;
+use crate::error::{AuthorityListError, ConsensusError};
+use crate::multisig::QcSignature;
+use crate::threshold::{BftThreshold, ThresholdPolicy};
+use crate::types::{Address, AggregatedSignature, Node};
 use crate::utils::rand_proposer::get_random_proposer_index;
+#[cfg(feature = "runtime")]
+use crate::AddressScheme;
 use crate::ConsensusResult;
 
 /// Authority manage is an extensional data structure of authority list which means
 /// `Vec<Node>`. It transforms the information in `Node` struct into a more suitable data structure
 /// according to its usage scene. The vote weight need look up by address frequently, therefore,
-/// address with vote weight saved in a `HashMap`.
-#[derive(Clone, Debug, Display, PartialEq, Eq)]
+/// address with vote weight saved in a `HashMap`. The quorum of the votes is decided by the
+/// threshold policy, which is kept over the updates of the authority list, and so are the
+/// skipped proposers and the proposer seed.
+#[derive(Clone, Debug, Display)]
 #[display(fmt = "Authority List {:?}", address)]
 pub struct AuthorityManage {
     address: Vec<Address>,
     propose_weights: Vec<u64>,
-    vote_weight_map: HashMap<Address, u32>,
+    vote_weight_map: HashMap<Address, u128>,
     propose_weight_sum: u64,
-    vote_weight_sum: u64,
+    vote_weight_sum: u128,
+    policy: Arc<dyn ThresholdPolicy>,
+    skipped: Vec<Address>,
+    proposer_seed: u64,
 }
 
 impl AuthorityManage {
@@ -31,11 +44,46 @@ impl AuthorityManage {
             propose_weights: Vec::new(),
             vote_weight_map: HashMap::new(),
             propose_weight_sum: 0u64,
-            vote_weight_sum: 0u64,
+            vote_weight_sum: 0u128,
+            policy: Arc::new(BftThreshold),
+            skipped: Vec::new(),
+            proposer_seed: 0,
         }
     }
 
-    /// Update the height authority manage by a new authority list.
+    /// Set the threshold policy of the quorum.
+    pub fn set_policy(&mut self, policy: Arc<dyn ThresholdPolicy>) {
+        self.policy = policy;
+    }
+
+    /// Set the proposers to skip, which propose after all of the others in the rounds of a
+    /// height.
+    #[cfg(feature = "runtime")]
+    pub fn set_skipped_proposers(&mut self, skipped: Vec<Address>) {
+        self.skipped = skipped;
+    }
+
+    /// Get the skipped proposers.
+    #[cfg(feature = "runtime")]
+    pub fn skipped_proposers(&self) -> &[Address] {
+        &self.skipped
+    }
+
+    /// Set the seed of the proposer schedule, which offsets the rotation of the proposers, or
+    /// seeds the random proposers with the `random_leader` feature. The seed zero is the
+    /// schedule without a seed.
+    pub fn set_proposer_seed(&mut self, seed: u64) {
+        self.proposer_seed = seed;
+    }
+
+    /// Get the seed of the proposer schedule.
+    #[cfg(feature = "runtime")]
+    pub fn proposer_seed(&self) -> u64 {
+        self.proposer_seed
+    }
+
+    /// Update the height authority manage by a new authority list. The sum of the vote weights
+    /// saturates at `u128::MAX`.
     pub fn update(&mut self, authority_list: &mut Vec<Node>) {
         self.flush();
         authority_list.sort();
@@ -49,48 +97,227 @@ impl AuthorityManage {
             self.vote_weight_map
                 .insert(node.address.clone(), vote_weight);
             self.propose_weight_sum += propose_weight;
-            self.vote_weight_sum += u64::from(vote_weight);
+            self.vote_weight_sum = self.vote_weight_sum.saturating_add(vote_weight);
         }
     }
 
+    /// Update the height authority manage by a new authority list, the nodes whose address is
+    /// invalid in the address scheme are excluded. Return the excluded nodes.
+    #[cfg(feature = "runtime")]
+    pub fn update_with_scheme(
+        &mut self,
+        authority_list: &mut Vec<Node>,
+        scheme: &dyn AddressScheme,
+    ) -> Vec<Node> {
+        let (mut valid, invalid): (Vec<Node>, Vec<Node>) = authority_list
+            .drain(..)
+            .partition(|node| scheme.is_valid_address(&node.address));
+        self.update(&mut valid);
+        *authority_list = valid;
+        invalid
+    }
+
+    /// Rebuild the sorted authority list from the authority manage.
+    pub fn get_authority_list(&self) -> Vec<Node> {
+        self.address
+            .iter()
+            .zip(self.propose_weights.iter())
+            .map(|(address, propose_weight)| Node {
+                address: address.clone(),
+                propose_weight: *propose_weight as u32,
+                vote_weight: self.vote_weight_map.get(address).copied().unwrap_or(0),
+            })
+            .collect()
+    }
+
     /// Get a vote weight of the node.
-    pub fn get_vote_weight(&self, addr: &Address) -> ConsensusResult<&u32> {
+    #[cfg(feature = "runtime")]
+    pub fn get_vote_weight(&self, addr: &Address) -> ConsensusResult<&u128> {
         self.vote_weight_map
             .get(addr)
             .ok_or(ConsensusError::InvalidAddress)
     }
 
-    /// Get the proposer address by a given seed.
+    /// Get the proposer address by a given seed. If some proposers are skipped, the proposers
+    /// of the first rounds of the height are reordered, the skipped ones after the others, and
+    /// the rounds take them in turn.
     pub fn get_proposer(&self, height: u64, round: u64) -> ConsensusResult<Address> {
+        if self.skipped.is_empty() || self.address.is_empty() {
+            return self.base_proposer(height, round);
+        }
+
+        let len = self.address.len() as u64;
+        let mut proposers = Vec::with_capacity(len as usize);
+        let mut skipped = Vec::new();
+        for i in 0..len {
+            let proposer = self.base_proposer(height, i)?;
+            if self.skipped.contains(&proposer) {
+                skipped.push(proposer);
+            } else {
+                proposers.push(proposer);
+            }
+        }
+        proposers.append(&mut skipped);
+        Ok(proposers[(round % len) as usize].clone())
+    }
+
+    fn base_proposer(&self, height: u64, round: u64) -> ConsensusResult<Address> {
         let index = if cfg!(feature = "random_leader") {
             get_random_proposer_index(
-                height + round,
+                (height + round) ^ self.proposer_seed,
                 &self.propose_weights,
                 self.propose_weight_sum,
             )
         } else {
-            rotation_leader_index(height, round, self.address.len())
+            rotation_leader_index(height, round, self.proposer_seed, self.address.len())
         };
 
         if let Some(addr) = self.address.get(index) {
             return Ok(addr.to_owned());
         }
-        Err(ConsensusError::Other(
+        Err(ConsensusError::AuthErr(
             "The address list mismatch propose weight list".to_string(),
         ))
     }
 
-    /// Calculate whether the sum of vote weights from bitmap is above 2/3.
+    /// Get the first `count` proposers of the given height and round in rank order. The first
+    /// one is the proposer returned by `get_proposer()`, the others follow it in the sorted
+    /// authority list.
+    #[cfg(feature = "multi_proposal")]
+    pub fn get_proposers(
+        &self,
+        height: u64,
+        round: u64,
+        count: usize,
+    ) -> ConsensusResult<Vec<Address>> {
+        let primary = self.get_proposer(height, round)?;
+        let index = self
+            .address
+            .iter()
+            .position(|addr| addr == &primary)
+            .ok_or(ConsensusError::InvalidAddress)?;
+        let len = self.address.len();
+
+        Ok((0..count.min(len))
+            .map(|i| self.address[(index + i) % len].clone())
+            .collect())
+    }
+
+    /// Get the relayer of the given hop in the fallback chain of the leader. The chain follows
+    /// the leader round robin in the sorted authority list, and the hop zero is the leader.
+    #[cfg(feature = "runtime")]
+    pub fn get_relayer(&self, leader: &Address, hop: usize) -> ConsensusResult<Address> {
+        let index = self
+            .address
+            .iter()
+            .position(|addr| addr == leader)
+            .ok_or(ConsensusError::InvalidAddress)?;
+        Ok(self.address[(index + hop) % self.address.len()].clone())
+    }
+
+    /// Get at most `count` backup relayers of the votes of the given height and round by the
+    /// policy. The leader is never a backup relayer, and each relayer appears once.
+    #[cfg(feature = "runtime")]
+    pub fn get_backup_relayers(
+        &self,
+        height: u64,
+        round: u64,
+        leader: &Address,
+        count: usize,
+        policy: RelayerPolicy,
+    ) -> ConsensusResult<Vec<Address>> {
+        let count = count.min(self.address.len().saturating_sub(1));
+        let mut relayers: Vec<Address> = Vec::with_capacity(count);
+        // Each round of the rotation visits the authorities once, so the proposers of the
+        // next rounds cover all of the others within twice the length.
+        let candidates = self.address.len() * 2;
+        for i in 1..=candidates {
+            if relayers.len() >= count {
+                break;
+            }
+
+            let relayer = match policy {
+                RelayerPolicy::FallbackChain => self.get_relayer(leader, i)?,
+                RelayerPolicy::NextProposers => {
+                    self.get_proposer(height, round.saturating_add(i as u64))?
+                }
+            };
+            if relayer != *leader && !relayers.contains(&relayer) {
+                relayers.push(relayer);
+            }
+        }
+        Ok(relayers)
+    }
+
+    /// Calculate whether the voters from bitmap are a quorum by the threshold policy.
     pub fn is_above_threshold(&self, bitmap: &[u8]) -> ConsensusResult<bool> {
+        let (acc, count) = self.get_bitmap_weight(bitmap)?;
+        Ok(self.is_quorum(acc, count))
+    }
+
+    /// Check that the threshold policy is safe for an authority list before it is applied.
+    #[cfg(feature = "runtime")]
+    pub fn check_threshold(
+        &self,
+        authority_list: &[Node],
+    ) -> Result<(), AuthorityListError> {
+        let total_weight = authority_list
+            .iter()
+            .fold(0u128, |sum, node| sum.saturating_add(node.vote_weight));
+        if self.policy.is_safe_for(total_weight, authority_list.len()) {
+            Ok(())
+        } else {
+            Err(AuthorityListError::UnsafeThreshold(authority_list.len()))
+        }
+    }
+
+    /// Calculate whether the voters of the vote weight and the count are a quorum by the
+    /// threshold policy.
+    pub fn is_quorum(&self, weight: u128, count: usize) -> bool {
+        self.policy
+            .is_quorum(weight, count, self.vote_weight_sum, self.address.len())
+    }
+
+    /// Calculate whether the distinct voters are a quorum by the threshold policy. The voters
+    /// out of the authority list neither weigh nor count.
+    #[cfg(feature = "runtime")]
+    pub fn is_above_threshold_by_voters(&self, voters: &[Address]) -> bool {
+        let mut counted = HashSet::with_capacity(voters.len());
+        let mut acc = 0u128;
+        for voter in voters.iter() {
+            if let Some(weight) = self.vote_weight_map.get(voter) {
+                if counted.insert(voter) {
+                    acc = acc.saturating_add(*weight);
+                }
+            }
+        }
+        self.is_quorum(acc, counted.len())
+    }
+
+    /// Calculate whether the signature of a QC of the scheme is above the threshold. A
+    /// signature naming no voters is above the threshold if it passes the verification.
+    pub fn is_qc_above_threshold<S: QcSignature>(
+        &self,
+        signature: &AggregatedSignature,
+    ) -> ConsensusResult<bool> {
+        match S::from_wire(signature)?.address_bitmap() {
+            Some(bitmap) => self.is_above_threshold(bitmap),
+            None => Ok(true),
+        }
+    }
+
+    fn get_bitmap_weight(&self, bitmap: &[u8]) -> ConsensusResult<(u128, usize)> {
         let bitmap = BitVec::from_bytes(bitmap);
-        let mut acc = 0u64;
+        let mut acc = 0u128;
+        let mut count = 0usize;
 
         for node in bitmap.iter().zip(self.address.iter()) {
             if node.0 {
                 if let Some(weight) = self.vote_weight_map.get(node.1) {
-                    acc += u64::from(*weight);
+                    acc = acc.saturating_add(*weight);
+                    count += 1;
                 } else {
-                    return Err(ConsensusError::Other(format!(
+                    return Err(ConsensusError::AuthErr(format!(
                         "Lose {:?} vote weight",
                         node.1.clone()
                     )));
@@ -98,7 +325,7 @@ impl AuthorityManage {
             }
         }
 
-        Ok(acc * 3 > self.vote_weight_sum * 2)
+        Ok((acc, count))
     }
 
     pub fn get_voters(&self, bitmap: &[u8]) -> ConsensusResult<Vec<Address>> {
@@ -112,13 +339,27 @@ impl AuthorityManage {
         Ok(voters)
     }
 
+    /// Build the address bitmap of the voters by the order of the authority list, which is
+    /// the inverse of `get_voters()`. The voters out of the authority list are ignored.
+    pub fn get_bitmap(&self, voters: &[Address]) -> Bytes {
+        let voters = voters.iter().collect::<HashSet<_>>();
+        let mut bitmap = BitVec::from_elem(self.address.len(), false);
+        for (index, address) in self.address.iter().enumerate() {
+            if voters.contains(address) {
+                bitmap.set(index, true);
+            }
+        }
+        Bytes::from(bitmap.to_bytes())
+    }
+
     /// If the given address is in the current authority list.
     pub fn contains(&self, address: &Address) -> bool {
         self.address.contains(address)
     }
 
     /// Get the sum of the vote weights in the current height.
-    pub fn get_vote_weight_sum(&self) -> u64 {
+    #[cfg(feature = "runtime")]
+    pub fn get_vote_weight_sum(&self) -> u128 {
         self.vote_weight_sum
     }
 
@@ -132,13 +373,10 @@ impl AuthorityManage {
     }
 
     /// Get the length of the current authority list.
+    #[cfg(feature = "runtime")]
     pub fn len(&self) -> usize {
         self.address.len()
     }
-
-    pub fn get_addres_ref(&self) -> &Vec<Address> {
-        &self.address
-    }
 }
 
 /// Give the validators list and bitmap, returns the activated validators, the authority list MUST
@@ -158,46 +396,129 @@ pub fn extract_voters(
     Ok(voters)
 }
 
-/// Get the leader address of the height and the round, the authority list MUST be sorted.
-pub fn get_leader(height: u64, round: u64, mut authority_list: Vec<Node>) -> Address {
-    authority_list.sort();
-    let mut weight_sum = 0;
-    let mut propose_weights = Vec::new();
+/// Build the address bitmap of the voters of the authority list, which is the inverse of
+/// `extract_voters()`. The authority list needs not be sorted, and the voters out of it are
+/// ignored.
+pub fn build_bitmap(authority_list: &[Node], voters: &[Address]) -> Bytes {
+    let mut authority = AuthorityManage::new();
+    authority.update(&mut authority_list.to_vec());
+    authority.get_bitmap(voters)
+}
+
+/// Get the leader address of the height and the round. It panics if the authority list is
+/// empty, `proposer_at()` returns an error instead.
+pub fn get_leader(height: u64, round: u64, authority_list: Vec<Node>) -> Address {
+    proposer_at(height, round, &authority_list).expect("Empty authority list")
+}
+
+/// Get the proposer address of the height and the round, which is calculated by the same
+/// authority manage as the engine, so the explorers, the monitors and the applications
+/// predict the leaders without drift. The authority list needs not be sorted. If the
+/// instance has an address scheme, the authorities of invalid address must be excluded
+/// from the list first, as the engine does. The `Status::skipped_proposers` are not
+/// considered, and the schedule is the one without a seed, `seeded_proposer_at()` takes the
+/// `Status::proposer_seed` of the chain.
+pub fn proposer_at(
+    height: u64,
+    round: u64,
+    authority_list: &[Node],
+) -> ConsensusResult<Address> {
+    seeded_proposer_at(height, round, 0, authority_list)
+}
+
+/// Get the proposer address of the height and the round in the schedule of the proposer
+/// seed, like `proposer_at()`.
+pub fn seeded_proposer_at(
+    height: u64,
+    round: u64,
+    seed: u64,
+    authority_list: &[Node],
+) -> ConsensusResult<Address> {
+    if authority_list.is_empty() {
+        return Err(ConsensusError::AuthErr("Empty authority list".to_string()));
+    }
+
+    let mut authority = AuthorityManage::new();
+    authority.update(&mut authority_list.to_vec());
+    authority.set_proposer_seed(seed);
+    authority.get_proposer(height, round)
+}
+
+/// Check an authority list before it is applied: it is not empty, no address is in it twice,
+/// every authority has a vote weight and the sum of the vote weights does not overflow. With
+/// the `random_leader` feature the sum of the propose weights must not be zero either.
+pub fn validate_authority_list(
+    authority_list: &[Node],
+) -> Result<(), AuthorityListError> {
+    if authority_list.is_empty() {
+        return Err(AuthorityListError::Empty);
+    }
+
+    let mut seen = HashSet::with_capacity(authority_list.len());
+    let mut vote_weight_sum = 0u128;
+    let mut propose_weight_sum = 0u64;
     for node in authority_list.iter() {
-        weight_sum += node.propose_weight;
-        propose_weights.push(node.propose_weight as u64);
+        if !seen.insert(&node.address) {
+            return Err(AuthorityListError::DuplicateAddress(node.address.clone()));
+        }
+        if node.vote_weight == 0 {
+            return Err(AuthorityListError::ZeroVoteWeight(node.address.clone()));
+        }
+        vote_weight_sum = vote_weight_sum
+            .checked_add(node.vote_weight)
+            .ok_or(AuthorityListError::VoteWeightOverflow)?;
+        propose_weight_sum += u64::from(node.propose_weight);
     }
 
-    let index = if cfg!(feature = "random_leader") {
-        get_random_proposer_index(height + round, &propose_weights, weight_sum as u64)
-    } else {
-        rotation_leader_index(height, round, authority_list.len())
-    };
+    if cfg!(feature = "random_leader") && propose_weight_sum == 0 {
+        return Err(AuthorityListError::ZeroProposeWeight);
+    }
+    Ok(())
+}
 
-    authority_list[index].address.clone()
+/// The least vote weight above 2/3 of the sum, which is computed without overflow for any sum.
+pub(crate) fn quorum(sum: u128) -> u128 {
+    2 * (sum / 3) + 2 * (sum % 3) / 3 + 1
 }
 
-fn rotation_leader_index(height: u64, round: u64, authority_len: usize) -> usize {
+fn rotation_leader_index(
+    height: u64,
+    round: u64,
+    seed: u64,
+    authority_len: usize,
+) -> usize {
     let len = authority_len as u32;
     let prime_num = *get_primes_less_than_x(len).last().unwrap_or(&1) as u64;
     let res = (height * prime_num + round) % (len as u64);
-    res as usize
+    ((res + seed % len as u64) % len as u64) as usize
 }
 
 #[cfg(test)]
 mod test {
+    #[cfg(feature = "runtime")]
+    use std::sync::Arc;
+
     use bit_vec::BitVec;
     use bytes::Bytes;
     use rand::random;
 
+    #[cfg(feature = "runtime")]
+    use crate::address::RawAddressScheme;
+    #[cfg(all(feature = "runtime", not(feature = "random_leader")))]
+    use crate::config::RelayerPolicy;
+    use crate::error::AuthorityListError;
+    #[cfg(feature = "runtime")]
     use crate::error::ConsensusError;
-    use crate::extract_voters;
+    use crate::test_utils::gen_address;
+    #[cfg(feature = "runtime")]
+    use crate::threshold::FixedCountThreshold;
     use crate::types::{Address, Node};
-    use crate::utils::auth_manage::AuthorityManage;
-
-    fn gen_address() -> Address {
-        Address::from((0..32).map(|_| random::<u8>()).collect::<Vec<_>>())
-    }
+    use crate::utils::auth_manage::{
+        get_leader, proposer_at, validate_authority_list, AuthorityManage,
+    };
+    #[cfg(feature = "runtime")]
+    use crate::utils::auth_manage::{quorum, seeded_proposer_at};
+    use crate::{build_bitmap, extract_voters};
 
     fn gen_auth_list(len: usize) -> Vec<Node> {
         if len == 0 {
@@ -209,13 +530,13 @@ mod test {
             authority_list.push(gen_node(
                 gen_address(),
                 random::<u32>(),
-                random::<u32>(),
+                u128::from(random::<u32>()),
             ));
         }
         authority_list
     }
 
-    fn gen_node(addr: Address, propose_weight: u32, vote_weight: u32) -> Node {
+    fn gen_node(addr: Address, propose_weight: u32, vote_weight: u128) -> Node {
         let mut node = Node::new(addr);
         node.set_propose_weight(propose_weight);
         node.set_vote_weight(vote_weight);
@@ -230,6 +551,7 @@ mod test {
         bv
     }
 
+    #[cfg(feature = "runtime")]
     #[test]
     fn test_vote_weight() {
         let mut authority_list = gen_auth_list(0);
@@ -252,11 +574,46 @@ mod test {
         for node in authority_list.iter() {
             assert_eq!(
                 *authority_manage.get_vote_weight(&node.address).unwrap(),
-                node.propose_weight
+                u128::from(node.propose_weight)
             );
         }
     }
 
+    #[test]
+    fn test_validate_authority_list() {
+        let mut authority_list = gen_auth_list(4);
+        authority_list
+            .iter_mut()
+            .for_each(|node| node.set_vote_weight(1));
+        assert!(validate_authority_list(&authority_list).is_ok());
+        assert_eq!(validate_authority_list(&[]), Err(AuthorityListError::Empty));
+
+        let mut duplicate = authority_list.clone();
+        duplicate.push(authority_list[2].clone());
+        assert_eq!(
+            validate_authority_list(&duplicate),
+            Err(AuthorityListError::DuplicateAddress(
+                authority_list[2].address.clone()
+            ))
+        );
+
+        let mut zero = authority_list.clone();
+        zero[1].set_vote_weight(0);
+        assert_eq!(
+            validate_authority_list(&zero),
+            Err(AuthorityListError::ZeroVoteWeight(
+                authority_list[1].address.clone()
+            ))
+        );
+
+        let mut overflow = authority_list.clone();
+        overflow[0].set_vote_weight(u128::MAX);
+        assert_eq!(
+            validate_authority_list(&overflow),
+            Err(AuthorityListError::VoteWeightOverflow)
+        );
+    }
+
     #[test]
     fn test_update() {
         let mut authority_list = gen_auth_list(random::<u8>() as usize);
@@ -271,13 +628,14 @@ mod test {
         );
     }
 
+    #[cfg(feature = "runtime")]
     #[test]
     fn test_vote_threshold() {
         let mut authority_list = vec![
-            gen_node(gen_address(), 1u32, 1u32),
-            gen_node(gen_address(), 1u32, 1u32),
-            gen_node(gen_address(), 1u32, 1u32),
-            gen_node(gen_address(), 1u32, 1u32),
+            gen_node(gen_address(), 1u32, 1u128),
+            gen_node(gen_address(), 1u32, 1u128),
+            gen_node(gen_address(), 1u32, 1u128),
+            gen_node(gen_address(), 1u32, 1u128),
         ];
         authority_list.sort();
         let mut authority = AuthorityManage::new();
@@ -308,7 +666,268 @@ mod test {
 
         let bit_map = gen_bitmap(4, vec![0, 1, 2, 3]);
         let res = authority.is_above_threshold(Bytes::from(bit_map.to_bytes()).as_ref());
-        assert!(res.unwrap())
+        assert!(res.unwrap());
+
+        // The duplicated voters and the voters out of the list weigh nothing.
+        let addrs = authority
+            .get_authority_list()
+            .into_iter()
+            .map(|node| node.address)
+            .collect::<Vec<_>>();
+        let voters = vec![addrs[0].clone(), addrs[1].clone(), addrs[1].clone()];
+        assert!(!authority.is_above_threshold_by_voters(&voters));
+        let voters = vec![addrs[0].clone(), addrs[1].clone(), gen_address()];
+        assert!(!authority.is_above_threshold_by_voters(&voters));
+        assert!(authority.is_above_threshold_by_voters(&addrs[1..]));
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_stake_threshold() {
+        assert_eq!(quorum(0), 1);
+        assert_eq!(quorum(3), 3);
+        assert_eq!(quorum(4), 3);
+        assert_eq!(quorum(5), 4);
+        assert_eq!(quorum(u128::MAX), u128::MAX / 3 * 2 + 1);
+
+        // The raw stakes whose sum is close to the max are compared without overflow.
+        let stake = u128::MAX / 4;
+        let mut authority_list = (0..4)
+            .map(|_| gen_node(gen_address(), 1u32, stake))
+            .collect::<Vec<_>>();
+        authority_list.sort();
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list);
+        assert_eq!(authority.get_vote_weight_sum(), stake * 4);
+
+        let bit_map = gen_bitmap(4, vec![0, 1]);
+        let res = authority.is_above_threshold(Bytes::from(bit_map.to_bytes()).as_ref());
+        assert!(!res.unwrap());
+        let bit_map = gen_bitmap(4, vec![0, 1, 2]);
+        let res = authority.is_above_threshold(Bytes::from(bit_map.to_bytes()).as_ref());
+        assert!(res.unwrap());
+        let threshold = quorum(stake * 4);
+        assert!(!authority.is_quorum(threshold - 1, 3));
+        assert!(authority.is_quorum(threshold, 3));
+        assert!(threshold > stake * 2 && threshold <= stake * 3);
+
+        // The sum saturates instead of wrapping.
+        authority_list.push(gen_node(gen_address(), 1u32, u128::MAX));
+        authority.update(&mut authority_list);
+        assert_eq!(authority.get_vote_weight_sum(), u128::MAX);
+    }
+
+    #[test]
+    fn test_proposer_at() {
+        let mut authority_list = gen_auth_list(7);
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list.clone());
+
+        // The order of the given list makes no difference.
+        authority_list.reverse();
+        for height in 1..20 {
+            for round in 0..3 {
+                let proposer = authority.get_proposer(height, round).unwrap();
+                assert_eq!(
+                    proposer_at(height, round, &authority_list).unwrap(),
+                    proposer
+                );
+                assert_eq!(get_leader(height, round, authority_list.clone()), proposer);
+            }
+        }
+        assert!(proposer_at(1, 0, &[]).is_err());
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_proposer_seed() {
+        let mut authority_list = gen_auth_list(7);
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list);
+
+        let schedule = |authority: &AuthorityManage| {
+            (1..20)
+                .map(|height| authority.get_proposer(height, 0).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let unseeded = schedule(&authority);
+        authority.set_proposer_seed(3);
+        let seeded = schedule(&authority);
+        assert_ne!(seeded, unseeded);
+
+        // The seed is kept over the updates of the authority list.
+        authority.update(&mut authority_list.clone());
+        assert_eq!(authority.proposer_seed(), 3);
+        assert_eq!(schedule(&authority), seeded);
+        for (height, proposer) in (1..20).zip(seeded.iter()) {
+            assert_eq!(
+                &seeded_proposer_at(height, 0, 3, &authority_list).unwrap(),
+                proposer
+            );
+        }
+        assert_eq!(seeded_proposer_at(1, 0, 0, &authority_list).unwrap(), unseeded[0]);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_check_threshold() {
+        let authority_list = gen_auth_list(4);
+        let mut authority = AuthorityManage::new();
+        assert_eq!(authority.check_threshold(&authority_list), Ok(()));
+
+        // Two of four authorities are no quorum that intersects in an honest voter.
+        authority.set_policy(Arc::new(FixedCountThreshold(2)));
+        assert_eq!(
+            authority.check_threshold(&authority_list),
+            Err(AuthorityListError::UnsafeThreshold(4))
+        );
+        authority.set_policy(Arc::new(FixedCountThreshold(3)));
+        assert_eq!(authority.check_threshold(&authority_list), Ok(()));
+    }
+
+    // The random proposers of the rounds may repeat, so the order is of the rotation only.
+    #[cfg(all(feature = "runtime", not(feature = "random_leader")))]
+    #[test]
+    fn test_skipped_proposers() {
+        let mut authority_list = gen_auth_list(4);
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list);
+
+        let base = (0..4)
+            .map(|round| authority.get_proposer(5, round).unwrap())
+            .collect::<Vec<_>>();
+        authority.set_skipped_proposers(vec![base[0].clone(), base[2].clone()]);
+        let order = (0..8)
+            .map(|round| authority.get_proposer(5, round).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order[..4],
+            [
+                base[1].clone(),
+                base[3].clone(),
+                base[0].clone(),
+                base[2].clone()
+            ]
+        );
+        assert_eq!(order[..4], order[4..]);
+
+        // The skipped proposers are kept over the updates of the authority list.
+        authority.update(&mut authority_list);
+        assert_eq!(authority.get_proposer(5, 0).unwrap(), base[1]);
+        authority.set_skipped_proposers(Vec::new());
+        assert_eq!(authority.get_proposer(5, 0).unwrap(), base[0]);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_update_with_scheme() {
+        let mut authority_list = gen_auth_list(4);
+        let invalid = Node::new(Address::from_slice(&[1u8; 20]).unwrap());
+        authority_list.push(invalid.clone());
+
+        let mut authority = AuthorityManage::new();
+        let excluded = authority.update_with_scheme(
+            &mut authority_list,
+            &RawAddressScheme::with_key_len(32),
+        );
+        assert_eq!(excluded, vec![invalid.clone()]);
+        assert_eq!(authority_list.len(), 4);
+        let vote_weight_sum = authority_list
+            .iter()
+            .map(|node| node.vote_weight)
+            .sum::<u128>();
+        assert_eq!(authority.get_vote_weight_sum(), vote_weight_sum);
+        assert!(!authority.contains(&invalid.address));
+        assert!(authority.contains(&authority_list[0].address));
+    }
+
+    #[cfg(feature = "multi_proposal")]
+    #[test]
+    fn test_get_proposers() {
+        let mut authority_list = gen_auth_list(7);
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list);
+
+        for height in 0..10 {
+            for round in 0..10 {
+                let proposers = authority.get_proposers(height, round, 3).unwrap();
+                assert_eq!(proposers.len(), 3);
+                assert_eq!(proposers[0], authority.get_proposer(height, round).unwrap());
+                assert_ne!(proposers[0], proposers[1]);
+                assert_ne!(proposers[1], proposers[2]);
+                assert_ne!(proposers[0], proposers[2]);
+                assert_eq!(
+                    proposers,
+                    authority.get_proposers(height, round, 3).unwrap()
+                );
+            }
+        }
+
+        assert_eq!(authority.get_proposers(1, 0, 10).unwrap().len(), 7);
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_get_relayer() {
+        let mut authority_list = gen_auth_list(4);
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list);
+
+        let leader = authority.get_proposer(1, 0).unwrap();
+        assert_eq!(authority.get_relayer(&leader, 0).unwrap(), leader);
+        assert_eq!(authority.get_relayer(&leader, 4).unwrap(), leader);
+        let relayers = (1..4)
+            .map(|hop| authority.get_relayer(&leader, hop).unwrap())
+            .collect::<Vec<_>>();
+        assert!(relayers.iter().all(|relayer| *relayer != leader));
+        assert_ne!(relayers[0], relayers[1]);
+        assert_ne!(relayers[1], relayers[2]);
+        assert_eq!(
+            authority.get_relayer(&gen_address(), 1),
+            Err(ConsensusError::InvalidAddress)
+        );
+    }
+
+    // The random proposers of the next rounds may repeat the leader or each other, so the
+    // next proposers are of the rotation only.
+    #[cfg(all(feature = "runtime", not(feature = "random_leader")))]
+    #[test]
+    fn test_get_backup_relayers() {
+        let mut authority_list = gen_auth_list(4);
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut authority_list);
+
+        let leader = authority.get_proposer(1, 0).unwrap();
+        let relayers = authority
+            .get_backup_relayers(1, 0, &leader, 2, RelayerPolicy::FallbackChain)
+            .unwrap();
+        assert_eq!(
+            relayers,
+            vec![
+                authority.get_relayer(&leader, 1).unwrap(),
+                authority.get_relayer(&leader, 2).unwrap()
+            ]
+        );
+
+        let relayers = authority
+            .get_backup_relayers(1, 0, &leader, 2, RelayerPolicy::NextProposers)
+            .unwrap();
+        assert_eq!(
+            relayers,
+            vec![
+                authority.get_proposer(1, 1).unwrap(),
+                authority.get_proposer(1, 2).unwrap()
+            ]
+        );
+
+        // The backup relayers are capped by the other authorities.
+        for policy in [RelayerPolicy::FallbackChain, RelayerPolicy::NextProposers] {
+            let relayers = authority
+                .get_backup_relayers(1, 0, &leader, 10, policy)
+                .unwrap();
+            assert_eq!(relayers.len(), 3);
+            assert!(!relayers.contains(&leader));
+        }
     }
 
     #[test]
@@ -349,10 +968,10 @@ mod test {
     #[test]
     fn test_poll_leader() {
         let mut authority_list = vec![
-            gen_node(gen_address(), 1u32, 1u32),
-            gen_node(gen_address(), 1u32, 1u32),
-            gen_node(gen_address(), 1u32, 1u32),
-            gen_node(gen_address(), 1u32, 1u32),
+            gen_node(gen_address(), 1u32, 1u128),
+            gen_node(gen_address(), 1u32, 1u128),
+            gen_node(gen_address(), 1u32, 1u128),
+            gen_node(gen_address(), 1u32, 1u128),
         ];
         authority_list.sort();
         let mut authority = AuthorityManage::new();
@@ -402,4 +1021,28 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_build_bitmap() {
+        let mut auth_list = gen_auth_list(10);
+        let mut voters = auth_list
+            .iter()
+            .step_by(3)
+            .map(|node| node.address.clone())
+            .collect::<Vec<_>>();
+        voters.push(gen_address());
+
+        // The bitmap is the inverse of the voters extracted from it.
+        let bitmap = build_bitmap(&auth_list, &voters);
+        let mut res = extract_voters(&mut auth_list, &bitmap).unwrap();
+        voters.pop();
+        voters.sort();
+        res.sort();
+        assert_eq!(res, voters);
+
+        let mut authority = AuthorityManage::new();
+        authority.update(&mut auth_list);
+        assert_eq!(authority.get_bitmap(&voters), bitmap);
+        assert_eq!(authority.get_bitmap(&[]), Bytes::from(vec![0u8; 2]));
+    }
 }
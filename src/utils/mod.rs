@@ -3,4 +3,5 @@ pub mod auth_manage;
 ///
 mod rand_proposer;
 ///
+#[cfg(feature = "runtime")]
 pub mod timer_config;
@@ -1,33 +1,43 @@
-use std::cell::Cell;
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::rng::{Rng, SeededRng};
 use crate::smr::smr_types::SMREvent;
-use crate::DurationConfig;
 use crate::{error::ConsensusError, ConsensusResult};
+use crate::{BackoffConfig, DurationConfig};
 
 /// Mlm timer config.
 #[derive(Debug, Clone)]
 pub struct TimerConfig {
-    interval: Cell<u64>,
+    interval: Duration,
     propose: (u64, u64),
     prevote: (u64, u64),
     precommit: (u64, u64),
     brake: (u64, u64),
+    backoff: BackoffConfig,
+    choke_free: bool,
+    jitter: u64,
+    rng: Arc<dyn Rng>,
 }
 
 impl TimerConfig {
-    pub fn new(interval: u64) -> Self {
+    pub fn new(interval: Duration) -> Self {
+        let classic = DurationConfig::classic();
         TimerConfig {
-            interval: Cell::new(interval),
-            propose: (24, 10),
-            prevote: (10, 10),
-            precommit: (5, 10),
-            brake: (3, 10),
+            interval,
+            propose: classic.get_propose_config(),
+            prevote: classic.get_prevote_config(),
+            precommit: classic.get_precommit_config(),
+            brake: classic.get_brake_config(),
+            backoff: BackoffConfig::default(),
+            choke_free: false,
+            jitter: 0,
+            rng: Arc::new(SeededRng::new(0)),
         }
     }
 
-    pub fn set_interval(&self, interval: u64) {
-        self.interval.set(interval);
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
     }
 
     pub fn update(&mut self, config: DurationConfig) {
@@ -35,31 +45,119 @@ impl TimerConfig {
         self.prevote = config.get_prevote_config();
         self.precommit = config.get_precommit_config();
         self.brake = config.get_brake_config();
+        self.backoff = config.backoff.unwrap_or_default();
+        self.choke_free = config.choke_free;
     }
 
-    pub fn get_timeout(&self, event: SMREvent) -> ConsensusResult<Duration> {
-        match event {
-            SMREvent::NewRoundInfo { .. } => Ok(self.get_propose_timeout()),
-            SMREvent::PrevoteVote { .. } => Ok(self.get_prevote_timeout()),
-            SMREvent::PrecommitVote { .. } => Ok(self.get_precommit_timeout()),
-            SMREvent::Brake { .. } => Ok(self.get_brake_timeout()),
-            _ => Err(ConsensusError::TimerErr("No commit timer".to_string())),
-        }
+    /// Whether the round changes on the precommit timeout without the chokes.
+    pub fn is_choke_free(&self) -> bool {
+        self.choke_free
+    }
+
+    /// Set the max percentage of the jitter of the timeouts, and the rng of the jitter whose
+    /// numbers should differ between the nodes.
+    pub fn set_jitter(&mut self, jitter: u64, rng: Arc<dyn Rng>) {
+        self.jitter = jitter.min(100);
+        self.rng = rng;
+    }
+
+    /// Get the timeout of the step with the jitter.
+    pub fn get_timeout(&mut self, event: SMREvent) -> ConsensusResult<Duration> {
+        let timeout = match event {
+            SMREvent::NewRoundInfo { .. } => self.get_propose_timeout(),
+            SMREvent::PrevoteVote { .. } => self.get_prevote_timeout(),
+            SMREvent::PrecommitVote { .. } => self.get_precommit_timeout(),
+            SMREvent::Brake { .. } => self.get_brake_timeout(),
+            _ => return Err(ConsensusError::TimerErr("No commit timer".to_string())),
+        };
+        Ok(self.with_jitter(timeout))
+    }
+
+    /// Apply the backoff policy to the timeout of the given round.
+    pub fn get_backoff_timeout(&self, timeout: Duration, round: u64) -> Duration {
+        self.backoff.get_timeout(timeout, round)
+    }
+
+    pub fn get_propose_timeout(&self) -> Duration {
+        self.proportion(self.propose)
+    }
+
+    pub fn get_prevote_timeout(&self) -> Duration {
+        self.proportion(self.prevote)
     }
 
-    fn get_propose_timeout(&self) -> Duration {
-        Duration::from_millis(self.interval.get() * self.propose.0 / self.propose.1)
+    pub fn get_precommit_timeout(&self) -> Duration {
+        self.proportion(self.precommit)
     }
 
-    fn get_prevote_timeout(&self) -> Duration {
-        Duration::from_millis(self.interval.get() * self.prevote.0 / self.prevote.1)
+    pub fn get_brake_timeout(&self) -> Duration {
+        self.proportion(self.brake)
     }
 
-    fn get_precommit_timeout(&self) -> Duration {
-        Duration::from_millis(self.interval.get() * self.precommit.0 / self.precommit.1)
+    /// Lengthen the timeout by a random proportion up to the jitter in basis points, so that
+    /// the nodes do not time out at the same instant.
+    fn with_jitter(&mut self, timeout: Duration) -> Duration {
+        if self.jitter == 0 {
+            return timeout;
+        }
+        let points = self.rng.next_u64() % (self.jitter * 100 + 1);
+        timeout + timeout.saturating_mul(points as u32) / 10000
     }
 
-    fn get_brake_timeout(&self) -> Duration {
-        Duration::from_millis(self.interval.get() * self.brake.0 / self.brake.1)
+    /// The proportion of the interval, in nanoseconds so that a sub-millisecond interval keeps
+    /// its precision.
+    fn proportion(&self, (numerator, denominator): (u64, u64)) -> Duration {
+        let nanos =
+            self.interval.as_nanos() * u128::from(numerator) / u128::from(denominator);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::TimerConfig;
+    use crate::rng::SeededRng;
+    use crate::smr::smr_types::SMREvent;
+
+    #[test]
+    fn test_timer_jitter() {
+        let event = SMREvent::Brake {
+            height: 1,
+            round: 0,
+            lock_round: None,
+        };
+        let mut config = TimerConfig::new(Duration::from_millis(1000));
+        assert_eq!(
+            config.get_timeout(event.clone()).unwrap(),
+            Duration::from_millis(300)
+        );
+
+        // The timeouts are lengthened by at most ten percent, and differ between the seeds.
+        config.set_jitter(10, Arc::new(SeededRng::new(1)));
+        let timeouts = (0..20)
+            .map(|_| config.get_timeout(event.clone()).unwrap())
+            .collect::<Vec<_>>();
+        assert!(timeouts.iter().all(
+            |t| *t >= Duration::from_millis(300) && *t <= Duration::from_millis(330)
+        ));
+        assert!(timeouts.iter().any(|t| *t != timeouts[0]));
+
+        let mut other = TimerConfig::new(Duration::from_millis(1000));
+        other.set_jitter(10, Arc::new(SeededRng::new(2)));
+        let others = (0..20)
+            .map(|_| other.get_timeout(event.clone()).unwrap())
+            .collect::<Vec<_>>();
+        assert_ne!(timeouts, others);
+
+        // The rng of the same seed reproduces the timeouts.
+        let mut same = TimerConfig::new(Duration::from_millis(1000));
+        same.set_jitter(10, Arc::new(SeededRng::new(1)));
+        let sames = (0..20)
+            .map(|_| same.get_timeout(event.clone()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(timeouts, sames);
     }
 }
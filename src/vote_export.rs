@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+
+use crate::types::{Proof, SignedVote};
+
+/// The raw votes of a committed height with the proof of it. The votes are the ones that the
+/// node has collected in all of the rounds of the height, ordered by the round and the voter.
+/// Since the votes are sent to the leader of each round, only the leader collects the vote sets
+/// of its round, which hold the votes received until the commit. The vote sets exported by the
+/// other nodes are usually empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoteSet {
+    /// The proof of the committed height.
+    pub proof: Proof,
+    /// The collected prevotes of the height.
+    pub prevotes: Vec<SignedVote>,
+    /// The collected precommits of the height.
+    pub precommits: Vec<SignedVote>,
+}
+
+/// Trait for the consumers of the raw votes of the committed heights, such as an external
+/// finality gadget or a data availability committee. The engine exports the vote set of each
+/// height after the proof is saved and before the `commit()` interface is called.
+#[async_trait]
+pub trait VoteSink: Debug + Send + Sync {
+    /// Export the vote set of a committed height.
+    async fn export(&self, votes: VoteSet) -> Result<(), Box<dyn Error + Send>>;
+}
+
+/// A vote sink in memory. If the capacity is set, only the vote sets of the latest heights are
+/// kept.
+#[derive(Debug, Default)]
+pub struct MemoryVoteSink {
+    sets: RwLock<BTreeMap<u64, VoteSet>>,
+    capacity: Option<usize>,
+}
+
+impl MemoryVoteSink {
+    /// Create a memory vote sink without capacity limit.
+    pub fn new() -> Self {
+        MemoryVoteSink::default()
+    }
+
+    /// Create a memory vote sink that keeps the vote sets of the latest `capacity` heights.
+    pub fn with_capacity(capacity: usize) -> Self {
+        MemoryVoteSink {
+            sets: RwLock::new(BTreeMap::new()),
+            capacity: Some(capacity),
+        }
+    }
+
+    /// Get the vote set of the given height.
+    pub fn get(&self, height: u64) -> Option<VoteSet> {
+        self.sets.read().get(&height).cloned()
+    }
+
+    /// Get the heights of the kept vote sets.
+    pub fn heights(&self) -> Vec<u64> {
+        self.sets.read().keys().copied().collect()
+    }
+}
+
+#[async_trait]
+impl VoteSink for MemoryVoteSink {
+    async fn export(&self, votes: VoteSet) -> Result<(), Box<dyn Error + Send>> {
+        let mut sets = self.sets.write();
+        sets.insert(votes.proof.height, votes);
+
+        if let Some(capacity) = self.capacity {
+            while sets.len() > capacity {
+                sets.pop_first();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{MemoryVoteSink, VoteSet, VoteSink};
+    use crate::types::{AggregatedSignature, Hash, Proof};
+
+    fn gen_vote_set(height: u64) -> VoteSet {
+        VoteSet {
+            proof: Proof {
+                height,
+                round: 0,
+                block_hash: Hash::from(height.to_be_bytes().to_vec()),
+                signature: AggregatedSignature {
+                    signature: Bytes::from(vec![1, 2, 3]),
+                    address_bitmap: Bytes::from(vec![0xff]),
+                },
+                extensions: Vec::new(),
+            },
+            prevotes: Vec::new(),
+            precommits: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_sink() {
+        let sink = MemoryVoteSink::with_capacity(2);
+        for height in 1..=3 {
+            sink.export(gen_vote_set(height)).await.unwrap();
+        }
+
+        assert_eq!(sink.heights(), vec![2, 3]);
+        assert_eq!(sink.get(3), Some(gen_vote_set(3)));
+        assert_eq!(sink.get(1), None);
+    }
+}
@@ -0,0 +1,276 @@
+use std::error::Error;
+use std::fmt::Debug;
+
+use bytes::Bytes;
+#[cfg(feature = "runtime")]
+use bytes::{BufMut, BytesMut};
+
+use crate::wal::{WalMigration, WalRecordKind};
+
+/// The first byte of an encrypted wal record, which is followed by the output of the cipher.
+/// It is neither a version nor an rlp list, so an encrypted record is never taken as a plain
+/// one.
+pub(crate) const SEALED_RECORD: u8 = 0x20;
+
+/// Trait for encrypting the wal records at rest, keyed by the material of the application.
+/// All of the records saved through the `Wal` trait are encrypted, and the record kind should
+/// be bound to the ciphertext, such as the associated data of an AEAD, so that a record can
+/// not be replayed as another kind.
+pub trait WalCipher: Debug + Send + Sync {
+    /// Encrypt a wal record of the kind.
+    fn encrypt(
+        &self,
+        kind: WalRecordKind,
+        record: Bytes,
+    ) -> Result<Bytes, Box<dyn Error + Send>>;
+
+    /// Decrypt a wal record of the kind, which fails if the record is tampered with.
+    fn decrypt(
+        &self,
+        kind: WalRecordKind,
+        record: Bytes,
+    ) -> Result<Bytes, Box<dyn Error + Send>>;
+}
+
+/// Encrypt a wal record by the cipher if there is one.
+#[cfg(feature = "runtime")]
+pub(crate) fn seal_record(
+    kind: WalRecordKind,
+    record: Bytes,
+    cipher: Option<&dyn WalCipher>,
+) -> Result<Bytes, Box<dyn Error + Send>> {
+    let cipher = match cipher {
+        Some(cipher) => cipher,
+        None => return Ok(record),
+    };
+
+    let sealed = cipher.encrypt(kind, record)?;
+    let mut out = BytesMut::with_capacity(sealed.len() + 1);
+    out.put_u8(SEALED_RECORD);
+    out.extend_from_slice(&sealed);
+    Ok(out.freeze())
+}
+
+/// Decrypt a wal record by the cipher. An encrypted record is an error without the cipher,
+/// and a plain one is an error with it, unless the migration adopts it to enable the
+/// encryption on an existing wal.
+pub(crate) fn open_record(
+    kind: WalRecordKind,
+    record: Bytes,
+    cipher: Option<&dyn WalCipher>,
+    migration: Option<&dyn WalMigration>,
+) -> Result<Bytes, String> {
+    let sealed = record.first() == Some(&SEALED_RECORD);
+    let cipher = match cipher {
+        Some(cipher) if sealed => cipher,
+        Some(_) if migration.is_some_and(|m| m.adopt_plain(kind)) => return Ok(record),
+        Some(_) => return Err(format!("{} record is not encrypted", kind)),
+        None if sealed => return Err(format!("{} record is encrypted", kind)),
+        None => return Ok(record),
+    };
+    cipher
+        .decrypt(kind, record.slice(1..))
+        .map_err(|e| format!("decrypt {} record error {:?}", kind, e))
+}
+
+#[cfg(feature = "wal-aes-gcm")]
+pub use self::aes::AesGcmCipher;
+
+#[cfg(feature = "wal-aes-gcm")]
+mod aes {
+    use std::error::Error;
+
+    use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+    use aes_gcm::{Aes256Gcm, Key, Nonce};
+    use bytes::Bytes;
+
+    use super::WalCipher;
+    use crate::error::ConsensusError;
+    use crate::wal::WalRecordKind;
+
+    const NONCE_LEN: usize = 12;
+
+    /// The AES-256-GCM encryption of the wal records. An encrypted record is a random nonce
+    /// followed by the ciphertext, and the record kind is the associated data.
+    pub struct AesGcmCipher {
+        cipher: Aes256Gcm,
+    }
+
+    impl AesGcmCipher {
+        /// Create the cipher by the 32 bytes key.
+        pub fn new(key: &[u8; 32]) -> Self {
+            AesGcmCipher {
+                cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            }
+        }
+    }
+
+    impl std::fmt::Debug for AesGcmCipher {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("AesGcmCipher")
+        }
+    }
+
+    impl WalCipher for AesGcmCipher {
+        fn encrypt(
+            &self,
+            kind: WalRecordKind,
+            record: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            let aad = kind.to_string();
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let payload = Payload {
+                msg: &record,
+                aad: aad.as_bytes(),
+            };
+            let ciphertext = self.cipher.encrypt(&nonce, payload).map_err(cipher_err)?;
+            let mut out = nonce.to_vec();
+            out.extend_from_slice(&ciphertext);
+            Ok(Bytes::from(out))
+        }
+
+        fn decrypt(
+            &self,
+            kind: WalRecordKind,
+            record: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            if record.len() < NONCE_LEN {
+                return Err(cipher_err("record shorter than the nonce"));
+            }
+            let aad = kind.to_string();
+            let (nonce, ciphertext) = record.split_at(NONCE_LEN);
+            let payload = Payload {
+                msg: ciphertext,
+                aad: aad.as_bytes(),
+            };
+            let plain = self
+                .cipher
+                .decrypt(Nonce::from_slice(nonce), payload)
+                .map_err(cipher_err)?;
+            Ok(Bytes::from(plain))
+        }
+    }
+
+    fn cipher_err<E: std::fmt::Debug>(e: E) -> Box<dyn Error + Send> {
+        Box::new(ConsensusError::CryptoErr(format!("wal cipher {:?}", e)))
+    }
+}
+
+#[cfg(all(test, any(feature = "runtime", feature = "wal-aes-gcm")))]
+mod test {
+    use std::error::Error;
+
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[cfg(feature = "runtime")]
+    #[derive(Debug)]
+    struct XorCipher(u8);
+
+    #[cfg(feature = "runtime")]
+    #[derive(Debug)]
+    struct AdoptPlain;
+
+    #[cfg(feature = "runtime")]
+    impl WalMigration for AdoptPlain {
+        fn migrate(
+            &self,
+            _kind: WalRecordKind,
+            _version: u8,
+            payload: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            Ok(payload)
+        }
+
+        fn adopt_plain(&self, _kind: WalRecordKind) -> bool {
+            true
+        }
+    }
+
+    #[cfg(feature = "runtime")]
+    impl WalCipher for XorCipher {
+        fn encrypt(
+            &self,
+            _kind: WalRecordKind,
+            record: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            Ok(record.iter().map(|b| b ^ self.0).collect())
+        }
+
+        fn decrypt(
+            &self,
+            kind: WalRecordKind,
+            record: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            self.encrypt(kind, record)
+        }
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_seal_record() {
+        let kind = WalRecordKind::WalInfo;
+        let record = Bytes::from(vec![1u8, 2, 3]);
+        let cipher = XorCipher(0xff);
+        let sealed = seal_record(kind, record.clone(), Some(&cipher)).unwrap();
+        assert_eq!(sealed[0], SEALED_RECORD);
+        assert_eq!(
+            open_record(kind, sealed.clone(), Some(&cipher), None).unwrap(),
+            record
+        );
+
+        // A plain record is loaded as it is without the cipher, and an encrypted one needs
+        // the cipher.
+        assert_eq!(seal_record(kind, record.clone(), None).unwrap(), record);
+        assert_eq!(open_record(kind, record.clone(), None, None).unwrap(), record);
+        assert!(open_record(kind, sealed, None, None).is_err());
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_plain_record_downgrade() {
+        let kind = WalRecordKind::LastSigned;
+        let record = Bytes::from(vec![1u8, 2, 3]);
+        let cipher = XorCipher(0xff);
+
+        // A plain record in place of an encrypted one is refused with the cipher, unless the
+        // migration adopts it.
+        assert!(open_record(kind, record.clone(), Some(&cipher), None).is_err());
+        assert_eq!(
+            open_record(kind, record.clone(), Some(&cipher), Some(&AdoptPlain))
+                .unwrap(),
+            record
+        );
+    }
+
+    #[cfg(feature = "wal-aes-gcm")]
+    #[test]
+    fn test_aes_gcm_cipher() {
+        let cipher = AesGcmCipher::new(&[7u8; 32]);
+        let record = Bytes::from(vec![1u8; 64]);
+        let sealed = cipher
+            .encrypt(WalRecordKind::LastSigned, record.clone())
+            .unwrap();
+        assert_ne!(sealed, record);
+        assert_eq!(
+            cipher
+                .decrypt(WalRecordKind::LastSigned, sealed.clone())
+                .unwrap(),
+            record
+        );
+
+        // The tampered record, the record of another kind and another key are refused.
+        let mut tampered = sealed.to_vec();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(cipher
+            .decrypt(WalRecordKind::LastSigned, Bytes::from(tampered))
+            .is_err());
+        assert!(cipher
+            .decrypt(WalRecordKind::WalInfo, sealed.clone())
+            .is_err());
+        assert!(AesGcmCipher::new(&[8u8; 32])
+            .decrypt(WalRecordKind::LastSigned, sealed)
+            .is_err());
+    }
+}
@@ -1,3 +1,652 @@
+mod cipher;
+#[cfg(feature = "rocksdb")]
+mod rocks;
+mod step;
+mod sync;
 mod wal_type;
 
-pub use self::wal_type::{SMRBase, WalInfo, WalLock};
+#[cfg(feature = "wal-aes-gcm")]
+pub use self::cipher::AesGcmCipher;
+pub use self::cipher::WalCipher;
+pub(crate) use self::cipher::open_record;
+#[cfg(feature = "runtime")]
+pub(crate) use self::cipher::seal_record;
+#[cfg(feature = "rocksdb")]
+pub use self::rocks::{
+    RocksWal, RocksWalDB, DEFAULT_RETAIN_HEIGHTS, DEFAULT_RETAIN_STEPS,
+};
+pub use self::step::{StepRecord, StepRecords};
+pub use self::sync::{WalSyncPolicy, WalSyncer};
+pub use self::wal_type::{LastSigned, SMRBase, WalInfo, WalLock};
+
+use std::error::Error;
+use std::fmt::Debug;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use derive_more::Display;
+use hummer::coding::hex_encode;
+use rlp::{Rlp, RlpStream};
+
+use crate::error::{ConsensusError, ErrorKind};
+#[cfg(feature = "runtime")]
+use crate::state::snapshot::ConsensusSnapshot;
+#[cfg(feature = "runtime")]
+use crate::types::AggregatedVote;
+use crate::types::{Proof, SignedVote};
+use crate::wire::WireCodec;
+use crate::{Codec, ConsensusResult};
+
+/// The version of the wal record format. A record is the version byte followed by the rlp
+/// encoded payload.
+pub const WAL_VERSION: u8 = 1;
+
+/// The records saved before the format is versioned have no version byte. Such a record is
+/// an rlp list, whose first byte is at least `0xc0`, so the versions must be less than it.
+const LEGACY_VERSION: u8 = 0;
+const RLP_LIST_OFFSET: u8 = 0xc0;
+/// The flag of the version byte which marks a record tagged with the run id. The rest of such
+/// a record is an rlp list of the run id and the payload.
+const RUN_TAG_FLAG: u8 = 0x40;
+
+/// Kinds of the wal records.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum WalRecordKind {
+    /// The record of `Wal::save()`.
+    #[display(fmt = "wal info")]
+    WalInfo,
+    /// The record of `Wal::save_last_signed()`.
+    #[display(fmt = "last signed")]
+    LastSigned,
+    /// The record of `Wal::save_checkpoint()`.
+    #[display(fmt = "checkpoint")]
+    Checkpoint,
+    /// The record of `Wal::save_proof_archive()`.
+    #[display(fmt = "proof archive")]
+    ProofArchive,
+    /// The record of `Wal::append_step()`.
+    #[display(fmt = "step")]
+    Step,
+    /// The record of `Wal::save_round_votes()`.
+    #[display(fmt = "round votes")]
+    RoundVotes,
+}
+
+/// Trait for migrating the wal records of the older formats, so that a node upgrading across
+/// the format versions can recover from its wal.
+pub trait WalMigration: Debug + Send + Sync {
+    /// Migrate the payload of a record of an older version to the payload of the current
+    /// version.
+    fn migrate(
+        &self,
+        kind: WalRecordKind,
+        version: u8,
+        payload: Bytes,
+    ) -> Result<Bytes, Box<dyn Error + Send>>;
+
+    /// Whether to adopt a record tagged with the run id of another run, or an untagged record
+    /// of which the run id is empty. Such records are rejected by default.
+    fn adopt_run(&self, _kind: WalRecordKind, _run_id: &[u8]) -> bool {
+        false
+    }
+
+    /// Whether to adopt a plain record while a `WalCipher` is set, which enables the
+    /// encryption on an existing wal. Such records are rejected by default, so that a plain
+    /// record written in place of an encrypted one is never loaded.
+    fn adopt_plain(&self, _kind: WalRecordKind) -> bool {
+        false
+    }
+}
+
+/// The payload of a wal record, which is encoded by the wire codec.
+pub(crate) trait WalPayload: Sized {
+    fn encode_by(&self, codec: &dyn WireCodec) -> ConsensusResult<Bytes>;
+
+    fn decode_by(codec: &dyn WireCodec, payload: &[u8]) -> Result<Self, String>;
+}
+
+impl<T: Codec> WalPayload for WalInfo<T> {
+    fn encode_by(&self, codec: &dyn WireCodec) -> ConsensusResult<Bytes> {
+        codec.encode_wal_info(&WalInfo {
+            height: self.height,
+            round: self.round,
+            step: self.step.clone(),
+            lock: encode_wal_lock(self.lock.as_ref())?,
+            from: self.from.clone(),
+            status: self.status.clone(),
+            pending_commit: encode_wal_lock(self.pending_commit.as_ref())?,
+        })
+    }
+
+    fn decode_by(codec: &dyn WireCodec, payload: &[u8]) -> Result<Self, String> {
+        let info = codec.decode_wal_info(payload)?;
+        Ok(WalInfo {
+            height: info.height,
+            round: info.round,
+            step: info.step,
+            lock: decode_wal_lock(info.lock)?,
+            from: info.from,
+            status: info.status,
+            pending_commit: decode_wal_lock(info.pending_commit)?,
+        })
+    }
+}
+
+fn encode_wal_lock<T: Codec>(
+    lock: Option<&WalLock<T>>,
+) -> ConsensusResult<Option<WalLock<Bytes>>> {
+    match lock {
+        Some(lock) => Ok(Some(WalLock {
+            lock_round: lock.lock_round,
+            lock_votes: lock.lock_votes.clone(),
+            content: lock.content.encode().map_err(|e| {
+                ConsensusError::with_source(
+                    ErrorKind::Internal,
+                    "Encode locked block",
+                    e,
+                )
+            })?,
+        })),
+        None => Ok(None),
+    }
+}
+
+fn decode_wal_lock<T: Codec>(
+    lock: Option<WalLock<Bytes>>,
+) -> Result<Option<WalLock<T>>, String> {
+    match lock {
+        Some(lock) => Ok(Some(WalLock {
+            lock_round: lock.lock_round,
+            lock_votes: lock.lock_votes,
+            content: T::decode(lock.content)
+                .map_err(|e| format!("decode locked block error {:?}", e))?,
+        })),
+        None => Ok(None),
+    }
+}
+
+impl WalPayload for LastSigned {
+    fn encode_by(&self, codec: &dyn WireCodec) -> ConsensusResult<Bytes> {
+        codec.encode_last_signed(self)
+    }
+
+    fn decode_by(codec: &dyn WireCodec, payload: &[u8]) -> Result<Self, String> {
+        codec.decode_last_signed(payload)
+    }
+}
+
+/// A checkpoint is an rlp list of the payloads of its wal info and its last signed record,
+/// which is empty if there is none, and the rlp list of its QCs.
+#[cfg(feature = "runtime")]
+impl<T: Codec> WalPayload for ConsensusSnapshot<T> {
+    fn encode_by(&self, codec: &dyn WireCodec) -> ConsensusResult<Bytes> {
+        let last_signed = match self.last_signed.as_ref() {
+            Some(last_signed) => last_signed.encode_by(codec)?.to_vec(),
+            None => Vec::new(),
+        };
+        let mut stream = RlpStream::new_list(3);
+        stream.append(&self.wal_info.encode_by(codec)?.to_vec());
+        stream.append(&last_signed);
+        stream.append_list(&self.qcs);
+        Ok(stream.out().freeze())
+    }
+
+    fn decode_by(codec: &dyn WireCodec, payload: &[u8]) -> Result<Self, String> {
+        let rlp = Rlp::new(payload);
+        let field = |index: usize| {
+            rlp.val_at::<Vec<u8>>(index)
+                .map_err(|e| format!("decode checkpoint error {:?}", e))
+        };
+        let wal_info = WalInfo::decode_by(codec, &field(0)?)?;
+        let last_signed = field(1)?;
+        let last_signed = if last_signed.is_empty() {
+            None
+        } else {
+            Some(LastSigned::decode_by(codec, &last_signed)?)
+        };
+        let qcs: Vec<AggregatedVote> = rlp
+            .list_at(2)
+            .map_err(|e| format!("decode checkpoint qcs error {:?}", e))?;
+        Ok(ConsensusSnapshot {
+            wal_info,
+            last_signed,
+            qcs,
+        })
+    }
+}
+
+/// The archive of the commit proofs is the rlp list of the proofs in the order of their
+/// heights.
+impl WalPayload for Vec<Proof> {
+    fn encode_by(&self, _codec: &dyn WireCodec) -> ConsensusResult<Bytes> {
+        Ok(Bytes::from(rlp::encode_list(self)))
+    }
+
+    fn decode_by(_codec: &dyn WireCodec, payload: &[u8]) -> Result<Self, String> {
+        Rlp::new(payload)
+            .as_list()
+            .map_err(|e| format!("decode proof archive error {:?}", e))
+    }
+}
+
+/// The collected votes of a round are the rlp list of the signed votes, whatever the wire
+/// codec is.
+impl WalPayload for Vec<SignedVote> {
+    fn encode_by(&self, _codec: &dyn WireCodec) -> ConsensusResult<Bytes> {
+        Ok(Bytes::from(rlp::encode_list(self)))
+    }
+
+    fn decode_by(_codec: &dyn WireCodec, payload: &[u8]) -> Result<Self, String> {
+        Rlp::new(payload)
+            .as_list()
+            .map_err(|e| format!("decode round votes error {:?}", e))
+    }
+}
+
+impl WalPayload for StepRecord {
+    fn encode_by(&self, _codec: &dyn WireCodec) -> ConsensusResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(self)))
+    }
+
+    fn decode_by(_codec: &dyn WireCodec, payload: &[u8]) -> Result<Self, String> {
+        rlp::decode(payload).map_err(|e| format!("decode step record error {:?}", e))
+    }
+}
+
+/// Encode a wal record of the current version, which is tagged with the run id unless it is
+/// empty.
+pub(crate) fn encode_record<E: WalPayload>(
+    item: &E,
+    codec: &dyn WireCodec,
+    run_id: &[u8],
+) -> ConsensusResult<Bytes> {
+    let payload = item.encode_by(codec)?;
+    if run_id.is_empty() {
+        let mut record = BytesMut::with_capacity(payload.len() + 1);
+        record.put_u8(WAL_VERSION);
+        record.extend_from_slice(&payload);
+        return Ok(record.freeze());
+    }
+
+    let mut stream = RlpStream::new_list(2);
+    stream.append(&run_id);
+    stream.append_raw(&payload, 1);
+    let tagged = stream.out();
+
+    let mut record = BytesMut::with_capacity(tagged.len() + 1);
+    record.put_u8(WAL_VERSION | RUN_TAG_FLAG);
+    record.extend_from_slice(&tagged);
+    Ok(record.freeze())
+}
+
+/// Split a wal record into the version, the run id and the payload. The run id of an untagged
+/// record is empty.
+fn split_record(record: &[u8]) -> Result<(u8, Vec<u8>, &[u8]), String> {
+    let (first, rest) = match record.split_first() {
+        Some((first, _)) if *first >= RLP_LIST_OFFSET => {
+            return Ok((LEGACY_VERSION, Vec::new(), record))
+        }
+        Some(split) => split,
+        None => return Err("empty record".to_string()),
+    };
+
+    if first & RUN_TAG_FLAG == 0 {
+        return Ok((*first, Vec::new(), rest));
+    }
+
+    let tagged = Rlp::new(rest);
+    let run_id = tagged
+        .val_at::<Vec<u8>>(0)
+        .map_err(|e| format!("decode run id error {:?}", e))?;
+    let payload = tagged
+        .at(1)
+        .map_err(|e| format!("decode tagged payload error {:?}", e))?
+        .as_raw();
+    Ok((first & !RUN_TAG_FLAG, run_id, payload))
+}
+
+/// Decode a wal record by its version. A record of an older version is migrated by the
+/// migration if there is one, and a legacy record without the version byte is decoded as it
+/// is since the layout is unchanged from it. A record of a newer version is an error. If the
+/// run id is given, a record of another run is an error unless the migration adopts it.
+pub(crate) fn decode_record<D: WalPayload>(
+    kind: WalRecordKind,
+    record: &[u8],
+    codec: &dyn WireCodec,
+    migration: Option<&dyn WalMigration>,
+    run_id: Option<&[u8]>,
+) -> Result<D, String> {
+    let (version, record_run_id, payload) =
+        split_record(record).map_err(|e| format!("{} {}", kind, e))?;
+
+    if let Some(run_id) = run_id {
+        if record_run_id != run_id
+            && !migration.is_some_and(|m| m.adopt_run(kind, &record_run_id))
+        {
+            return Err(format!(
+                "{} record of run {:?} is not of run {:?}",
+                kind,
+                hex_encode(&record_run_id),
+                hex_encode(run_id)
+            ));
+        }
+    }
+
+    let payload = if version == WAL_VERSION {
+        Bytes::copy_from_slice(payload)
+    } else if version > WAL_VERSION {
+        return Err(format!(
+            "{} record of version {} is newer than {}",
+            kind, version, WAL_VERSION
+        ));
+    } else if let Some(migration) = migration {
+        migration
+            .migrate(kind, version, Bytes::copy_from_slice(payload))
+            .map_err(|e| {
+                format!(
+                    "migrate {} record of version {} error {:?}",
+                    kind, version, e
+                )
+            })?
+    } else if version == LEGACY_VERSION {
+        Bytes::copy_from_slice(payload)
+    } else {
+        return Err(format!(
+            "no migration of {} record of version {}",
+            kind, version
+        ));
+    };
+
+    D::decode_by(codec, &payload)
+        .map_err(|e| format!("decode {} record error {}", kind, e))
+}
+
+/// Get the height of a wal record, which is the first item of the payload of the wal info and
+/// the last signed records. The height of an encrypted record is unknown.
+#[cfg(feature = "rocksdb")]
+pub(crate) fn record_height(record: &[u8]) -> Option<u64> {
+    if record.first() == Some(&cipher::SEALED_RECORD) {
+        return None;
+    }
+    let (_, _, payload) = split_record(record).ok()?;
+    Rlp::new(payload).val_at(0).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    #[cfg(feature = "runtime")]
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::smr::smr_types::Step;
+    use crate::types::{Address, AggregatedSignature, Hash, Proof, SignedVote, Vote, VoteType};
+    #[cfg(feature = "runtime")]
+    use crate::types::{Node, Status, UpdateFrom};
+    use crate::wire::RlpCodec;
+
+    #[derive(Debug)]
+    struct BumpRound;
+
+    impl WalMigration for BumpRound {
+        fn migrate(
+            &self,
+            kind: WalRecordKind,
+            _version: u8,
+            payload: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            assert_eq!(kind, WalRecordKind::LastSigned);
+            let mut record: LastSigned = rlp::decode(&payload).unwrap();
+            record.round += 1;
+            Ok(Bytes::from(rlp::encode(&record)))
+        }
+    }
+
+    fn gen_last_signed() -> LastSigned {
+        LastSigned {
+            height: 1,
+            round: 2,
+            step: Step::Prevote,
+            hash: Hash::from(vec![3u8; 32]),
+        }
+    }
+
+    #[test]
+    fn test_versioned_record() {
+        let kind = WalRecordKind::LastSigned;
+        let last_signed = gen_last_signed();
+        let record = encode_record(&last_signed, &RlpCodec, &[]).unwrap();
+        assert_eq!(record[0], WAL_VERSION);
+        assert_eq!(
+            decode_record::<LastSigned>(kind, &record, &RlpCodec, None, Some(&[]))
+                .unwrap(),
+            last_signed
+        );
+
+        // A legacy record is decoded as it is, or migrated if there is a migration.
+        let legacy = rlp::encode(&last_signed);
+        assert_eq!(
+            decode_record::<LastSigned>(kind, &legacy, &RlpCodec, None, None).unwrap(),
+            last_signed
+        );
+        let migrated = decode_record::<LastSigned>(
+            kind,
+            &legacy,
+            &RlpCodec,
+            Some(&BumpRound),
+            None,
+        );
+        assert_eq!(migrated.unwrap().round, last_signed.round + 1);
+
+        // A record of a newer version is refused.
+        let mut newer = record.to_vec();
+        newer[0] = WAL_VERSION + 1;
+        assert!(decode_record::<LastSigned>(
+            kind,
+            &newer,
+            &RlpCodec,
+            Some(&BumpRound),
+            None
+        )
+        .is_err());
+        assert!(decode_record::<LastSigned>(kind, &[], &RlpCodec, None, None).is_err());
+    }
+
+    #[derive(Debug)]
+    struct AdoptUntagged;
+
+    impl WalMigration for AdoptUntagged {
+        fn migrate(
+            &self,
+            _kind: WalRecordKind,
+            _version: u8,
+            payload: Bytes,
+        ) -> Result<Bytes, Box<dyn Error + Send>> {
+            Ok(payload)
+        }
+
+        fn adopt_run(&self, _kind: WalRecordKind, run_id: &[u8]) -> bool {
+            run_id.is_empty()
+        }
+    }
+
+    #[test]
+    fn test_run_tagged_record() {
+        let kind = WalRecordKind::LastSigned;
+        let last_signed = gen_last_signed();
+        let run_a: &[u8] = b"run-a";
+        let record = encode_record(&last_signed, &RlpCodec, run_a).unwrap();
+        assert_eq!(record[0], WAL_VERSION | RUN_TAG_FLAG);
+        assert_eq!(
+            decode_record::<LastSigned>(kind, &record, &RlpCodec, None, Some(run_a))
+                .unwrap(),
+            last_signed
+        );
+        assert_eq!(
+            decode_record::<LastSigned>(kind, &record, &RlpCodec, None, None).unwrap(),
+            last_signed
+        );
+
+        // The records of another run or untagged are refused unless adopted.
+        let untagged = encode_record(&last_signed, &RlpCodec, &[]).unwrap();
+        assert!(decode_record::<LastSigned>(
+            kind,
+            &record,
+            &RlpCodec,
+            None,
+            Some(b"run-b")
+        )
+        .is_err());
+        assert!(
+            decode_record::<LastSigned>(kind, &record, &RlpCodec, None, Some(&[]))
+                .is_err()
+        );
+        assert!(decode_record::<LastSigned>(
+            kind,
+            &untagged,
+            &RlpCodec,
+            None,
+            Some(run_a)
+        )
+        .is_err());
+        assert_eq!(
+            decode_record::<LastSigned>(
+                kind,
+                &untagged,
+                &RlpCodec,
+                Some(&AdoptUntagged),
+                Some(run_a)
+            )
+            .unwrap(),
+            last_signed
+        );
+        assert!(decode_record::<LastSigned>(
+            kind,
+            &record,
+            &RlpCodec,
+            Some(&AdoptUntagged),
+            Some(b"run-b")
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_checkpoint_record() {
+        let qc = AggregatedVote {
+            signature: AggregatedSignature {
+                signature: Bytes::from(vec![1u8; 64]),
+                address_bitmap: Bytes::from(vec![0b1110_0000]),
+            },
+            vote_type: VoteType::Precommit,
+            height: 1,
+            round: 2,
+            block_hash: Hash::from(vec![4u8; 32]),
+            leader: Address::from_slice(&[5u8; 20]).unwrap(),
+            extensions: Vec::new(),
+        };
+        let wal_info = WalInfo::<Bytes> {
+            height: 1,
+            round: 2,
+            step: Step::Propose,
+            lock: None,
+            from: UpdateFrom::PrecommitQC(qc.clone()),
+            status: Some(Status {
+                height: 1,
+                interval: Some(Duration::from_millis(3000)),
+                timer_config: None,
+                authority_list: vec![Node::new(
+                    Address::from_slice(&[5u8; 20]).unwrap(),
+                )],
+                next_authority_list: None,
+                proposer_seed: None,
+                skipped_proposers: Vec::new(),
+            }),
+            pending_commit: None,
+        };
+        let mut checkpoint = ConsensusSnapshot {
+            wal_info,
+            last_signed: Some(gen_last_signed()),
+            qcs: vec![qc],
+        };
+
+        let kind = WalRecordKind::Checkpoint;
+        let record = encode_record(&checkpoint, &RlpCodec, b"run-a").unwrap();
+        let decoded: ConsensusSnapshot<Bytes> =
+            decode_record(kind, &record, &RlpCodec, None, Some(b"run-a")).unwrap();
+        assert_eq!(decoded, checkpoint);
+        assert!(decode_record::<ConsensusSnapshot<Bytes>>(
+            kind,
+            &record,
+            &RlpCodec,
+            None,
+            Some(b"run-b")
+        )
+        .is_err());
+
+        checkpoint.last_signed = None;
+        let record = encode_record(&checkpoint, &RlpCodec, &[]).unwrap();
+        let decoded: ConsensusSnapshot<Bytes> =
+            decode_record(kind, &record, &RlpCodec, None, None).unwrap();
+        assert_eq!(decoded, checkpoint);
+    }
+
+    #[test]
+    fn test_proof_archive_record() {
+        let proofs = (1..=3)
+            .map(|height| Proof {
+                height,
+                round: 0,
+                block_hash: Hash::from(vec![height as u8; 32]),
+                signature: AggregatedSignature {
+                    signature: Bytes::from(vec![1u8; 64]),
+                    address_bitmap: Bytes::from(vec![0b1110_0000]),
+                },
+                extensions: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        let kind = WalRecordKind::ProofArchive;
+        let record = encode_record(&proofs, &RlpCodec, &[]).unwrap();
+        let decoded: Vec<Proof> =
+            decode_record(kind, &record, &RlpCodec, None, None).unwrap();
+        assert_eq!(decoded, proofs);
+
+        let record = encode_record(&Vec::<Proof>::new(), &RlpCodec, &[]).unwrap();
+        let decoded: Vec<Proof> =
+            decode_record(kind, &record, &RlpCodec, None, None).unwrap();
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    fn test_round_votes_record() {
+        let votes = [VoteType::Prevote, VoteType::Precommit]
+            .into_iter()
+            .enumerate()
+            .map(|(i, vote_type)| SignedVote {
+                signature: Bytes::from(vec![i as u8; 64]),
+                vote: Vote {
+                    height: 2,
+                    round: 1,
+                    vote_type,
+                    block_hash: Hash::from(vec![1u8; 32]),
+                },
+                voter: Address::from_slice(&[i as u8; 20]).unwrap(),
+                extension: Bytes::new(),
+                extension_signature: Bytes::new(),
+            })
+            .collect::<Vec<_>>();
+
+        let kind = WalRecordKind::RoundVotes;
+        let record = encode_record(&votes, &RlpCodec, b"run").unwrap();
+        let decoded: Vec<SignedVote> =
+            decode_record(kind, &record, &RlpCodec, None, Some(b"run")).unwrap();
+        assert_eq!(decoded, votes);
+
+        // The votes saved by another run are not restored.
+        let res: Result<Vec<SignedVote>, _> =
+            decode_record(kind, &record, &RlpCodec, None, Some(b"new"));
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use std::error::Error;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use rocksdb::{
+    BoundColumnFamily, DBWithThreadMode, Direction, IteratorMode, MultiThreaded,
+    Options, WriteBatch, WriteOptions,
+};
+
+use crate::wal::{record_height, WalSyncPolicy, WalSyncer};
+use crate::Wal;
+
+/// The rocksdb that the wals of the instances share.
+pub type RocksWalDB = DBWithThreadMode<MultiThreaded>;
+
+/// The default number of the heights whose last wal info is kept as the history.
+pub const DEFAULT_RETAIN_HEIGHTS: u64 = 16;
+
+/// The default number of the recent step records kept.
+pub const DEFAULT_RETAIN_STEPS: u64 = 1024;
+
+const INFO_KEY: &[u8] = b"info";
+const LAST_SIGNED_KEY: &[u8] = b"last_signed";
+const CHECKPOINT_KEY: &[u8] = b"checkpoint";
+const PROOF_ARCHIVE_KEY: &[u8] = b"proof_archive";
+const ROUND_VOTES_KEY: &[u8] = b"round_votes";
+const HISTORY_PREFIX: u8 = b'h';
+const STEP_PREFIX: u8 = b's';
+
+/// A wal in rocksdb. Each instance has its own column family, so the instances of a process
+/// can share a database. A wal info is written with the history record of its height in an
+/// atomic batch, which also prunes the history records of the heights out of the retained
+/// ones. The writes are synced by the `WalSyncPolicy`, which syncs all of them by default.
+/// The encrypted records by a `WalCipher` have no history, since their heights are unknown.
+/// The step records are kept by their sequence numbers, and the ones out of the retained
+/// recent records are pruned when a record is appended.
+pub struct RocksWal {
+    db: Arc<RocksWalDB>,
+    instance: String,
+    retain_heights: u64,
+    retain_steps: u64,
+    next_step: AtomicU64,
+    syncer: WalSyncer,
+}
+
+impl RocksWal {
+    /// Open or create a rocksdb in the given path, and the wal of the instance in it.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        instance: &str,
+    ) -> Result<Self, rocksdb::Error> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+
+        let cfs = RocksWalDB::list_cf(&opts, &path).unwrap_or_default();
+        let db = RocksWalDB::open_cf(&opts, &path, cfs)?;
+        RocksWal::with_db(Arc::new(db), instance)
+    }
+
+    /// Create the wal of the instance in an opened rocksdb, the column family of the instance
+    /// is created if it is missing.
+    pub fn with_db(db: Arc<RocksWalDB>, instance: &str) -> Result<Self, rocksdb::Error> {
+        if db.cf_handle(instance).is_none() {
+            db.create_cf(instance, &Options::default())?;
+        }
+
+        let mut wal = RocksWal {
+            db,
+            instance: instance.to_string(),
+            retain_heights: DEFAULT_RETAIN_HEIGHTS,
+            retain_steps: DEFAULT_RETAIN_STEPS,
+            next_step: AtomicU64::new(0),
+            syncer: WalSyncer::new(WalSyncPolicy::Always),
+        };
+        wal.next_step = AtomicU64::new(wal.last_step()?.map_or(0, |seq| seq + 1));
+        Ok(wal)
+    }
+
+    /// Set the number of the heights whose last wal info is kept as the history. Zero means
+    /// no pruning.
+    pub fn set_retain_heights(&mut self, retain_heights: u64) {
+        self.retain_heights = retain_heights;
+    }
+
+    /// Set the number of the recent step records kept. Zero means no pruning.
+    pub fn set_retain_steps(&mut self, retain_steps: u64) {
+        self.retain_steps = retain_steps;
+    }
+
+    /// Set the policy of syncing the writes. The records of the last signed message and the
+    /// wal info with a lock are always synced.
+    pub fn set_sync_policy(&mut self, policy: WalSyncPolicy) {
+        self.syncer = WalSyncer::new(policy);
+    }
+
+    /// Get the last wal info saved in the given height, if it is not pruned.
+    pub fn history(&self, height: u64) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        self.get(&history_key(height))
+    }
+
+    fn cf(&self) -> Result<Arc<BoundColumnFamily<'_>>, Box<dyn Error + Send>> {
+        self.db.cf_handle(&self.instance).ok_or_else(|| {
+            Box::new(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no column family of wal {}", self.instance),
+            )) as Box<dyn Error + Send>
+        })
+    }
+
+    /// The sequence number of the last step record, or `None` if there is none.
+    fn last_step(&self) -> Result<Option<u64>, rocksdb::Error> {
+        let cf = match self.db.cf_handle(&self.instance) {
+            Some(cf) => cf,
+            None => return Ok(None),
+        };
+        let end = step_key(u64::MAX);
+        let mode = IteratorMode::From(&end, Direction::Reverse);
+        match self.db.iterator_cf(&cf, mode).next() {
+            Some(item) => {
+                let (key, _) = item?;
+                Ok(step_seq(&key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        self.db
+            .get_cf(&self.cf()?, key)
+            .map(|data| data.map(Bytes::from))
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+    }
+
+    fn write(
+        &self,
+        batch: WriteBatch,
+        critical: bool,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let mut opts = WriteOptions::default();
+        opts.set_sync(self.syncer.should_sync(critical));
+        self.db
+            .write_opt(batch, &opts)
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+    }
+
+    fn save_info(
+        &self,
+        info: Bytes,
+        critical: bool,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let cf = self.cf()?;
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&cf, INFO_KEY, &info);
+
+        if let Some(height) = record_height(&info) {
+            batch.put_cf(&cf, history_key(height), &info);
+            if self.retain_heights > 0 && height >= self.retain_heights {
+                let end = height - self.retain_heights + 1;
+                batch.delete_range_cf(&cf, history_key(0), history_key(end));
+            }
+        }
+        self.write(batch, critical)
+    }
+}
+
+impl std::fmt::Debug for RocksWal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RocksWal")
+            .field("path", &self.db.path())
+            .field("instance", &self.instance)
+            .field("retain_heights", &self.retain_heights)
+            .field("sync_policy", &self.syncer.policy())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Wal for RocksWal {
+    async fn save(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        self.save_info(info, false)
+    }
+
+    async fn save_durable(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        self.save_info(info, true)
+    }
+
+    async fn load(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        self.get(INFO_KEY)
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn Error + Send>> {
+        self.db
+            .flush_wal(true)
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+    }
+
+    async fn save_last_signed(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&self.cf()?, LAST_SIGNED_KEY, &info);
+        self.write(batch, true)
+    }
+
+    async fn load_last_signed(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        self.get(LAST_SIGNED_KEY)
+    }
+
+    async fn save_checkpoint(
+        &self,
+        checkpoint: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&self.cf()?, CHECKPOINT_KEY, &checkpoint);
+        self.write(batch, false)
+    }
+
+    async fn load_checkpoint(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        self.get(CHECKPOINT_KEY)
+    }
+
+    async fn save_proof_archive(
+        &self,
+        archive: Bytes,
+    ) -> Result<(), Box<dyn Error + Send>> {
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&self.cf()?, PROOF_ARCHIVE_KEY, &archive);
+        self.write(batch, false)
+    }
+
+    async fn load_proof_archive(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        self.get(PROOF_ARCHIVE_KEY)
+    }
+
+    async fn save_round_votes(&self, votes: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&self.cf()?, ROUND_VOTES_KEY, &votes);
+        self.write(batch, false)
+    }
+
+    async fn load_round_votes(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        self.get(ROUND_VOTES_KEY)
+    }
+
+    async fn append_step(&self, record: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        let cf = self.cf()?;
+        let seq = self.next_step.fetch_add(1, Ordering::Relaxed);
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&cf, step_key(seq), &record);
+        if self.retain_steps > 0 && seq >= self.retain_steps {
+            let end = seq - self.retain_steps + 1;
+            batch.delete_range_cf(&cf, step_key(0), step_key(end));
+        }
+        self.write(batch, false)
+    }
+
+    async fn load_steps(&self) -> Result<Vec<Bytes>, Box<dyn Error + Send>> {
+        let cf = self.cf()?;
+        let start = step_key(0);
+        let mut steps = Vec::new();
+        let mode = IteratorMode::From(&start, Direction::Forward);
+        for item in self.db.iterator_cf(&cf, mode) {
+            let (key, value) = item.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+            if step_seq(&key).is_none() {
+                break;
+            }
+            steps.push(Bytes::from(value.into_vec()));
+        }
+        Ok(steps)
+    }
+}
+
+fn history_key(height: u64) -> [u8; 9] {
+    let mut key = [HISTORY_PREFIX; 9];
+    key[1..].copy_from_slice(&height.to_be_bytes());
+    key
+}
+
+fn step_key(seq: u64) -> [u8; 9] {
+    let mut key = [STEP_PREFIX; 9];
+    key[1..].copy_from_slice(&seq.to_be_bytes());
+    key
+}
+
+/// The sequence number of the key of a step record, or `None` if it is another key.
+fn step_seq(key: &[u8]) -> Option<u64> {
+    match key.split_first() {
+        Some((&STEP_PREFIX, seq)) => Some(u64::from_be_bytes(seq.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use rand::random;
+
+    use super::RocksWal;
+    use crate::smr::smr_types::Step;
+    use crate::types::Hash;
+    use crate::wal::{encode_record, LastSigned, WalSyncPolicy};
+    use crate::wire::RlpCodec;
+    use crate::Wal;
+
+    /// The records of the wal info and the last signed message both start with the height.
+    fn gen_record(height: u64) -> Bytes {
+        encode_record(
+            &LastSigned {
+                height,
+                round: 0,
+                step: Step::Propose,
+                hash: Hash::from(vec![height as u8; 32]),
+            },
+            &RlpCodec,
+            b"rocks",
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rocks_wal() {
+        let path = std::env::temp_dir().join(format!("mlm-wal-{}", random::<u64>()));
+        {
+            let mut wal = RocksWal::open(&path, "a").unwrap();
+            wal.set_retain_heights(2);
+            wal.set_sync_policy(WalSyncPolicy::EveryNRecords(2));
+            let other = RocksWal::with_db(wal.db.clone(), "b").unwrap();
+
+            for height in 1..=4 {
+                wal.save(gen_record(height)).await.unwrap();
+            }
+            assert_eq!(wal.load().await.unwrap(), Some(gen_record(4)));
+            assert_eq!(wal.history(3).unwrap(), Some(gen_record(3)));
+            assert_eq!(wal.history(2).unwrap(), None);
+            assert_eq!(other.load().await.unwrap(), None);
+
+            wal.save_last_signed(gen_record(4)).await.unwrap();
+            assert_eq!(wal.load_last_signed().await.unwrap(), Some(gen_record(4)));
+            assert_eq!(other.load_last_signed().await.unwrap(), None);
+
+            wal.save_checkpoint(gen_record(4)).await.unwrap();
+            assert_eq!(wal.load_checkpoint().await.unwrap(), Some(gen_record(4)));
+            assert_eq!(other.load_checkpoint().await.unwrap(), None);
+
+            wal.save_proof_archive(gen_record(4)).await.unwrap();
+            assert_eq!(wal.load_proof_archive().await.unwrap(), Some(gen_record(4)));
+            assert_eq!(other.load_proof_archive().await.unwrap(), None);
+
+            wal.save_round_votes(gen_record(4)).await.unwrap();
+            assert_eq!(wal.load_round_votes().await.unwrap(), Some(gen_record(4)));
+            assert_eq!(other.load_round_votes().await.unwrap(), None);
+
+            wal.set_retain_steps(3);
+            for height in 1..=4 {
+                wal.append_step(gen_record(height)).await.unwrap();
+            }
+            assert_eq!(
+                wal.load_steps().await.unwrap(),
+                (2..=4).map(gen_record).collect::<Vec<_>>()
+            );
+            assert!(other.load_steps().await.unwrap().is_empty());
+        }
+
+        // The column families of the instances are opened again, and the step records are
+        // appended after the kept ones.
+        {
+            let wal = RocksWal::open(&path, "a").unwrap();
+            assert_eq!(wal.load().await.unwrap(), Some(gen_record(4)));
+            wal.append_step(gen_record(5)).await.unwrap();
+            assert_eq!(
+                wal.load_steps().await.unwrap(),
+                (2..=5).map(gen_record).collect::<Vec<_>>()
+            );
+        }
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+use std::vec;
+
+use bytes::Bytes;
+use derive_more::Display;
+
+use crate::error::ConsensusError;
+use crate::types::{Hash, VoteType};
+use crate::wal::{decode_record, open_record, WalCipher, WalRecordKind};
+use crate::wire::RlpCodec;
+use crate::ConsensusResult;
+
+/// A step record, which the state appends to the wal by `Wal::append_step()` at each
+/// transition relevant to the safety of the node. The records are the history of the
+/// consensus actions of the node for the applications to inspect, and are not read by the
+/// recovery. The payload of a record is rlp whatever the wire codec is.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum StepRecord {
+    /// The node enters a round.
+    #[display(fmt = "new round height {}, round {}", height, round)]
+    NewRound {
+        /// The height of the round.
+        height: u64,
+        /// The round entered.
+        round: u64,
+    },
+    /// The node locks a block by the prevote QC of the round.
+    #[display(fmt = "locked height {}, round {}", height, round)]
+    Locked {
+        /// The height of the lock.
+        height: u64,
+        /// The round of the lock, which is the round of its prevote QC.
+        round: u64,
+        /// The hash of the locked block.
+        block_hash: Hash,
+    },
+    /// The node signs a vote.
+    #[display(fmt = "voted {:?} height {}, round {}", vote_type, height, round)]
+    Voted {
+        /// The height of the vote.
+        height: u64,
+        /// The round of the vote.
+        round: u64,
+        /// The type of the vote.
+        vote_type: VoteType,
+        /// The block hash of the vote, which is empty for a vote of nil.
+        block_hash: Hash,
+    },
+    /// The node commits a block.
+    #[display(fmt = "committed height {}, round {}", height, round)]
+    Committed {
+        /// The committed height.
+        height: u64,
+        /// The round that the block is decided in.
+        round: u64,
+        /// The hash of the committed block.
+        block_hash: Hash,
+    },
+    /// The node saves a checkpoint at the start of a height.
+    #[display(fmt = "checkpoint height {}", height)]
+    Checkpoint {
+        /// The height of the checkpoint.
+        height: u64,
+    },
+}
+
+impl StepRecord {
+    /// The height of the record.
+    pub fn height(&self) -> u64 {
+        match self {
+            StepRecord::NewRound { height, .. }
+            | StepRecord::Locked { height, .. }
+            | StepRecord::Voted { height, .. }
+            | StepRecord::Committed { height, .. }
+            | StepRecord::Checkpoint { height } => *height,
+        }
+    }
+}
+
+/// The iterator of the step records loaded by `Wal::load_steps()`, which decodes each of them
+/// in the order of the loaded ones. The records encrypted by a `WalCipher` are decrypted by
+/// the cipher, the plain ones are refused with the cipher, and the run ids of the records
+/// are not checked.
+///
+/// ```ignore
+/// for record in StepRecords::new(wal.load_steps().await?) {
+///     println!("{}", record?);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct StepRecords {
+    records: vec::IntoIter<Bytes>,
+    cipher: Option<Arc<dyn WalCipher>>,
+}
+
+impl StepRecords {
+    /// Create the iterator of the loaded step records.
+    pub fn new(records: Vec<Bytes>) -> Self {
+        StepRecords {
+            records: records.into_iter(),
+            cipher: None,
+        }
+    }
+
+    /// Decrypt the encrypted records by the cipher of the wal.
+    pub fn with_cipher(mut self, cipher: Arc<dyn WalCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+}
+
+impl Iterator for StepRecords {
+    type Item = ConsensusResult<StepRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let kind = WalRecordKind::Step;
+        let record = self.records.next()?;
+        let step = open_record(kind, record, self.cipher.as_deref(), None)
+            .and_then(|record| decode_record(kind, &record, &RlpCodec, None, None))
+            .map_err(ConsensusError::LoadWalErr);
+        Some(step)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.records.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{StepRecord, StepRecords};
+    use crate::types::{Hash, VoteType};
+    use crate::wal::encode_record;
+    use crate::wire::RlpCodec;
+
+    #[test]
+    fn test_step_records() {
+        let block_hash = Hash::from(vec![1u8; 32]);
+        let steps = vec![
+            StepRecord::NewRound {
+                height: 2,
+                round: 0,
+            },
+            StepRecord::Voted {
+                height: 2,
+                round: 0,
+                vote_type: VoteType::Prevote,
+                block_hash: block_hash.clone(),
+            },
+            StepRecord::Locked {
+                height: 2,
+                round: 0,
+                block_hash: block_hash.clone(),
+            },
+            StepRecord::Voted {
+                height: 2,
+                round: 0,
+                vote_type: VoteType::Precommit,
+                block_hash: Hash::new(),
+            },
+            StepRecord::Committed {
+                height: 2,
+                round: 0,
+                block_hash,
+            },
+            StepRecord::Checkpoint { height: 3 },
+        ];
+
+        let mut records = steps
+            .iter()
+            .map(|step| encode_record(step, &RlpCodec, b"run").unwrap())
+            .collect::<Vec<_>>();
+        let decoded = StepRecords::new(records.clone())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded, steps);
+        assert_eq!(decoded[5].height(), 3);
+
+        // A broken record is an error, and the records after it are still decoded.
+        records.insert(1, Bytes::from_static(&[1, 0xc2, 9]));
+        let decoded = StepRecords::new(records).collect::<Vec<_>>();
+        assert_eq!(decoded.len(), steps.len() + 1);
+        assert!(decoded[1].is_err());
+        assert_eq!(decoded[2].as_ref().unwrap(), &steps[1]);
+    }
+}
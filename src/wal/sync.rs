@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// The policy of syncing the wal records to the disk, which trades the durability of the wal
+/// information for the latency of saving it. A record that is not synced may be lost by a
+/// crash of the machine, and the node then recovers from an older one.
+///
+/// The policy applies to `Wal::save()` and `Wal::save_checkpoint()` only. The records of
+/// `Wal::save_last_signed()` and `Wal::save_durable()`, which guard against double sign and
+/// keep the lock, are always synced. A custom wal should follow the same rule, and can decide
+/// by a `WalSyncer`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WalSyncPolicy {
+    /// Sync every record.
+    #[default]
+    Always,
+    /// Sync every such number of the records. Zero means always.
+    EveryNRecords(u64),
+    /// Sync a record if the milliseconds have passed since the last sync. Zero means always.
+    Interval(u64),
+}
+
+/// The state of a `WalSyncPolicy`, which decides whether to sync each record.
+#[derive(Debug)]
+pub struct WalSyncer {
+    policy: WalSyncPolicy,
+    state: Mutex<SyncState>,
+}
+
+#[derive(Debug)]
+struct SyncState {
+    unsynced: u64,
+    /// The instant of the last sync, which is read by the interval policy only, so the other
+    /// policies work on a target without a clock.
+    last_sync: Option<Instant>,
+}
+
+impl WalSyncer {
+    /// Create the syncer of the policy.
+    pub fn new(policy: WalSyncPolicy) -> Self {
+        WalSyncer {
+            policy,
+            state: Mutex::new(SyncState {
+                unsynced: 0,
+                last_sync: None,
+            }),
+        }
+    }
+
+    /// The policy of the syncer.
+    pub fn policy(&self) -> WalSyncPolicy {
+        self.policy
+    }
+
+    /// Whether to sync the next record by the policy. A safety critical record is always
+    /// synced. The decision is counted as the record is written, so it should be called once
+    /// for each record.
+    pub fn should_sync(&self, critical: bool) -> bool {
+        let mut state = self.state.lock();
+        let sync = critical
+            || match self.policy {
+                WalSyncPolicy::Always => true,
+                WalSyncPolicy::EveryNRecords(n) => state.unsynced + 1 >= n,
+                WalSyncPolicy::Interval(millis) => {
+                    let last_sync = *state.last_sync.get_or_insert_with(Instant::now);
+                    last_sync.elapsed() >= Duration::from_millis(millis)
+                }
+            };
+
+        if sync {
+            state.unsynced = 0;
+            if matches!(self.policy, WalSyncPolicy::Interval(_)) {
+                state.last_sync = Some(Instant::now());
+            }
+        } else {
+            state.unsynced += 1;
+        }
+        sync
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wal_syncer() {
+        let always = WalSyncer::new(WalSyncPolicy::default());
+        assert!((0..3).all(|_| always.should_sync(false)));
+
+        let every = WalSyncer::new(WalSyncPolicy::EveryNRecords(3));
+        let syncs = (0..6).map(|_| every.should_sync(false)).collect::<Vec<_>>();
+        assert_eq!(syncs, vec![false, false, true, false, false, true]);
+
+        // A critical record is synced and restarts the count.
+        assert!(!every.should_sync(false));
+        assert!(every.should_sync(true));
+        assert!(!every.should_sync(false));
+        assert!(!every.should_sync(false));
+        assert!(every.should_sync(false));
+        // Only the interval policy reads the clock.
+        assert!(always.state.lock().last_sync.is_none());
+        assert!(every.state.lock().last_sync.is_none());
+
+        let interval = WalSyncer::new(WalSyncPolicy::Interval(60_000));
+        assert!(!interval.should_sync(false));
+        assert!(interval.should_sync(true));
+        assert!(WalSyncer::new(WalSyncPolicy::Interval(0)).should_sync(false));
+    }
+}
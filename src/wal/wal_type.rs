@@ -1,9 +1,14 @@
+use bytes::Bytes;
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
 use crate::smr::smr_types::{Lock, Step};
-use crate::types::{AggregatedVote, UpdateFrom};
-use crate::Codec;
+use crate::types::{AggregatedVote, Hash, Status, UpdateFrom};
+#[cfg(feature = "runtime")]
+use crate::types::VoteType;
+use crate::wal::{decode_record, encode_record, WalRecordKind};
+use crate::wire::RlpCodec;
+use crate::{Codec, ConsensusResult};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Display, Eq, PartialEq)]
 #[rustfmt::skip]
@@ -23,9 +28,34 @@ pub struct WalInfo<T: Codec> {
     pub lock:   Option<WalLock<T>>,
     /// from
     pub from:   UpdateFrom,
+    /// The status applied at the height, which is `None` in the records saved before it is
+    /// persisted.
+    #[serde(default)]
+    pub status: Option<Status>,
+    /// The precommit QC and the block of the previous height whose commit is still executing in
+    /// pipelined mode. It is delivered to the application again on recovery.
+    #[serde(default = "Option::default")]
+    pub pending_commit: Option<WalLock<T>>,
 }
 
 impl<T: Codec> WalInfo<T> {
+    /// Encode the wal info into a wal record of the current version, whose payload is rlp.
+    pub fn to_record(&self) -> ConsensusResult<Bytes> {
+        encode_record(self, &RlpCodec, &[])
+    }
+
+    /// Encode the wal info into a wal record of the current version tagged with the run id,
+    /// whose payload is rlp.
+    pub fn to_tagged_record(&self, run_id: &[u8]) -> ConsensusResult<Bytes> {
+        encode_record(self, &RlpCodec, run_id)
+    }
+
+    /// Decode a wal info from a wal record of the current version or a legacy one, whose
+    /// payload is rlp. The run id of the record is not checked.
+    pub fn from_record(record: &[u8]) -> Result<Self, String> {
+        decode_record(WalRecordKind::WalInfo, record, &RlpCodec, None, None)
+    }
+
     /// transfer WalInfo to SMRBase
     pub fn into_smr_base(self) -> SMRBase {
         SMRBase {
@@ -52,6 +82,89 @@ impl<T: Codec> WalLock<T> {
             hash: self.lock_votes.block_hash.clone(),
         }
     }
+
+    /// Check the lock against the height, round and step of the wal info that carries it. The
+    /// lock of a commit step is the precommit QC, and the lock of the other steps is the
+    /// prevote QC of the lock round.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn check(
+        &self,
+        height: u64,
+        round: u64,
+        step: &Step,
+    ) -> Result<(), String> {
+        let qc = &self.lock_votes;
+        if qc.height != height || qc.round != self.lock_round || self.lock_round > round
+        {
+            return Err(format!(
+                "lock of height {}, round {} in the wal info of height {}, round {}",
+                qc.height, qc.round, height, round
+            ));
+        }
+
+        let vote_type = if *step == Step::Commit {
+            VoteType::Precommit
+        } else {
+            VoteType::Prevote
+        };
+        if qc.vote_type != vote_type {
+            return Err(format!(
+                "{:?} QC as the lock of {:?} step",
+                qc.vote_type, step
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The record of the last message signed by the node. It is saved before the signature leaves
+/// the node, so that a restarted node refuses to sign a conflicting message.
+#[derive(Serialize, Deserialize, Clone, Debug, Display, PartialEq, Eq)]
+#[display(
+    fmt = "last signed height {}, round {}, step {:?}",
+    height,
+    round,
+    step
+)]
+pub struct LastSigned {
+    /// The height of the signed message.
+    pub height: u64,
+    /// The round of the signed message.
+    pub round: u64,
+    /// The step of the signed message, `Brake` for a choke.
+    pub step: Step,
+    /// The hash of the signed message.
+    #[serde(with = "crate::serde_hex")]
+    pub hash: Hash,
+}
+
+impl LastSigned {
+    /// Check whether the node can sign the given message after the last signed one. A message
+    /// of the same height, round and step is allowed only if it is the same message, and a
+    /// message of an earlier height, round or step is never allowed. Return whether the record
+    /// needs to be updated.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn check(&self, next: &LastSigned) -> Result<bool, String> {
+        let last = (self.height, self.round, &self.step);
+        match (next.height, next.round, &next.step).cmp(&last) {
+            std::cmp::Ordering::Greater => Ok(true),
+            std::cmp::Ordering::Equal if next.hash == self.hash => Ok(false),
+            std::cmp::Ordering::Equal => Err(format!("conflict with the {}", self)),
+            std::cmp::Ordering::Less => Err(format!("behind the {}", self)),
+        }
+    }
+
+    /// The round that the given height is restarted from, which is the given round or the one
+    /// after the last signed round of the height, so that the restarted rounds never sign a
+    /// message behind or in conflict with the last signed one.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn restart_round(&self, height: u64, round: u64) -> u64 {
+        if self.height == height {
+            round.max(self.round + 1)
+        } else {
+            round
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -64,13 +177,13 @@ pub struct SMRBase {
 
 #[cfg(test)]
 mod test {
-    use std::error::Error;
+    use std::convert::Infallible;
 
     use bytes::Bytes;
     use rand::random;
 
     use super::*;
-    use crate::types::{AggregatedSignature, VoteType};
+    use crate::types::{Address, AggregatedSignature, VoteType};
 
     #[derive(Clone, Debug, PartialEq, Eq)]
     struct Pill {
@@ -78,13 +191,15 @@ mod test {
     }
 
     impl Codec for Pill {
-        fn encode(&self) -> Result<Bytes, Box<dyn Error + Send>> {
+        type Error = Infallible;
+
+        fn encode(&self) -> Result<Bytes, Self::Error> {
             Ok(Bytes::from(self.inner.clone()))
         }
 
-        fn decode(data: Bytes) -> Result<Self, Box<dyn Error + Send>> {
+        fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
             Ok(Pill {
-                inner: data.as_ref().to_vec(),
+                inner: data.to_vec(),
             })
         }
     }
@@ -108,8 +223,9 @@ mod test {
             vote_type: VoteType::Precommit,
             height: 0u64,
             round: 0u64,
-            block_hash: Bytes::default(),
-            leader: Bytes::default(),
+            block_hash: Hash::default(),
+            leader: Address::default(),
+            extensions: Vec::new(),
         }
     }
 
@@ -128,6 +244,8 @@ mod test {
             step: Step::Propose,
             lock: Some(wal_lock),
             from: UpdateFrom::PrecommitQC(mock_qc()),
+            status: None,
+            pending_commit: None,
         };
 
         assert_eq!(
@@ -135,4 +253,72 @@ mod test {
             "wal info height 0, round 0, step Propose"
         );
     }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_lock_check() {
+        let mut qc = mock_qc();
+        qc.height = 1;
+        qc.round = 2;
+        qc.vote_type = VoteType::Prevote;
+        let lock = WalLock {
+            lock_round: 2,
+            lock_votes: qc,
+            content: Pill::new(),
+        };
+
+        assert!(lock.check(1, 2, &Step::Propose).is_ok());
+        assert!(lock.check(1, 3, &Step::Precommit).is_ok());
+        assert!(lock.check(1, 2, &Step::Commit).is_err());
+        assert!(lock.check(2, 3, &Step::Propose).is_err());
+        assert!(lock.check(1, 1, &Step::Propose).is_err());
+
+        let mut lock = lock;
+        lock.lock_votes.vote_type = VoteType::Precommit;
+        assert!(lock.check(1, 2, &Step::Prevote).is_err());
+        assert!(lock.check(1, 2, &Step::Commit).is_ok());
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_last_signed() {
+        let record = |round: u64, step: Step, hash: &'static [u8]| LastSigned {
+            height: 1,
+            round,
+            step,
+            hash: Hash::from_slice(hash),
+        };
+        let last = record(1, Step::Prevote, b"a");
+
+        assert_eq!(last.check(&record(1, Step::Prevote, b"a")), Ok(false));
+        assert!(last.check(&record(1, Step::Prevote, b"b")).is_err());
+        assert!(last.check(&record(1, Step::Propose, b"a")).is_err());
+        assert!(last.check(&record(0, Step::Brake, b"a")).is_err());
+        assert_eq!(last.check(&record(1, Step::Precommit, b"b")), Ok(true));
+        assert_eq!(last.check(&record(2, Step::Propose, b"b")), Ok(true));
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_restart_round() {
+        let last = LastSigned {
+            height: 2,
+            round: 3,
+            step: Step::Prevote,
+            hash: Hash::from_slice(b"a"),
+        };
+
+        // A prevote at a round after the first one is never followed by an earlier round.
+        assert_eq!(last.restart_round(2, 0), 4);
+        assert_eq!(last.restart_round(2, 3), 4);
+        assert_eq!(last.restart_round(2, 5), 5);
+        assert!(last.check(&LastSigned {
+            height: 2,
+            round: last.restart_round(2, 0),
+            step: Step::Propose,
+            hash: Hash::from_slice(b"b"),
+        })
+        .is_ok());
+        assert_eq!(last.restart_round(3, 0), 0);
+    }
 }
@@ -0,0 +1,401 @@
+/// The protobuf encoding of the signature preimages.
+#[cfg(feature = "wire-protobuf")]
+mod protobuf;
+
+#[cfg(feature = "wire-protobuf")]
+pub use self::protobuf::ProtobufCodec;
+
+use std::fmt::Debug;
+
+use bytes::Bytes;
+#[cfg(feature = "wire-bincode")]
+use serde::Serialize;
+
+use crate::canonical;
+use crate::error::{ConsensusError, ErrorKind};
+use crate::types::{Heartbeat, KeyRotation, Proposal, Vote};
+use crate::wal::{LastSigned, WalInfo};
+use crate::{Codec, ConsensusResult};
+
+/// The encoding of the preimages of the signatures and the payloads of the wal records. The
+/// signatures of the proposals, the votes and the chokes are made over the hash of their
+/// preimages, so the nodes of a chain must use the same encoding. It is `RlpCodec` by default,
+/// and a chain standardizing on another encoding sets its own, so the signed bytes are the
+/// ones that the other components of the chain produce.
+///
+/// The wal payloads are rlp unless the codec overrides them. A `RocksWal` keeps the history of
+/// the heights by the rlp payloads, so it keeps no history of the payloads of the others.
+///
+/// A codec that fails to encode a value returns `EncodeErr`, and a block that fails to encode
+/// is kept as the source of an internal error. Either fails the step that signs, verifies or
+/// saves the value.
+pub trait WireCodec: Debug + Send + Sync {
+    /// The preimage of the signature of a proposal, whose block is encoded by its `Codec`.
+    fn proposal_preimage(&self, proposal: &Proposal<Bytes>) -> ConsensusResult<Bytes>;
+
+    /// The preimage of the signature of a vote, which is also the preimage of the aggregated
+    /// signature of a QC of the vote.
+    fn vote_preimage(&self, vote: &Vote) -> ConsensusResult<Bytes>;
+
+    /// The preimage of the signature of a choke of the height and the round, which is also
+    /// the preimage of the aggregated signature of a timeout certificate.
+    fn choke_preimage(&self, height: u64, round: u64) -> ConsensusResult<Bytes>;
+
+    /// The preimage of the signature of the extension of a vote, which binds the payload to
+    /// the vote. It is rlp unless the codec overrides it.
+    fn vote_extension_preimage(
+        &self,
+        vote: &Vote,
+        extension: &[u8],
+    ) -> ConsensusResult<Bytes> {
+        Ok(canonical::vote_extension_preimage(vote, extension))
+    }
+
+    /// The preimage of the signature of a heartbeat, which is rlp unless the codec overrides
+    /// it.
+    fn heartbeat_preimage(&self, heartbeat: &Heartbeat) -> ConsensusResult<Bytes> {
+        Ok(canonical::heartbeat_preimage(heartbeat))
+    }
+
+    /// The preimage of the signature of a key rotation, which is rlp unless the codec
+    /// overrides it.
+    fn key_rotation_preimage(&self, rotation: &KeyRotation) -> ConsensusResult<Bytes> {
+        Ok(canonical::key_rotation_preimage(rotation))
+    }
+
+    /// The input of the VRF of the leader election of the height and the round, which is rlp
+    /// unless the codec overrides it.
+    fn vrf_input(&self, height: u64, round: u64, seed: &[u8]) -> ConsensusResult<Bytes> {
+        Ok(canonical::vrf_input(height, round, seed))
+    }
+
+    /// Encode the payload of a wal info record, whose locked block is encoded by its `Codec`.
+    fn encode_wal_info(&self, info: &WalInfo<Bytes>) -> ConsensusResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(info)))
+    }
+
+    /// Decode the payload of a wal info record.
+    fn decode_wal_info(&self, payload: &[u8]) -> Result<WalInfo<Bytes>, String> {
+        rlp::decode(payload).map_err(|e| format!("{:?}", e))
+    }
+
+    /// Encode the payload of a last signed record.
+    fn encode_last_signed(&self, last_signed: &LastSigned) -> ConsensusResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(last_signed)))
+    }
+
+    /// Decode the payload of a last signed record.
+    fn decode_last_signed(&self, payload: &[u8]) -> Result<LastSigned, String> {
+        rlp::decode(payload).map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// The rlp encoding, which is the encoding of the signatures and the wal before the codec is
+/// pluggable.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RlpCodec;
+
+impl WireCodec for RlpCodec {
+    fn proposal_preimage(&self, proposal: &Proposal<Bytes>) -> ConsensusResult<Bytes> {
+        Ok(Bytes::from(rlp::encode(proposal)))
+    }
+
+    fn vote_preimage(&self, vote: &Vote) -> ConsensusResult<Bytes> {
+        Ok(canonical::vote_preimage(vote))
+    }
+
+    fn choke_preimage(&self, height: u64, round: u64) -> ConsensusResult<Bytes> {
+        Ok(canonical::choke_preimage(height, round))
+    }
+}
+
+/// The bincode encoding of the serde representation of the types, for both the signature
+/// preimages and the wal payloads. The choke preimage is the tuple of the height and the round.
+#[cfg(feature = "wire-bincode")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "wire-bincode")]
+impl BincodeCodec {
+    fn encode<S: Serialize>(item: &S) -> ConsensusResult<Bytes> {
+        bincode::serialize(item)
+            .map(Bytes::from)
+            .map_err(|e| ConsensusError::EncodeErr(e.to_string()))
+    }
+}
+
+#[cfg(feature = "wire-bincode")]
+impl WireCodec for BincodeCodec {
+    fn proposal_preimage(&self, proposal: &Proposal<Bytes>) -> ConsensusResult<Bytes> {
+        Self::encode(proposal)
+    }
+
+    fn vote_preimage(&self, vote: &Vote) -> ConsensusResult<Bytes> {
+        Self::encode(vote)
+    }
+
+    fn choke_preimage(&self, height: u64, round: u64) -> ConsensusResult<Bytes> {
+        Self::encode(&(height, round))
+    }
+
+    fn vote_extension_preimage(
+        &self,
+        vote: &Vote,
+        extension: &[u8],
+    ) -> ConsensusResult<Bytes> {
+        Self::encode(&(vote, extension))
+    }
+
+    fn heartbeat_preimage(&self, heartbeat: &Heartbeat) -> ConsensusResult<Bytes> {
+        Self::encode(heartbeat)
+    }
+
+    fn key_rotation_preimage(&self, rotation: &KeyRotation) -> ConsensusResult<Bytes> {
+        Self::encode(rotation)
+    }
+
+    fn vrf_input(&self, height: u64, round: u64, seed: &[u8]) -> ConsensusResult<Bytes> {
+        Self::encode(&(height, round, seed))
+    }
+
+    fn encode_wal_info(&self, info: &WalInfo<Bytes>) -> ConsensusResult<Bytes> {
+        Self::encode(info)
+    }
+
+    fn decode_wal_info(&self, payload: &[u8]) -> Result<WalInfo<Bytes>, String> {
+        bincode::deserialize(payload).map_err(|e| e.to_string())
+    }
+
+    fn encode_last_signed(&self, last_signed: &LastSigned) -> ConsensusResult<Bytes> {
+        Self::encode(last_signed)
+    }
+
+    fn decode_last_signed(&self, payload: &[u8]) -> Result<LastSigned, String> {
+        bincode::deserialize(payload).map_err(|e| e.to_string())
+    }
+}
+
+/// The preimage of the signature of a proposal by the codec, which fails if the block fails
+/// to encode.
+pub(crate) fn proposal_preimage<T: Codec>(
+    codec: &dyn WireCodec,
+    proposal: &Proposal<T>,
+) -> ConsensusResult<Bytes> {
+    let content = proposal.content.encode().map_err(|e| {
+        ConsensusError::with_source(ErrorKind::Internal, "Encode block", e)
+    })?;
+    codec.proposal_preimage(&Proposal {
+        height: proposal.height,
+        round: proposal.round,
+        content,
+        block_hash: proposal.block_hash.clone(),
+        lock: proposal.lock.clone(),
+        proposer: proposal.proposer.clone(),
+        timestamp: proposal.timestamp,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::smr::smr_types::Step;
+    use crate::types::{
+        Address, AggregatedSignature, AggregatedVote, Hash, HashChoke, PoLC, VoteType,
+    };
+
+    fn gen_vote() -> Vote {
+        Vote {
+            height: 10,
+            round: 1,
+            vote_type: VoteType::Precommit,
+            block_hash: Hash::from(vec![7u8; 32]),
+        }
+    }
+
+    fn gen_proposal() -> Proposal<Bytes> {
+        Proposal {
+            height: 10,
+            round: 1,
+            content: Bytes::from(vec![1, 2, 3]),
+            block_hash: Hash::from(vec![7u8; 32]),
+            lock: Some(PoLC {
+                lock_round: 0,
+                lock_votes: AggregatedVote {
+                    signature: AggregatedSignature {
+                        signature: Bytes::from(vec![9u8; 96]),
+                        address_bitmap: Bytes::from(vec![0b1110_0000]),
+                    },
+                    vote_type: VoteType::Prevote,
+                    height: 10,
+                    round: 0,
+                    block_hash: Hash::from(vec![7u8; 32]),
+                    leader: Address::from_slice(&[5u8; 20]).unwrap(),
+                    extensions: Vec::new(),
+                },
+            }),
+            proposer: Address::from_slice(&[5u8; 20]).unwrap(),
+            timestamp: None,
+        }
+    }
+
+    /// The preimages of the default codec are the rlp encodings that the signatures are made
+    /// over before the codec is pluggable.
+    #[test]
+    fn test_rlp_preimages() {
+        let codec = RlpCodec;
+        let vote = gen_vote();
+        assert_eq!(codec.vote_preimage(&vote).unwrap(), rlp::encode(&vote));
+
+        let proposal = gen_proposal();
+        assert_eq!(
+            proposal_preimage(&codec, &proposal).unwrap(),
+            rlp::encode(&proposal)
+        );
+
+        let choke = HashChoke {
+            height: 10,
+            round: 2,
+        };
+        assert_eq!(codec.choke_preimage(10, 2).unwrap(), rlp::encode(&choke));
+
+        let last_signed = LastSigned {
+            height: 10,
+            round: 1,
+            step: Step::Precommit,
+            hash: Hash::from(vec![7u8; 32]),
+        };
+        let payload = codec.encode_last_signed(&last_signed).unwrap();
+        assert_eq!(payload, rlp::encode(&last_signed));
+        assert_eq!(codec.decode_last_signed(&payload).unwrap(), last_signed);
+        assert!(codec.decode_last_signed(&[0xc1]).is_err());
+    }
+
+    /// The block fails to encode, so the preimage of its proposal fails with the error of the
+    /// block as the source.
+    #[test]
+    fn test_encode_error() {
+        use std::error::Error;
+        use std::io;
+
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        struct Block;
+
+        impl Codec for Block {
+            type Error = io::Error;
+
+            fn encode(&self) -> Result<Bytes, Self::Error> {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "unencodable"))
+            }
+
+            fn decode_ref(_data: &[u8]) -> Result<Self, Self::Error> {
+                Ok(Block)
+            }
+        }
+
+        let proposal = gen_proposal();
+        let proposal = Proposal {
+            content: Block,
+            height: proposal.height,
+            round: proposal.round,
+            block_hash: proposal.block_hash,
+            lock: proposal.lock,
+            proposer: proposal.proposer,
+            timestamp: proposal.timestamp,
+        };
+        let err = proposal_preimage(&RlpCodec, &proposal).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Internal);
+        assert_eq!(err.source().unwrap().to_string(), "unencodable");
+    }
+
+    #[cfg(feature = "wire-bincode")]
+    #[test]
+    fn test_bincode_encode_error() {
+        struct Unencodable;
+
+        impl Serialize for Unencodable {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("unencodable"))
+            }
+        }
+
+        let err = BincodeCodec::encode(&Unencodable).unwrap_err();
+        assert!(matches!(err, ConsensusError::EncodeErr(_)));
+        assert_eq!(err.kind(), ErrorKind::Internal);
+    }
+
+    #[cfg(feature = "wire-bincode")]
+    #[test]
+    fn test_bincode_codec() {
+        use std::time::Duration;
+
+        use crate::types::{Node, Status, UpdateFrom};
+        use crate::wal::WalLock;
+
+        let codec = BincodeCodec;
+        assert_ne!(
+            codec.vote_preimage(&gen_vote()).unwrap(),
+            rlp::encode(&gen_vote())
+        );
+        assert_ne!(
+            codec.choke_preimage(10, 1).unwrap(),
+            codec.choke_preimage(10, 2).unwrap()
+        );
+
+        let proposal = gen_proposal();
+        let preimage = codec.proposal_preimage(&proposal).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Proposal<Bytes>>(&preimage).unwrap(),
+            proposal
+        );
+
+        let last_signed = LastSigned {
+            height: 10,
+            round: 1,
+            step: Step::Brake,
+            hash: Hash::from(vec![7u8; 32]),
+        };
+        let payload = codec.encode_last_signed(&last_signed).unwrap();
+        assert_eq!(codec.decode_last_signed(&payload).unwrap(), last_signed);
+        assert!(codec.decode_last_signed(&payload[..4]).is_err());
+
+        let lock_votes = gen_proposal().lock.unwrap().lock_votes;
+        let info = WalInfo {
+            height: 10,
+            round: 1,
+            step: Step::Precommit,
+            lock: Some(WalLock {
+                lock_round: 0,
+                lock_votes: lock_votes.clone(),
+                content: Bytes::from(vec![1, 2, 3]),
+            }),
+            from: UpdateFrom::PrevoteQC(lock_votes.clone()),
+            status: Some(Status {
+                height: 10,
+                interval: Some(Duration::from_millis(3000)),
+                timer_config: None,
+                authority_list: vec![Node::new(
+                    Address::from_slice(&[5u8; 20]).unwrap(),
+                )],
+                next_authority_list: None,
+                proposer_seed: None,
+                skipped_proposers: Vec::new(),
+            }),
+            pending_commit: None,
+        };
+        let payload = codec.encode_wal_info(&info).unwrap();
+        assert_eq!(codec.decode_wal_info(&payload).unwrap(), info);
+
+        let info = WalInfo {
+            pending_commit: Some(WalLock {
+                lock_round: 2,
+                lock_votes,
+                content: Bytes::from(vec![4, 5, 6]),
+            }),
+            ..info
+        };
+        let payload = codec.encode_wal_info(&info).unwrap();
+        assert_eq!(codec.decode_wal_info(&payload).unwrap(), info);
+    }
+}
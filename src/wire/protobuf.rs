@@ -0,0 +1,297 @@
+use bytes::Bytes;
+use prost::Message;
+
+#[cfg(feature = "fuzzing")]
+use crate::types::{Address, AggregatedSignature, Hash};
+use crate::types::{AggregatedVote, PoLC, Proposal, Vote, VoteType};
+use crate::wire::WireCodec;
+use crate::ConsensusResult;
+
+/// The protobuf encoding of the signature preimages, of the schema below. The vote type is 1
+/// for prevote and 2 for precommit as in rlp, and the content of a proposal is the block
+/// encoded by its `Codec`. The wal payloads are rlp.
+///
+/// ```text
+/// message Vote {
+///     uint64 height = 1;
+///     uint64 round = 2;
+///     uint32 vote_type = 3;
+///     bytes block_hash = 4;
+/// }
+///
+/// message Choke {
+///     uint64 height = 1;
+///     uint64 round = 2;
+/// }
+///
+/// message AggregatedVote {
+///     bytes signature = 1;
+///     bytes address_bitmap = 2;
+///     uint32 vote_type = 3;
+///     uint64 height = 4;
+///     uint64 round = 5;
+///     bytes block_hash = 6;
+///     bytes leader = 7;
+/// }
+///
+/// message PoLC {
+///     uint64 lock_round = 1;
+///     AggregatedVote lock_votes = 2;
+/// }
+///
+/// message Proposal {
+///     uint64 height = 1;
+///     uint64 round = 2;
+///     bytes content = 3;
+///     bytes block_hash = 4;
+///     PoLC lock = 5;
+///     bytes proposer = 6;
+///     optional uint64 timestamp = 7;
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProtobufCodec;
+
+impl WireCodec for ProtobufCodec {
+    fn proposal_preimage(&self, proposal: &Proposal<Bytes>) -> ConsensusResult<Bytes> {
+        let message = ProposalMessage {
+            height: proposal.height,
+            round: proposal.round,
+            content: proposal.content.clone(),
+            block_hash: proposal.block_hash.as_bytes().clone(),
+            lock: proposal.lock.as_ref().map(PoLCMessage::from),
+            proposer: proposal.proposer.as_bytes().clone(),
+            timestamp: proposal.timestamp,
+        };
+        Ok(Bytes::from(message.encode_to_vec()))
+    }
+
+    fn vote_preimage(&self, vote: &Vote) -> ConsensusResult<Bytes> {
+        let message = VoteMessage {
+            height: vote.height,
+            round: vote.round,
+            vote_type: vote_type(&vote.vote_type),
+            block_hash: vote.block_hash.as_bytes().clone(),
+        };
+        Ok(Bytes::from(message.encode_to_vec()))
+    }
+
+    fn choke_preimage(&self, height: u64, round: u64) -> ConsensusResult<Bytes> {
+        Ok(Bytes::from(ChokeMessage { height, round }.encode_to_vec()))
+    }
+}
+
+fn vote_type(vote_type: &VoteType) -> u32 {
+    u8::from(vote_type.clone()).into()
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct VoteMessage {
+    #[prost(uint64, tag = "1")]
+    height: u64,
+    #[prost(uint64, tag = "2")]
+    round: u64,
+    #[prost(uint32, tag = "3")]
+    vote_type: u32,
+    #[prost(bytes = "bytes", tag = "4")]
+    block_hash: Bytes,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ChokeMessage {
+    #[prost(uint64, tag = "1")]
+    height: u64,
+    #[prost(uint64, tag = "2")]
+    round: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct AggregatedVoteMessage {
+    #[prost(bytes = "bytes", tag = "1")]
+    signature: Bytes,
+    #[prost(bytes = "bytes", tag = "2")]
+    address_bitmap: Bytes,
+    #[prost(uint32, tag = "3")]
+    vote_type: u32,
+    #[prost(uint64, tag = "4")]
+    height: u64,
+    #[prost(uint64, tag = "5")]
+    round: u64,
+    #[prost(bytes = "bytes", tag = "6")]
+    block_hash: Bytes,
+    #[prost(bytes = "bytes", tag = "7")]
+    leader: Bytes,
+}
+
+impl From<&AggregatedVote> for AggregatedVoteMessage {
+    fn from(qc: &AggregatedVote) -> Self {
+        AggregatedVoteMessage {
+            signature: qc.signature.signature.clone(),
+            address_bitmap: qc.signature.address_bitmap.clone(),
+            vote_type: vote_type(&qc.vote_type),
+            height: qc.height,
+            round: qc.round,
+            block_hash: qc.block_hash.as_bytes().clone(),
+            leader: qc.leader.as_bytes().clone(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PoLCMessage {
+    #[prost(uint64, tag = "1")]
+    lock_round: u64,
+    #[prost(message, optional, tag = "2")]
+    lock_votes: Option<AggregatedVoteMessage>,
+}
+
+impl From<&PoLC> for PoLCMessage {
+    fn from(polc: &PoLC) -> Self {
+        PoLCMessage {
+            lock_round: polc.lock_round,
+            lock_votes: Some(AggregatedVoteMessage::from(&polc.lock_votes)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProposalMessage {
+    #[prost(uint64, tag = "1")]
+    height: u64,
+    #[prost(uint64, tag = "2")]
+    round: u64,
+    #[prost(bytes = "bytes", tag = "3")]
+    content: Bytes,
+    #[prost(bytes = "bytes", tag = "4")]
+    block_hash: Bytes,
+    #[prost(message, optional, tag = "5")]
+    lock: Option<PoLCMessage>,
+    #[prost(bytes = "bytes", tag = "6")]
+    proposer: Bytes,
+    #[prost(uint64, optional, tag = "7")]
+    timestamp: Option<u64>,
+}
+
+/// The decoding of the preimages, which is the inverse of the encoding for the differential
+/// fuzzing of the codecs. A QC decodes without the vote extensions, which are not in the
+/// schema.
+#[cfg(feature = "fuzzing")]
+impl ProtobufCodec {
+    pub(crate) fn decode_vote(preimage: &[u8]) -> Option<Vote> {
+        let message = VoteMessage::decode(preimage).ok()?;
+        Some(Vote {
+            height: message.height,
+            round: message.round,
+            vote_type: decode_vote_type(message.vote_type)?,
+            block_hash: Hash::from(message.block_hash),
+        })
+    }
+
+    pub(crate) fn decode_proposal(preimage: &[u8]) -> Option<Proposal<Bytes>> {
+        let message = ProposalMessage::decode(preimage).ok()?;
+        let lock = match message.lock {
+            Some(polc) => Some(PoLC {
+                lock_round: polc.lock_round,
+                lock_votes: decode_qc(polc.lock_votes?)?,
+            }),
+            None => None,
+        };
+        Some(Proposal {
+            height: message.height,
+            round: message.round,
+            content: message.content,
+            block_hash: Hash::from(message.block_hash),
+            lock,
+            proposer: Address::new(message.proposer).ok()?,
+            timestamp: message.timestamp,
+        })
+    }
+}
+
+#[cfg(feature = "fuzzing")]
+fn decode_vote_type(vote_type: u32) -> Option<VoteType> {
+    VoteType::try_from(u8::try_from(vote_type).ok()?).ok()
+}
+
+#[cfg(feature = "fuzzing")]
+fn decode_qc(message: AggregatedVoteMessage) -> Option<AggregatedVote> {
+    // The initial QC has no leader, as in rlp.
+    let leader = if message.leader.is_empty() {
+        Address::default()
+    } else {
+        Address::new(message.leader).ok()?
+    };
+    Some(AggregatedVote {
+        signature: AggregatedSignature {
+            signature: message.signature,
+            address_bitmap: message.address_bitmap,
+        },
+        vote_type: decode_vote_type(message.vote_type)?,
+        height: message.height,
+        round: message.round,
+        block_hash: Hash::from(message.block_hash),
+        leader,
+        extensions: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::types::{Address, Hash};
+
+    #[test]
+    fn test_protobuf_preimages() {
+        let codec = ProtobufCodec;
+        let vote = Vote {
+            height: 1,
+            round: 2,
+            vote_type: VoteType::Precommit,
+            block_hash: Hash::from(vec![0xab, 0xcd]),
+        };
+        // The fields of the tags 1 to 4 in order.
+        assert_eq!(
+            codec.vote_preimage(&vote).unwrap().as_ref(),
+            &[0x08, 0x01, 0x10, 0x02, 0x18, 0x02, 0x22, 0x02, 0xab, 0xcd]
+        );
+        assert_eq!(
+            codec.choke_preimage(1, 300).unwrap().as_ref(),
+            &[0x08, 0x01, 0x10, 0xac, 0x02]
+        );
+
+        let proposal = Proposal {
+            height: 1,
+            round: 0,
+            content: Bytes::from(vec![1, 2, 3]),
+            block_hash: Hash::from(vec![0xab]),
+            lock: None,
+            proposer: Address::from_slice(&[0xff]).unwrap(),
+            timestamp: None,
+        };
+        // The round of zero and the empty lock are omitted.
+        assert_eq!(
+            codec.proposal_preimage(&proposal).unwrap().as_ref(),
+            &[
+                0x08, 0x01, 0x1a, 0x03, 1, 2, 3, 0x22, 0x01, 0xab, 0x32, 0x01, 0xff
+            ]
+        );
+        let decoded =
+            ProposalMessage::decode(codec.proposal_preimage(&proposal).unwrap())
+                .unwrap();
+        assert_eq!(decoded.content, proposal.content);
+
+        // The timestamp is the field of the tag 7.
+        let stamped = Proposal {
+            timestamp: Some(1),
+            ..proposal
+        };
+        assert!(
+            codec
+                .proposal_preimage(&stamped)
+                .unwrap()
+                .ends_with(&[0x38, 0x01])
+        );
+    }
+}
@@ -1,31 +1,37 @@
 use super::utils::hash;
+use async_trait::async_trait;
 use bytes::Bytes;
-use mlm::Crypto;
+use mlm::types::{Address, AggregatedSignature, Hash};
+use mlm::{AsyncCrypto, Crypto};
 use std::error::Error;
+use std::time::Duration;
+use tokio::time::sleep;
 
 pub struct MockCrypto {
-    name: Bytes,
+    name: Address,
 }
 
 impl MockCrypto {
-    pub fn new(name: Bytes) -> Self {
+    pub fn new(name: Address) -> Self {
         MockCrypto { name }
     }
 }
 
 impl Crypto for MockCrypto {
-    fn hash(&self, speech: Bytes) -> Bytes {
+    type QcSignature = AggregatedSignature;
+
+    fn hash(&self, speech: Bytes) -> Hash {
         hash(&speech)
     }
 
-    fn sign(&self, _hash: Bytes) -> Result<Bytes, Box<dyn Error + Send>> {
-        Ok(self.name.clone())
+    fn sign(&self, _hash: Hash) -> Result<Bytes, Box<dyn Error + Send>> {
+        Ok(self.name.as_bytes().clone())
     }
 
     fn aggregate_signatures(
         &self,
         _signatures: Vec<Bytes>,
-        _speaker: Vec<Bytes>,
+        _speaker: Vec<Address>,
     ) -> Result<Bytes, Box<dyn Error + Send>> {
         Ok(Bytes::new())
     }
@@ -33,8 +39,8 @@ impl Crypto for MockCrypto {
     fn verify_signature(
         &self,
         _signature: Bytes,
-        _hash: Bytes,
-        _voter: Bytes,
+        _hash: Hash,
+        _voter: Address,
     ) -> Result<(), Box<dyn Error + Send>> {
         Ok(())
     }
@@ -42,9 +48,29 @@ impl Crypto for MockCrypto {
     fn verify_aggregated_signature(
         &self,
         _aggregated_signature: Bytes,
-        _hash: Bytes,
-        _voters: Vec<Bytes>,
+        _hash: Hash,
+        _voters: Vec<Address>,
     ) -> Result<(), Box<dyn Error + Send>> {
         Ok(())
     }
 }
+
+/// Sign by the name after a delay, as a remote signer does.
+#[derive(Debug)]
+pub struct MockAsyncCrypto {
+    name: Address,
+}
+
+impl MockAsyncCrypto {
+    pub fn new(name: Address) -> Self {
+        MockAsyncCrypto { name }
+    }
+}
+
+#[async_trait]
+impl AsyncCrypto for MockAsyncCrypto {
+    async fn sign(&self, _hash: Hash) -> Result<Bytes, Box<dyn Error + Send>> {
+        sleep(Duration::from_millis(10)).await;
+        Ok(self.name.as_bytes().clone())
+    }
+}
@@ -6,7 +6,11 @@ mod wal;
 
 // use std::fs;
 
-use run::run_test;
+use bytes::Bytes;
+use mlm::config::RelayerPolicy;
+use mlm::{MlmConfig, ThreadHints};
+
+use run::{run_test, run_test_with_config};
 use wal::Record;
 
 const TEST_CASE_DIR: &str = "./tests/integration_tests/test_case/";
@@ -27,6 +31,109 @@ async fn test_4_wal() {
     run_test(Record::new(4, 10), 1, 10).await
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_pipelined() {
+    let mut config = MlmConfig::default();
+    config.set_pipelined(true);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_vote_resend() {
+    let mut config = MlmConfig::default();
+    config.set_vote_resend_ratio(5);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_relayer_fallback() {
+    let mut config = MlmConfig::default();
+    config.set_relayer_fallback_ratio(3);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_future_buffer() {
+    let mut config = MlmConfig::default();
+    config.set_future_buffer(16, 256);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_rate_limit() {
+    let mut config = MlmConfig::default();
+    config.set_rate_limit(100, 50);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_height_window() {
+    let mut config = MlmConfig::default();
+    config.set_height_window(8, true);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_run_id() {
+    let mut config = MlmConfig::default();
+    config.set_run_id(Bytes::from_static(b"run-4"));
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_backup_relayers() {
+    let mut config = MlmConfig::default();
+    config.set_backup_relayers(2, RelayerPolicy::NextProposers);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_gossip_qc() {
+    let mut config = MlmConfig::default();
+    config.set_gossip_qc(true);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_thread_hints() {
+    let mut config = MlmConfig::default();
+    config.set_thread_hints(ThreadHints {
+        threads: 2,
+        cores: vec![0, 1],
+        nice: 0,
+    });
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_async_crypto() {
+    let mut config = MlmConfig::default();
+    config.set_sign_timeout(500);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_external_aggregation() {
+    let mut config = MlmConfig::default();
+    config.set_external_aggregation(true);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_round_caps() {
+    let mut config = MlmConfig::default();
+    config.set_round_caps(1, 8);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
+#[cfg(feature = "multi_proposal")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_4_multi_proposal() {
+    let mut config = MlmConfig::default();
+    config.set_multi_proposal(2, 0);
+    run_test_with_config(Record::new(4, 10), config, 1, 10).await
+}
+
 // #[tokio::test(flavor = "multi_thread")]
 // async fn test_21_wal() {
 //     // let _ = env_logger::builder().is_test(true).try_init();
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::error::Error;
-use std::sync::Arc;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -10,10 +13,16 @@ use crossbeam_channel::{Receiver, Sender};
 use serde::{Deserialize, Serialize};
 
 use mlm::error::ConsensusError;
-use mlm::types::{Commit, Hash, MlmMsg, Node, Status, ViewChangeReason};
-use mlm::{Codec, Consensus, DurationConfig, Mlm, MlmHandler};
-
-use super::crypto::MockCrypto;
+use mlm::replay::TraceRecorder;
+use mlm::types::{
+    Address, AggregatedSignature, Commit, Hash, MlmMsg, Node, SignedVote, Status,
+    ViewChangeReason,
+};
+use mlm::{
+    build_bitmap, Codec, Consensus, DurationConfig, Mlm, MlmConfig, MlmHandler, WalInfo,
+};
+
+use super::crypto::{MockAsyncCrypto, MockCrypto};
 use super::utils::{gen_random_bytes, hash, timer_config, to_hex};
 use super::wal::{MockWal, RECORD_TMP_FILE};
 use crate::integration_tests::wal::RecordInternal;
@@ -33,26 +42,34 @@ impl Block {
 }
 
 impl Codec for Block {
-    fn encode(&self) -> Result<Bytes, Box<dyn Error + Send>> {
+    type Error = Infallible;
+
+    fn encode(&self) -> Result<Bytes, Self::Error> {
         Ok(self.inner.clone())
     }
 
-    fn decode(data: Bytes) -> Result<Self, Box<dyn Error + Send>> {
+    fn decode(data: Bytes) -> Result<Self, Self::Error> {
         Ok(Block { inner: data })
     }
+
+    fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Block {
+            inner: Bytes::copy_from_slice(data),
+        })
+    }
 }
 
 pub struct Adapter {
-    pub address: Bytes, // address
-    pub talk_to: HashMap<Bytes, Sender<MlmMsg<Block>>>,
+    pub address: Address,
+    pub talk_to: HashMap<Address, Sender<MlmMsg<Block>>>,
     pub hearing: Receiver<MlmMsg<Block>>,
     pub records: RecordInternal,
 }
 
 impl Adapter {
     fn new(
-        address: Bytes,
-        talk_to: HashMap<Bytes, Sender<MlmMsg<Block>>>,
+        address: Address,
+        talk_to: HashMap<Address, Sender<MlmMsg<Block>>>,
         hearing: Receiver<MlmMsg<Block>>,
         records: RecordInternal,
     ) -> Adapter {
@@ -94,9 +111,12 @@ impl Consensus<Block> for Adapter {
     ) -> Result<Status, Box<dyn Error + Send>> {
         let status = Status {
             height: height + 1,
-            interval: Some(self.records.interval),
+            interval: Some(Duration::from_millis(self.records.interval)),
             timer_config: None,
             authority_list: self.records.node_record.clone(),
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: Vec::new(),
         };
 
         let commit_block_hash = hash(&commit.content.inner);
@@ -167,7 +187,7 @@ impl Consensus<Block> for Adapter {
     async fn transmit_to_relayer(
         &self,
         _ctx: Context,
-        address: Bytes,
+        address: Address,
         words: MlmMsg<Block>,
     ) -> Result<(), Box<dyn Error + Send>> {
         if let Some(sender) = self.talk_to.get(&address) {
@@ -176,6 +196,18 @@ impl Consensus<Block> for Adapter {
         Ok(())
     }
 
+    async fn aggregate_votes(
+        &self,
+        _ctx: Context,
+        votes: Vec<SignedVote>,
+    ) -> Result<AggregatedSignature, Box<dyn Error + Send>> {
+        let voters = votes.into_iter().map(|vote| vote.voter).collect::<Vec<_>>();
+        Ok(AggregatedSignature {
+            signature: Bytes::new(),
+            address_bitmap: build_bitmap(&self.records.node_record, &voters),
+        })
+    }
+
     fn report_error(&self, _ctx: Context, _err: ConsensusError) {}
 
     fn report_view_change(
@@ -192,14 +224,32 @@ pub struct Participant {
     pub mlm: Arc<Mlm<Block, Adapter, MockCrypto, MockWal>>,
     pub handler: MlmHandler<Block>,
     pub adapter: Arc<Adapter>,
+    // the wal that the node starts from, and the trace of its state machine since then
+    pub wal: Option<WalInfo<Block>>,
+    pub trace: SharedTrace,
+}
+
+#[derive(Clone, Default)]
+pub struct SharedTrace(pub Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedTrace {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 impl Participant {
     pub fn new(
-        address: &Bytes,
-        talk_to: HashMap<Bytes, Sender<MlmMsg<Block>>>,
+        address: &Address,
+        talk_to: HashMap<Address, Sender<MlmMsg<Block>>>,
         hearing: Receiver<MlmMsg<Block>>,
         records: RecordInternal,
+        config: MlmConfig,
     ) -> Self {
         let crypto = MockCrypto::new(address.clone());
         let adapter = Arc::new(Adapter::new(
@@ -208,12 +258,21 @@ impl Participant {
             hearing,
             records.clone(),
         ));
+        let wal = records.wal_record.get(address).unwrap().clone();
+        let wal_info = wal.wal_info();
         let mlm = Mlm::new(
             address.clone(),
             Arc::clone(&adapter),
             Arc::new(crypto),
-            Arc::new(records.wal_record.get(address).unwrap().clone()),
+            Arc::new(wal),
         );
+        let trace = SharedTrace::default();
+        mlm.set_trace_recorder(Arc::new(TraceRecorder::new(trace.clone())));
+        // The tests with a sign timeout sign by the async crypto.
+        if config.sign_timeout > 0 {
+            mlm.set_async_crypto(Arc::new(MockAsyncCrypto::new(address.clone())));
+        }
+        mlm.set_config(config);
         let mlm_handler = mlm.get_handler();
 
         mlm_handler
@@ -221,9 +280,12 @@ impl Participant {
                 Context::new(),
                 MlmMsg::RichStatus(Status {
                     height: 1,
-                    interval: Some(records.interval),
+                    interval: Some(Duration::from_millis(records.interval)),
                     timer_config: timer_config(),
                     authority_list: records.node_record,
+                    next_authority_list: None,
+                    proposer_seed: None,
+                    skipped_proposers: Vec::new(),
                 }),
             )
             .unwrap();
@@ -232,6 +294,8 @@ impl Participant {
             mlm: Arc::new(mlm),
             handler: mlm_handler,
             adapter,
+            wal: wal_info,
+            trace,
         }
     }
 
@@ -278,7 +342,7 @@ impl Participant {
         });
 
         self.mlm
-            .run(1, interval, node_list, timer_config)
+            .run_ms(1, interval, node_list, timer_config)
             .await
             .unwrap();
 
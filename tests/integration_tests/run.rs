@@ -3,17 +3,28 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use bytes::Bytes;
 use creep::Context;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 
-use mlm::types::{MlmMsg, Node, Status};
+use mlm::prelude::{Address, MlmConfig, MlmMsg, Node, Status};
+use mlm::replay::{read_trace, replay_from, TraceEntry};
+use mlm::SmrOutput;
 
 use super::primitive::{Block, Channel, Participant};
 use super::utils::{get_max_alive_height, timer_config, to_hex, to_hex_strings};
 use super::wal::{Record, RECORD_TMP_FILE};
 
 pub async fn run_test(records: Record, refresh_height: u64, test_height: u64) {
+    run_test_with_config(records, MlmConfig::default(), refresh_height, test_height)
+        .await
+}
+
+pub async fn run_test_with_config(
+    records: Record,
+    config: MlmConfig,
+    refresh_height: u64,
+    test_height: u64,
+) {
     let interval = records.interval;
     let start_height =
         get_max_alive_height(&records.height_record, &records.node_record);
@@ -38,7 +49,8 @@ pub async fn run_test(records: Record, refresh_height: u64, test_height: u64) {
 
         let height_start = get_max_alive_height(&records.height_record, &alive_nodes);
 
-        let (alive_handlers, senders) = run_alive_nodes(&records, alive_nodes.clone());
+        let (alive_handlers, senders) =
+            run_alive_nodes(&records, alive_nodes.clone(), &config);
         synchronize_height(
             &records,
             alive_nodes.clone(),
@@ -69,7 +81,8 @@ pub async fn run_test(records: Record, refresh_height: u64, test_height: u64) {
             test_id, height_start, height_end
         );
 
-        kill_alive_nodes(alive_handlers, senders);
+        kill_alive_nodes(alive_handlers.clone(), senders);
+        check_decisions(&alive_handlers, test_id);
 
         test_id += 1;
 
@@ -84,13 +97,14 @@ pub async fn run_test(records: Record, refresh_height: u64, test_height: u64) {
 fn run_alive_nodes(
     records: &Record,
     alive_nodes: Vec<Node>,
+    config: &MlmConfig,
 ) -> (Vec<Arc<Participant>>, Vec<Sender<MlmMsg<Block>>>) {
     let records = records.as_internal();
     let interval = records.interval;
     let alive_num = alive_nodes.len();
 
     let channels: Vec<Channel> = (0..alive_num).map(|_| unbounded()).collect();
-    let hearings: HashMap<Bytes, Receiver<MlmMsg<Block>>> = alive_nodes
+    let hearings: HashMap<Address, Receiver<MlmMsg<Block>>> = alive_nodes
         .iter()
         .map(|node| node.address.clone())
         .zip(channels.iter().map(|(_, receiver)| receiver.clone()))
@@ -99,7 +113,7 @@ fn run_alive_nodes(
     let mut alive_handlers = Vec::new();
     for node in alive_nodes.iter() {
         let address = node.address.clone();
-        let mut talk_to: HashMap<Bytes, Sender<MlmMsg<Block>>> = alive_nodes
+        let mut talk_to: HashMap<Address, Sender<MlmMsg<Block>>> = alive_nodes
             .iter()
             .map(|node| node.address.clone())
             .zip(channels.iter().map(|(sender, _)| sender.clone()))
@@ -111,6 +125,7 @@ fn run_alive_nodes(
             talk_to,
             hearings.get(&address).unwrap().clone(),
             records.clone(),
+            config.clone(),
         ));
 
         alive_handlers.push(Arc::<Participant>::clone(&node));
@@ -133,7 +148,8 @@ fn synchronize_height(
     test_id: u64,
 ) {
     let interval = records.interval;
-    let height_record = Arc::<Mutex<HashMap<Bytes, u64>>>::clone(&records.height_record);
+    let height_record =
+        Arc::<Mutex<HashMap<Address, u64>>>::clone(&records.height_record);
     let node_record = records.node_record.clone();
 
     tokio::spawn(async move {
@@ -144,7 +160,7 @@ fn synchronize_height(
             if *height < max_height {
                 alive_handlers
                     .iter()
-                    .filter(|node| node.adapter.address == address)
+                    .filter(|node| &node.adapter.address == address)
                     .for_each(|node| {
                         println!(
                             "Cycle {:?}, synchronize {:?} to node {:?} of height {:?}",
@@ -157,9 +173,12 @@ fn synchronize_height(
                             Context::new(),
                             MlmMsg::RichStatus(Status {
                                 height: max_height + 1,
-                                interval: Some(interval),
+                                interval: Some(Duration::from_millis(interval)),
                                 timer_config: timer_config(),
                                 authority_list: node_record.clone(),
+                                next_authority_list: None,
+                                proposer_seed: None,
+                                skipped_proposers: Vec::new(),
                             }),
                         );
                     });
@@ -174,8 +193,33 @@ fn kill_alive_nodes(
 ) {
     alive_handlers
         .iter()
-        .for_each(|node| node.handler.send_msg(Context::new(), MlmMsg::Stop).unwrap());
+        .for_each(|node| node.handler.stop().unwrap());
     senders
         .iter()
         .for_each(|sender| sender.send(MlmMsg::Stop).unwrap());
 }
+
+// replay the trace of each node from its wal, which must reach the same decisions as the node
+fn check_decisions(alive_handlers: &[Arc<Participant>], test_id: u64) {
+    for node in alive_handlers.iter() {
+        // the trace is complete once the state machine stops
+        let mut records = Vec::new();
+        for _ in 0..100 {
+            records = read_trace(&node.trace.0.lock().unwrap()).unwrap();
+            if matches!(records.last(), Some(record) if record.entry == TraceEntry::Output(SmrOutput::Stop))
+            {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        if let Err(divergence) = replay_from(node.wal.as_ref(), &records) {
+            panic!(
+                "Cycle {:?}, node {:?} replay from {:?}: {}",
+                test_id,
+                to_hex(&node.adapter.address),
+                node.wal,
+                divergence
+            );
+        }
+    }
+}
@@ -1,13 +1,13 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use bytes::{Bytes, BytesMut};
+use bytes::Bytes;
 use hasher::{Hasher, HasherKeccak};
 use hummer::coding::hex_encode;
 use lazy_static::lazy_static;
 use rand::{random, seq::SliceRandom, thread_rng};
 
-use mlm::types::Node;
+use mlm::types::{Address, Hash, Node};
 use mlm::DurationConfig;
 
 lazy_static! {
@@ -19,10 +19,14 @@ pub fn gen_random_bytes() -> Bytes {
     Bytes::from(vec)
 }
 
-pub fn hash(bytes: &Bytes) -> Bytes {
+pub fn gen_random_address() -> Address {
+    Address::new(gen_random_bytes()).unwrap()
+}
+
+pub fn hash(bytes: &Bytes) -> Hash {
     let mut out = [0u8; 32];
     out.copy_from_slice(&HASHER_INST.digest(bytes));
-    BytesMut::from(&out[..]).freeze()
+    Hash::from(out)
 }
 
 pub fn timer_config() -> Option<DurationConfig> {
@@ -43,15 +47,15 @@ pub fn create_alive_nodes(nodes: Vec<Node>) -> Vec<Node> {
 }
 
 pub fn get_max_alive_height(
-    height_record: &Arc<Mutex<HashMap<Bytes, u64>>>,
+    height_record: &Arc<Mutex<HashMap<Address, u64>>>,
     alives: &[Node],
 ) -> u64 {
     let height_record = height_record.lock().unwrap();
     if let Some(max_height) = height_record
         .clone()
         .into_iter()
-        .filter(|(address, _)| alives.iter().any(|node| node.address == address))
-        .collect::<HashMap<Bytes, u64>>()
+        .filter(|(address, _)| alives.iter().any(|node| &node.address == address))
+        .collect::<HashMap<Address, u64>>()
         .values()
         .max()
     {
@@ -65,7 +69,7 @@ pub fn to_hex_strings(nodes: &[Node]) -> Vec<String> {
     nodes.iter().map(|node| hex_encode(&node.address)).collect()
 }
 
-pub fn to_hex(address: &Bytes) -> String {
+pub fn to_hex(address: &Address) -> String {
     hex_encode(address)
 }
 
@@ -78,10 +82,10 @@ pub fn get_index_array(nodes: &[Node], alives: &[Node]) -> Vec<usize> {
         .collect()
 }
 
-pub fn get_index(nodes: &[Node], address: &Bytes) -> usize {
+pub fn get_index(nodes: &[Node], address: &Address) -> usize {
     let mut index = std::usize::MAX;
     nodes.iter().enumerate().for_each(|(i, node)| {
-        if node.address == address {
+        if &node.address == address {
             index = i;
         }
     });
@@ -7,14 +7,13 @@ use std::sync::{Arc, Mutex};
 use async_trait::async_trait;
 use bytes::Bytes;
 use lru_cache::LruCache;
-use rlp::Encodable;
 use serde::{Deserialize, Serialize};
 
-use mlm::types::Node;
+use mlm::types::{Address, Hash, Node};
 use mlm::{Wal, WalInfo};
 
 use super::primitive::Block;
-use super::utils::{create_alive_nodes, gen_random_bytes};
+use super::utils::{create_alive_nodes, gen_random_address, gen_random_bytes};
 use crate::integration_tests::utils::to_hex;
 
 pub const RECORD_TMP_FILE: &str = "./tests/integration_tests/test.json";
@@ -23,23 +22,31 @@ pub const RECORD_TMP_FILE: &str = "./tests/integration_tests/test.json";
 pub struct MockWal {
     test_id: u64,
     test_id_updated: Arc<Mutex<u64>>,
-    address: Bytes,
+    address: Address,
     content: Arc<Mutex<Option<Bytes>>>,
+    last_signed: Arc<Mutex<Option<Bytes>>>,
 }
 
 impl MockWal {
     pub fn new(
         test_id_updated: &Arc<Mutex<u64>>,
-        addr: Bytes,
+        addr: Address,
         content: &Arc<Mutex<Option<Bytes>>>,
+        last_signed: &Arc<Mutex<Option<Bytes>>>,
     ) -> MockWal {
         MockWal {
             test_id: *test_id_updated.lock().unwrap(),
             address: addr,
             test_id_updated: Arc::<Mutex<u64>>::clone(test_id_updated),
             content: Arc::<Mutex<Option<Bytes>>>::clone(content),
+            last_signed: Arc::<Mutex<Option<Bytes>>>::clone(last_signed),
         }
     }
+
+    pub fn wal_info(&self) -> Option<WalInfo<Block>> {
+        let info = self.content.lock().unwrap().as_ref().cloned();
+        info.map(|info| WalInfo::<Block>::from_record(&info).unwrap())
+    }
 }
 
 #[async_trait]
@@ -48,7 +55,7 @@ impl Wal for MockWal {
         let test_id_updated = *self.test_id_updated.lock().unwrap();
         // avoid previous test overwrite wal of the latest test
         if test_id_updated == self.test_id {
-            // let content: WalInfo<Block> = rlp::decode(&info).unwrap();
+            // let content = WalInfo::<Block>::from_record(&info).unwrap();
             // println!("{:?} save {:?}", to_hex(&self.address), content);
             *self.content.lock().unwrap() = Some(info);
         } else {
@@ -60,20 +67,31 @@ impl Wal for MockWal {
     async fn load(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
         let info = self.content.lock().unwrap().as_ref().cloned();
         if let Some(info) = info.clone() {
-            let content: WalInfo<Block> = rlp::decode(&info).unwrap();
+            let content = WalInfo::<Block>::from_record(&info).unwrap();
             println!("{:?} load {:?}", to_hex(&self.address), content);
         }
         Ok(info)
     }
+
+    async fn save_last_signed(&self, info: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        if *self.test_id_updated.lock().unwrap() == self.test_id {
+            *self.last_signed.lock().unwrap() = Some(info);
+        }
+        Ok(())
+    }
+
+    async fn load_last_signed(&self) -> Result<Option<Bytes>, Box<dyn Error + Send>> {
+        Ok(self.last_signed.lock().unwrap().as_ref().cloned())
+    }
 }
 
 pub struct Record {
     pub test_id: Arc<Mutex<u64>>,
     pub node_record: Vec<Node>,
     pub alive_record: Mutex<Vec<Node>>,
-    pub wal_record: HashMap<Bytes, MockWal>,
-    pub commit_record: Arc<Mutex<LruCache<u64, Bytes>>>,
-    pub height_record: Arc<Mutex<HashMap<Bytes, u64>>>,
+    pub wal_record: HashMap<Address, MockWal>,
+    pub commit_record: Arc<Mutex<LruCache<u64, Hash>>>,
+    pub height_record: Arc<Mutex<HashMap<Address, u64>>>,
     pub interval: u64,
 }
 
@@ -81,9 +99,9 @@ impl Record {
     pub fn new(num: usize, interval: u64) -> Record {
         let test_id = Arc::new(Mutex::new(0));
         let node_record: Vec<Node> =
-            (0..num).map(|_| Node::new(gen_random_bytes())).collect();
+            (0..num).map(|_| Node::new(gen_random_address())).collect();
         let alive_record = Mutex::new(create_alive_nodes(node_record.clone()));
-        let wal_record: HashMap<Bytes, MockWal> = (0..num)
+        let wal_record: HashMap<Address, MockWal> = (0..num)
             .map(|i| {
                 let address = node_record.get(i).unwrap().address.clone();
                 (
@@ -92,14 +110,18 @@ impl Record {
                         &Arc::new(Mutex::new(0)),
                         address,
                         &Arc::new(Mutex::new(None)),
+                        &Arc::new(Mutex::new(None)),
                     ),
                 )
             })
             .collect();
-        let commit_record: Arc<Mutex<LruCache<u64, Bytes>>> =
+        let commit_record: Arc<Mutex<LruCache<u64, Hash>>> =
             Arc::new(Mutex::new(LruCache::new(10)));
-        commit_record.lock().unwrap().insert(0, gen_random_bytes());
-        let height_record: Arc<Mutex<HashMap<Bytes, u64>>> = Arc::new(Mutex::new(
+        commit_record
+            .lock()
+            .unwrap()
+            .insert(0, Hash::from(gen_random_bytes()));
+        let height_record: Arc<Mutex<HashMap<Address, u64>>> = Arc::new(Mutex::new(
             (0..num)
                 .map(|i| (node_record.get(i).unwrap().address.clone(), 0))
                 .collect(),
@@ -130,7 +152,7 @@ impl Record {
                         .lock()
                         .unwrap()
                         .as_ref()
-                        .map(|wal| rlp::decode(wal).unwrap()),
+                        .map(|wal| WalInfo::from_record(wal).unwrap()),
                 )
             })
             .collect();
@@ -180,20 +202,25 @@ impl Record {
         let test_id_updated = Arc::<Mutex<u64>>::clone(&self.test_id);
         let node_record = self.node_record.clone();
         let alive_record = self.alive_record.lock().unwrap().clone();
-        let wal_record: HashMap<Bytes, MockWal> = self
+        let wal_record: HashMap<Address, MockWal> = self
             .wal_record
             .iter()
             .map(|(address, wal)| {
                 (
                     address.clone(),
-                    MockWal::new(&test_id_updated, address.clone(), &wal.content),
+                    MockWal::new(
+                        &test_id_updated,
+                        address.clone(),
+                        &wal.content,
+                        &wal.last_signed,
+                    ),
                 )
             })
             .collect();
         let commit_record =
-            Arc::<Mutex<LruCache<u64, Bytes>>>::clone(&self.commit_record);
+            Arc::<Mutex<LruCache<u64, Hash>>>::clone(&self.commit_record);
         let height_record =
-            Arc::<Mutex<HashMap<Bytes, u64>>>::clone(&self.height_record);
+            Arc::<Mutex<HashMap<Address, u64>>>::clone(&self.height_record);
         let interval = self.interval;
 
         RecordInternal {
@@ -228,9 +255,9 @@ pub struct RecordInternal {
     pub test_id_updated: Arc<Mutex<u64>>,
     pub node_record: Vec<Node>,
     pub alive_record: Vec<Node>,
-    pub wal_record: HashMap<Bytes, MockWal>,
-    pub commit_record: Arc<Mutex<LruCache<u64, Bytes>>>,
-    pub height_record: Arc<Mutex<HashMap<Bytes, u64>>>,
+    pub wal_record: HashMap<Address, MockWal>,
+    pub commit_record: Arc<Mutex<LruCache<u64, Hash>>>,
+    pub height_record: Arc<Mutex<HashMap<Address, u64>>>,
     pub interval: u64,
 }
 
@@ -249,7 +276,7 @@ impl RecordInternal {
                         .lock()
                         .unwrap()
                         .as_ref()
-                        .map(|wal| rlp::decode(wal).unwrap()),
+                        .map(|wal| WalInfo::from_record(wal).unwrap()),
                 )
             })
             .collect();
@@ -288,15 +315,15 @@ impl RecordInternal {
 
 #[derive(Serialize, Deserialize)]
 struct TupleWalRecord(
-    #[serde(with = "mlm::serde_hex")] Bytes,
+    #[serde(with = "mlm::serde_hex")] Address,
     Option<WalInfo<Block>>,
 );
 
 #[derive(Serialize, Deserialize, Clone)]
-struct TupleCommitRecord(u64, #[serde(with = "mlm::serde_hex")] Bytes);
+struct TupleCommitRecord(u64, #[serde(with = "mlm::serde_hex")] Hash);
 
 #[derive(Serialize, Deserialize, Clone)]
-struct TupleHeightRecord(#[serde(with = "mlm::serde_hex")] Bytes, u64);
+struct TupleHeightRecord(#[serde(with = "mlm::serde_hex")] Address, u64);
 
 #[derive(Serialize, Deserialize)]
 struct RecordForWal {
@@ -314,7 +341,7 @@ impl RecordForWal {
         let test_id = Arc::new(Mutex::new(self.test_id));
         let node_record = self.node_record.clone();
         let alive_record = Mutex::new(self.alive_record.clone());
-        let wal_record: HashMap<Bytes, MockWal> = self
+        let wal_record: HashMap<Address, MockWal> = self
             .wal_record
             .iter()
             .map(|TupleWalRecord(address, wal)| {
@@ -325,17 +352,19 @@ impl RecordForWal {
                         test_id_updated: Arc::clone(&test_id),
                         address: address.clone(),
                         content: Arc::new(Mutex::new(
-                            wal.as_ref().map(|wal| Bytes::from(wal.rlp_bytes())),
+                            wal.as_ref().map(|wal| wal.to_record().unwrap()),
                         )),
+                        // The record file keeps no last signed message.
+                        last_signed: Arc::new(Mutex::new(None)),
                     },
                 )
             })
             .collect();
-        let mut commit_record: LruCache<u64, Bytes> = LruCache::new(10);
+        let mut commit_record: LruCache<u64, Hash> = LruCache::new(10);
         for TupleCommitRecord(height, commit_hash) in self.commit_record.clone() {
             commit_record.insert(height, commit_hash);
         }
-        let height_record: HashMap<Bytes, u64> = self
+        let height_record: HashMap<Address, u64> = self
             .height_record
             .iter()
             .map(|TupleHeightRecord(address, height)| (address.clone(), *height))
@@ -3,12 +3,13 @@ use std::error::Error;
 use async_trait::async_trait;
 use bincode::{deserialize, serialize};
 use blake2b_simd::blake2b;
-use bytes::{Bytes, BytesMut};
+use bytes::Bytes;
 use creep::Context;
 use crossbeam_channel::Sender;
 use mlm::error::ConsensusError;
 use mlm::types::{
-    Address, Commit, Hash, MlmMsg, Node, Signature, Status, ViewChangeReason,
+    Address, AggregatedSignature, Commit, Hash, MlmMsg, Node, Signature, Status,
+    ViewChangeReason,
 };
 use mlm::{Codec, Consensus, Crypto};
 use rand::random;
@@ -26,14 +27,14 @@ struct Pill {
 }
 
 impl Codec for Pill {
-    fn encode(&self) -> Result<Bytes, Box<dyn Error + Send>> {
-        let encode: Vec<u8> = serialize(&self).expect("Serialize Pill error");
-        Ok(Bytes::from(encode))
+    type Error = bincode::Error;
+
+    fn encode(&self) -> Result<Bytes, Self::Error> {
+        Ok(Bytes::from(serialize(&self)?))
     }
 
-    fn decode(data: Bytes) -> Result<Self, Box<dyn Error + Send>> {
-        let decode: Pill = deserialize(data.as_ref()).expect("Deserialize Pill error.");
-        Ok(decode)
+    fn decode_ref(data: &[u8]) -> Result<Self, Self::Error> {
+        deserialize(data)
     }
 }
 
@@ -58,7 +59,10 @@ impl Consensus<Pill> for ConsensusHelper<Pill> {
         height: u64,
     ) -> Result<(Pill, Hash), Box<dyn Error + Send>> {
         let epoch = Pill::new(height);
-        let hash = BytesMut::from(blake2b(epoch.encode()?.as_ref()).as_bytes()).freeze();
+        let encoded = epoch
+            .encode()
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+        let hash = Hash::from_slice(blake2b(encoded.as_ref()).as_bytes());
         Ok((epoch, hash))
     }
 
@@ -84,6 +88,9 @@ impl Consensus<Pill> for ConsensusHelper<Pill> {
             interval: None,
             timer_config: None,
             authority_list: self.auth_list.clone(),
+            next_authority_list: None,
+            proposer_seed: None,
+            skipped_proposers: Vec::new(),
         };
         Ok(status)
     }
@@ -141,12 +148,14 @@ impl Consensus<Pill> for ConsensusHelper<Pill> {
 struct BlsCrypto(Address);
 
 impl Crypto for BlsCrypto {
+    type QcSignature = AggregatedSignature;
+
     fn hash(&self, _msg: Bytes) -> Hash {
-        self.0.clone()
+        Hash::from(self.0.as_bytes().clone())
     }
 
     fn sign(&self, hash: Hash) -> Result<Signature, Box<dyn Error + Send>> {
-        Ok(hash)
+        Ok(hash.into_bytes())
     }
 
     fn verify_signature(
@@ -163,7 +172,7 @@ impl Crypto for BlsCrypto {
         _signatures: Vec<Signature>,
         _voters: Vec<Address>,
     ) -> Result<Signature, Box<dyn Error + Send>> {
-        Ok(gen_hash())
+        Ok(gen_hash().into_bytes())
     }
 
     fn verify_aggregated_signature(